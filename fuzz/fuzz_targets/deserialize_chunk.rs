@@ -0,0 +1,19 @@
+//! Chunk data as received from the network: decoding must never panic, and any chunk it accepts
+//! must be safe to index by world coordinates
+#![no_main]
+
+use dreamgame::shared::world_generation::{deserialize_chunk, WorldGrid};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(chunk) = deserialize_chunk(data) else {
+        return;
+    };
+    let grid = WorldGrid::new(chunk.size());
+    chunk.checksum();
+    for tile in chunk.tiles() {
+        assert_eq!(grid.tile_to_chunk(tile.position), chunk.coord);
+        let (local_x, local_y) = grid.tile_to_local(tile.position);
+        assert_eq!(chunk.get_tile(local_x, local_y), Some(tile));
+    }
+});
@@ -0,0 +1,76 @@
+//! Every message of the protocol, decoded from arbitrary bytes. The first byte picks the message
+//! type, the rest is the encoded message. Decoding must never panic, and the checks the server runs
+//! on client-controlled coordinates must hold for any decoded value.
+#![no_main]
+
+use dreamgame::protocol::*;
+use dreamgame::shared::seasons::SeasonChanged;
+use dreamgame::shared::world_generation::{
+    deserialize_chunk, tile_distance, ChunkData, ChunkDelta, ChunkRequest, WorldConfig, WorldGrid,
+};
+use libfuzzer_sys::fuzz_target;
+use serde::de::DeserializeOwned;
+
+fn decode<M: DeserializeOwned>(data: &[u8]) -> Option<M> {
+    bincode::deserialize(data).ok()
+}
+
+// Checks applied to coordinates sent by clients before the server uses them
+fn check_tile(tile: (i32, i32)) {
+    let chunk_size = WorldConfig::default().chunk_size;
+    tile_distance(tile, (0, 0));
+    let grid = WorldGrid::new(chunk_size);
+    let coord = grid.tile_to_chunk(tile);
+    let (local_x, local_y) = grid.tile_to_local(tile);
+    assert!(local_x < chunk_size && local_y < chunk_size);
+    assert!(coord.is_in_world(chunk_size));
+}
+
+fuzz_target!(|data: &[u8]| {
+    let Some((selector, data)) = data.split_first() else {
+        return;
+    };
+    match selector % 16 {
+        0 => drop(decode::<Message1>(data)),
+        1 => drop(decode::<ChatMessage>(data)),
+        2 => drop(decode::<ChatLine>(data)),
+        3 => drop(decode::<SetLinkConditioner>(data)),
+        4 => drop(decode::<WorldTimeSync>(data)),
+        5 => {
+            if let Some(request) = decode::<HarvestRequest>(data) {
+                check_tile(request.tile);
+            }
+        }
+        6 => {
+            if let Some(request) = decode::<TerrainEditRequest>(data) {
+                check_tile(request.tile);
+            }
+        }
+        7 => {
+            if let Some(request) = decode::<CastLine>(data) {
+                check_tile(request.tile);
+            }
+        }
+        8 => drop(decode::<ReelIn>(data)),
+        9 => drop(decode::<FishingEvent>(data)),
+        10 => drop(decode::<SelectHotbarSlot>(data)),
+        11 => {
+            if let Some(request) = decode::<InteractRequest>(data) {
+                check_tile(request.target.tile());
+            }
+        }
+        12 => drop(decode::<SeasonChanged>(data)),
+        13 => drop(decode::<ChunkDelta>(data)),
+        14 => {
+            if let Some(request) = decode::<ChunkRequest>(data) {
+                request.coord.is_in_world(WorldConfig::default().chunk_size);
+            }
+        }
+        _ => {
+            // the chunk itself is packed in whichever format was negotiated
+            if let Some(data) = decode::<ChunkData>(data) {
+                drop(deserialize_chunk(&data.data));
+            }
+        }
+    }
+});
@@ -18,13 +18,15 @@ use clap::{Parser, Subcommand, ValueEnum};
 use lightyear::prelude::client::ClientConfig;
 use lightyear::prelude::*;
 use lightyear::prelude::{client, server};
-use lightyear::server::config::ServerConfig;
+use lightyear::server::config::{PacketConfig, ServerConfig};
 use lightyear::transport::LOCAL_SOCKET;
 use serde::{Deserialize, Serialize};
 
+use crate::bench::BenchTarget;
 use crate::settings::*;
 use crate::settings_common::*;
-use crate::shared_config::{shared_config, REPLICATION_INTERVAL};
+use crate::shared::feature_flags::Ruleset;
+use crate::shared_config::{shared_config, REPLICATION_INTERVAL, SEND_BANDWIDTH_CAP};
 
 
 
@@ -41,6 +43,62 @@ use bevy::window::PresentMode;
 pub struct Cli {
     #[command(subcommand)]
     pub mode: Option<Mode>,
+
+    #[command(flatten)]
+    pub netsim: NetsimArgs,
+
+    /// Replay the shared simulation on the client and dump the first divergence from the server
+    #[arg(long, global = true)]
+    pub lockstep_debug: bool,
+
+    /// Rules of the world if it doesn't exist yet; an existing world keeps its own
+    #[arg(long, global = true)]
+    pub ruleset: Option<Ruleset>,
+
+    /// Run the server as this shard of a cluster sharing the `world` directory
+    #[cfg(feature = "sharding")]
+    #[arg(long, global = true)]
+    pub shard: Option<u32>,
+}
+
+impl Cli {
+    /// Port the bot controller connects to, if running in bot mode
+    #[cfg(feature = "bot")]
+    pub fn bot_port(&self) -> Option<u16> {
+        match self.mode {
+            Some(Mode::Bot { port, .. }) => Some(port),
+            _ => None,
+        }
+    }
+}
+
+/// Network impairment options, overriding the link conditioner from the settings.
+/// Applied to incoming packets on both the client and the server.
+#[derive(clap::Args, Debug, Default, Clone)]
+pub struct NetsimArgs {
+    /// Simulated one way latency in milliseconds
+    #[arg(long, global = true)]
+    pub latency_ms: Option<u16>,
+    /// Simulated one way jitter in milliseconds
+    #[arg(long, global = true)]
+    pub jitter_ms: Option<u16>,
+    /// Simulated packet loss, between 0 and 1
+    #[arg(long, global = true)]
+    pub packet_loss: Option<f32>,
+}
+
+impl NetsimArgs {
+    /// The conditioner described by the arguments, if any of them was provided
+    pub fn conditioner(&self) -> Option<Conditioner> {
+        if self.latency_ms.is_none() && self.jitter_ms.is_none() && self.packet_loss.is_none() {
+            return None;
+        }
+        Some(Conditioner {
+            latency_ms: self.latency_ms.unwrap_or(0),
+            jitter_ms: self.jitter_ms.unwrap_or(0),
+            packet_loss: self.packet_loss.unwrap_or(0.0).clamp(0.0, 1.0),
+        })
+    }
 }
 
 #[derive(Subcommand, Debug)]
@@ -61,6 +119,42 @@ pub enum Mode {
         #[arg(short, long, default_value = None)]
         client_id: Option<u64>,
     },
+    #[cfg(feature = "bot")]
+    /// Runs a headless client driven by a controller connecting to a local TCP port
+    Bot {
+        #[arg(short, long, default_value = None)]
+        client_id: Option<u64>,
+        /// Port the controller connects to, on the loopback interface
+        #[arg(long, default_value_t = 7100)]
+        port: u16,
+    },
+    /// Runs a benchmark and exits, without starting any app
+    Bench {
+        #[command(subcommand)]
+        target: BenchTarget,
+    },
+    /// Assembles the frames of a server's time-lapse into an animated GIF and exits
+    Timelapse {
+        /// Directory holding the frames, as set in the server settings
+        #[arg(long)]
+        frames: String,
+        /// GIF file to write
+        #[arg(long, default_value = "timelapse.gif")]
+        output: String,
+        /// Milliseconds each frame is shown for
+        #[arg(long, default_value_t = 100)]
+        frame_ms: u32,
+    },
+    #[cfg(all(feature = "gui", feature = "client"))]
+    /// Renders the chunks listed in a suite offscreen and compares them with reference images
+    VisualTest {
+        /// File listing the seed, the tolerance and the chunks to render
+        #[arg(long, default_value = "assets/visual_tests/suite.ron")]
+        suite: String,
+        /// Write the renders as the new reference images instead of comparing them
+        #[arg(long)]
+        update: bool,
+    },
     #[cfg(all(feature = "client", feature = "server"))]
     /// Run the app in host-server mode.
     /// The client and the server will run inside the same app. The peer acts both as a client and a server.
@@ -111,7 +205,12 @@ pub fn cli() -> Cli {
             Cli {
                 mode: Some(Mode::Client {
                     client_id: Some(client_id),
-                })
+                }),
+                netsim: NetsimArgs::default(),
+                lockstep_debug: false,
+                ruleset: None,
+                #[cfg(feature = "sharding")]
+                shard: None,
             }
         } else {
             Cli::parse()
@@ -179,7 +278,8 @@ impl Apps {
                     &settings.shared,
                     transport_config,
                 );
-                let (mut client_app, client_config) = client_app(settings.clone(), net_config);
+                let (mut client_app, client_config) =
+                    client_app(true, settings.clone(), net_config);
                 client_app.add_plugins(ExampleClientRendererPlugin::new(name));
 
                 // create server app, which will be headless when we have client app in same process
@@ -206,10 +306,19 @@ impl Apps {
                 // use the cli-provided client id if it exists, otherwise use the settings client id
                 let client_id = client_id.unwrap_or(settings.client.client_id);
                 let net_config = get_client_net_config(&settings, client_id);
-                let (mut app, config) = client_app(settings, net_config);
+                #[allow(unused_mut)]
+                let (mut app, config) = client_app(true, settings, net_config);
+                #[cfg(feature = "gui")]
                 app.add_plugins(ExampleClientRendererPlugin::new(name));
                 Apps::Client { app, config }
             }
+            #[cfg(feature = "bot")]
+            Some(Mode::Bot { client_id, .. }) => {
+                let client_id = client_id.unwrap_or(settings.client.client_id);
+                let net_config = get_client_net_config(&settings, client_id);
+                let (app, config) = client_app(false, settings, net_config);
+                Apps::Client { app, config }
+            }
             #[cfg(feature = "server")]
             Some(Mode::Server) => {
                 #[allow(unused_mut)]
@@ -220,6 +329,16 @@ impl Apps {
                 app.add_plugins(ExampleServerRendererPlugin::new(name));
                 Apps::Server { app, config }
             }
+            Some(Mode::Bench { .. }) => {
+                unreachable!("benchmarks run without building an app")
+            }
+            Some(Mode::Timelapse { .. }) => {
+                unreachable!("time-lapses are assembled without building an app")
+            }
+            #[cfg(all(feature = "gui", feature = "client"))]
+            Some(Mode::VisualTest { .. }) => {
+                unreachable!("visual tests build their own app")
+            }
             None => {
                 cfg_if::cfg_if! {
                     if #[cfg(all(feature = "client", feature = "server"))] {
@@ -230,7 +349,17 @@ impl Apps {
                         let mode = Mode::Client { client_id: None };
                     }
                 };
-                Apps::new(settings, Cli { mode: Some(mode) }, name)
+                Apps::new(
+                    settings,
+                    Cli {
+                        mode: Some(mode),
+                        netsim: cli.netsim,
+                        lockstep_debug: cli.lockstep_debug,
+                        #[cfg(feature = "sharding")]
+                        shard: cli.shard,
+                    },
+                    name,
+                )
             }
         }
     }
@@ -499,9 +628,10 @@ pub fn new_gui_app(add_inspector: bool) -> App {
             .set(log_plugin())
             .set(window_plugin()),
     );
-    /*if add_inspector {
-        app.add_plugins(bevy_inspector_egui::quick::WorldInspectorPlugin::new());
-    }*/
+    #[cfg(feature = "inspector")]
+    if add_inspector {
+        app.add_plugins(bevy_egui::EguiPlugin);
+    }
     app
 }
 
@@ -520,8 +650,19 @@ pub fn new_headless_app() -> App {
 /// Build the client app with the `ClientPlugins` added.
 /// Takes in a `net_config` parameter so that we configure the network transport.
 #[cfg(feature = "client")]
-pub fn client_app(settings: Settings, net_config: client::NetConfig) -> (App, ClientConfig) {
-    let app = new_gui_app(settings.client.inspector);
+pub fn client_app(
+    enable_gui: bool,
+    settings: Settings,
+    net_config: client::NetConfig,
+) -> (App, ClientConfig) {
+    #[cfg(feature = "gui")]
+    let mut app = if enable_gui {
+        new_gui_app(settings.client.inspector)
+    } else {
+        new_headless_app()
+    };
+    #[cfg(not(feature = "gui"))]
+    let mut app = new_headless_app();
 
     let client_config = ClientConfig {
         shared: shared_config(lightyear::shared::config::Mode::Separate),
@@ -532,6 +673,7 @@ pub fn client_app(settings: Settings, net_config: client::NetConfig) -> (App, Cl
         },
         ..default()
     };
+    app.insert_resource(settings);
     (app, client_config)
 }
 
@@ -543,13 +685,13 @@ pub fn server_app(
     extra_transport_configs: Vec<server::ServerTransport>,
 ) -> (App, ServerConfig) {
     #[cfg(feature = "gui")]
-    let app = if enable_gui {
+    let mut app = if enable_gui {
         new_gui_app(settings.server.inspector)
     } else {
         new_headless_app()
     };
     #[cfg(not(feature = "gui"))]
-    let app = new_headless_app();
+    let mut app = new_headless_app();
     info!("server_app. gui={}", cfg!(feature = "gui"));
     // configure the network configuration
     let mut net_configs = get_server_net_configs(&settings);
@@ -564,8 +706,14 @@ pub fn server_app(
             send_interval: REPLICATION_INTERVAL,
             ..default()
         },
+        packet: PacketConfig {
+            send_bandwidth_cap: SEND_BANDWIDTH_CAP,
+            bandwidth_cap_enabled: true,
+            ..default()
+        },
         ..default()
     };
+    app.insert_resource(settings);
     (app, server_config)
 }
 
@@ -576,7 +724,7 @@ pub fn combined_app(
     extra_transport_configs: Vec<server::ServerTransport>,
     client_net_config: client::NetConfig,
 ) -> (App, ClientConfig, ServerConfig) {
-    let app = new_gui_app(settings.client.inspector || settings.server.inspector);
+    let mut app = new_gui_app(settings.client.inspector || settings.server.inspector);
     // server config
     let mut net_configs = get_server_net_configs(&settings);
     let extra_net_configs = extra_transport_configs.into_iter().map(|c| {
@@ -590,6 +738,11 @@ pub fn combined_app(
             send_interval: REPLICATION_INTERVAL,
             ..default()
         },
+        packet: PacketConfig {
+            send_bandwidth_cap: SEND_BANDWIDTH_CAP,
+            bandwidth_cap_enabled: true,
+            ..default()
+        },
         ..default()
     };
 
@@ -599,5 +752,6 @@ pub fn combined_app(
         net: client_net_config,
         ..default()
     };
+    app.insert_resource(settings);
     (app, client_config, server_config)
 }
@@ -0,0 +1,178 @@
+//! Benchmarks run from the command line with `bench <target>`, outside of any app
+use clap::Subcommand;
+use std::time::{Duration, Instant};
+
+use crate::shared::chunk_format::ChunkFormat;
+use crate::shared::world_generation::{
+    build_chunk_timed, serialize_chunk, ChunkCoord, ChunkGenerator, GenerationTimings, WorldConfig,
+};
+
+#[derive(Subcommand, Debug)]
+pub enum BenchTarget {
+    /// Generate chunks across several seeds and report throughput and per-pass timings
+    Worldgen {
+        /// Chunks generated per seed
+        #[arg(long, default_value_t = 256)]
+        chunks: usize,
+        /// Number of seeds, starting from the default world seed
+        #[arg(long, default_value_t = 4)]
+        seeds: u32,
+    },
+}
+
+pub fn run(target: &BenchTarget) {
+    match target {
+        BenchTarget::Worldgen { chunks, seeds } => bench_worldgen(*chunks, *seeds),
+    }
+}
+
+/// Chunk coordinates spiralling out of the origin, like a player exploring from spawn
+fn coords(count: usize) -> impl Iterator<Item = ChunkCoord> {
+    let radius = ((count as f64).sqrt() / 2.0).ceil() as i32;
+    let mut coords: Vec<ChunkCoord> = (-radius..=radius)
+        .flat_map(|y| (-radius..=radius).map(move |x| ChunkCoord { x, y }))
+        .collect();
+    coords.sort_by_key(|coord| coord.x.abs().max(coord.y.abs()));
+    coords.into_iter().take(count)
+}
+
+fn per_chunk(total: Duration, chunks: usize) -> String {
+    format!(
+        "{:>9.1}us/chunk",
+        total.as_secs_f64() * 1e6 / chunks.max(1) as f64
+    )
+}
+
+fn bench_worldgen(chunks: usize, seeds: u32) {
+    let base = WorldConfig::default();
+    println!(
+        "Generating {} chunks of {}x{} tiles for each of {} seeds",
+        chunks, base.chunk_size, base.chunk_size, seeds
+    );
+
+    let mut timings = GenerationTimings::default();
+    let mut serialization = Duration::ZERO;
+    let mut bytes = 0;
+    let allocations = alloc_stats::snapshot();
+    let start = Instant::now();
+    for seed in 0..seeds {
+        let config = WorldConfig {
+            seed: base.seed.wrapping_add(seed),
+            ..base.clone()
+        };
+        // one generator per world, like the server's, reusing its noise and buffers
+        let mut generator = ChunkGenerator::default();
+        for coord in coords(chunks) {
+            let chunk = generator.build(&coord, &config, 0.0, &mut timings);
+            let serialize_start = Instant::now();
+            bytes +=
+                serialize_chunk(&chunk, ChunkFormat::best_supported()).map_or(0, |data| data.len());
+            serialization += serialize_start.elapsed();
+        }
+    }
+    let elapsed = start.elapsed();
+    let allocations = alloc_stats::since(allocations);
+
+    // the same chunks with nothing reused from one chunk to the next, for comparison
+    let fresh_start = Instant::now();
+    for seed in 0..seeds {
+        let config = WorldConfig {
+            seed: base.seed.wrapping_add(seed),
+            ..base.clone()
+        };
+        for coord in coords(chunks) {
+            build_chunk_timed(&coord, &config, 0.0, &mut GenerationTimings::default());
+        }
+    }
+    let fresh = fresh_start.elapsed();
+
+    let total = chunks * seeds as usize;
+    println!(
+        "{} chunks in {:.2}s: {:.1} chunks/s",
+        total,
+        elapsed.as_secs_f64(),
+        total as f64 / elapsed.as_secs_f64()
+    );
+    println!("  biome          {}", per_chunk(timings.biome, total));
+    println!("  noise          {}", per_chunk(timings.noise, total));
+    println!("  resources      {}", per_chunk(timings.resources, total));
+    println!("  structures     {}", per_chunk(timings.structures, total));
+    println!("  decorations    {}", per_chunk(timings.decorations, total));
+    println!("  serialization  {}", per_chunk(serialization, total));
+    println!(
+        "  generation     {} reused, {} fresh",
+        per_chunk(elapsed - serialization, total),
+        per_chunk(fresh, total)
+    );
+    println!(
+        "  serialized size {:.1} KiB/chunk",
+        bytes as f64 / 1024.0 / total.max(1) as f64
+    );
+    match allocations {
+        Some(stats) => println!(
+            "  allocations    {:.0}/chunk, {:.1} KiB/chunk",
+            stats.count as f64 / total.max(1) as f64,
+            stats.bytes as f64 / 1024.0 / total.max(1) as f64
+        ),
+        None => println!("  allocations    not tracked, build with --features alloc-stats"),
+    }
+}
+
+/// Allocation counting, only compiled in with the `alloc-stats` feature so that regular builds
+/// keep the system allocator untouched
+mod alloc_stats {
+    #[derive(Clone, Copy, Debug)]
+    pub struct AllocStats {
+        pub count: u64,
+        pub bytes: u64,
+    }
+
+    /// Allocations made since the `before` snapshot was taken
+    pub fn since(before: Option<AllocStats>) -> Option<AllocStats> {
+        let (now, before) = (snapshot()?, before?);
+        Some(AllocStats {
+            count: now.count - before.count,
+            bytes: now.bytes - before.bytes,
+        })
+    }
+
+    #[cfg(feature = "alloc-stats")]
+    mod counting {
+        use std::alloc::{GlobalAlloc, Layout, System};
+        use std::sync::atomic::{AtomicU64, Ordering};
+
+        pub static COUNT: AtomicU64 = AtomicU64::new(0);
+        pub static BYTES: AtomicU64 = AtomicU64::new(0);
+
+        struct CountingAllocator;
+
+        unsafe impl GlobalAlloc for CountingAllocator {
+            unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+                COUNT.fetch_add(1, Ordering::Relaxed);
+                BYTES.fetch_add(layout.size() as u64, Ordering::Relaxed);
+                System.alloc(layout)
+            }
+
+            unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+                System.dealloc(ptr, layout)
+            }
+        }
+
+        #[global_allocator]
+        static ALLOCATOR: CountingAllocator = CountingAllocator;
+    }
+
+    #[cfg(feature = "alloc-stats")]
+    pub fn snapshot() -> Option<AllocStats> {
+        use std::sync::atomic::Ordering;
+        Some(AllocStats {
+            count: counting::COUNT.load(Ordering::Relaxed),
+            bytes: counting::BYTES.load(Ordering::Relaxed),
+        })
+    }
+
+    #[cfg(not(feature = "alloc-stats"))]
+    pub fn snapshot() -> Option<AllocStats> {
+        None
+    }
+}
@@ -16,9 +16,13 @@ use lightyear::client::input::native::InputSystemSet;
 pub use lightyear::prelude::client::*;
 use lightyear::prelude::*;
 
+use crate::client::plugins::ClientWorldState;
 use crate::protocol::Direction;
 use crate::protocol::*;
 use crate::shared;
+use crate::shared::kinematics::Velocity;
+use crate::shared::movement::LoadedTiles;
+use crate::shared::rails::Riding;
 
 pub mod plugins;
 
@@ -27,11 +31,14 @@ pub struct ExampleClientPlugin;
 impl Plugin for ExampleClientPlugin {
     fn build(&self, app: &mut App) {
         // Inputs have to be buffered in the FixedPreUpdate schedule
+        // headless clients (bots) have no keyboard
         app.add_systems(
             FixedPreUpdate,
-            buffer_input.in_set(InputSystemSet::BufferInputs),
+            buffer_input
+                .in_set(InputSystemSet::BufferInputs)
+                .run_if(resource_exists::<ButtonInput<KeyCode>>),
         );
-        app.add_systems(FixedUpdate, player_movement);
+        app.add_systems(FixedUpdate, (player_movement, player_drift).chain());
         app.add_systems(
             Update,
             (
@@ -56,9 +63,18 @@ pub(crate) fn buffer_input(
     tick_manager: Res<TickManager>,
     mut input_manager: ResMut<InputManager<Inputs>>,
     keypress: Res<ButtonInput<KeyCode>>,
+    chat: Option<Res<plugins::ChatInput>>,
+    loading: Option<Res<State<plugins::LoadingState>>>,
 ) {
     let tick = tick_manager.tick();
     let mut input = Inputs::None;
+    // keys typed into the chat box, or pressed behind the loading screen, shouldn't move the player
+    if chat.is_some_and(|chat| chat.open)
+        || loading.is_some_and(|loading| *loading.get() == plugins::LoadingState::Loading)
+    {
+        input_manager.add_input(input, tick);
+        return;
+    }
     let mut direction = Direction {
         up: false,
         down: false,
@@ -94,22 +110,49 @@ pub(crate) fn buffer_input(
 /// This works because we only predict the user's controlled entity.
 /// If we were predicting more entities, we would have to only apply movement to the player owned one.
 fn player_movement(
-    mut position_query: Query<&mut PlayerPosition, With<Predicted>>,
+    mut position_query: Query<(&mut PlayerPosition, Option<&mut Riding>), With<Predicted>>,
     mut input_reader: EventReader<InputEvent<Inputs>>,
+    tiles: LoadedTiles,
+    client_world: Res<ClientWorldState>,
 ) {
     for input in input_reader.read() {
         if let Some(input) = input.input() {
-            //No need to iterate the position when the input is None
-            if input == &Inputs::None {
-                continue;
-            }
-            for position in position_query.iter_mut() {
-                shared::movement::shared_movement_behaviour(position, input);
+            for (mut position, riding) in position_query.iter_mut() {
+                // carts keep rolling without input
+                if let Some(mut riding) = riding {
+                    shared::rails::ride_rails(&mut position, &mut riding, input, &tiles);
+                    continue;
+                }
+                //No need to iterate the position when the input is None
+                if input == &Inputs::None {
+                    continue;
+                }
+                // the server doesn't check moves inside instances either
+                if client_world.in_instance {
+                    shared::movement::shared_movement_behaviour(position, input, &());
+                } else {
+                    shared::movement::shared_movement_behaviour(position, input, &tiles);
+                }
             }
         }
     }
 }
 
+/// Predicted players drift like they do on the server, so that a knockback replays the same way
+fn player_drift(
+    mut players: Query<(&mut PlayerPosition, &mut Velocity), (With<Predicted>, Without<Riding>)>,
+    tiles: LoadedTiles,
+    client_world: Res<ClientWorldState>,
+) {
+    for (position, velocity) in players.iter_mut() {
+        if client_world.in_instance {
+            shared::kinematics::shared_drift_behaviour(position, velocity, &());
+        } else {
+            shared::kinematics::shared_drift_behaviour(position, velocity, &tiles);
+        }
+    }
+}
+
 /// System to receive messages on the client
 pub(crate) fn receive_message1(mut reader: EventReader<MessageEvent<Message1>>) {
     for event in reader.read() {
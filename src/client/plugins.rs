@@ -1,7 +1,212 @@
 // export client_world as ClientWorldPlugin
 mod client_world;
-pub use client_world::ClientWorldPlugin;
+pub use client_world::{ClientWorldPlugin, ClientWorldState};
 
 // export client_render_world as ClientWorldRenderPlugin
 mod client_render_world;
-pub use client_render_world::ClientWorldRenderPlugin;
+pub use client_render_world::{
+    ChunkRender, ChunkRenderCache, ChunkRenderQueue, ClientWorldRenderPlugin, ResourceSprite,
+    TileProjection, TileRenderState, TileSprite, WorldCamera,
+};
+
+// export client_chat as ClientChatPlugin
+mod client_chat;
+pub use client_chat::{ChatInput, ChatLog, ClientChatPlugin};
+
+// export client_tile_inspect as ClientTileInspectPlugin
+mod client_tile_inspect;
+pub use client_tile_inspect::{hovered_tile, ClientTileInspectPlugin};
+
+// export client_netsim as ClientNetsimPlugin
+mod client_netsim;
+pub use client_netsim::ClientNetsimPlugin;
+
+// export client_shards as ClientShardsPlugin
+mod client_shards;
+pub use client_shards::ClientShardsPlugin;
+
+// export client_restart as ClientRestartPlugin
+mod client_restart;
+pub use client_restart::ClientRestartPlugin;
+
+// export client_worldgen_debug as ClientWorldgenDebugPlugin
+mod client_worldgen_debug;
+pub use client_worldgen_debug::{ClientWorldgenDebugPlugin, DebugView, WorldgenDebug};
+
+// export client_regions as ClientRegionsPlugin
+mod client_regions;
+pub use client_regions::{ClientRegionsPlugin, RegionNames};
+
+// export client_compass as ClientCompassPlugin
+mod client_compass;
+pub use client_compass::{ClientCompassPlugin, CompassMarker, CompassMarkers, Waypoint};
+
+// export client_party as ClientPartyPlugin
+mod client_party;
+pub use client_party::{ClientParty, ClientPartyPlugin};
+
+// export client_graves as ClientGravesPlugin
+mod client_graves;
+pub use client_graves::{ClientGravesPlugin, GRAVE_COLOR};
+
+// export client_cloud_sync as ClientCloudSyncPlugin
+#[cfg(feature = "cloud-sync")]
+mod client_cloud_sync;
+#[cfg(feature = "cloud-sync")]
+pub use client_cloud_sync::ClientCloudSyncPlugin;
+
+// export client_desync as ClientDesyncPlugin
+mod client_desync;
+pub use client_desync::{ClientDesyncPlugin, DesyncStats};
+
+// export client_items as ClientItemsRenderPlugin
+mod client_items;
+pub use client_items::{ClientItemsRenderPlugin, ItemColors};
+
+// export client_harvest as ClientHarvestPlugin
+mod client_harvest;
+pub use client_harvest::ClientHarvestPlugin;
+
+// export client_fishing as ClientFishingPlugin
+mod client_fishing;
+pub use client_fishing::{ClientFishingPlugin, FishingState};
+
+// export client_temperature as ClientTemperaturePlugin
+mod client_temperature;
+pub use client_temperature::ClientTemperaturePlugin;
+
+// export client_feature_flags as ClientFeatureFlagsPlugin
+mod client_feature_flags;
+pub use client_feature_flags::ClientFeatureFlagsPlugin;
+
+// export client_survival as ClientSurvivalPlugin
+mod client_survival;
+pub use client_survival::ClientSurvivalPlugin;
+
+// export client_terrain as ClientTerrainPlugin
+mod client_terrain;
+pub use client_terrain::ClientTerrainPlugin;
+
+// export client_doors as ClientDoorsPlugin
+mod client_doors;
+pub use client_doors::{ClientDoorsPlugin, DoorSprite};
+
+// export client_rails as ClientRailsPlugin
+mod client_rails;
+pub use client_rails::ClientRailsPlugin;
+
+// export client_logistics as ClientLogisticsPlugin
+mod client_logistics;
+pub use client_logistics::ClientLogisticsPlugin;
+
+// export client_power as ClientPowerPlugin
+mod client_power;
+pub use client_power::ClientPowerPlugin;
+
+// export client_villages as ClientVillagesPlugin
+mod client_villages;
+pub use client_villages::ClientVillagesPlugin;
+
+// export client_fog as ClientFogPlugin
+mod client_fog;
+pub use client_fog::ClientFogPlugin;
+
+// export client_history as ClientHistoryPlugin
+mod client_history;
+pub use client_history::ClientHistoryPlugin;
+
+// export client_telemetry as ClientTelemetryPlugin
+mod client_telemetry;
+pub use client_telemetry::{ClientTelemetryPlugin, FeatureUsed};
+
+// export client_audio as ClientAudioPlugin
+mod client_audio;
+pub use client_audio::{occlusion, AudioProfiles, ClientAudioPlugin, PlaySound, ReverbProfile};
+
+// export client_hotbar as ClientHotbarPlugin
+mod client_hotbar;
+pub use client_hotbar::ClientHotbarPlugin;
+
+// export client_interaction as ClientInteractionPlugin
+mod client_interaction;
+pub use client_interaction::ClientInteractionPlugin;
+
+// export client_combat as ClientCombatPlugin
+mod client_combat;
+pub use client_combat::ClientCombatPlugin;
+
+// export client_view_distance as ClientViewDistancePlugin
+mod client_view_distance;
+pub use client_view_distance::{AdaptiveViewDistance, ClientViewDistancePlugin, ViewLimit};
+
+// export client_queue as ClientQueuePlugin
+mod client_queue;
+pub use client_queue::ClientQueuePlugin;
+
+// export client_loading as ClientLoadingPlugin
+mod client_loading;
+pub use client_loading::{ClientLoadingPlugin, LoadingState};
+
+// export client_resource_pack as ClientResourcePackPlugin
+mod client_resource_pack;
+pub use client_resource_pack::ClientResourcePackPlugin;
+
+// export client_instances as ClientInstancesPlugin
+mod client_instances;
+pub use client_instances::ClientInstancesPlugin;
+
+// export client_portals as ClientPortalsPlugin
+mod client_portals;
+pub use client_portals::{ClientPortalsPlugin, KnownPortals};
+
+// export client_music as ClientMusicPlugin
+mod client_music;
+pub use client_music::{ClientMusicPlugin, MusicDirector};
+
+// export client_social as ClientSocialPlugin
+mod client_social;
+pub use client_social::{ClientSocialLists, ClientSocialPlugin};
+
+// export client_skills as ClientSkillsPlugin
+mod client_skills;
+pub use client_skills::ClientSkillsPlugin;
+
+// export client_toasts as ClientToastsPlugin
+mod client_toasts;
+pub use client_toasts::{ClientToastsPlugin, ShowToast};
+
+// export client_achievements as ClientAchievementsPlugin
+mod client_achievements;
+pub use client_achievements::ClientAchievementsPlugin;
+
+// export client_tutorial as ClientTutorialPlugin
+mod client_tutorial;
+pub use client_tutorial::ClientTutorialPlugin;
+
+// export client_help as ClientHelpPlugin
+mod client_help;
+pub use client_help::{ClientHelpPlugin, SearchEncyclopedia};
+
+// export client_lockstep as ClientLockstepPlugin
+mod client_lockstep;
+pub use client_lockstep::{ClientLockstepPlugin, Lockstep};
+
+// export client_observer as ClientObserverPlugin
+mod client_observer;
+pub use client_observer::{ClientObserverPlugin, ObserverView};
+
+// export client_bot as ClientBotPlugin
+#[cfg(feature = "bot")]
+mod client_bot;
+#[cfg(feature = "bot")]
+pub use client_bot::{BotAction, BotController, BotLink, BotObservation, ClientBotPlugin};
+
+// export client_inspector as ClientInspectorPlugin
+#[cfg(feature = "inspector")]
+mod client_inspector;
+#[cfg(feature = "inspector")]
+pub use client_inspector::ClientInspectorPlugin;
+
+// export client_crash as ClientCrashPlugin
+mod client_crash;
+pub use client_crash::ClientCrashPlugin;
@@ -0,0 +1,117 @@
+use bevy::prelude::*;
+use lightyear::prelude::client::*;
+
+use crate::client::plugins::{ChatInput, ShowToast};
+use crate::shared::achievements::{AchievementUnlocked, Achievements};
+
+// Key toggling the achievements panel
+const ACHIEVEMENTS_KEY: KeyCode = KeyCode::KeyJ;
+
+// Client plugin announcing unlocked achievements and listing the player's progress
+pub struct ClientAchievementsPlugin;
+
+impl Plugin for ClientAchievementsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_achievements_panel)
+            .add_systems(
+                Update,
+                (
+                    announce_unlocks,
+                    achievements_input,
+                    update_achievements_panel,
+                )
+                    .chain(),
+            );
+    }
+}
+
+#[derive(Component)]
+struct AchievementsPanel;
+
+#[derive(Component)]
+struct AchievementsText;
+
+fn setup_achievements_panel(mut commands: Commands) {
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                // right of the skills panel, so that both can be open
+                left: Val::Px(300.0),
+                top: Val::Px(60.0),
+                padding: UiRect::all(Val::Px(6.0)),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.6)),
+            Visibility::Hidden,
+            AchievementsPanel,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new("Achievements"),
+                TextFont::from_font_size(14.0),
+                TextColor(Color::WHITE),
+                AchievementsText,
+            ));
+        });
+}
+
+fn announce_unlocks(
+    mut events: EventReader<MessageEvent<AchievementUnlocked>>,
+    mut toasts: EventWriter<ShowToast>,
+) {
+    for event in events.read() {
+        let unlocked = event.message();
+        toasts.send(ShowToast {
+            title: format!("Achievement unlocked: {}", unlocked.name),
+            body: unlocked.description.clone(),
+        });
+    }
+}
+
+fn achievements_input(
+    keypress: Res<ButtonInput<KeyCode>>,
+    chat: Res<ChatInput>,
+    mut panel: Query<&mut Visibility, With<AchievementsPanel>>,
+) {
+    if chat.open || !keypress.just_pressed(ACHIEVEMENTS_KEY) {
+        return;
+    }
+    for mut visibility in panel.iter_mut() {
+        visibility.toggle_visible_hidden();
+    }
+}
+
+fn update_achievements_panel(
+    player: Query<Ref<Achievements>, With<Predicted>>,
+    mut text: Query<&mut Text, With<AchievementsText>>,
+) {
+    let Ok(achievements) = player.get_single() else {
+        return;
+    };
+    if !achievements.is_changed() {
+        return;
+    }
+    let lines: Vec<String> = achievements
+        .list
+        .iter()
+        .map(|status| {
+            format!(
+                "[{}] {} - {} ({}/{})",
+                if status.unlocked { "x" } else { " " },
+                status.name,
+                status.description,
+                status.progress,
+                status.goal
+            )
+        })
+        .collect();
+    for mut text in text.iter_mut() {
+        text.0 = format!(
+            "Achievements {}/{}\n{}",
+            achievements.unlocked(),
+            achievements.list.len(),
+            lines.join("\n")
+        );
+    }
+}
@@ -0,0 +1,302 @@
+use bevy::asset::io::Reader;
+use bevy::asset::{ron, AssetLoader, LoadContext};
+use bevy::audio::{AddAudioSource, Decodable, PlaybackMode, Source, Volume};
+use bevy::prelude::*;
+use lightyear::prelude::client::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::f32::consts::TAU;
+use std::fmt;
+use std::time::Duration;
+
+use crate::protocol::PlayerPosition;
+use crate::shared::biome_map::BiomeMap;
+use crate::shared::world_generation::{
+    BiomeType, Chunk, TileType, WorldConfig, WorldGrid, WorldState,
+};
+
+// Occlusion and reverb profiles, see `AudioProfiles`
+pub(crate) const AUDIO_PROFILES_PATH: &str = "audio/profiles.audio.ron";
+// Sample rate of generated tones
+const SAMPLE_RATE: u32 = 44_100;
+// Distance between two occlusion samples along the listener-source line, in tiles
+const OCCLUSION_STEP: f32 = 0.5;
+
+// Client audio: plays sounds with tile-based occlusion and the reverb of the listener's biome
+pub struct ClientAudioPlugin;
+
+impl Plugin for ClientAudioPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_audio_source::<Tone>()
+            .init_asset::<AudioProfiles>()
+            .init_asset_loader::<AudioProfilesLoader>()
+            .add_event::<PlaySound>()
+            .add_systems(Startup, load_audio_profiles)
+            .add_systems(PostUpdate, play_sounds);
+    }
+}
+
+/// Request to play a tone. Sounds with a `position` are attenuated by the tiles between them and
+/// the listener; sounds without one are heard as if coming from the listener itself.
+#[derive(Event, Clone, Debug)]
+pub struct PlaySound {
+    pub frequency: f32,
+    pub duration: Duration,
+    pub volume: f32,
+    pub position: Option<Vec2>,
+}
+
+impl PlaySound {
+    pub fn tone(frequency: f32, duration: Duration, volume: f32) -> Self {
+        Self {
+            frequency,
+            duration,
+            volume,
+            position: None,
+        }
+    }
+
+    pub fn at(mut self, position: Vec2) -> Self {
+        self.position = Some(position);
+        self
+    }
+}
+
+/// Reverb and filtering applied to every sound heard in a biome
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct ReverbProfile {
+    pub reverb_delay_ms: u64,
+    /// Volume of the echo, relative to the direct sound
+    pub reverb_amount: f32,
+    /// Cutoff of the low-pass filter; muffles everything above it
+    pub low_pass_hz: u32,
+}
+
+impl Default for ReverbProfile {
+    fn default() -> Self {
+        Self {
+            reverb_delay_ms: 60,
+            reverb_amount: 0.2,
+            low_pass_hz: 8000,
+        }
+    }
+}
+
+/// Data asset describing how the world shapes sounds
+#[derive(Asset, TypePath, Deserialize, Clone, Debug, Default)]
+pub struct AudioProfiles {
+    /// Volume kept by a sound for each tile of that type it travels through
+    pub occlusion: HashMap<TileType, f32>,
+    /// Occluded sounds never get quieter than this fraction of their volume
+    pub min_occlusion: f32,
+    pub default_reverb: ReverbProfile,
+    pub biomes: HashMap<BiomeType, ReverbProfile>,
+}
+
+impl AudioProfiles {
+    pub fn reverb(&self, biome: BiomeType) -> ReverbProfile {
+        self.biomes
+            .get(&biome)
+            .copied()
+            .unwrap_or(self.default_reverb)
+    }
+}
+
+#[derive(Resource)]
+struct AudioProfilesHandle(Handle<AudioProfiles>);
+
+#[derive(Default)]
+struct AudioProfilesLoader;
+
+#[derive(Debug)]
+enum AudioProfilesLoaderError {
+    Io(std::io::Error),
+    Ron(ron::error::SpannedError),
+}
+
+impl fmt::Display for AudioProfilesLoaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AudioProfilesLoaderError::Io(e) => write!(f, "Could not read audio profiles: {}", e),
+            AudioProfilesLoaderError::Ron(e) => write!(f, "Invalid audio profiles: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for AudioProfilesLoaderError {}
+
+impl AssetLoader for AudioProfilesLoader {
+    type Asset = AudioProfiles;
+    type Settings = ();
+    type Error = AudioProfilesLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<AudioProfiles, AudioProfilesLoaderError> {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(AudioProfilesLoaderError::Io)?;
+        ron::de::from_bytes(&bytes).map_err(AudioProfilesLoaderError::Ron)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["audio.ron"]
+    }
+}
+
+/// A generated sine tone, played through a biome's reverb profile
+#[derive(Asset, TypePath, Clone, Debug)]
+pub struct Tone {
+    pub frequency: f32,
+    pub duration: Duration,
+    pub reverb: ReverbProfile,
+}
+
+#[derive(Clone)]
+pub struct ToneSource {
+    frequency: f32,
+    sample: u32,
+    samples: u32,
+}
+
+impl Iterator for ToneSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.sample >= self.samples {
+            return None;
+        }
+        let time = self.sample as f32 / SAMPLE_RATE as f32;
+        self.sample += 1;
+        Some((TAU * self.frequency * time).sin())
+    }
+}
+
+impl Source for ToneSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        Some(Duration::from_secs_f32(
+            self.samples as f32 / SAMPLE_RATE as f32,
+        ))
+    }
+}
+
+impl Decodable for Tone {
+    type DecoderItem = f32;
+    type Decoder = Box<dyn Source<Item = f32> + Send>;
+
+    fn decoder(&self) -> Self::Decoder {
+        let source = ToneSource {
+            frequency: self.frequency,
+            sample: 0,
+            samples: (self.duration.as_secs_f32() * SAMPLE_RATE as f32) as u32,
+        };
+        Box::new(
+            source
+                .reverb(
+                    Duration::from_millis(self.reverb.reverb_delay_ms),
+                    self.reverb.reverb_amount,
+                )
+                .low_pass(self.reverb.low_pass_hz),
+        )
+    }
+}
+
+fn load_audio_profiles(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(AudioProfilesHandle(asset_server.load(AUDIO_PROFILES_PATH)));
+}
+
+/// Fraction of its volume a sound keeps after travelling from `from` to `to`, given the type of
+/// the tile at each world position
+pub fn occlusion(
+    from: Vec2,
+    to: Vec2,
+    profiles: &AudioProfiles,
+    tile_at: impl Fn(i32, i32) -> Option<TileType>,
+) -> f32 {
+    let steps = (from.distance(to) / OCCLUSION_STEP).ceil() as usize;
+    let mut volume = 1.0;
+    let mut last_tile = WorldGrid::world_to_tile(from);
+    for step in 1..steps {
+        let point = from.lerp(to, step as f32 / steps as f32);
+        let tile = WorldGrid::world_to_tile(point);
+        // the source's own tile doesn't occlude it, and each tile only counts once
+        if tile == last_tile || tile == WorldGrid::world_to_tile(to) {
+            continue;
+        }
+        last_tile = tile;
+        if let Some(tile_type) = tile_at(tile.0, tile.1) {
+            volume *= profiles.occlusion.get(&tile_type).copied().unwrap_or(1.0);
+        }
+    }
+    volume.max(profiles.min_occlusion)
+}
+
+fn play_sounds(
+    mut commands: Commands,
+    mut sounds: EventReader<PlaySound>,
+    mut tones: ResMut<Assets<Tone>>,
+    profiles: Res<Assets<AudioProfiles>>,
+    profiles_handle: Option<Res<AudioProfilesHandle>>,
+    biome_map: Res<BiomeMap>,
+    world_state: Res<WorldState>,
+    world_config: Res<WorldConfig>,
+    chunks: Query<&Chunk>,
+    listener: Query<&PlayerPosition, With<Predicted>>,
+) {
+    if sounds.is_empty() {
+        return;
+    }
+    // built-in defaults until the profiles asset is loaded
+    let fallback = AudioProfiles::default();
+    let profiles = profiles_handle
+        .and_then(|handle| profiles.get(&handle.0))
+        .unwrap_or(&fallback);
+    let listener = listener.get_single().map(|position| position.0).ok();
+    let reverb = profiles.reverb(biome_map.biome_at_position(listener.unwrap_or_default()));
+
+    let tile_at = |x: i32, y: i32| {
+        let coord = world_config.grid().tile_to_chunk((x, y));
+        let (local_x, local_y) = world_config.grid().tile_to_local((x, y));
+        world_state
+            .chunks
+            .get(&coord)
+            .and_then(|entity| chunks.get(*entity).ok())
+            .and_then(|chunk| Some(chunk.get_tile(local_x, local_y)?.tile_type))
+    };
+
+    for sound in sounds.read() {
+        let occluded = match (listener, sound.position) {
+            (Some(listener), Some(position)) => occlusion(position, listener, profiles, tile_at),
+            _ => 1.0,
+        };
+        commands.spawn((
+            AudioPlayer(tones.add(Tone {
+                frequency: sound.frequency,
+                duration: sound.duration,
+                reverb,
+            })),
+            PlaybackSettings {
+                mode: PlaybackMode::Despawn,
+                volume: Volume::new(sound.volume * occluded),
+                ..default()
+            },
+        ));
+    }
+}
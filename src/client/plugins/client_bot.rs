@@ -0,0 +1,293 @@
+//! Headless bot API: an external controller drives the client instead of the keyboard.
+//!
+//! Every fixed tick the client sends a [`BotObservation`] of the world around its player to the
+//! controller, and applies the [`BotAction`]s the controller sent since the previous tick. The
+//! controller either runs in the same process and talks to a [`BotLink`] through channels
+//! ([`BotLink::local`]), or connects to a local TCP socket ([`BotLink::listen`]) and exchanges
+//! one JSON object per line.
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::thread;
+
+use bevy::prelude::*;
+use crossbeam_channel::{Receiver, Sender};
+use lightyear::client::input::native::InputSystemSet;
+use lightyear::prelude::client::*;
+use serde::{Deserialize, Serialize};
+
+use crate::protocol::{
+    Channel1, ChatChannel, ChatMessage, Direction, HarvestRequest, Inputs, InteractRequest,
+    PlayerId, PlayerName, PlayerPosition, SelectHotbarSlot, TerrainAction, TerrainEditRequest,
+};
+use crate::shared::error::{GameError, ReportError};
+use crate::shared::interaction::InteractionTarget;
+use crate::shared::items::{DroppedItem, HeldItem, Inventory};
+use crate::shared::movement::{LoadedTiles, TileSource};
+use crate::shared::npc::Npc;
+use crate::shared::world_generation::{Tile, WorldGrid};
+
+// Tiles observed around the player, in each direction
+const OBSERVATION_RADIUS: i32 = 8;
+// Observations kept for a controller that falls behind; newer ones are dropped once it's full
+const OBSERVATION_BUFFER: usize = 64;
+
+/// What the controller sees of the world each tick
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BotObservation {
+    pub tick: u16,
+    /// `None` until the player entity has been replicated
+    pub position: Option<Vec2>,
+    /// Loaded tiles within `OBSERVATION_RADIUS` of the player, row by row
+    pub tiles: Vec<Tile>,
+    pub players: Vec<BotPlayer>,
+    pub npcs: Vec<Vec2>,
+    pub items: Vec<DroppedItem>,
+    pub inventory: Option<Inventory>,
+    pub held_slot: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BotPlayer {
+    pub name: String,
+    pub position: Vec2,
+}
+
+/// What the controller can do. Movement lasts until the next `Move` or `Stop`; the other actions
+/// are sent to the server once, and validated there like the player's own.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum BotAction {
+    Move {
+        up: bool,
+        down: bool,
+        left: bool,
+        right: bool,
+    },
+    Stop,
+    Harvest {
+        tile: (i32, i32),
+    },
+    EditTerrain {
+        tile: (i32, i32),
+        action: TerrainAction,
+    },
+    Interact {
+        target: InteractionTarget,
+    },
+    SelectSlot {
+        slot: usize,
+    },
+    Chat {
+        text: String,
+    },
+}
+
+/// The client's end of the connection to a controller
+#[derive(Resource, Clone)]
+pub struct BotLink {
+    observations: Sender<BotObservation>,
+    actions: Receiver<BotAction>,
+}
+
+/// The controller's end of a [`BotLink::local`] connection
+pub struct BotController {
+    pub observations: Receiver<BotObservation>,
+    pub actions: Sender<BotAction>,
+}
+
+impl BotLink {
+    /// A link to a controller running in the same process
+    pub fn local() -> (Self, BotController) {
+        let (observations, observations_recv) = crossbeam_channel::bounded(OBSERVATION_BUFFER);
+        let (actions_send, actions) = crossbeam_channel::unbounded();
+        (
+            Self {
+                observations,
+                actions,
+            },
+            BotController {
+                observations: observations_recv,
+                actions: actions_send,
+            },
+        )
+    }
+
+    /// A link to a controller connecting to `port` on the loopback interface. One controller is
+    /// served at a time; observations are dropped while none is connected or while it lags behind.
+    pub fn listen(port: u16) -> std::io::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+        let (link, controller) = Self::local();
+        info!("Waiting for a bot controller on port {}", port);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => serve_controller(stream, &controller),
+                    Err(e) => warn!("Bot controller failed to connect: {}", e),
+                }
+            }
+        });
+        Ok(link)
+    }
+}
+
+// Exchange JSON lines with a connected controller until it disconnects
+fn serve_controller(stream: TcpStream, controller: &BotController) {
+    info!("Bot controller connected");
+    // observations queued while nobody was listening are stale
+    controller.observations.try_iter().for_each(drop);
+    let Ok(reader) = stream.try_clone() else {
+        return;
+    };
+    let actions = controller.actions.clone();
+    thread::spawn(move || {
+        for line in BufReader::new(reader).lines().map_while(Result::ok) {
+            match serde_json::from_str::<BotAction>(&line) {
+                Ok(action) => {
+                    if actions.send(action).is_err() {
+                        return;
+                    }
+                }
+                Err(e) => warn!("Ignoring malformed bot action {:?}: {}", line, e),
+            }
+        }
+    });
+    let mut writer = stream;
+    for observation in controller.observations.iter() {
+        let Ok(mut line) = serde_json::to_string(&observation) else {
+            continue;
+        };
+        line.push('\n');
+        if writer.write_all(line.as_bytes()).is_err() {
+            break;
+        }
+    }
+    info!("Bot controller disconnected");
+}
+
+// Client-side plugin letting a controller play instead of the keyboard
+pub struct ClientBotPlugin(pub BotLink);
+
+impl Plugin for ClientBotPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.0.clone())
+            .init_resource::<BotMovement>()
+            .add_systems(
+                FixedPreUpdate,
+                (apply_bot_actions, observe_world)
+                    .chain()
+                    .in_set(InputSystemSet::BufferInputs),
+            );
+    }
+}
+
+// Direction the controller last asked to move in
+#[derive(Resource, Default)]
+struct BotMovement(Option<Direction>);
+
+fn apply_bot_actions(
+    link: Res<BotLink>,
+    tick_manager: Res<TickManager>,
+    mut movement: ResMut<BotMovement>,
+    mut input_manager: ResMut<InputManager<Inputs>>,
+    mut client: ResMut<ConnectionManager>,
+    mut errors: EventWriter<ReportError>,
+) {
+    for action in link.actions.try_iter() {
+        let sent = match action {
+            BotAction::Move {
+                up,
+                down,
+                left,
+                right,
+            } => {
+                let direction = Direction {
+                    up,
+                    down,
+                    left,
+                    right,
+                };
+                movement.0 = (!direction.is_none()).then_some(direction);
+                Ok(())
+            }
+            BotAction::Stop => {
+                movement.0 = None;
+                Ok(())
+            }
+            BotAction::Harvest { tile } => {
+                client.send_message::<Channel1, _>(&HarvestRequest { tile })
+            }
+            BotAction::EditTerrain { tile, action } => {
+                client.send_message::<Channel1, _>(&TerrainEditRequest { tile, action })
+            }
+            BotAction::Interact { target } => {
+                client.send_message::<Channel1, _>(&InteractRequest { target })
+            }
+            BotAction::SelectSlot { slot } => {
+                client.send_message::<Channel1, _>(&SelectHotbarSlot { slot })
+            }
+            BotAction::Chat { text } => {
+                client.send_message::<ChatChannel, _>(&ChatMessage { text })
+            }
+        };
+        sent.unwrap_or_else(|e| {
+            errors.send(ReportError(GameError::send("BotAction", e)));
+        });
+    }
+    let input = match &movement.0 {
+        Some(direction) => Inputs::Direction(direction.clone()),
+        None => Inputs::None,
+    };
+    input_manager.add_input(input, tick_manager.tick());
+}
+
+fn observe_world(
+    link: Res<BotLink>,
+    tick_manager: Res<TickManager>,
+    tiles: LoadedTiles,
+    player: Query<(&PlayerPosition, Option<&Inventory>, Option<&HeldItem>), With<Predicted>>,
+    others: Query<(&PlayerPosition, &PlayerName), (With<PlayerId>, With<Interpolated>)>,
+    npcs: Query<&Npc>,
+    items: Query<&DroppedItem>,
+) {
+    let player = player.get_single().ok();
+    let position = player.map(|(position, ..)| position.0);
+    let mut observation = BotObservation {
+        tick: tick_manager.tick().0,
+        position,
+        tiles: Vec::new(),
+        players: Vec::new(),
+        npcs: Vec::new(),
+        items: Vec::new(),
+        inventory: player.and_then(|(_, inventory, _)| inventory.cloned()),
+        held_slot: player.and_then(|(.., held)| held.map(|held| held.slot)),
+    };
+    if let Some(position) = position {
+        let (center_x, center_y) = WorldGrid::world_to_tile(position);
+        let range = OBSERVATION_RADIUS as f32 * WorldGrid::TILE_SIZE;
+        let in_range = |other: Vec2| (other - position).abs().max_element() <= range;
+        for y in center_y - OBSERVATION_RADIUS..=center_y + OBSERVATION_RADIUS {
+            for x in center_x - OBSERVATION_RADIUS..=center_x + OBSERVATION_RADIUS {
+                observation.tiles.extend(tiles.tile((x, y)).cloned());
+            }
+        }
+        observation.players = others
+            .iter()
+            .filter(|(other, _)| in_range(other.0))
+            .map(|(other, name)| BotPlayer {
+                name: name.0.clone(),
+                position: other.0,
+            })
+            .collect();
+        observation.npcs = npcs
+            .iter()
+            .map(|npc| npc.position)
+            .filter(|npc| in_range(*npc))
+            .collect();
+        observation.items = items
+            .iter()
+            .filter(|item| in_range(WorldGrid::tile_to_world(item.tile)))
+            .cloned()
+            .collect();
+    }
+    // the controller is slow, gone or not connected yet: keep playing without it
+    let _ = link.observations.try_send(observation);
+}
@@ -0,0 +1,211 @@
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::input::ButtonState;
+use bevy::prelude::*;
+use lightyear::prelude::client::*;
+use std::collections::VecDeque;
+
+use crate::client::plugins::SearchEncyclopedia;
+use crate::protocol::{ChatChannel, ChatLine, ChatMessage, PlayerName};
+use crate::shared::error::{GameError, ReportError};
+use crate::shared::moderation::{ReportPlayer, MAX_EXCERPT_LINES};
+
+// Number of chat lines kept on screen
+const CHAT_HISTORY: usize = 10;
+// Chat command reporting a player, handled here so that the report carries what they said
+const REPORT_COMMAND: &str = "/report ";
+// Chat command searching the encyclopedia, which lives on the client
+const WIKI_COMMAND: &str = "/wiki";
+
+// Client-side plugin for typing chat lines/commands and displaying the chat log
+pub struct ClientChatPlugin;
+
+impl Plugin for ClientChatPlugin {
+    fn build(&self, app: &mut App) {
+        info!("Building ClientChatPlugin");
+        app.init_resource::<ChatInput>()
+            .init_resource::<ChatLog>()
+            .add_systems(Startup, setup_chat_ui)
+            .add_systems(
+                Update,
+                (chat_keyboard_input, receive_chat_lines, update_chat_ui).chain(),
+            );
+    }
+}
+
+/// State of the chat input line. While `open`, keyboard input goes to the chat instead of movement.
+#[derive(Resource, Default)]
+pub struct ChatInput {
+    pub open: bool,
+    pub buffer: String,
+}
+
+// Most recent chat lines, oldest first
+#[derive(Resource, Default)]
+pub struct ChatLog {
+    pub lines: VecDeque<String>,
+}
+
+impl ChatLog {
+    pub fn push(&mut self, line: String) {
+        self.lines.push_back(line);
+        while self.lines.len() > CHAT_HISTORY {
+            self.lines.pop_front();
+        }
+    }
+}
+
+#[derive(Component)]
+struct ChatLogText;
+
+#[derive(Component)]
+struct ChatInputText;
+
+fn setup_chat_ui(mut commands: Commands) {
+    commands
+        .spawn(Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(10.0),
+            bottom: Val::Px(90.0),
+            flex_direction: FlexDirection::Column,
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(""),
+                TextFont::from_font_size(14.0),
+                TextColor(Color::WHITE),
+                ChatLogText,
+            ));
+            parent.spawn((
+                Text::new(""),
+                TextFont::from_font_size(14.0),
+                TextColor(Color::srgb(0.9, 0.9, 0.5)),
+                ChatInputText,
+            ));
+        });
+}
+
+// Turn `/report <player> <reason>` into a report. Names may contain spaces, so the longest
+// known name the line starts with is the reported player.
+fn parse_report<'a>(
+    line: &str,
+    names: impl Iterator<Item = &'a str>,
+    log: &ChatLog,
+) -> Option<ReportPlayer> {
+    let rest = line.strip_prefix(REPORT_COMMAND)?.trim_start();
+    let player = names
+        .filter(|name| {
+            rest.len() > name.len()
+                && rest.is_char_boundary(name.len())
+                && rest[..name.len()].eq_ignore_ascii_case(name)
+                && rest[name.len()..].starts_with(' ')
+        })
+        .max_by_key(|name| name.len())?;
+    let reason = rest[player.len()..].trim().to_string();
+    let prefix = format!("<{}> ", player);
+    let mut excerpt: Vec<String> = log
+        .lines
+        .iter()
+        .rev()
+        .filter(|line| line.starts_with(&prefix))
+        .take(MAX_EXCERPT_LINES)
+        .cloned()
+        .collect();
+    excerpt.reverse();
+    Some(ReportPlayer {
+        player: player.to_string(),
+        reason,
+        excerpt: (!excerpt.is_empty()).then_some(excerpt),
+    })
+}
+
+// Enter opens the chat and sends the typed line, Escape cancels
+fn chat_keyboard_input(
+    mut events: EventReader<KeyboardInput>,
+    mut chat: ResMut<ChatInput>,
+    mut log: ResMut<ChatLog>,
+    players: Query<&PlayerName>,
+    mut client: ResMut<ConnectionManager>,
+    mut searches: EventWriter<SearchEncyclopedia>,
+    mut errors: EventWriter<ReportError>,
+) {
+    for event in events.read() {
+        if event.state != ButtonState::Pressed {
+            continue;
+        }
+        match &event.logical_key {
+            Key::Enter => {
+                if chat.open {
+                    let text = std::mem::take(&mut chat.buffer);
+                    if text.starts_with(REPORT_COMMAND) {
+                        let names = players.iter().map(|name| name.0.as_str());
+                        match parse_report(&text, names, &log) {
+                            Some(report) => client
+                                .send_message::<ChatChannel, _>(&report)
+                                .unwrap_or_else(|e| {
+                                    errors.send(ReportError(GameError::send("ReportPlayer", e)));
+                                }),
+                            None => log.push("Usage: /report <player> <reason>".to_string()),
+                        }
+                    } else if let Some(query) = text
+                        .strip_prefix(WIKI_COMMAND)
+                        .filter(|rest| rest.is_empty() || rest.starts_with(' '))
+                    {
+                        searches.send(SearchEncyclopedia {
+                            query: query.to_string(),
+                        });
+                    } else if !text.trim().is_empty() {
+                        client
+                            .send_message::<ChatChannel, _>(&ChatMessage { text })
+                            .unwrap_or_else(|e| {
+                                error!("Failed to send chat message: {:?}", e);
+                            });
+                    }
+                }
+                chat.open = !chat.open;
+            }
+            Key::Escape if chat.open => {
+                chat.open = false;
+                chat.buffer.clear();
+            }
+            Key::Backspace if chat.open => {
+                chat.buffer.pop();
+            }
+            Key::Space if chat.open => chat.buffer.push(' '),
+            Key::Character(characters) if chat.open => chat.buffer.push_str(characters),
+            _ => {}
+        }
+    }
+}
+
+fn receive_chat_lines(mut events: EventReader<MessageEvent<ChatLine>>, mut log: ResMut<ChatLog>) {
+    for event in events.read() {
+        let line = event.message();
+        match &line.sender {
+            Some(sender) => log.push(format!("<{}> {}", sender, line.text)),
+            None => log.push(line.text.clone()),
+        }
+    }
+}
+
+fn update_chat_ui(
+    chat: Res<ChatInput>,
+    log: Res<ChatLog>,
+    mut log_text: Query<&mut Text, (With<ChatLogText>, Without<ChatInputText>)>,
+    mut input_text: Query<&mut Text, (With<ChatInputText>, Without<ChatLogText>)>,
+) {
+    if log.is_changed() {
+        for mut text in log_text.iter_mut() {
+            text.0 = log.lines.iter().cloned().collect::<Vec<_>>().join("\n");
+        }
+    }
+    if chat.is_changed() {
+        for mut text in input_text.iter_mut() {
+            text.0 = if chat.open {
+                format!("> {}_", chat.buffer)
+            } else {
+                String::new()
+            };
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use bevy::prelude::*;
+
+use crate::shared::cloud_sync::{utc_time, CloudSyncStatus, ResolveSyncConflict, SaveManifest};
+
+// Client plugin showing the state of the hosted world's cloud save, and asking the player which
+// copy to keep when both changed
+pub struct ClientCloudSyncPlugin;
+
+impl Plugin for ClientCloudSyncPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_cloud_sync_hud).add_systems(
+            Update,
+            (choose_save, update_cloud_sync_hud).run_if(resource_exists::<CloudSyncStatus>),
+        );
+    }
+}
+
+#[derive(Component)]
+struct CloudSyncText;
+
+fn setup_cloud_sync_hud(mut commands: Commands) {
+    commands.spawn((
+        Text::new(""),
+        TextFont::from_font_size(18.0),
+        TextColor(Color::WHITE),
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Percent(25.0),
+            top: Val::Px(40.0),
+            ..default()
+        },
+        CloudSyncText,
+    ));
+}
+
+fn describe(manifest: &SaveManifest) -> String {
+    let (year, month, day, hours, minutes, _) = utc_time(manifest.saved_at);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+        year, month, day, hours, minutes
+    )
+}
+
+fn choose_save(
+    keys: Res<ButtonInput<KeyCode>>,
+    status: Res<CloudSyncStatus>,
+    mut choices: EventWriter<ResolveSyncConflict>,
+) {
+    if !matches!(*status, CloudSyncStatus::Conflict { .. }) {
+        return;
+    }
+    if keys.just_pressed(KeyCode::KeyL) {
+        choices.send(ResolveSyncConflict::KeepLocal);
+    } else if keys.just_pressed(KeyCode::KeyC) {
+        choices.send(ResolveSyncConflict::UseCloud);
+    }
+}
+
+fn update_cloud_sync_hud(
+    status: Res<CloudSyncStatus>,
+    mut texts: Query<&mut Text, With<CloudSyncText>>,
+) {
+    if !status.is_changed() {
+        return;
+    }
+    let text = match &*status {
+        CloudSyncStatus::Disabled | CloudSyncStatus::InSync => String::new(),
+        CloudSyncStatus::Uploading => "Saving to the cloud...".to_string(),
+        CloudSyncStatus::Downloading => {
+            "Downloading the cloud save, the game will close once it's done...".to_string()
+        }
+        CloudSyncStatus::Conflict { local, cloud } => format!(
+            "This world changed both here (saved {}) and in the cloud (saved {}).\n\
+             [L] keep this computer's save   [C] use the cloud save (the game will close)",
+            describe(local),
+            describe(cloud)
+        ),
+        CloudSyncStatus::Failed(reason) => format!("Cloud save failed: {}", reason),
+    };
+    for mut hud in texts.iter_mut() {
+        hud.0 = text.clone();
+    }
+}
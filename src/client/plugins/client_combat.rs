@@ -0,0 +1,90 @@
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use lightyear::prelude::client::*;
+
+use crate::client::plugins::{ChatInput, FeatureUsed, TileProjection, WorldCamera};
+use crate::protocol::{Channel1, PlayerPosition};
+use crate::shared::error::{GameError, ReportError};
+use crate::shared::projectiles::{Projectile, ThrowStone};
+
+// Button throwing a stone towards the cursor
+const THROW_BUTTON: MouseButton = MouseButton::Right;
+const STONE_COLOR: Color = Color::srgb(0.55, 0.55, 0.55);
+// Size of a stone relative to a tile
+const STONE_SIZE: f32 = 0.25;
+// Stones fly over everything on the ground
+const STONE_Z: f32 = 0.8;
+
+// Client plugin throwing stones, and drawing those in flight
+pub struct ClientCombatPlugin;
+
+impl Plugin for ClientCombatPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (throw_input, spawn_projectile_sprites, place_projectiles).chain(),
+        );
+    }
+}
+
+fn throw_input(
+    buttons: Res<ButtonInput<MouseButton>>,
+    chat: Res<ChatInput>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<WorldCamera>>,
+    projection: Res<TileProjection>,
+    player: Query<&PlayerPosition, With<Predicted>>,
+    mut client: ResMut<ConnectionManager>,
+    mut features: EventWriter<FeatureUsed>,
+    mut errors: EventWriter<ReportError>,
+) {
+    if chat.open || !buttons.just_pressed(THROW_BUTTON) {
+        return;
+    }
+    let (Ok(window), Ok((camera, camera_transform)), Ok(position)) = (
+        windows.get_single(),
+        cameras.get_single(),
+        player.get_single(),
+    ) else {
+        return;
+    };
+    let Some(screen) = window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor).ok())
+    else {
+        return;
+    };
+    let direction = projection.to_world(screen) - position.0;
+    client
+        .send_message::<Channel1, _>(&ThrowStone { direction })
+        .unwrap_or_else(|e| {
+            errors.send(ReportError(GameError::send("ThrowStone", e)));
+        });
+    features.send(FeatureUsed("throw"));
+}
+
+fn spawn_projectile_sprites(mut commands: Commands, projectiles: Query<Entity, Added<Projectile>>) {
+    for entity in projectiles.iter() {
+        commands.entity(entity).insert((
+            Sprite {
+                custom_size: Some(Vec2::splat(STONE_SIZE)),
+                color: STONE_COLOR,
+                ..default()
+            },
+            Transform::default(),
+        ));
+    }
+}
+
+fn place_projectiles(
+    projection: Res<TileProjection>,
+    mut projectiles: Query<(Ref<Projectile>, &mut Transform), With<Sprite>>,
+) {
+    for (projectile, mut transform) in projectiles.iter_mut() {
+        if !projectile.is_changed() && !projection.is_changed() {
+            continue;
+        }
+        let screen = projection.to_screen(projectile.position);
+        transform.translation = screen.extend(STONE_Z + projection.depth(projectile.position));
+    }
+}
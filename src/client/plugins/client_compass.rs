@@ -0,0 +1,282 @@
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use lightyear::prelude::client::*;
+use std::collections::HashMap;
+
+use crate::client::plugins::{hovered_tile, ChatInput, RegionNames, TileProjection, WorldCamera};
+use crate::protocol::PlayerPosition;
+use crate::shared::survival::RESPAWN_POSITION;
+use crate::shared::world_generation::{WorldConfig, WorldGrid};
+
+// Key setting the waypoint on the hovered tile, or clearing it when pressed on it again
+const WAYPOINT_KEY: KeyCode = KeyCode::KeyV;
+// Key toggling the coordinate readout
+const COORDINATES_KEY: KeyCode = KeyCode::F5;
+
+// Size of the compass and of the markers on its rim, in pixels
+const COMPASS_SIZE: f32 = 96.0;
+const MARKER_SIZE: f32 = 10.0;
+// Targets closer than this many tiles are shown in the middle of the compass
+const HERE_DISTANCE: f32 = 1.0;
+const SPAWN_COLOR: Color = Color::srgb(1.0, 0.85, 0.3);
+const WAYPOINT_COLOR: Color = Color::srgb(0.3, 0.9, 1.0);
+
+// Keys of the markers this plugin sets itself
+const SPAWN_MARKER: &str = "spawn";
+const WAYPOINT_MARKER: &str = "waypoint";
+
+// Client plugin for the compass: markers on its rim point toward the spawn point, the waypoint
+// and anything else other plugins mark, with an optional readout of the player's coordinates
+pub struct ClientCompassPlugin;
+
+impl Plugin for ClientCompassPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CompassMarkers>()
+            .init_resource::<Waypoint>()
+            .init_resource::<ShowCoordinates>()
+            .add_systems(Startup, setup_compass)
+            .add_systems(
+                Update,
+                (
+                    compass_input,
+                    update_own_markers,
+                    update_compass,
+                    update_coordinates,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Something the compass points at
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompassMarker {
+    /// World position of the target
+    pub position: Vec2,
+    pub color: Color,
+}
+
+/// Everything the compass points at, by key. Plugins add their own markers, e.g. one per party
+/// member, and remove them when they're gone.
+#[derive(Resource, Default)]
+pub struct CompassMarkers {
+    markers: HashMap<String, CompassMarker>,
+}
+
+impl CompassMarkers {
+    pub fn set(&mut self, key: impl Into<String>, marker: CompassMarker) {
+        let key = key.into();
+        if self.markers.get(&key) != Some(&marker) {
+            self.markers.insert(key, marker);
+        }
+    }
+
+    pub fn remove(&mut self, key: &str) {
+        self.markers.remove(key);
+    }
+
+    /// Remove the markers whose key starts with `prefix` and isn't kept
+    pub fn retain(&mut self, prefix: &str, keep: impl Fn(&str) -> bool) {
+        self.markers
+            .retain(|key, _| !key.starts_with(prefix) || keep(key));
+    }
+}
+
+/// The tile the player marked to find their way back to
+#[derive(Resource, Default)]
+pub struct Waypoint(pub Option<(i32, i32)>);
+
+#[derive(Resource, Default)]
+struct ShowCoordinates(bool);
+
+#[derive(Component)]
+struct CompassRim;
+
+#[derive(Component)]
+struct CompassNeedle(String);
+
+#[derive(Component)]
+struct CoordinatesText;
+
+fn setup_compass(mut commands: Commands) {
+    commands
+        .spawn(Node {
+            position_type: PositionType::Absolute,
+            right: Val::Px(10.0),
+            bottom: Val::Px(60.0),
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::End,
+            row_gap: Val::Px(4.0),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                Node {
+                    width: Val::Px(COMPASS_SIZE),
+                    height: Val::Px(COMPASS_SIZE),
+                    border: UiRect::all(Val::Px(2.0)),
+                    ..default()
+                },
+                BorderColor(Color::srgba(1.0, 1.0, 1.0, 0.6)),
+                BorderRadius::MAX,
+                BackgroundColor(Color::srgba(0.1, 0.1, 0.2, 0.5)),
+                CompassRim,
+            ));
+            parent.spawn((
+                Text::new(""),
+                TextFont::from_font_size(14.0),
+                TextColor(Color::WHITE),
+                CoordinatesText,
+            ));
+        });
+}
+
+fn compass_input(
+    keypress: Res<ButtonInput<KeyCode>>,
+    chat: Res<ChatInput>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<WorldCamera>>,
+    projection: Res<TileProjection>,
+    mut waypoint: ResMut<Waypoint>,
+    mut show_coordinates: ResMut<ShowCoordinates>,
+) {
+    if chat.open {
+        return;
+    }
+    if keypress.just_pressed(COORDINATES_KEY) {
+        show_coordinates.0 = !show_coordinates.0;
+    }
+    if keypress.just_pressed(WAYPOINT_KEY) {
+        let (Ok(window), Ok((camera, camera_transform))) =
+            (windows.get_single(), cameras.get_single())
+        else {
+            return;
+        };
+        let Some(tile) = hovered_tile(window, camera, camera_transform, &projection) else {
+            return;
+        };
+        waypoint.0 = if waypoint.0 == Some(tile) {
+            None
+        } else {
+            Some(tile)
+        };
+        info!("Waypoint: {:?}", waypoint.0);
+    }
+}
+
+fn update_own_markers(waypoint: Res<Waypoint>, mut markers: ResMut<CompassMarkers>) {
+    markers.set(
+        SPAWN_MARKER,
+        CompassMarker {
+            position: RESPAWN_POSITION,
+            color: SPAWN_COLOR,
+        },
+    );
+    match waypoint.0 {
+        Some(tile) => markers.set(
+            WAYPOINT_MARKER,
+            CompassMarker {
+                position: WorldGrid::tile_to_world(tile),
+                color: WAYPOINT_COLOR,
+            },
+        ),
+        None => markers.remove(WAYPOINT_MARKER),
+    }
+}
+
+// Place a needle on the rim for every marker, in the direction it is on screen
+fn update_compass(
+    mut commands: Commands,
+    markers: Res<CompassMarkers>,
+    projection: Res<TileProjection>,
+    player: Query<&PlayerPosition, With<Predicted>>,
+    rim: Query<Entity, With<CompassRim>>,
+    mut needles: Query<(Entity, &CompassNeedle, &mut Node)>,
+) {
+    let (Ok(position), Ok(rim)) = (player.get_single(), rim.get_single()) else {
+        return;
+    };
+    for (entity, needle, _) in needles.iter() {
+        if !markers.markers.contains_key(&needle.0) {
+            commands.entity(entity).despawn();
+        }
+    }
+    let center = (COMPASS_SIZE - MARKER_SIZE) * 0.5;
+    let radius = center - 2.0;
+    let offset_of = |target: Vec2| {
+        if target.distance(position.0) < HERE_DISTANCE {
+            return Vec2::ZERO;
+        }
+        let direction =
+            (projection.to_screen(target) - projection.to_screen(position.0)).normalize_or_zero();
+        // screen space goes up while UI space goes down
+        Vec2::new(direction.x, -direction.y) * radius
+    };
+    for (key, marker) in markers.markers.iter() {
+        let offset = offset_of(marker.position);
+        let (left, top) = (Val::Px(center + offset.x), Val::Px(center + offset.y));
+        match needles.iter_mut().find(|(_, needle, _)| needle.0 == *key) {
+            Some((_, _, mut node)) => {
+                if node.left != left || node.top != top {
+                    node.left = left;
+                    node.top = top;
+                }
+            }
+            None => {
+                let needle = commands
+                    .spawn((
+                        Node {
+                            position_type: PositionType::Absolute,
+                            left,
+                            top,
+                            width: Val::Px(MARKER_SIZE),
+                            height: Val::Px(MARKER_SIZE),
+                            ..default()
+                        },
+                        BackgroundColor(marker.color),
+                        BorderRadius::MAX,
+                        CompassNeedle(key.clone()),
+                    ))
+                    .id();
+                commands.entity(rim).add_child(needle);
+            }
+        }
+    }
+}
+
+fn update_coordinates(
+    show_coordinates: Res<ShowCoordinates>,
+    waypoint: Res<Waypoint>,
+    world_config: Res<WorldConfig>,
+    regions: Option<Res<RegionNames>>,
+    player: Query<&PlayerPosition, With<Predicted>>,
+    mut texts: Query<&mut Text, With<CoordinatesText>>,
+) {
+    let text = match (show_coordinates.0, player.get_single()) {
+        (true, Ok(position)) => {
+            let tile = WorldGrid::world_to_tile(position.0);
+            let chunk = world_config.grid().tile_to_chunk(tile);
+            let mut lines = vec![
+                format!("{}, {}", tile.0, tile.1),
+                format!("chunk {}, {}", chunk.x, chunk.y),
+            ];
+            if let Some(region) = regions
+                .as_ref()
+                .and_then(|regions| regions.current().and_then(|region| regions.name(region)))
+            {
+                lines.push(region.to_string());
+            }
+            if let Some(target) = waypoint.0 {
+                let distance = WorldGrid::tile_to_world(target).distance(position.0);
+                lines.push(format!("waypoint {:.0} tiles away", distance));
+            }
+            lines.join("\n")
+        }
+        _ => String::new(),
+    };
+    for mut hud in texts.iter_mut() {
+        if hud.0 != text {
+            hud.0 = text.clone();
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use bevy::prelude::*;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::crash::{mark_seen, unseen_report};
+
+// Client plugin telling the player when the previous session crashed, and offering to copy the
+// crash report so that it can be pasted into a bug report
+pub struct ClientCrashPlugin;
+
+impl Plugin for ClientCrashPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, show_crash_dialog)
+            .add_systems(Update, crash_dialog_buttons);
+    }
+}
+
+#[derive(Component)]
+struct CrashDialog {
+    report: PathBuf,
+}
+
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+enum CrashButton {
+    Copy,
+    Dismiss,
+}
+
+#[derive(Component)]
+struct CrashDialogStatus;
+
+fn dialog_button(label: &str, button: CrashButton) -> impl Bundle {
+    (
+        Button,
+        Node {
+            padding: UiRect::axes(Val::Px(10.0), Val::Px(4.0)),
+            margin: UiRect::right(Val::Px(8.0)),
+            ..default()
+        },
+        BackgroundColor(Color::srgb(0.25, 0.25, 0.3)),
+        button,
+        Text::new(label),
+        TextFont::from_font_size(14.0),
+        TextColor(Color::WHITE),
+    )
+}
+
+fn show_crash_dialog(mut commands: Commands) {
+    let Some(report) = unseen_report() else {
+        return;
+    };
+    let message = format!(
+        "Sorry, the game crashed last time.\nA report was saved to {}.\nSharing it with the developers helps them fix the problem.",
+        report.display()
+    );
+    commands
+        .spawn(Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    Node {
+                        flex_direction: FlexDirection::Column,
+                        padding: UiRect::all(Val::Px(12.0)),
+                        row_gap: Val::Px(10.0),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgba(0.05, 0.05, 0.08, 0.95)),
+                    CrashDialog { report },
+                ))
+                .with_children(|dialog| {
+                    dialog.spawn((
+                        Text::new(message),
+                        TextFont::from_font_size(16.0),
+                        TextColor(Color::WHITE),
+                    ));
+                    dialog.spawn(Node::default()).with_children(|row| {
+                        row.spawn(dialog_button("Copy report", CrashButton::Copy));
+                        row.spawn(dialog_button("Dismiss", CrashButton::Dismiss));
+                    });
+                    dialog.spawn((
+                        Text::new(""),
+                        TextFont::from_font_size(12.0),
+                        TextColor(Color::srgb(0.7, 0.9, 0.7)),
+                        CrashDialogStatus,
+                    ));
+                });
+        });
+}
+
+#[cfg(not(target_family = "wasm"))]
+fn copy_to_clipboard(text: String) -> Result<(), String> {
+    arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.set_text(text))
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(target_family = "wasm")]
+fn copy_to_clipboard(_text: String) -> Result<(), String> {
+    Err("the clipboard isn't available in the browser".to_string())
+}
+
+fn crash_dialog_buttons(
+    mut commands: Commands,
+    buttons: Query<(&Interaction, &CrashButton), Changed<Interaction>>,
+    dialog: Query<(&CrashDialog, &Parent)>,
+    mut status: Query<&mut Text, With<CrashDialogStatus>>,
+) {
+    let Ok((dialog, root)) = dialog.get_single() else {
+        return;
+    };
+    for (interaction, button) in buttons.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match button {
+            CrashButton::Copy => {
+                let result = fs::read_to_string(&dialog.report)
+                    .map_err(|e| e.to_string())
+                    .and_then(copy_to_clipboard);
+                let message = match result {
+                    Ok(()) => "Report copied to the clipboard".to_string(),
+                    Err(e) => format!("Couldn't copy the report: {}", e),
+                };
+                for mut text in status.iter_mut() {
+                    text.0 = message.clone();
+                }
+            }
+            CrashButton::Dismiss => {
+                if let Err(e) = mark_seen(&dialog.report) {
+                    warn!("Failed to remember the crash report as seen: {}", e);
+                }
+                commands.entity(root.get()).despawn_recursive();
+            }
+        }
+    }
+}
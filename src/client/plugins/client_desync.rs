@@ -0,0 +1,139 @@
+use bevy::prelude::*;
+use bevy::time::common_conditions::on_timer;
+use bevy::utils::Duration;
+use lightyear::prelude::client::*;
+use lightyear::prelude::*;
+
+use crate::protocol::PlayerPosition;
+use crate::shared::movement::MOVE_SPEED;
+
+// How often predicted and confirmed state are compared
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(250);
+// Divergence (in world units) tolerated on top of what the prediction lead explains
+const DIVERGENCE_TOLERANCE: f32 = 2.0 * MOVE_SPEED;
+// Consecutive bad samples before the connection is flagged unstable
+const UNSTABLE_AFTER: u32 = 4;
+// Consecutive good samples before the flag is cleared again
+const STABLE_AFTER: u32 = 8;
+
+// Client-side plugin that detects when the predicted player drifts away from the server state
+pub struct ClientDesyncPlugin;
+
+impl Plugin for ClientDesyncPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DesyncStats>()
+            .add_systems(Startup, setup_desync_indicator)
+            .add_systems(
+                Update,
+                (
+                    sample_player_desync.run_if(on_timer(SAMPLE_INTERVAL)),
+                    update_desync_indicator,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Divergence statistics between predicted and confirmed state
+#[derive(Resource, Default, Debug)]
+pub struct DesyncStats {
+    pub samples: u64,
+    pub desynced_samples: u64,
+    pub last_divergence: f32,
+    pub max_divergence: f32,
+    // exponential moving average of the divergence
+    pub average_divergence: f32,
+    pub unstable: bool,
+    consecutive_bad: u32,
+    consecutive_good: u32,
+}
+
+impl DesyncStats {
+    fn record(&mut self, divergence: f32, allowed: f32) {
+        self.samples += 1;
+        self.last_divergence = divergence;
+        self.max_divergence = self.max_divergence.max(divergence);
+        self.average_divergence = if self.samples == 1 {
+            divergence
+        } else {
+            self.average_divergence * 0.9 + divergence * 0.1
+        };
+
+        if divergence > allowed {
+            self.desynced_samples += 1;
+            self.consecutive_bad += 1;
+            self.consecutive_good = 0;
+            if !self.unstable && self.consecutive_bad >= UNSTABLE_AFTER {
+                self.unstable = true;
+                warn!(
+                    "Prediction diverged from server state for {} samples (divergence {:.1}, allowed {:.1}, {}/{} desynced samples)",
+                    self.consecutive_bad, divergence, allowed, self.desynced_samples, self.samples
+                );
+            }
+        } else {
+            self.consecutive_good += 1;
+            self.consecutive_bad = 0;
+            if self.unstable && self.consecutive_good >= STABLE_AFTER {
+                self.unstable = false;
+                info!("Prediction back in sync with server state");
+            }
+        }
+    }
+}
+
+#[derive(Component)]
+struct DesyncIndicator;
+
+fn setup_desync_indicator(mut commands: Commands) {
+    commands.spawn((
+        Text::new(""),
+        TextFont::from_font_size(16.0),
+        TextColor(Color::srgb(1.0, 0.6, 0.2)),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(40.0),
+            right: Val::Px(10.0),
+            ..default()
+        },
+        DesyncIndicator,
+    ));
+}
+
+/// Compare the predicted position with the latest confirmed one.
+/// The predicted entity runs ahead of the confirmed state by the number of ticks since the last server
+/// update, so up to `MOVE_SPEED` per tick and axis of that lead is expected divergence.
+fn sample_player_desync(
+    tick_manager: Res<TickManager>,
+    predicted: Query<(&PlayerPosition, &Predicted)>,
+    confirmed: Query<(&PlayerPosition, &Confirmed)>,
+    mut stats: ResMut<DesyncStats>,
+) {
+    for (predicted_position, predicted) in predicted.iter() {
+        let Some(confirmed_entity) = predicted.confirmed_entity else {
+            continue;
+        };
+        let Ok((confirmed_position, confirmed)) = confirmed.get(confirmed_entity) else {
+            continue;
+        };
+        let lead = (tick_manager.tick() - confirmed.tick).max(0) as f32;
+        let allowed = lead * MOVE_SPEED * std::f32::consts::SQRT_2 + DIVERGENCE_TOLERANCE;
+        let divergence = predicted_position.0.distance(confirmed_position.0);
+        stats.record(divergence, allowed);
+    }
+}
+
+fn update_desync_indicator(
+    stats: Res<DesyncStats>,
+    mut indicator: Query<&mut Text, With<DesyncIndicator>>,
+) {
+    if !stats.is_changed() {
+        return;
+    }
+    for mut text in indicator.iter_mut() {
+        text.0 = if stats.unstable {
+            "Connection unstable".to_string()
+        } else {
+            String::new()
+        };
+    }
+}
@@ -0,0 +1,51 @@
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+// Time a door takes to swing open or shut, in seconds
+const SWING_SECS: f32 = 0.25;
+// Share of its width an open door still covers, seen edge on
+const OPEN_WIDTH: f32 = 0.2;
+
+// Client-side animation of doors and gates swinging open and shut
+pub struct ClientDoorsPlugin;
+
+impl Plugin for ClientDoorsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DoorSwings>()
+            .add_systems(Update, animate_doors);
+    }
+}
+
+/// Sprite of a door or gate, and whether its tile says it's open
+#[derive(Component)]
+pub struct DoorSprite {
+    pub tile: (i32, i32),
+    pub open: bool,
+}
+
+// How far each door has swung open, from 0 (shut) to 1 (open). Kept apart from the sprites, which
+// are spawned again when the chunk is rendered again after the door changed.
+#[derive(Resource, Default)]
+struct DoorSwings(HashMap<(i32, i32), f32>);
+
+fn animate_doors(
+    time: Res<Time>,
+    mut swings: ResMut<DoorSwings>,
+    mut doors: Query<(&DoorSprite, &mut Transform)>,
+) {
+    let step = time.delta_secs() / SWING_SECS;
+    for (door, mut transform) in doors.iter_mut() {
+        let target = if door.open { 1.0 } else { 0.0 };
+        // doors seen for the first time are drawn as they are, without swinging
+        let swing = swings.0.entry(door.tile).or_insert(target);
+        *swing = if *swing < target {
+            (*swing + step).min(target)
+        } else {
+            (*swing - step).max(target)
+        };
+        let width = 1.0 - (1.0 - OPEN_WIDTH) * *swing;
+        if transform.scale.x != width {
+            transform.scale.x = width;
+        }
+    }
+}
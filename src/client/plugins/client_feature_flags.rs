@@ -0,0 +1,23 @@
+use bevy::prelude::*;
+use lightyear::prelude::client::*;
+
+use crate::shared::feature_flags::ServerFeatureFlags;
+
+// Client plugin taking on the feature flags of the server it connects to
+pub struct ClientFeatureFlagsPlugin;
+
+impl Plugin for ClientFeatureFlagsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PreUpdate, receive_feature_flags.after(MainSet::Receive));
+    }
+}
+
+fn receive_feature_flags(
+    mut events: EventReader<MessageEvent<ServerFeatureFlags>>,
+    mut flags: ResMut<ServerFeatureFlags>,
+) {
+    for event in events.read() {
+        *flags = *event.message();
+        info!("Server features: {}", flags.enabled().join(", "));
+    }
+}
@@ -0,0 +1,146 @@
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use lightyear::prelude::client::*;
+use std::time::Duration;
+
+use crate::client::plugins::{
+    hovered_tile, ChatInput, ChatLog, PlaySound, TileProjection, WorldCamera,
+};
+use crate::protocol::{CastLine, Channel1, FishingEvent, ReelIn};
+use crate::shared::world_generation::WorldGrid;
+
+// Key that casts the line onto the hovered water tile, or reels it in
+const FISHING_KEY: KeyCode = KeyCode::KeyF;
+// Tone of the splash played when a fish bites
+const BITE_FREQUENCY: f32 = 660.0;
+
+// Client-side fishing: cast/reel input, and feedback on the session
+pub struct ClientFishingPlugin;
+
+impl Plugin for ClientFishingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FishingState>()
+            .init_resource::<CastTile>()
+            .add_systems(Startup, setup_fishing_ui)
+            .add_systems(
+                Update,
+                (fishing_input, receive_fishing_events, update_fishing_ui).chain(),
+            );
+    }
+}
+
+#[derive(Resource, Default, Debug, PartialEq)]
+pub enum FishingState {
+    #[default]
+    Idle,
+    Waiting,
+    Bite,
+}
+
+// Tile the line was last cast on
+#[derive(Resource, Default)]
+struct CastTile(Option<(i32, i32)>);
+
+#[derive(Component)]
+struct FishingText;
+
+fn setup_fishing_ui(mut commands: Commands) {
+    commands.spawn((
+        Text::new(""),
+        TextFont::from_font_size(28.0),
+        TextColor(Color::srgb(1.0, 0.9, 0.3)),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(40.0),
+            left: Val::Percent(48.0),
+            ..default()
+        },
+        FishingText,
+    ));
+}
+
+fn fishing_input(
+    keypress: Res<ButtonInput<KeyCode>>,
+    chat: Res<ChatInput>,
+    state: Res<FishingState>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<WorldCamera>>,
+    projection: Res<TileProjection>,
+    mut cast_tile: ResMut<CastTile>,
+    mut client: ResMut<ConnectionManager>,
+) {
+    if chat.open || !keypress.just_pressed(FISHING_KEY) {
+        return;
+    }
+    let result = if *state == FishingState::Idle {
+        let (Ok(window), Ok((camera, camera_transform))) =
+            (windows.get_single(), cameras.get_single())
+        else {
+            return;
+        };
+        let Some(tile) = hovered_tile(window, camera, camera_transform, &projection) else {
+            return;
+        };
+        cast_tile.0 = Some(tile);
+        client.send_message::<Channel1, _>(&CastLine { tile })
+    } else {
+        client.send_message::<Channel1, _>(&ReelIn)
+    };
+    result.unwrap_or_else(|e| {
+        error!("Failed to send fishing input: {:?}", e);
+    });
+}
+
+fn receive_fishing_events(
+    mut events: EventReader<MessageEvent<FishingEvent>>,
+    mut state: ResMut<FishingState>,
+    mut log: ResMut<ChatLog>,
+    cast_tile: Res<CastTile>,
+    mut sounds: EventWriter<PlaySound>,
+) {
+    for event in events.read() {
+        match event.message() {
+            FishingEvent::Cast => {
+                *state = FishingState::Waiting;
+                log.push("You cast your line...".to_string());
+            }
+            FishingEvent::Bite => {
+                *state = FishingState::Bite;
+                if let Some((x, y)) = cast_tile.0 {
+                    sounds.send(
+                        PlaySound::tone(BITE_FREQUENCY, Duration::from_millis(120), 0.4)
+                            .at(WorldGrid::tile_to_world((x, y))),
+                    );
+                }
+            }
+            FishingEvent::Caught { kind, count } => {
+                *state = FishingState::Idle;
+                log.push(format!("You caught {} {:?}!", count, kind));
+            }
+            FishingEvent::TooEarly => {
+                *state = FishingState::Idle;
+                log.push("You reeled in too early".to_string());
+            }
+            FishingEvent::Escaped => {
+                *state = FishingState::Idle;
+                log.push("It got away...".to_string());
+            }
+            FishingEvent::Rejected(reason) => {
+                *state = FishingState::Idle;
+                log.push(reason.clone());
+            }
+        }
+    }
+}
+
+fn update_fishing_ui(state: Res<FishingState>, mut texts: Query<&mut Text, With<FishingText>>) {
+    if !state.is_changed() {
+        return;
+    }
+    for mut text in texts.iter_mut() {
+        text.0 = match *state {
+            FishingState::Bite => "!".to_string(),
+            _ => String::new(),
+        };
+    }
+}
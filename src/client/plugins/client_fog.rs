@@ -0,0 +1,121 @@
+use bevy::prelude::*;
+use lightyear::prelude::client::*;
+
+use crate::client::plugins::{ClientWorldState, TileProjection};
+use crate::protocol::PlayerPosition;
+use crate::shared::line_of_sight::{can_see, has_line_of_sight, SIGHT_RANGE};
+use crate::shared::movement::{LoadedTiles, TileSource};
+use crate::shared::npc::Npc;
+use crate::shared::world_generation::{Chunk, WorldGrid};
+
+const FOG_COLOR: Color = Color::srgba(0.0, 0.0, 0.05, 0.5);
+// Fog is drawn over the tiles and their overlays, under everything standing on them
+const FOG_Z: f32 = 0.3;
+
+// Client plugin for the fog of war: within sight range, tiles hidden behind forests, mountains
+// and closed doors are shaded, and NPCs the player can't see aren't drawn at all. Instances are
+// small enough to be seen whole.
+pub struct ClientFogPlugin;
+
+impl Plugin for ClientFogPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FogOfWar>()
+            .add_systems(Update, (update_fog, hide_unseen_npcs).chain());
+    }
+}
+
+// Tile the fog was last computed around, and the render parent of its shades
+#[derive(Resource, Default)]
+struct FogOfWar {
+    center: Option<(i32, i32)>,
+    layer: Option<Entity>,
+}
+
+// Where the player sees from, outside of instances
+fn viewpoint(
+    client_world: &ClientWorldState,
+    player: &Query<&PlayerPosition, With<Predicted>>,
+) -> Option<Vec2> {
+    if client_world.in_instance {
+        return None;
+    }
+    player.get_single().ok().map(|position| position.0)
+}
+
+// Shade the tiles out of sight again whenever the player steps onto another tile, a chunk arrives
+// or changes, or the projection is switched
+fn update_fog(
+    mut commands: Commands,
+    mut fog: ResMut<FogOfWar>,
+    projection: Res<TileProjection>,
+    client_world: Res<ClientWorldState>,
+    tiles: LoadedTiles,
+    player: Query<&PlayerPosition, With<Predicted>>,
+    changed_chunks: Query<(), Changed<Chunk>>,
+) {
+    let eye = viewpoint(&client_world, &player);
+    let center = eye.map(WorldGrid::world_to_tile);
+    if center == fog.center && !projection.is_changed() && changed_chunks.is_empty() {
+        return;
+    }
+    fog.center = center;
+    if let Some(layer) = fog.layer.take() {
+        commands.entity(layer).despawn_recursive();
+    }
+    let (Some(eye), Some(center)) = (eye, center) else {
+        return;
+    };
+
+    // the shades are laid out like the tiles of a chunk whose origin is the world origin
+    let (transform, rotation) = projection.chunk_transforms(Vec2::ZERO);
+    let layer = commands
+        .spawn(SpatialBundle {
+            transform: transform.with_translation(transform.translation.with_z(FOG_Z)),
+            ..default()
+        })
+        .id();
+    let shades = commands
+        .spawn(SpatialBundle {
+            transform: Transform::from_rotation(rotation),
+            ..default()
+        })
+        .set_parent(layer)
+        .id();
+    let range = SIGHT_RANGE as i32;
+    commands.entity(shades).with_children(|parent| {
+        for dy in -range..=range {
+            for dx in -range..=range {
+                if dx * dx + dy * dy > range * range {
+                    continue;
+                }
+                let tile = (center.0 + dx, center.1 + dy);
+                let world = WorldGrid::tile_to_world(tile);
+                if tiles.tile(tile).is_none() || has_line_of_sight(eye, world, &tiles) {
+                    continue;
+                }
+                parent.spawn((
+                    Sprite::from_color(FOG_COLOR, Vec2::splat(WorldGrid::TILE_SIZE)),
+                    Transform::from_translation(world.extend(0.0)),
+                ));
+            }
+        }
+    });
+    fog.layer = Some(layer);
+}
+
+fn hide_unseen_npcs(
+    client_world: Res<ClientWorldState>,
+    tiles: LoadedTiles,
+    player: Query<&PlayerPosition, With<Predicted>>,
+    mut npcs: Query<(&Npc, &mut Visibility), With<Sprite>>,
+) {
+    let eye = viewpoint(&client_world, &player);
+    for (npc, mut visibility) in npcs.iter_mut() {
+        let seen = eye.is_none_or(|eye| can_see(eye, npc.position, &tiles));
+        visibility.set_if_neq(if seen {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        });
+    }
+}
@@ -0,0 +1,78 @@
+use bevy::prelude::*;
+use lightyear::prelude::client::*;
+
+use crate::client::plugins::{CompassMarker, CompassMarkers, TileProjection};
+use crate::shared::graves::{Grave, GraveSites};
+use crate::shared::world_generation::WorldGrid;
+
+pub const GRAVE_COLOR: Color = Color::srgb(0.55, 0.5, 0.45);
+// Size of a grave relative to a tile
+const GRAVE_SIZE: f32 = 0.7;
+
+// Prefix of the keys of the compass markers pointing at the player's graves
+const MARKER_PREFIX: &str = "grave:";
+
+// Client plugin drawing graves, and pointing the compass at the ones the player left
+pub struct ClientGravesPlugin;
+
+impl Plugin for ClientGravesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (spawn_grave_sprites, place_graves, update_grave_markers).chain(),
+        );
+    }
+}
+
+fn spawn_grave_sprites(mut commands: Commands, graves: Query<Entity, Added<Grave>>) {
+    for entity in graves.iter() {
+        commands.entity(entity).insert((
+            Sprite {
+                custom_size: Some(Vec2::splat(GRAVE_SIZE)),
+                color: GRAVE_COLOR,
+                ..default()
+            },
+            Transform::default(),
+        ));
+    }
+}
+
+// Position graves on screen according to the current projection
+fn place_graves(
+    projection: Res<TileProjection>,
+    mut graves: Query<(Ref<Grave>, &mut Transform), With<Sprite>>,
+) {
+    for (grave, mut transform) in graves.iter_mut() {
+        if !grave.is_changed() && !projection.is_changed() {
+            continue;
+        }
+        let world = WorldGrid::tile_to_world(grave.tile);
+        let screen = projection.to_screen(world);
+        transform.translation = screen.extend(0.4 + projection.depth(world));
+    }
+}
+
+fn update_grave_markers(
+    sites: Query<Ref<GraveSites>, With<Predicted>>,
+    mut markers: ResMut<CompassMarkers>,
+) {
+    let Ok(sites) = sites.get_single() else {
+        return;
+    };
+    if !sites.is_changed() {
+        return;
+    }
+    let key = |tile: (i32, i32)| format!("{}{},{}", MARKER_PREFIX, tile.0, tile.1);
+    for tile in sites.0.iter().copied() {
+        markers.set(
+            key(tile),
+            CompassMarker {
+                position: WorldGrid::tile_to_world(tile),
+                color: GRAVE_COLOR,
+            },
+        );
+    }
+    markers.retain(MARKER_PREFIX, |marker| {
+        sites.0.iter().any(|tile| key(*tile) == marker)
+    });
+}
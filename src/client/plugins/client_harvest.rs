@@ -0,0 +1,43 @@
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use lightyear::prelude::client::*;
+
+use crate::client::plugins::{hovered_tile, ChatInput, TileProjection, WorldCamera};
+use crate::protocol::{Channel1, HarvestRequest};
+
+// Key that harvests the resource on the hovered tile
+const HARVEST_KEY: KeyCode = KeyCode::KeyH;
+
+// Client-side plugin sending harvest requests; the server validates them
+pub struct ClientHarvestPlugin;
+
+impl Plugin for ClientHarvestPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, harvest_hovered_tile);
+    }
+}
+
+fn harvest_hovered_tile(
+    keypress: Res<ButtonInput<KeyCode>>,
+    chat: Res<ChatInput>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<WorldCamera>>,
+    projection: Res<TileProjection>,
+    mut client: ResMut<ConnectionManager>,
+) {
+    if chat.open || !keypress.just_pressed(HARVEST_KEY) {
+        return;
+    }
+    let (Ok(window), Ok((camera, camera_transform))) = (windows.get_single(), cameras.get_single())
+    else {
+        return;
+    };
+    let Some(tile) = hovered_tile(window, camera, camera_transform, &projection) else {
+        return;
+    };
+    client
+        .send_message::<Channel1, _>(&HarvestRequest { tile })
+        .unwrap_or_else(|e| {
+            error!("Failed to send harvest request: {:?}", e);
+        });
+}
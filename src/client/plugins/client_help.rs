@@ -0,0 +1,149 @@
+use bevy::prelude::*;
+
+use crate::client::plugins::ChatInput;
+use crate::shared::encyclopedia::{catalog, search, Entry};
+
+// Key toggling the encyclopedia
+const HELP_KEY: KeyCode = KeyCode::F1;
+// Entries shown at once; Page Up/Page Down flip through the rest
+const ENTRIES_PER_PAGE: usize = 6;
+
+// Client plugin showing the in-game encyclopedia, opened with F1 or searched with /wiki
+pub struct ClientHelpPlugin;
+
+impl Plugin for ClientHelpPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Encyclopedia {
+            entries: catalog(),
+            query: String::new(),
+            page: 0,
+        })
+        .add_event::<SearchEncyclopedia>()
+        .add_systems(Startup, setup_help_panel)
+        .add_systems(
+            Update,
+            (open_searches, help_input, update_help_panel).chain(),
+        );
+    }
+}
+
+/// Opens the encyclopedia on the entries matching `query`
+#[derive(Event, Clone, Debug)]
+pub struct SearchEncyclopedia {
+    pub query: String,
+}
+
+#[derive(Resource)]
+struct Encyclopedia {
+    entries: Vec<Entry>,
+    query: String,
+    page: usize,
+}
+
+#[derive(Component)]
+struct HelpPanel;
+
+#[derive(Component)]
+struct HelpText;
+
+fn setup_help_panel(mut commands: Commands) {
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                right: Val::Px(10.0),
+                top: Val::Px(60.0),
+                width: Val::Px(420.0),
+                padding: UiRect::all(Val::Px(6.0)),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.75)),
+            Visibility::Hidden,
+            HelpPanel,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(""),
+                TextFont::from_font_size(14.0),
+                TextColor(Color::WHITE),
+                HelpText,
+            ));
+        });
+}
+
+fn open_searches(
+    mut searches: EventReader<SearchEncyclopedia>,
+    mut encyclopedia: ResMut<Encyclopedia>,
+    mut panel: Query<&mut Visibility, With<HelpPanel>>,
+) {
+    let Some(search) = searches.read().last() else {
+        return;
+    };
+    encyclopedia.query = search.query.trim().to_string();
+    encyclopedia.page = 0;
+    for mut visibility in panel.iter_mut() {
+        *visibility = Visibility::Visible;
+    }
+}
+
+fn help_input(
+    keypress: Res<ButtonInput<KeyCode>>,
+    chat: Res<ChatInput>,
+    mut encyclopedia: ResMut<Encyclopedia>,
+    mut panel: Query<&mut Visibility, With<HelpPanel>>,
+) {
+    if chat.open {
+        return;
+    }
+    if keypress.just_pressed(HELP_KEY) {
+        for mut visibility in panel.iter_mut() {
+            visibility.toggle_visible_hidden();
+        }
+    }
+    if keypress.just_pressed(KeyCode::PageDown) {
+        encyclopedia.page += 1;
+    }
+    if keypress.just_pressed(KeyCode::PageUp) {
+        encyclopedia.page = encyclopedia.page.saturating_sub(1);
+    }
+}
+
+fn update_help_panel(
+    mut encyclopedia: ResMut<Encyclopedia>,
+    mut text: Query<&mut Text, With<HelpText>>,
+) {
+    if !encyclopedia.is_changed() {
+        return;
+    }
+    let matches = search(&encyclopedia.entries, &encyclopedia.query);
+    let pages = matches.len().div_ceil(ENTRIES_PER_PAGE).max(1);
+    let page = encyclopedia.page.min(pages - 1);
+    let heading = if encyclopedia.query.is_empty() {
+        "Encyclopedia".to_string()
+    } else {
+        format!("Encyclopedia: \"{}\"", encyclopedia.query)
+    };
+    let body = if matches.is_empty() {
+        "Nothing found".to_string()
+    } else {
+        matches
+            .iter()
+            .skip(page * ENTRIES_PER_PAGE)
+            .take(ENTRIES_PER_PAGE)
+            .map(|entry| format!("{} ({:?})\n  {}", entry.title, entry.category, entry.text))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    let text_value = format!(
+        "{} - page {}/{}\n{}\n\n/wiki <words> to search, PgUp/PgDn to flip pages, F1 to close",
+        heading,
+        page + 1,
+        pages,
+        body
+    );
+    for mut text in text.iter_mut() {
+        text.0 = text_value.clone();
+    }
+    // keep the page in range without retriggering the update
+    encyclopedia.bypass_change_detection().page = page;
+}
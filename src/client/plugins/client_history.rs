@@ -0,0 +1,113 @@
+use bevy::prelude::*;
+use lightyear::prelude::client::*;
+
+use crate::client::plugins::{ChatInput, FeatureUsed, ShowToast};
+use crate::shared::history::{HistoryEntry, HistoryRecorded, WorldHistory};
+
+// Key toggling the timeline of the world's history
+const TIMELINE_KEY: KeyCode = KeyCode::F6;
+// Entries shown on the timeline, the latest ones
+const TIMELINE_LENGTH: usize = 20;
+
+// Client plugin announcing milestones of the world as they happen and showing its history on a
+// timeline
+pub struct ClientHistoryPlugin;
+
+impl Plugin for ClientHistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<KnownHistory>()
+            .add_systems(Startup, setup_timeline)
+            .add_systems(
+                Update,
+                (receive_history, timeline_input, update_timeline).chain(),
+            );
+    }
+}
+
+// History of the world, as the server told it
+#[derive(Resource, Default)]
+struct KnownHistory(Vec<HistoryEntry>);
+
+#[derive(Component)]
+struct Timeline;
+
+#[derive(Component)]
+struct TimelineText;
+
+fn setup_timeline(mut commands: Commands) {
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                right: Val::Px(10.0),
+                top: Val::Px(60.0),
+                padding: UiRect::all(Val::Px(6.0)),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.6)),
+            Visibility::Hidden,
+            Timeline,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new("History"),
+                TextFont::from_font_size(14.0),
+                TextColor(Color::WHITE),
+                TimelineText,
+            ));
+        });
+}
+
+fn receive_history(
+    mut history: ResMut<KnownHistory>,
+    mut logs: EventReader<MessageEvent<WorldHistory>>,
+    mut recorded: EventReader<MessageEvent<HistoryRecorded>>,
+    mut toasts: EventWriter<ShowToast>,
+) {
+    for event in logs.read() {
+        history.0 = event.message().entries.clone();
+    }
+    for event in recorded.read() {
+        let entry = event.message().entry.clone();
+        toasts.send(ShowToast {
+            title: format!("Day {}", entry.day()),
+            body: entry.describe(),
+        });
+        history.0.push(entry);
+    }
+}
+
+fn timeline_input(
+    keypress: Res<ButtonInput<KeyCode>>,
+    chat: Res<ChatInput>,
+    mut timeline: Query<&mut Visibility, With<Timeline>>,
+    mut features: EventWriter<FeatureUsed>,
+) {
+    if chat.open || !keypress.just_pressed(TIMELINE_KEY) {
+        return;
+    }
+    features.send(FeatureUsed("history_timeline"));
+    for mut visibility in timeline.iter_mut() {
+        visibility.toggle_visible_hidden();
+    }
+}
+
+// One line per entry, grouped under the day it happened on
+fn update_timeline(history: Res<KnownHistory>, mut text: Query<&mut Text, With<TimelineText>>) {
+    if !history.is_changed() {
+        return;
+    }
+    let skipped = history.0.len().saturating_sub(TIMELINE_LENGTH);
+    let mut lines = vec![format!("History ({} milestones)", history.0.len())];
+    let mut day = None;
+    for entry in history.0[skipped..].iter() {
+        if day != Some(entry.day()) {
+            day = Some(entry.day());
+            lines.push(format!("Day {}", entry.day()));
+        }
+        lines.push(format!("  {}", entry.describe()));
+    }
+    for mut text in text.iter_mut() {
+        text.0 = lines.join("\n");
+    }
+}
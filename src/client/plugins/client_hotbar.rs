@@ -0,0 +1,161 @@
+use bevy::input::mouse::MouseWheel;
+use bevy::prelude::*;
+use lightyear::prelude::client::*;
+
+use crate::client::plugins::{ChatInput, ItemColors, TileProjection};
+use crate::protocol::{Channel1, PlayerPosition, SelectHotbarSlot};
+use crate::shared::items::{HeldItem, Inventory, HOTBAR_SLOTS};
+
+// Keys selecting hotbar slots 1 to 9
+const SLOT_KEYS: [KeyCode; HOTBAR_SLOTS] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
+// Size of a hotbar slot on screen, in pixels
+const SLOT_SIZE: f32 = 40.0;
+// Size of the held item drawn next to players, relative to a tile
+const HELD_ITEM_SIZE: f32 = 0.25;
+
+// Client-side hotbar: slot selection, hotbar HUD, and the items held by players
+pub struct ClientHotbarPlugin;
+
+impl Plugin for ClientHotbarPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_hotbar_ui).add_systems(
+            Update,
+            (hotbar_input, update_hotbar_ui, draw_held_items).chain(),
+        );
+    }
+}
+
+#[derive(Component)]
+struct HotbarSlot(usize);
+
+#[derive(Component)]
+struct HotbarCount(usize);
+
+fn setup_hotbar_ui(mut commands: Commands) {
+    commands
+        .spawn(Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(10.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            column_gap: Val::Px(4.0),
+            ..default()
+        })
+        .with_children(|parent| {
+            for slot in 0..HOTBAR_SLOTS {
+                parent
+                    .spawn((
+                        Node {
+                            width: Val::Px(SLOT_SIZE),
+                            height: Val::Px(SLOT_SIZE),
+                            border: UiRect::all(Val::Px(2.0)),
+                            align_items: AlignItems::End,
+                            justify_content: JustifyContent::End,
+                            ..default()
+                        },
+                        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.5)),
+                        BorderColor(Color::srgb(0.3, 0.3, 0.3)),
+                        HotbarSlot(slot),
+                    ))
+                    .with_children(|slot_node| {
+                        slot_node.spawn((
+                            Text::new(""),
+                            TextFont::from_font_size(12.0),
+                            TextColor(Color::WHITE),
+                            HotbarCount(slot),
+                        ));
+                    });
+            }
+        });
+}
+
+// Number keys select a slot directly, the scroll wheel cycles through them
+fn hotbar_input(
+    keypress: Res<ButtonInput<KeyCode>>,
+    mut wheel: EventReader<MouseWheel>,
+    chat: Res<ChatInput>,
+    player: Query<&HeldItem, With<Predicted>>,
+    mut client: ResMut<ConnectionManager>,
+) {
+    let scroll: f32 = wheel.read().map(|event| event.y).sum();
+    if chat.open {
+        return;
+    }
+    let Ok(held) = player.get_single() else {
+        return;
+    };
+    let mut slot = SLOT_KEYS.iter().position(|key| keypress.just_pressed(*key));
+    if slot.is_none() && scroll != 0.0 {
+        let step = if scroll > 0.0 { HOTBAR_SLOTS - 1 } else { 1 };
+        slot = Some((held.slot + step) % HOTBAR_SLOTS);
+    }
+    let Some(slot) = slot.filter(|slot| *slot != held.slot) else {
+        return;
+    };
+    client
+        .send_message::<Channel1, _>(&SelectHotbarSlot { slot })
+        .unwrap_or_else(|e| {
+            error!("Failed to send hotbar selection: {:?}", e);
+        });
+}
+
+fn update_hotbar_ui(
+    player: Query<
+        (&Inventory, &HeldItem),
+        (With<Predicted>, Or<(Changed<Inventory>, Changed<HeldItem>)>),
+    >,
+    mut slots: Query<(&HotbarSlot, &mut BackgroundColor, &mut BorderColor)>,
+    mut counts: Query<(&HotbarCount, &mut Text)>,
+    colors: Res<ItemColors>,
+) {
+    let Ok((inventory, held)) = player.get_single() else {
+        return;
+    };
+    for (slot, mut background, mut border) in slots.iter_mut() {
+        background.0 = match inventory.slots.get(slot.0) {
+            Some(stack) => colors.get(stack.kind),
+            None => Color::srgba(0.0, 0.0, 0.0, 0.5),
+        };
+        border.0 = if slot.0 == held.slot {
+            Color::WHITE
+        } else {
+            Color::srgb(0.3, 0.3, 0.3)
+        };
+    }
+    for (slot, mut text) in counts.iter_mut() {
+        text.0 = match inventory.slots.get(slot.0) {
+            Some(stack) if stack.count > 1 => stack.count.to_string(),
+            _ => String::new(),
+        };
+    }
+}
+
+// Draw the item each player holds next to them
+fn draw_held_items(
+    mut gizmos: Gizmos,
+    projection: Res<TileProjection>,
+    players: Query<(&PlayerPosition, &HeldItem)>,
+    colors: Res<ItemColors>,
+) {
+    for (position, held) in players.iter() {
+        let Some(kind) = held.kind else {
+            continue;
+        };
+        let hand = projection.to_screen(position.0 + Vec2::new(0.35, 0.0));
+        gizmos.rect_2d(
+            Isometry2d::from_translation(hand),
+            Vec2::splat(HELD_ITEM_SIZE),
+            colors.get(kind),
+        );
+    }
+}
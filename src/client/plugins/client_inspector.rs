@@ -0,0 +1,60 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts, EguiPlugin};
+use std::collections::HashSet;
+
+use crate::client::plugins::ClientWorldState;
+use crate::shared::world_generation::{ChunkCoord, WorldState};
+
+// Client debug plugin showing what the client knows about the world in an egui window. Only does
+// anything when the app was built with the inspector enabled.
+pub struct ClientInspectorPlugin;
+
+impl Plugin for ClientInspectorPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            return;
+        }
+        app.add_systems(Update, client_inspector_ui);
+    }
+}
+
+fn chunk_list<'a>(ui: &mut egui::Ui, title: &str, coords: impl Iterator<Item = &'a ChunkCoord>) {
+    let mut coords: Vec<&ChunkCoord> = coords.collect();
+    coords.sort_by_key(|coord| (coord.y, coord.x));
+    ui.collapsing(format!("{} ({})", title, coords.len()), |ui| {
+        egui::ScrollArea::vertical()
+            .id_salt(title)
+            .max_height(150.0)
+            .show(ui, |ui| {
+                for coord in coords {
+                    ui.label(format!("({}, {})", coord.x, coord.y));
+                }
+            });
+    });
+}
+
+fn client_inspector_ui(
+    mut contexts: EguiContexts,
+    client_world: Res<ClientWorldState>,
+    world_state: Res<WorldState>,
+) {
+    let Some(ctx) = contexts.try_ctx_mut() else {
+        return;
+    };
+    egui::Window::new("Client world").show(ctx, |ui| {
+        ui.label(format!("Player chunk: {:?}", client_world.player_chunk));
+        ui.label(format!("View distance: {}", client_world.view_distance));
+        ui.label(format!("In instance: {}", client_world.in_instance));
+        ui.label(format!("Chunk entities: {}", world_state.chunks.len()));
+        chunk_list(ui, "Visible", client_world.visible_chunks.iter());
+        chunk_list(ui, "Loaded", client_world.loaded_chunks.iter());
+        chunk_list(ui, "Requested", client_world.requested_chunks.keys());
+        chunk_list(ui, "Pending deltas", client_world.pending_deltas.keys());
+        // visible chunks we have no data for are the ones the player sees as holes
+        let missing: HashSet<&ChunkCoord> = client_world
+            .visible_chunks
+            .difference(&client_world.loaded_chunks)
+            .collect();
+        chunk_list(ui, "Visible but not loaded", missing.into_iter());
+    });
+}
@@ -0,0 +1,113 @@
+use bevy::prelude::*;
+use lightyear::prelude::client::*;
+
+use crate::client::plugins::{ClientWorldState, TileProjection};
+use crate::shared::instances::{DungeonDoor, EnterInstance, LeaveInstance};
+use crate::shared::world_generation::{ChunkCoord, WorldGrid};
+
+// Size of a door relative to a tile
+const DOOR_SIZE: f32 = 0.8;
+
+// Client plugin swapping the world we show when entering or leaving an instance, and drawing
+// dungeon doors
+pub struct ClientInstancesPlugin;
+
+impl Plugin for ClientInstancesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                (enter_instance, leave_instance).chain(),
+                (spawn_door_sprites, place_doors).chain(),
+            ),
+        );
+    }
+}
+
+fn door_color(door: &DungeonDoor) -> Color {
+    match door {
+        DungeonDoor::Entrance { .. } => Color::srgb(0.25, 0.1, 0.3),
+        DungeonDoor::Exit { .. } => Color::srgb(0.9, 0.8, 0.4),
+    }
+}
+
+// Forget every chunk we have: they belong to the world we just left
+fn clear_chunks(
+    commands: &mut Commands,
+    client_world: &mut ClientWorldState,
+    chunks: &Query<Entity, With<ChunkCoord>>,
+) {
+    for entity in chunks.iter() {
+        commands.entity(entity).despawn();
+    }
+    client_world.visible_chunks.clear();
+    client_world.loaded_chunks.clear();
+    client_world.requested_chunks.clear();
+    client_world.pending_deltas.clear();
+    client_world.player_chunk = None;
+}
+
+fn enter_instance(
+    mut commands: Commands,
+    mut events: EventReader<MessageEvent<EnterInstance>>,
+    mut client_world: ResMut<ClientWorldState>,
+    chunks: Query<Entity, With<ChunkCoord>>,
+) {
+    for event in events.read() {
+        let chunk = event.message().chunk.clone();
+        let coord = chunk.coord;
+        info!("Entered an instance");
+        clear_chunks(&mut commands, &mut client_world, &chunks);
+        client_world.in_instance = true;
+        client_world.visible_chunks.insert(coord);
+        client_world.loaded_chunks.insert(coord);
+        commands.spawn((chunk, coord));
+    }
+}
+
+fn leave_instance(
+    mut commands: Commands,
+    mut events: EventReader<MessageEvent<LeaveInstance>>,
+    mut client_world: ResMut<ClientWorldState>,
+    chunks: Query<Entity, With<ChunkCoord>>,
+) {
+    if events.read().count() == 0 {
+        return;
+    }
+    info!("Left the instance");
+    // the overworld chunks around the player get requested again
+    clear_chunks(&mut commands, &mut client_world, &chunks);
+    client_world.in_instance = false;
+}
+
+fn spawn_door_sprites(
+    mut commands: Commands,
+    doors: Query<(Entity, &DungeonDoor), Added<DungeonDoor>>,
+) {
+    for (entity, door) in doors.iter() {
+        commands.entity(entity).insert((
+            Sprite {
+                custom_size: Some(Vec2::splat(DOOR_SIZE)),
+                color: door_color(door),
+                ..default()
+            },
+            Transform::default(),
+        ));
+    }
+}
+
+// Position doors on screen according to the current projection
+fn place_doors(
+    projection: Res<TileProjection>,
+    mut doors: Query<(Ref<DungeonDoor>, &mut Transform)>,
+) {
+    for (door, mut transform) in doors.iter_mut() {
+        if !door.is_added() && !projection.is_changed() {
+            continue;
+        }
+        let (x, y) = door.tile();
+        let world = WorldGrid::tile_to_world((x, y));
+        let screen = projection.to_screen(world);
+        transform.translation = screen.extend(0.4 + projection.depth(world));
+    }
+}
@@ -0,0 +1,105 @@
+use bevy::prelude::*;
+use lightyear::prelude::client::*;
+
+use crate::client::plugins::{ChatInput, ChatLog, FeatureUsed};
+use crate::protocol::{Channel1, InteractRequest, PlayerPosition};
+use crate::shared::error::{GameError, ReportError};
+use crate::shared::graves::Grave;
+use crate::shared::instances::DungeonDoor;
+use crate::shared::interaction::{best_target, nearby_targets, INTERACT_REACH};
+use crate::shared::items::DroppedItem;
+use crate::shared::npc::Npc;
+use crate::shared::rails::{Cart, LeaveCart, Riding};
+use crate::shared::reputation::AttackNpc;
+use crate::shared::world_generation::{Chunk, WorldConfig, WorldGrid, WorldState};
+
+// Key interacting with the best target around the player
+const INTERACT_KEY: KeyCode = KeyCode::KeyE;
+// Held with the interact key to attack the closest NPC instead
+const ATTACK_MODIFIER: KeyCode = KeyCode::ShiftLeft;
+
+// Client-side interact key: picks what to interact with and asks the server to do it
+pub struct ClientInteractionPlugin;
+
+impl Plugin for ClientInteractionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, interact_input);
+    }
+}
+
+fn interact_input(
+    keypress: Res<ButtonInput<KeyCode>>,
+    chat: Res<ChatInput>,
+    mut log: ResMut<ChatLog>,
+    world_state: Res<WorldState>,
+    world_config: Res<WorldConfig>,
+    chunks: Query<&Chunk>,
+    player: Query<(&PlayerPosition, Has<Riding>), With<Predicted>>,
+    items: Query<&DroppedItem>,
+    graves: Query<&Grave>,
+    npcs: Query<&Npc>,
+    doors: Query<&DungeonDoor>,
+    carts: Query<&Cart>,
+    mut client: ResMut<ConnectionManager>,
+    mut features: EventWriter<FeatureUsed>,
+    mut errors: EventWriter<ReportError>,
+) {
+    if chat.open || !keypress.just_pressed(INTERACT_KEY) {
+        return;
+    }
+    let Ok((position, riding)) = player.get_single() else {
+        return;
+    };
+    // riders get off their cart
+    if riding {
+        client
+            .send_message::<Channel1, _>(&LeaveCart)
+            .unwrap_or_else(|e| {
+                errors.send(ReportError(GameError::send("LeaveCart", e)));
+            });
+        return;
+    }
+    if keypress.pressed(ATTACK_MODIFIER) {
+        let closest = npcs
+            .iter()
+            .filter(|npc| npc.position.distance(position.0) <= INTERACT_REACH)
+            .min_by(|a, b| {
+                a.position
+                    .distance_squared(position.0)
+                    .total_cmp(&b.position.distance_squared(position.0))
+            });
+        let Some(npc) = closest else {
+            log.push("There is nobody to attack here".to_string());
+            return;
+        };
+        client
+            .send_message::<Channel1, _>(&AttackNpc {
+                tile: WorldGrid::world_to_tile(npc.position),
+            })
+            .unwrap_or_else(|e| {
+                errors.send(ReportError(GameError::send("AttackNpc", e)));
+            });
+        features.send(FeatureUsed("attack"));
+        return;
+    }
+    let targets = nearby_targets(
+        position.0,
+        &world_state,
+        &world_config,
+        &chunks,
+        items.iter(),
+        graves.iter(),
+        npcs.iter(),
+        doors.iter(),
+        carts.iter(),
+    );
+    let Some(target) = best_target(position.0, targets) else {
+        log.push("There is nothing to interact with here".to_string());
+        return;
+    };
+    client
+        .send_message::<Channel1, _>(&InteractRequest { target })
+        .unwrap_or_else(|e| {
+            error!("Failed to send interaction: {:?}", e);
+        });
+}
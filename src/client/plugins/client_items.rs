@@ -0,0 +1,125 @@
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+use crate::client::plugins::TileProjection;
+use crate::shared::items::{DroppedItem, ItemKind};
+use crate::shared::world_generation::WorldGrid;
+
+// Size of a dropped item relative to a tile
+const ITEM_SIZE: f32 = 0.4;
+// Scale applied to the count badge text so that it fits on a tile
+const BADGE_SCALE: f32 = 0.03;
+
+// Plugin to render dropped items and their stack count
+pub struct ClientItemsRenderPlugin;
+
+impl Plugin for ClientItemsRenderPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ItemColors>().add_systems(
+            Update,
+            (spawn_item_sprites, update_count_badges, place_items).chain(),
+        );
+    }
+}
+
+// Text showing how many items a dropped stack holds
+#[derive(Component)]
+struct CountBadge;
+
+/// Colors items are drawn with: the built-in ones, unless the server's resource pack replaced them
+#[derive(Resource, Default)]
+pub struct ItemColors(pub HashMap<ItemKind, Color>);
+
+impl ItemColors {
+    pub fn get(&self, kind: ItemKind) -> Color {
+        self.0
+            .get(&kind)
+            .copied()
+            .unwrap_or_else(|| item_color(kind))
+    }
+}
+
+pub fn item_color(kind: ItemKind) -> Color {
+    match kind {
+        ItemKind::Wood => Color::srgb(0.55, 0.35, 0.15),
+        ItemKind::Stone => Color::srgb(0.6, 0.6, 0.6),
+        ItemKind::Iron => Color::srgb(0.7, 0.7, 0.8),
+        ItemKind::Copper => Color::srgb(0.85, 0.5, 0.2),
+        ItemKind::Coal => Color::srgb(0.15, 0.15, 0.15),
+        ItemKind::Gold => Color::srgb(1.0, 0.85, 0.0),
+        ItemKind::Fish => Color::srgb(0.4, 0.7, 0.9),
+        ItemKind::Berries => Color::srgb(0.7, 0.1, 0.35),
+        ItemKind::CookedFish => Color::srgb(0.8, 0.55, 0.3),
+        ItemKind::Stew => Color::srgb(0.6, 0.4, 0.2),
+        ItemKind::Axe => Color::srgb(0.75, 0.3, 0.25),
+        ItemKind::Pickaxe => Color::srgb(0.35, 0.45, 0.75),
+        ItemKind::Torch => Color::srgb(1.0, 0.6, 0.1),
+        ItemKind::Water => Color::srgb(0.3, 0.5, 1.0),
+    }
+}
+
+fn badge_text(count: u32) -> String {
+    if count > 1 {
+        count.to_string()
+    } else {
+        String::new()
+    }
+}
+
+fn spawn_item_sprites(
+    mut commands: Commands,
+    items: Query<(Entity, &DroppedItem), Added<DroppedItem>>,
+    colors: Res<ItemColors>,
+) {
+    for (entity, item) in items.iter() {
+        commands
+            .entity(entity)
+            .insert((
+                Sprite {
+                    custom_size: Some(Vec2::splat(ITEM_SIZE)),
+                    color: colors.get(item.kind),
+                    ..default()
+                },
+                Transform::default(),
+            ))
+            .with_children(|parent| {
+                parent.spawn((
+                    Text2d::new(badge_text(item.count)),
+                    TextFont::from_font_size(12.0),
+                    TextColor(Color::WHITE),
+                    Transform::from_xyz(ITEM_SIZE * 0.5, -ITEM_SIZE * 0.5, 0.1)
+                        .with_scale(Vec3::splat(BADGE_SCALE)),
+                    CountBadge,
+                ));
+            });
+    }
+}
+
+// Update the badge when the server merges more items into a stack
+fn update_count_badges(
+    items: Query<(&DroppedItem, &Children), Changed<DroppedItem>>,
+    mut badges: Query<&mut Text2d, With<CountBadge>>,
+) {
+    for (item, children) in items.iter() {
+        for child in children.iter() {
+            if let Ok(mut text) = badges.get_mut(*child) {
+                text.0 = badge_text(item.count);
+            }
+        }
+    }
+}
+
+// Position items on screen according to the current projection
+fn place_items(
+    projection: Res<TileProjection>,
+    mut items: Query<(Ref<DroppedItem>, &mut Transform)>,
+) {
+    for (item, mut transform) in items.iter_mut() {
+        if !item.is_added() && !projection.is_changed() {
+            continue;
+        }
+        let world = WorldGrid::tile_to_world(item.tile);
+        let screen = projection.to_screen(world);
+        transform.translation = screen.extend(0.5 + projection.depth(world));
+    }
+}
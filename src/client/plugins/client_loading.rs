@@ -0,0 +1,234 @@
+use bevy::asset::{LoadState, UntypedHandle};
+use bevy::prelude::*;
+use lightyear::prelude::client::*;
+use std::time::Duration;
+
+use crate::client::plugins::client_audio::AUDIO_PROFILES_PATH;
+use crate::client::plugins::client_music::{MusicPlaylist, PLAYLIST_PATH};
+use crate::client::plugins::{
+    AdaptiveViewDistance, AudioProfiles, ClientWorldState, TileRenderState,
+};
+use crate::protocol::PlayerPosition;
+use crate::shared::feature_flags::ServerFeatureFlags;
+
+const FONT_PATH: &str = "fonts/FiraSans-Regular.ttf";
+// Past this after connecting, the game starts with whatever is ready rather than keep the player
+// waiting
+const MAX_LOADING_TIME: Duration = Duration::from_secs(30);
+// Share of the progress bar each step takes; the initial chunks take the rest
+const ASSETS_SHARE: f32 = 0.2;
+const HANDSHAKE_SHARE: f32 = 0.2;
+const PLAYER_SHARE: f32 = 0.1;
+const BAR_WIDTH: f32 = 400.0;
+
+// Client plugin holding the game behind a loading screen until the assets are loaded, the server
+// has sent its settings and the chunks around the player have arrived
+pub struct ClientLoadingPlugin;
+
+impl Plugin for ClientLoadingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_state::<LoadingState>()
+            .init_resource::<LoadingProgress>()
+            .add_systems(Startup, (preload_assets, setup_loading_screen))
+            .add_systems(
+                Update,
+                (receive_handshake, track_loading, update_loading_screen)
+                    .chain()
+                    .run_if(in_state(LoadingState::Loading)),
+            )
+            .add_systems(OnEnter(LoadingState::Playing), close_loading_screen);
+    }
+}
+
+/// Whether the client is still loading. Player input is ignored until it's playing.
+#[derive(States, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum LoadingState {
+    #[default]
+    Loading,
+    Playing,
+}
+
+#[derive(Resource, Default)]
+struct LoadingProgress {
+    assets: Vec<UntypedHandle>,
+    // The server's feature flags have arrived
+    feature_flags: bool,
+    fraction: f32,
+    step: &'static str,
+    elapsed: Duration,
+}
+
+#[derive(Component)]
+struct LoadingScreen;
+
+#[derive(Component)]
+struct LoadingBar;
+
+#[derive(Component)]
+struct LoadingText;
+
+fn preload_assets(asset_server: Res<AssetServer>, mut progress: ResMut<LoadingProgress>) {
+    // the plugins using them load the same paths, and get the same handles
+    progress.assets = vec![
+        asset_server.load::<Font>(FONT_PATH).untyped(),
+        asset_server
+            .load::<AudioProfiles>(AUDIO_PROFILES_PATH)
+            .untyped(),
+        asset_server.load::<MusicPlaylist>(PLAYLIST_PATH).untyped(),
+    ];
+}
+
+fn setup_loading_screen(mut commands: Commands) {
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                row_gap: Val::Px(12.0),
+                ..default()
+            },
+            BackgroundColor(Color::srgb(0.05, 0.05, 0.08)),
+            // over the HUDs, but under the queue position
+            GlobalZIndex(100),
+            LoadingScreen,
+        ))
+        .with_children(|screen| {
+            screen.spawn((
+                Text::new("Loading"),
+                TextFont::from_font_size(20.0),
+                TextColor(Color::WHITE),
+                LoadingText,
+            ));
+            screen
+                .spawn((
+                    Node {
+                        width: Val::Px(BAR_WIDTH),
+                        height: Val::Px(12.0),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgb(0.2, 0.2, 0.25)),
+                ))
+                .with_children(|frame| {
+                    frame.spawn((
+                        Node {
+                            width: Val::Px(0.0),
+                            height: Val::Percent(100.0),
+                            ..default()
+                        },
+                        BackgroundColor(Color::srgb(0.3, 0.7, 0.3)),
+                        LoadingBar,
+                    ));
+                });
+        });
+}
+
+fn receive_handshake(
+    mut events: EventReader<MessageEvent<ServerFeatureFlags>>,
+    mut progress: ResMut<LoadingProgress>,
+) {
+    if events.read().count() > 0 {
+        progress.feature_flags = true;
+    }
+}
+
+// Fraction of the steps that are done
+fn done(steps: &[bool]) -> f32 {
+    steps.iter().filter(|done| **done).count() as f32 / steps.len() as f32
+}
+
+fn track_loading(
+    time: Res<Time<Real>>,
+    asset_server: Res<AssetServer>,
+    tile_render: Res<TileRenderState>,
+    view: Res<AdaptiveViewDistance>,
+    client_world: Res<ClientWorldState>,
+    player: Query<(), (With<PlayerPosition>, With<Predicted>)>,
+    mut progress: ResMut<LoadingProgress>,
+    mut next_state: ResMut<NextState<LoadingState>>,
+) {
+    // assets that failed to load won't load any better by waiting
+    let loaded = progress
+        .assets
+        .iter()
+        .filter(|handle| {
+            matches!(
+                asset_server.load_state(handle.id()),
+                LoadState::Loaded | LoadState::Failed(_)
+            )
+        })
+        .count();
+    let assets = loaded as f32 / progress.assets.len().max(1) as f32;
+    let sprites = tile_render.tile_sprites.is_some();
+    let handshake = done(&[progress.feature_flags, view.server_limit.is_some()]);
+    // observers have no player, only a place to look at
+    let spawned = !player.is_empty() || client_world.focus.is_some();
+    // the chunks around the player are only known once it has spawned
+    let chunks = match client_world.visible_chunks.len() {
+        0 => 0.0,
+        visible => {
+            client_world
+                .visible_chunks
+                .intersection(&client_world.loaded_chunks)
+                .count() as f32
+                / visible as f32
+        }
+    };
+
+    progress.step = if assets < 1.0 || !sprites {
+        "Loading assets"
+    } else if handshake < 1.0 {
+        "Connecting to the server"
+    } else if !spawned {
+        "Joining the world"
+    } else {
+        "Receiving terrain"
+    };
+    progress.fraction = assets * ASSETS_SHARE
+        + handshake * HANDSHAKE_SHARE
+        + done(&[spawned]) * PLAYER_SHARE
+        + chunks * (1.0 - ASSETS_SHARE - HANDSHAKE_SHARE - PLAYER_SHARE);
+    // waiting to connect, or in the queue of a full server, doesn't count
+    if handshake >= 1.0 {
+        progress.elapsed += time.delta();
+    }
+
+    let ready = assets >= 1.0 && sprites && handshake >= 1.0 && spawned && chunks >= 1.0;
+    if ready {
+        info!("Loaded in {:.1}s", progress.elapsed.as_secs_f32());
+        next_state.set(LoadingState::Playing);
+    } else if progress.elapsed >= MAX_LOADING_TIME {
+        warn!(
+            "Still loading after {}s ({}, {:.0}%), starting anyway",
+            MAX_LOADING_TIME.as_secs(),
+            progress.step,
+            progress.fraction * 100.0
+        );
+        next_state.set(LoadingState::Playing);
+    }
+}
+
+fn update_loading_screen(
+    progress: Res<LoadingProgress>,
+    mut bars: Query<&mut Node, With<LoadingBar>>,
+    mut texts: Query<&mut Text, With<LoadingText>>,
+) {
+    for mut bar in bars.iter_mut() {
+        bar.width = Val::Px(BAR_WIDTH * progress.fraction.clamp(0.0, 1.0));
+    }
+    let text = format!("{}... {:.0}%", progress.step, progress.fraction * 100.0);
+    for mut label in texts.iter_mut() {
+        if label.0 != text {
+            label.0 = text.clone();
+        }
+    }
+}
+
+fn close_loading_screen(mut commands: Commands, screens: Query<Entity, With<LoadingScreen>>) {
+    for screen in screens.iter() {
+        commands.entity(screen).despawn_recursive();
+    }
+}
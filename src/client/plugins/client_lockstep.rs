@@ -0,0 +1,193 @@
+use bevy::prelude::*;
+use lightyear::prelude::client::*;
+use lightyear::prelude::*;
+use std::collections::VecDeque;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::client::plugins::ClientWorldState;
+use crate::protocol::{Inputs, PlayerPosition};
+use crate::settings_common::Settings;
+use crate::shared::error::{GameError, ReportError};
+use crate::shared::movement::{step_movement, LoadedTiles, TileSource};
+
+// Directory the divergence reports are written to
+const DUMP_DIR: &str = "desync";
+// Ticks of inputs and simulated states kept; server updates older than that can't be checked
+const HISTORY_TICKS: usize = 256;
+
+// Client debug plugin replaying the shared simulation locally, from the same inputs as the server,
+// and checking every confirmed server state against it. Unlike prediction, the replay never
+// rolls back, so any divergence is nondeterminism in the shared systems (or a server-only change
+// such as a teleport). The first divergence is dumped to a file with the inputs leading to it.
+// Only enabled with `--lockstep-debug`.
+pub struct ClientLockstepPlugin;
+
+impl Plugin for ClientLockstepPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Lockstep>()
+            .add_systems(Startup, load_lockstep_settings)
+            .add_systems(
+                FixedUpdate,
+                simulate_lockstep.run_if(|lockstep: Res<Lockstep>| lockstep.enabled),
+            )
+            .add_systems(
+                Update,
+                check_lockstep.run_if(|lockstep: Res<Lockstep>| lockstep.enabled),
+            );
+    }
+}
+
+/// Local replay of the player's simulation, and how it compared to the server so far
+#[derive(Resource, Default, Debug)]
+pub struct Lockstep {
+    pub enabled: bool,
+    pub checks: u64,
+    pub divergences: u64,
+    // Input applied on each tick, oldest first
+    inputs: VecDeque<(Tick, Inputs)>,
+    // Replayed state at the end of each tick, oldest first. Empty until seeded from the server.
+    states: VecDeque<(Tick, PlayerPosition)>,
+    last_checked: Option<Tick>,
+    dumped: Option<PathBuf>,
+}
+
+impl Lockstep {
+    fn state_at(&self, tick: Tick) -> Option<&PlayerPosition> {
+        self.states
+            .iter()
+            .find(|(state_tick, _)| *state_tick == tick)
+            .map(|(_, state)| state)
+    }
+
+    // Restart the replay from a server state, applying the inputs recorded since
+    fn seed(&mut self, tick: Tick, state: PlayerPosition, tiles: &impl TileSource) {
+        self.states.clear();
+        let mut state = state;
+        self.states.push_back((tick, state.clone()));
+        for (input_tick, input) in self.inputs.iter() {
+            if *input_tick - tick > 0 {
+                step_movement(&mut state, input, tiles);
+                self.states.push_back((*input_tick, state.clone()));
+            }
+        }
+    }
+
+    fn report(&self, tick: Tick, local: &PlayerPosition, server: &PlayerPosition) -> String {
+        let mut report = format!(
+            "First divergence at tick {:?}\ncomponent: PlayerPosition\nlocal: {:?}\nserver: {:?}\n\ninputs:\n",
+            tick, local, server
+        );
+        for (input_tick, input) in self.inputs.iter().filter(|(t, _)| *t - tick <= 0) {
+            report.push_str(&format!("{:?}: {:?}\n", input_tick, input));
+        }
+        report
+    }
+}
+
+fn load_lockstep_settings(settings: Option<Res<Settings>>, mut lockstep: ResMut<Lockstep>) {
+    if let Some(settings) = settings {
+        lockstep.enabled = settings.client.lockstep_debug;
+    }
+    if lockstep.enabled {
+        info!(
+            "Lockstep debugging enabled, divergences are dumped to {}/",
+            DUMP_DIR
+        );
+    }
+}
+
+// Record this tick's input, and advance the replay with it
+fn simulate_lockstep(
+    tick_manager: Res<TickManager>,
+    mut input_reader: EventReader<InputEvent<Inputs>>,
+    mut lockstep: ResMut<Lockstep>,
+    tiles: LoadedTiles,
+    client_world: Res<ClientWorldState>,
+) {
+    let tick = tick_manager.tick();
+    let input = input_reader
+        .read()
+        .filter_map(|event| event.input().clone())
+        .last()
+        .unwrap_or(Inputs::None);
+    // ticks replayed by a rollback were already recorded
+    if lockstep
+        .inputs
+        .back()
+        .is_some_and(|(last, _)| tick - *last <= 0)
+    {
+        return;
+    }
+    if let Some((_, state)) = lockstep.states.back() {
+        let mut state = state.clone();
+        if client_world.in_instance {
+            step_movement(&mut state, &input, &());
+        } else {
+            step_movement(&mut state, &input, &tiles);
+        }
+        lockstep.states.push_back((tick, state));
+    }
+    lockstep.inputs.push_back((tick, input));
+    while lockstep.inputs.len() > HISTORY_TICKS {
+        lockstep.inputs.pop_front();
+    }
+    while lockstep.states.len() > HISTORY_TICKS {
+        lockstep.states.pop_front();
+    }
+}
+
+// Compare each new server state of the player with the replay at the same tick
+fn check_lockstep(
+    predicted: Query<&Predicted, With<PlayerPosition>>,
+    confirmed: Query<(&PlayerPosition, &Confirmed)>,
+    mut lockstep: ResMut<Lockstep>,
+    mut errors: EventWriter<ReportError>,
+    tiles: LoadedTiles,
+    client_world: Res<ClientWorldState>,
+) {
+    let Some((server, confirmed)) = predicted
+        .iter()
+        .find_map(|predicted| confirmed.get(predicted.confirmed_entity?).ok())
+    else {
+        return;
+    };
+    let tick = confirmed.tick;
+    if lockstep.last_checked == Some(tick) {
+        return;
+    }
+    lockstep.last_checked = Some(tick);
+    let Some(local) = lockstep.state_at(tick).cloned() else {
+        // not seeded yet, or the server state is older than the history
+        if client_world.in_instance {
+            lockstep.seed(tick, server.clone(), &());
+        } else {
+            lockstep.seed(tick, server.clone(), &tiles);
+        }
+        return;
+    };
+    lockstep.checks += 1;
+    if local == *server {
+        return;
+    }
+    lockstep.divergences += 1;
+    warn!(
+        "Lockstep divergence at tick {:?}: local {:?}, server {:?}",
+        tick, local, server
+    );
+    if lockstep.dumped.is_none() {
+        let path = PathBuf::from(DUMP_DIR).join(format!("lockstep-{}.txt", tick.0));
+        let report = lockstep.report(tick, &local, server);
+        match fs::create_dir_all(DUMP_DIR).and_then(|_| fs::write(&path, report)) {
+            Ok(()) => {
+                info!("Dumped the first lockstep divergence to {}", path.display());
+                lockstep.dumped = Some(path);
+            }
+            Err(e) => {
+                errors.send(ReportError(GameError::from(e)));
+            }
+        }
+    }
+    // continue from the server state, so that one divergence isn't reported on every tick after
+    lockstep.seed(tick, server.clone());
+}
@@ -0,0 +1,72 @@
+use bevy::prelude::*;
+use lightyear::prelude::client::*;
+
+use crate::client::plugins::{ItemColors, TileProjection};
+use crate::shared::logistics::ItemsMoved;
+use crate::shared::world_generation::WorldGrid;
+
+// Time items take to travel from one container to the next, in seconds
+const FLOW_SECS: f32 = 0.6;
+// Size of an item on its way, relative to a tile
+const FLOW_SIZE: f32 = 0.25;
+// Items on their way are drawn over the inserter carrying them
+const FLOW_Z: f32 = 0.45;
+
+// Client-side drawing of the items inserters move between containers
+pub struct ClientLogisticsPlugin;
+
+impl Plugin for ClientLogisticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (spawn_moving_items, move_items).chain());
+    }
+}
+
+// An item sprite travelling between two containers
+#[derive(Component)]
+struct MovingItem {
+    from: Vec2,
+    to: Vec2,
+    elapsed: f32,
+}
+
+fn spawn_moving_items(
+    mut commands: Commands,
+    mut events: EventReader<MessageEvent<ItemsMoved>>,
+    colors: Res<ItemColors>,
+) {
+    for event in events.read() {
+        for moved in event.message().moves.iter() {
+            commands.spawn((
+                Sprite {
+                    custom_size: Some(Vec2::splat(FLOW_SIZE * WorldGrid::TILE_SIZE)),
+                    color: colors.get(moved.kind),
+                    ..default()
+                },
+                Transform::default(),
+                MovingItem {
+                    from: WorldGrid::tile_to_world(moved.from),
+                    to: WorldGrid::tile_to_world(moved.to),
+                    elapsed: 0.0,
+                },
+            ));
+        }
+    }
+}
+
+fn move_items(
+    mut commands: Commands,
+    time: Res<Time>,
+    projection: Res<TileProjection>,
+    mut items: Query<(Entity, &mut MovingItem, &mut Transform)>,
+) {
+    for (entity, mut item, mut transform) in items.iter_mut() {
+        item.elapsed += time.delta_secs();
+        if item.elapsed >= FLOW_SECS {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        let world = item.from.lerp(item.to, item.elapsed / FLOW_SECS);
+        let screen = projection.to_screen(world);
+        transform.translation = screen.extend(FLOW_Z + projection.depth(world));
+    }
+}
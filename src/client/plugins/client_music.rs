@@ -0,0 +1,372 @@
+use bevy::asset::io::Reader;
+use bevy::asset::{ron, AssetLoader, LoadContext};
+use bevy::audio::{AddAudioSource, Decodable, PlaybackMode, Source, Volume};
+use bevy::prelude::*;
+use lightyear::prelude::client::*;
+use serde::Deserialize;
+use std::f32::consts::TAU;
+use std::fmt;
+use std::time::Duration;
+
+use crate::client::plugins::{ChatInput, FeatureUsed};
+use crate::protocol::PlayerPosition;
+use crate::settings_common::Settings;
+use crate::shared::biome_map::BiomeMap;
+use crate::shared::danger::{DangerLevel, MAX_DANGER};
+use crate::shared::day_night::DayPhase;
+use crate::shared::world_generation::{BiomeType, Chunk, ChunkCoord, WorldConfig, WorldState};
+
+// Tracks and the conditions they play in, see `MusicPlaylist`
+pub(crate) const PLAYLIST_PATH: &str = "audio/playlist.music.ron";
+// Sample rate of generated melodies
+const SAMPLE_RATE: u32 = 44_100;
+// Fade at the start and end of every note, so notes don't click
+const NOTE_ATTACK_SECS: f32 = 0.01;
+// Keys muting the music, and turning it down and up
+const MUTE_KEY: KeyCode = KeyCode::KeyN;
+const VOLUME_DOWN_KEY: KeyCode = KeyCode::Minus;
+const VOLUME_UP_KEY: KeyCode = KeyCode::Equal;
+const VOLUME_STEP: f32 = 0.1;
+
+// Client music director: plays the track of the playlist that fits the player's biome, the time of
+// day and how much danger they are in, crossfading when that changes
+pub struct ClientMusicPlugin;
+
+impl Plugin for ClientMusicPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_audio_source::<Melody>()
+            .init_asset::<MusicPlaylist>()
+            .init_asset_loader::<MusicPlaylistLoader>()
+            .init_resource::<MusicDirector>()
+            .add_systems(Startup, (load_playlist, load_music_settings))
+            .add_systems(Update, (music_input, pick_track, crossfade).chain());
+    }
+}
+
+/// A track: a melody looped for as long as its conditions hold. Empty `biomes` or `phases` match
+/// any biome or time of day.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct MusicTrack {
+    pub name: String,
+    /// Frequencies of the notes in Hz, 0 for a rest
+    pub notes: Vec<f32>,
+    pub note_ms: u64,
+    #[serde(default = "full_volume")]
+    pub volume: f32,
+    #[serde(default)]
+    pub biomes: Vec<BiomeType>,
+    #[serde(default)]
+    pub phases: Vec<DayPhase>,
+    #[serde(default)]
+    pub min_danger: u8,
+    #[serde(default = "max_danger")]
+    pub max_danger: u8,
+}
+
+fn full_volume() -> f32 {
+    1.0
+}
+
+fn max_danger() -> u8 {
+    MAX_DANGER
+}
+
+impl MusicTrack {
+    pub fn matches(&self, biome: BiomeType, phase: DayPhase, danger: u8) -> bool {
+        (self.biomes.is_empty() || self.biomes.contains(&biome))
+            && (self.phases.is_empty() || self.phases.contains(&phase))
+            && (self.min_danger..=self.max_danger).contains(&danger)
+    }
+}
+
+/// Data asset listing the music tracks
+#[derive(Asset, TypePath, Deserialize, Clone, Debug, Default)]
+pub struct MusicPlaylist {
+    pub crossfade_secs: f32,
+    /// Tracks in order of preference: the first one matching plays
+    pub tracks: Vec<MusicTrack>,
+}
+
+impl MusicPlaylist {
+    pub fn pick(&self, biome: BiomeType, phase: DayPhase, danger: u8) -> Option<&MusicTrack> {
+        self.tracks
+            .iter()
+            .find(|track| track.matches(biome, phase, danger))
+    }
+}
+
+#[derive(Resource)]
+struct MusicPlaylistHandle(Handle<MusicPlaylist>);
+
+#[derive(Default)]
+struct MusicPlaylistLoader;
+
+#[derive(Debug)]
+enum MusicPlaylistLoaderError {
+    Io(std::io::Error),
+    Ron(ron::error::SpannedError),
+}
+
+impl fmt::Display for MusicPlaylistLoaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MusicPlaylistLoaderError::Io(e) => write!(f, "Could not read the playlist: {}", e),
+            MusicPlaylistLoaderError::Ron(e) => write!(f, "Invalid playlist: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for MusicPlaylistLoaderError {}
+
+impl AssetLoader for MusicPlaylistLoader {
+    type Asset = MusicPlaylist;
+    type Settings = ();
+    type Error = MusicPlaylistLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<MusicPlaylist, MusicPlaylistLoaderError> {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(MusicPlaylistLoaderError::Io)?;
+        ron::de::from_bytes(&bytes).map_err(MusicPlaylistLoaderError::Ron)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["music.ron"]
+    }
+}
+
+/// A generated melody, looped forever
+#[derive(Asset, TypePath, Clone, Debug)]
+pub struct Melody {
+    pub notes: Vec<f32>,
+    pub note: Duration,
+}
+
+#[derive(Clone)]
+pub struct MelodySource {
+    notes: Vec<f32>,
+    samples_per_note: u32,
+    sample: u64,
+}
+
+impl Iterator for MelodySource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.notes.is_empty() || self.samples_per_note == 0 {
+            return None;
+        }
+        let note = (self.sample / self.samples_per_note as u64) as usize % self.notes.len();
+        let in_note = (self.sample % self.samples_per_note as u64) as f32;
+        self.sample += 1;
+        let time = in_note / SAMPLE_RATE as f32;
+        let left = (self.samples_per_note as f32 - in_note) / SAMPLE_RATE as f32;
+        let envelope = (time.min(left) / NOTE_ATTACK_SECS).min(1.0);
+        Some((TAU * self.notes[note] * time).sin() * envelope)
+    }
+}
+
+impl Source for MelodySource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+impl Decodable for Melody {
+    type DecoderItem = f32;
+    type Decoder = MelodySource;
+
+    fn decoder(&self) -> Self::Decoder {
+        MelodySource {
+            notes: self.notes.clone(),
+            samples_per_note: (self.note.as_secs_f32() * SAMPLE_RATE as f32) as u32,
+            sample: 0,
+        }
+    }
+}
+
+/// What the music director is playing, and how loud
+#[derive(Resource, Debug)]
+pub struct MusicDirector {
+    pub current: Option<String>,
+    pub volume: f32,
+    pub muted: bool,
+}
+
+impl Default for MusicDirector {
+    fn default() -> Self {
+        Self {
+            current: None,
+            volume: 0.5,
+            muted: false,
+        }
+    }
+}
+
+impl MusicDirector {
+    fn master_volume(&self) -> f32 {
+        if self.muted {
+            0.0
+        } else {
+            self.volume
+        }
+    }
+}
+
+/// A playing track, fading in while it's the current one and out once it isn't
+#[derive(Component, Debug)]
+struct MusicVoice {
+    track: String,
+    volume: f32,
+    fade: f32,
+    fading_in: bool,
+}
+
+fn load_playlist(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(MusicPlaylistHandle(asset_server.load(PLAYLIST_PATH)));
+}
+
+fn load_music_settings(settings: Option<Res<Settings>>, mut director: ResMut<MusicDirector>) {
+    if let Some(settings) = settings {
+        director.volume = settings.client.music.volume.clamp(0.0, 1.0);
+        director.muted = settings.client.music.muted;
+    }
+}
+
+fn music_input(
+    keypress: Res<ButtonInput<KeyCode>>,
+    chat: Res<ChatInput>,
+    mut director: ResMut<MusicDirector>,
+    mut features: EventWriter<FeatureUsed>,
+) {
+    if chat.open {
+        return;
+    }
+    if keypress.just_pressed(MUTE_KEY) {
+        director.muted = !director.muted;
+        features.send(FeatureUsed("music_mute"));
+    }
+    if keypress.just_pressed(VOLUME_DOWN_KEY) {
+        director.volume = (director.volume - VOLUME_STEP).max(0.0);
+    }
+    if keypress.just_pressed(VOLUME_UP_KEY) {
+        director.volume = (director.volume + VOLUME_STEP).min(1.0);
+    }
+}
+
+// Start the track fitting the player's situation, and let the others fade out
+fn pick_track(
+    mut commands: Commands,
+    mut director: ResMut<MusicDirector>,
+    mut melodies: ResMut<Assets<Melody>>,
+    playlists: Res<Assets<MusicPlaylist>>,
+    playlist_handle: Option<Res<MusicPlaylistHandle>>,
+    biome_map: Res<BiomeMap>,
+    world_state: Res<WorldState>,
+    world_config: Res<WorldConfig>,
+    player: Query<(&PlayerPosition, Option<&DangerLevel>), With<Predicted>>,
+    chunks: Query<(&Chunk, &ChunkCoord)>,
+    mut voices: Query<&mut MusicVoice>,
+) {
+    let Some(playlist) = playlist_handle.and_then(|handle| playlists.get(&handle.0)) else {
+        return;
+    };
+    let Ok((position, danger)) = player.get_single() else {
+        return;
+    };
+    // the chunk we stand in knows its biome, even in instances; the biome map is a fallback
+    let coord = world_config.grid().world_to_chunk(position.0);
+    let biome = chunks
+        .iter()
+        .find(|(_, chunk_coord)| **chunk_coord == coord)
+        .map(|(chunk, _)| chunk.biome_type)
+        .unwrap_or_else(|| biome_map.biome_at_position(position.0));
+    let phase = DayPhase::at(world_state.world_time);
+    let danger = danger.map_or(0, |danger| danger.0);
+
+    let track = playlist.pick(biome, phase, danger);
+    let name = track.map(|track| track.name.clone());
+    if name == director.current {
+        return;
+    }
+    debug!(
+        "Music: {:?} -> {:?} ({:?}, {:?}, danger {})",
+        director.current, name, biome, phase, danger
+    );
+    director.current = name;
+    for mut voice in voices.iter_mut() {
+        voice.fading_in = false;
+    }
+    let Some(track) = track else {
+        return;
+    };
+    commands.spawn((
+        AudioPlayer(melodies.add(Melody {
+            notes: track.notes.clone(),
+            note: Duration::from_millis(track.note_ms),
+        })),
+        PlaybackSettings {
+            mode: PlaybackMode::Once,
+            volume: Volume::new(0.0),
+            ..default()
+        },
+        MusicVoice {
+            track: track.name.clone(),
+            volume: track.volume,
+            fade: 0.0,
+            fading_in: true,
+        },
+    ));
+}
+
+fn crossfade(
+    mut commands: Commands,
+    time: Res<Time>,
+    director: Res<MusicDirector>,
+    playlists: Res<Assets<MusicPlaylist>>,
+    playlist_handle: Option<Res<MusicPlaylistHandle>>,
+    mut voices: Query<(Entity, &mut MusicVoice, Option<&AudioSink>)>,
+) {
+    let crossfade_secs = playlist_handle
+        .and_then(|handle| playlists.get(&handle.0))
+        .map_or(0.0, |playlist| playlist.crossfade_secs);
+    let step = if crossfade_secs > 0.0 {
+        time.delta_secs() / crossfade_secs
+    } else {
+        1.0
+    };
+    for (entity, mut voice, sink) in voices.iter_mut() {
+        voice.fade = if voice.fading_in {
+            (voice.fade + step).min(1.0)
+        } else {
+            (voice.fade - step).max(0.0)
+        };
+        if !voice.fading_in && voice.fade == 0.0 {
+            trace!("Music: stopped {}", voice.track);
+            commands.entity(entity).despawn();
+            continue;
+        }
+        if let Some(sink) = sink {
+            sink.set_volume(voice.fade * voice.volume * director.master_volume());
+        }
+    }
+}
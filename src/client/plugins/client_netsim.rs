@@ -0,0 +1,59 @@
+use bevy::prelude::*;
+use lightyear::prelude::client::*;
+
+use crate::protocol::SetLinkConditioner;
+use crate::settings_common::Conditioner;
+
+// Client-side plugin applying link conditions requested by the server's /netsim command
+pub struct ClientNetsimPlugin;
+
+impl Plugin for ClientNetsimPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PendingReconnect>().add_systems(
+            Update,
+            (receive_link_conditioner, reconnect_with_new_conditioner).chain(),
+        );
+    }
+}
+
+// Set when we disconnected to apply a new conditioner, and must connect again
+#[derive(Resource, Default)]
+struct PendingReconnect(bool);
+
+fn receive_link_conditioner(
+    mut commands: Commands,
+    mut events: EventReader<MessageEvent<SetLinkConditioner>>,
+    mut config: ResMut<ClientConfig>,
+    mut pending_reconnect: ResMut<PendingReconnect>,
+) {
+    for event in events.read() {
+        let conditioner = event.message().0.map(|c| {
+            Conditioner {
+                latency_ms: c.latency_ms,
+                jitter_ms: c.jitter_ms,
+                packet_loss: c.packet_loss,
+            }
+            .build()
+        });
+        // The conditioner is part of the io config, which only gets rebuilt when connecting
+        let NetConfig::Netcode { io, .. } = &mut config.net else {
+            warn!("Link conditioner is only supported with netcode transports");
+            continue;
+        };
+        info!("Reconnecting with link conditioner {:?}", conditioner);
+        io.conditioner = conditioner;
+        commands.disconnect_client();
+        pending_reconnect.0 = true;
+    }
+}
+
+fn reconnect_with_new_conditioner(
+    mut commands: Commands,
+    state: Res<State<NetworkingState>>,
+    mut pending_reconnect: ResMut<PendingReconnect>,
+) {
+    if pending_reconnect.0 && state.get() == &NetworkingState::Disconnected {
+        commands.connect_client();
+        pending_reconnect.0 = false;
+    }
+}
@@ -0,0 +1,133 @@
+use bevy::prelude::*;
+use bevy::time::common_conditions::on_timer;
+use bevy::utils::Duration;
+use lightyear::prelude::client::*;
+
+use crate::client::plugins::{ChatInput, ClientWorldState, TileProjection};
+use crate::protocol::{ObserveRegion, ObserverChannel, ObserverSnapshot};
+use crate::shared::error::{GameError, ReportError};
+
+// Speed of the observer's camera, in world units per second
+const PAN_SPEED: f32 = 400.0;
+// How often the server is told where the observer looks, if it moved
+const REGION_UPDATE_INTERVAL: Duration = Duration::from_millis(500);
+
+// Client-side plugin for observers: clients the server streams delayed snapshots to instead of
+// giving them a player. The camera pans freely with the movement keys.
+pub struct ClientObserverPlugin;
+
+impl Plugin for ClientObserverPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ObserverView>().add_systems(
+            Update,
+            (
+                receive_snapshots,
+                pan_camera,
+                send_observed_region.run_if(on_timer(REGION_UPDATE_INTERVAL)),
+                draw_observed_entities,
+            )
+                .chain(),
+        );
+    }
+}
+
+/// The latest snapshot received, if the server streams this client as an observer
+#[derive(Resource, Default)]
+pub struct ObserverView {
+    pub snapshot: Option<ObserverSnapshot>,
+    // Focus last sent to the server
+    sent_focus: Option<Vec2>,
+}
+
+fn receive_snapshots(
+    mut snapshots: EventReader<MessageEvent<ObserverSnapshot>>,
+    mut view: ResMut<ObserverView>,
+    mut client_world: ResMut<ClientWorldState>,
+) {
+    for snapshot in snapshots.read() {
+        if view.snapshot.is_none() {
+            info!(
+                "Observing the world, {}s behind",
+                snapshot.message().delay_secs
+            );
+            client_world.focus.get_or_insert(Vec2::ZERO);
+        }
+        view.snapshot = Some(snapshot.message().clone());
+    }
+}
+
+fn pan_camera(
+    time: Res<Time>,
+    keypress: Res<ButtonInput<KeyCode>>,
+    chat: Res<ChatInput>,
+    view: Res<ObserverView>,
+    mut client_world: ResMut<ClientWorldState>,
+) {
+    if view.snapshot.is_none() || chat.open {
+        return;
+    }
+    let mut direction = Vec2::ZERO;
+    if keypress.pressed(KeyCode::KeyW) || keypress.pressed(KeyCode::ArrowUp) {
+        direction.y += 1.0;
+    }
+    if keypress.pressed(KeyCode::KeyS) || keypress.pressed(KeyCode::ArrowDown) {
+        direction.y -= 1.0;
+    }
+    if keypress.pressed(KeyCode::KeyA) || keypress.pressed(KeyCode::ArrowLeft) {
+        direction.x -= 1.0;
+    }
+    if keypress.pressed(KeyCode::KeyD) || keypress.pressed(KeyCode::ArrowRight) {
+        direction.x += 1.0;
+    }
+    if direction != Vec2::ZERO {
+        let focus = client_world.focus.get_or_insert(Vec2::ZERO);
+        *focus += direction.normalize() * PAN_SPEED * time.delta_secs();
+    }
+}
+
+fn send_observed_region(
+    mut view: ResMut<ObserverView>,
+    client_world: Res<ClientWorldState>,
+    mut client: ResMut<ConnectionManager>,
+    mut errors: EventWriter<ReportError>,
+) {
+    let Some(focus) = client_world.focus else {
+        return;
+    };
+    if view.snapshot.is_none() || view.sent_focus == Some(focus) {
+        return;
+    }
+    view.sent_focus = Some(focus);
+    client
+        .send_message::<ObserverChannel, _>(&ObserveRegion {
+            center: focus,
+            radius: None,
+        })
+        .unwrap_or_else(|e| {
+            errors.send(ReportError(GameError::send("ObserveRegion", e)));
+        });
+}
+
+fn draw_observed_entities(
+    mut gizmos: Gizmos,
+    view: Res<ObserverView>,
+    projection: Res<TileProjection>,
+) {
+    let Some(snapshot) = &view.snapshot else {
+        return;
+    };
+    for player in &snapshot.players {
+        gizmos.rect_2d(
+            Isometry2d::from_translation(projection.to_screen(player.position)),
+            Vec2::ONE,
+            player.color,
+        );
+    }
+    for npc in &snapshot.npcs {
+        gizmos.circle_2d(
+            Isometry2d::from_translation(projection.to_screen(*npc)),
+            0.5,
+            Color::srgb(0.9, 0.3, 0.3),
+        );
+    }
+}
@@ -0,0 +1,337 @@
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use lightyear::prelude::client::*;
+use lightyear::prelude::ClientId;
+
+use crate::client::plugins::{
+    hovered_tile, ChatInput, CompassMarker, CompassMarkers, TileProjection, Waypoint, WorldCamera,
+};
+use crate::protocol::{Channel1, PlayerId, PlayerPosition};
+use crate::shared::error::{GameError, ReportError};
+use crate::shared::party::{PartyInvite, PartyPing, PartyRequest, PartySync};
+use crate::shared::world_generation::WorldGrid;
+
+// Key pinging the hovered tile for the party
+const PING_KEY: KeyCode = KeyCode::KeyQ;
+// Key accepting the last invite
+const ACCEPT_KEY: KeyCode = KeyCode::KeyY;
+
+// Seconds pings stay on screen, and how far their circle grows in tiles
+const PING_SECS: f32 = 4.0;
+const PING_RADIUS: f32 = 1.5;
+// Seconds the invite prompt stays on screen
+const INVITE_SECS: f32 = 30.0;
+// Distance in pixels between the indicators of offscreen members and the window edge
+const EDGE_MARGIN: f32 = 24.0;
+
+const MEMBER_COLOR: Color = Color::srgb(0.4, 1.0, 0.5);
+const PARTY_WAYPOINT_COLOR: Color = Color::srgb(0.8, 0.5, 1.0);
+const PING_COLOR: Color = Color::srgb(1.0, 0.6, 0.2);
+
+// Prefix of the keys of the compass markers this plugin sets
+const MARKER_PREFIX: &str = "party:";
+const WAYPOINT_MARKER: &str = "party:waypoint";
+
+// Client plugin for parties: shows invites, shares the player's waypoint with their party, pings
+// tiles, and points at the other members on the compass and at the window edge
+pub struct ClientPartyPlugin;
+
+impl Plugin for ClientPartyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ClientParty>()
+            .add_systems(Startup, setup_invite_prompt)
+            .add_systems(
+                Update,
+                (
+                    receive_party,
+                    party_input,
+                    share_waypoint,
+                    update_party_markers,
+                    update_edge_indicators,
+                    draw_party,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// The party the player is in, as last sent by the server
+#[derive(Resource, Default)]
+pub struct ClientParty {
+    pub party: PartySync,
+    // Name of who last invited us, and when
+    invite: Option<(String, f32)>,
+    pings: Vec<(PartyPing, f32)>,
+}
+
+#[derive(Component)]
+struct InvitePrompt;
+
+#[derive(Component)]
+struct EdgeIndicator(ClientId);
+
+fn setup_invite_prompt(mut commands: Commands) {
+    commands
+        .spawn(Node {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(30.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(""),
+                TextFont::from_font_size(20.0),
+                TextColor(MEMBER_COLOR),
+                InvitePrompt,
+            ));
+        });
+}
+
+fn receive_party(
+    time: Res<Time>,
+    mut syncs: EventReader<MessageEvent<PartySync>>,
+    mut invites: EventReader<MessageEvent<PartyInvite>>,
+    mut pings: EventReader<MessageEvent<PartyPing>>,
+    mut party: ResMut<ClientParty>,
+    mut prompt: Query<&mut Text, With<InvitePrompt>>,
+) {
+    let now = time.elapsed_secs();
+    for sync in syncs.read() {
+        party.party = sync.message().clone();
+    }
+    for invite in invites.read() {
+        party.invite = Some((invite.message().from.clone(), now));
+    }
+    for ping in pings.read() {
+        party.pings.push((ping.message().clone(), now));
+    }
+    if party.pings.iter().any(|(_, at)| now - at > PING_SECS) {
+        party.pings.retain(|(_, at)| now - at <= PING_SECS);
+    }
+    if party
+        .invite
+        .as_ref()
+        .is_some_and(|(_, at)| now - at > INVITE_SECS)
+    {
+        party.invite = None;
+    }
+    let text = match &party.invite {
+        Some((from, _)) => format!("{} invites you to their party, press Y to join", from),
+        None => String::new(),
+    };
+    for mut prompt in prompt.iter_mut() {
+        if prompt.0 != text {
+            prompt.0 = text.clone();
+        }
+    }
+}
+
+fn party_input(
+    keypress: Res<ButtonInput<KeyCode>>,
+    chat: Res<ChatInput>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<WorldCamera>>,
+    projection: Res<TileProjection>,
+    mut party: ResMut<ClientParty>,
+    mut client: ResMut<ConnectionManager>,
+    mut errors: EventWriter<ReportError>,
+) {
+    if chat.open {
+        return;
+    }
+    if keypress.just_pressed(ACCEPT_KEY) && party.invite.take().is_some() {
+        client
+            .send_message::<Channel1, _>(&PartyRequest::Accept)
+            .unwrap_or_else(|e| {
+                errors.send(ReportError(GameError::send("PartyRequest", e)));
+            });
+    }
+    if keypress.just_pressed(PING_KEY) && !party.party.members.is_empty() {
+        let (Ok(window), Ok((camera, camera_transform))) =
+            (windows.get_single(), cameras.get_single())
+        else {
+            return;
+        };
+        let Some(tile) = hovered_tile(window, camera, camera_transform, &projection) else {
+            return;
+        };
+        client
+            .send_message::<Channel1, _>(&PartyRequest::Ping { tile })
+            .unwrap_or_else(|e| {
+                errors.send(ReportError(GameError::send("PartyRequest", e)));
+            });
+    }
+}
+
+// Send the player's waypoint to the party when it changes, or when joining one
+fn share_waypoint(
+    waypoint: Res<Waypoint>,
+    party: Res<ClientParty>,
+    mut shared: Local<Option<(i32, i32)>>,
+    mut client: ResMut<ConnectionManager>,
+    mut errors: EventWriter<ReportError>,
+) {
+    if party.party.members.is_empty() {
+        *shared = None;
+        return;
+    }
+    if waypoint.0 == *shared {
+        return;
+    }
+    *shared = waypoint.0;
+    if waypoint.0 == party.party.waypoint {
+        return;
+    }
+    client
+        .send_message::<Channel1, _>(&PartyRequest::SetWaypoint(waypoint.0))
+        .unwrap_or_else(|e| {
+            errors.send(ReportError(GameError::send("PartyRequest", e)));
+        });
+}
+
+fn update_party_markers(
+    party: Res<ClientParty>,
+    members: Query<(&PlayerId, &PlayerPosition), With<Interpolated>>,
+    mut markers: ResMut<CompassMarkers>,
+) {
+    let mut keys = Vec::new();
+    for (player_id, position) in members.iter() {
+        let client_id = player_id.client_id();
+        if !party
+            .party
+            .members
+            .iter()
+            .any(|member| member.client_id == client_id)
+        {
+            continue;
+        }
+        let key = format!("{}{:?}", MARKER_PREFIX, client_id);
+        markers.set(
+            key.clone(),
+            CompassMarker {
+                position: position.0,
+                color: MEMBER_COLOR,
+            },
+        );
+        keys.push(key);
+    }
+    if let Some(tile) = party.party.waypoint {
+        markers.set(
+            WAYPOINT_MARKER,
+            CompassMarker {
+                position: WorldGrid::tile_to_world(tile),
+                color: PARTY_WAYPOINT_COLOR,
+            },
+        );
+        keys.push(WAYPOINT_MARKER.to_string());
+    }
+    markers.retain(MARKER_PREFIX, |key| keys.iter().any(|kept| kept == key));
+}
+
+// Show the name of every member outside the window at the edge, in their direction
+fn update_edge_indicators(
+    mut commands: Commands,
+    party: Res<ClientParty>,
+    projection: Res<TileProjection>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<WorldCamera>>,
+    members: Query<(&PlayerId, &PlayerPosition), With<Interpolated>>,
+    mut indicators: Query<(Entity, &EdgeIndicator, &mut Node)>,
+) {
+    let (Ok(window), Ok((camera, camera_transform))) = (windows.get_single(), cameras.get_single())
+    else {
+        return;
+    };
+    let size = window.size();
+    let center = size * 0.5;
+    let mut offscreen = Vec::new();
+    for (player_id, position) in members.iter() {
+        let client_id = player_id.client_id();
+        let Some(member) = party
+            .party
+            .members
+            .iter()
+            .find(|member| member.client_id == client_id)
+        else {
+            continue;
+        };
+        let Ok(viewport) = camera.world_to_viewport(
+            camera_transform,
+            projection.to_screen(position.0).extend(0.0),
+        ) else {
+            continue;
+        };
+        if viewport.cmpge(Vec2::ZERO).all() && viewport.cmple(size).all() {
+            continue;
+        }
+        // pull the point back toward the center until it is within the margins
+        let half = (center - EDGE_MARGIN).max(Vec2::ONE);
+        let offset = viewport - center;
+        let scale = (offset.abs() / half).max_element().max(1.0);
+        offscreen.push((client_id, &member.name, center + offset / scale));
+    }
+    for (entity, indicator, _) in indicators.iter() {
+        if !offscreen.iter().any(|(id, _, _)| *id == indicator.0) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+    for (client_id, name, at) in offscreen {
+        let (left, top) = (
+            Val::Px(at.x - EDGE_MARGIN),
+            Val::Px(at.y - EDGE_MARGIN * 0.5),
+        );
+        match indicators
+            .iter_mut()
+            .find(|(_, indicator, _)| indicator.0 == client_id)
+        {
+            Some((_, _, mut node)) => {
+                if node.left != left || node.top != top {
+                    node.left = left;
+                    node.top = top;
+                }
+            }
+            None => {
+                commands.spawn((
+                    Node {
+                        position_type: PositionType::Absolute,
+                        left,
+                        top,
+                        padding: UiRect::axes(Val::Px(4.0), Val::Px(2.0)),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgba(0.1, 0.1, 0.2, 0.6)),
+                    Text::new(name.clone()),
+                    TextFont::from_font_size(14.0),
+                    TextColor(MEMBER_COLOR),
+                    EdgeIndicator(client_id),
+                ));
+            }
+        }
+    }
+}
+
+// Draw the shared waypoint, and pings as circles growing until they disappear
+fn draw_party(
+    mut gizmos: Gizmos,
+    time: Res<Time>,
+    party: Res<ClientParty>,
+    projection: Res<TileProjection>,
+) {
+    if let Some(tile) = party.party.waypoint {
+        gizmos.rect_2d(
+            Isometry2d::from_translation(projection.to_screen(WorldGrid::tile_to_world(tile))),
+            Vec2::splat(0.8),
+            PARTY_WAYPOINT_COLOR,
+        );
+    }
+    for (ping, at) in &party.pings {
+        let age = ((time.elapsed_secs() - at) / PING_SECS).clamp(0.0, 1.0);
+        gizmos.circle_2d(
+            Isometry2d::from_translation(projection.to_screen(WorldGrid::tile_to_world(ping.tile))),
+            0.3 + age * PING_RADIUS,
+            PING_COLOR.with_alpha(1.0 - age),
+        );
+    }
+}
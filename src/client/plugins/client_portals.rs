@@ -0,0 +1,326 @@
+use bevy::prelude::*;
+use lightyear::prelude::client::*;
+
+use crate::client::plugins::{ChatInput, ChatLog, TileProjection, GRAVE_COLOR};
+use crate::protocol::{Channel1, PlayerPosition};
+use crate::shared::error::{GameError, ReportError};
+use crate::shared::graves::GraveSites;
+use crate::shared::portals::{DiscoveredPortals, Portal, PortalLink, UsePortal, PORTAL_REACH};
+use crate::shared::world_generation::{tile_distance, WorldGrid};
+
+// Key using the portal the player stands next to
+const USE_PORTAL_KEY: KeyCode = KeyCode::KeyP;
+// Key toggling the map of discovered portals and of the player's graves, and keys picking the
+// hub's destination on it
+const MAP_KEY: KeyCode = KeyCode::KeyM;
+const PREVIOUS_KEY: KeyCode = KeyCode::BracketLeft;
+const NEXT_KEY: KeyCode = KeyCode::BracketRight;
+// Side of the map overlay in pixels, and the tiles one pixel stands for
+const MAP_SIZE: f32 = 240.0;
+const TILES_PER_PIXEL: f32 = 4.0;
+const DOT_SIZE: f32 = 6.0;
+// Size of a portal relative to a tile
+const PORTAL_SIZE: f32 = 0.9;
+
+// Client plugin drawing portals, using them, and showing a map of the discovered ones
+pub struct ClientPortalsPlugin;
+
+impl Plugin for ClientPortalsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<KnownPortals>()
+            .add_systems(Startup, setup_portal_map)
+            .add_systems(
+                Update,
+                (
+                    receive_discoveries,
+                    portal_input,
+                    update_portal_map,
+                    (spawn_portal_sprites, place_portals).chain(),
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// The portals the server told us we discovered, and the hub destination picked on the map
+#[derive(Resource, Default)]
+pub struct KnownPortals {
+    pub portals: Vec<Portal>,
+    pub selected: usize,
+}
+
+impl KnownPortals {
+    // Where the hub would take us: any discovered portal but the hub itself
+    fn destinations(&self) -> impl Iterator<Item = &Portal> {
+        self.portals
+            .iter()
+            .filter(|portal| portal.link != PortalLink::Hub)
+    }
+
+    pub fn selected_destination(&self) -> Option<(i32, i32)> {
+        self.destinations()
+            .nth(self.selected)
+            .map(|portal| portal.tile)
+    }
+}
+
+#[derive(Component)]
+struct PortalMap;
+
+#[derive(Component)]
+struct PortalMapArea;
+
+#[derive(Component)]
+struct PortalMapText;
+
+fn portal_color(link: PortalLink) -> Color {
+    match link {
+        PortalLink::Hub => Color::srgb(0.9, 0.6, 1.0),
+        PortalLink::ToHub | PortalLink::Pair(_) => Color::srgb(0.5, 0.3, 0.9),
+        PortalLink::Unlinked => Color::srgb(0.4, 0.4, 0.45),
+    }
+}
+
+fn setup_portal_map(mut commands: Commands) {
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                left: Val::Px(10.0),
+                top: Val::Px(60.0),
+                column_gap: Val::Px(8.0),
+                padding: UiRect::all(Val::Px(6.0)),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.6)),
+            Visibility::Hidden,
+            PortalMap,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Node {
+                    width: Val::Px(MAP_SIZE),
+                    height: Val::Px(MAP_SIZE),
+                    border: UiRect::all(Val::Px(1.0)),
+                    ..default()
+                },
+                BorderColor(Color::srgb(0.4, 0.4, 0.4)),
+                PortalMapArea,
+            ));
+            parent.spawn((
+                Text::new(""),
+                TextFont::from_font_size(14.0),
+                TextColor(Color::WHITE),
+                PortalMapText,
+            ));
+        });
+}
+
+fn receive_discoveries(
+    mut events: EventReader<MessageEvent<DiscoveredPortals>>,
+    mut known: ResMut<KnownPortals>,
+) {
+    if let Some(event) = events.read().last() {
+        let selected = known.selected_destination();
+        known.portals = event.message().0.clone();
+        // keep the same destination picked if it's still there
+        known.selected = selected
+            .and_then(|tile| known.destinations().position(|portal| portal.tile == tile))
+            .unwrap_or(0);
+    }
+}
+
+fn portal_input(
+    keypress: Res<ButtonInput<KeyCode>>,
+    chat: Res<ChatInput>,
+    mut log: ResMut<ChatLog>,
+    mut known: ResMut<KnownPortals>,
+    mut map: Query<&mut Visibility, With<PortalMap>>,
+    player: Query<&PlayerPosition, With<Predicted>>,
+    portals: Query<&Portal>,
+    mut client: ResMut<ConnectionManager>,
+    mut errors: EventWriter<ReportError>,
+) {
+    if chat.open {
+        return;
+    }
+    if keypress.just_pressed(MAP_KEY) {
+        for mut visibility in map.iter_mut() {
+            visibility.toggle_visible_hidden();
+        }
+    }
+    let destinations = known.destinations().count();
+    if destinations > 0 {
+        if keypress.just_pressed(NEXT_KEY) {
+            known.selected = (known.selected + 1) % destinations;
+        }
+        if keypress.just_pressed(PREVIOUS_KEY) {
+            known.selected = (known.selected + destinations - 1) % destinations;
+        }
+    }
+
+    if !keypress.just_pressed(USE_PORTAL_KEY) {
+        return;
+    }
+    let Ok(position) = player.get_single() else {
+        return;
+    };
+    let player_tile = WorldGrid::world_to_tile(position.0);
+    let Some(portal) = portals
+        .iter()
+        .filter(|portal| tile_distance(portal.tile, player_tile) <= PORTAL_REACH)
+        .min_by_key(|portal| tile_distance(portal.tile, player_tile))
+    else {
+        log.push("There is no portal here".to_string());
+        return;
+    };
+    let destination = match portal.link {
+        PortalLink::Hub => match known.selected_destination() {
+            Some(destination) => Some(destination),
+            None => {
+                log.push("Discover other portals to travel from the hub".to_string());
+                return;
+            }
+        },
+        _ => None,
+    };
+    client
+        .send_message::<Channel1, _>(&UsePortal {
+            portal: portal.tile,
+            destination,
+        })
+        .unwrap_or_else(|e| {
+            errors.send(ReportError(GameError::send("UsePortal", e)));
+        });
+}
+
+// Redraw the map around the player: the player in the middle, one dot per discovered portal and
+// per grave the player left
+fn update_portal_map(
+    mut commands: Commands,
+    known: Res<KnownPortals>,
+    map: Query<Ref<Visibility>, With<PortalMap>>,
+    areas: Query<Entity, With<PortalMapArea>>,
+    mut texts: Query<&mut Text, With<PortalMapText>>,
+    player: Query<(Ref<PlayerPosition>, Option<Ref<GraveSites>>), With<Predicted>>,
+) {
+    let Ok((position, graves)) = player.get_single() else {
+        return;
+    };
+    if map
+        .iter()
+        .all(|visibility| *visibility == Visibility::Hidden)
+    {
+        return;
+    }
+    let opened = map.iter().any(|visibility| visibility.is_changed());
+    let graves_changed = graves.as_ref().is_some_and(|graves| graves.is_changed());
+    if !opened && !known.is_changed() && !position.is_changed() && !graves_changed {
+        return;
+    }
+    let player_tile = WorldGrid::world_to_tile(position.0);
+    let graves: &[(i32, i32)] = graves.as_ref().map_or(&[], |graves| &graves.0);
+    let offset_of = |tile: (i32, i32)| {
+        Vec2::new(
+            (tile.0 - player_tile.0) as f32,
+            (tile.1 - player_tile.1) as f32,
+        )
+    };
+
+    let dot = |offset: Vec2, color: Color| {
+        let center = MAP_SIZE / 2.0;
+        let max = MAP_SIZE - DOT_SIZE;
+        (
+            Node {
+                position_type: PositionType::Absolute,
+                left: Val::Px(
+                    (center + offset.x / TILES_PER_PIXEL - DOT_SIZE / 2.0).clamp(0.0, max),
+                ),
+                // screen y grows downwards
+                top: Val::Px(
+                    (center - offset.y / TILES_PER_PIXEL - DOT_SIZE / 2.0).clamp(0.0, max),
+                ),
+                width: Val::Px(DOT_SIZE),
+                height: Val::Px(DOT_SIZE),
+                ..default()
+            },
+            BackgroundColor(color),
+        )
+    };
+    for area in areas.iter() {
+        commands
+            .entity(area)
+            .despawn_descendants()
+            .with_children(|parent| {
+                for portal in &known.portals {
+                    parent.spawn(dot(offset_of(portal.tile), portal_color(portal.link)));
+                }
+                for grave in graves {
+                    parent.spawn(dot(offset_of(*grave), GRAVE_COLOR));
+                }
+                parent.spawn(dot(Vec2::ZERO, Color::WHITE));
+            });
+    }
+
+    let mut lines = vec!["Portals - [ and ] pick the hub's destination, P travels".to_string()];
+    let selected = known.selected_destination();
+    for portal in &known.portals {
+        let marker = if Some(portal.tile) == selected {
+            ">"
+        } else {
+            " "
+        };
+        let kind = match portal.link {
+            PortalLink::Hub => "hub",
+            _ => "portal",
+        };
+        lines.push(format!(
+            "{} {} at ({}, {}), {} tiles away",
+            marker,
+            kind,
+            portal.tile.0,
+            portal.tile.1,
+            tile_distance(portal.tile, player_tile)
+        ));
+    }
+    for grave in graves {
+        lines.push(format!(
+            "  your grave at ({}, {}), {} tiles away",
+            grave.0,
+            grave.1,
+            tile_distance(*grave, player_tile)
+        ));
+    }
+    for mut text in texts.iter_mut() {
+        text.0 = lines.join("\n");
+    }
+}
+
+fn spawn_portal_sprites(mut commands: Commands, portals: Query<(Entity, &Portal), Added<Portal>>) {
+    for (entity, portal) in portals.iter() {
+        commands.entity(entity).insert((
+            Sprite {
+                custom_size: Some(Vec2::splat(PORTAL_SIZE)),
+                color: portal_color(portal.link),
+                ..default()
+            },
+            Transform::default(),
+        ));
+    }
+}
+
+// Position portals on screen according to the current projection, recolouring relinked ones
+fn place_portals(
+    projection: Res<TileProjection>,
+    mut portals: Query<(Ref<Portal>, &mut Transform, &mut Sprite)>,
+) {
+    for (portal, mut transform, mut sprite) in portals.iter_mut() {
+        if !portal.is_changed() && !projection.is_changed() {
+            continue;
+        }
+        sprite.color = portal_color(portal.link);
+        let world = WorldGrid::tile_to_world(portal.tile);
+        let screen = projection.to_screen(world);
+        transform.translation = screen.extend(0.4 + projection.depth(world));
+    }
+}
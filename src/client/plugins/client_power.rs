@@ -0,0 +1,61 @@
+use bevy::prelude::*;
+
+use crate::client::plugins::TileProjection;
+use crate::shared::power::PowerState;
+use crate::shared::world_generation::WorldGrid;
+
+const POWERED_COLOR: Color = Color::srgb(1.0, 0.9, 0.3);
+const UNPOWERED_COLOR: Color = Color::srgb(0.3, 0.3, 0.3);
+// Size of the light shown on assemblers, relative to a tile
+const LIGHT_SIZE: f32 = 0.2;
+// Lights are drawn over the assembler they belong to
+const LIGHT_Z: f32 = 0.4;
+
+// Client plugin lighting up assemblers while their network powers them
+pub struct ClientPowerPlugin;
+
+impl Plugin for ClientPowerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (spawn_power_lights, update_power_lights).chain());
+    }
+}
+
+fn light_color(powered: bool) -> Color {
+    if powered {
+        POWERED_COLOR
+    } else {
+        UNPOWERED_COLOR
+    }
+}
+
+fn spawn_power_lights(
+    mut commands: Commands,
+    states: Query<(Entity, &PowerState), Added<PowerState>>,
+) {
+    for (entity, state) in states.iter() {
+        commands.entity(entity).insert((
+            Sprite {
+                custom_size: Some(Vec2::splat(LIGHT_SIZE * WorldGrid::TILE_SIZE)),
+                color: light_color(state.powered),
+                ..default()
+            },
+            Transform::default(),
+        ));
+    }
+}
+
+// Color lights after their power state, and place them according to the current projection
+fn update_power_lights(
+    projection: Res<TileProjection>,
+    mut lights: Query<(Ref<PowerState>, &mut Sprite, &mut Transform)>,
+) {
+    for (state, mut sprite, mut transform) in lights.iter_mut() {
+        if !state.is_changed() && !projection.is_changed() {
+            continue;
+        }
+        sprite.color = light_color(state.powered);
+        let world = WorldGrid::tile_to_world(state.tile);
+        let screen = projection.to_screen(world);
+        transform.translation = screen.extend(LIGHT_Z + projection.depth(world));
+    }
+}
@@ -0,0 +1,56 @@
+use bevy::prelude::*;
+use lightyear::prelude::client::*;
+
+use crate::protocol::{PlayerId, QueueStatus};
+
+// Client plugin telling the player where they stand in the queue of a full server
+pub struct ClientQueuePlugin;
+
+impl Plugin for ClientQueuePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_queue_hud)
+            .add_systems(Update, update_queue_hud);
+    }
+}
+
+#[derive(Component)]
+struct QueueText;
+
+fn setup_queue_hud(mut commands: Commands) {
+    commands.spawn((
+        Text::new(""),
+        TextFont::from_font_size(24.0),
+        TextColor(Color::WHITE),
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Percent(35.0),
+            top: Val::Percent(45.0),
+            ..default()
+        },
+        // shown over the loading screen, which waits for the server's handshake
+        GlobalZIndex(101),
+        QueueText,
+    ));
+}
+
+fn update_queue_hud(
+    mut events: EventReader<MessageEvent<QueueStatus>>,
+    admitted: Query<(), (With<PlayerId>, Added<Predicted>)>,
+    mut texts: Query<&mut Text, With<QueueText>>,
+) {
+    let text = if let Some(event) = events.read().last() {
+        let status = event.message();
+        format!(
+            "Server full - you are {} of {} in the queue",
+            status.position, status.length
+        )
+    } else if !admitted.is_empty() {
+        // our player got spawned: we're in
+        String::new()
+    } else {
+        return;
+    };
+    for mut hud in texts.iter_mut() {
+        hud.0 = text.clone();
+    }
+}
@@ -0,0 +1,132 @@
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use lightyear::prelude::client::*;
+
+use crate::client::plugins::{hovered_tile, ChatInput, TileProjection, WorldCamera};
+use crate::protocol::{Channel1, PlayerPosition};
+use crate::shared::error::{GameError, ReportError};
+use crate::shared::rails::{Cart, PlaceCart, Riding};
+use crate::shared::world_generation::WorldGrid;
+
+// Shift and the rail key put a cart on the hovered rail
+const CART_KEY: KeyCode = KeyCode::KeyZ;
+const CART_MODIFIER: KeyCode = KeyCode::ShiftLeft;
+
+const CART_COLOR: Color = Color::srgb(0.4, 0.38, 0.36);
+// Size of a cart relative to a tile
+const CART_SIZE: f32 = 0.75;
+// Carts are drawn under their rider
+const CART_Z: f32 = 0.35;
+
+// Client plugin putting carts on rails and drawing them, standing or ridden
+pub struct ClientRailsPlugin;
+
+impl Plugin for ClientRailsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                place_cart_input,
+                spawn_cart_sprites,
+                place_carts,
+                spawn_ridden_carts,
+                follow_riders,
+            )
+                .chain(),
+        );
+    }
+}
+
+// Cart drawn under a player riding it
+#[derive(Component)]
+struct RiddenCart(Entity);
+
+fn cart_sprite() -> Sprite {
+    Sprite {
+        custom_size: Some(Vec2::splat(CART_SIZE * WorldGrid::TILE_SIZE)),
+        color: CART_COLOR,
+        ..default()
+    }
+}
+
+fn place_cart_input(
+    keypress: Res<ButtonInput<KeyCode>>,
+    chat: Res<ChatInput>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<WorldCamera>>,
+    projection: Res<TileProjection>,
+    mut client: ResMut<ConnectionManager>,
+    mut errors: EventWriter<ReportError>,
+) {
+    if chat.open || !keypress.pressed(CART_MODIFIER) || !keypress.just_pressed(CART_KEY) {
+        return;
+    }
+    let (Ok(window), Ok((camera, camera_transform))) = (windows.get_single(), cameras.get_single())
+    else {
+        return;
+    };
+    let Some(tile) = hovered_tile(window, camera, camera_transform, &projection) else {
+        return;
+    };
+    client
+        .send_message::<Channel1, _>(&PlaceCart { tile })
+        .unwrap_or_else(|e| {
+            errors.send(ReportError(GameError::send("PlaceCart", e)));
+        });
+}
+
+fn spawn_cart_sprites(mut commands: Commands, carts: Query<Entity, Added<Cart>>) {
+    for entity in carts.iter() {
+        commands
+            .entity(entity)
+            .insert((cart_sprite(), Transform::default()));
+    }
+}
+
+// Position standing carts on screen according to the current projection
+fn place_carts(
+    projection: Res<TileProjection>,
+    mut carts: Query<(Ref<Cart>, &mut Transform), With<Sprite>>,
+) {
+    for (cart, mut transform) in carts.iter_mut() {
+        if !cart.is_changed() && !projection.is_changed() {
+            continue;
+        }
+        let world = WorldGrid::tile_to_world(cart.tile);
+        let screen = projection.to_screen(world);
+        transform.translation = screen.extend(CART_Z + projection.depth(world));
+    }
+}
+
+// Riders are drawn as predicted for the player, interpolated for everyone else
+type Drawn = Or<(With<Predicted>, With<Interpolated>)>;
+
+fn spawn_ridden_carts(
+    mut commands: Commands,
+    riders: Query<Entity, (Added<Riding>, Drawn)>,
+    carts: Query<(Entity, &RiddenCart)>,
+) {
+    for rider in riders.iter() {
+        if carts.iter().any(|(_, cart)| cart.0 == rider) {
+            continue;
+        }
+        commands.spawn((cart_sprite(), Transform::default(), RiddenCart(rider)));
+    }
+}
+
+// Keep ridden carts under their riders, and take them away once they get off
+fn follow_riders(
+    mut commands: Commands,
+    projection: Res<TileProjection>,
+    riders: Query<&PlayerPosition, (With<Riding>, Drawn)>,
+    mut carts: Query<(Entity, &RiddenCart, &mut Transform)>,
+) {
+    for (entity, cart, mut transform) in carts.iter_mut() {
+        let Ok(position) = riders.get(cart.0) else {
+            commands.entity(entity).despawn();
+            continue;
+        };
+        let screen = projection.to_screen(position.0);
+        transform.translation = screen.extend(CART_Z + projection.depth(position.0));
+    }
+}
@@ -0,0 +1,162 @@
+use bevy::prelude::*;
+use lightyear::prelude::client::*;
+use std::collections::{HashMap, HashSet};
+
+use crate::protocol::{Channel1, PlayerPosition};
+use crate::shared::error::{GameError, ReportError};
+use crate::shared::regions::{RegionCoord, RegionNameRequest, RegionNameSync};
+use crate::shared::world_generation::{Chunk, WorldConfig, WorldGrid};
+
+// Seconds the banner stays on screen when entering a region, the last of which it fades out
+const BANNER_SECS: f32 = 4.0;
+const BANNER_FADE_SECS: f32 = 1.0;
+const BANNER_COLOR: Color = Color::srgb(1.0, 0.95, 0.8);
+
+// Client plugin asking the server for the names of the regions around the player, and showing
+// the name of each region the player enters
+pub struct ClientRegionsPlugin;
+
+impl Plugin for ClientRegionsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RegionNames>()
+            .add_systems(Startup, setup_region_banner)
+            .add_systems(
+                Update,
+                (
+                    receive_region_names,
+                    request_region_names,
+                    announce_region,
+                    fade_region_banner,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Names of the regions received from the server
+#[derive(Resource, Default)]
+pub struct RegionNames {
+    names: HashMap<RegionCoord, String>,
+    requested: HashSet<RegionCoord>,
+    // Region the player was last seen in, and the last one whose name was shown
+    current: Option<RegionCoord>,
+    announced: Option<RegionCoord>,
+}
+
+impl RegionNames {
+    pub fn name(&self, region: RegionCoord) -> Option<&str> {
+        self.names.get(&region).map(String::as_str)
+    }
+
+    /// Region the player is in
+    pub fn current(&self) -> Option<RegionCoord> {
+        self.current
+    }
+}
+
+#[derive(Component)]
+struct RegionBanner {
+    shown_at: f32,
+}
+
+fn setup_region_banner(mut commands: Commands) {
+    commands
+        .spawn(Node {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(20.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(""),
+                TextFont::from_font_size(32.0),
+                TextColor(BANNER_COLOR.with_alpha(0.0)),
+                RegionBanner {
+                    shown_at: f32::NEG_INFINITY,
+                },
+            ));
+        });
+}
+
+fn receive_region_names(
+    mut events: EventReader<MessageEvent<RegionNameSync>>,
+    mut regions: ResMut<RegionNames>,
+) {
+    for event in events.read() {
+        let sync = event.message();
+        regions.names.insert(sync.region, sync.name.clone());
+    }
+}
+
+// Ask for the names of the region the player is in and of the regions of loaded chunks, once
+fn request_region_names(
+    mut regions: ResMut<RegionNames>,
+    mut client: ResMut<ConnectionManager>,
+    world_config: Res<WorldConfig>,
+    player: Query<&PlayerPosition, With<Predicted>>,
+    chunks: Query<&Chunk, Added<Chunk>>,
+    mut errors: EventWriter<ReportError>,
+) {
+    if let Ok(position) = player.get_single() {
+        let tile = WorldGrid::world_to_tile(position.0);
+        let region = RegionCoord::of_chunk(world_config.grid().tile_to_chunk(tile));
+        if regions.current != Some(region) {
+            regions.current = Some(region);
+        }
+    }
+    let wanted: Vec<RegionCoord> = regions
+        .current
+        .into_iter()
+        .chain(
+            chunks
+                .iter()
+                .map(|chunk| RegionCoord::of_chunk(chunk.coord)),
+        )
+        .collect();
+    for region in wanted {
+        if regions.requested.contains(&region) {
+            continue;
+        }
+        regions.requested.insert(region);
+        client
+            .send_message::<Channel1, _>(&RegionNameRequest(region))
+            .unwrap_or_else(|e| {
+                errors.send(ReportError(GameError::send("RegionNameRequest", e)));
+            });
+    }
+}
+
+// Show the name of the region the player just entered, as soon as it's known
+fn announce_region(
+    time: Res<Time>,
+    mut regions: ResMut<RegionNames>,
+    mut banner: Query<(&mut Text, &mut RegionBanner)>,
+) {
+    let Some(current) = regions.current else {
+        return;
+    };
+    if regions.announced == Some(current) {
+        return;
+    }
+    let Some(name) = regions.name(current).map(str::to_string) else {
+        return;
+    };
+    regions.announced = Some(current);
+    info!("Entering {}", name);
+    for (mut text, mut banner) in banner.iter_mut() {
+        text.0 = name.clone();
+        banner.shown_at = time.elapsed_secs();
+    }
+}
+
+fn fade_region_banner(time: Res<Time>, mut banner: Query<(&mut TextColor, &RegionBanner)>) {
+    for (mut color, banner) in banner.iter_mut() {
+        let left = BANNER_SECS - (time.elapsed_secs() - banner.shown_at);
+        let alpha = (left / BANNER_FADE_SECS).clamp(0.0, 1.0);
+        if color.0.alpha() != alpha {
+            color.0.set_alpha(alpha);
+        }
+    }
+}
@@ -1,8 +1,16 @@
 use bevy::prelude::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 
+use crate::client::plugins::{ClientWorldState, DoorSprite};
 use crate::protocol::PlayerPosition;
-use crate::shared::world_generation::{Chunk, ChunkCoord, ResourceType, TileType, WorldConfig};
+use crate::settings_common::Settings;
+use crate::shared::day_night::DayPhase;
+use crate::shared::height_map::HeightMap;
+use crate::shared::seasons::{CurrentSeason, Season};
+use crate::shared::world_generation::{
+    elevation_level, is_cliff, BiomeType, Chunk, ChunkCoord, Decoration, Overlay, ResourceType,
+    TileType, WorldConfig, WorldGrid, WorldState, ELEVATION_STEP,
+};
 use lightyear::prelude::client::Predicted;
 
 // Plugin to handle rendering of the world tiles
@@ -15,13 +23,20 @@ impl Plugin for ClientWorldRenderPlugin {
             rendered_chunks: HashMap::new(),
             tile_sprites: None,
         })
-        .add_systems(Startup, setup_tile_sprites)
+        .init_resource::<TileProjection>()
+        .init_resource::<ChunkRenderCache>()
+        .init_resource::<ChunkRenderQueue>()
+        .add_systems(Startup, (setup_tile_sprites, load_projection_setting))
         .add_systems(
             Update,
             (
                 render_new_chunks,
-                update_visible_chunks.after(render_new_chunks),
+                build_pending_chunks.after(render_new_chunks),
+                update_visible_chunks.after(build_pending_chunks),
                 camera_follow_player,
+                fade_unavailable_resources.after(build_pending_chunks),
+                apply_season_overlay.after(build_pending_chunks),
+                toggle_projection.before(render_new_chunks),
             ),
         );
     }
@@ -34,17 +49,243 @@ pub struct TileRenderState {
     pub tile_sprites: Option<TileSprites>,            // Sprites for different tile types
 }
 
+// Alpha of resource indicators outside of their time-of-day schedule
+const UNAVAILABLE_RESOURCE_ALPHA: f32 = 0.15;
+
+// Z offsets of the layers drawn over a tile's ground sprite
+const CLIFF_Z: f32 = 0.03;
+const DECORATION_Z: f32 = 0.05;
+const RESOURCE_Z: f32 = 0.1;
+const OVERLAY_Z: f32 = 0.2;
+
+// World units shown per screen pixel
+const CAMERA_ZOOM: f32 = 2.0;
+
+// Key switching between the top-down and isometric projections
+const PROJECTION_TOGGLE_KEY: KeyCode = KeyCode::F2;
+
+/// How world tile coordinates are projected on screen. Only affects rendering: tile data, movement
+/// and everything on the server keep using world coordinates.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TileProjection {
+    #[default]
+    TopDown,
+    // 2:1 isometric, tiles drawn as diamonds
+    Isometric,
+}
+
+impl TileProjection {
+    pub fn to_screen(&self, world: Vec2) -> Vec2 {
+        match self {
+            TileProjection::TopDown => world,
+            TileProjection::Isometric => {
+                Vec2::new((world.x - world.y) * 0.5, (world.x + world.y) * 0.25)
+            }
+        }
+    }
+
+    pub fn to_world(&self, screen: Vec2) -> Vec2 {
+        match self {
+            TileProjection::TopDown => screen,
+            TileProjection::Isometric => {
+                Vec2::new(screen.x + 2.0 * screen.y, 2.0 * screen.y - screen.x)
+            }
+        }
+    }
+
+    /// Small z offset so that objects further back are drawn first
+    pub fn depth(&self, world: Vec2) -> f32 {
+        match self {
+            TileProjection::TopDown => -world.y * 0.0001,
+            TileProjection::Isometric => -(world.x + world.y) * 0.0001,
+        }
+    }
+
+    // Transform of a chunk's render parent, and rotation of the node holding its tiles.
+    // Rotating the grid by 45° and then squashing it vertically turns squares into iso diamonds.
+    pub fn chunk_transforms(&self, origin: Vec2) -> (Transform, Quat) {
+        let screen = self.to_screen(origin);
+        let transform = Transform::from_xyz(screen.x, screen.y, 0.0);
+        match self {
+            TileProjection::TopDown => (transform, Quat::IDENTITY),
+            TileProjection::Isometric => (
+                transform.with_scale(Vec3::new(
+                    std::f32::consts::FRAC_1_SQRT_2,
+                    std::f32::consts::FRAC_1_SQRT_2 * 0.5,
+                    1.0,
+                )),
+                Quat::from_rotation_z(std::f32::consts::FRAC_PI_4),
+            ),
+        }
+    }
+}
+
+// Number of tiles built per frame, so that rendering new chunks doesn't cause frame spikes
+const TILES_PER_FRAME: usize = 2048;
+
+// A chunk whose tiles are being built over several frames
+struct ChunkRenderJob {
+    coord: ChunkCoord,
+    chunk: Entity,
+    parent: Entity,
+    tiles_parent: Entity,
+    placeholder: Option<Entity>,
+    // Render being replaced once this one is complete
+    previous: Option<Entity>,
+    next_row: usize,
+}
+
+// Chunks waiting to be built, oldest first
+#[derive(Resource, Default)]
+pub struct ChunkRenderQueue {
+    jobs: VecDeque<ChunkRenderJob>,
+}
+
+impl ChunkRenderQueue {
+    pub fn len(&self) -> usize {
+        self.jobs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+}
+
+// Approximate memory used by one rendered tile: sprite entities and their render data
+const RENDERED_TILE_BYTES: usize = 512;
+// Memory budget for the renders of chunks that are no longer loaded
+const RENDER_CACHE_BUDGET: usize = 32 * 1024 * 1024;
+
+/// Render parent of a chunk
+#[derive(Component, Clone, Copy, Debug)]
+pub struct ChunkRender {
+    pub coord: ChunkCoord,
+    // Checksum of the chunk when it was rendered
+    pub version: u64,
+    pub projection: TileProjection,
+    pub tiles: usize,
+}
+
+struct CachedRender {
+    entity: Entity,
+    render: ChunkRender,
+    last_used: u64,
+}
+
+/// Hidden renders of recently unloaded chunks, reused when the same version of the chunk
+/// is loaded again. Bounded by an estimate of the memory used, least recently used go first.
+#[derive(Resource, Default)]
+pub struct ChunkRenderCache {
+    entries: HashMap<ChunkCoord, CachedRender>,
+    used_bytes: usize,
+    clock: u64,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl ChunkRenderCache {
+    fn bytes(render: &ChunkRender) -> usize {
+        render.tiles * RENDERED_TILE_BYTES
+    }
+
+    /// Take the cached render of a chunk, if it matches the chunk version and projection
+    pub fn take(
+        &mut self,
+        coord: ChunkCoord,
+        version: u64,
+        projection: TileProjection,
+    ) -> Option<Entity> {
+        let matches = self.entries.get(&coord).is_some_and(|cached| {
+            cached.render.version == version && cached.render.projection == projection
+        });
+        if !matches {
+            self.misses += 1;
+            return None;
+        }
+        self.hits += 1;
+        let cached = self.entries.remove(&coord)?;
+        self.used_bytes -= Self::bytes(&cached.render);
+        Some(cached.entity)
+    }
+
+    /// Cache a render, returning the entities evicted to stay within budget
+    pub fn insert(&mut self, entity: Entity, render: &ChunkRender) -> Vec<Entity> {
+        let mut evicted = Vec::new();
+        self.clock += 1;
+        if let Some(previous) = self.entries.remove(&render.coord) {
+            self.used_bytes -= Self::bytes(&previous.render);
+            evicted.push(previous.entity);
+        }
+        self.used_bytes += Self::bytes(render);
+        self.entries.insert(
+            render.coord,
+            CachedRender {
+                entity,
+                render: *render,
+                last_used: self.clock,
+            },
+        );
+        while self.used_bytes > RENDER_CACHE_BUDGET {
+            let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, cached)| cached.last_used)
+                .map(|(coord, _)| *coord)
+            else {
+                break;
+            };
+            if let Some(cached) = self.entries.remove(&oldest) {
+                self.used_bytes -= Self::bytes(&cached.render);
+                evicted.push(cached.entity);
+            }
+        }
+        evicted
+    }
+}
+
+// Base sprite of a tile
+#[derive(Component)]
+pub struct TileSprite(pub TileType);
+
+// Resource indicator drawn on top of a tile
+#[derive(Component)]
+pub struct ResourceSprite(pub ResourceType);
+
+// Marker for the camera that looks at the world tiles
+#[derive(Component)]
+pub struct WorldCamera;
+
 // Sprites for rendering different tile types
 #[derive(Resource, Clone)]
 pub struct TileSprites {
     pub grass: Handle<Image>,
-    pub water: Handle<Image>,
+    pub shallow_water: Handle<Image>,
+    pub deep_water: Handle<Image>,
     pub sand: Handle<Image>,
     pub stone: Handle<Image>,
     pub forest: Handle<Image>,
     pub mountain: Handle<Image>,
     pub snow: Handle<Image>,
 
+    // Decoration and overlay images
+    pub flowers: Handle<Image>,
+    pub grass_tufts: Handle<Image>,
+    pub pebbles: Handle<Image>,
+    pub rocks: Handle<Image>,
+    pub shells: Handle<Image>,
+    pub path: Handle<Image>,
+    pub stairs: Handle<Image>,
+    pub furnace: Handle<Image>,
+    pub door: Handle<Image>,
+    pub gate: Handle<Image>,
+    pub rail: Handle<Image>,
+    pub chest: Handle<Image>,
+    pub inserter: Handle<Image>,
+    pub generator: Handle<Image>,
+    pub wire: Handle<Image>,
+    pub assembler: Handle<Image>,
+    pub snow_cover: Handle<Image>,
+
     // Resource images
     pub iron: Handle<Image>,
     pub copper: Handle<Image>,
@@ -52,6 +293,111 @@ pub struct TileSprites {
     pub gold: Handle<Image>,
     pub tree: Handle<Image>,
     pub resource_stone: Handle<Image>,
+    pub fish: Handle<Image>,
+
+    // Damaged resource node images (chopped trees, cracked stone...), by resource and damage tier
+    pub damaged: HashMap<(ResourceType, u8), Handle<Image>>,
+}
+
+impl TileSprites {
+    pub fn decoration(&self, decoration: Decoration) -> Option<&Handle<Image>> {
+        match decoration {
+            Decoration::Flowers => Some(&self.flowers),
+            Decoration::GrassTufts => Some(&self.grass_tufts),
+            Decoration::Pebbles => Some(&self.pebbles),
+            Decoration::Rocks => Some(&self.rocks),
+            Decoration::Shells => Some(&self.shells),
+            Decoration::Path => Some(&self.path),
+            Decoration::Stairs => Some(&self.stairs),
+            Decoration::Furnace => Some(&self.furnace),
+            Decoration::Door { .. } => Some(&self.door),
+            Decoration::Gate { .. } => Some(&self.gate),
+            Decoration::Rail => Some(&self.rail),
+            Decoration::Chest => Some(&self.chest),
+            Decoration::Inserter { .. } => Some(&self.inserter),
+            Decoration::Generator => Some(&self.generator),
+            Decoration::Wire => Some(&self.wire),
+            Decoration::Assembler => Some(&self.assembler),
+            Decoration::None => None,
+        }
+    }
+
+    pub fn overlay(&self, overlay: Overlay) -> Option<&Handle<Image>> {
+        match overlay {
+            Overlay::Snow => Some(&self.snow_cover),
+            Overlay::None => None,
+        }
+    }
+
+    // Image of a resource node in the given damage state
+    pub fn resource(&self, resource: ResourceType, tier: u8) -> Option<&Handle<Image>> {
+        if tier > 0 {
+            if let Some(image) = self.damaged.get(&(resource, tier)) {
+                return Some(image);
+            }
+        }
+        match resource {
+            ResourceType::Iron => Some(&self.iron),
+            ResourceType::Copper => Some(&self.copper),
+            ResourceType::Coal => Some(&self.coal),
+            ResourceType::Gold => Some(&self.gold),
+            ResourceType::Tree => Some(&self.tree),
+            ResourceType::Stone => Some(&self.resource_stone),
+            ResourceType::Fish => Some(&self.fish),
+            ResourceType::None => None,
+        }
+    }
+
+    /// Image of a sprite by the name resource packs refer to it with: the name of its field, or
+    /// e.g. `tree_damaged_1` for damaged resource nodes
+    pub fn named(&self, name: &str) -> Option<&Handle<Image>> {
+        let image = match name {
+            "grass" => &self.grass,
+            "shallow_water" => &self.shallow_water,
+            "deep_water" => &self.deep_water,
+            "sand" => &self.sand,
+            "stone" => &self.stone,
+            "forest" => &self.forest,
+            "mountain" => &self.mountain,
+            "snow" => &self.snow,
+            "flowers" => &self.flowers,
+            "grass_tufts" => &self.grass_tufts,
+            "pebbles" => &self.pebbles,
+            "rocks" => &self.rocks,
+            "shells" => &self.shells,
+            "path" => &self.path,
+            "stairs" => &self.stairs,
+            "furnace" => &self.furnace,
+            "door" => &self.door,
+            "gate" => &self.gate,
+            "rail" => &self.rail,
+            "chest" => &self.chest,
+            "inserter" => &self.inserter,
+            "generator" => &self.generator,
+            "wire" => &self.wire,
+            "assembler" => &self.assembler,
+            "snow_cover" => &self.snow_cover,
+            "iron" => &self.iron,
+            "copper" => &self.copper,
+            "coal" => &self.coal,
+            "gold" => &self.gold,
+            "tree" => &self.tree,
+            "resource_stone" => &self.resource_stone,
+            "fish" => &self.fish,
+            _ => {
+                let (resource, tier) = name.rsplit_once("_damaged_")?;
+                let tier: u8 = tier.parse().ok()?;
+                return self
+                    .damaged
+                    .iter()
+                    .find(|((kind, damage), _)| {
+                        *damage == tier && format!("{:?}", kind).eq_ignore_ascii_case(resource)
+                    })
+                    .map(|(_, image)| image);
+            }
+        };
+        Some(image)
+    }
 }
 
 // Setup sprites for tile rendering - using colored sprites for simplicity
@@ -67,13 +413,33 @@ fn setup_tile_sprites(
     let tile_sprites = TileSprites {
         // Base tile types
         grass: make_colored_image(Color::rgb(0.2, 0.8, 0.2), &asset_server),
-        water: make_colored_image(Color::rgb(0.0, 0.3, 0.8), &asset_server),
+        shallow_water: make_colored_image(Color::rgb(0.2, 0.5, 0.9), &asset_server),
+        deep_water: make_colored_image(Color::rgb(0.0, 0.2, 0.6), &asset_server),
         sand: make_colored_image(Color::rgb(0.9, 0.9, 0.5), &asset_server),
         stone: make_colored_image(Color::rgb(0.5, 0.5, 0.5), &asset_server),
         forest: make_colored_image(Color::rgb(0.0, 0.6, 0.0), &asset_server),
         mountain: make_colored_image(Color::rgb(0.4, 0.3, 0.2), &asset_server),
         snow: make_colored_image(Color::rgb(0.9, 0.9, 1.0), &asset_server),
 
+        // Decorations and overlays
+        flowers: make_colored_image(Color::rgb(0.95, 0.6, 0.8), &asset_server),
+        grass_tufts: make_colored_image(Color::rgb(0.3, 0.65, 0.2), &asset_server),
+        pebbles: make_colored_image(Color::rgb(0.65, 0.6, 0.55), &asset_server),
+        rocks: make_colored_image(Color::rgb(0.45, 0.43, 0.4), &asset_server),
+        shells: make_colored_image(Color::rgb(1.0, 0.9, 0.85), &asset_server),
+        path: make_colored_image(Color::rgb(0.6, 0.5, 0.35), &asset_server),
+        stairs: make_colored_image(Color::rgb(0.55, 0.4, 0.25), &asset_server),
+        furnace: make_colored_image(Color::rgb(0.35, 0.3, 0.3), &asset_server),
+        door: make_colored_image(Color::rgb(0.45, 0.28, 0.12), &asset_server),
+        gate: make_colored_image(Color::rgb(0.6, 0.45, 0.25), &asset_server),
+        rail: make_colored_image(Color::rgb(0.3, 0.3, 0.32), &asset_server),
+        chest: make_colored_image(Color::rgb(0.55, 0.35, 0.15), &asset_server),
+        inserter: make_colored_image(Color::rgb(0.75, 0.6, 0.2), &asset_server),
+        generator: make_colored_image(Color::rgb(0.25, 0.35, 0.5), &asset_server),
+        wire: make_colored_image(Color::rgb(0.85, 0.5, 0.2), &asset_server),
+        assembler: make_colored_image(Color::rgb(0.4, 0.45, 0.5), &asset_server),
+        snow_cover: make_colored_image(Color::rgba(0.95, 0.95, 1.0, 0.6), &asset_server),
+
         // Resource types
         iron: make_colored_image(Color::rgb(0.6, 0.6, 0.7), &asset_server),
         copper: make_colored_image(Color::rgb(0.8, 0.5, 0.2), &asset_server),
@@ -81,16 +447,114 @@ fn setup_tile_sprites(
         gold: make_colored_image(Color::rgb(0.9, 0.8, 0.0), &asset_server),
         tree: make_colored_image(Color::rgb(0.0, 0.4, 0.0), &asset_server),
         resource_stone: make_colored_image(Color::rgb(0.4, 0.4, 0.4), &asset_server),
+        fish: make_colored_image(Color::rgb(0.7, 0.9, 1.0), &asset_server),
+
+        // Damaged resource nodes
+        damaged: HashMap::from([
+            // chopped tree, then stump
+            (
+                (ResourceType::Tree, 1),
+                make_colored_image(Color::rgb(0.2, 0.45, 0.1), &asset_server),
+            ),
+            (
+                (ResourceType::Tree, 2),
+                make_colored_image(Color::rgb(0.45, 0.3, 0.15), &asset_server),
+            ),
+            // cracked stone, then rubble
+            (
+                (ResourceType::Stone, 1),
+                make_colored_image(Color::rgb(0.33, 0.33, 0.33), &asset_server),
+            ),
+            (
+                (ResourceType::Stone, 2),
+                make_colored_image(Color::rgb(0.55, 0.52, 0.48), &asset_server),
+            ),
+            // ore veins get duller as they are mined out
+            (
+                (ResourceType::Iron, 1),
+                make_colored_image(Color::rgb(0.5, 0.5, 0.55), &asset_server),
+            ),
+            (
+                (ResourceType::Iron, 2),
+                make_colored_image(Color::rgb(0.42, 0.42, 0.44), &asset_server),
+            ),
+            (
+                (ResourceType::Copper, 1),
+                make_colored_image(Color::rgb(0.65, 0.42, 0.2), &asset_server),
+            ),
+            (
+                (ResourceType::Copper, 2),
+                make_colored_image(Color::rgb(0.5, 0.36, 0.22), &asset_server),
+            ),
+            (
+                (ResourceType::Coal, 1),
+                make_colored_image(Color::rgb(0.2, 0.2, 0.2), &asset_server),
+            ),
+            (
+                (ResourceType::Coal, 2),
+                make_colored_image(Color::rgb(0.3, 0.28, 0.26), &asset_server),
+            ),
+            (
+                (ResourceType::Gold, 1),
+                make_colored_image(Color::rgb(0.75, 0.65, 0.1), &asset_server),
+            ),
+            (
+                (ResourceType::Gold, 2),
+                make_colored_image(Color::rgb(0.55, 0.5, 0.25), &asset_server),
+            ),
+        ]),
     };
 
     // Store sprites in resource
     tile_render_state.tile_sprites = Some(tile_sprites);
 
     // Create a camera that works well for a 2D top-down game
-    commands.spawn(Camera2dBundle {
-        transform: Transform::from_xyz(0.0, 0.0, 999.9),
-        ..default()
-    });
+    commands.spawn((
+        Camera2dBundle {
+            transform: Transform::from_xyz(0.0, 0.0, 999.9),
+            ..default()
+        },
+        WorldCamera,
+    ));
+}
+
+// Color of the placeholder shown while a chunk's tiles are being built
+fn biome_color(biome: BiomeType) -> Color {
+    match biome {
+        BiomeType::Plains => Color::srgb(0.2, 0.8, 0.2),
+        BiomeType::Ocean => Color::srgb(0.0, 0.3, 0.8),
+        BiomeType::Desert => Color::srgb(0.9, 0.9, 0.5),
+        BiomeType::Forest => Color::srgb(0.0, 0.6, 0.0),
+        BiomeType::Mountain => Color::srgb(0.5, 0.5, 0.5),
+        BiomeType::Tundra => Color::srgb(0.9, 0.9, 1.0),
+    }
+}
+
+// Strength of the height-based shading
+const HEIGHT_SHADING: f32 = 0.25;
+// Darkness of the face drawn along a cliff edge
+const CLIFF_FACE: Color = Color::srgba(0.0, 0.0, 0.0, 0.45);
+// Width of a cliff face, as a fraction of a tile
+const CLIFF_FACE_WIDTH: f32 = 0.15;
+
+// Height snapped to its elevation level, so that terrain is drawn as terraces
+fn terrace(height: f32) -> f32 {
+    elevation_level(height) as f32 * ELEVATION_STEP
+}
+
+// Shading of a tile from its height and the height of its upper-left neighbour (light comes from
+// the upper left): low terraces are darker, slopes facing the light are highlighted, and tiles at
+// the foot of a cliff get a drop shadow
+fn tile_shade(height: f32, upper_left: f32) -> Color {
+    let (level, upper_left_level) = (terrace(height), terrace(upper_left));
+    let mut shade = 1.0 + level.clamp(-1.0, 1.0) * HEIGHT_SHADING;
+    let slope = level - upper_left_level;
+    shade += slope.clamp(-0.2, 0.2);
+    if upper_left > height && is_cliff(upper_left, height) {
+        shade *= 0.7;
+    }
+    let shade = shade.clamp(0.4, 1.15);
+    Color::srgb(shade, shade, shade)
 }
 
 // Helper to create colored sprites
@@ -127,140 +591,425 @@ fn make_colored_image(color: Color, asset_server: &AssetServer) -> Handle<Image>
     asset_server.add(image)
 }
 
-// System to render new chunks as they are loaded
+// System to render new chunks as they are loaded, and re-render chunks whose tiles changed
 fn render_new_chunks(
     mut commands: Commands,
-    chunks_query: Query<(Entity, &Chunk), Added<Chunk>>,
+    chunks_query: Query<(Entity, &Chunk), Changed<Chunk>>,
     world_config: Res<WorldConfig>,
+    projection: Res<TileProjection>,
     mut render_state: ResMut<TileRenderState>,
+    mut cache: ResMut<ChunkRenderCache>,
+    mut queue: ResMut<ChunkRenderQueue>,
 ) {
-    // Extract and clone sprites before doing any mutable operations
-    let sprites_option = render_state.tile_sprites.clone();
-    let Some(sprites) = sprites_option else {
+    if render_state.tile_sprites.is_none() {
         return;
-    };
+    }
 
-    let chunk_size = world_config.chunk_size as f32;
+    let grid = world_config.grid();
 
     for (entity, chunk) in chunks_query.iter() {
-        // Check if we've already rendered this chunk
-        if render_state.rendered_chunks.contains_key(&chunk.coord) {
-            continue;
+        // A chunk we come back to can reuse its previous render
+        let version = chunk.checksum();
+        if !render_state.rendered_chunks.contains_key(&chunk.coord) {
+            if let Some(cached) = cache.take(chunk.coord, version, *projection) {
+                debug!("Reusing cached render of chunk {:?}", chunk.coord);
+                commands.entity(cached).insert(Visibility::Inherited);
+                render_state.rendered_chunks.insert(chunk.coord, cached);
+                continue;
+            }
+        }
+
+        // A newer version of the chunk supersedes a render still in progress
+        if let Some(index) = queue.jobs.iter().position(|job| job.coord == chunk.coord) {
+            if let Some(job) = queue.jobs.remove(index) {
+                commands.entity(job.parent).despawn_recursive();
+            }
         }
 
         info!("Rendering chunk at {:?}", chunk.coord);
 
         // Create a parent entity for this chunk's tiles
+        let (chunk_transform, tiles_rotation) =
+            projection.chunk_transforms(grid.chunk_to_world(chunk.coord));
+        let previous = render_state.rendered_chunks.get(&chunk.coord).copied();
         let chunk_parent = commands
             .spawn((
                 SpatialBundle {
-                    transform: Transform::from_xyz(
-                        chunk.coord.x as f32 * chunk_size,
-                        chunk.coord.y as f32 * chunk_size,
-                        0.0,
-                    ),
+                    transform: chunk_transform,
+                    // a modified chunk keeps showing its previous render until the new one is built
+                    visibility: if previous.is_some() {
+                        Visibility::Hidden
+                    } else {
+                        Visibility::Inherited
+                    },
                     ..default()
                 },
-                chunk.coord,
+                ChunkRender {
+                    coord: chunk.coord,
+                    version,
+                    projection: *projection,
+                    tiles: chunk.tiles().len(),
+                },
             ))
             .id();
+        let tiles_parent = commands
+            .spawn(SpatialBundle {
+                transform: Transform::from_rotation(tiles_rotation),
+                ..default()
+            })
+            .set_parent(chunk_parent)
+            .id();
 
-        // Add tiles as children of the chunk parent
-        commands.entity(chunk_parent).with_children(|parent| {
-            for y in 0..chunk.tiles.len() {
-                for x in 0..chunk.tiles[y].len() {
-                    let tile = &chunk.tiles[y][x];
-
-                    // Get the sprite for this tile type
-                    let tile_sprite = match tile.tile_type {
-                        TileType::Grass => &sprites.grass,
-                        TileType::Water => &sprites.water,
-                        TileType::Sand => &sprites.sand,
-                        TileType::Stone => &sprites.stone,
-                        TileType::Forest => &sprites.forest,
-                        TileType::Mountain => &sprites.mountain,
-                        TileType::Snow => &sprites.snow,
-                    };
+        // Until its tiles are built, a new chunk is shown as a single quad of its biome's color
+        let placeholder = previous.is_none().then(|| {
+            let size = chunk.size() as f32;
+            commands
+                .spawn((
+                    Sprite {
+                        custom_size: Some(Vec2::splat(size)),
+                        color: biome_color(chunk.biome_type),
+                        ..default()
+                    },
+                    Transform::from_xyz((size - 1.0) * 0.5, (size - 1.0) * 0.5, -0.01),
+                ))
+                .set_parent(tiles_parent)
+                .id()
+        });
+
+        queue.jobs.push_back(ChunkRenderJob {
+            coord: chunk.coord,
+            chunk: entity,
+            parent: chunk_parent,
+            tiles_parent,
+            placeholder,
+            previous,
+            next_row: 0,
+        });
+    }
+}
 
-                    // Spawn the tile as a sprite
-                    let tile_size = 0.9; // Slightly smaller than 1.0 to have small gaps between tiles
-                    let mut tile_entity = parent.spawn((
-                        Sprite {
-                            custom_size: Some(Vec2::new(tile_size, tile_size)),
-                            color: Color::WHITE,
-                            image: tile_sprite.clone(),
-                            ..default()
-                        },
-                        Transform::from_xyz(x as f32, y as f32, 0.0),
+// Build the tiles of queued chunks, a limited number of tiles per frame
+fn build_pending_chunks(
+    mut commands: Commands,
+    chunks: Query<&Chunk>,
+    world_config: Res<WorldConfig>,
+    height_map: Res<HeightMap>,
+    mut render_state: ResMut<TileRenderState>,
+    mut queue: ResMut<ChunkRenderQueue>,
+) {
+    let Some(sprites) = render_state.tile_sprites.clone() else {
+        return;
+    };
+    let mut budget = TILES_PER_FRAME;
+
+    while budget > 0 {
+        let Some(job) = queue.jobs.front_mut() else {
+            break;
+        };
+        // The chunk was unloaded before its render was finished
+        let Ok(chunk) = chunks.get(job.chunk) else {
+            commands.entity(job.parent).despawn_recursive();
+            queue.jobs.pop_front();
+            continue;
+        };
+
+        let rows = chunk.size();
+        let row_tiles = world_config.chunk_size.max(1);
+        let row_count = (budget / row_tiles).max(1).min(rows - job.next_row);
+        let first_row = job.next_row;
+        commands.entity(job.tiles_parent).with_children(|parent| {
+            for y in first_row..first_row + row_count {
+                spawn_tile_row(parent, chunk, y, &sprites, &height_map);
+            }
+        });
+        job.next_row += row_count;
+        budget = budget.saturating_sub(row_count * row_tiles);
+
+        if job.next_row < rows {
+            continue;
+        }
+
+        // The chunk is complete: swap it in
+        let Some(job) = queue.jobs.pop_front() else {
+            break;
+        };
+        if let Some(placeholder) = job.placeholder {
+            commands.entity(placeholder).despawn_recursive();
+        }
+        if let Some(previous) = job.previous {
+            if let Some(entity_commands) = commands.get_entity(previous) {
+                entity_commands.despawn_recursive();
+            }
+        }
+        commands.entity(job.parent).insert(Visibility::Inherited);
+        render_state.rendered_chunks.insert(job.coord, job.parent);
+    }
+}
+
+// Spawn the sprites of one row of a chunk
+fn spawn_tile_row(
+    parent: &mut ChildBuilder,
+    chunk: &Chunk,
+    y: usize,
+    sprites: &TileSprites,
+    height_map: &HeightMap,
+) {
+    // Height of a tile relative to this chunk, falling back to the height map outside of it
+    let height = |x: i32, y: i32| -> f32 {
+        let size = chunk.size() as i32;
+        let inside = (0..size).contains(&x) && (0..size).contains(&y);
+        match inside
+            .then(|| chunk.get_tile(x as usize, y as usize))
+            .flatten()
+        {
+            Some(tile) => tile.height,
+            None => height_map.get_height(chunk.coord.x * size + x, chunk.coord.y * size + y),
+        }
+    };
+
+    for x in 0..chunk.size() {
+        let Some(tile) = chunk.get_tile(x, y) else {
+            continue;
+        };
+
+        // Get the sprite for this tile type
+        let tile_sprite = match tile.tile_type {
+            TileType::Grass => &sprites.grass,
+            TileType::ShallowWater => &sprites.shallow_water,
+            TileType::DeepWater => &sprites.deep_water,
+            TileType::Sand => &sprites.sand,
+            TileType::Stone => &sprites.stone,
+            TileType::Forest => &sprites.forest,
+            TileType::Mountain => &sprites.mountain,
+            TileType::Snow => &sprites.snow,
+        };
+
+        // Spawn the tile as a sprite
+        // Slightly smaller than a tile to have small gaps between tiles
+        let tile_size = 0.9 * WorldGrid::TILE_SIZE;
+        let mut tile_entity = parent.spawn((
+            Sprite {
+                custom_size: Some(Vec2::new(tile_size, tile_size)),
+                color: tile_shade(tile.height, height(x as i32 - 1, y as i32 + 1)),
+                image: tile_sprite.clone(),
+                ..default()
+            },
+            Transform::from_translation(WorldGrid::tile_to_world((x as i32, y as i32)).extend(0.0)),
+            TileSprite(tile.tile_type),
+        ));
+
+        // Layers above the ground are children of its sprite, so they are drawn over it
+        tile_entity.with_children(|layers| {
+            // Faces along the edges that drop down a cliff (south, east and west: north faces
+            // point away from the camera)
+            for (dx, dy) in [(0, -1), (1, 0), (-1, 0)] {
+                let below = height(x as i32 + dx, y as i32 + dy);
+                if below < tile.height && is_cliff(tile.height, below) {
+                    let offset =
+                        Vec2::new(dx as f32, dy as f32) * (tile_size - CLIFF_FACE_WIDTH) / 2.0;
+                    let size = if dx == 0 {
+                        Vec2::new(tile_size, CLIFF_FACE_WIDTH)
+                    } else {
+                        Vec2::new(CLIFF_FACE_WIDTH, tile_size)
+                    };
+                    layers.spawn((
+                        Sprite::from_color(CLIFF_FACE, size),
+                        Transform::from_translation(offset.extend(CLIFF_Z)),
                     ));
+                }
+            }
 
-                    // If the tile has a resource, add a resource indicator on top
-                    if tile.resource != ResourceType::None {
-                        let resource_sprite = match tile.resource {
-                            ResourceType::Iron => &sprites.iron,
-                            ResourceType::Copper => &sprites.copper,
-                            ResourceType::Coal => &sprites.coal,
-                            ResourceType::Gold => &sprites.gold,
-                            ResourceType::Tree => &sprites.tree,
-                            ResourceType::Stone => &sprites.resource_stone,
-                            ResourceType::None => continue,
-                        };
-
-                        // Add a smaller resource indicator on top of the tile
-                        tile_entity.with_children(|resource_parent| {
-                            resource_parent.spawn((
-                                Sprite {
-                                    custom_size: Some(Vec2::new(tile_size * 0.5, tile_size * 0.5)),
-                                    color: Color::WHITE,
-                                    image: resource_sprite.clone(),
-                                    ..default()
-                                },
-                                Transform::from_xyz(0.0, 0.0, 0.1),
-                            ));
-                        });
-                    }
+            if let Some(decoration_sprite) = sprites.decoration(tile.decoration) {
+                let mut decoration = layers.spawn((
+                    Sprite {
+                        custom_size: Some(Vec2::splat(
+                            tile_size * decoration_size(tile.decoration),
+                        )),
+                        image: decoration_sprite.clone(),
+                        ..default()
+                    },
+                    Transform::from_xyz(0.0, 0.0, DECORATION_Z),
+                ));
+                if tile.decoration.is_openable() {
+                    decoration.insert(DoorSprite {
+                        tile: tile.position,
+                        open: !tile.decoration.is_closed(),
+                    });
                 }
             }
+
+            // If the tile has a resource, add a smaller resource indicator on top
+            let tier = tile.resource.damage_tier(tile.damage);
+            if let Some(resource_sprite) = sprites.resource(tile.resource, tier) {
+                layers.spawn((
+                    Sprite {
+                        // damaged nodes shrink as they get closer to breaking
+                        custom_size: Some(Vec2::splat(tile_size * (0.5 - 0.1 * tier as f32))),
+                        color: Color::WHITE,
+                        image: resource_sprite.clone(),
+                        ..default()
+                    },
+                    Transform::from_xyz(0.0, 0.0, RESOURCE_Z),
+                    ResourceSprite(tile.resource),
+                ));
+            }
+
+            if let Some(overlay_sprite) = sprites.overlay(tile.overlay) {
+                layers.spawn((
+                    Sprite {
+                        custom_size: Some(Vec2::splat(tile_size)),
+                        image: overlay_sprite.clone(),
+                        ..default()
+                    },
+                    Transform::from_xyz(0.0, 0.0, OVERLAY_Z),
+                ));
+            }
         });
+    }
+}
+
+// Share of a tile covered by its decoration: paths and stairs run across the whole tile
+fn decoration_size(decoration: Decoration) -> f32 {
+    match decoration {
+        Decoration::Path | Decoration::Stairs | Decoration::Rail => 0.8,
+        Decoration::Rocks | Decoration::Inserter { .. } => 0.4,
+        Decoration::Furnace
+        | Decoration::Chest
+        | Decoration::Assembler
+        | Decoration::Generator
+        | Decoration::Door { .. } => 0.6,
+        Decoration::Gate { .. } => 0.9,
+        Decoration::Pebbles | Decoration::Shells | Decoration::Wire => 0.2,
+        _ => 0.3,
+    }
+}
+
+// Fade resources that can't be harvested at the current time of day
+fn fade_unavailable_resources(
+    world_state: Res<WorldState>,
+    mut last_phase: Local<Option<DayPhase>>,
+    mut resources: Query<(&ResourceSprite, &mut Sprite)>,
+    new_resources: Query<(), Added<ResourceSprite>>,
+) {
+    let phase = DayPhase::at(world_state.world_time);
+    if *last_phase == Some(phase) && new_resources.is_empty() {
+        return;
+    }
+    *last_phase = Some(phase);
+    for (resource, mut sprite) in resources.iter_mut() {
+        let alpha = if resource.0.is_available(phase) {
+            1.0
+        } else {
+            UNAVAILABLE_RESOURCE_ALPHA
+        };
+        sprite.color.set_alpha(alpha);
+    }
+}
+
+fn load_projection_setting(
+    settings: Option<Res<Settings>>,
+    mut projection: ResMut<TileProjection>,
+) {
+    if settings.is_some_and(|s| s.client.isometric) {
+        *projection = TileProjection::Isometric;
+    }
+}
+
+// Switch projection at runtime; every chunk gets re-rendered with the new projection
+fn toggle_projection(
+    keypress: Res<ButtonInput<KeyCode>>,
+    mut projection: ResMut<TileProjection>,
+    mut chunks: Query<&mut Chunk>,
+) {
+    if !keypress.just_pressed(PROJECTION_TOGGLE_KEY) {
+        return;
+    }
+    *projection = match *projection {
+        TileProjection::TopDown => TileProjection::Isometric,
+        TileProjection::Isometric => TileProjection::TopDown,
+    };
+    info!("Switched to {:?} projection", *projection);
+    for mut chunk in chunks.iter_mut() {
+        chunk.set_changed();
+    }
+}
 
-        // Store the rendered chunk in our state
-        render_state
-            .rendered_chunks
-            .insert(chunk.coord, chunk_parent);
+// Cover grass in snow during winter
+fn apply_season_overlay(
+    season: Res<CurrentSeason>,
+    render_state: Res<TileRenderState>,
+    mut tiles: Query<(&TileSprite, &mut Sprite)>,
+    new_tiles: Query<(), Added<TileSprite>>,
+) {
+    if !season.is_changed() && new_tiles.is_empty() {
+        return;
+    }
+    let Some(sprites) = &render_state.tile_sprites else {
+        return;
+    };
+    let winter = season.0 == Season::Winter;
+    for (tile, mut sprite) in tiles.iter_mut() {
+        if tile.0 == TileType::Grass {
+            sprite.image = if winter {
+                sprites.snow.clone()
+            } else {
+                sprites.grass.clone()
+            };
+        }
     }
 }
 
-// System to update existing rendered chunks (not needed for basic implementation)
+// System to update existing rendered chunks: renders of chunks that were unloaded are hidden
+// and kept in the render cache, so that they can be shown again if the player comes back
 fn update_visible_chunks(
+    mut commands: Commands,
     mut render_state: ResMut<TileRenderState>,
-    chunk_visibility_query: Query<(Entity, &ChunkCoord), Without<Chunk>>,
+    mut cache: ResMut<ChunkRenderCache>,
+    chunks: Query<&Chunk>,
+    renders: Query<&ChunkRender>,
 ) {
-    // This is a placeholder for potential future enhancements like:
-    // - LOD (Level of Detail) system
-    // - Chunk visibility culling
-    // - Dynamic updates to chunks
+    let loaded: HashSet<ChunkCoord> = chunks.iter().map(|chunk| chunk.coord).collect();
+    let unloaded: Vec<ChunkCoord> = render_state
+        .rendered_chunks
+        .keys()
+        .filter(|coord| !loaded.contains(coord))
+        .copied()
+        .collect();
+
+    for coord in unloaded {
+        let Some(entity) = render_state.rendered_chunks.remove(&coord) else {
+            continue;
+        };
+        let Ok(render) = renders.get(entity) else {
+            continue;
+        };
+        commands.entity(entity).insert(Visibility::Hidden);
+        for evicted in cache.insert(entity, render) {
+            commands.entity(evicted).despawn_recursive();
+        }
+    }
 }
 
-// System to make the camera follow the player
+// System to make the camera follow the player, or the focus of an observer
 fn camera_follow_player(
     player_query: Query<&PlayerPosition, With<Predicted>>,
     mut camera_query: Query<&mut Transform, With<Camera>>,
-    world_config: Res<WorldConfig>,
+    projection: Res<TileProjection>,
+    client_world: Res<ClientWorldState>,
 ) {
+    let target = player_query
+        .get_single()
+        .map(|player_pos| player_pos.0)
+        .ok()
+        .or(client_world.focus);
     // If we have a player and a camera, make the camera follow the player
-    if let (Ok(player_pos), Ok(mut camera_transform)) =
-        (player_query.get_single(), camera_query.get_single_mut())
-    {
-        // Calculate world position
-        let chunk_size = world_config.chunk_size as f32;
-
+    if let (Some(target), Ok(mut camera_transform)) = (target, camera_query.get_single_mut()) {
         // Smooth follow with some scaling to ensure proper view of the world
-        camera_transform.translation.x = player_pos.x;
-        camera_transform.translation.y = player_pos.y;
+        let screen = projection.to_screen(target);
+        camera_transform.translation.x = screen.x;
+        camera_transform.translation.y = screen.y;
 
-        // Set an appropriate zoom level based on the chunk size
-        // This can be adjusted based on preference
-        let zoom_factor = chunk_size / 16.0; // Adjust this divisor to change the default zoom
-        camera_transform.scale = Vec3::new(zoom_factor, zoom_factor, 1.0);
+        // The zoom is in world units, so the same number of tiles is visible whatever the chunk size
+        camera_transform.scale = Vec3::new(CAMERA_ZOOM, CAMERA_ZOOM, 1.0);
     }
 }
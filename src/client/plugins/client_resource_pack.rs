@@ -0,0 +1,364 @@
+use bevy::prelude::*;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use lightyear::prelude::client::*;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::client::plugins::{ChatInput, ItemColors, ShowToast, TileRenderState};
+use crate::protocol::{
+    ResourcePackChannel, ResourcePackOffer, ResourcePackPart, ResourcePackRequest,
+};
+use crate::settings_common::{PackConsent, ResourcePackSettings, Settings};
+use crate::shared::error::{GameError, ReportError};
+use crate::shared::resource_pack::{hash, hex, PackHash, ResourcePack, MAX_PACK_BYTES};
+
+// Downloaded packs, named after their hash, so that they are only downloaded once
+const CACHE_DIR: &str = "cache/resource_packs";
+// Keys accepting and declining the offered pack
+const ACCEPT_KEY: KeyCode = KeyCode::F7;
+const DECLINE_KEY: KeyCode = KeyCode::F8;
+
+// Client plugin taking on the resource pack the server offers: once the player agrees (or the
+// settings do), the pack is downloaded, checked against its hash and cached, then its sprites
+// replace the built-in ones
+pub struct ClientResourcePackPlugin;
+
+impl Plugin for ClientResourcePackPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ClientResourcePack>()
+            .add_systems(Startup, (load_pack_settings, setup_pack_prompt))
+            .add_systems(
+                Update,
+                (
+                    receive_pack_offer,
+                    pack_prompt_input,
+                    receive_pack_parts,
+                    apply_resource_pack,
+                    update_pack_prompt,
+                )
+                    .chain(),
+            );
+    }
+}
+
+#[derive(Default)]
+enum PackState {
+    #[default]
+    None,
+    // Waiting for the player to accept or decline the offer
+    Offered(ResourcePackOffer),
+    Downloading {
+        offer: ResourcePackOffer,
+        data: Vec<u8>,
+    },
+    // Checked, waiting for the built-in sprites it replaces
+    Ready(ResourcePack),
+    Applied,
+    Declined,
+}
+
+#[derive(Resource, Default)]
+struct ClientResourcePack {
+    settings: ResourcePackSettings,
+    state: PackState,
+}
+
+#[derive(Component)]
+struct PackPrompt;
+
+fn cache_path(hash: &PackHash) -> PathBuf {
+    PathBuf::from(CACHE_DIR).join(format!("{}.pack", hex(hash)))
+}
+
+// The pack with this hash, if it was downloaded before
+fn load_cached(expected: &PackHash) -> Result<Option<ResourcePack>, GameError> {
+    let path = cache_path(expected);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let data = fs::read(&path)?;
+    // a damaged copy is downloaded again
+    if hash(&data) != *expected {
+        fs::remove_file(&path)?;
+        return Ok(None);
+    }
+    ResourcePack::decode(&data).map(Some)
+}
+
+fn save_cached(hash: &PackHash, data: &[u8]) -> Result<(), GameError> {
+    fs::create_dir_all(CACHE_DIR)?;
+    Ok(fs::write(cache_path(hash), data)?)
+}
+
+fn mib(bytes: usize) -> String {
+    format!("{:.1} MiB", bytes as f32 / (1024.0 * 1024.0))
+}
+
+fn load_pack_settings(settings: Option<Res<Settings>>, mut pack: ResMut<ClientResourcePack>) {
+    if let Some(settings) = settings {
+        pack.settings = settings.client.resource_packs.clone();
+    }
+}
+
+fn setup_pack_prompt(mut commands: Commands) {
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                top: Val::Percent(60.0),
+                width: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            // the offer comes as the client connects, over the loading screen
+            GlobalZIndex(102),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(""),
+                TextFont::from_font_size(18.0),
+                TextColor(Color::WHITE),
+                TextLayout::new_with_justify(JustifyText::Center),
+                PackPrompt,
+            ));
+        });
+}
+
+fn request_pack(
+    pack: &mut ClientResourcePack,
+    offer: ResourcePackOffer,
+    client: &mut ConnectionManager,
+    errors: &mut EventWriter<ReportError>,
+) {
+    let request = ResourcePackRequest { hash: offer.hash };
+    if let Err(e) = client.send_message::<ResourcePackChannel, _>(&request) {
+        errors.send(ReportError(GameError::send("ResourcePackRequest", e)));
+        pack.state = PackState::None;
+        return;
+    }
+    info!("Downloading resource pack '{}'", offer.name);
+    pack.state = PackState::Downloading {
+        data: Vec::with_capacity(offer.size as usize),
+        offer,
+    };
+}
+
+fn receive_pack_offer(
+    mut offers: EventReader<MessageEvent<ResourcePackOffer>>,
+    mut pack: ResMut<ClientResourcePack>,
+    mut client: ResMut<ConnectionManager>,
+    mut toasts: EventWriter<ShowToast>,
+    mut errors: EventWriter<ReportError>,
+) {
+    for offer in offers.read() {
+        let offer = offer.message().clone();
+        match load_cached(&offer.hash) {
+            Ok(Some(cached)) => {
+                info!("Using the cached resource pack '{}'", offer.name);
+                pack.state = PackState::Ready(cached);
+                continue;
+            }
+            Ok(None) => {}
+            Err(e) => {
+                errors.send(ReportError(e));
+            }
+        }
+        let max_size = pack.settings.max_size.min(MAX_PACK_BYTES);
+        if offer.size as usize > max_size {
+            warn!(
+                "Declining resource pack '{}': {} bytes, more than the {} allowed",
+                offer.name, offer.size, max_size
+            );
+            toasts.send(ShowToast {
+                title: "Resource pack declined".to_string(),
+                body: format!(
+                    "'{}' is {}, more than the {} allowed in the settings",
+                    offer.name,
+                    mib(offer.size as usize),
+                    mib(max_size)
+                ),
+            });
+            pack.state = PackState::Declined;
+            continue;
+        }
+        match pack.settings.consent {
+            PackConsent::Ask => pack.state = PackState::Offered(offer),
+            PackConsent::Always => request_pack(&mut pack, offer, &mut client, &mut errors),
+            PackConsent::Never => {
+                info!(
+                    "Declining resource pack '{}', as set in the settings",
+                    offer.name
+                );
+                pack.state = PackState::Declined;
+            }
+        }
+    }
+}
+
+fn pack_prompt_input(
+    keypress: Res<ButtonInput<KeyCode>>,
+    chat: Res<ChatInput>,
+    mut pack: ResMut<ClientResourcePack>,
+    mut client: ResMut<ConnectionManager>,
+    mut errors: EventWriter<ReportError>,
+) {
+    if chat.open {
+        return;
+    }
+    let PackState::Offered(offer) = &pack.state else {
+        return;
+    };
+    if keypress.just_pressed(ACCEPT_KEY) {
+        let offer = offer.clone();
+        request_pack(&mut pack, offer, &mut client, &mut errors);
+    } else if keypress.just_pressed(DECLINE_KEY) {
+        info!("Playing without the resource pack '{}'", offer.name);
+        pack.state = PackState::Declined;
+    }
+}
+
+fn receive_pack_parts(
+    mut parts: EventReader<MessageEvent<ResourcePackPart>>,
+    mut pack: ResMut<ClientResourcePack>,
+    mut errors: EventWriter<ReportError>,
+) {
+    for part in parts.read() {
+        let part = part.message();
+        let PackState::Downloading { offer, data } = &mut pack.state else {
+            continue;
+        };
+        let size = offer.size as usize;
+        if part.offset as usize != data.len() || data.len() + part.data.len() > size {
+            errors.send(ReportError(GameError::ResourcePack(format!(
+                "unexpected part at byte {} of '{}'",
+                part.offset, offer.name
+            ))));
+            pack.state = PackState::Declined;
+            continue;
+        }
+        data.extend_from_slice(&part.data);
+        if data.len() < size {
+            continue;
+        }
+        if hash(data) != offer.hash {
+            errors.send(ReportError(GameError::ResourcePack(format!(
+                "'{}' doesn't match the hash it was offered with",
+                offer.name
+            ))));
+            pack.state = PackState::Declined;
+            continue;
+        }
+        match ResourcePack::decode(data) {
+            Ok(decoded) => {
+                // a pack that can't be cached is still good for this session
+                if let Err(e) = save_cached(&offer.hash, data) {
+                    errors.send(ReportError(e));
+                }
+                pack.state = PackState::Ready(decoded);
+            }
+            Err(e) => {
+                errors.send(ReportError(e));
+                pack.state = PackState::Declined;
+            }
+        }
+    }
+}
+
+fn apply_resource_pack(
+    mut pack: ResMut<ClientResourcePack>,
+    tile_render: Res<TileRenderState>,
+    mut images: ResMut<Assets<Image>>,
+    mut item_colors: ResMut<ItemColors>,
+    mut toasts: EventWriter<ShowToast>,
+    mut errors: EventWriter<ReportError>,
+) {
+    // sprites are replaced in place, so that chunks already drawn change as well
+    let Some(sprites) = &tile_render.tile_sprites else {
+        return;
+    };
+    if !matches!(pack.state, PackState::Ready(_)) {
+        return;
+    }
+    let PackState::Ready(resource_pack) = std::mem::take(&mut pack.state) else {
+        return;
+    };
+    let atlas = match resource_pack.atlas_image() {
+        Ok(atlas) => atlas,
+        Err(e) => {
+            errors.send(ReportError(e));
+            pack.state = PackState::Declined;
+            return;
+        }
+    };
+    let manifest = &resource_pack.manifest;
+    for (name, cell) in &manifest.sprites {
+        let Some(handle) = sprites.named(name) else {
+            warn!(
+                "Resource pack '{}' replaces unknown sprite {}",
+                manifest.name, name
+            );
+            continue;
+        };
+        let sprite = resource_pack.sprite(&atlas, *cell);
+        images.insert(
+            handle.id(),
+            Image::new(
+                Extent3d {
+                    width: sprite.width(),
+                    height: sprite.height(),
+                    depth_or_array_layers: 1,
+                },
+                TextureDimension::D2,
+                sprite.into_raw(),
+                TextureFormat::Rgba8UnormSrgb,
+                RenderAssetUsages::default(),
+            ),
+        );
+    }
+    item_colors.0 = manifest
+        .item_colors
+        .iter()
+        .map(|(kind, (r, g, b))| (*kind, Color::srgb(*r, *g, *b)))
+        .collect();
+    info!(
+        "Applied resource pack '{}': {} sprites, {} item colors",
+        manifest.name,
+        manifest.sprites.len(),
+        manifest.item_colors.len()
+    );
+    toasts.send(ShowToast {
+        title: "Resource pack".to_string(),
+        body: manifest.name.clone(),
+    });
+    pack.state = PackState::Applied;
+}
+
+fn update_pack_prompt(
+    pack: Res<ClientResourcePack>,
+    mut prompts: Query<&mut Text, With<PackPrompt>>,
+) {
+    if !pack.is_changed() {
+        return;
+    }
+    let text = match &pack.state {
+        PackState::Offered(offer) => format!(
+            "The server offers the resource pack '{}' ({})\n{}\nPress F7 to download it, F8 to play \
+             without it",
+            offer.name,
+            mib(offer.size as usize),
+            offer.description
+        ),
+        PackState::Downloading { offer, data } => format!(
+            "Downloading the resource pack '{}'... {:.0}%",
+            offer.name,
+            data.len() as f32 / offer.size.max(1) as f32 * 100.0
+        ),
+        _ => String::new(),
+    };
+    for mut prompt in prompts.iter_mut() {
+        if prompt.0 != text {
+            prompt.0 = text.clone();
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use bevy::prelude::*;
+use lightyear::prelude::client::*;
+use std::time::Duration;
+
+use crate::protocol::ServerRestarting;
+
+// Time between attempts to connect to a restarting server
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(3);
+// Attempts before giving up on the server coming back
+const RECONNECT_ATTEMPTS: u32 = 40;
+
+// Client-side plugin connecting again to a server that restarted on its schedule
+pub struct ClientRestartPlugin;
+
+impl Plugin for ClientRestartPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PendingRestart>().add_systems(
+            Update,
+            (receive_restart_notice, reconnect_after_restart).chain(),
+        );
+    }
+}
+
+// Set when the server told us it's restarting, until we are connected to it again
+#[derive(Resource, Default)]
+struct PendingRestart(Option<Reconnect>);
+
+struct Reconnect {
+    timer: Timer,
+    attempts: u32,
+}
+
+fn receive_restart_notice(
+    mut events: EventReader<MessageEvent<ServerRestarting>>,
+    mut pending_restart: ResMut<PendingRestart>,
+) {
+    if events.read().count() > 0 {
+        info!("Server is restarting, reconnecting once it's back");
+        pending_restart.0 = Some(Reconnect {
+            timer: Timer::new(RECONNECT_INTERVAL, TimerMode::Repeating),
+            attempts: 0,
+        });
+    }
+}
+
+fn reconnect_after_restart(
+    mut commands: Commands,
+    time: Res<Time>,
+    state: Res<State<NetworkingState>>,
+    mut pending_restart: ResMut<PendingRestart>,
+) {
+    let Some(reconnect) = pending_restart.0.as_mut() else {
+        return;
+    };
+    match state.get() {
+        // still connected to the old server, or waiting on an attempt
+        NetworkingState::Connected if reconnect.attempts == 0 => {}
+        NetworkingState::Connected => {
+            info!("Reconnected to the restarted server");
+            pending_restart.0 = None;
+        }
+        NetworkingState::Connecting => {}
+        NetworkingState::Disconnected => {
+            if !reconnect.timer.tick(time.delta()).just_finished() {
+                return;
+            }
+            if reconnect.attempts >= RECONNECT_ATTEMPTS {
+                warn!("Server didn't come back after restarting, giving up reconnecting");
+                pending_restart.0 = None;
+                return;
+            }
+            reconnect.attempts += 1;
+            commands.connect_client();
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use bevy::prelude::*;
+use lightyear::prelude::client::*;
+
+use crate::protocol::ShardHandoff;
+
+// Client-side plugin following the server's shard handoffs: the client reconnects to the shard
+// owning the region the player walked into
+pub struct ClientShardsPlugin;
+
+impl Plugin for ClientShardsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PendingHandoff>()
+            .add_systems(Update, (receive_handoff, reconnect_to_shard).chain());
+    }
+}
+
+// Set when we disconnected to switch shards, and must connect again
+#[derive(Resource, Default)]
+struct PendingHandoff(bool);
+
+fn receive_handoff(
+    mut commands: Commands,
+    mut events: EventReader<MessageEvent<ShardHandoff>>,
+    mut config: ResMut<ClientConfig>,
+    mut pending_handoff: ResMut<PendingHandoff>,
+) {
+    for event in events.read() {
+        let server_addr = event.message().server_addr;
+        // The server address is part of the auth and io configs, which only get used when connecting
+        let NetConfig::Netcode {
+            auth: Authentication::Manual {
+                server_addr: addr, ..
+            },
+            io,
+            ..
+        } = &mut config.net
+        else {
+            warn!("Shard handoffs are only supported with manual netcode authentication");
+            continue;
+        };
+        info!("Handed off to the shard at {}", server_addr);
+        *addr = server_addr;
+        match &mut io.transport {
+            ClientTransport::WebTransportClient {
+                server_addr: addr, ..
+            } => *addr = server_addr,
+            #[cfg(feature = "websocket")]
+            ClientTransport::WebSocketClient { server_addr: addr } => *addr = server_addr,
+            _ => {}
+        }
+        commands.disconnect_client();
+        pending_handoff.0 = true;
+    }
+}
+
+fn reconnect_to_shard(
+    mut commands: Commands,
+    state: Res<State<NetworkingState>>,
+    mut pending_handoff: ResMut<PendingHandoff>,
+) {
+    if pending_handoff.0 && state.get() == &NetworkingState::Disconnected {
+        commands.connect_client();
+        pending_handoff.0 = false;
+    }
+}
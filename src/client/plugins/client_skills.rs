@@ -0,0 +1,110 @@
+use bevy::prelude::*;
+use lightyear::prelude::client::*;
+
+use crate::client::plugins::ChatInput;
+use crate::shared::skills::{Skill, SkillLevels};
+
+// Key toggling the skills panel
+const SKILLS_KEY: KeyCode = KeyCode::KeyK;
+// Width of the progress bars, in characters
+const BAR_WIDTH: usize = 10;
+
+// Client plugin showing the player's skill levels
+pub struct ClientSkillsPlugin;
+
+impl Plugin for ClientSkillsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_skills_panel)
+            .add_systems(Update, (skills_input, update_skills_panel).chain());
+    }
+}
+
+#[derive(Component)]
+struct SkillsPanel;
+
+#[derive(Component)]
+struct SkillsText;
+
+fn setup_skills_panel(mut commands: Commands) {
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                left: Val::Px(10.0),
+                top: Val::Px(60.0),
+                padding: UiRect::all(Val::Px(6.0)),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.6)),
+            Visibility::Hidden,
+            SkillsPanel,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(""),
+                TextFont::from_font_size(14.0),
+                TextColor(Color::WHITE),
+                SkillsText,
+            ));
+        });
+}
+
+fn skills_input(
+    keypress: Res<ButtonInput<KeyCode>>,
+    chat: Res<ChatInput>,
+    mut panel: Query<&mut Visibility, With<SkillsPanel>>,
+) {
+    if chat.open || !keypress.just_pressed(SKILLS_KEY) {
+        return;
+    }
+    for mut visibility in panel.iter_mut() {
+        visibility.toggle_visible_hidden();
+    }
+}
+
+fn progress_bar(xp: u32, from: u32, to: u32) -> String {
+    let fraction = if to > from {
+        (xp.saturating_sub(from)) as f32 / (to - from) as f32
+    } else {
+        1.0
+    };
+    let filled = (fraction.clamp(0.0, 1.0) * BAR_WIDTH as f32).round() as usize;
+    format!("{}{}", "#".repeat(filled), "-".repeat(BAR_WIDTH - filled))
+}
+
+fn update_skills_panel(
+    player: Query<Ref<SkillLevels>, With<Predicted>>,
+    mut text: Query<&mut Text, With<SkillsText>>,
+) {
+    let Ok(levels) = player.get_single() else {
+        return;
+    };
+    if !levels.is_changed() {
+        return;
+    }
+    let lines: Vec<String> = Skill::ALL
+        .into_iter()
+        .map(|skill| {
+            let level = levels.get(skill);
+            match level.next_level_xp {
+                Some(next) => format!(
+                    "{:<12} {:>3} [{}] {}/{}",
+                    format!("{:?}", skill),
+                    level.level,
+                    progress_bar(level.xp, level.level_xp, next),
+                    level.xp,
+                    next
+                ),
+                None => format!(
+                    "{:<12} {:>3} [{}] max",
+                    format!("{:?}", skill),
+                    level.level,
+                    progress_bar(1, 0, 1)
+                ),
+            }
+        })
+        .collect();
+    for mut text in text.iter_mut() {
+        text.0 = format!("Skills\n{}", lines.join("\n"));
+    }
+}
@@ -0,0 +1,283 @@
+use bevy::prelude::*;
+use lightyear::prelude::client::*;
+use lightyear::prelude::ClientId;
+
+use crate::client::plugins::{ChatInput, TileProjection};
+use crate::protocol::{Channel1, PlayerId, PlayerName, PlayerPosition};
+use crate::shared::error::{GameError, ReportError};
+use crate::shared::social::{Relation, SetRelation, SocialLists, SocialListsSync};
+
+// Key toggling the roster of online players
+const ROSTER_KEY: KeyCode = KeyCode::Tab;
+// Height of a name label above the player, in tiles
+const LABEL_OFFSET: f32 = 0.8;
+// Scale applied to label text so that it fits the tile grid
+const LABEL_SCALE: f32 = 0.03;
+const ACTIVE_COLOR: Color = Color::srgb(0.8, 0.3, 0.3);
+const INACTIVE_COLOR: Color = Color::srgb(0.3, 0.3, 0.3);
+
+// Client plugin drawing other players' names, and a roster to mute or block them
+pub struct ClientSocialPlugin;
+
+impl Plugin for ClientSocialPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ClientSocialLists>()
+            .add_systems(Startup, setup_roster)
+            .add_systems(
+                Update,
+                (
+                    receive_social_lists,
+                    roster_input,
+                    (spawn_name_labels, update_name_labels).chain(),
+                    update_roster,
+                    roster_buttons,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// The players we muted or blocked, as last sent by the server
+#[derive(Resource, Default)]
+pub struct ClientSocialLists(pub SocialLists);
+
+#[derive(Component)]
+struct NameLabel {
+    player: Entity,
+}
+
+#[derive(Component)]
+struct Roster;
+
+#[derive(Component)]
+struct RosterRows;
+
+// A roster button setting or clearing a relation with a player
+#[derive(Component)]
+struct RosterToggle {
+    player: ClientId,
+    relation: Relation,
+}
+
+fn setup_roster(mut commands: Commands) {
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                right: Val::Px(10.0),
+                top: Val::Px(60.0),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(4.0),
+                padding: UiRect::all(Val::Px(6.0)),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.6)),
+            Visibility::Hidden,
+            Roster,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new("Players online"),
+                TextFont::from_font_size(14.0),
+                TextColor(Color::WHITE),
+            ));
+            parent.spawn((
+                Node {
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(4.0),
+                    ..default()
+                },
+                RosterRows,
+            ));
+        });
+}
+
+fn receive_social_lists(
+    mut events: EventReader<MessageEvent<SocialListsSync>>,
+    mut lists: ResMut<ClientSocialLists>,
+) {
+    if let Some(event) = events.read().last() {
+        lists.0 = event.message().0.clone();
+    }
+}
+
+fn roster_input(
+    keypress: Res<ButtonInput<KeyCode>>,
+    chat: Res<ChatInput>,
+    mut roster: Query<&mut Visibility, With<Roster>>,
+) {
+    if chat.open || !keypress.just_pressed(ROSTER_KEY) {
+        return;
+    }
+    for mut visibility in roster.iter_mut() {
+        visibility.toggle_visible_hidden();
+    }
+}
+
+// Labels are separate entities, so that player entities keep their own transform
+fn spawn_name_labels(
+    mut commands: Commands,
+    players: Query<(Entity, &PlayerName), (Added<PlayerName>, With<Interpolated>)>,
+) {
+    for (entity, name) in players.iter() {
+        commands.spawn((
+            Text2d::new(name.0.clone()),
+            TextFont::from_font_size(14.0),
+            TextColor(Color::WHITE),
+            Transform::from_scale(Vec3::splat(LABEL_SCALE)),
+            NameLabel { player: entity },
+        ));
+    }
+}
+
+// Follow the player, pick up renames, and hide the names of blocked players
+fn update_name_labels(
+    mut commands: Commands,
+    projection: Res<TileProjection>,
+    lists: Res<ClientSocialLists>,
+    players: Query<(&PlayerId, &PlayerName, &PlayerPosition), With<Interpolated>>,
+    mut labels: Query<(
+        Entity,
+        &NameLabel,
+        &mut Text2d,
+        &mut Transform,
+        &mut Visibility,
+    )>,
+) {
+    for (entity, label, mut text, mut transform, mut visibility) in labels.iter_mut() {
+        let Ok((player_id, name, position)) = players.get(label.player) else {
+            commands.entity(entity).despawn();
+            continue;
+        };
+        if text.0 != name.0 {
+            text.0 = name.0.clone();
+        }
+        let world = position.0 + Vec2::Y * LABEL_OFFSET;
+        let screen = projection.to_screen(world);
+        transform.translation = screen.extend(0.9 + projection.depth(world));
+        visibility.set_if_neq(if lists.0.blocks(player_id.client_id()) {
+            Visibility::Hidden
+        } else {
+            Visibility::Inherited
+        });
+    }
+}
+
+fn toggle_button(player: ClientId, relation: Relation, active: bool) -> impl Bundle {
+    let label = match relation {
+        Relation::Muted => "Mute",
+        Relation::Blocked => "Block",
+    };
+    (
+        Button,
+        Node {
+            padding: UiRect::axes(Val::Px(6.0), Val::Px(2.0)),
+            ..default()
+        },
+        BackgroundColor(if active { ACTIVE_COLOR } else { INACTIVE_COLOR }),
+        RosterToggle { player, relation },
+        Text::new(label),
+        TextFont::from_font_size(12.0),
+        TextColor(Color::WHITE),
+    )
+}
+
+// Rebuild the roster rows when it's open and players or lists changed
+fn update_roster(
+    mut commands: Commands,
+    lists: Res<ClientSocialLists>,
+    roster: Query<Ref<Visibility>, With<Roster>>,
+    rows: Query<Entity, With<RosterRows>>,
+    players: Query<(&PlayerId, Ref<PlayerName>), With<Interpolated>>,
+    mut removed: RemovedComponents<PlayerName>,
+) {
+    if roster
+        .iter()
+        .all(|visibility| *visibility == Visibility::Hidden)
+    {
+        return;
+    }
+    let opened = roster.iter().any(|visibility| visibility.is_changed());
+    let players_changed =
+        removed.read().count() > 0 || players.iter().any(|(_, name)| name.is_changed());
+    if !opened && !lists.is_changed() && !players_changed {
+        return;
+    }
+    let mut roster_players: Vec<(ClientId, String)> = players
+        .iter()
+        .map(|(id, name)| (id.client_id(), name.0.clone()))
+        .collect();
+    roster_players.sort_by(|a, b| a.1.cmp(&b.1));
+
+    for rows in rows.iter() {
+        commands
+            .entity(rows)
+            .despawn_descendants()
+            .with_children(|parent| {
+                if roster_players.is_empty() {
+                    parent.spawn((
+                        Text::new("Nobody else is here"),
+                        TextFont::from_font_size(12.0),
+                        TextColor(Color::srgb(0.7, 0.7, 0.7)),
+                    ));
+                }
+                for (client_id, name) in &roster_players {
+                    let relation = lists.0.relation(*client_id);
+                    parent
+                        .spawn(Node {
+                            column_gap: Val::Px(6.0),
+                            align_items: AlignItems::Center,
+                            ..default()
+                        })
+                        .with_children(|row| {
+                            row.spawn((
+                                Text::new(name.clone()),
+                                TextFont::from_font_size(12.0),
+                                TextColor(Color::WHITE),
+                                Node {
+                                    width: Val::Px(140.0),
+                                    ..default()
+                                },
+                            ));
+                            row.spawn(toggle_button(
+                                *client_id,
+                                Relation::Muted,
+                                relation == Some(Relation::Muted),
+                            ));
+                            row.spawn(toggle_button(
+                                *client_id,
+                                Relation::Blocked,
+                                relation == Some(Relation::Blocked),
+                            ));
+                        });
+                }
+            });
+    }
+}
+
+// Clicking a toggle sets its relation, or clears it if it's already set
+fn roster_buttons(
+    lists: Res<ClientSocialLists>,
+    buttons: Query<(&Interaction, &RosterToggle), Changed<Interaction>>,
+    mut client: ResMut<ConnectionManager>,
+    mut errors: EventWriter<ReportError>,
+) {
+    for (interaction, toggle) in buttons.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let relation = if lists.0.relation(toggle.player) == Some(toggle.relation) {
+            None
+        } else {
+            Some(toggle.relation)
+        };
+        client
+            .send_message::<Channel1, _>(&SetRelation {
+                player: toggle.player,
+                relation,
+            })
+            .unwrap_or_else(|e| {
+                errors.send(ReportError(GameError::send("SetRelation", e)));
+            });
+    }
+}
@@ -0,0 +1,175 @@
+use bevy::prelude::*;
+use lightyear::prelude::client::*;
+
+use crate::client::plugins::{ChatInput, PlaySound};
+use crate::protocol::Channel1;
+use crate::shared::error::{GameError, ReportError};
+use crate::shared::feature_flags::ServerFeatureFlags;
+use crate::shared::survival::{Breath, EatHeldItem, Health, Hunger, Spectator};
+
+// Breath fraction below which the low breath cue plays
+const LOW_BREATH: f32 = 0.3;
+// Key eating the held item, when it is food
+const EAT_KEY: KeyCode = KeyCode::KeyR;
+
+// Client-side HUD and audio cues for health, breath and hunger, and the eat key
+pub struct ClientSurvivalPlugin;
+
+impl Plugin for ClientSurvivalPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_survival_hud).add_systems(
+            Update,
+            (
+                update_survival_hud,
+                update_hunger_hud,
+                play_survival_cues,
+                eat_input,
+            ),
+        );
+    }
+}
+
+#[derive(Component)]
+struct HealthText;
+
+#[derive(Component)]
+struct BreathText;
+
+#[derive(Component)]
+struct HungerText;
+
+fn setup_survival_hud(mut commands: Commands) {
+    commands
+        .spawn(Node {
+            position_type: PositionType::Absolute,
+            right: Val::Px(10.0),
+            bottom: Val::Px(10.0),
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::End,
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(""),
+                TextFont::from_font_size(16.0),
+                TextColor(Color::srgb(0.9, 0.3, 0.3)),
+                HealthText,
+            ));
+            parent.spawn((
+                Text::new(""),
+                TextFont::from_font_size(16.0),
+                TextColor(Color::srgb(0.5, 0.8, 1.0)),
+                BreathText,
+            ));
+            parent.spawn((
+                Text::new(""),
+                TextFont::from_font_size(16.0),
+                TextColor(Color::srgb(0.9, 0.7, 0.3)),
+                HungerText,
+            ));
+        });
+}
+
+fn bar(current: f32, max: f32) -> String {
+    let filled = ((current / max).clamp(0.0, 1.0) * 10.0).round() as usize;
+    format!("{}{}", "#".repeat(filled), "-".repeat(10 - filled))
+}
+
+fn update_survival_hud(
+    player: Query<
+        (&Health, &Breath, Has<Spectator>),
+        (
+            With<Predicted>,
+            Or<(Changed<Health>, Changed<Breath>, Added<Spectator>)>,
+        ),
+    >,
+    mut health_text: Query<&mut Text, (With<HealthText>, Without<BreathText>)>,
+    mut breath_text: Query<&mut Text, (With<BreathText>, Without<HealthText>)>,
+) {
+    let Ok((health, breath, spectator)) = player.get_single() else {
+        return;
+    };
+    for mut text in health_text.iter_mut() {
+        text.0 = if spectator {
+            "You died for good, and can only watch now".to_string()
+        } else {
+            format!("HP [{}]", bar(health.current, health.max))
+        };
+    }
+    for mut text in breath_text.iter_mut() {
+        // only shown while holding breath
+        text.0 = if breath.current < breath.max {
+            format!("Air [{}]", bar(breath.current, breath.max))
+        } else {
+            String::new()
+        };
+    }
+}
+
+fn update_hunger_hud(
+    flags: Res<ServerFeatureFlags>,
+    player: Query<Ref<Hunger>, With<Predicted>>,
+    mut hunger_text: Query<&mut Text, With<HungerText>>,
+) {
+    let Ok(hunger) = player.get_single() else {
+        return;
+    };
+    if !hunger.is_changed() && !flags.is_changed() {
+        return;
+    }
+    for mut text in hunger_text.iter_mut() {
+        // nothing to watch on servers where hunger doesn't drain
+        text.0 = if flags.hunger && !flags.creative {
+            format!("Food [{}]", bar(hunger.current, hunger.max))
+        } else {
+            String::new()
+        };
+    }
+}
+
+fn eat_input(
+    keypress: Res<ButtonInput<KeyCode>>,
+    chat: Res<ChatInput>,
+    mut client: ResMut<ConnectionManager>,
+    mut errors: EventWriter<ReportError>,
+) {
+    if chat.open || !keypress.just_pressed(EAT_KEY) {
+        return;
+    }
+    client
+        .send_message::<Channel1, _>(&EatHeldItem)
+        .unwrap_or_else(|e| {
+            errors.send(ReportError(GameError::send("EatHeldItem", e)));
+        });
+}
+
+// Short tones when breath runs low and while taking drowning damage
+fn play_survival_cues(
+    mut sounds: EventWriter<PlaySound>,
+    player: Query<(&Health, &Breath), With<Predicted>>,
+    mut last: Local<Option<(f32, f32)>>,
+) {
+    let Ok((health, breath)) = player.get_single() else {
+        return;
+    };
+    let previous = last.replace((health.current, breath.current));
+    let Some((previous_health, previous_breath)) = previous else {
+        return;
+    };
+
+    let low_breath = breath.max * LOW_BREATH;
+    let cue = if breath.current < low_breath && previous_breath >= low_breath {
+        Some(880.0)
+    } else if health.current < previous_health && breath.current == 0.0 {
+        Some(220.0)
+    } else {
+        None
+    };
+    if let Some(frequency) = cue {
+        sounds.send(PlaySound::tone(
+            frequency,
+            std::time::Duration::from_millis(150),
+            0.3,
+        ));
+    }
+}
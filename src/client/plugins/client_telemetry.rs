@@ -0,0 +1,259 @@
+use bevy::app::AppExit;
+use bevy::asset::ron;
+use bevy::prelude::*;
+use bevy::tasks::{block_on, futures_lite::future, IoTaskPool, Task};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::client::plugins::ClientWorldState;
+use crate::settings_common::{Settings, TelemetrySettings};
+use crate::shared::error::{GameError, ReportError};
+
+// Reports waiting to be sent, kept across sessions so that offline play loses nothing
+const QUEUE_PATH: &str = "telemetry/queue.ron";
+// Reports kept at most while the endpoint can't be reached; the oldest ones are dropped first
+const MAX_QUEUED: usize = 500;
+// Reports sent in one request
+const BATCH_SIZE: usize = 50;
+// How often the number of loaded chunks is sampled
+const SAMPLE_INTERVAL: f32 = 5.0;
+
+// Client plugin collecting anonymous statistics about the session, for players who opted in from
+// the settings. Statistics are cut into a report every flush interval, queued on disk, and sent
+// in batches whenever the endpoint can be reached.
+pub struct ClientTelemetryPlugin;
+
+impl Plugin for ClientTelemetryPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<FeatureUsed>()
+            .init_resource::<Telemetry>()
+            .add_systems(Startup, setup_telemetry)
+            .add_systems(
+                Update,
+                (sample_session, flush_reports, finish_upload, save_on_exit)
+                    .chain()
+                    .run_if(|telemetry: Res<Telemetry>| telemetry.enabled),
+            );
+    }
+}
+
+/// Sent by other client plugins when the player uses one of their features, e.g. opens a panel.
+/// Only the name of the feature and how often it was used end up in the reports.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct FeatureUsed(pub &'static str);
+
+/// Statistics of part of a session. Reports of the same session share its id, which is drawn at
+/// random when the game starts and says nothing about the player.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct TelemetryReport {
+    session: u64,
+    /// Seconds of play covered by the report
+    seconds: u64,
+    average_loaded_chunks: f32,
+    /// Times each feature was used
+    features: BTreeMap<String, u32>,
+}
+
+#[derive(Resource, Default)]
+struct Telemetry {
+    enabled: bool,
+    endpoint: String,
+    flush_timer: Timer,
+    sample_timer: Timer,
+    session: u64,
+    // Statistics since the last report
+    seconds: f32,
+    chunk_samples: Vec<usize>,
+    features: BTreeMap<String, u32>,
+    queue: Vec<TelemetryReport>,
+    // Batch being sent, and the number of reports it holds
+    upload: Option<(Task<Result<(), GameError>>, usize)>,
+}
+
+impl Telemetry {
+    // Cut the statistics gathered since the last report into a new one
+    fn cut_report(&mut self) {
+        if self.seconds < 1.0 {
+            return;
+        }
+        let average_loaded_chunks = if self.chunk_samples.is_empty() {
+            0.0
+        } else {
+            self.chunk_samples.iter().sum::<usize>() as f32 / self.chunk_samples.len() as f32
+        };
+        self.queue.push(TelemetryReport {
+            session: self.session,
+            seconds: self.seconds as u64,
+            average_loaded_chunks,
+            features: std::mem::take(&mut self.features),
+        });
+        self.seconds = 0.0;
+        self.chunk_samples.clear();
+        let dropped = self.queue.len().saturating_sub(MAX_QUEUED);
+        self.queue.drain(..dropped);
+        if let Some((_, sent)) = self.upload.as_mut() {
+            *sent = sent.saturating_sub(dropped);
+        }
+    }
+}
+
+fn load_queue(path: &Path) -> Result<Vec<TelemetryReport>, GameError> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let text = fs::read_to_string(path)?;
+    ron::from_str(&text).map_err(|e| GameError::Telemetry(e.to_string()))
+}
+
+fn save_queue(path: &Path, queue: &[TelemetryReport]) -> Result<(), GameError> {
+    let text = ron::to_string(queue).map_err(|e| GameError::Telemetry(e.to_string()))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // write then rename, so that quitting mid-write never loses the reports already queued
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, text)?;
+    Ok(fs::rename(tmp, path)?)
+}
+
+#[cfg(feature = "telemetry")]
+fn post_reports(endpoint: &str, reports: &[TelemetryReport]) -> Result<(), GameError> {
+    let body = serde_json::to_vec(reports).map_err(|e| GameError::Telemetry(e.to_string()))?;
+    ureq::post(endpoint)
+        .set("Content-Type", "application/json")
+        .send_bytes(&body)
+        .map_err(|e| GameError::Telemetry(e.to_string()))?;
+    Ok(())
+}
+
+#[cfg(not(feature = "telemetry"))]
+fn post_reports(_endpoint: &str, _reports: &[TelemetryReport]) -> Result<(), GameError> {
+    Err(GameError::Telemetry(
+        "the game was built without the telemetry feature".to_string(),
+    ))
+}
+
+fn setup_telemetry(
+    settings: Option<Res<Settings>>,
+    mut telemetry: ResMut<Telemetry>,
+    mut errors: EventWriter<ReportError>,
+) {
+    let settings = settings.map_or_else(TelemetrySettings::default, |settings| {
+        settings.client.telemetry.clone()
+    });
+    let path = Path::new(QUEUE_PATH);
+    if !settings.enabled {
+        // opting out also takes back whatever was collected before
+        if path.exists() {
+            if let Err(e) = fs::remove_file(path) {
+                errors.send(ReportError(e.into()));
+            }
+        }
+        return;
+    }
+    if !cfg!(feature = "telemetry") {
+        warn!("Telemetry is enabled in the settings, but the game was built without it");
+        return;
+    }
+    let queue = load_queue(path).unwrap_or_else(|e| {
+        errors.send(ReportError(e));
+        Vec::new()
+    });
+    info!(
+        "Telemetry enabled: anonymous statistics are sent to {} ({} reports queued)",
+        settings.endpoint,
+        queue.len()
+    );
+    *telemetry = Telemetry {
+        enabled: true,
+        endpoint: settings.endpoint,
+        flush_timer: Timer::new(settings.flush_interval, TimerMode::Repeating),
+        sample_timer: Timer::from_seconds(SAMPLE_INTERVAL, TimerMode::Repeating),
+        session: rand::random(),
+        queue,
+        ..default()
+    };
+}
+
+fn sample_session(
+    time: Res<Time>,
+    client_world: Res<ClientWorldState>,
+    mut features: EventReader<FeatureUsed>,
+    mut telemetry: ResMut<Telemetry>,
+) {
+    telemetry.seconds += time.delta_secs();
+    if telemetry.sample_timer.tick(time.delta()).just_finished() {
+        telemetry
+            .chunk_samples
+            .push(client_world.loaded_chunks.len());
+    }
+    for FeatureUsed(feature) in features.read() {
+        *telemetry.features.entry(feature.to_string()).or_default() += 1;
+    }
+}
+
+// Queue a report of the last interval, and send the oldest queued reports if nothing is being
+// sent already
+fn flush_reports(
+    time: Res<Time>,
+    mut telemetry: ResMut<Telemetry>,
+    mut errors: EventWriter<ReportError>,
+) {
+    if !telemetry.flush_timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    telemetry.cut_report();
+    if let Err(e) = save_queue(Path::new(QUEUE_PATH), &telemetry.queue) {
+        errors.send(ReportError(e));
+    }
+    if telemetry.upload.is_some() || telemetry.queue.is_empty() {
+        return;
+    }
+    let batch: Vec<TelemetryReport> = telemetry.queue.iter().take(BATCH_SIZE).cloned().collect();
+    let endpoint = telemetry.endpoint.clone();
+    let sent = batch.len();
+    let task = IoTaskPool::get().spawn(async move { post_reports(&endpoint, &batch) });
+    telemetry.upload = Some((task, sent));
+}
+
+// Sent reports leave the queue; the others stay until the endpoint can be reached again
+fn finish_upload(mut telemetry: ResMut<Telemetry>, mut errors: EventWriter<ReportError>) {
+    let Some((task, _)) = telemetry.upload.as_mut() else {
+        return;
+    };
+    let Some(result) = block_on(future::poll_once(task)) else {
+        return;
+    };
+    let Some((_, sent)) = telemetry.upload.take() else {
+        return;
+    };
+    match result {
+        Ok(()) => {
+            // reports are only ever added at the back, so the batch is still at the front
+            let sent = sent.min(telemetry.queue.len());
+            telemetry.queue.drain(..sent);
+            if let Err(e) = save_queue(Path::new(QUEUE_PATH), &telemetry.queue) {
+                errors.send(ReportError(e));
+            }
+        }
+        // most likely offline, so not worth reporting as an error
+        Err(e) => debug!("Telemetry reports kept for later: {}", e),
+    }
+}
+
+// The end of the session is queued, to be sent next time the game starts
+fn save_on_exit(
+    mut exit: EventReader<AppExit>,
+    mut telemetry: ResMut<Telemetry>,
+    mut errors: EventWriter<ReportError>,
+) {
+    if exit.read().count() == 0 {
+        return;
+    }
+    telemetry.cut_report();
+    if let Err(e) = save_queue(Path::new(QUEUE_PATH), &telemetry.queue) {
+        errors.send(ReportError(e));
+    }
+}
@@ -0,0 +1,116 @@
+use bevy::prelude::*;
+use lightyear::prelude::client::*;
+
+use crate::client::plugins::ChatLog;
+use crate::shared::temperature::{
+    CurrentWeather, Exposure, Hazard, Weather, WeatherChanged, MAX_EXPOSURE,
+};
+
+const COLD_COLOR: Color = Color::srgb(0.6, 0.85, 1.0);
+const HEAT_COLOR: Color = Color::srgb(1.0, 0.6, 0.2);
+
+// Client-side weather and the HUD warning shown while getting too cold or too hot
+pub struct ClientTemperaturePlugin;
+
+impl Plugin for ClientTemperaturePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_hazard_warning)
+            .add_systems(Update, (sync_weather, update_hazard_warning).chain());
+    }
+}
+
+#[derive(Component)]
+struct HazardWarning;
+
+fn setup_hazard_warning(mut commands: Commands) {
+    commands
+        .spawn(Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(40.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(""),
+                TextFont::from_font_size(18.0),
+                TextColor(COLD_COLOR),
+                HazardWarning,
+            ));
+        });
+}
+
+fn sync_weather(
+    mut events: EventReader<MessageEvent<WeatherChanged>>,
+    mut weather: ResMut<CurrentWeather>,
+    mut log: ResMut<ChatLog>,
+) {
+    let Some(event) = events.read().last() else {
+        return;
+    };
+    let previous = std::mem::replace(&mut weather.0, event.message().0);
+    let text = match (previous, weather.0) {
+        (_, Weather::Blizzard) => "A blizzard is blowing over the tundras",
+        (_, Weather::Sandstorm) => "A sandstorm is blowing over the deserts",
+        (Weather::Blizzard | Weather::Sandstorm, Weather::Clear) => "The storm has passed",
+        (Weather::Clear, Weather::Clear) => return,
+    };
+    log.push(text.to_string());
+}
+
+fn warning(exposure: &Exposure, weather: Weather) -> Option<(String, Color)> {
+    let hazard = exposure.hazard?;
+    let storm = if hazard.storm(weather) {
+        let name = format!("{:?}", weather).to_lowercase();
+        format!(" The {} makes it worse.", name)
+    } else {
+        String::new()
+    };
+    let (feeling, dying, advice, color) = match hazard {
+        Hazard::Cold => (
+            "You are cold",
+            "You are freezing",
+            "Hold a torch to keep warm.",
+            COLD_COLOR,
+        ),
+        Hazard::Heat => (
+            "You are hot",
+            "You are overheating",
+            "Drink water (R) or wade into it.",
+            HEAT_COLOR,
+        ),
+    };
+    if exposure.protected {
+        // nothing to warn about while the player is doing the right thing
+        return (exposure.level > 0.0).then(|| (format!("{}, but recovering", feeling), color));
+    }
+    let filled = (exposure.level / MAX_EXPOSURE * 10.0)
+        .round()
+        .clamp(0.0, 10.0) as usize;
+    let bar = format!("{}{}", "#".repeat(filled), "-".repeat(10 - filled));
+    let feeling = if exposure.level >= MAX_EXPOSURE {
+        dying
+    } else {
+        feeling
+    };
+    Some((format!("{} [{}] {}{}", feeling, bar, advice, storm), color))
+}
+
+fn update_hazard_warning(
+    weather: Res<CurrentWeather>,
+    player: Query<Ref<Exposure>, With<Predicted>>,
+    mut text: Query<(&mut Text, &mut TextColor), With<HazardWarning>>,
+) {
+    let Ok(exposure) = player.get_single() else {
+        return;
+    };
+    if !exposure.is_changed() && !weather.is_changed() {
+        return;
+    }
+    let (content, color) = warning(&exposure, weather.0).unwrap_or((String::new(), COLD_COLOR));
+    for (mut text, mut text_color) in text.iter_mut() {
+        text.0 = content.clone();
+        text_color.0 = color;
+    }
+}
@@ -0,0 +1,94 @@
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use lightyear::prelude::client::*;
+
+use crate::client::plugins::{hovered_tile, ChatInput, TileProjection, WorldCamera};
+use crate::protocol::{Channel1, TerrainAction, TerrainEditRequest};
+use crate::shared::world_generation::TileType;
+
+// Keys digging and raising the hovered tile, and building structures on it
+const DIG_KEY: KeyCode = KeyCode::KeyG;
+const RAISE_KEY: KeyCode = KeyCode::KeyT;
+const STAIRS_KEY: KeyCode = KeyCode::KeyB;
+const FURNACE_KEY: KeyCode = KeyCode::KeyU;
+const DOOR_KEY: KeyCode = KeyCode::KeyO;
+const GATE_KEY: KeyCode = KeyCode::KeyX;
+// with shift held, the rail key puts a cart on the rails instead
+const RAIL_KEY: KeyCode = KeyCode::KeyZ;
+// shift turns the furnace key into the chest key, and the door key into the inserter key
+const SHIFT: KeyCode = KeyCode::ShiftLeft;
+const CHEST_KEY: KeyCode = KeyCode::KeyU;
+const INSERTER_KEY: KeyCode = KeyCode::KeyO;
+// with shift held, the dig, raise and stairs keys build a generator, an assembler and a wire
+const GENERATOR_KEY: KeyCode = KeyCode::KeyG;
+const ASSEMBLER_KEY: KeyCode = KeyCode::KeyT;
+const WIRE_KEY: KeyCode = KeyCode::KeyB;
+// with shift held, the gate key lays stone ground instead, and with control held, water
+const PLACE_KEY: KeyCode = KeyCode::KeyX;
+const WATER_MODIFIER: KeyCode = KeyCode::ControlLeft;
+
+// Client-side terrain tools; the server validates edits and sends back the modified tiles
+pub struct ClientTerrainPlugin;
+
+impl Plugin for ClientTerrainPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, edit_hovered_tile);
+    }
+}
+
+fn edit_hovered_tile(
+    keypress: Res<ButtonInput<KeyCode>>,
+    chat: Res<ChatInput>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<WorldCamera>>,
+    projection: Res<TileProjection>,
+    mut client: ResMut<ConnectionManager>,
+) {
+    if chat.open {
+        return;
+    }
+    let shift = keypress.pressed(SHIFT);
+    let action = if keypress.just_pressed(GENERATOR_KEY) && shift {
+        TerrainAction::Generator
+    } else if keypress.just_pressed(ASSEMBLER_KEY) && shift {
+        TerrainAction::Assembler
+    } else if keypress.just_pressed(WIRE_KEY) && shift {
+        TerrainAction::Wire
+    } else if keypress.just_pressed(DIG_KEY) {
+        TerrainAction::Dig
+    } else if keypress.just_pressed(RAISE_KEY) {
+        TerrainAction::Raise
+    } else if keypress.just_pressed(STAIRS_KEY) {
+        TerrainAction::Stairs
+    } else if keypress.just_pressed(CHEST_KEY) && shift {
+        TerrainAction::Chest
+    } else if keypress.just_pressed(FURNACE_KEY) {
+        TerrainAction::Furnace
+    } else if keypress.just_pressed(INSERTER_KEY) && shift {
+        TerrainAction::Inserter
+    } else if keypress.just_pressed(DOOR_KEY) {
+        TerrainAction::Door
+    } else if keypress.just_pressed(PLACE_KEY) && keypress.pressed(WATER_MODIFIER) {
+        TerrainAction::Place(TileType::ShallowWater)
+    } else if keypress.just_pressed(PLACE_KEY) && shift {
+        TerrainAction::Place(TileType::Stone)
+    } else if keypress.just_pressed(GATE_KEY) {
+        TerrainAction::Gate
+    } else if keypress.just_pressed(RAIL_KEY) && !shift {
+        TerrainAction::Rail
+    } else {
+        return;
+    };
+    let (Ok(window), Ok((camera, camera_transform))) = (windows.get_single(), cameras.get_single())
+    else {
+        return;
+    };
+    let Some(tile) = hovered_tile(window, camera, camera_transform, &projection) else {
+        return;
+    };
+    client
+        .send_message::<Channel1, _>(&TerrainEditRequest { tile, action })
+        .unwrap_or_else(|e| {
+            error!("Failed to send terrain edit: {:?}", e);
+        });
+}
@@ -0,0 +1,61 @@
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use lightyear::prelude::client::*;
+
+use crate::client::plugins::{ChatInput, TileProjection, WorldCamera};
+use crate::protocol::{ChatChannel, ChatMessage};
+use crate::shared::world_generation::WorldGrid;
+
+// Key that asks the server for the modification history of the hovered tile
+const INSPECT_KEY: KeyCode = KeyCode::KeyI;
+
+// Client-side tool to inspect the tile under the cursor
+pub struct ClientTileInspectPlugin;
+
+impl Plugin for ClientTileInspectPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, inspect_hovered_tile);
+    }
+}
+
+/// World tile under the cursor. Tiles are rendered one world unit apart, centered on integer positions,
+/// and then projected on screen.
+pub fn hovered_tile(
+    window: &Window,
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+    projection: &TileProjection,
+) -> Option<(i32, i32)> {
+    let cursor = window.cursor_position()?;
+    let screen = camera.viewport_to_world_2d(camera_transform, cursor).ok()?;
+    let world = projection.to_world(screen);
+    Some(WorldGrid::world_to_tile(world))
+}
+
+// Send an /inspect command for the hovered tile; the reply shows up in the chat log
+fn inspect_hovered_tile(
+    keypress: Res<ButtonInput<KeyCode>>,
+    chat: Res<ChatInput>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<WorldCamera>>,
+    projection: Res<TileProjection>,
+    mut client: ResMut<ConnectionManager>,
+) {
+    if chat.open || !keypress.just_pressed(INSPECT_KEY) {
+        return;
+    }
+    let (Ok(window), Ok((camera, camera_transform))) = (windows.get_single(), cameras.get_single())
+    else {
+        return;
+    };
+    let Some((x, y)) = hovered_tile(window, camera, camera_transform, &projection) else {
+        return;
+    };
+    client
+        .send_message::<ChatChannel, _>(&ChatMessage {
+            text: format!("/inspect {} {}", x, y),
+        })
+        .unwrap_or_else(|e| {
+            error!("Failed to send inspect request: {:?}", e);
+        });
+}
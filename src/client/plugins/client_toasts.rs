@@ -0,0 +1,101 @@
+use bevy::prelude::*;
+
+// Seconds a toast stays on screen
+const TOAST_SECS: f32 = 5.0;
+// Toasts shown at once; older ones are dismissed early
+const MAX_TOASTS: usize = 3;
+
+// Client plugin showing short notifications at the top of the screen
+pub struct ClientToastsPlugin;
+
+impl Plugin for ClientToastsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ShowToast>()
+            .add_systems(Startup, setup_toasts)
+            .add_systems(Update, (show_toasts, expire_toasts).chain());
+    }
+}
+
+/// Show a notification with a title and a line of text
+#[derive(Event, Clone, Debug)]
+pub struct ShowToast {
+    pub title: String,
+    pub body: String,
+}
+
+#[derive(Component)]
+struct ToastArea;
+
+#[derive(Component)]
+struct Toast {
+    shown_at: f32,
+}
+
+fn setup_toasts(mut commands: Commands) {
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.0),
+            width: Val::Percent(100.0),
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            row_gap: Val::Px(4.0),
+            ..default()
+        },
+        ToastArea,
+    ));
+}
+
+fn show_toasts(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut events: EventReader<ShowToast>,
+    area: Query<Entity, With<ToastArea>>,
+) {
+    let Ok(area) = area.get_single() else {
+        return;
+    };
+    for event in events.read() {
+        let toast = commands
+            .spawn((
+                Node {
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    padding: UiRect::axes(Val::Px(12.0), Val::Px(6.0)),
+                    ..default()
+                },
+                BackgroundColor(Color::srgba(0.1, 0.1, 0.2, 0.85)),
+                Toast {
+                    shown_at: time.elapsed_secs(),
+                },
+            ))
+            .with_children(|parent| {
+                parent.spawn((
+                    Text::new(event.title.clone()),
+                    TextFont::from_font_size(16.0),
+                    TextColor(Color::srgb(1.0, 0.85, 0.3)),
+                ));
+                parent.spawn((
+                    Text::new(event.body.clone()),
+                    TextFont::from_font_size(13.0),
+                    TextColor(Color::WHITE),
+                ));
+            })
+            .id();
+        commands.entity(area).add_child(toast);
+    }
+}
+
+fn expire_toasts(mut commands: Commands, time: Res<Time>, toasts: Query<(Entity, &Toast)>) {
+    let now = time.elapsed_secs();
+    let mut shown: Vec<(Entity, f32)> = toasts
+        .iter()
+        .map(|(entity, toast)| (entity, toast.shown_at))
+        .collect();
+    shown.sort_by(|a, b| b.1.total_cmp(&a.1));
+    for (index, (entity, shown_at)) in shown.into_iter().enumerate() {
+        if index >= MAX_TOASTS || now - shown_at >= TOAST_SECS {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
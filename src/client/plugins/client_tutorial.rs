@@ -0,0 +1,106 @@
+use bevy::prelude::*;
+use lightyear::prelude::client::*;
+
+use crate::client::plugins::TileProjection;
+use crate::shared::tutorial::{Tutorial, TutorialStep};
+
+// Size of the marker drawn over the tile a step takes place on, in tiles
+const MARKER_SIZE: f32 = 0.4;
+// Height of the marker above the tile, in tiles
+const MARKER_OFFSET: f32 = 0.8;
+// Marker bobbing speed, in radians per second
+const MARKER_BOB_SPEED: f32 = 4.0;
+
+// Client plugin showing the current tutorial step and where it takes place
+pub struct ClientTutorialPlugin;
+
+impl Plugin for ClientTutorialPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_tutorial_hud)
+            .add_systems(Update, (update_tutorial_hud, place_tutorial_marker));
+    }
+}
+
+#[derive(Component)]
+struct TutorialPrompt;
+
+#[derive(Component)]
+struct TutorialMarker;
+
+fn setup_tutorial_hud(mut commands: Commands) {
+    commands
+        .spawn(Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(80.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(""),
+                TextFont::from_font_size(18.0),
+                TextColor(Color::srgb(1.0, 0.95, 0.6)),
+                TextLayout::new_with_justify(JustifyText::Center),
+                Visibility::Hidden,
+                TutorialPrompt,
+            ));
+        });
+    commands.spawn((
+        Sprite {
+            custom_size: Some(Vec2::splat(MARKER_SIZE)),
+            color: Color::srgb(1.0, 0.85, 0.2),
+            ..default()
+        },
+        Transform::default(),
+        Visibility::Hidden,
+        TutorialMarker,
+    ));
+}
+
+fn update_tutorial_hud(
+    player: Query<Option<Ref<Tutorial>>, With<Predicted>>,
+    mut prompt: Query<(&mut Text, &mut Visibility), With<TutorialPrompt>>,
+) {
+    let Ok(tutorial) = player.get_single() else {
+        return;
+    };
+    for (mut text, mut visibility) in prompt.iter_mut() {
+        let Some(tutorial) = tutorial.as_ref() else {
+            visibility.set_if_neq(Visibility::Hidden);
+            continue;
+        };
+        visibility.set_if_neq(Visibility::Inherited);
+        if tutorial.is_changed() {
+            text.0 = format!(
+                "Tutorial {}/{}: {}\n(type /tutorial skip to skip it)",
+                tutorial.step.index() + 1,
+                TutorialStep::ALL.len(),
+                tutorial.step.prompt()
+            );
+        }
+    }
+}
+
+fn place_tutorial_marker(
+    time: Res<Time>,
+    projection: Res<TileProjection>,
+    player: Query<&Tutorial, With<Predicted>>,
+    mut marker: Query<(&mut Transform, &mut Visibility), With<TutorialMarker>>,
+) {
+    let target = player
+        .get_single()
+        .ok()
+        .and_then(|tutorial| tutorial.marker);
+    for (mut transform, mut visibility) in marker.iter_mut() {
+        let Some(target) = target else {
+            visibility.set_if_neq(Visibility::Hidden);
+            continue;
+        };
+        visibility.set_if_neq(Visibility::Inherited);
+        let bob = (time.elapsed_secs() * MARKER_BOB_SPEED).sin() * 0.1;
+        let world = target + Vec2::Y * (MARKER_OFFSET + bob);
+        let screen = projection.to_screen(world);
+        transform.translation = screen.extend(0.9 + projection.depth(world));
+    }
+}
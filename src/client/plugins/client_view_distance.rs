@@ -0,0 +1,226 @@
+use bevy::prelude::*;
+use lightyear::prelude::client::*;
+use std::time::Duration;
+
+use crate::client::plugins::client_world::REQUEST_TIMEOUT;
+use crate::client::plugins::ClientWorldState;
+use crate::protocol::ViewDistanceLimit;
+
+// Round trip time above which the connection counts as degraded
+const DEGRADED_RTT: Duration = Duration::from_millis(250);
+// Round trip time below which it counts as healthy again; the gap avoids flapping
+const HEALTHY_RTT: Duration = Duration::from_millis(150);
+// Unanswered chunk requests above which the link counts as saturated
+const DEGRADED_STALE_REQUESTS: usize = 8;
+// How long a condition must hold before the view distance shrinks, and before it grows back
+const DEGRADE_AFTER: Duration = Duration::from_secs(2);
+const RECOVER_AFTER: Duration = Duration::from_secs(10);
+// The view distance never shrinks below this many chunks around the player
+const MIN_VIEW_DISTANCE: i32 = 1;
+// Chunk requests sent per frame while the view distance is reduced
+const DEGRADED_REQUESTS_PER_FRAME: usize = 4;
+
+// Client plugin shrinking the view distance (and the chunk request rate) on a poor connection,
+// and restoring it once the connection recovers. The view distance also never goes beyond the
+// limit set by the server, which shrinks it while under load.
+pub struct ClientViewDistancePlugin;
+
+impl Plugin for ClientViewDistancePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AdaptiveViewDistance>()
+            .add_systems(Startup, setup_view_distance_hud)
+            .add_systems(
+                Update,
+                (
+                    receive_server_limit,
+                    adapt_view_distance,
+                    update_view_distance_hud,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Why the view distance is currently reduced
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ViewLimit {
+    HighLatency(Duration),
+    /// Chunk requests pile up faster than the server's answers arrive
+    LowBandwidth(usize),
+}
+
+#[derive(Resource, Debug)]
+pub struct AdaptiveViewDistance {
+    /// View distance when the connection is healthy, taken from `ClientWorldState` at startup
+    pub base: Option<i32>,
+    /// Chunks removed from the base view distance
+    pub reduction: i32,
+    pub limit: Option<ViewLimit>,
+    /// Limit last received from the server
+    pub server_limit: Option<ViewDistanceLimit>,
+    // Time spent in the current connection state, degraded or healthy
+    degraded_for: Duration,
+    healthy_for: Duration,
+}
+
+impl Default for AdaptiveViewDistance {
+    fn default() -> Self {
+        Self {
+            base: None,
+            reduction: 0,
+            limit: None,
+            server_limit: None,
+            degraded_for: Duration::ZERO,
+            healthy_for: Duration::ZERO,
+        }
+    }
+}
+
+impl AdaptiveViewDistance {
+    /// Classify the connection: `Some` if degraded, `None` if healthy. Anything in between keeps
+    /// the current state's timers where they are.
+    fn classify(&self, rtt: Duration, stale_requests: usize) -> Option<Option<ViewLimit>> {
+        if rtt > DEGRADED_RTT {
+            Some(Some(ViewLimit::HighLatency(rtt)))
+        } else if stale_requests > DEGRADED_STALE_REQUESTS {
+            Some(Some(ViewLimit::LowBandwidth(stale_requests)))
+        } else if rtt < HEALTHY_RTT && stale_requests == 0 {
+            Some(None)
+        } else {
+            None
+        }
+    }
+}
+
+fn receive_server_limit(
+    mut events: EventReader<MessageEvent<ViewDistanceLimit>>,
+    mut adaptive: ResMut<AdaptiveViewDistance>,
+) {
+    for event in events.read() {
+        let limit = event.message;
+        if limit.reduced {
+            warn!(
+                "Server under load, view distance limited to {}",
+                limit.max_chunks
+            );
+        }
+        adaptive.server_limit = Some(limit);
+    }
+}
+
+fn adapt_view_distance(
+    time: Res<Time>,
+    connection: Res<ConnectionManager>,
+    mut adaptive: ResMut<AdaptiveViewDistance>,
+    mut client_world: ResMut<ClientWorldState>,
+) {
+    let base = *adaptive.base.get_or_insert(client_world.view_distance);
+    let stale_requests = client_world
+        .requested_chunks
+        .values()
+        .filter(|frame| client_world.frame_counter.saturating_sub(**frame) > REQUEST_TIMEOUT)
+        .count();
+
+    match adaptive.classify(connection.rtt(), stale_requests) {
+        Some(Some(limit)) => {
+            adaptive.healthy_for = Duration::ZERO;
+            adaptive.degraded_for += time.delta();
+            if adaptive.degraded_for >= DEGRADE_AFTER
+                && base - adaptive.reduction > MIN_VIEW_DISTANCE
+            {
+                adaptive.degraded_for = Duration::ZERO;
+                adaptive.reduction += 1;
+                adaptive.limit = Some(limit);
+                warn!(
+                    "Connection degraded ({:?}), reducing view distance to {}",
+                    limit,
+                    base - adaptive.reduction
+                );
+            }
+        }
+        Some(None) => {
+            adaptive.degraded_for = Duration::ZERO;
+            adaptive.healthy_for += time.delta();
+            if adaptive.healthy_for >= RECOVER_AFTER && adaptive.reduction > 0 {
+                adaptive.healthy_for = Duration::ZERO;
+                adaptive.reduction -= 1;
+                info!(
+                    "Connection recovered, increasing view distance to {}",
+                    base - adaptive.reduction
+                );
+                if adaptive.reduction == 0 {
+                    adaptive.limit = None;
+                }
+            }
+        }
+        None => {}
+    }
+
+    let view_distance = adaptive
+        .server_limit
+        .map_or(base - adaptive.reduction, |limit| {
+            (base - adaptive.reduction).min(limit.max_chunks)
+        });
+    if client_world.view_distance != view_distance {
+        client_world.view_distance = view_distance;
+        // forces the visible chunks to be recomputed with the new distance
+        client_world.player_chunk = None;
+    }
+    let max_requests = (adaptive.reduction > 0).then_some(DEGRADED_REQUESTS_PER_FRAME);
+    if client_world.max_requests_per_frame != max_requests {
+        client_world.max_requests_per_frame = max_requests;
+    }
+}
+
+#[derive(Component)]
+struct ViewDistanceText;
+
+fn setup_view_distance_hud(mut commands: Commands) {
+    commands.spawn((
+        Text::new(""),
+        TextFont::from_font_size(14.0),
+        TextColor(Color::srgb(1.0, 0.8, 0.3)),
+        Node {
+            position_type: PositionType::Absolute,
+            right: Val::Px(10.0),
+            top: Val::Px(10.0),
+            ..default()
+        },
+        ViewDistanceText,
+    ));
+}
+
+fn update_view_distance_hud(
+    adaptive: Res<AdaptiveViewDistance>,
+    client_world: Res<ClientWorldState>,
+    mut texts: Query<&mut Text, With<ViewDistanceText>>,
+) {
+    if !adaptive.is_changed() {
+        return;
+    }
+    let text = match adaptive.limit.filter(|_| adaptive.reduction > 0) {
+        Some(ViewLimit::HighLatency(rtt)) => format!(
+            "View distance reduced to {}: high latency ({}ms)",
+            client_world.view_distance,
+            rtt.as_millis()
+        ),
+        Some(ViewLimit::LowBandwidth(pending)) => format!(
+            "View distance reduced to {}: slow connection ({} chunks pending)",
+            client_world.view_distance, pending
+        ),
+        None => match adaptive.server_limit {
+            Some(limit) if limit.reduced && client_world.view_distance >= limit.max_chunks => {
+                format!(
+                    "View distance reduced to {}: server under heavy load",
+                    client_world.view_distance
+                )
+            }
+            _ => String::new(),
+        },
+    };
+    for mut hud in texts.iter_mut() {
+        if hud.0 != text {
+            hud.0 = text.clone();
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use bevy::prelude::*;
+
+use crate::client::plugins::TileProjection;
+use crate::shared::npc::Npc;
+use crate::shared::villages::Villager;
+
+const VILLAGER_COLOR: Color = Color::srgb(0.35, 0.6, 0.35);
+// Size of a villager relative to a tile
+const VILLAGER_SIZE: f32 = 0.6;
+// Villagers are drawn over decorations and dropped items
+const VILLAGER_Z: f32 = 0.6;
+
+// Client plugin drawing the villagers living in villages
+pub struct ClientVillagesPlugin;
+
+impl Plugin for ClientVillagesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (spawn_villager_sprites, place_villagers).chain());
+    }
+}
+
+fn spawn_villager_sprites(mut commands: Commands, villagers: Query<Entity, Added<Villager>>) {
+    for entity in villagers.iter() {
+        commands.entity(entity).insert((
+            Sprite {
+                custom_size: Some(Vec2::splat(VILLAGER_SIZE)),
+                color: VILLAGER_COLOR,
+                ..default()
+            },
+            Transform::default(),
+        ));
+    }
+}
+
+// Position villagers on screen as they walk around, according to the current projection
+fn place_villagers(
+    projection: Res<TileProjection>,
+    mut villagers: Query<(Ref<Npc>, &mut Transform), (With<Villager>, With<Sprite>)>,
+) {
+    for (npc, mut transform) in villagers.iter_mut() {
+        if !npc.is_changed() && !projection.is_changed() {
+            continue;
+        }
+        let screen = projection.to_screen(npc.position);
+        transform.translation = screen.extend(VILLAGER_Z + projection.depth(npc.position));
+    }
+}
@@ -3,41 +3,43 @@ use lightyear::prelude::client::*;
 use std::collections::{HashMap, HashSet};
 
 use crate::protocol::*;
+use crate::shared::chunk_format::ChunkFormat;
+use crate::shared::error::{GameError, ReportError};
+use crate::shared::seasons::{CurrentSeason, SeasonChanged};
 use crate::shared::world_generation::{
-    deserialize_chunk, Chunk, ChunkChannel, ChunkCoord, ChunkData, ChunkRequest, ResourceType,
-    TileType, WorldConfig,
+    deserialize_chunk, is_newer_version, Chunk, ChunkChannel, ChunkCoord, ChunkData, ChunkDelta,
+    ChunkRequest, ChunkUnload, DeltaOutcome, ResourceType, TileType, WorldConfig, WorldState,
 };
 
+// Frames after which an unanswered chunk request is sent again (~2 seconds)
+pub const REQUEST_TIMEOUT: u32 = 120;
+
 // Client-side plugin for handling world data
 pub struct ClientWorldPlugin;
 
 impl Plugin for ClientWorldPlugin {
     fn build(&self, app: &mut App) {
         info!("Building ClientWorldPlugin");
-        app.insert_resource(ClientWorldState {
-            visible_chunks: HashSet::new(),
-            loaded_chunks: HashSet::new(),
-            requested_chunks: HashMap::new(),
-            player_chunk: None,
-            view_distance: 2, // Default view distance in chunks
-            frame_counter: 0, // Track how many frames we've processed
-        })
-        .add_systems(
-            Update,
-            (
-                // First update player position and calculate visible chunks
-                update_visible_chunks,
-                // Clean up chunks that are no longer visible
-                cleanup_invisible_chunks,
-                // Then process any received chunk data
-                handle_chunk_data,
-                // Finally request any chunks we still need
-                request_visible_chunks,
-                // Debug system to monitor chunk state
-                debug_chunk_state,
+        app.init_resource::<ClientWorldState>()
+            .add_systems(
+                Update,
+                (
+                    // First update player position and calculate visible chunks
+                    update_visible_chunks,
+                    // Clean up chunks that are no longer visible
+                    cleanup_invisible_chunks,
+                    // Drop the chunks the server stopped sending updates for
+                    handle_chunk_unloads,
+                    // Then process any received chunk data
+                    handle_chunk_data,
+                    // Finally request any chunks we still need
+                    request_visible_chunks,
+                    // Debug system to monitor chunk state
+                    debug_chunk_state,
+                )
+                    .chain(), // Ensure these systems run in order
             )
-                .chain(), // Ensure these systems run in order
-        );
+            .add_systems(Update, (sync_world_time, sync_season, apply_chunk_deltas));
     }
 }
 
@@ -47,11 +49,37 @@ pub struct ClientWorldState {
     pub visible_chunks: HashSet<ChunkCoord>,
     pub loaded_chunks: HashSet<ChunkCoord>,
     pub requested_chunks: HashMap<ChunkCoord, u32>, // Map of requested chunks and the frame they were requested
+    /// Deltas for requested chunks that haven't arrived yet. Chunk data travels on its own
+    /// unordered channel, so a delta can overtake the chunk it applies to.
+    pub pending_deltas: HashMap<ChunkCoord, Vec<ChunkDelta>>,
     pub player_chunk: Option<ChunkCoord>,
     pub view_distance: i32,
+    /// Limit on chunk requests sent per frame, nearest chunks first; `None` requests everything at once
+    pub max_requests_per_frame: Option<usize>,
+    /// Inside an instance the only chunk is the one the server sent when we entered it
+    pub in_instance: bool,
+    /// Where chunks are loaded around when there's no player to follow (observers)
+    pub focus: Option<Vec2>,
     pub frame_counter: u32, // Track frames for debugging
 }
 
+impl Default for ClientWorldState {
+    fn default() -> Self {
+        Self {
+            visible_chunks: HashSet::new(),
+            loaded_chunks: HashSet::new(),
+            requested_chunks: HashMap::new(),
+            pending_deltas: HashMap::new(),
+            player_chunk: None,
+            view_distance: 2, // Default view distance in chunks
+            max_requests_per_frame: None,
+            in_instance: false,
+            focus: None,
+            frame_counter: 0, // Track how many frames we've processed
+        }
+    }
+}
+
 // System to track which chunk the player is in and update visible chunks
 fn update_visible_chunks(
     mut player_query: Query<&mut PlayerPosition, With<Predicted>>,
@@ -60,17 +88,19 @@ fn update_visible_chunks(
 ) {
     // Increment frame counter to track system calls
     client_world.frame_counter += 1;
+    if client_world.in_instance {
+        return;
+    }
 
-    // Only process if we have a player
-    if let Ok(player_pos) = player_query.get_single() {
+    // Only process if we have a player, or a place to look at
+    let focus = player_query
+        .get_single()
+        .map(|player_pos| player_pos.0)
+        .ok()
+        .or(client_world.focus);
+    if let Some(focus) = focus {
         // Calculate which chunk the player is in
-        let chunk_size = world_config.chunk_size as i32;
-        let chunk_x = (player_pos.x as i32).div_euclid(chunk_size);
-        let chunk_y = (player_pos.y as i32).div_euclid(chunk_size);
-        let current_chunk = ChunkCoord {
-            x: chunk_x,
-            y: chunk_y,
-        };
+        let current_chunk = world_config.grid().world_to_chunk(focus);
 
         // Update player chunk and visible chunks if this is the first run
         // or if the player has moved to a different chunk
@@ -167,6 +197,39 @@ fn cleanup_invisible_chunks(
 
     for coord in requested_to_remove {
         client_world.requested_chunks.remove(&coord);
+        client_world.pending_deltas.remove(&coord);
+    }
+}
+
+// System to drop the chunks the server unloaded for us. Those still visible are requested again
+// once the request timeout has passed, should the server's view distance be the larger one.
+fn handle_chunk_unloads(
+    mut commands: Commands,
+    mut events: EventReader<MessageEvent<ChunkUnload>>,
+    mut client_world: ResMut<ClientWorldState>,
+    chunk_query: Query<(Entity, &ChunkCoord)>,
+) {
+    let mut unloaded = HashSet::new();
+    for event in events.read() {
+        unloaded.extend(event.message().coords.iter().copied());
+    }
+    if unloaded.is_empty() {
+        return;
+    }
+    debug!("Server unloaded {} chunks", unloaded.len());
+
+    let current_frame = client_world.frame_counter;
+    for coord in &unloaded {
+        client_world.loaded_chunks.remove(coord);
+        client_world.pending_deltas.remove(coord);
+        if client_world.visible_chunks.contains(coord) {
+            client_world.requested_chunks.insert(*coord, current_frame);
+        }
+    }
+    for (entity, coord) in chunk_query.iter() {
+        if unloaded.contains(coord) {
+            commands.entity(entity).despawn();
+        }
     }
 }
 
@@ -180,37 +243,48 @@ fn request_visible_chunks(
         return;
     }
 
-    // Define threshold for re-requesting (only re-request chunks after 120 frames/~2 seconds)
-    const REQUEST_TIMEOUT: u32 = 120;
-    
     // Collect all data we need first to avoid borrowing conflicts
     let current_frame = client_world.frame_counter;
-    
+
     // Find chunks that need to be requested (visible but not loaded)
     let mut chunks_to_request = Vec::new();
-    
+
     for &coord in &client_world.visible_chunks {
         // Skip if already loaded
         if client_world.loaded_chunks.contains(&coord) {
             continue;
         }
-        
+
         // Check if already requested recently
         match client_world.requested_chunks.get(&coord) {
             // If not requested or requested a long time ago, add to request list
             None => chunks_to_request.push(coord),
-            Some(&frame) if current_frame - frame > REQUEST_TIMEOUT => chunks_to_request.push(coord),
+            Some(&frame) if current_frame - frame > REQUEST_TIMEOUT => {
+                chunks_to_request.push(coord)
+            }
             _ => {}
         }
     }
-    
+
+    // When the request rate is limited, the chunks closest to the player go first
+    if let (Some(limit), Some(center)) = (
+        client_world.max_requests_per_frame,
+        client_world.player_chunk,
+    ) {
+        chunks_to_request.sort_by_key(|c| (c.x - center.x).abs().max((c.y - center.y).abs()));
+        chunks_to_request.truncate(limit);
+    }
+
     // Now process all the chunks we need to request
     let requests_count = chunks_to_request.len();
-    
+
     for coord in &chunks_to_request {
         // Send a request to the server for this chunk
-        client.send_message::<ChunkChannel, _>(&ChunkRequest { coord: *coord });
-        
+        client.send_message::<ChunkChannel, _>(&ChunkRequest {
+            coord: *coord,
+            accepts: ChunkFormat::best_supported(),
+        });
+
         // Mark as requested on this frame
         client_world.requested_chunks.insert(*coord, current_frame);
     }
@@ -238,10 +312,28 @@ fn handle_chunk_data(
     mut commands: Commands,
     mut events: EventReader<MessageEvent<ChunkData>>,
     mut client_world: ResMut<ClientWorldState>,
+    world_config: Res<WorldConfig>,
+    loaded: Query<(Entity, &Chunk, &ChunkCoord)>,
+    mut errors: EventWriter<ReportError>,
 ) {
     for event in events.read() {
         let chunk_data = &event.message;
-        let coord = chunk_data.chunk.coord;
+        let coord = chunk_data.coord;
+
+        // Everything indexing into the chunk's tiles relies on its shape
+        let mut chunk = match deserialize_chunk(&chunk_data.data)
+            .and_then(|chunk| chunk.validate(world_config.chunk_size).map(|()| chunk))
+        {
+            Ok(chunk) if chunk.coord == coord => chunk,
+            Ok(_) => {
+                errors.send(ReportError(GameError::MalformedChunk(coord)));
+                continue;
+            }
+            Err(e) => {
+                errors.send(ReportError(e));
+                continue;
+            }
+        };
 
         // Skip if no longer visible (player moved away while request was in flight)
         if !client_world.visible_chunks.contains(&coord) {
@@ -252,14 +344,46 @@ fn handle_chunk_data(
             continue;
         }
 
-        // Check if we've already loaded this chunk to avoid duplicates
+        // Check if we've already loaded this chunk to avoid duplicates. The server sends a newer
+        // copy when it rebuilt the chunk, which replaces ours.
         if client_world.loaded_chunks.contains(&coord) {
-            info!("Already have chunk at {:?}, skipping", coord);
-            continue;
+            let ours: Vec<(Entity, u32)> = loaded
+                .iter()
+                .filter(|(_, _, loaded_coord)| **loaded_coord == coord)
+                .map(|(entity, loaded_chunk, _)| (entity, loaded_chunk.version()))
+                .collect();
+            if ours
+                .iter()
+                .any(|(_, version)| !is_newer_version(chunk.version(), *version))
+            {
+                info!("Already have chunk at {:?}, skipping", coord);
+                continue;
+            }
+            info!("Replacing chunk at {:?} with a newer copy", coord);
+            for (entity, _) in ours {
+                commands.entity(entity).despawn();
+            }
         }
 
-        // Store the chunk entity
-        commands.spawn((chunk_data.chunk.clone(), coord));
+        // Store the chunk entity, with the deltas that arrived before it
+        let missed = client_world
+            .pending_deltas
+            .remove(&coord)
+            .unwrap_or_default()
+            .iter()
+            .fold(false, |missed, delta| {
+                chunk.apply_delta(delta) == DeltaOutcome::Missed || missed
+            });
+        if missed {
+            // encoded before modifications whose deltas we already dropped, it's requested again
+            warn!(
+                "Chunk {:?} is behind its deltas, requesting it again",
+                coord
+            );
+            client_world.requested_chunks.remove(&coord);
+            continue;
+        }
+        commands.spawn((chunk, coord));
 
         // Mark as loaded and remove from requested
         client_world.loaded_chunks.insert(coord);
@@ -326,3 +450,98 @@ fn debug_chunk_state(client_world: Res<ClientWorldState>) {
         }
     }
 }
+
+// Adopt the server's world time so that the day/night cycle is the same for everyone
+fn sync_world_time(
+    mut events: EventReader<MessageEvent<WorldTimeSync>>,
+    mut world_state: ResMut<WorldState>,
+) {
+    if let Some(event) = events.read().last() {
+        world_state.world_time = event.message().world_time;
+    }
+}
+
+fn sync_season(
+    mut events: EventReader<MessageEvent<SeasonChanged>>,
+    mut season: ResMut<CurrentSeason>,
+) {
+    if let Some(event) = events.read().last() {
+        season.0 = event.message().0;
+    }
+}
+
+// Apply the tile modifications the server sent to our copy of the chunks. Chunks that missed some
+// are dropped, to be requested whole again. Chunks bordering a modified tile are marked as changed
+// too, so that they get re-rendered.
+fn apply_chunk_deltas(
+    mut commands: Commands,
+    mut events: EventReader<MessageEvent<ChunkDelta>>,
+    world_config: Res<WorldConfig>,
+    mut client_world: ResMut<ClientWorldState>,
+    mut chunks: Query<&mut Chunk>,
+    chunk_entities: Query<(Entity, &ChunkCoord)>,
+) {
+    let mut dropped = HashSet::new();
+    for event in events.read() {
+        // deltas are for the overworld, our instance chunk has the same coordinates as one of it
+        if client_world.in_instance {
+            continue;
+        }
+        let delta = event.message();
+        let coord = delta.coord;
+        if dropped.contains(&coord) {
+            continue;
+        }
+        if !client_world.loaded_chunks.contains(&coord) {
+            if client_world.requested_chunks.contains_key(&coord) {
+                client_world
+                    .pending_deltas
+                    .entry(coord)
+                    .or_default()
+                    .push(delta.clone());
+            }
+            continue;
+        }
+
+        let neighbours: HashSet<ChunkCoord> = delta
+            .tiles
+            .iter()
+            .flat_map(|(_, tile)| {
+                let (x, y) = tile.position;
+                [
+                    (x.saturating_add(1), y),
+                    (x.saturating_sub(1), y),
+                    (x, y.saturating_add(1)),
+                    (x, y.saturating_sub(1)),
+                ]
+            })
+            .map(|tile| world_config.grid().tile_to_chunk(tile))
+            .filter(|neighbour| *neighbour != coord)
+            .collect();
+        let mut missed = false;
+        for mut chunk in chunks.iter_mut() {
+            if chunk.coord == coord {
+                missed |= chunk.apply_delta(delta) == DeltaOutcome::Missed;
+            } else if neighbours.contains(&chunk.coord) {
+                chunk.set_changed();
+            }
+        }
+        if missed {
+            warn!(
+                "Chunk {:?} missed modifications before version {}, requesting it again",
+                coord, delta.version
+            );
+            dropped.insert(coord);
+        }
+    }
+
+    // no longer loaded nor requested, so it's requested again with the other visible chunks
+    for coord in &dropped {
+        client_world.loaded_chunks.remove(coord);
+    }
+    for (entity, coord) in chunk_entities.iter() {
+        if dropped.contains(coord) {
+            commands.entity(entity).despawn();
+        }
+    }
+}
@@ -0,0 +1,326 @@
+use bevy::image::ImageSampler;
+use bevy::prelude::*;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use std::collections::HashMap;
+
+use crate::client::plugins::{ChatInput, RegionNames, TileProjection};
+use crate::shared::regions::RegionCoord;
+use crate::shared::world_generation::{
+    BiomeType, Chunk, ChunkCoord, NoiseBuffers, NoiseSampler, WorldConfig,
+};
+
+// Key cycling through the generation views, and key toggling chunk borders and coordinates
+const VIEW_KEY: KeyCode = KeyCode::F3;
+const BORDERS_KEY: KeyCode = KeyCode::F4;
+
+// Above the tiles, their resources and overlays, under players
+const DEBUG_VIEW_Z: f32 = 0.5;
+const LABEL_Z: f32 = 0.6;
+const LABEL_SCALE: f32 = 0.05;
+const BORDER_COLOR: Color = Color::srgb(1.0, 0.2, 0.8);
+
+// Client plugin drawing how world generation sees each loaded chunk instead of its sprites, to
+// see how generation parameters change the world
+pub struct ClientWorldgenDebugPlugin;
+
+impl Plugin for ClientWorldgenDebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WorldgenDebug>().add_systems(
+            Update,
+            (
+                toggle_worldgen_debug,
+                update_debug_views,
+                update_chunk_labels,
+                draw_chunk_borders,
+            )
+                .chain(),
+        );
+    }
+}
+
+/// What generation value tiles are colored by
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DebugView {
+    /// Height noise, from black at -1 to white at 1
+    Height,
+    /// Biome of the chunk, one hue per biome
+    Biome,
+    /// Resource noise: dark below the density threshold, where no resource can spawn
+    ResourceRoll,
+}
+
+#[derive(Resource, Default)]
+pub struct WorldgenDebug {
+    pub view: Option<DebugView>,
+    pub chunk_borders: bool,
+    // Entities drawing the current view and the coordinates, per chunk
+    views: HashMap<ChunkCoord, Entity>,
+    labels: HashMap<ChunkCoord, Entity>,
+    // The view entities were drawn for this view and projection
+    drawn: Option<(DebugView, TileProjection)>,
+    sampler: Option<(u32, NoiseSampler)>,
+}
+
+impl WorldgenDebug {
+    fn sampler(&mut self, seed: u32) -> &NoiseSampler {
+        if self
+            .sampler
+            .as_ref()
+            .is_none_or(|(cached, _)| *cached != seed)
+        {
+            self.sampler = Some((seed, NoiseSampler::new(seed)));
+        }
+        &self.sampler.as_ref().expect("sampler was just set").1
+    }
+}
+
+fn toggle_worldgen_debug(
+    keypress: Res<ButtonInput<KeyCode>>,
+    chat: Res<ChatInput>,
+    mut debug: ResMut<WorldgenDebug>,
+) {
+    if chat.open {
+        return;
+    }
+    if keypress.just_pressed(VIEW_KEY) {
+        debug.view = match debug.view {
+            None => Some(DebugView::Height),
+            Some(DebugView::Height) => Some(DebugView::Biome),
+            Some(DebugView::Biome) => Some(DebugView::ResourceRoll),
+            Some(DebugView::ResourceRoll) => None,
+        };
+        info!("World generation view: {:?}", debug.view);
+    }
+    if keypress.just_pressed(BORDERS_KEY) {
+        debug.chunk_borders = !debug.chunk_borders;
+    }
+}
+
+fn biome_hue(biome: BiomeType) -> Color {
+    let index = BiomeType::ALL
+        .iter()
+        .position(|other| *other == biome)
+        .unwrap_or_default();
+    Color::hsl(index as f32 * 360.0 / BiomeType::ALL.len() as f32, 0.8, 0.5)
+}
+
+// Color of every tile of a chunk for a view, row by row from the bottom
+fn tile_colors(
+    view: DebugView,
+    chunk: &Chunk,
+    config: &WorldConfig,
+    sampler: &NoiseSampler,
+) -> Vec<Color> {
+    let size = config.chunk_size;
+    match view {
+        DebugView::Biome => vec![biome_hue(chunk.biome_type); size * size],
+        DebugView::Height | DebugView::ResourceRoll => {
+            // the raw noise, rather than the heights of tiles that may have been edited since
+            let mut buffers = NoiseBuffers::default();
+            sampler.sample_chunk(&chunk.coord, config, &mut buffers);
+            if view == DebugView::Height {
+                buffers
+                    .heights
+                    .iter()
+                    .map(|height| {
+                        let value = (height + 1.0) * 0.5;
+                        Color::srgb(value, value, value)
+                    })
+                    .collect()
+            } else {
+                let threshold = 1.0 - config.resource_density;
+                buffers
+                    .resources
+                    .iter()
+                    .map(|roll| {
+                        let value = roll.abs();
+                        if value < threshold {
+                            Color::srgb(0.1, 0.1, 0.2 + value * 0.3)
+                        } else {
+                            Color::srgb(1.0, value, 0.0)
+                        }
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+// One pixel per tile. Image rows go top down while chunk rows go bottom up.
+fn view_image(colors: &[Color], size: usize) -> Image {
+    let mut data = vec![0; size * size * 4];
+    for (index, color) in colors.iter().enumerate() {
+        let (x, y) = (index % size, index / size);
+        let pixel = ((size - 1 - y) * size + x) * 4;
+        data[pixel..pixel + 4].copy_from_slice(&color.to_srgba().to_u8_array());
+    }
+    let mut image = Image::new(
+        Extent3d {
+            width: size as u32,
+            height: size as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    image.sampler = ImageSampler::nearest();
+    image
+}
+
+// Draw the current view over every loaded chunk, and redraw chunks that changed
+#[allow(clippy::too_many_arguments)]
+fn update_debug_views(
+    mut commands: Commands,
+    mut debug: ResMut<WorldgenDebug>,
+    mut images: ResMut<Assets<Image>>,
+    projection: Res<TileProjection>,
+    world_config: Res<WorldConfig>,
+    chunks: Query<&Chunk>,
+    changed: Query<&Chunk, Changed<Chunk>>,
+) {
+    let Some(view) = debug.view else {
+        for (_, entity) in debug.views.drain() {
+            commands.entity(entity).despawn_recursive();
+        }
+        debug.drawn = None;
+        return;
+    };
+    let redraw_all = debug.drawn != Some((view, *projection));
+    debug.drawn = Some((view, *projection));
+    let loaded: HashMap<ChunkCoord, &Chunk> =
+        chunks.iter().map(|chunk| (chunk.coord, chunk)).collect();
+    let stale: Vec<ChunkCoord> = debug
+        .views
+        .keys()
+        .copied()
+        .filter(|coord| redraw_all || !loaded.contains_key(coord))
+        .chain(changed.iter().map(|chunk| chunk.coord))
+        .collect();
+    for coord in stale {
+        if let Some(entity) = debug.views.remove(&coord) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+
+    let size = world_config.chunk_size;
+    let grid = world_config.grid();
+    for (coord, chunk) in loaded {
+        if size == 0 || debug.views.contains_key(&coord) {
+            continue;
+        }
+        let colors = tile_colors(view, chunk, &world_config, debug.sampler(world_config.seed));
+        let image = images.add(view_image(&colors, size));
+        // laid out like the chunk's tiles, so that it lines up in both projections
+        let (transform, rotation) = projection.chunk_transforms(grid.chunk_to_world(coord));
+        let extent = size as f32;
+        let entity = commands
+            .spawn(SpatialBundle::from_transform(transform))
+            .with_children(|parent| {
+                parent
+                    .spawn(SpatialBundle::from_transform(Transform::from_rotation(
+                        rotation,
+                    )))
+                    .with_children(|tiles| {
+                        tiles.spawn((
+                            Sprite {
+                                image,
+                                custom_size: Some(Vec2::splat(extent)),
+                                ..default()
+                            },
+                            Transform::from_xyz(
+                                (extent - 1.0) * 0.5,
+                                (extent - 1.0) * 0.5,
+                                DEBUG_VIEW_Z,
+                            ),
+                        ));
+                    });
+            })
+            .id();
+        debug.views.insert(coord, entity);
+    }
+}
+
+// Coordinates of every loaded chunk at its center, under the name of its region if known
+fn update_chunk_labels(
+    mut commands: Commands,
+    mut debug: ResMut<WorldgenDebug>,
+    projection: Res<TileProjection>,
+    world_config: Res<WorldConfig>,
+    regions: Option<Res<RegionNames>>,
+    chunks: Query<&Chunk>,
+) {
+    let renamed = regions.as_ref().is_some_and(|regions| regions.is_changed());
+    let loaded: Vec<ChunkCoord> = if debug.chunk_borders {
+        chunks.iter().map(|chunk| chunk.coord).collect()
+    } else {
+        Vec::new()
+    };
+    let gone: Vec<ChunkCoord> = debug
+        .labels
+        .keys()
+        .copied()
+        .filter(|coord| !loaded.contains(coord) || projection.is_changed() || renamed)
+        .collect();
+    for coord in gone {
+        if let Some(entity) = debug.labels.remove(&coord) {
+            commands.entity(entity).despawn();
+        }
+    }
+    let grid = world_config.grid();
+    let half = (world_config.chunk_size as f32 - 1.0) * 0.5;
+    for coord in loaded {
+        if debug.labels.contains_key(&coord) {
+            continue;
+        }
+        let center = projection.to_screen(grid.chunk_to_world(coord) + Vec2::splat(half));
+        let region = regions
+            .as_ref()
+            .and_then(|regions| regions.name(RegionCoord::of_chunk(coord)));
+        let label = match region {
+            Some(region) => format!("{}\n{}, {}", region, coord.x, coord.y),
+            None => format!("{}, {}", coord.x, coord.y),
+        };
+        let entity = commands
+            .spawn((
+                Text2d::new(label),
+                TextFont::from_font_size(24.0),
+                TextColor(BORDER_COLOR),
+                Transform::from_xyz(center.x, center.y, LABEL_Z)
+                    .with_scale(Vec3::splat(LABEL_SCALE)),
+            ))
+            .id();
+        debug.labels.insert(coord, entity);
+    }
+}
+
+fn draw_chunk_borders(
+    mut gizmos: Gizmos,
+    debug: Res<WorldgenDebug>,
+    projection: Res<TileProjection>,
+    world_config: Res<WorldConfig>,
+    chunks: Query<&Chunk>,
+) {
+    if !debug.chunk_borders {
+        return;
+    }
+    let grid = world_config.grid();
+    let size = world_config.chunk_size as f32;
+    for chunk in chunks.iter() {
+        // tiles are centered on their coordinates, so chunks start half a tile before their origin
+        let corner = grid.chunk_to_world(chunk.coord) - Vec2::splat(0.5);
+        let corners = [
+            corner,
+            corner + Vec2::new(size, 0.0),
+            corner + Vec2::splat(size),
+            corner + Vec2::new(0.0, size),
+            corner,
+        ];
+        gizmos.linestrip_2d(
+            corners.iter().map(|corner| projection.to_screen(*corner)),
+            BORDER_COLOR,
+        );
+    }
+}
@@ -0,0 +1,214 @@
+//! Crash reports: a panic hook writing what went wrong, and what the game was doing, to `crash/`
+use bevy::prelude::*;
+use bevy::time::common_conditions::on_timer;
+use bevy::utils::Duration;
+use lightyear::prelude::client::Predicted;
+use std::backtrace::Backtrace;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::protocol::{PlayerId, PlayerPosition};
+use crate::settings_common::Settings;
+use crate::shared::world_generation::{WorldConfig, WorldState};
+
+// Directory crash reports are written to
+pub const CRASH_DIR: &str = "crash";
+// File remembering the last report the player was told about
+const SEEN_FILE: &str = "last_seen";
+// How often the session context is sampled
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+// Player positions kept in the context of a server
+const MAX_POSITIONS: usize = 16;
+
+/// What an app was doing at the time of its last sample
+#[derive(Clone, Debug, Default)]
+struct AppContext {
+    seed: u32,
+    loaded_chunks: usize,
+    world_time: f64,
+    player_positions: Vec<Vec2>,
+}
+
+// Sampled from the running apps and read by the panic hook, which can't reach into the ECS
+struct CrashContext {
+    settings: String,
+    apps: Vec<(&'static str, AppContext)>,
+}
+
+static CONTEXT: Mutex<CrashContext> = Mutex::new(CrashContext {
+    settings: String::new(),
+    apps: Vec::new(),
+});
+
+/// Install the panic hook, keeping a snapshot of the settings (without secrets) for the reports
+pub fn install_hook(settings: &Settings) {
+    let mut snapshot = settings.clone();
+    // other credentials are `Secret`s, which don't print their value
+    snapshot.shared.private_key = [0; 32];
+    if let Ok(mut context) = CONTEXT.lock() {
+        context.settings = format!("{:#?}", snapshot);
+    }
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let report = crash_report(&info.to_string(), &Backtrace::force_capture());
+        match write_report(&report) {
+            Ok(path) => eprintln!("Crash report written to {}", path.display()),
+            Err(e) => eprintln!("Failed to write the crash report: {}", e),
+        }
+        previous(info);
+    }));
+}
+
+fn crash_report(message: &str, backtrace: &Backtrace) -> String {
+    let mut report = format!(
+        "{} {} crashed\n\n{}\n\n",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION"),
+        message
+    );
+    // the panic may come from a thread holding the lock; report without the context then
+    match CONTEXT.try_lock() {
+        Ok(context) => {
+            for (side, app) in context.apps.iter() {
+                let _ = writeln!(
+                    report,
+                    "{}: seed {}, {} chunks loaded, world time {:.1}s, player positions {:?}",
+                    side, app.seed, app.loaded_chunks, app.world_time, app.player_positions
+                );
+            }
+            let _ = write!(report, "\nSettings:\n{}\n\n", context.settings);
+        }
+        Err(_) => report.push_str("Session context unavailable\n\n"),
+    }
+    let _ = write!(report, "Backtrace:\n{}\n", backtrace);
+    report
+}
+
+fn write_report(report: &str) -> std::io::Result<PathBuf> {
+    fs::create_dir_all(CRASH_DIR)?;
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    let path = Path::new(CRASH_DIR).join(format!("crash-{}.txt", timestamp));
+    fs::write(&path, report)?;
+    Ok(path)
+}
+
+/// The most recent crash report, if the player hasn't been told about it yet
+pub fn unseen_report() -> Option<PathBuf> {
+    let latest = fs::read_dir(CRASH_DIR)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("crash-") && name.ends_with(".txt"))
+        })
+        .max()?;
+    let seen = fs::read_to_string(Path::new(CRASH_DIR).join(SEEN_FILE)).unwrap_or_default();
+    let name = latest.file_name()?.to_str()?;
+    (seen.trim() != name).then_some(latest)
+}
+
+/// Remember that the player was told about a report, so that it isn't shown again
+pub fn mark_seen(report: &Path) -> std::io::Result<()> {
+    let name = report
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    fs::write(Path::new(CRASH_DIR).join(SEEN_FILE), name)
+}
+
+/// Which app a [`CrashContextPlugin`] samples
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CrashSide {
+    Client,
+    Server,
+}
+
+impl CrashSide {
+    fn name(&self) -> &'static str {
+        match self {
+            CrashSide::Client => "client",
+            CrashSide::Server => "server",
+        }
+    }
+}
+
+// Plugin keeping the session context of crash reports up to date. Added once for the client and
+// once for the server, which can share the same app.
+pub struct CrashContextPlugin(pub CrashSide);
+
+impl Plugin for CrashContextPlugin {
+    fn build(&self, app: &mut App) {
+        match self.0 {
+            CrashSide::Client => app.add_systems(
+                Update,
+                sample_client_context.run_if(on_timer(SAMPLE_INTERVAL)),
+            ),
+            CrashSide::Server => app.add_systems(
+                Update,
+                sample_server_context.run_if(on_timer(SAMPLE_INTERVAL)),
+            ),
+        };
+    }
+
+    fn is_unique(&self) -> bool {
+        false
+    }
+}
+
+fn sample_client_context(
+    world_config: Res<WorldConfig>,
+    world_state: Res<WorldState>,
+    player: Query<&PlayerPosition, With<Predicted>>,
+) {
+    record_context(
+        CrashSide::Client,
+        AppContext {
+            seed: world_config.seed,
+            loaded_chunks: world_state.chunks.len(),
+            world_time: world_state.world_time,
+            player_positions: player.iter().map(|position| position.0).collect(),
+        },
+    );
+}
+
+fn sample_server_context(
+    world_config: Res<WorldConfig>,
+    world_state: Res<WorldState>,
+    players: Query<&PlayerPosition, With<PlayerId>>,
+) {
+    record_context(
+        CrashSide::Server,
+        AppContext {
+            seed: world_config.seed,
+            loaded_chunks: world_state.chunks.len(),
+            world_time: world_state.world_time,
+            player_positions: players
+                .iter()
+                .take(MAX_POSITIONS)
+                .map(|position| position.0)
+                .collect(),
+        },
+    );
+}
+
+fn record_context(side: CrashSide, app: AppContext) {
+    let Ok(mut context) = CONTEXT.lock() else {
+        return;
+    };
+    match context
+        .apps
+        .iter_mut()
+        .find(|(name, _)| *name == side.name())
+    {
+        Some((_, existing)) => *existing = app,
+        None => context.apps.push((side.name(), app)),
+    }
+}
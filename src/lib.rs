@@ -0,0 +1,6 @@
+//! Library target exposing the protocol and the shared world code, so that tools living outside
+//! of the game binary (the fuzz targets) can use them
+#![allow(dead_code)]
+
+pub mod protocol;
+pub mod shared;
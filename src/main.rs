@@ -20,6 +20,11 @@ mod shared;
 mod shared_config;
 
 mod app;
+mod bench;
+mod timelapse;
+#[cfg(all(feature = "gui", feature = "client"))]
+mod visual_test;
+mod crash;
 mod settings;
 mod settings_common;
 
@@ -28,31 +33,228 @@ mod renderer;
 
 fn main() {
     let cli = Cli::default();
+    if let Some(Mode::Bench { target }) = &cli.mode {
+        bench::run(target);
+        return;
+    }
+    if let Some(Mode::Timelapse { frames, output, frame_ms }) = &cli.mode {
+        std::process::exit(timelapse::run(frames, output, *frame_ms));
+    }
+    #[cfg(all(feature = "gui", feature = "client"))]
+    if let Some(Mode::VisualTest { suite, update }) = &cli.mode {
+        std::process::exit(visual_test::run(suite, *update));
+    }
     #[allow(unused_mut)]
     let mut settings = get_settings();
     #[cfg(target_family = "wasm")]
     lightyear_examples_common::settings::modify_digest_on_wasm(&mut settings.client);
+    let conditioner = cli.netsim.conditioner();
+    if let Some(conditioner) = &conditioner {
+        settings.server.conditioner = Some(conditioner.clone());
+        settings.client.conditioner = Some(conditioner.clone());
+    }
+    if cli.lockstep_debug {
+        settings.client.lockstep_debug = true;
+    }
+    if cli.ruleset.is_some() {
+        settings.server.ruleset = cli.ruleset;
+    }
+    #[cfg(feature = "sharding")]
+    if let Some(id) = cli.shard {
+        configure_shard(&mut settings, id);
+    }
+
+    // bots run the client without any window, rendering or keyboard
+    #[cfg(feature = "bot")]
+    let bot_port = cli.bot_port();
+    #[cfg(not(feature = "bot"))]
+    let bot_port: Option<u16> = None;
+
+    crash::install_hook(&settings);
+    let frame_budget = settings.shared.frame_budget;
 
     let mut app = Apps::new(settings, cli, env!("CARGO_PKG_NAME").to_string());
+    // logged once the apps have set up logging
+    if let Some(conditioner) = conditioner {
+        info!("Simulating network conditions: {:?}", conditioner);
+    }
 
     app.add_lightyear_plugins();
     app.add_user_shared_plugin(ProtocolPlugin);
+    app.add_user_shared_plugin(shared::error::ErrorsPlugin);
+    app.add_user_shared_plugin(shared::net_diagnostics::NetDiagnosticsPlugin);
     app.add_user_shared_plugin(shared::world_generation::WorldGenerationPlugin);
+    app.add_user_shared_plugin(shared::commands::CommandsPlugin);
+    app.add_user_shared_plugin(shared::seasons::SeasonsPlugin);
+    app.add_user_shared_plugin(shared::temperature::TemperaturePlugin);
+    app.add_user_shared_plugin(shared::feature_flags::FeatureFlagsPlugin);
+    app.add_user_shared_plugin(shared::frame_pacing::FramePacingPlugin(frame_budget));
+    #[cfg(feature = "cloud-sync")]
+    app.add_user_shared_plugin(shared::cloud_sync::CloudSyncPlugin);
+    app.add_user_client_plugin(crash::CrashContextPlugin(crash::CrashSide::Client));
+    app.add_user_server_plugin(crash::CrashContextPlugin(crash::CrashSide::Server));
     #[cfg(feature = "client")]
-    app.add_user_client_plugin(client::ExampleClientPlugin);
-    app.add_user_client_plugin(client::plugins::ClientWorldPlugin);
-    // Add the ClientWorldRenderPlugin for rendering the world tiles
-    app.add_user_client_plugin(client::plugins::ClientWorldRenderPlugin);
+    {
+        app.add_user_client_plugin(client::ExampleClientPlugin);
+        app.add_user_client_plugin(client::plugins::ClientWorldPlugin);
+        app.add_user_client_plugin(client::plugins::ClientShardsPlugin);
+        app.add_user_client_plugin(client::plugins::ClientRestartPlugin);
+        app.add_user_client_plugin(client::plugins::ClientFeatureFlagsPlugin);
+    }
+    #[cfg(feature = "bot")]
+    if let Some(port) = bot_port {
+        let link = match client::plugins::BotLink::listen(port) {
+            Ok(link) => link,
+            Err(e) => panic!("Can't listen for a bot controller on port {}: {}", port, e),
+        };
+        app.add_user_client_plugin(client::plugins::ClientBotPlugin(link));
+    }
+    #[cfg(feature = "client")]
+    if bot_port.is_none() {
+        // Add the ClientWorldRenderPlugin for rendering the world tiles
+        app.add_user_client_plugin(client::plugins::ClientWorldRenderPlugin);
+        app.add_user_client_plugin(client::plugins::ClientChatPlugin);
+        app.add_user_client_plugin(client::plugins::ClientTileInspectPlugin);
+        app.add_user_client_plugin(client::plugins::ClientNetsimPlugin);
+        app.add_user_client_plugin(client::plugins::ClientDesyncPlugin);
+        app.add_user_client_plugin(client::plugins::ClientItemsRenderPlugin);
+        app.add_user_client_plugin(client::plugins::ClientHarvestPlugin);
+        app.add_user_client_plugin(client::plugins::ClientFishingPlugin);
+        app.add_user_client_plugin(client::plugins::ClientSurvivalPlugin);
+        app.add_user_client_plugin(client::plugins::ClientTerrainPlugin);
+        app.add_user_client_plugin(client::plugins::ClientDoorsPlugin);
+        app.add_user_client_plugin(client::plugins::ClientRailsPlugin);
+        app.add_user_client_plugin(client::plugins::ClientLogisticsPlugin);
+        app.add_user_client_plugin(client::plugins::ClientPowerPlugin);
+        app.add_user_client_plugin(client::plugins::ClientVillagesPlugin);
+        app.add_user_client_plugin(client::plugins::ClientFogPlugin);
+        app.add_user_client_plugin(client::plugins::ClientHistoryPlugin);
+        app.add_user_client_plugin(client::plugins::ClientTelemetryPlugin);
+        app.add_user_client_plugin(client::plugins::ClientAudioPlugin);
+        app.add_user_client_plugin(client::plugins::ClientHotbarPlugin);
+        app.add_user_client_plugin(client::plugins::ClientInteractionPlugin);
+        app.add_user_client_plugin(client::plugins::ClientCombatPlugin);
+        app.add_user_client_plugin(client::plugins::ClientViewDistancePlugin);
+        app.add_user_client_plugin(client::plugins::ClientQueuePlugin);
+        app.add_user_client_plugin(client::plugins::ClientLoadingPlugin);
+        app.add_user_client_plugin(client::plugins::ClientResourcePackPlugin);
+        app.add_user_client_plugin(client::plugins::ClientInstancesPlugin);
+        app.add_user_client_plugin(client::plugins::ClientPortalsPlugin);
+        app.add_user_client_plugin(client::plugins::ClientMusicPlugin);
+        app.add_user_client_plugin(client::plugins::ClientSocialPlugin);
+        app.add_user_client_plugin(client::plugins::ClientSkillsPlugin);
+        app.add_user_client_plugin(client::plugins::ClientToastsPlugin);
+        app.add_user_client_plugin(client::plugins::ClientAchievementsPlugin);
+        app.add_user_client_plugin(client::plugins::ClientTutorialPlugin);
+        app.add_user_client_plugin(client::plugins::ClientHelpPlugin);
+        app.add_user_client_plugin(client::plugins::ClientLockstepPlugin);
+        app.add_user_client_plugin(client::plugins::ClientCrashPlugin);
+        app.add_user_client_plugin(client::plugins::ClientObserverPlugin);
+        app.add_user_client_plugin(client::plugins::ClientRegionsPlugin);
+        app.add_user_client_plugin(client::plugins::ClientCompassPlugin);
+        app.add_user_client_plugin(client::plugins::ClientPartyPlugin);
+        app.add_user_client_plugin(client::plugins::ClientGravesPlugin);
+        app.add_user_client_plugin(client::plugins::ClientTemperaturePlugin);
+        app.add_user_client_plugin(client::plugins::ClientWorldgenDebugPlugin);
+        #[cfg(feature = "cloud-sync")]
+        app.add_user_client_plugin(client::plugins::ClientCloudSyncPlugin);
+        #[cfg(feature = "inspector")]
+        app.add_user_client_plugin(client::plugins::ClientInspectorPlugin);
+    }
 
     #[cfg(feature = "server")]
-    app.add_user_server_plugin(server::ExampleServerPlugin);
-    app.add_user_server_plugin(server::plugins::ServerWorldPlugin);
+    {
+        app.add_user_server_plugin(server::ExampleServerPlugin);
+        app.add_user_server_plugin(server::plugins::ServerWorldPlugin);
+        app.add_user_server_plugin(server::plugins::ServerCommandsPlugin);
+        app.add_user_server_plugin(server::plugins::ServerFeatureFlagsPlugin);
+        app.add_user_server_plugin(server::plugins::ServerTileHistoryPlugin);
+        app.add_user_server_plugin(server::plugins::ServerRestartPlugin);
+        app.add_user_server_plugin(server::plugins::ServerNetsimPlugin);
+        app.add_user_server_plugin(server::plugins::ServerChunkAuditPlugin);
+        app.add_user_server_plugin(server::plugins::ServerGuardrailsPlugin);
+        app.add_user_server_plugin(server::plugins::ServerItemsPlugin);
+        app.add_user_server_plugin(server::plugins::ServerHarvestPlugin);
+        app.add_user_server_plugin(server::plugins::ServerFishingPlugin);
+        app.add_user_server_plugin(server::plugins::ServerSurvivalPlugin);
+        app.add_user_server_plugin(server::plugins::ServerClaimsPlugin);
+        app.add_user_server_plugin(server::plugins::ServerTerrainPlugin);
+        app.add_user_server_plugin(server::plugins::ServerSeasonsPlugin);
+        app.add_user_server_plugin(server::plugins::ServerHotbarPlugin);
+        app.add_user_server_plugin(server::plugins::ServerInteractionPlugin);
+        app.add_user_server_plugin(server::plugins::ServerChunkStorePlugin);
+        app.add_user_server_plugin(server::plugins::ServerTerrainImportPlugin);
+        app.add_user_server_plugin(server::plugins::ServerRegionsPlugin);
+        app.add_user_server_plugin(server::plugins::ServerPartyPlugin);
+        app.add_user_server_plugin(server::plugins::ServerGravesPlugin);
+        app.add_user_server_plugin(server::plugins::ServerCookingPlugin);
+        app.add_user_server_plugin(server::plugins::ServerTemperaturePlugin);
+        app.add_user_server_plugin(server::plugins::ServerDoorsPlugin);
+        app.add_user_server_plugin(server::plugins::ServerRailsPlugin);
+        app.add_user_server_plugin(server::plugins::ServerLogisticsPlugin);
+        app.add_user_server_plugin(server::plugins::ServerPowerPlugin);
+        app.add_user_server_plugin(server::plugins::ServerVillagesPlugin);
+        app.add_user_server_plugin(server::plugins::ServerCombatPlugin);
+        app.add_user_server_plugin(server::plugins::ServerReputationPlugin);
+        app.add_user_server_plugin(server::plugins::ServerHistoryPlugin);
+        app.add_user_server_plugin(server::plugins::ServerPathfindingPlugin);
+        app.add_user_server_plugin(server::plugins::ServerDifficultyPlugin);
+        app.add_user_server_plugin(server::plugins::ServerPregenPlugin);
+        app.add_user_server_plugin(server::plugins::ServerProfilesPlugin);
+        app.add_user_server_plugin(server::plugins::ServerAfkPlugin);
+        app.add_user_server_plugin(server::plugins::ServerQueuePlugin);
+        app.add_user_server_plugin(server::plugins::ServerInstancesPlugin);
+        app.add_user_server_plugin(server::plugins::ServerPortalsPlugin);
+        app.add_user_server_plugin(server::plugins::ServerDangerPlugin);
+        app.add_user_server_plugin(server::plugins::ServerContentFilterPlugin);
+        app.add_user_server_plugin(server::plugins::ServerModerationPlugin);
+        app.add_user_server_plugin(server::plugins::ServerSocialPlugin);
+        app.add_user_server_plugin(server::plugins::ServerSkillsPlugin);
+        app.add_user_server_plugin(server::plugins::ServerAchievementsPlugin);
+        app.add_user_server_plugin(server::plugins::ServerTutorialPlugin);
+        app.add_user_server_plugin(server::plugins::ServerWarmChunksPlugin);
+        app.add_user_server_plugin(server::plugins::ServerViewDistancePlugin);
+        app.add_user_server_plugin(server::plugins::ServerObserversPlugin);
+        app.add_user_server_plugin(server::plugins::ServerResourcePackPlugin);
+        app.add_user_server_plugin(server::plugins::ServerTimelapsePlugin);
+        #[cfg(feature = "sharding")]
+        app.add_user_server_plugin(server::plugins::ServerShardsPlugin);
+        #[cfg(feature = "sqlite")]
+        app.add_user_server_plugin(server::plugins::ServerDatabasePlugin);
+        #[cfg(feature = "cloud-sync")]
+        app.add_user_server_plugin(server::plugins::ServerCloudSyncPlugin);
+        #[cfg(feature = "inspector")]
+        app.add_user_server_plugin(server::plugins::ServerInspectorPlugin);
+        #[cfg(feature = "worldgen-tuning")]
+        app.add_user_server_plugin(server::plugins::ServerWorldgenTuningPlugin);
+    }
     #[cfg(feature = "gui")]
-    app.add_user_renderer_plugin(renderer::ExampleRendererPlugin);
+    if bot_port.is_none() {
+        app.add_user_renderer_plugin(renderer::ExampleRendererPlugin);
+    }
     // run the app
     app.run();
 }
 
+// Shards of a local cluster listen on their own ports, 10 apart
+#[cfg(feature = "sharding")]
+fn configure_shard(settings: &mut settings_common::Settings, id: u32) {
+    use settings_common::{ServerTransports, ShardSettings};
+    let offset = (id * 10) as u16;
+    for transport in settings.server.transport.iter_mut() {
+        match transport {
+            ServerTransports::Udp { local_port }
+            | ServerTransports::WebTransport { local_port, .. } => *local_port += offset,
+            #[cfg(feature = "websocket")]
+            ServerTransports::WebSocket { local_port } => *local_port += offset,
+            #[cfg(feature = "steam")]
+            ServerTransports::Steam { game_port, .. } => *game_port += offset,
+        }
+    }
+    // clients are handed off to the port of the same transport they connected with
+    settings.server.shard = Some(ShardSettings::local(id, settings.client.server_port + offset));
+}
+
 // 2d camera
 fn setup_camera(mut commands: Commands) {
     commands.spawn(Camera2dBundle::default());
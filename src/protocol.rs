@@ -4,92 +4,39 @@
 //! You can use the `#[protocol]` attribute to specify additional behaviour:
 //! - how entities contained in the message should be mapped from the remote world to the local world
 //! - how the component should be synchronized between the `Confirmed` entity and the `Predicted`/`Interpolated` entity
-use std::ops::{Add, Mul};
-
-use bevy::ecs::entity::MapEntities;
-use bevy::prelude::{
-    default, Bundle, Color, Component, Deref, DerefMut, Entity, EntityMapper, Vec2,
-};
+//!
+//! The protocol is split by topic; each submodule defines its types and registers them in its
+//! `register` function. Every registration must run on both sides, whatever features are enabled:
+//! the client and the server only understand each other if they build the same protocol. Types
+//! defined by gameplay modules are registered here too, never in their plugins, so that the
+//! protocol test covers the whole protocol.
+use bevy::prelude::default;
 use bevy::prelude::{App, Plugin};
 use serde::{Deserialize, Serialize};
 
-use lightyear::client::components::ComponentSyncMode;
 use lightyear::prelude::*;
 
-// Player
-#[derive(Bundle)]
-pub(crate) struct PlayerBundle {
-    id: PlayerId,
-    position: PlayerPosition,
-    color: PlayerColor,
-    name: PlayerName,
-}
-
-impl PlayerBundle {
-    pub(crate) fn new(id: ClientId, position: Vec2) -> Self {
-        // Generate pseudo random color from client id.
-        let h = (((id.to_bits().wrapping_mul(30)) % 360) as f32) / 360.0;
-        let s = 0.8;
-        let l = 0.5;
-        let color = Color::hsl(h, s, l);
-        Self {
-            id: PlayerId(id),
-            position: PlayerPosition(position),
-            color: PlayerColor(color),
-            name: PlayerName(format!("Player {}", id)),
-        }
-    }
-}
-
-// Components
-
-#[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq)]
-pub struct PlayerId(ClientId);
-
-impl PlayerId {
-    pub fn client_id(&self) -> ClientId {
-        self.0
-    }
-}
-
-#[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq, Deref, DerefMut)]
-pub struct PlayerPosition(pub Vec2);
-
-impl Add for PlayerPosition {
-    type Output = PlayerPosition;
-    #[inline]
-    fn add(self, rhs: PlayerPosition) -> PlayerPosition {
-        PlayerPosition(self.0.add(rhs.0))
-    }
-}
-
-impl Mul<f32> for &PlayerPosition {
-    type Output = PlayerPosition;
-
-    fn mul(self, rhs: f32) -> Self::Output {
-        PlayerPosition(self.0 * rhs)
-    }
-}
-
-#[derive(Component, Deserialize, Serialize, Clone, Debug, PartialEq)]
-pub struct PlayerColor(pub(crate) Color);
-
-#[derive(Component, Deserialize, Serialize, Clone, Debug, PartialEq)]
-pub struct PlayerName(String);
-
-// Example of a component that contains an entity.
-// This component, when replicated, needs to have the inner entity mapped from the Server world
-// to the client World.
-// You will need to derive the `MapEntities` trait for the component, and register
-// app.add_map_entities<PlayerParent>() in your protocol
-#[derive(Component, Deserialize, Serialize, Clone, Debug, PartialEq)]
-pub struct PlayerParent(Entity);
-
-impl MapEntities for PlayerParent {
-    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
-        self.0 = entity_mapper.map_entity(self.0);
-    }
-}
+use crate::shared::net_diagnostics::RegisterNetMessageExt;
+
+mod admin;
+mod chat;
+mod inventory;
+mod observer;
+mod player;
+mod progress;
+mod resource_pack;
+mod social;
+mod structures;
+mod survival;
+mod world;
+
+pub use admin::*;
+pub use chat::*;
+pub use inventory::*;
+pub use observer::*;
+pub use player::*;
+pub use resource_pack::*;
+pub use world::*;
 
 // Channels
 
@@ -101,62 +48,32 @@ pub struct Channel1;
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct Message1(pub usize);
 
-// Inputs
-
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
-pub struct Direction {
-    pub(crate) up: bool,
-    pub(crate) down: bool,
-    pub(crate) left: bool,
-    pub(crate) right: bool,
-}
-
-impl Direction {
-    pub(crate) fn is_none(&self) -> bool {
-        !self.up && !self.down && !self.left && !self.right
-    }
-}
-
-#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
-pub enum Inputs {
-    Direction(Direction),
-    Delete,
-    Spawn,
-    None,
-}
-
 // Protocol
 #[derive(Clone)]
 pub(crate) struct ProtocolPlugin;
 
 impl Plugin for ProtocolPlugin {
     fn build(&self, app: &mut App) {
-        // messages
-        app.register_message::<Message1>(ChannelDirection::Bidirectional);
-        // inputs
-        app.add_plugins(InputPlugin::<Inputs>::default());
-        // components
-        app.register_component::<PlayerId>(ChannelDirection::ServerToClient)
-            .add_prediction(ComponentSyncMode::Once)
-            .add_interpolation(ComponentSyncMode::Once);
-
-        app.register_component::<PlayerPosition>(ChannelDirection::ServerToClient)
-            .add_prediction(ComponentSyncMode::Full)
-            .add_interpolation(ComponentSyncMode::Full)
-            .add_linear_interpolation_fn();
-
-        app.register_component::<PlayerColor>(ChannelDirection::ServerToClient)
-            .add_prediction(ComponentSyncMode::Once)
-            .add_interpolation(ComponentSyncMode::Once);
-
-        app.register_component::<PlayerName>(ChannelDirection::ServerToClient)
-            .add_prediction(ComponentSyncMode::Once)
-            .add_interpolation(ComponentSyncMode::Once);
+        app.register_net_message::<Message1, Channel1>(ChannelDirection::Bidirectional);
+        player::register(app);
+        chat::register(app);
+        world::register(app);
+        inventory::register(app);
+        admin::register(app);
+        observer::register(app);
+        resource_pack::register(app);
+        social::register(app);
+        progress::register(app);
+        structures::register(app);
+        survival::register(app);
 
         // channels
-        app.add_channel::<Channel1>(ChannelSettings {
+        app.add_net_channel::<Channel1>(ChannelSettings {
             mode: ChannelMode::OrderedReliable(ReliableSettings::default()),
             ..default()
         });
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests;
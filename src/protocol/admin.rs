@@ -0,0 +1,51 @@
+//! Server administration: network simulation, the connection queue, shard handoffs, restarts and
+//! the server's feature flags
+use std::net::SocketAddr;
+
+use bevy::prelude::App;
+use serde::{Deserialize, Serialize};
+
+use lightyear::prelude::*;
+
+use super::Channel1;
+use crate::shared::feature_flags::ServerFeatureFlags;
+use crate::shared::net_diagnostics::RegisterNetMessageExt;
+
+/// Asks a client to simulate bad network conditions on its incoming packets (`None` turns it off).
+/// Sent by the `/netsim` admin command; the client reconnects to apply it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SetLinkConditioner(pub Option<LinkConditions>);
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct LinkConditions {
+    pub latency_ms: u16,
+    pub jitter_ms: u16,
+    pub packet_loss: f32,
+}
+
+/// Sent to clients waiting for a free slot on a full server; position 1 is next in line
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct QueueStatus {
+    pub position: usize,
+    pub length: usize,
+}
+
+/// Sent by a shard when the player walks into a region owned by another shard: the client
+/// reconnects to `server_addr`, which loads the player from the shared profile store
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct ShardHandoff {
+    pub server_addr: SocketAddr,
+}
+
+/// Sent when a scheduled restart is about to take the server down: the client keeps trying to
+/// connect again until the server is back
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct ServerRestarting;
+
+pub(crate) fn register(app: &mut App) {
+    app.register_net_message::<SetLinkConditioner, Channel1>(ChannelDirection::ServerToClient);
+    app.register_net_message::<QueueStatus, Channel1>(ChannelDirection::ServerToClient);
+    app.register_net_message::<ShardHandoff, Channel1>(ChannelDirection::ServerToClient);
+    app.register_net_message::<ServerRestarting, Channel1>(ChannelDirection::ServerToClient);
+    app.register_net_message::<ServerFeatureFlags, Channel1>(ChannelDirection::ServerToClient);
+}
@@ -0,0 +1,42 @@
+//! Chat lines typed by players and printed by the server
+use bevy::prelude::{default, App};
+use serde::{Deserialize, Serialize};
+
+use lightyear::prelude::*;
+
+use crate::shared::net_diagnostics::RegisterNetMessageExt;
+
+#[derive(Channel)]
+pub struct ChatChannel;
+
+/// A line typed by a player. Lines starting with `/` are parsed as commands by the server.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ChatMessage {
+    pub text: String,
+}
+
+/// A line displayed in the client chat log. `sender` is `None` for server/system messages
+/// (command feedback, announcements).
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ChatLine {
+    pub sender: Option<String>,
+    pub text: String,
+}
+
+impl ChatLine {
+    pub fn system(text: impl Into<String>) -> Self {
+        Self {
+            sender: None,
+            text: text.into(),
+        }
+    }
+}
+
+pub(crate) fn register(app: &mut App) {
+    app.register_net_message::<ChatMessage, ChatChannel>(ChannelDirection::ClientToServer);
+    app.register_net_message::<ChatLine, ChatChannel>(ChannelDirection::ServerToClient);
+    app.add_net_channel::<ChatChannel>(ChannelSettings {
+        mode: ChannelMode::OrderedReliable(ReliableSettings::default()),
+        ..default()
+    });
+}
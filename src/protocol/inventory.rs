@@ -0,0 +1,31 @@
+//! Items carried, held and dropped by players
+use bevy::prelude::App;
+use serde::{Deserialize, Serialize};
+
+use lightyear::client::components::ComponentSyncMode;
+use lightyear::prelude::*;
+
+use super::Channel1;
+use crate::shared::items::{DroppedItem, HeldItem, Inventory};
+use crate::shared::net_diagnostics::RegisterNetMessageExt;
+
+/// Select the hotbar slot whose item the player holds
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct SelectHotbarSlot {
+    pub slot: usize,
+}
+
+pub(crate) fn register(app: &mut App) {
+    app.register_net_message::<SelectHotbarSlot, Channel1>(ChannelDirection::ClientToServer);
+
+    app.register_component::<DroppedItem>(ChannelDirection::ServerToClient)
+        .add_prediction(ComponentSyncMode::Once)
+        .add_interpolation(ComponentSyncMode::Once);
+
+    app.register_component::<Inventory>(ChannelDirection::ServerToClient)
+        .add_prediction(ComponentSyncMode::Simple);
+
+    app.register_component::<HeldItem>(ChannelDirection::ServerToClient)
+        .add_prediction(ComponentSyncMode::Simple)
+        .add_interpolation(ComponentSyncMode::Simple);
+}
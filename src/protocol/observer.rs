@@ -0,0 +1,49 @@
+//! Observers: clients watching the world from delayed snapshots, without a player entity
+use bevy::prelude::{default, App, Color, Vec2};
+use serde::{Deserialize, Serialize};
+
+use lightyear::prelude::*;
+
+use crate::shared::net_diagnostics::RegisterNetMessageExt;
+
+/// Channel for observer snapshots. A newer snapshot replaces an older one, so lost snapshots are
+/// never resent, and casts yield to the players' own traffic.
+#[derive(Channel)]
+pub struct ObserverChannel;
+
+// Priority of `ObserverChannel` relative to the default priority (1.0) of the other channels
+pub const OBSERVER_PRIORITY: f32 = 0.5;
+
+/// The world as it was `delay_secs` ago, around the region the observer watches
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ObserverSnapshot {
+    pub world_time: f64,
+    pub delay_secs: f64,
+    pub players: Vec<ObservedPlayer>,
+    pub npcs: Vec<Vec2>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ObservedPlayer {
+    pub name: String,
+    pub color: Color,
+    pub position: Vec2,
+}
+
+/// Move the region an observer watches; `radius` is in tiles, `None` watches the whole world.
+/// The server caps the radius.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct ObserveRegion {
+    pub center: Vec2,
+    pub radius: Option<f32>,
+}
+
+pub(crate) fn register(app: &mut App) {
+    app.register_net_message::<ObserverSnapshot, ObserverChannel>(ChannelDirection::ServerToClient);
+    app.register_net_message::<ObserveRegion, ObserverChannel>(ChannelDirection::ClientToServer);
+    app.add_net_channel::<ObserverChannel>(ChannelSettings {
+        mode: ChannelMode::SequencedUnreliable,
+        priority: OBSERVER_PRIORITY,
+        ..default()
+    });
+}
@@ -0,0 +1,147 @@
+//! Player entities and the inputs that move them
+use std::ops::{Add, Mul};
+
+use bevy::ecs::entity::MapEntities;
+use bevy::prelude::{App, Bundle, Color, Component, Deref, DerefMut, Entity, EntityMapper, Vec2};
+use serde::{Deserialize, Serialize};
+
+use lightyear::client::components::ComponentSyncMode;
+use lightyear::prelude::*;
+
+use crate::shared::items::{HeldItem, Inventory, ItemKind};
+use crate::shared::kinematics::Velocity;
+
+// Player
+#[derive(Bundle)]
+pub(crate) struct PlayerBundle {
+    id: PlayerId,
+    position: PlayerPosition,
+    color: PlayerColor,
+    name: PlayerName,
+    inventory: Inventory,
+    held_item: HeldItem,
+    velocity: Velocity,
+}
+
+impl PlayerBundle {
+    pub(crate) fn new(id: ClientId, position: Vec2) -> Self {
+        // Generate pseudo random color from client id.
+        let h = (((id.to_bits().wrapping_mul(30)) % 360) as f32) / 360.0;
+        let s = 0.8;
+        let l = 0.5;
+        let color = Color::hsl(h, s, l);
+        Self {
+            id: PlayerId(id),
+            position: PlayerPosition(position),
+            color: PlayerColor(color),
+            name: PlayerName(format!("Player {}", id)),
+            inventory: Inventory::starter_kit(),
+            held_item: HeldItem {
+                slot: 0,
+                kind: Some(ItemKind::Axe),
+            },
+            velocity: Velocity::default(),
+        }
+    }
+}
+
+// Components
+
+#[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PlayerId(ClientId);
+
+impl PlayerId {
+    pub fn client_id(&self) -> ClientId {
+        self.0
+    }
+}
+
+#[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq, Deref, DerefMut)]
+pub struct PlayerPosition(pub Vec2);
+
+impl Add for PlayerPosition {
+    type Output = PlayerPosition;
+    #[inline]
+    fn add(self, rhs: PlayerPosition) -> PlayerPosition {
+        PlayerPosition(self.0.add(rhs.0))
+    }
+}
+
+impl Mul<f32> for &PlayerPosition {
+    type Output = PlayerPosition;
+
+    fn mul(self, rhs: f32) -> Self::Output {
+        PlayerPosition(self.0 * rhs)
+    }
+}
+
+#[derive(Component, Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct PlayerColor(pub(crate) Color);
+
+#[derive(Component, Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct PlayerName(pub String);
+
+// Example of a component that contains an entity.
+// This component, when replicated, needs to have the inner entity mapped from the Server world
+// to the client World.
+// You will need to derive the `MapEntities` trait for the component, and register
+// app.add_map_entities<PlayerParent>() in your protocol
+#[derive(Component, Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct PlayerParent(Entity);
+
+impl MapEntities for PlayerParent {
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        self.0 = entity_mapper.map_entity(self.0);
+    }
+}
+
+// Inputs
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct Direction {
+    pub(crate) up: bool,
+    pub(crate) down: bool,
+    pub(crate) left: bool,
+    pub(crate) right: bool,
+}
+
+impl Direction {
+    pub(crate) fn is_none(&self) -> bool {
+        !self.up && !self.down && !self.left && !self.right
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub enum Inputs {
+    Direction(Direction),
+    Delete,
+    Spawn,
+    None,
+}
+
+pub(crate) fn register(app: &mut App) {
+    // inputs
+    app.add_plugins(InputPlugin::<Inputs>::default());
+    // components
+    app.register_component::<PlayerId>(ChannelDirection::ServerToClient)
+        .add_prediction(ComponentSyncMode::Once)
+        .add_interpolation(ComponentSyncMode::Once);
+
+    app.register_component::<PlayerPosition>(ChannelDirection::ServerToClient)
+        .add_prediction(ComponentSyncMode::Full)
+        .add_interpolation(ComponentSyncMode::Full)
+        .add_linear_interpolation_fn();
+
+    app.register_component::<PlayerColor>(ChannelDirection::ServerToClient)
+        .add_prediction(ComponentSyncMode::Once)
+        .add_interpolation(ComponentSyncMode::Once);
+
+    // names change with /name, so keep syncing them
+    app.register_component::<PlayerName>(ChannelDirection::ServerToClient)
+        .add_prediction(ComponentSyncMode::Simple)
+        .add_interpolation(ComponentSyncMode::Simple);
+
+    // predicted, so that a knockback replays on the client like the inputs do
+    app.register_component::<Velocity>(ChannelDirection::ServerToClient)
+        .add_prediction(ComponentSyncMode::Full);
+}
@@ -0,0 +1,24 @@
+//! How far players got: their skills, achievements and the tutorial
+use bevy::prelude::App;
+
+use lightyear::client::components::ComponentSyncMode;
+use lightyear::prelude::*;
+
+use super::Channel1;
+use crate::shared::achievements::{AchievementUnlocked, Achievements};
+use crate::shared::net_diagnostics::RegisterNetMessageExt;
+use crate::shared::skills::SkillLevels;
+use crate::shared::tutorial::Tutorial;
+
+pub(crate) fn register(app: &mut App) {
+    app.register_net_message::<AchievementUnlocked, Channel1>(ChannelDirection::ServerToClient);
+
+    app.register_component::<SkillLevels>(ChannelDirection::ServerToClient)
+        .add_prediction(ComponentSyncMode::Simple);
+
+    app.register_component::<Achievements>(ChannelDirection::ServerToClient)
+        .add_prediction(ComponentSyncMode::Simple);
+
+    app.register_component::<Tutorial>(ChannelDirection::ServerToClient)
+        .add_prediction(ComponentSyncMode::Simple);
+}
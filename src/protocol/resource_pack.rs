@@ -0,0 +1,56 @@
+//! Resource packs offered by the server, and their download
+use bevy::prelude::{default, App};
+use serde::{Deserialize, Serialize};
+
+use lightyear::prelude::*;
+
+use crate::shared::net_diagnostics::RegisterNetMessageExt;
+use crate::shared::resource_pack::PackHash;
+
+/// Channel for resource pack downloads, yielding to the chunk stream so that terrain keeps
+/// arriving while a pack downloads
+#[derive(Channel)]
+pub struct ResourcePackChannel;
+
+// Priority of `ResourcePackChannel` relative to the default priority (1.0) of the other channels
+pub const RESOURCE_PACK_PRIORITY: f32 = 0.1;
+
+/// Sent by the server as clients connect, if it has a resource pack
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ResourcePackOffer {
+    pub name: String,
+    pub description: String,
+    /// Bytes to download
+    pub size: u32,
+    pub hash: PackHash,
+}
+
+/// Asks the server to send the pack it offered
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct ResourcePackRequest {
+    pub hash: PackHash,
+}
+
+/// Part of the pack being downloaded, starting `offset` bytes into it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ResourcePackPart {
+    pub offset: u32,
+    pub data: Vec<u8>,
+}
+
+pub(crate) fn register(app: &mut App) {
+    app.register_net_message::<ResourcePackOffer, ResourcePackChannel>(
+        ChannelDirection::ServerToClient,
+    );
+    app.register_net_message::<ResourcePackRequest, ResourcePackChannel>(
+        ChannelDirection::ClientToServer,
+    );
+    app.register_net_message::<ResourcePackPart, ResourcePackChannel>(
+        ChannelDirection::ServerToClient,
+    );
+    app.add_net_channel::<ResourcePackChannel>(ChannelSettings {
+        mode: ChannelMode::OrderedReliable(ReliableSettings::default()),
+        priority: RESOURCE_PACK_PRIORITY,
+        ..default()
+    });
+}
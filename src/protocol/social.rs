@@ -0,0 +1,22 @@
+//! Playing with others: parties, friend and block lists, and reports to the moderators
+use bevy::prelude::App;
+
+use lightyear::prelude::*;
+
+use super::{Channel1, ChatChannel};
+use crate::shared::moderation::ReportPlayer;
+use crate::shared::net_diagnostics::RegisterNetMessageExt;
+use crate::shared::party::{PartyInvite, PartyPing, PartyRequest, PartySync};
+use crate::shared::social::{SetRelation, SocialListsSync};
+
+pub(crate) fn register(app: &mut App) {
+    app.register_net_message::<PartyRequest, Channel1>(ChannelDirection::ClientToServer);
+    app.register_net_message::<PartySync, Channel1>(ChannelDirection::ServerToClient);
+    app.register_net_message::<PartyInvite, Channel1>(ChannelDirection::ServerToClient);
+    app.register_net_message::<PartyPing, Channel1>(ChannelDirection::ServerToClient);
+
+    app.register_net_message::<SetRelation, Channel1>(ChannelDirection::ClientToServer);
+    app.register_net_message::<SocialListsSync, Channel1>(ChannelDirection::ServerToClient);
+
+    app.register_net_message::<ReportPlayer, ChatChannel>(ChannelDirection::ClientToServer);
+}
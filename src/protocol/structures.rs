@@ -0,0 +1,39 @@
+//! Structures players build or find in the world: rails and the carts riding them, containers
+//! and power networks, portals and dungeons
+use bevy::prelude::App;
+
+use lightyear::client::components::ComponentSyncMode;
+use lightyear::prelude::*;
+
+use super::Channel1;
+use crate::shared::instances::{DungeonDoor, EnterInstance, LeaveInstance};
+use crate::shared::logistics::ItemsMoved;
+use crate::shared::net_diagnostics::RegisterNetMessageExt;
+use crate::shared::portals::{DiscoveredPortals, Portal, UsePortal};
+use crate::shared::power::PowerState;
+use crate::shared::rails::{Cart, LeaveCart, PlaceCart, Riding};
+
+pub(crate) fn register(app: &mut App) {
+    app.register_net_message::<PlaceCart, Channel1>(ChannelDirection::ClientToServer);
+    app.register_net_message::<LeaveCart, Channel1>(ChannelDirection::ClientToServer);
+    app.register_net_message::<ItemsMoved, Channel1>(ChannelDirection::ServerToClient);
+    app.register_net_message::<UsePortal, Channel1>(ChannelDirection::ClientToServer);
+    app.register_net_message::<DiscoveredPortals, Channel1>(ChannelDirection::ServerToClient);
+    app.register_net_message::<EnterInstance, Channel1>(ChannelDirection::ServerToClient);
+    app.register_net_message::<LeaveInstance, Channel1>(ChannelDirection::ServerToClient);
+
+    app.register_component::<Cart>(ChannelDirection::ServerToClient)
+        .add_interpolation(ComponentSyncMode::Simple);
+    app.register_component::<Riding>(ChannelDirection::ServerToClient)
+        .add_prediction(ComponentSyncMode::Full)
+        .add_interpolation(ComponentSyncMode::Simple);
+
+    app.register_component::<PowerState>(ChannelDirection::ServerToClient)
+        .add_interpolation(ComponentSyncMode::Simple);
+
+    app.register_component::<Portal>(ChannelDirection::ServerToClient)
+        .add_interpolation(ComponentSyncMode::Once);
+
+    app.register_component::<DungeonDoor>(ChannelDirection::ServerToClient)
+        .add_interpolation(ComponentSyncMode::Once);
+}
@@ -0,0 +1,43 @@
+//! Keeping players alive: their health, hunger and breath, the weather and their exposure to it,
+//! the danger around them, and the graves they leave when they die
+use bevy::prelude::App;
+
+use lightyear::client::components::ComponentSyncMode;
+use lightyear::prelude::*;
+
+use super::Channel1;
+use crate::shared::danger::DangerLevel;
+use crate::shared::graves::{Grave, GraveSites};
+use crate::shared::net_diagnostics::RegisterNetMessageExt;
+use crate::shared::survival::{Breath, EatHeldItem, Health, Hunger, Spectator};
+use crate::shared::temperature::{Exposure, WeatherChanged};
+
+pub(crate) fn register(app: &mut App) {
+    app.register_net_message::<EatHeldItem, Channel1>(ChannelDirection::ClientToServer);
+    app.register_net_message::<WeatherChanged, Channel1>(ChannelDirection::ServerToClient);
+
+    app.register_component::<Health>(ChannelDirection::ServerToClient)
+        .add_prediction(ComponentSyncMode::Simple)
+        .add_interpolation(ComponentSyncMode::Simple);
+
+    app.register_component::<Breath>(ChannelDirection::ServerToClient)
+        .add_prediction(ComponentSyncMode::Simple);
+
+    app.register_component::<Hunger>(ChannelDirection::ServerToClient)
+        .add_prediction(ComponentSyncMode::Simple);
+
+    app.register_component::<Spectator>(ChannelDirection::ServerToClient)
+        .add_prediction(ComponentSyncMode::Once);
+
+    app.register_component::<Exposure>(ChannelDirection::ServerToClient)
+        .add_prediction(ComponentSyncMode::Simple);
+
+    app.register_component::<DangerLevel>(ChannelDirection::ServerToClient)
+        .add_prediction(ComponentSyncMode::Simple);
+
+    app.register_component::<Grave>(ChannelDirection::ServerToClient)
+        .add_interpolation(ComponentSyncMode::Simple);
+
+    app.register_component::<GraveSites>(ChannelDirection::ServerToClient)
+        .add_prediction(ComponentSyncMode::Simple);
+}
@@ -0,0 +1,63 @@
+//! The protocol must build identically on the client, on the server and in a host-server app.
+//! CI also runs these tests with only the `client` and only the `server` feature, so that the
+//! protocol is checked in every build that ships.
+use bevy::prelude::*;
+use lightyear::prelude::client::ClientConfig;
+use lightyear::prelude::server::ServerConfig;
+use lightyear::prelude::*;
+
+use super::ProtocolPlugin;
+use crate::shared::net_diagnostics::{MessageEntry, ProtocolManifest};
+
+enum Side {
+    Client,
+    Server,
+    HostServer,
+}
+
+// Messages and channels declared by the protocol when built for the given side
+fn build_protocol(side: Side) -> (Vec<MessageEntry>, Vec<&'static str>) {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins);
+    let mode = match side {
+        Side::HostServer => Mode::HostServer,
+        _ => Mode::Separate,
+    };
+    let shared = || SharedConfig { mode, ..default() };
+    if matches!(side, Side::Client | Side::HostServer) {
+        app.add_plugins(client::ClientPlugins {
+            config: ClientConfig {
+                shared: shared(),
+                ..default()
+            },
+        });
+    }
+    if matches!(side, Side::Server | Side::HostServer) {
+        app.add_plugins(server::ServerPlugins {
+            config: ServerConfig {
+                shared: shared(),
+                ..default()
+            },
+        });
+    }
+    app.add_plugins(ProtocolPlugin);
+    app.finish();
+    app.cleanup();
+
+    let manifest = app
+        .world_mut()
+        .remove_resource::<ProtocolManifest>()
+        .unwrap();
+    assert!(manifest.problems().is_empty(), "{:?}", manifest.problems());
+    (manifest.messages, manifest.channels)
+}
+
+#[test]
+fn client_and_server_build_the_same_protocol() {
+    let client = build_protocol(Side::Client);
+    let server = build_protocol(Side::Server);
+    let host_server = build_protocol(Side::HostServer);
+    assert!(!client.0.is_empty());
+    assert_eq!(client, server);
+    assert_eq!(client, host_server);
+}
@@ -0,0 +1,148 @@
+//! Terrain streaming, the world's time, seasons, regions and history, its NPCs, and the ways
+//! players act on the world: harvesting, terrain edits, fishing, interactions and fights
+use bevy::prelude::{default, App};
+use serde::{Deserialize, Serialize};
+
+use lightyear::client::components::ComponentSyncMode;
+use lightyear::prelude::*;
+
+use super::Channel1;
+use crate::shared::history::{HistoryRecorded, WorldHistory};
+use crate::shared::interaction::InteractionTarget;
+use crate::shared::items::ItemKind;
+use crate::shared::net_diagnostics::RegisterNetMessageExt;
+use crate::shared::npc::Npc;
+use crate::shared::projectiles::{Projectile, ThrowStone};
+use crate::shared::regions::{RegionNameRequest, RegionNameSync};
+use crate::shared::reputation::AttackNpc;
+use crate::shared::seasons::SeasonChanged;
+use crate::shared::villages::Villager;
+use crate::shared::world_generation::{
+    ChunkChannel, ChunkData, ChunkDelta, ChunkRequest, ChunkStreamChannel, ChunkUnload, TileType,
+    CHUNK_STREAM_PRIORITY,
+};
+
+/// Authoritative world time, sent periodically so the client's day/night cycle matches the server's
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct WorldTimeSync {
+    pub world_time: f64,
+}
+
+/// Farthest, in chunks, the server sends chunks around the player. `reduced` while the server
+/// shrinks it to keep up with its load.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ViewDistanceLimit {
+    pub max_chunks: i32,
+    pub reduced: bool,
+}
+
+/// Asks the server to harvest the resource on a tile next to the player
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct HarvestRequest {
+    pub tile: (i32, i32),
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TerrainAction {
+    Dig,
+    Raise,
+    // Build stairs, letting players climb the cliffs around the tile
+    Stairs,
+    // Build a furnace out of stone, to cook at
+    Furnace,
+    // Build a wooden door, or a wider gate, that players open and close
+    Door,
+    Gate,
+    // Lay a rail, made of iron, for carts to roll along
+    Rail,
+    // Build a wooden chest to store items in, or an inserter moving items between the containers
+    // behind and in front of it
+    Chest,
+    Inserter,
+    // Build a generator, a copper wire carrying its power, or an assembler running on it
+    Generator,
+    Wire,
+    Assembler,
+    // Lay a tile of ground, made of the item it's laid from: stone, or water from a bucket
+    Place(TileType),
+}
+
+/// Asks the server to dig, raise, place ground, or build a structure on a tile near the player
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct TerrainEditRequest {
+    pub tile: (i32, i32),
+    pub action: TerrainAction,
+}
+
+/// Cast a fishing line onto a water tile next to the player
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct CastLine {
+    pub tile: (i32, i32),
+}
+
+/// Reel the fishing line in; catches the fish if sent during the bite window
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct ReelIn;
+
+/// Feedback on the player's fishing session
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum FishingEvent {
+    Cast,
+    Bite,
+    Caught { kind: ItemKind, count: u32 },
+    // Reeled in before anything bit
+    TooEarly,
+    // The bite window passed without reeling in
+    Escaped,
+    Rejected(String),
+}
+
+/// Interact with a target picked by `best_target`; the server checks it's still valid
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct InteractRequest {
+    pub target: InteractionTarget,
+}
+
+pub(crate) fn register(app: &mut App) {
+    app.register_net_message::<WorldTimeSync, Channel1>(ChannelDirection::ServerToClient);
+    app.register_net_message::<SeasonChanged, Channel1>(ChannelDirection::ServerToClient);
+    app.register_net_message::<WorldHistory, Channel1>(ChannelDirection::ServerToClient);
+    app.register_net_message::<HistoryRecorded, Channel1>(ChannelDirection::ServerToClient);
+    app.register_net_message::<RegionNameRequest, Channel1>(ChannelDirection::ClientToServer);
+    app.register_net_message::<RegionNameSync, Channel1>(ChannelDirection::ServerToClient);
+    app.register_net_message::<ViewDistanceLimit, Channel1>(ChannelDirection::ServerToClient);
+    app.register_net_message::<HarvestRequest, Channel1>(ChannelDirection::ClientToServer);
+    app.register_net_message::<TerrainEditRequest, Channel1>(ChannelDirection::ClientToServer);
+    app.register_net_message::<CastLine, Channel1>(ChannelDirection::ClientToServer);
+    app.register_net_message::<ReelIn, Channel1>(ChannelDirection::ClientToServer);
+    app.register_net_message::<FishingEvent, Channel1>(ChannelDirection::ServerToClient);
+    app.register_net_message::<InteractRequest, Channel1>(ChannelDirection::ClientToServer);
+    app.register_net_message::<AttackNpc, Channel1>(ChannelDirection::ClientToServer);
+    app.register_net_message::<ThrowStone, Channel1>(ChannelDirection::ClientToServer);
+
+    app.register_component::<Npc>(ChannelDirection::ServerToClient)
+        .add_prediction(ComponentSyncMode::Simple)
+        .add_interpolation(ComponentSyncMode::Simple);
+
+    app.register_component::<Villager>(ChannelDirection::ServerToClient)
+        .add_interpolation(ComponentSyncMode::Simple);
+
+    app.register_component::<Projectile>(ChannelDirection::ServerToClient);
+
+    // Terrain, only ever sent as messages: whole chunks, the tiles changed in them, and the
+    // chunks to drop
+    app.register_net_message::<ChunkDelta, ChunkChannel>(ChannelDirection::ServerToClient);
+    app.register_net_message::<ChunkUnload, ChunkChannel>(ChannelDirection::ServerToClient);
+    app.register_net_message::<ChunkRequest, ChunkChannel>(ChannelDirection::ClientToServer);
+    app.register_net_message::<ChunkData, ChunkStreamChannel>(ChannelDirection::ServerToClient);
+
+    app.add_net_channel::<ChunkChannel>(ChannelSettings {
+        mode: ChannelMode::OrderedReliable(ReliableSettings::default()),
+        ..default()
+    });
+    app.add_net_channel::<ChunkStreamChannel>(ChannelSettings {
+        mode: ChannelMode::UnorderedReliable(ReliableSettings::default()),
+        priority: CHUNK_STREAM_PRIORITY,
+        ..default()
+    });
+}
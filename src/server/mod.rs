@@ -15,6 +15,10 @@ use std::sync::Arc;
 
 use crate::protocol::*;
 use crate::shared;
+use crate::shared::instances::InstanceId;
+use crate::shared::kinematics::Velocity;
+use crate::shared::movement::LoadedTiles;
+use crate::shared::rails::Riding;
 
 pub mod plugins;
 
@@ -25,7 +29,7 @@ impl Plugin for ExampleServerPlugin {
         app.init_resource::<ClientEntityMap>();
         app.add_systems(Startup, start_server);
         // the physics/FixedUpdates systems that consume inputs should be run in this set.
-        app.add_systems(FixedUpdate, movement);
+        app.add_systems(FixedUpdate, (movement, drift).chain());
         app.add_systems(Update, (send_message, handle_connections));
         #[cfg(not(feature = "client"))]
         app.add_systems(Update, server_start_stop);
@@ -42,14 +46,14 @@ fn start_server(mut commands: Commands) {
     commands.start_server();
 }
 
-/// Server connection system, create a player upon connection
+/// Server connection system, create a player once the connection queue admits the client
 pub(crate) fn handle_connections(
-    mut connections: EventReader<ConnectEvent>,
+    mut admissions: EventReader<plugins::PlayerAdmitted>,
     mut entity_map: ResMut<ClientEntityMap>,
     mut commands: Commands,
 ) {
-    for connection in connections.read() {
-        let client_id = connection.client_id;
+    for admission in admissions.read() {
+        let client_id = admission.client_id;
         // in host-server mode, server and client are running in the same app, no need to replicate to the local client
         let replicate = Replicate {
             sync: SyncTarget {
@@ -100,7 +104,12 @@ pub(crate) fn handle_disconnections(
 
 /// Read client inputs and move players in server therefore giving a basis for other clients
 fn movement(
-    mut position_query: Query<&mut PlayerPosition>,
+    mut position_query: Query<(
+        &mut PlayerPosition,
+        Option<&mut Riding>,
+        Option<&InstanceId>,
+    )>,
+    tiles: LoadedTiles,
     entity_map: Res<ClientEntityMap>,
     mut input_reader: EventReader<InputEvent<Inputs>>,
     tick_manager: Res<TickManager>,
@@ -116,8 +125,19 @@ fn movement(
             );
 
             if let Some(player) = entity_map.0.get(&client_id) {
-                if let Ok(position) = position_query.get_mut(*player) {
-                    shared::movement::shared_movement_behaviour(position, input);
+                match position_query.get_mut(*player) {
+                    // riders go where the rails take them
+                    Ok((mut position, Some(mut riding), _)) => {
+                        shared::rails::ride_rails(&mut position, &mut riding, input, &tiles);
+                    }
+                    // instances aren't part of the loaded overworld tiles
+                    Ok((position, None, Some(_))) => {
+                        shared::movement::shared_movement_behaviour(position, input, &());
+                    }
+                    Ok((position, None, None)) => {
+                        shared::movement::shared_movement_behaviour(position, input, &tiles);
+                    }
+                    Err(_) => {}
                 }
             } else {
                 debug!(
@@ -129,6 +149,21 @@ fn movement(
     }
 }
 
+/// Players drift with their velocity every tick, input or not, e.g. after a knockback.
+/// Carts hold their riders in place.
+fn drift(
+    mut players: Query<(&mut PlayerPosition, &mut Velocity, Option<&InstanceId>), Without<Riding>>,
+    tiles: LoadedTiles,
+) {
+    for (position, velocity, instance) in players.iter_mut() {
+        if instance.is_some() {
+            shared::kinematics::shared_drift_behaviour(position, velocity, &());
+        } else {
+            shared::kinematics::shared_drift_behaviour(position, velocity, &tiles);
+        }
+    }
+}
+
 // only run this in dedicated server mode
 #[cfg(not(feature = "client"))]
 pub(crate) fn server_start_stop(
@@ -1,4 +1,257 @@
 mod server_world;
 
 // export server_world as ServerWorldPlugin
-pub use server_world::ServerWorldPlugin;
+pub use server_world::{ChunkInterest, ServerWorldPlugin};
+
+// export server_commands as ServerCommandsPlugin
+mod server_commands;
+pub use server_commands::{CommandPermissions, ServerCommandsPlugin};
+
+// export server_tile_history as ServerTileHistoryPlugin
+mod server_tile_history;
+pub use server_tile_history::{ServerTileHistoryPlugin, TileHistory, TileModifiedEvent};
+
+// export server_restart as ServerRestartPlugin
+mod server_restart;
+pub use server_restart::{RestartScheduler, ServerRestartPlugin};
+
+// export server_netsim as ServerNetsimPlugin
+mod server_netsim;
+pub use server_netsim::ServerNetsimPlugin;
+
+// export server_chunk_audit as ServerChunkAuditPlugin
+mod server_chunk_audit;
+pub use server_chunk_audit::{ChunkAudit, ServerChunkAuditPlugin};
+
+// export server_guardrails as ServerGuardrailsPlugin
+mod server_guardrails;
+pub use server_guardrails::{Guardrails, ServerGuardrailsPlugin};
+
+// export server_items as ServerItemsPlugin
+mod server_items;
+pub use server_items::{spawn_dropped_item, ServerItemsPlugin};
+
+// export server_harvest as ServerHarvestPlugin
+mod server_harvest;
+pub use server_harvest::{
+    validate_harvest, HarvestError, HarvestTileEvent, ResourceHarvestedEvent, ServerHarvestPlugin,
+};
+
+// export server_fishing as ServerFishingPlugin
+mod server_fishing;
+pub use server_fishing::{fishing_loot, FishingSession, ServerFishingPlugin};
+
+// export server_survival as ServerSurvivalPlugin
+mod server_survival;
+pub use server_survival::{
+    tile_type_at, DeathCause, PlayerDiedEvent, ServerSurvivalPlugin, RESPAWN_POSITION,
+};
+
+// export server_feature_flags as ServerFeatureFlagsPlugin
+mod server_feature_flags;
+pub use server_feature_flags::ServerFeatureFlagsPlugin;
+
+// export server_claims as ServerClaimsPlugin
+mod server_claims;
+pub use server_claims::{LandClaims, ServerClaimsPlugin};
+
+// export server_terrain as ServerTerrainPlugin
+mod server_terrain;
+pub use server_terrain::{derive_traversable, ServerTerrainPlugin, TerrainEditedEvent};
+
+// export server_seasons as ServerSeasonsPlugin
+mod server_seasons;
+pub use server_seasons::{SeasonConfig, ServerSeasonsPlugin};
+
+// export server_hotbar as ServerHotbarPlugin
+mod server_hotbar;
+pub use server_hotbar::ServerHotbarPlugin;
+
+// export server_interaction as ServerInteractionPlugin
+mod server_interaction;
+pub use server_interaction::{
+    CartInteractionEvent, DoorInteractionEvent, GraveInteractionEvent, NpcInteractionEvent,
+    ServerInteractionPlugin, StructureInteractionEvent,
+};
+
+// export server_chunk_store as ServerChunkStorePlugin
+mod server_chunk_store;
+pub use server_chunk_store::{ChunkStore, PendingSaves, ServerChunkStorePlugin};
+
+// export server_terrain_import as ServerTerrainImportPlugin
+mod server_terrain_import;
+pub use server_terrain_import::ServerTerrainImportPlugin;
+
+// export server_regions as ServerRegionsPlugin
+mod server_regions;
+pub use server_regions::ServerRegionsPlugin;
+
+// export server_party as ServerPartyPlugin
+mod server_party;
+pub use server_party::{Parties, ServerPartyPlugin};
+
+// export server_graves as ServerGravesPlugin
+mod server_graves;
+pub use server_graves::ServerGravesPlugin;
+
+// export server_cooking as ServerCookingPlugin
+mod server_cooking;
+pub use server_cooking::ServerCookingPlugin;
+
+// export server_temperature as ServerTemperaturePlugin
+mod server_temperature;
+pub use server_temperature::ServerTemperaturePlugin;
+
+// export server_doors as ServerDoorsPlugin
+mod server_doors;
+pub use server_doors::ServerDoorsPlugin;
+
+// export server_rails as ServerRailsPlugin
+mod server_rails;
+pub use server_rails::ServerRailsPlugin;
+
+// export server_logistics as ServerLogisticsPlugin
+mod server_logistics;
+pub use server_logistics::ServerLogisticsPlugin;
+
+// export server_power as ServerPowerPlugin
+mod server_power;
+pub use server_power::{PowerGrid, ServerPowerPlugin};
+
+// export server_villages as ServerVillagesPlugin
+mod server_villages;
+pub use server_villages::ServerVillagesPlugin;
+
+// export server_combat as ServerCombatPlugin
+mod server_combat;
+pub use server_combat::{NpcStruckEvent, ServerCombatPlugin};
+
+// export server_reputation as ServerReputationPlugin
+mod server_reputation;
+pub use server_reputation::{ReputationEvent, ServerReputationPlugin};
+
+// export server_history as ServerHistoryPlugin
+mod server_history;
+pub use server_history::ServerHistoryPlugin;
+
+// export server_pathfinding as ServerPathfindingPlugin
+mod server_pathfinding;
+pub use server_pathfinding::{
+    NavChangedEvent, PathRequest, PathResult, PathfindingStats, ServerPathfindingPlugin,
+};
+
+// export server_difficulty as ServerDifficultyPlugin
+mod server_difficulty;
+pub use server_difficulty::{ServerDifficultyPlugin, WorldDifficulty};
+
+// export server_pregen as ServerPregenPlugin
+mod server_pregen;
+pub use server_pregen::{Pregen, ServerPregenPlugin};
+
+// export server_profiles as ServerProfilesPlugin
+mod server_profiles;
+pub use server_profiles::{LoggedOutBody, PlayerProfile, ProfileStore, ServerProfilesPlugin};
+
+// export server_afk as ServerAfkPlugin
+mod server_afk;
+pub use server_afk::{Afk, Idle, LastActivity, ServerAfkPlugin};
+
+// export server_queue as ServerQueuePlugin
+mod server_queue;
+pub use server_queue::{ConnectionQueue, PlayerAdmitted, ServerQueuePlugin};
+
+// export server_instances as ServerInstancesPlugin
+mod server_instances;
+pub use server_instances::{entrance_tile, Instance, Instances, ServerInstancesPlugin};
+
+// export server_portals as ServerPortalsPlugin
+mod server_portals;
+pub use server_portals::{
+    PendingTeleport, PortalCooldown, PortalError, PortalNetwork, ServerPortalsPlugin,
+};
+
+// export server_danger as ServerDangerPlugin
+mod server_danger;
+pub use server_danger::{ActiveRaid, RaidSurvivedEvent, ServerDangerPlugin};
+
+// export server_content_filter as ServerContentFilterPlugin
+mod server_content_filter;
+pub use server_content_filter::{
+    AddContentFilterExt, ContentFilter, ContentFlagged, ContentKind, FilterAction, FilterHook,
+    FilterMatch, FilterOutcome, ServerContentFilterPlugin, Wordlist,
+};
+
+// export server_moderation as ServerModerationPlugin
+mod server_moderation;
+pub use server_moderation::{
+    ModerationAction, ModerationConfig, ModerationEffect, ModerationError, ModerationQueue, Report,
+    Resolution, ServerModerationPlugin,
+};
+
+// export server_social as ServerSocialPlugin
+mod server_social;
+pub use server_social::ServerSocialPlugin;
+
+// export server_skills as ServerSkillsPlugin
+mod server_skills;
+pub use server_skills::{ServerSkillsPlugin, SkillActionEvent, SkillCurve, SkillTier};
+
+// export server_achievements as ServerAchievementsPlugin
+mod server_achievements;
+pub use server_achievements::{
+    AchievementBook, AchievementDef, AchievementStats, Goal, ServerAchievementsPlugin,
+};
+
+// export server_tutorial as ServerTutorialPlugin
+mod server_tutorial;
+pub use server_tutorial::ServerTutorialPlugin;
+
+// export server_warm_chunks as ServerWarmChunksPlugin
+mod server_warm_chunks;
+pub use server_warm_chunks::{ServerWarmChunksPlugin, WarmChunkTick, WarmChunks};
+
+// export server_view_distance as ServerViewDistancePlugin
+mod server_view_distance;
+pub use server_view_distance::{ServerViewDistance, ServerViewDistancePlugin};
+
+// export server_resource_pack as ServerResourcePackPlugin
+mod server_resource_pack;
+pub use server_resource_pack::ServerResourcePackPlugin;
+
+// export server_timelapse as ServerTimelapsePlugin
+mod server_timelapse;
+pub use server_timelapse::ServerTimelapsePlugin;
+
+// export server_observers as ServerObserversPlugin
+mod server_observers;
+pub use server_observers::{Observers, ServerObserversPlugin};
+
+// export server_shards as ServerShardsPlugin
+#[cfg(feature = "sharding")]
+mod server_shards;
+#[cfg(feature = "sharding")]
+pub use server_shards::{ServerShardsPlugin, ShardMap};
+
+// export server_database as ServerDatabasePlugin
+#[cfg(feature = "sqlite")]
+mod server_database;
+#[cfg(feature = "sqlite")]
+pub use server_database::{open_database, Database, ServerDatabasePlugin};
+
+// export server_cloud_sync as ServerCloudSyncPlugin
+#[cfg(feature = "cloud-sync")]
+mod server_cloud_sync;
+#[cfg(feature = "cloud-sync")]
+pub use server_cloud_sync::ServerCloudSyncPlugin;
+
+// export server_inspector as ServerInspectorPlugin
+#[cfg(feature = "inspector")]
+mod server_inspector;
+#[cfg(feature = "inspector")]
+pub use server_inspector::ServerInspectorPlugin;
+
+// export server_worldgen_tuning as ServerWorldgenTuningPlugin
+#[cfg(feature = "worldgen-tuning")]
+mod server_worldgen_tuning;
+#[cfg(feature = "worldgen-tuning")]
+pub use server_worldgen_tuning::ServerWorldgenTuningPlugin;
@@ -0,0 +1,221 @@
+use bevy::asset::ron;
+use bevy::prelude::*;
+use bevy::time::common_conditions::on_timer;
+use bevy::utils::Duration;
+use lightyear::prelude::server::*;
+use lightyear::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+
+use crate::protocol::{Channel1, PlayerId, PlayerPosition};
+use crate::server::plugins::{RaidSurvivedEvent, ResourceHarvestedEvent};
+use crate::settings_common::Settings;
+use crate::shared::achievements::{AchievementStatus, AchievementUnlocked, Achievements};
+use crate::shared::biome_map::BiomeMap;
+use crate::shared::error::{GameError, ReportError};
+use crate::shared::instances::InstanceId;
+use crate::shared::world_generation::{BiomeType, ResourceType};
+
+const EXPLORE_INTERVAL: Duration = Duration::from_secs(1);
+
+// Server plugin tracking what players did and unlocking the achievements listed in the server
+// settings once their goals are met
+pub struct ServerAchievementsPlugin;
+
+impl Plugin for ServerAchievementsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AchievementBook>()
+            .add_systems(Startup, load_achievements)
+            .add_systems(
+                Update,
+                (
+                    record_biomes.run_if(on_timer(EXPLORE_INTERVAL)),
+                    record_harvests,
+                    record_raids,
+                    check_achievements,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// What has to be done to unlock an achievement
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub enum Goal {
+    VisitBiomes(u32),
+    Harvest { resource: ResourceType, count: u32 },
+    SurviveRaids(u32),
+}
+
+impl Goal {
+    pub fn target(&self) -> u32 {
+        match self {
+            Goal::VisitBiomes(count) | Goal::SurviveRaids(count) => *count,
+            Goal::Harvest { count, .. } => *count,
+        }
+    }
+
+    pub fn progress(&self, stats: &AchievementStats) -> u32 {
+        let progress = match self {
+            Goal::VisitBiomes(_) => stats.biomes.len() as u32,
+            Goal::Harvest { resource, .. } => stats.harvested.get(resource).copied().unwrap_or(0),
+            Goal::SurviveRaids(_) => stats.raids_survived,
+        };
+        progress.min(self.target())
+    }
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct AchievementDef {
+    // Stable identifier saved in profiles; names and descriptions can be reworded
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub goal: Goal,
+}
+
+/// Achievements players can unlock, loaded from the file named in the server settings
+#[derive(Resource, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct AchievementBook(pub Vec<AchievementDef>);
+
+impl AchievementBook {
+    pub fn load(path: &str) -> Result<Self, GameError> {
+        let text = fs::read_to_string(path)?;
+        ron::from_str(&text).map_err(|e| GameError::AchievementList {
+            path: path.to_string(),
+            reason: e.to_string(),
+        })
+    }
+}
+
+/// What a player did towards achievements, and which ones they unlocked. Saved in the profile.
+#[derive(Component, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct AchievementStats {
+    pub biomes: HashSet<BiomeType>,
+    pub harvested: HashMap<ResourceType, u32>,
+    pub raids_survived: u32,
+    pub unlocked: HashSet<String>,
+}
+
+fn load_achievements(
+    settings: Option<Res<Settings>>,
+    mut book: ResMut<AchievementBook>,
+    mut errors: EventWriter<ReportError>,
+) {
+    let Some(settings) = settings else {
+        return;
+    };
+    match AchievementBook::load(&settings.server.achievements.list) {
+        Ok(loaded) => {
+            info!("Loaded {} achievement(s)", loaded.0.len());
+            *book = loaded;
+        }
+        Err(e) => errors.send(ReportError(e)),
+    }
+}
+
+fn record_biomes(
+    biome_map: Res<BiomeMap>,
+    mut players: Query<(&PlayerPosition, &mut AchievementStats), Without<InstanceId>>,
+) {
+    for (position, mut stats) in players.iter_mut() {
+        let biome = biome_map.biome_at_position(position.0);
+        // only touch the stats when there's something new, so that they're only checked then
+        if !stats.biomes.contains(&biome) {
+            stats.biomes.insert(biome);
+        }
+    }
+}
+
+fn record_harvests(
+    mut harvested: EventReader<ResourceHarvestedEvent>,
+    mut players: Query<(&PlayerId, &mut AchievementStats)>,
+) {
+    for event in harvested.read() {
+        if let Some((_, mut stats)) = players
+            .iter_mut()
+            .find(|(id, _)| id.client_id() == event.client_id)
+        {
+            *stats.harvested.entry(event.resource).or_default() += event.count;
+        }
+    }
+}
+
+fn record_raids(
+    mut survived: EventReader<RaidSurvivedEvent>,
+    mut players: Query<(&PlayerId, &mut AchievementStats)>,
+) {
+    for event in survived.read() {
+        for (_, mut stats) in players
+            .iter_mut()
+            .filter(|(id, _)| event.survivors.contains(&id.client_id()))
+        {
+            stats.raids_survived += 1;
+        }
+    }
+}
+
+// Recompute progress whenever stats change, unlocking the achievements whose goal is met
+fn check_achievements(
+    mut commands: Commands,
+    book: Res<AchievementBook>,
+    mut players: Query<(
+        Entity,
+        &PlayerId,
+        &mut AchievementStats,
+        Option<&mut Achievements>,
+    )>,
+    mut connection_manager: ResMut<ConnectionManager>,
+    mut errors: EventWriter<ReportError>,
+) {
+    for (entity, player_id, mut stats, achievements) in players.iter_mut() {
+        if achievements.is_some() && !stats.is_changed() && !book.is_changed() {
+            continue;
+        }
+        let mut list = Vec::with_capacity(book.0.len());
+        for def in book.0.iter() {
+            let progress = def.goal.progress(&stats);
+            let mut unlocked = stats.unlocked.contains(&def.id);
+            if !unlocked && progress >= def.goal.target() {
+                unlocked = true;
+                // bypass change detection, the stats are already being processed
+                stats
+                    .bypass_change_detection()
+                    .unlocked
+                    .insert(def.id.clone());
+                info!(
+                    "Player {} unlocked the achievement {}",
+                    player_id.client_id(),
+                    def.id
+                );
+                if let Err(e) = connection_manager.send_message::<Channel1, _>(
+                    player_id.client_id(),
+                    &AchievementUnlocked {
+                        name: def.name.clone(),
+                        description: def.description.clone(),
+                    },
+                ) {
+                    errors.send(ReportError(GameError::send("AchievementUnlocked", e)));
+                }
+            }
+            list.push(AchievementStatus {
+                id: def.id.clone(),
+                name: def.name.clone(),
+                description: def.description.clone(),
+                progress,
+                goal: def.goal.target(),
+                unlocked,
+            });
+        }
+        let computed = Achievements { list };
+        match achievements {
+            Some(mut achievements) => {
+                achievements.set_if_neq(computed);
+            }
+            None => {
+                commands.entity(entity).insert(computed);
+            }
+        }
+    }
+}
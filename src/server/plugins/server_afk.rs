@@ -0,0 +1,225 @@
+use bevy::prelude::*;
+use bevy::time::common_conditions::on_timer;
+use bevy::utils::Duration;
+use lightyear::prelude::server::*;
+use lightyear::prelude::*;
+
+use crate::protocol::{
+    CastLine, ChatChannel, ChatLine, ChatMessage, HarvestRequest, Inputs, InteractRequest,
+    PlayerId, PlayerName, TerrainEditRequest,
+};
+use crate::server::plugins::ConnectionQueue;
+use crate::settings_common::{AfkSettings, Settings};
+use crate::shared::commands::{CommandInvoked, CommandReply, CommandSpec, RegisterCommandExt};
+use crate::shared::error::{GameError, ReportError};
+use crate::shared::survival::SurvivalPaused;
+
+// How often players are checked for being idle
+const AFK_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+// Server plugin marking players that stopped sending input as idle, and kicking them when the
+// server is full
+pub struct ServerAfkPlugin;
+
+impl Plugin for ServerAfkPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Afk>()
+            .register_command(CommandSpec::new(
+                "who",
+                "List connected players, and who is idle",
+            ))
+            .add_systems(Startup, load_afk_settings)
+            .add_systems(
+                Update,
+                (
+                    track_new_players,
+                    record_input_activity,
+                    record_message_activity,
+                    update_idle_players.run_if(on_timer(AFK_CHECK_INTERVAL)),
+                    kick_idle_players.run_if(on_timer(AFK_CHECK_INTERVAL)),
+                    handle_who_command,
+                )
+                    .chain(),
+            );
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Afk {
+    pub settings: AfkSettings,
+}
+
+/// Time of the player's last input, in seconds since startup
+#[derive(Component, Debug)]
+pub struct LastActivity(pub f64);
+
+/// Marks a player that hasn't sent any input for a while
+#[derive(Component, Debug)]
+pub struct Idle {
+    pub since: f64,
+}
+
+fn load_afk_settings(settings: Option<Res<Settings>>, mut afk: ResMut<Afk>) {
+    if let Some(settings) = settings {
+        afk.settings = settings.server.afk.clone();
+    }
+}
+
+fn track_new_players(
+    mut commands: Commands,
+    time: Res<Time>,
+    players: Query<Entity, Added<PlayerId>>,
+) {
+    for entity in players.iter() {
+        commands
+            .entity(entity)
+            .insert(LastActivity(time.elapsed_secs_f64()));
+    }
+}
+
+fn mark_active(client_id: ClientId, now: f64, players: &mut Query<(&PlayerId, &mut LastActivity)>) {
+    if let Some((_, mut activity)) = players
+        .iter_mut()
+        .find(|(id, _)| id.client_id() == client_id)
+    {
+        activity.0 = now;
+    }
+}
+
+// Clients send an input every tick; only the ones actually doing something count as activity
+fn record_input_activity(
+    time: Res<Time>,
+    mut inputs: EventReader<InputEvent<Inputs>>,
+    mut players: Query<(&PlayerId, &mut LastActivity)>,
+) {
+    let now = time.elapsed_secs_f64();
+    for input in inputs.read() {
+        let active = match input.input() {
+            Some(Inputs::Direction(direction)) => !direction.is_none(),
+            Some(Inputs::None) | None => false,
+            Some(_) => true,
+        };
+        if active {
+            mark_active(input.from(), now, &mut players);
+        }
+    }
+}
+
+fn record_message_activity(
+    time: Res<Time>,
+    mut chat: EventReader<MessageEvent<ChatMessage>>,
+    mut harvest: EventReader<MessageEvent<HarvestRequest>>,
+    mut terrain: EventReader<MessageEvent<TerrainEditRequest>>,
+    mut casts: EventReader<MessageEvent<CastLine>>,
+    mut interactions: EventReader<MessageEvent<InteractRequest>>,
+    mut players: Query<(&PlayerId, &mut LastActivity)>,
+) {
+    let now = time.elapsed_secs_f64();
+    let senders: Vec<ClientId> = chat
+        .read()
+        .map(|e| e.from())
+        .chain(harvest.read().map(|e| e.from()))
+        .chain(terrain.read().map(|e| e.from()))
+        .chain(casts.read().map(|e| e.from()))
+        .chain(interactions.read().map(|e| e.from()))
+        .collect();
+    for client_id in senders {
+        mark_active(client_id, now, &mut players);
+    }
+}
+
+fn update_idle_players(
+    mut commands: Commands,
+    time: Res<Time>,
+    afk: Res<Afk>,
+    players: Query<(Entity, &PlayerName, &LastActivity, Option<&Idle>)>,
+) {
+    let now = time.elapsed_secs_f64();
+    let idle_after = afk.settings.idle_after_secs as f64;
+    for (entity, name, activity, idle) in players.iter() {
+        let inactive = now - activity.0 >= idle_after;
+        match (inactive, idle.is_some()) {
+            (true, false) => {
+                info!("{} is now idle", name.0);
+                let mut player = commands.entity(entity);
+                player.insert(Idle { since: activity.0 });
+                if afk.settings.pause_survival {
+                    player.insert(SurvivalPaused);
+                }
+            }
+            (false, true) => {
+                info!("{} is back", name.0);
+                commands
+                    .entity(entity)
+                    .remove::<Idle>()
+                    .remove::<SurvivalPaused>();
+            }
+            _ => {}
+        }
+    }
+}
+
+// On a full server, the longest idle player past the limit is kicked to make room
+fn kick_idle_players(
+    time: Res<Time>,
+    afk: Res<Afk>,
+    players: Query<(&PlayerId, &PlayerName, &Idle)>,
+    all_players: Query<&PlayerId>,
+    mut connection_manager: ResMut<ConnectionManager>,
+    mut connections: ResMut<ServerConnections>,
+    queue: Option<Res<ConnectionQueue>>,
+    mut errors: EventWriter<ReportError>,
+) {
+    let Some(kick_after) = afk.settings.kick_after_secs else {
+        return;
+    };
+    // someone waiting for a slot means the server is full, whatever the player count
+    let someone_waiting = queue.is_some_and(|queue| !queue.waiting.is_empty());
+    if !someone_waiting && all_players.iter().count() < afk.settings.full_at {
+        return;
+    }
+    let now = time.elapsed_secs_f64();
+    let Some((player_id, name, _)) = players
+        .iter()
+        .filter(|(_, _, idle)| now - idle.since >= kick_after as f64)
+        .min_by(|a, b| a.2.since.total_cmp(&b.2.since))
+    else {
+        return;
+    };
+    let client_id = player_id.client_id();
+    info!("Kicking {} for being idle on a full server", name.0);
+    connection_manager
+        .send_message::<ChatChannel, _>(
+            client_id,
+            &ChatLine::system("You were disconnected for being idle while the server is full"),
+        )
+        .unwrap_or_else(|e| {
+            errors.send(ReportError(GameError::send("ChatLine", e)));
+        });
+    if let Err(e) = connections.disconnect(client_id) {
+        error!("Failed to kick idle player {}: {:?}", client_id, e);
+    }
+}
+
+fn handle_who_command(
+    mut invoked: EventReader<CommandInvoked>,
+    mut replies: EventWriter<CommandReply>,
+    time: Res<Time>,
+    players: Query<(&PlayerName, Option<&Idle>), With<PlayerId>>,
+) {
+    for command in invoked.read().filter(|c| c.name == "who") {
+        let now = time.elapsed_secs_f64();
+        let mut roster: Vec<String> = players
+            .iter()
+            .map(|(name, idle)| match idle {
+                Some(idle) => format!("{} (idle {}m)", name.0, ((now - idle.since) / 60.0) as u64),
+                None => name.0.clone(),
+            })
+            .collect();
+        roster.sort();
+        replies.send(CommandReply::new(
+            command.source,
+            format!("{} online: {}", roster.len(), roster.join(", ")),
+        ));
+    }
+}
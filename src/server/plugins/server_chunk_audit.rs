@@ -0,0 +1,384 @@
+use bevy::prelude::*;
+use bevy::tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task};
+use bevy::time::common_conditions::on_timer;
+use bevy::utils::Duration;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::server::plugins::{ChunkStore, PendingSaves, TileModifiedEvent};
+use crate::settings_common::Settings;
+use crate::shared::commands::{
+    ArgKind, CommandInvoked, CommandReply, CommandSource, CommandSpec, PermissionLevel,
+    RegisterCommandExt,
+};
+use crate::shared::error::GameError;
+use crate::shared::frame_pacing::not_degraded;
+use crate::shared::instances::InstanceId;
+use crate::shared::world_generation::{
+    build_chunk, is_newer_version, Chunk, ChunkCoord, WorldConfig, WorldState,
+};
+
+// How often the background auditor runs
+const AUDIT_INTERVAL: Duration = Duration::from_secs(2);
+// Chunks checked per background pass, so auditing never costs more than a few hashes per frame
+const CHUNKS_PER_PASS: usize = 16;
+
+// Server plugin that periodically re-hashes loaded chunks, and compares them with their saved copy,
+// to catch corruption and unsanctioned mutation paths (anything that changes tiles without sending
+// a `TileModifiedEvent`)
+pub struct ServerChunkAuditPlugin;
+
+impl Plugin for ServerChunkAuditPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ChunkAudit>()
+            .add_systems(Startup, load_audit_settings)
+            .register_command(
+                CommandSpec::new(
+                    "audit",
+                    "Audit all loaded chunks now; 'repair' regenerates corrupted untouched chunks",
+                )
+                .optional_arg("mode", ArgKind::Word)
+                .permission(PermissionLevel::Admin),
+            )
+            .add_systems(
+                Update,
+                (
+                    record_chunk_baselines,
+                    audit_chunks
+                        .run_if(on_timer(AUDIT_INTERVAL))
+                        .run_if(not_degraded),
+                    handle_audit_command,
+                    finish_audit,
+                )
+                    .chain(),
+            );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditOutcome {
+    Ok,
+    /// Checksum changed, or differs from the saved copy, on a chunk that was never legitimately
+    /// modified: it can be regenerated
+    Corrupted,
+    /// Checksum changed on a chunk that also has legitimate modifications, or its saved copy is
+    /// unreadable: needs a human to look at it
+    NeedsReview,
+}
+
+/// Expected checksum of every loaded chunk, as of its generation or its last sanctioned modification
+#[derive(Resource)]
+pub struct ChunkAudit {
+    pub expected: HashMap<ChunkCoord, u64>,
+    /// Chunks that have been modified through `TileModifiedEvent`s since they were loaded
+    pub modified: HashSet<ChunkCoord>,
+    /// Chunks that failed the audit and couldn't be repaired automatically
+    pub flagged: HashSet<ChunkCoord>,
+    /// Regenerate corrupted untouched chunks during background audits
+    pub auto_repair: bool,
+    // Round-robin position of the background auditor
+    cursor: usize,
+    // Audit in progress, waiting for the saved copies of its chunks
+    batch: Option<AuditBatch>,
+    // Audits asked for with the command, and whether they repair chunks
+    requests: VecDeque<(CommandSource, bool)>,
+}
+
+impl Default for ChunkAudit {
+    fn default() -> Self {
+        Self {
+            expected: HashMap::new(),
+            modified: HashSet::new(),
+            flagged: HashSet::new(),
+            auto_repair: false,
+            cursor: 0,
+            batch: None,
+            requests: VecDeque::new(),
+        }
+    }
+}
+
+impl ChunkAudit {
+    /// Check a loaded chunk against its expected checksum, and against its copy in the store
+    /// (`None` if it was never saved). Chunks modified since they were loaded only differ from
+    /// their saved copy until the next save, so only their checksum is checked.
+    pub fn check(&self, chunk: &Chunk, stored: Option<&Result<Chunk, GameError>>) -> AuditOutcome {
+        let modified = self.modified.contains(&chunk.coord);
+        let checksum = chunk.checksum();
+        let changed = self
+            .expected
+            .get(&chunk.coord)
+            .is_some_and(|expected| *expected != checksum);
+        let (differs, saved_modified) = match stored {
+            // a copy saved at another version is expected to differ: the loaded chunk was
+            // changed since it was saved, or the saved copy is being replaced by a newer one
+            Some(Ok(stored)) if stored.version() != chunk.version() => {
+                return if !changed && is_newer_version(chunk.version(), stored.version()) {
+                    AuditOutcome::Ok
+                } else {
+                    AuditOutcome::NeedsReview
+                };
+            }
+            // saved copies count their modifications, made before the chunk was loaded
+            Some(Ok(stored)) => (
+                !modified && stored.checksum() != checksum,
+                stored.version() > 0,
+            ),
+            Some(Err(_)) => return AuditOutcome::NeedsReview,
+            None => (false, false),
+        };
+        match (changed || differs, modified || saved_modified || chunk.version() > 0) {
+            (false, _) => AuditOutcome::Ok,
+            (true, false) => AuditOutcome::Corrupted,
+            (true, true) => AuditOutcome::NeedsReview,
+        }
+    }
+}
+
+fn load_audit_settings(settings: Option<Res<Settings>>, mut audit: ResMut<ChunkAudit>) {
+    if let Some(settings) = settings {
+        audit.auto_repair = settings.server.audit_auto_repair;
+    }
+}
+
+// Record the checksum of new chunks, and re-baseline chunks after sanctioned modifications
+fn record_chunk_baselines(
+    mut audit: ResMut<ChunkAudit>,
+    mut modifications: EventReader<TileModifiedEvent>,
+    new_chunks: Query<&Chunk, (Added<Chunk>, Without<InstanceId>)>,
+    chunks: Query<&Chunk>,
+    world_state: Res<WorldState>,
+    world_config: Res<WorldConfig>,
+) {
+    for chunk in new_chunks.iter() {
+        audit.expected.insert(chunk.coord, chunk.checksum());
+        audit.modified.remove(&chunk.coord);
+        audit.flagged.remove(&chunk.coord);
+    }
+
+    let modified: HashSet<ChunkCoord> = modifications
+        .read()
+        .map(|event| world_config.grid().tile_to_chunk(event.position))
+        .collect();
+    for coord in modified {
+        if let Some(chunk) = world_state
+            .chunks
+            .get(&coord)
+            .and_then(|e| chunks.get(*e).ok())
+        {
+            audit.expected.insert(coord, chunk.checksum());
+            audit.modified.insert(coord);
+        }
+    }
+
+    // Forget chunks that have been unloaded
+    let loaded = &world_state.chunks;
+    audit.expected.retain(|coord, _| loaded.contains_key(coord));
+}
+
+// Everything the audit reads and repairs chunks with
+struct AuditContext<'a, 'w, 's> {
+    commands: &'a mut Commands<'w, 's>,
+    world_state: &'a mut WorldState,
+    world_config: &'a WorldConfig,
+    pending: &'a mut PendingSaves,
+}
+
+// A chunk, along with its saved copy if it was ever saved
+type SavedCopy = (ChunkCoord, Option<Result<Chunk, GameError>>);
+
+// Saved copies of the chunks of an audit, read on the async compute pool so that audits never
+// wait on region files being written
+struct AuditBatch {
+    task: Task<Vec<SavedCopy>>,
+    repair: bool,
+    // Who asked for the audit, answered once it's done; `None` for the background auditor
+    requested_by: Option<CommandSource>,
+}
+
+impl AuditBatch {
+    fn start(
+        store: &ChunkStore,
+        coords: Vec<ChunkCoord>,
+        repair: bool,
+        requested_by: Option<CommandSource>,
+    ) -> Self {
+        let store = store.clone();
+        let task = AsyncComputeTaskPool::get().spawn(async move {
+            coords
+                .into_iter()
+                .map(|coord| (coord, store.contains(coord).then(|| store.load(coord))))
+                .collect()
+        });
+        Self {
+            task,
+            repair,
+            requested_by,
+        }
+    }
+}
+
+/// Audit the given chunks, returning the number of corrupted and flagged chunks
+fn run_audit<'a>(
+    audit: &mut ChunkAudit,
+    chunks: impl Iterator<Item = (Entity, &'a Chunk, Option<Result<Chunk, GameError>>)>,
+    repair: bool,
+    context: &mut AuditContext,
+) -> (usize, usize) {
+    let mut corrupted = 0;
+    let mut flagged = 0;
+    for (entity, chunk, stored) in chunks {
+        let coord = chunk.coord;
+        match audit.check(chunk, stored.as_ref()) {
+            AuditOutcome::Ok => {}
+            AuditOutcome::Corrupted if repair => {
+                warn!("Chunk {:?} failed its audit, regenerating it", coord);
+                corrupted += 1;
+                // generated again rather than requested, which would load the saved copy
+                let world_time = context.world_state.world_time;
+                let rebuilt = build_chunk(&coord, context.world_config, world_time);
+                // the saved copy may be the corrupted one
+                context.pending.queue(rebuilt.clone());
+                context.commands.entity(entity).despawn();
+                let rebuilt = context.commands.spawn(rebuilt).id();
+                context.world_state.chunks.insert(coord, rebuilt);
+                context.world_state.active_chunks.insert(coord);
+                context
+                    .world_state
+                    .generation_time
+                    .insert(coord, world_time);
+                audit.expected.remove(&coord);
+            }
+            outcome => {
+                if audit.flagged.insert(coord) {
+                    warn!(
+                        "Chunk {:?} failed its audit ({:?}), flagged for review",
+                        coord, outcome
+                    );
+                }
+                flagged += 1;
+            }
+        }
+    }
+    (corrupted, flagged)
+}
+
+// Background auditor: reads the saved copies of a few chunks per pass, in round-robin order.
+// Chunks waiting to be saved are skipped, their saved copy is about to be replaced.
+fn audit_chunks(
+    mut audit: ResMut<ChunkAudit>,
+    store: Res<ChunkStore>,
+    pending: Res<PendingSaves>,
+    chunks: Query<&Chunk, Without<InstanceId>>,
+) {
+    if audit.batch.is_some() || !audit.requests.is_empty() {
+        return;
+    }
+    let mut loaded: Vec<ChunkCoord> = chunks.iter().map(|chunk| chunk.coord).collect();
+    if loaded.is_empty() {
+        return;
+    }
+    loaded.sort_by_key(|coord| (coord.x, coord.y));
+    let start = audit.cursor % loaded.len();
+    audit.cursor = start + CHUNKS_PER_PASS;
+    let batch = loaded
+        .iter()
+        .copied()
+        .cycle()
+        .skip(start)
+        .take(CHUNKS_PER_PASS.min(loaded.len()))
+        .filter(|coord| !pending.contains(*coord))
+        .collect();
+    let repair = audit.auto_repair;
+    audit.batch = Some(AuditBatch::start(&store, batch, repair, None));
+}
+
+// Queue audits of every loaded chunk, started once the audit in progress is done
+fn handle_audit_command(
+    mut invoked: EventReader<CommandInvoked>,
+    mut audit: ResMut<ChunkAudit>,
+    store: Res<ChunkStore>,
+    pending: Res<PendingSaves>,
+    chunks: Query<&Chunk, Without<InstanceId>>,
+) {
+    for command in invoked.read().filter(|c| c.name == "audit") {
+        let repair = command.args.str("mode") == Some("repair");
+        audit.requests.push_back((command.source, repair));
+    }
+    if audit.batch.is_some() {
+        return;
+    }
+    let Some((source, repair)) = audit.requests.pop_front() else {
+        return;
+    };
+    let coords = chunks
+        .iter()
+        .map(|chunk| chunk.coord)
+        .filter(|coord| !pending.contains(*coord))
+        .collect();
+    audit.batch = Some(AuditBatch::start(&store, coords, repair, Some(source)));
+}
+
+// Check the chunks of an audit once their saved copies are read. Chunks unloaded in the meantime
+// are left out, and so are the ones queued to be saved since.
+fn finish_audit(
+    mut commands: Commands,
+    mut audit: ResMut<ChunkAudit>,
+    mut world_state: ResMut<WorldState>,
+    world_config: Res<WorldConfig>,
+    mut pending: ResMut<PendingSaves>,
+    mut replies: EventWriter<CommandReply>,
+    chunks: Query<&Chunk, Without<InstanceId>>,
+) {
+    let Some(batch) = audit.batch.as_mut() else {
+        return;
+    };
+    let Some(saved) = block_on(future::poll_once(&mut batch.task)) else {
+        return;
+    };
+    let Some(AuditBatch {
+        repair,
+        requested_by,
+        ..
+    }) = audit.batch.take()
+    else {
+        return;
+    };
+    let checked: Vec<_> = saved
+        .into_iter()
+        .filter(|(coord, _)| !pending.contains(*coord))
+        .filter_map(|(coord, stored)| {
+            let entity = *world_state.chunks.get(&coord)?;
+            Some((entity, chunks.get(entity).ok()?, stored))
+        })
+        .collect();
+    let audited = checked.len();
+    let (corrupted, flagged) = run_audit(
+        &mut audit,
+        checked.into_iter(),
+        repair,
+        &mut AuditContext {
+            commands: &mut commands,
+            world_state: &mut world_state,
+            world_config: &world_config,
+            pending: &mut pending,
+        },
+    );
+    let Some(source) = requested_by else {
+        return;
+    };
+    replies.send(CommandReply::new(
+        source,
+        format!(
+            "Audited {} chunks: {} regenerated, {} failing checks",
+            audited, corrupted, flagged
+        ),
+    ));
+    if !audit.flagged.is_empty() {
+        let mut flagged: Vec<_> = audit.flagged.iter().map(|c| (c.x, c.y)).collect();
+        flagged.sort();
+        replies.send(CommandReply::new(
+            source,
+            format!("Flagged for review: {:?}", flagged),
+        ));
+    }
+}
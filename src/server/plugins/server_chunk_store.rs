@@ -0,0 +1,516 @@
+use bevy::prelude::*;
+use bevy::tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+#[cfg(feature = "sqlite")]
+use crate::server::plugins::Database;
+#[cfg(feature = "sharding")]
+use crate::server::plugins::ShardMap;
+use crate::shared::error::{GameError, ReportError};
+use crate::shared::graves::Grave;
+use crate::shared::instances::InstanceId;
+use crate::shared::logistics::Container;
+use crate::shared::villages::VillagerRecord;
+use crate::shared::world_generation::{
+    handle_chunk_requests, Chunk, ChunkCoord, ChunkRequestEvent, ChunkUnloadedEvent,
+    SaveWorldEvent, WorldState,
+};
+
+mod region_file;
+mod saved_chunk;
+
+// Directory holding the region files of persisted chunks, and the per-chunk files of everything
+// saved along with them
+const CHUNK_DIR: &str = "world/chunks";
+
+// Server plugin persisting chunks to disk and loading them back instead of regenerating them
+pub struct ServerChunkStorePlugin;
+
+impl Plugin for ServerChunkStorePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ChunkStore>()
+            .init_resource::<PendingSaves>()
+            .add_systems(
+                Update,
+                (
+                    load_stored_chunks.before(handle_chunk_requests),
+                    save_loaded_chunks,
+                    (save_unloaded_chunks, write_pending_saves).chain(),
+                ),
+            )
+            .add_systems(Last, finish_pending_saves);
+    }
+}
+
+/// Chunks saved on disk. Cheap to clone, so it can be handed to background tasks.
+#[derive(Resource, Clone, Debug)]
+pub struct ChunkStore {
+    dir: PathBuf,
+    // Held while reading or writing a region file, as tasks save chunks too
+    regions: Arc<Mutex<()>>,
+    // Chunks whose saved copy couldn't be read. They are never saved over, so that nothing is
+    // lost until someone looks into them.
+    unreadable: Arc<Mutex<HashSet<ChunkCoord>>>,
+    #[cfg(feature = "sqlite")]
+    database: Option<Database>,
+}
+
+impl Default for ChunkStore {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from(CHUNK_DIR),
+            regions: Arc::default(),
+            unreadable: Arc::default(),
+            #[cfg(feature = "sqlite")]
+            database: None,
+        }
+    }
+}
+
+impl ChunkStore {
+    /// Save chunks to the database instead of region files
+    #[cfg(feature = "sqlite")]
+    pub fn use_database(&mut self, database: Database) {
+        self.database = Some(database);
+    }
+
+    fn region_path(&self, coord: ChunkCoord) -> PathBuf {
+        let (x, y) = region_file::region_of(coord);
+        self.dir.join(format!("r.{}.{}.region", x, y))
+    }
+
+    fn graves_path(&self, coord: ChunkCoord) -> PathBuf {
+        self.dir.join(format!("{}_{}.graves", coord.x, coord.y))
+    }
+
+    fn containers_path(&self, coord: ChunkCoord) -> PathBuf {
+        self.dir.join(format!("{}_{}.containers", coord.x, coord.y))
+    }
+
+    fn villagers_path(&self, coord: ChunkCoord) -> PathBuf {
+        self.dir.join(format!("{}_{}.villagers", coord.x, coord.y))
+    }
+
+    pub fn contains(&self, coord: ChunkCoord) -> bool {
+        #[cfg(feature = "sqlite")]
+        if let Some(database) = &self.database {
+            return database.contains_chunk(coord).unwrap_or_else(|e| {
+                error!("Failed to look up chunk {:?}: {}", coord, e);
+                false
+            });
+        }
+        let _lock = self.regions.lock().unwrap();
+        region_file::contains(&self.region_path(coord), coord).unwrap_or_else(|e| {
+            error!("Failed to look up chunk {:?}: {}", coord, e);
+            false
+        })
+    }
+
+    pub fn save(&self, chunk: &Chunk) -> Result<(), GameError> {
+        self.save_all([chunk])
+    }
+
+    /// Save chunks, writing each region they are in once. Every chunk that can be saved is; the
+    /// first error is returned.
+    pub fn save_all<'a>(
+        &self,
+        chunks: impl IntoIterator<Item = &'a Chunk>,
+    ) -> Result<(), GameError> {
+        let mut result = Ok(());
+        let mut regions: HashMap<(i32, i32), Vec<(ChunkCoord, Vec<u8>)>> = HashMap::new();
+        let unreadable = self.unreadable.lock().unwrap().clone();
+        for chunk in chunks {
+            if unreadable.contains(&chunk.coord) {
+                warn!(
+                    "Not saving chunk {:?} over its unreadable saved copy",
+                    chunk.coord
+                );
+                continue;
+            }
+            let data = match saved_chunk::encode(chunk) {
+                Ok(data) => data,
+                Err(e) => {
+                    result = result.and(Err(e));
+                    continue;
+                }
+            };
+            #[cfg(feature = "sqlite")]
+            if let Some(database) = &self.database {
+                result = result.and(database.save_chunk(chunk.coord, data));
+                continue;
+            }
+            regions
+                .entry(region_file::region_of(chunk.coord))
+                .or_default()
+                .push((chunk.coord, data));
+        }
+        if regions.is_empty() {
+            return result;
+        }
+        fs::create_dir_all(&self.dir)?;
+        for chunks in regions.values() {
+            result = result.and(self.write_region(chunks));
+        }
+        result
+    }
+
+    /// Save a chunk unless a copy of it is saved already, looking and saving at once so that a
+    /// copy saved in the meantime is never replaced. Returns whether the chunk was saved.
+    pub fn save_new(&self, chunk: &Chunk) -> Result<bool, GameError> {
+        let data = saved_chunk::encode(chunk)?;
+        #[cfg(feature = "sqlite")]
+        if let Some(database) = &self.database {
+            if database.contains_chunk(chunk.coord)? {
+                return Ok(false);
+            }
+            database.save_chunk(chunk.coord, data)?;
+            return Ok(true);
+        }
+        fs::create_dir_all(&self.dir)?;
+        let _lock = self.regions.lock().unwrap();
+        let path = self.region_path(chunk.coord);
+        if region_file::contains(&path, chunk.coord)? {
+            return Ok(false);
+        }
+        region_file::write(&path, &[(chunk.coord, data)])?;
+        Ok(true)
+    }
+
+    fn write_region(&self, chunks: &[(ChunkCoord, Vec<u8>)]) -> Result<(), GameError> {
+        let _lock = self.regions.lock().unwrap();
+        Ok(region_file::write(&self.region_path(chunks[0].0), chunks)?)
+    }
+
+    pub fn load(&self, coord: ChunkCoord) -> Result<Chunk, GameError> {
+        saved_chunk::decode(&self.load_data(coord)?)
+    }
+
+    // The chunk as it was saved, in whatever layout
+    fn load_data(&self, coord: ChunkCoord) -> Result<Vec<u8>, GameError> {
+        #[cfg(feature = "sqlite")]
+        if let Some(database) = &self.database {
+            return database
+                .load_chunk(coord)?
+                .ok_or(GameError::MalformedChunk(coord));
+        }
+        let _lock = self.regions.lock().unwrap();
+        region_file::read(&self.region_path(coord), coord)?.ok_or(GameError::MalformedChunk(coord))
+    }
+
+    /// Keep a chunk whose saved copy can't be read from ever being saved over, and put a copy of
+    /// it in `unreadable/` for someone to look into
+    pub fn set_aside(&self, coord: ChunkCoord) -> Result<(), GameError> {
+        if !self.unreadable.lock().unwrap().insert(coord) {
+            return Ok(());
+        }
+        let dir = self.dir.join("unreadable");
+        fs::create_dir_all(&dir)?;
+        Ok(fs::write(
+            dir.join(format!("{}_{}.chunk", coord.x, coord.y)),
+            self.load_data(coord)?,
+        )?)
+    }
+
+    /// Save the graves lying in a chunk, next to the chunk itself
+    pub fn save_graves(&self, coord: ChunkCoord, graves: &[Grave]) -> Result<(), GameError> {
+        let data = bincode::serialize(graves)
+            .map_err(|source| GameError::ChunkEncode { coord, source })?;
+        #[cfg(feature = "sqlite")]
+        if let Some(database) = &self.database {
+            return database.save_graves(coord, data);
+        }
+        let path = self.graves_path(coord);
+        if graves.is_empty() {
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+            return Ok(());
+        }
+        fs::create_dir_all(&self.dir)?;
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, data)?;
+        Ok(fs::rename(tmp, path)?)
+    }
+
+    pub fn load_graves(&self, coord: ChunkCoord) -> Result<Vec<Grave>, GameError> {
+        #[cfg(feature = "sqlite")]
+        if let Some(database) = &self.database {
+            return match database.load_graves(coord)? {
+                Some(data) => Ok(bincode::deserialize(&data)?),
+                None => Ok(Vec::new()),
+            };
+        }
+        let path = self.graves_path(coord);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        Ok(bincode::deserialize(&fs::read(path)?)?)
+    }
+
+    /// Save what the chests and furnaces of a chunk hold, next to the chunk itself
+    pub fn save_containers(
+        &self,
+        coord: ChunkCoord,
+        containers: &[Container],
+    ) -> Result<(), GameError> {
+        let data = bincode::serialize(containers)
+            .map_err(|source| GameError::ChunkEncode { coord, source })?;
+        #[cfg(feature = "sqlite")]
+        if let Some(database) = &self.database {
+            return database.save_containers(coord, data);
+        }
+        let path = self.containers_path(coord);
+        if containers.is_empty() {
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+            return Ok(());
+        }
+        fs::create_dir_all(&self.dir)?;
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, data)?;
+        Ok(fs::rename(tmp, path)?)
+    }
+
+    pub fn load_containers(&self, coord: ChunkCoord) -> Result<Vec<Container>, GameError> {
+        #[cfg(feature = "sqlite")]
+        if let Some(database) = &self.database {
+            return match database.load_containers(coord)? {
+                Some(data) => Ok(bincode::deserialize(&data)?),
+                None => Ok(Vec::new()),
+            };
+        }
+        let path = self.containers_path(coord);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        Ok(bincode::deserialize(&fs::read(path)?)?)
+    }
+
+    /// Save the villagers of the village centered in a chunk, next to the chunk itself. Villages
+    /// that were saved once, even empty, are never populated again.
+    pub fn save_villagers(
+        &self,
+        coord: ChunkCoord,
+        villagers: &[VillagerRecord],
+    ) -> Result<(), GameError> {
+        let data = bincode::serialize(villagers)
+            .map_err(|source| GameError::ChunkEncode { coord, source })?;
+        #[cfg(feature = "sqlite")]
+        if let Some(database) = &self.database {
+            return database.save_villagers(coord, data);
+        }
+        fs::create_dir_all(&self.dir)?;
+        let path = self.villagers_path(coord);
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, data)?;
+        Ok(fs::rename(tmp, path)?)
+    }
+
+    /// The villagers saved for the village centered in a chunk, if it was ever saved
+    pub fn load_villagers(
+        &self,
+        coord: ChunkCoord,
+    ) -> Result<Option<Vec<VillagerRecord>>, GameError> {
+        #[cfg(feature = "sqlite")]
+        if let Some(database) = &self.database {
+            return match database.load_villagers(coord)? {
+                Some(data) => Ok(Some(bincode::deserialize(&data)?)),
+                None => Ok(None),
+            };
+        }
+        let path = self.villagers_path(coord);
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(bincode::deserialize(&fs::read(path)?)?))
+    }
+}
+
+/// Chunks waiting to be saved, unloaded or repaired. They are saved on the async compute pool, so
+/// that syncing region files doesn't hold up frames, one batch at a time so that they are saved in
+/// the order they were queued.
+#[derive(Resource, Default)]
+pub struct PendingSaves {
+    queued: HashMap<ChunkCoord, Chunk>,
+    writing: Option<(Arc<HashMap<ChunkCoord, Chunk>>, Task<Result<(), GameError>>)>,
+}
+
+impl PendingSaves {
+    /// Save a chunk with the next batch, replacing its saved copy
+    pub fn queue(&mut self, chunk: Chunk) {
+        self.queued.insert(chunk.coord, chunk);
+    }
+
+    /// Whether a chunk has changes that aren't saved yet, so that its saved copy is out of date
+    pub fn contains(&self, coord: ChunkCoord) -> bool {
+        self.get(coord).is_some()
+    }
+
+    // Latest copy of a chunk that isn't saved yet
+    fn get(&self, coord: ChunkCoord) -> Option<&Chunk> {
+        self.queued
+            .get(&coord)
+            .or_else(|| self.writing.as_ref()?.0.get(&coord))
+    }
+
+    /// Wait for the batch being written, then save the queued chunks on this thread
+    pub fn finish(&mut self, store: &ChunkStore, errors: &mut EventWriter<ReportError>) {
+        if let Some((_, task)) = self.writing.take() {
+            if let Err(e) = block_on(task) {
+                errors.send(ReportError(e));
+            }
+        }
+        if let Err(e) = store.save_all(std::mem::take(&mut self.queued).values()) {
+            errors.send(ReportError(e));
+        }
+    }
+}
+
+// Load requested chunks from disk, or from the copies waiting to be saved; the generator only
+// handles the ones that were never saved
+fn load_stored_chunks(
+    mut commands: Commands,
+    mut requests: EventReader<ChunkRequestEvent>,
+    mut world_state: ResMut<WorldState>,
+    store: Res<ChunkStore>,
+    pending: Res<PendingSaves>,
+    mut errors: EventWriter<ReportError>,
+) {
+    for request in requests.read() {
+        if world_state.chunks.contains_key(&request.coord)
+            || world_state.held_chunks.contains(&request.coord)
+        {
+            continue;
+        }
+        let loaded = match pending.get(request.coord) {
+            Some(chunk) => {
+                // sent whole to clients, along with the changes they weren't sent yet
+                let mut chunk = chunk.clone();
+                chunk.take_delta();
+                Ok(chunk)
+            }
+            None if store.contains(request.coord) => store.load(request.coord),
+            None => continue,
+        };
+        let mut chunk = match loaded {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                // generating it again would save a fresh chunk over whatever was built there
+                error!(
+                    "Stored chunk {:?} is unreadable, leaving it unloaded",
+                    request.coord
+                );
+                errors.send(ReportError(e));
+                world_state.held_chunks.insert(request.coord);
+                if let Err(e) = store.set_aside(request.coord) {
+                    errors.send(ReportError(e));
+                }
+                continue;
+            }
+        };
+        chunk.last_accessed = world_state.world_time;
+        let entity = commands.spawn(chunk).id();
+        let world_time = world_state.world_time;
+        world_state.chunks.insert(request.coord, entity);
+        world_state.active_chunks.insert(request.coord);
+        world_state
+            .generation_time
+            .insert(request.coord, world_time);
+        debug!("Loaded chunk {:?} from disk", request.coord);
+    }
+}
+
+// Queue chunks to be saved as they are unloaded, so that changes made to them survive until
+// they're loaded again
+fn save_unloaded_chunks(
+    mut unloaded: EventReader<ChunkUnloadedEvent>,
+    mut pending: ResMut<PendingSaves>,
+    #[cfg(feature = "sharding")] shards: Option<Res<ShardMap>>,
+) {
+    for ChunkUnloadedEvent { chunk } in unloaded.read() {
+        #[cfg(feature = "sharding")]
+        if shards
+            .as_ref()
+            .is_some_and(|shards| !shards.owns_chunk(chunk.coord))
+        {
+            continue;
+        }
+        pending.queue(chunk.clone());
+    }
+}
+
+// Save the queued chunks in the background once the previous batch is written
+fn write_pending_saves(
+    mut pending: ResMut<PendingSaves>,
+    store: Res<ChunkStore>,
+    mut errors: EventWriter<ReportError>,
+) {
+    if let Some((_, task)) = pending.writing.as_mut() {
+        let Some(written) = block_on(future::poll_once(task)) else {
+            return;
+        };
+        pending.writing = None;
+        if let Err(e) = written {
+            errors.send(ReportError(e));
+        }
+    }
+    if pending.queued.is_empty() {
+        return;
+    }
+    let batch = Arc::new(std::mem::take(&mut pending.queued));
+    let store = store.clone();
+    let chunks = batch.clone();
+    let task = AsyncComputeTaskPool::get().spawn(async move { store.save_all(chunks.values()) });
+    pending.writing = Some((batch, task));
+}
+
+// Unloaded chunks are saved before the process goes away
+fn finish_pending_saves(
+    mut exits: EventReader<AppExit>,
+    mut pending: ResMut<PendingSaves>,
+    store: Res<ChunkStore>,
+    mut errors: EventWriter<ReportError>,
+) {
+    if exits.read().count() > 0 {
+        pending.finish(&store, &mut errors);
+    }
+}
+
+fn save_loaded_chunks(
+    mut events: EventReader<SaveWorldEvent>,
+    store: Res<ChunkStore>,
+    mut pending: ResMut<PendingSaves>,
+    // instances are generated again every time, they aren't saved
+    chunks: Query<&Chunk, Without<InstanceId>>,
+    // other shards save the chunks of their own regions
+    #[cfg(feature = "sharding")] shards: Option<Res<ShardMap>>,
+    mut errors: EventWriter<ReportError>,
+) {
+    if events.read().count() == 0 {
+        return;
+    }
+    // unloaded chunks go first, so that an older copy of a chunk loaded again isn't saved over it
+    pending.finish(&store, &mut errors);
+    let mut owned = Vec::new();
+    for chunk in chunks.iter() {
+        #[cfg(feature = "sharding")]
+        if shards
+            .as_ref()
+            .is_some_and(|shards| !shards.owns_chunk(chunk.coord))
+        {
+            continue;
+        }
+        owned.push(chunk);
+    }
+    // regions are synced once each, rather than once per chunk
+    match store.save_all(owned.iter().copied()) {
+        Ok(()) => info!("Saved {} chunks", owned.len()),
+        Err(e) => {
+            errors.send(ReportError(e));
+        }
+    }
+}
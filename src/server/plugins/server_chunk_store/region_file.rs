@@ -0,0 +1,219 @@
+//! Region files: the chunks of a square of `REGION_SIZE` x `REGION_SIZE` chunks saved together,
+//! so that a world doesn't end up as hundreds of thousands of tiny files.
+//!
+//! A region file starts with a header of one `(offset, length)` pair per chunk, both
+//! little-endian `u32`, a length of 0 meaning the chunk was never saved. Chunks are only ever
+//! appended and their header entry updated afterwards, so a crash while saving leaves the previous
+//! version of the chunk in place. Once most of the file is old versions, it is compacted.
+use bevy::log::warn;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::shared::world_generation::{ChunkCoord, MAX_CHUNK_BYTES};
+
+/// Chunks along each side of a region
+pub const REGION_SIZE: i32 = 32;
+
+const SLOTS: usize = (REGION_SIZE * REGION_SIZE) as usize;
+const ENTRY_BYTES: u64 = 8;
+const HEADER_BYTES: u64 = SLOTS as u64 * ENTRY_BYTES;
+// Old versions always tolerated before compacting, so that small regions aren't rewritten often
+const COMPACT_SLACK: u64 = 1024 * 1024;
+
+/// Region containing a chunk
+pub fn region_of(coord: ChunkCoord) -> (i32, i32) {
+    (
+        coord.x.div_euclid(REGION_SIZE),
+        coord.y.div_euclid(REGION_SIZE),
+    )
+}
+
+// Index of a chunk's entry in the header of its region
+fn slot(coord: ChunkCoord) -> usize {
+    (coord.y.rem_euclid(REGION_SIZE) * REGION_SIZE + coord.x.rem_euclid(REGION_SIZE)) as usize
+}
+
+fn corrupted(path: &Path, reason: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("region file {}: {}", path.display(), reason),
+    )
+}
+
+fn parse_entry(entry: &[u8]) -> (u32, u32) {
+    let (offset, length) = entry.split_at(4);
+    (
+        u32::from_le_bytes(offset.try_into().unwrap()),
+        u32::from_le_bytes(length.try_into().unwrap()),
+    )
+}
+
+fn read_entry(file: &mut File, slot: usize) -> io::Result<(u32, u32)> {
+    let mut entry = [0; ENTRY_BYTES as usize];
+    file.seek(SeekFrom::Start(slot as u64 * ENTRY_BYTES))?;
+    file.read_exact(&mut entry)?;
+    Ok(parse_entry(&entry))
+}
+
+fn read_header(file: &mut File) -> io::Result<Vec<(u32, u32)>> {
+    let mut header = vec![0; HEADER_BYTES as usize];
+    file.seek(SeekFrom::Start(0))?;
+    file.read_exact(&mut header)?;
+    Ok(header
+        .chunks_exact(ENTRY_BYTES as usize)
+        .map(parse_entry)
+        .collect())
+}
+
+fn write_entry(file: &mut File, slot: usize, (offset, length): (u32, u32)) -> io::Result<()> {
+    file.seek(SeekFrom::Start(slot as u64 * ENTRY_BYTES))?;
+    file.write_all(&offset.to_le_bytes())?;
+    file.write_all(&length.to_le_bytes())
+}
+
+// Data of the chunk in `slot`, if it was ever saved
+fn read_slot(file: &mut File, path: &Path, slot: usize) -> io::Result<Option<Vec<u8>>> {
+    let (offset, length) = read_entry(file, slot)?;
+    if length == 0 {
+        return Ok(None);
+    }
+    let end = offset as u64 + length as u64;
+    if (offset as u64) < HEADER_BYTES || end > file.metadata()?.len() {
+        return Err(corrupted(path, "chunk outside of the file"));
+    }
+    if length as u64 > MAX_CHUNK_BYTES {
+        return Err(corrupted(path, "oversized chunk"));
+    }
+    let mut data = vec![0; length as usize];
+    file.seek(SeekFrom::Start(offset as u64))?;
+    file.read_exact(&mut data)?;
+    Ok(Some(data))
+}
+
+fn open(path: &Path) -> io::Result<Option<File>> {
+    match File::open(path) {
+        Ok(file) if file.metadata()?.len() < HEADER_BYTES => Err(corrupted(path, "truncated")),
+        Ok(file) => Ok(Some(file)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Data saved for a chunk in the region file at `path`, if any
+pub fn read(path: &Path, coord: ChunkCoord) -> io::Result<Option<Vec<u8>>> {
+    match open(path)? {
+        Some(mut file) => read_slot(&mut file, path, slot(coord)),
+        None => Ok(None),
+    }
+}
+
+/// Whether a chunk was saved in the region file at `path`
+pub fn contains(path: &Path, coord: ChunkCoord) -> io::Result<bool> {
+    match open(path)? {
+        Some(mut file) => Ok(read_entry(&mut file, slot(coord))?.1 > 0),
+        None => Ok(false),
+    }
+}
+
+/// Save chunks in the region file at `path`, creating it if needed. However many chunks there are,
+/// the file is only synced twice. Callers make sure a single write to a region happens at a time.
+pub fn write(path: &Path, chunks: &[(ChunkCoord, Vec<u8>)]) -> io::Result<()> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)?;
+    let file_len = file.metadata()?.len();
+    if file_len < HEADER_BYTES {
+        file.set_len(HEADER_BYTES)?;
+    }
+    // the last version of a chunk saved twice wins
+    let replaced: HashMap<usize, &[u8]> = chunks
+        .iter()
+        .map(|(coord, data)| (slot(*coord), data.as_slice()))
+        .collect();
+    let live = read_header(&mut file)?
+        .iter()
+        .enumerate()
+        .filter(|(other, _)| !replaced.contains_key(other))
+        .map(|(_, (_, length))| *length as u64)
+        .chain(replaced.values().map(|data| data.len() as u64))
+        .sum::<u64>();
+    let end = file_len.max(HEADER_BYTES);
+    if end - HEADER_BYTES > 2 * live + COMPACT_SLACK && compact(&mut file, path, &replaced)? {
+        return Ok(());
+    }
+
+    let mut entries = Vec::with_capacity(replaced.len());
+    let mut offset = end;
+    file.seek(SeekFrom::Start(end))?;
+    for (slot, data) in &replaced {
+        let start = u32::try_from(offset).map_err(|_| corrupted(path, "region file too large"))?;
+        file.write_all(data)?;
+        entries.push((*slot, (start, data.len() as u32)));
+        offset += data.len() as u64;
+    }
+    // the data must be on disk before the header points to it
+    file.sync_data()?;
+    for (slot, entry) in entries {
+        write_entry(&mut file, slot, entry)?;
+    }
+    file.sync_data()
+}
+
+// Rewrite a region with only the latest version of each chunk, the chunks in `replaced` taking the
+// place of their slots, then swap it in for the old one. Returns false, leaving the region as it
+// is, if a chunk that isn't replaced can't be read: its bytes stay in the region for someone to
+// look into, and the chunks are appended instead.
+fn compact(file: &mut File, path: &Path, replaced: &HashMap<usize, &[u8]>) -> io::Result<bool> {
+    let mut header = Vec::with_capacity(HEADER_BYTES as usize);
+    let mut body = Vec::new();
+    for other in 0..SLOTS {
+        let chunk = match replaced.get(&other) {
+            Some(data) => Some(data.to_vec()),
+            None => match read_slot(file, path, other) {
+                Ok(chunk) => chunk,
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof
+                    ) =>
+                {
+                    warn!(
+                        "Not compacting {}, slot {} can't be read: {}",
+                        path.display(),
+                        other,
+                        e
+                    );
+                    return Ok(false);
+                }
+                Err(e) => return Err(e),
+            },
+        };
+        let (offset, length) = match chunk {
+            Some(chunk) => {
+                let offset = u32::try_from(HEADER_BYTES + body.len() as u64)
+                    .map_err(|_| corrupted(path, "region file too large"))?;
+                let length = chunk.len() as u32;
+                body.extend(chunk);
+                (offset, length)
+            }
+            None => (0, 0),
+        };
+        header.extend(offset.to_le_bytes());
+        header.extend(length.to_le_bytes());
+    }
+    let tmp = path.with_extension("tmp");
+    let mut compacted = File::create(&tmp)?;
+    compacted.write_all(&header)?;
+    compacted.write_all(&body)?;
+    compacted.sync_all()?;
+    fs::rename(tmp, path)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,103 @@
+//! Region files must give back what was saved, and survive damage to the parts they don't need
+use std::path::PathBuf;
+
+use super::*;
+
+// Fresh region file path for a test, in a directory of its own
+fn region(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("region-file-{}-{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir.join("r.0.0.region")
+}
+
+fn coord(x: i32, y: i32) -> ChunkCoord {
+    ChunkCoord { x, y }
+}
+
+fn kind(result: io::Result<Option<Vec<u8>>>) -> io::ErrorKind {
+    result.unwrap_err().kind()
+}
+
+#[test]
+fn saved_chunks_read_back() {
+    let path = region("round-trip");
+    write(
+        &path,
+        &[(coord(0, 0), vec![1, 2, 3]), (coord(5, 7), vec![4; 100])],
+    )
+    .unwrap();
+    write(&path, &[(coord(-1, -1), vec![9])]).unwrap();
+
+    assert_eq!(read(&path, coord(0, 0)).unwrap(), Some(vec![1, 2, 3]));
+    assert_eq!(read(&path, coord(5, 7)).unwrap(), Some(vec![4; 100]));
+    // wraps around to the last slot of the region
+    assert_eq!(read(&path, coord(-1, -1)).unwrap(), Some(vec![9]));
+    assert_eq!(read(&path, coord(1, 0)).unwrap(), None);
+    assert!(contains(&path, coord(5, 7)).unwrap());
+    assert!(!contains(&path, coord(6, 7)).unwrap());
+    assert_eq!(
+        read(&path.with_extension("missing"), coord(0, 0)).unwrap(),
+        None
+    );
+}
+
+#[test]
+fn overwritten_chunks_are_compacted_away() {
+    let path = region("compact");
+    write(&path, &[(coord(1, 1), vec![7; 10])]).unwrap();
+    let chunk = 256 * 1024;
+    for version in 0..8u8 {
+        write(&path, &[(coord(0, 0), vec![version; chunk])]).unwrap();
+    }
+
+    assert_eq!(read(&path, coord(0, 0)).unwrap(), Some(vec![7; chunk]));
+    assert_eq!(read(&path, coord(1, 1)).unwrap(), Some(vec![7; 10]));
+    // only the latest version of each chunk, and at most the slack, is left
+    let len = fs::metadata(&path).unwrap().len();
+    assert!(len <= HEADER_BYTES + 2 * (chunk as u64 + 10) + COMPACT_SLACK);
+    assert!(!path.with_extension("tmp").exists());
+}
+
+#[test]
+fn truncated_headers_are_rejected() {
+    let path = region("truncated");
+    fs::write(&path, vec![0; HEADER_BYTES as usize / 2]).unwrap();
+
+    assert_eq!(kind(read(&path, coord(0, 0))), io::ErrorKind::InvalidData);
+    assert!(contains(&path, coord(0, 0)).is_err());
+}
+
+#[test]
+fn entries_outside_of_the_file_are_rejected_and_kept_by_compaction() {
+    let path = region("out-of-bounds");
+    write(&path, &[(coord(0, 0), vec![1, 2, 3])]).unwrap();
+    let mut file = OpenOptions::new().write(true).open(&path).unwrap();
+    write_entry(
+        &mut file,
+        slot(coord(1, 0)),
+        (HEADER_BYTES as u32 + 1000, 8),
+    )
+    .unwrap();
+    drop(file);
+
+    assert_eq!(kind(read(&path, coord(1, 0))), io::ErrorKind::InvalidData);
+
+    let before = fs::read(&path).unwrap();
+    let mut file = File::open(&path).unwrap();
+    let data = [4, 5];
+    let compacted = compact(
+        &mut file,
+        &path,
+        &HashMap::from([(slot(coord(2, 0)), &data[..])]),
+    )
+    .unwrap();
+    assert!(!compacted);
+    assert_eq!(fs::read(&path).unwrap(), before);
+
+    // saving goes on without compacting, and the broken entry stays for someone to look into
+    write(&path, &[(coord(2, 0), data.to_vec())]).unwrap();
+    assert_eq!(read(&path, coord(0, 0)).unwrap(), Some(vec![1, 2, 3]));
+    assert_eq!(kind(read(&path, coord(1, 0))), io::ErrorKind::InvalidData);
+    assert_eq!(read(&path, coord(2, 0)).unwrap(), Some(vec![4, 5]));
+}
@@ -0,0 +1,52 @@
+//! Layout of the chunks saved on disk: a magic number and the version of the layout, then the
+//! chunk itself. The encoding of a chunk follows `Chunk` and `Tile`, so any change to them must
+//! bump `VERSION` and add a branch to `decode` reading the previous layout into the new one;
+//! a saved world is never read as something it is not.
+use crate::shared::chunk_format::ChunkFormat;
+use crate::shared::error::GameError;
+use crate::shared::world_generation::{deserialize_chunk, serialize_chunk, Chunk};
+
+const MAGIC: [u8; 4] = *b"DGCK";
+/// Version of the layout written by this build
+pub const VERSION: u16 = 1;
+const HEADER_BYTES: usize = MAGIC.len() + 2;
+
+/// A chunk as it is saved, in the current layout
+pub fn encode(chunk: &Chunk) -> Result<Vec<u8>, GameError> {
+    let body = serialize_chunk(chunk, ChunkFormat::best_supported())?;
+    let mut data = Vec::with_capacity(HEADER_BYTES + body.len());
+    data.extend(MAGIC);
+    data.extend(VERSION.to_le_bytes());
+    data.extend(body);
+    Ok(data)
+}
+
+/// Version of the layout a chunk was saved in, if it starts with a header
+pub fn version(data: &[u8]) -> Option<u16> {
+    match data.split_first_chunk::<HEADER_BYTES>() {
+        Some((header, _)) if header[..MAGIC.len()] == MAGIC => Some(u16::from_le_bytes([
+            header[MAGIC.len()],
+            header[MAGIC.len() + 1],
+        ])),
+        _ => None,
+    }
+}
+
+/// A saved chunk, migrated from the layout it was saved in
+pub fn decode(data: &[u8]) -> Result<Chunk, GameError> {
+    match version(data) {
+        Some(1) => deserialize_chunk(&data[HEADER_BYTES..]),
+        Some(newer) if newer > VERSION => Err(GameError::ChunkFormat(format!(
+            "saved in layout {} by a newer version of the game, this one reads up to {}",
+            newer, VERSION
+        ))),
+        Some(version) => Err(GameError::ChunkFormat(format!(
+            "unknown layout {}",
+            version
+        ))),
+        None => Err(GameError::ChunkFormat("not a saved chunk".to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests;
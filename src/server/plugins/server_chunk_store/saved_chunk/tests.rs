@@ -0,0 +1,28 @@
+//! Saved chunks must read back in whichever layout they were saved
+use super::*;
+use crate::shared::world_generation::{build_chunk, ChunkCoord, WorldConfig};
+
+fn chunk() -> Chunk {
+    build_chunk(&ChunkCoord { x: 3, y: -2 }, &WorldConfig::default(), 0.0).with_version(5)
+}
+
+#[test]
+fn saved_chunks_read_back() {
+    let data = encode(&chunk()).unwrap();
+    assert_eq!(version(&data), Some(VERSION));
+    assert_eq!(decode(&data).unwrap(), chunk());
+}
+
+#[test]
+fn chunks_without_a_header_are_refused() {
+    let data = serialize_chunk(&chunk(), ChunkFormat::Packed).unwrap();
+    assert_eq!(version(&data), None);
+    assert!(matches!(decode(&data), Err(GameError::ChunkFormat(_))));
+}
+
+#[test]
+fn newer_layouts_are_refused() {
+    let mut data = encode(&chunk()).unwrap();
+    data[MAGIC.len()..HEADER_BYTES].copy_from_slice(&(VERSION + 1).to_le_bytes());
+    assert!(matches!(decode(&data), Err(GameError::ChunkFormat(_))));
+}
@@ -0,0 +1,107 @@
+use bevy::prelude::*;
+use lightyear::prelude::*;
+use std::collections::HashMap;
+
+use crate::protocol::{PlayerId, PlayerPosition};
+use crate::server::plugins::CommandPermissions;
+use crate::shared::commands::{
+    CommandInvoked, CommandReply, CommandSource, CommandSpec, PermissionLevel, RegisterCommandExt,
+};
+use crate::shared::feature_flags::ServerFeatureFlags;
+use crate::shared::instances::InstanceId;
+use crate::shared::world_generation::{ChunkCoord, WorldConfig};
+
+// Server plugin letting players claim chunks so that only they can modify them
+pub struct ServerClaimsPlugin;
+
+impl Plugin for ServerClaimsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LandClaims>()
+            .register_command(CommandSpec::new(
+                "claim",
+                "Claim the chunk you are standing in",
+            ))
+            .register_command(CommandSpec::new(
+                "unclaim",
+                "Release your claim on the chunk you are standing in",
+            ))
+            .add_systems(Update, handle_claim_commands);
+    }
+}
+
+/// Owner of every claimed chunk
+#[derive(Resource, Default)]
+pub struct LandClaims {
+    pub owners: HashMap<ChunkCoord, ClientId>,
+}
+
+impl LandClaims {
+    /// Whether a player may modify tiles in the given chunk: it must be unclaimed or theirs,
+    /// unless they are a moderator
+    pub fn can_modify(
+        &self,
+        coord: ChunkCoord,
+        client_id: ClientId,
+        permissions: &CommandPermissions,
+    ) -> bool {
+        match self.owners.get(&coord) {
+            None => true,
+            Some(owner) if *owner == client_id => true,
+            Some(_) => {
+                permissions.level(CommandSource::Client(client_id)) >= PermissionLevel::Moderator
+            }
+        }
+    }
+}
+
+fn handle_claim_commands(
+    mut invoked: EventReader<CommandInvoked>,
+    mut replies: EventWriter<CommandReply>,
+    mut claims: ResMut<LandClaims>,
+    flags: Res<ServerFeatureFlags>,
+    world_config: Res<WorldConfig>,
+    // claims are on overworld chunks
+    players: Query<(&PlayerId, &PlayerPosition), Without<InstanceId>>,
+) {
+    for command in invoked
+        .read()
+        .filter(|c| c.name == "claim" || c.name == "unclaim")
+    {
+        let CommandSource::Client(client_id) = command.source else {
+            replies.send(CommandReply::new(
+                command.source,
+                "Only players can claim land",
+            ));
+            continue;
+        };
+        // with claims off nobody owns land, so nothing is ever protected
+        if !flags.claims {
+            replies.send(CommandReply::new(
+                command.source,
+                "Land claims are disabled on this server",
+            ));
+            continue;
+        }
+        let Some((_, position)) = players.iter().find(|(id, _)| id.client_id() == client_id) else {
+            continue;
+        };
+        let coord = world_config.grid().world_to_chunk(position.0);
+        let owner = claims.owners.get(&coord).copied();
+        let reply = match (command.name, owner) {
+            ("claim", None) => {
+                claims.owners.insert(coord, client_id);
+                format!("You claimed chunk ({}, {})", coord.x, coord.y)
+            }
+            ("claim", Some(owner)) if owner == client_id => {
+                "You already own this chunk".to_string()
+            }
+            ("claim", Some(_)) => "This chunk is claimed by someone else".to_string(),
+            (_, Some(owner)) if owner == client_id => {
+                claims.owners.remove(&coord);
+                format!("You released chunk ({}, {})", coord.x, coord.y)
+            }
+            _ => "You don't own this chunk".to_string(),
+        };
+        replies.send(CommandReply::new(command.source, reply));
+    }
+}
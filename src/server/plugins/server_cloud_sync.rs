@@ -0,0 +1,627 @@
+//! Cloud copy of a hosted world, on a WebDAV or S3-compatible endpoint.
+//!
+//! The whole `world` directory is packed into one archive and uploaded after every world save,
+//! along with a small manifest holding its hash. `world/cloud_sync.ron` remembers the manifest
+//! of the last archive synced from this computer: on startup, if only the cloud copy changed
+//! since then it's downloaded before the world loads, if only the local world changed it's
+//! uploaded, and if both did the player is asked which one to keep.
+//!
+//! Downloads are unpacked next to the world, into `world.download`, and only take its place at
+//! the next startup, before anything of the world is opened.
+use base64::Engine;
+use bevy::asset::ron;
+use bevy::prelude::*;
+use bevy::tasks::{block_on, futures_lite::future, IoTaskPool, Task};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "sqlite")]
+use crate::server::plugins::{open_database, Database};
+use crate::settings_common::{CloudEndpoint, CloudSyncSettings, Settings};
+use crate::shared::cloud_sync::{utc_time, CloudSyncStatus, ResolveSyncConflict, SaveManifest};
+use crate::shared::error::{GameError, ReportError};
+use crate::shared::world_generation::SaveWorldEvent;
+
+// Directory holding everything that makes up the world
+const WORLD_DIR: &str = "world";
+// Extensions of the directories a downloaded world is unpacked into, and the replaced world is
+// moved to, next to the world
+const DOWNLOAD_EXTENSION: &str = "download";
+const REPLACED_EXTENSION: &str = "old";
+// Manifest of the last archive synced from this computer, kept out of the archive
+const SYNC_STATE: &str = "cloud_sync.ron";
+// Subdirectories and file suffixes not worth syncing: the shard coordinator's state, temporary
+// files and sqlite's journals
+const SKIPPED_DIRS: [&str; 1] = ["cluster"];
+const SKIPPED_SUFFIXES: [&str; 3] = [".tmp", "-wal", "-shm"];
+
+// Server plugin keeping a copy of the world on the endpoint from the settings
+pub struct ServerCloudSyncPlugin;
+
+impl Plugin for ServerCloudSyncPlugin {
+    fn build(&self, app: &mut App) {
+        // before Startup and the database, so that a newer cloud copy is in place before anything
+        // is opened
+        #[cfg(feature = "sqlite")]
+        app.add_systems(PreStartup, check_cloud_save.before(open_database));
+        #[cfg(not(feature = "sqlite"))]
+        app.add_systems(PreStartup, check_cloud_save);
+        app.add_systems(
+            PostUpdate,
+            (
+                upload_on_save,
+                resolve_conflict,
+                finish_upload,
+                finish_download,
+            )
+                .chain()
+                .run_if(resource_exists::<CloudSync>),
+        );
+    }
+}
+
+#[derive(Resource)]
+struct CloudSync {
+    client: CloudClient,
+    world: PathBuf,
+    // The world changed since the last upload started
+    upload_wanted: bool,
+    upload: Option<Task<Result<SaveManifest, GameError>>>,
+    download: Option<Task<Result<(), GameError>>>,
+}
+
+fn cloud_error(e: impl std::fmt::Display) -> GameError {
+    GameError::CloudSync(e.to_string())
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+// Requests to the endpoint, for the files of one world
+#[derive(Clone)]
+struct CloudClient {
+    endpoint: CloudEndpoint,
+    world_name: String,
+}
+
+impl CloudClient {
+    fn new(settings: &CloudSyncSettings) -> Self {
+        Self {
+            endpoint: settings.endpoint.clone(),
+            world_name: settings.world_name.clone(),
+        }
+    }
+
+    // Path of a file of the world, below the endpoint's base URL
+    fn key(&self, name: &str) -> String {
+        format!("{}/{}", uri_encode(&self.world_name), name)
+    }
+
+    fn request(&self, method: &str, name: &str, body: &[u8]) -> ureq::Request {
+        match &self.endpoint {
+            CloudEndpoint::WebDav {
+                url,
+                username,
+                password,
+            } => {
+                let credentials = base64::engine::general_purpose::STANDARD
+                    .encode(format!("{}:{}", username, password.expose()));
+                ureq::request(method, &format!("{}{}", url, self.key(name)))
+                    .set("Authorization", &format!("Basic {}", credentials))
+            }
+            CloudEndpoint::S3 {
+                endpoint,
+                bucket,
+                region,
+                access_key,
+                secret_key,
+            } => {
+                let path = format!("/{}/{}", uri_encode(bucket), self.key(name));
+                let host = endpoint
+                    .split("://")
+                    .last()
+                    .and_then(|rest| rest.split('/').next())
+                    .unwrap_or_default();
+                let payload_hash = hex(&Sha256::digest(body));
+                let (amz_date, authorization) = sign_s3(
+                    method,
+                    host,
+                    &path,
+                    &payload_hash,
+                    unix_now(),
+                    region,
+                    access_key.expose(),
+                    secret_key.expose(),
+                );
+                ureq::request(
+                    method,
+                    &format!("{}{}", endpoint.trim_end_matches('/'), path),
+                )
+                .set("x-amz-date", &amz_date)
+                .set("x-amz-content-sha256", &payload_hash)
+                .set("Authorization", &authorization)
+            }
+        }
+    }
+
+    fn get(&self, name: &str) -> Result<Option<Vec<u8>>, GameError> {
+        match self.request("GET", name, &[]).call() {
+            Ok(response) => {
+                let mut data = Vec::new();
+                response.into_reader().read_to_end(&mut data)?;
+                Ok(Some(data))
+            }
+            Err(ureq::Error::Status(404, _)) => Ok(None),
+            Err(e) => Err(cloud_error(e)),
+        }
+    }
+
+    fn put(&self, name: &str, data: &[u8]) -> Result<(), GameError> {
+        if let CloudEndpoint::WebDav { .. } = self.endpoint {
+            // the world's collection must exist before files can be put in it
+            match self.request("MKCOL", "", &[]).call() {
+                Ok(_) | Err(ureq::Error::Status(405, _)) => {}
+                Err(e) => return Err(cloud_error(e)),
+            }
+        }
+        self.request("PUT", name, data)
+            .send_bytes(data)
+            .map_err(cloud_error)?;
+        Ok(())
+    }
+
+    fn manifest(&self) -> Result<Option<SaveManifest>, GameError> {
+        let Some(data) = self.get("manifest.ron")? else {
+            return Ok(None);
+        };
+        let text = String::from_utf8(data).map_err(cloud_error)?;
+        ron::from_str(&text).map(Some).map_err(cloud_error)
+    }
+
+    fn upload(&self, archive: &[u8], manifest: &SaveManifest) -> Result<(), GameError> {
+        // the manifest goes last, so that it never describes an archive that isn't there yet
+        self.put("world.bin", archive)?;
+        self.put(
+            "manifest.ron",
+            ron::to_string(manifest).map_err(cloud_error)?.as_bytes(),
+        )
+    }
+
+    fn download(&self, manifest: &SaveManifest) -> Result<Vec<u8>, GameError> {
+        let archive = self
+            .get("world.bin")?
+            .ok_or_else(|| cloud_error("the cloud save has a manifest but no archive"))?;
+        if hex(&Sha256::digest(&archive)) != manifest.hash {
+            return Err(cloud_error("the cloud save doesn't match its manifest"));
+        }
+        Ok(archive)
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// Percent-encode everything but the characters S3 leaves alone, and slashes
+fn uri_encode(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any size");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+// AWS signature version 4 for a request without a query string. Returns the `x-amz-date` and
+// `Authorization` headers.
+#[allow(clippy::too_many_arguments)]
+fn sign_s3(
+    method: &str,
+    host: &str,
+    path: &str,
+    payload_hash: &str,
+    now: u64,
+    region: &str,
+    access_key: &str,
+    secret_key: &str,
+) -> (String, String) {
+    let (year, month, day, hours, minutes, seconds) = utc_time(now);
+    let date = format!("{:04}{:02}{:02}", year, month, day);
+    let amz_date = format!("{}T{:02}{:02}{:02}Z", date, hours, minutes, seconds);
+    let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+    let canonical_request = format!(
+        "{}\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+        method, path, host, payload_hash, amz_date, signed_headers, payload_hash
+    );
+    let scope = format!("{}/{}/s3/aws4_request", date, region);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        hex(&Sha256::digest(canonical_request.as_bytes()))
+    );
+    let key = [date.as_str(), region, "s3", "aws4_request"]
+        .iter()
+        .fold(format!("AWS4{}", secret_key).into_bytes(), |key, part| {
+            hmac_sha256(&key, part)
+        });
+    let signature = hex(&hmac_sha256(&key, &string_to_sign));
+    let authorization = format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        access_key, scope, signed_headers, signature
+    );
+    (amz_date, authorization)
+}
+
+// Files of the world worth syncing, relative to its directory
+fn world_files(world: &Path) -> Result<Vec<PathBuf>, GameError> {
+    let mut files = Vec::new();
+    let mut dirs = vec![PathBuf::new()];
+    while let Some(dir) = dirs.pop() {
+        let full = world.join(&dir);
+        if !full.exists() {
+            continue;
+        }
+        for entry in fs::read_dir(full)? {
+            let entry = entry?;
+            let relative = dir.join(entry.file_name());
+            let name = entry.file_name().to_string_lossy().to_string();
+            if entry.file_type()?.is_dir() {
+                if !(dir.as_os_str().is_empty() && SKIPPED_DIRS.contains(&name.as_str())) {
+                    dirs.push(relative);
+                }
+            } else if !(dir.as_os_str().is_empty() && name == SYNC_STATE)
+                && !SKIPPED_SUFFIXES.iter().any(|suffix| name.ends_with(suffix))
+            {
+                files.push(relative);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+// Pack the world into one archive, and describe it
+fn archive_world(world: &Path) -> Result<(Vec<u8>, SaveManifest), GameError> {
+    let mut entries = Vec::new();
+    let mut saved_at = 0;
+    for file in world_files(world)? {
+        let path = world.join(&file);
+        let modified = fs::metadata(&path)?
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        saved_at = saved_at.max(modified);
+        // archives are shared between computers: always use forward slashes
+        let name = file.to_string_lossy().replace('\\', "/");
+        entries.push((name, fs::read(path)?));
+    }
+    let archive = bincode::serialize(&entries).map_err(cloud_error)?;
+    let manifest = SaveManifest {
+        saved_at,
+        hash: hex(&Sha256::digest(&archive)),
+    };
+    Ok((archive, manifest))
+}
+
+// Unpack an archive into a new directory
+fn unpack_world(dir: &Path, archive: &[u8]) -> Result<(), GameError> {
+    let entries: Vec<(String, Vec<u8>)> = bincode::deserialize(archive).map_err(cloud_error)?;
+    for (name, data) in entries {
+        if name.split('/').any(|part| part == ".." || part.is_empty()) {
+            return Err(cloud_error(format!(
+                "unsafe path {:?} in the cloud save",
+                name
+            )));
+        }
+        let path = dir.join(&name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, data)?;
+    }
+    Ok(())
+}
+
+// Download the cloud copy next to the world. Its sync state is written last, marking the
+// download as complete.
+fn download_world(
+    client: &CloudClient,
+    world: &Path,
+    cloud: &SaveManifest,
+) -> Result<(), GameError> {
+    let download = world.with_extension(DOWNLOAD_EXTENSION);
+    if download.exists() {
+        fs::remove_dir_all(&download)?;
+    }
+    let archive = client.download(cloud)?;
+    unpack_world(&download, &archive)?;
+    write_sync_state(&download, cloud)
+}
+
+fn download_task(
+    client: CloudClient,
+    world: PathBuf,
+    cloud: SaveManifest,
+) -> Task<Result<(), GameError>> {
+    IoTaskPool::get().spawn(async move { download_world(&client, &world, &cloud) })
+}
+
+// Put a complete download in place of the world, along with the world's directories that aren't
+// synced. Its sqlite journals are left behind with the replaced world. Returns whether there was
+// a download to put in place.
+fn swap_in_download(world: &Path) -> Result<bool, GameError> {
+    let download = world.with_extension(DOWNLOAD_EXTENSION);
+    if !download.exists() {
+        return Ok(false);
+    }
+    if read_sync_state(&download).is_none() {
+        warn!("Dropping an unfinished download of the cloud save");
+        fs::remove_dir_all(&download)?;
+        return Ok(false);
+    }
+    let replaced = world.with_extension(REPLACED_EXTENSION);
+    if replaced.exists() {
+        fs::remove_dir_all(&replaced)?;
+    }
+    if world.exists() {
+        for dir in SKIPPED_DIRS {
+            if world.join(dir).exists() {
+                fs::rename(world.join(dir), download.join(dir))?;
+            }
+        }
+        fs::rename(world, &replaced)?;
+    }
+    fs::rename(&download, world)?;
+    if replaced.exists() {
+        fs::remove_dir_all(&replaced)?;
+    }
+    Ok(true)
+}
+
+fn read_sync_state(world: &Path) -> Option<SaveManifest> {
+    let text = fs::read_to_string(world.join(SYNC_STATE)).ok()?;
+    ron::from_str(&text).ok()
+}
+
+fn write_sync_state(world: &Path, manifest: &SaveManifest) -> Result<(), GameError> {
+    fs::create_dir_all(world)?;
+    let text = ron::to_string(manifest).map_err(cloud_error)?;
+    Ok(fs::write(world.join(SYNC_STATE), text)?)
+}
+
+// What to do with the local world and the cloud copy when starting up
+#[derive(Debug, PartialEq)]
+enum SyncPlan {
+    UpToDate,
+    Upload,
+    Download(SaveManifest),
+    Conflict(SaveManifest),
+}
+
+fn plan_sync(
+    local: &SaveManifest,
+    local_is_empty: bool,
+    last_synced: Option<&SaveManifest>,
+    cloud: Option<SaveManifest>,
+) -> SyncPlan {
+    let Some(cloud) = cloud else {
+        return if local_is_empty {
+            SyncPlan::UpToDate
+        } else {
+            SyncPlan::Upload
+        };
+    };
+    if cloud.hash == local.hash {
+        return SyncPlan::UpToDate;
+    }
+    let changed =
+        |manifest: &SaveManifest| last_synced.is_none_or(|last| last.hash != manifest.hash);
+    match (!local_is_empty && changed(local), changed(&cloud)) {
+        (false, _) => SyncPlan::Download(cloud),
+        (true, false) => SyncPlan::Upload,
+        (true, true) => SyncPlan::Conflict(cloud),
+    }
+}
+
+fn upload_task(
+    client: CloudClient,
+    world: PathBuf,
+    #[cfg(feature = "sqlite")] database: Option<Database>,
+) -> Task<Result<SaveManifest, GameError>> {
+    IoTaskPool::get().spawn(async move {
+        // the last commits are in sqlite's WAL, which isn't archived: have them written to the
+        // database file, and keep that file as it is while it's read
+        #[cfg(feature = "sqlite")]
+        let pause = database.as_ref().map(Database::pause).transpose()?;
+        let (archive, manifest) = archive_world(&world)?;
+        #[cfg(feature = "sqlite")]
+        drop(pause);
+        client.upload(&archive, &manifest)?;
+        Ok(manifest)
+    })
+}
+
+fn sync_on_startup(sync: &mut CloudSync) -> Result<CloudSyncStatus, GameError> {
+    let (_, local) = archive_world(&sync.world)?;
+    let local_is_empty = world_files(&sync.world)?.is_empty();
+    let last_synced = read_sync_state(&sync.world);
+    let cloud = sync.client.manifest()?;
+    match plan_sync(&local, local_is_empty, last_synced.as_ref(), cloud) {
+        SyncPlan::UpToDate => {
+            if !local_is_empty {
+                write_sync_state(&sync.world, &local)?;
+            }
+            Ok(CloudSyncStatus::InSync)
+        }
+        SyncPlan::Upload => {
+            // once the world's database is open
+            sync.upload_wanted = true;
+            Ok(CloudSyncStatus::Uploading)
+        }
+        SyncPlan::Download(cloud) => {
+            info!("The cloud save is newer, downloading it");
+            download_world(&sync.client, &sync.world, &cloud)?;
+            swap_in_download(&sync.world)?;
+            Ok(CloudSyncStatus::InSync)
+        }
+        SyncPlan::Conflict(cloud) => {
+            warn!("Both the local world and its cloud save changed since the last sync");
+            Ok(CloudSyncStatus::Conflict { local, cloud })
+        }
+    }
+}
+
+fn check_cloud_save(
+    mut commands: Commands,
+    settings: Option<Res<Settings>>,
+    mut status: ResMut<CloudSyncStatus>,
+    mut errors: EventWriter<ReportError>,
+) {
+    let Some(cloud_sync) = settings.and_then(|settings| settings.server.cloud_sync.clone()) else {
+        return;
+    };
+    let mut sync = CloudSync {
+        client: CloudClient::new(&cloud_sync),
+        world: PathBuf::from(WORLD_DIR),
+        upload_wanted: false,
+        upload: None,
+        download: None,
+    };
+    // the world isn't loaded yet, so this has to finish before the game starts
+    *status = swap_in_download(&sync.world)
+        .and_then(|swapped| {
+            if swapped {
+                info!("Replaced the local world with the cloud save downloaded last time");
+            }
+            sync_on_startup(&mut sync)
+        })
+        .unwrap_or_else(|e| {
+            let reason = e.to_string();
+            errors.send(ReportError(e));
+            CloudSyncStatus::Failed(reason)
+        });
+    commands.insert_resource(sync);
+}
+
+// Upload the world after it's saved, one upload at a time, unless there is a conflict to settle
+// first or the world is about to be replaced
+fn upload_on_save(
+    mut save_events: EventReader<SaveWorldEvent>,
+    mut sync: ResMut<CloudSync>,
+    mut status: ResMut<CloudSyncStatus>,
+    #[cfg(feature = "sqlite")] database: Option<Res<Database>>,
+) {
+    if save_events.read().count() > 0 {
+        sync.upload_wanted = true;
+    }
+    if !sync.upload_wanted
+        || sync.upload.is_some()
+        || matches!(
+            *status,
+            CloudSyncStatus::Conflict { .. } | CloudSyncStatus::Downloading
+        )
+    {
+        return;
+    }
+    let (client, world) = (sync.client.clone(), sync.world.clone());
+    #[cfg(feature = "sqlite")]
+    let task = upload_task(client, world, database.as_deref().cloned());
+    #[cfg(not(feature = "sqlite"))]
+    let task = upload_task(client, world);
+    sync.upload_wanted = false;
+    sync.upload = Some(task);
+    *status = CloudSyncStatus::Uploading;
+}
+
+fn resolve_conflict(
+    mut choices: EventReader<ResolveSyncConflict>,
+    mut sync: ResMut<CloudSync>,
+    mut status: ResMut<CloudSyncStatus>,
+) {
+    let Some(choice) = choices.read().last() else {
+        return;
+    };
+    let CloudSyncStatus::Conflict { cloud, .. } = status.clone() else {
+        return;
+    };
+    match choice {
+        ResolveSyncConflict::KeepLocal => {
+            info!("Overwriting the cloud save with the local world");
+            sync.upload_wanted = true;
+            *status = CloudSyncStatus::Uploading;
+        }
+        ResolveSyncConflict::UseCloud => {
+            info!("Downloading the cloud save to replace the local world");
+            sync.download = Some(download_task(
+                sync.client.clone(),
+                sync.world.clone(),
+                cloud,
+            ));
+            *status = CloudSyncStatus::Downloading;
+        }
+    }
+}
+
+// The loaded world can't be replaced while it's running: quit once the cloud save is downloaded,
+// for it to be swapped in at the next start
+fn finish_download(
+    mut sync: ResMut<CloudSync>,
+    mut status: ResMut<CloudSyncStatus>,
+    mut app_exit: EventWriter<AppExit>,
+    mut errors: EventWriter<ReportError>,
+) {
+    let Some(task) = sync.download.as_mut() else {
+        return;
+    };
+    let Some(result) = block_on(future::poll_once(task)) else {
+        return;
+    };
+    sync.download = None;
+    match result {
+        Ok(()) => {
+            info!("Cloud save downloaded, start the game again to play it");
+            app_exit.send(AppExit::Success);
+        }
+        Err(e) => {
+            *status = CloudSyncStatus::Failed(e.to_string());
+            errors.send(ReportError(e));
+        }
+    }
+}
+
+fn finish_upload(
+    mut sync: ResMut<CloudSync>,
+    mut status: ResMut<CloudSyncStatus>,
+    mut errors: EventWriter<ReportError>,
+) {
+    let Some(task) = sync.upload.as_mut() else {
+        return;
+    };
+    let Some(result) = block_on(future::poll_once(task)) else {
+        return;
+    };
+    sync.upload = None;
+    match result.and_then(|manifest| write_sync_state(&sync.world, &manifest)) {
+        Ok(()) => *status = CloudSyncStatus::InSync,
+        Err(e) => {
+            *status = CloudSyncStatus::Failed(e.to_string());
+            errors.send(ReportError(e));
+        }
+    }
+}
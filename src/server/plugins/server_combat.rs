@@ -0,0 +1,213 @@
+use bevy::prelude::*;
+use lightyear::prelude::server::*;
+use lightyear::prelude::*;
+
+use crate::protocol::{PlayerId, PlayerPosition};
+use crate::shared::commands::{CommandReply, CommandSource};
+use crate::shared::feature_flags::ServerFeatureFlags;
+use crate::shared::instances::InstanceId;
+use crate::shared::interaction::INTERACT_REACH;
+use crate::shared::items::{Inventory, ItemKind};
+use crate::shared::kinematics::{step_velocity, Velocity, GROUND_FRICTION, KNOCKBACK};
+use crate::shared::line_of_sight::first_blocking;
+use crate::shared::movement::LoadedTiles;
+use crate::shared::npc::Npc;
+use crate::shared::projectiles::{Projectile, ThrowStone, AIR_FRICTION, HIT_RADIUS, THROW_SPEED};
+use crate::shared::reputation::AttackNpc;
+use crate::shared::survival::Spectator;
+use crate::shared::world_generation::WorldGrid;
+
+// Server plugin for fighting NPCs, up close or with thrown stones. Struck bodies are knocked back
+// through the shared kinematics; what a strike means for the player's standing is up to the
+// reputation plugin.
+pub struct ServerCombatPlugin;
+
+impl Plugin for ServerCombatPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<NpcStruckEvent>()
+            .add_systems(FixedUpdate, (fly_projectiles, drift_npcs))
+            .add_systems(
+                Update,
+                (handle_melee_attacks, throw_stones, knock_back_npcs).chain(),
+            );
+    }
+}
+
+/// Sent when a player hits an NPC, from `from`: where they stand, or where their stone came from
+#[derive(Event, Clone, Copy, Debug)]
+pub struct NpcStruckEvent {
+    pub client_id: ClientId,
+    pub npc: Entity,
+    pub from: Vec2,
+}
+
+fn handle_melee_attacks(
+    mut events: EventReader<MessageEvent<AttackNpc>>,
+    mut replies: EventWriter<CommandReply>,
+    mut struck: EventWriter<NpcStruckEvent>,
+    players: Query<(&PlayerId, &PlayerPosition, Option<&InstanceId>), Without<Spectator>>,
+    npcs: Query<(Entity, &Npc, Option<&InstanceId>)>,
+) {
+    for event in events.read() {
+        let client_id = event.from();
+        let tile = event.message().tile;
+        let Some((_, position, instance)) = players
+            .iter()
+            .find(|(id, _, _)| id.client_id() == client_id)
+        else {
+            continue;
+        };
+        let target = npcs.iter().find(|(_, npc, i)| {
+            *i == instance
+                && WorldGrid::world_to_tile(npc.position) == tile
+                && npc.position.distance(position.0) <= INTERACT_REACH
+        });
+        let Some((npc, _, _)) = target else {
+            replies.send(CommandReply::new(
+                CommandSource::Client(client_id),
+                "There is nobody to attack here",
+            ));
+            continue;
+        };
+        struck.send(NpcStruckEvent {
+            client_id,
+            npc,
+            from: position.0,
+        });
+    }
+}
+
+// Stones are only thrown in the overworld, where the server has the tiles they fly over
+fn throw_stones(
+    mut commands: Commands,
+    mut events: EventReader<MessageEvent<ThrowStone>>,
+    mut replies: EventWriter<CommandReply>,
+    mut players: Query<
+        (&PlayerId, &PlayerPosition, &mut Inventory),
+        (Without<InstanceId>, Without<Spectator>),
+    >,
+) {
+    for event in events.read() {
+        let client_id = event.from();
+        let direction = event.message().direction.normalize_or_zero();
+        if direction == Vec2::ZERO {
+            continue;
+        }
+        let Some((_, position, mut inventory)) = players
+            .iter_mut()
+            .find(|(id, _, _)| id.client_id() == client_id)
+        else {
+            continue;
+        };
+        if !inventory.remove(ItemKind::Stone, 1) {
+            replies.send(CommandReply::new(
+                CommandSource::Client(client_id),
+                "You have no stone to throw",
+            ));
+            continue;
+        }
+        commands.spawn((
+            Projectile {
+                owner: client_id,
+                position: position.0,
+            },
+            Velocity(direction * THROW_SPEED),
+            Replicate::default(),
+        ));
+    }
+}
+
+// Projectiles stop at the first body they hit, at cliffs and whatever can't be walked through
+// (trees, walls), and once they run out of speed. Other players are only pushed around on PvP
+// servers.
+fn fly_projectiles(
+    mut commands: Commands,
+    flags: Res<ServerFeatureFlags>,
+    tiles: LoadedTiles,
+    mut struck: EventWriter<NpcStruckEvent>,
+    mut projectiles: Query<(Entity, &mut Projectile, &mut Velocity), Without<PlayerId>>,
+    mut players: Query<
+        (&PlayerId, &PlayerPosition, &mut Velocity),
+        (Without<InstanceId>, Without<Spectator>),
+    >,
+    npcs: Query<(Entity, &Npc), Without<InstanceId>>,
+) {
+    for (entity, mut projectile, mut velocity) in projectiles.iter_mut() {
+        let from = projectile.position;
+        let blocked = step_velocity(
+            &mut projectile.position,
+            &mut velocity,
+            AIR_FRICTION,
+            &tiles,
+        );
+        let at = projectile.position;
+        if first_blocking(from, at, &tiles, |tile| !tile.traversable).is_some() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        if let Some((npc, _)) = npcs
+            .iter()
+            .find(|(_, npc)| npc.position.distance(at) <= HIT_RADIUS)
+        {
+            struck.send(NpcStruckEvent {
+                client_id: projectile.owner,
+                npc,
+                from,
+            });
+            commands.entity(entity).despawn();
+            continue;
+        }
+        if flags.pvp {
+            if let Some((_, position, mut knocked)) =
+                players.iter_mut().find(|(id, position, _)| {
+                    id.client_id() != projectile.owner && position.0.distance(at) <= HIT_RADIUS
+                })
+            {
+                knocked.knockback(from, position.0, KNOCKBACK);
+                commands.entity(entity).despawn();
+                continue;
+            }
+        }
+        if blocked.any() || velocity.is_resting() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn knock_back_npcs(
+    mut commands: Commands,
+    mut struck: EventReader<NpcStruckEvent>,
+    mut npcs: Query<(&Npc, Option<&mut Velocity>)>,
+) {
+    for event in struck.read() {
+        let Ok((npc, velocity)) = npcs.get_mut(event.npc) else {
+            continue;
+        };
+        match velocity {
+            Some(mut velocity) => velocity.knockback(event.from, npc.position, KNOCKBACK),
+            None => {
+                let mut velocity = Velocity::default();
+                velocity.knockback(event.from, npc.position, KNOCKBACK);
+                // the NPC may be gone by the time commands apply, e.g. a bandit driven off
+                commands.entity(event.npc).try_insert(velocity);
+            }
+        }
+    }
+}
+
+// NPCs drift when knocked back, on top of walking their paths
+fn drift_npcs(
+    tiles: LoadedTiles,
+    mut npcs: Query<(&mut Npc, &mut Velocity, Option<&InstanceId>), Without<PlayerId>>,
+) {
+    for (mut npc, mut velocity, instance) in npcs.iter_mut() {
+        if velocity.0 == Vec2::ZERO {
+            continue;
+        }
+        if instance.is_some() {
+            step_velocity(&mut npc.position, &mut velocity, GROUND_FRICTION, &());
+        } else {
+            step_velocity(&mut npc.position, &mut velocity, GROUND_FRICTION, &tiles);
+        }
+    }
+}
@@ -0,0 +1,279 @@
+use bevy::prelude::*;
+use crossbeam_channel::{Receiver, TryRecvError};
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use lightyear::prelude::server::*;
+use lightyear::prelude::*;
+
+use crate::protocol::{ChatChannel, ChatLine, ChatMessage, PlayerId, PlayerName};
+use crate::server::plugins::{ContentFilter, ContentFlagged, ContentKind, ModerationQueue};
+use crate::settings_common::{client_ids_verified, Settings};
+use crate::shared::commands::{
+    CommandError, CommandInvoked, CommandRegistry, CommandReply, CommandSource, PermissionLevel,
+    COMMAND_PREFIX,
+};
+use crate::shared::social::SocialLists;
+
+// Server plugin that turns chat lines and console input into command invocations
+pub struct ServerCommandsPlugin;
+
+impl Plugin for ServerCommandsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CommandPermissions>()
+            .add_systems(Startup, (load_admins, spawn_console_reader))
+            .add_systems(
+                Update,
+                (
+                    (receive_chat_messages, read_console_commands),
+                    handle_help_command,
+                    deliver_command_replies,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Permission level of every client. Clients that aren't listed are regular players.
+#[derive(Resource, Default)]
+pub struct CommandPermissions {
+    pub levels: HashMap<ClientId, PermissionLevel>,
+    pub admin_ids: Vec<u64>,
+}
+
+impl CommandPermissions {
+    pub fn level(&self, source: CommandSource) -> PermissionLevel {
+        match source {
+            CommandSource::Console => PermissionLevel::Admin,
+            // the host of a host-server game owns the server
+            CommandSource::Client(ClientId::Local(_)) => PermissionLevel::Admin,
+            CommandSource::Client(client_id) => {
+                if let Some(level) = self.levels.get(&client_id) {
+                    *level
+                } else if self.admin_ids.contains(&client_id.to_bits()) {
+                    PermissionLevel::Admin
+                } else {
+                    PermissionLevel::Player
+                }
+            }
+        }
+    }
+}
+
+/// Lines typed into the server's stdin
+#[derive(Resource)]
+struct ConsoleInput(Receiver<String>);
+
+fn load_admins(settings: Option<Res<Settings>>, mut permissions: ResMut<CommandPermissions>) {
+    let Some(settings) = settings else {
+        return;
+    };
+    if settings.server.admins.is_empty() {
+        return;
+    }
+    // anyone could claim an admin's id
+    if !client_ids_verified(&settings.shared) {
+        warn!(
+            "Ignoring the {} admin id(s) in the settings: client ids aren't verified. Run the \
+             server with its own private key in LIGHTYEAR_PRIVATE_KEY, and clients with connect \
+             tokens from a token server, to grant admin rights by id",
+            settings.server.admins.len()
+        );
+        return;
+    }
+    permissions.admin_ids = settings.server.admins.clone();
+}
+
+// Read the console on a separate thread so that the schedule never blocks on stdin
+fn spawn_console_reader(mut commands: Commands) {
+    let (sender, receiver) = crossbeam_channel::unbounded();
+    std::thread::spawn(move || {
+        let stdin = std::io::stdin();
+        for line in stdin.lock().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    commands.insert_resource(ConsoleInput(receiver));
+}
+
+fn dispatch(
+    line: &str,
+    source: CommandSource,
+    registry: &CommandRegistry,
+    permissions: &CommandPermissions,
+    invoked: &mut EventWriter<CommandInvoked>,
+    replies: &mut EventWriter<CommandReply>,
+) {
+    match registry.parse(line, permissions.level(source)) {
+        Ok((spec, args)) => {
+            info!("{:?} ran command {}", source, line);
+            invoked.send(CommandInvoked {
+                source,
+                name: spec.name,
+                args,
+            });
+        }
+        Err(CommandError::NotACommand) => {}
+        Err(error) => {
+            replies.send(CommandReply::new(source, error.to_string()));
+        }
+    }
+}
+
+/// Commands are dispatched, every other chat line goes through the content filter and is relayed
+/// to all players, but those who muted its author, unless the author is muted by a moderator
+fn receive_chat_messages(
+    mut events: EventReader<MessageEvent<ChatMessage>>,
+    registry: Res<CommandRegistry>,
+    permissions: Res<CommandPermissions>,
+    filter: Res<ContentFilter>,
+    moderation: Res<ModerationQueue>,
+    players: Query<(&PlayerId, &PlayerName, Option<&SocialLists>)>,
+    mut invoked: EventWriter<CommandInvoked>,
+    mut replies: EventWriter<CommandReply>,
+    mut flagged: EventWriter<ContentFlagged>,
+    mut connection_manager: ResMut<ConnectionManager>,
+) {
+    for event in events.read() {
+        let client_id = event.from();
+        let text = event.message().text.trim();
+        if text.is_empty() {
+            continue;
+        }
+        if text.starts_with(COMMAND_PREFIX) {
+            dispatch(
+                text,
+                CommandSource::Client(client_id),
+                &registry,
+                &permissions,
+                &mut invoked,
+                &mut replies,
+            );
+            continue;
+        }
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        if let Some(secs) = moderation.muted_for(client_id, now) {
+            replies.send(CommandReply::new(
+                CommandSource::Client(client_id),
+                format!("You are muted for {} more minutes", secs.div_ceil(60)),
+            ));
+            continue;
+        }
+        let outcome = filter.check(ContentKind::Chat, text);
+        let blocked = outcome.is_blocked();
+        let censored = outcome.text.clone();
+        if !outcome.is_clean() {
+            flagged.send(ContentFlagged {
+                client_id,
+                kind: ContentKind::Chat,
+                original: text.to_string(),
+                outcome,
+            });
+        }
+        if blocked {
+            continue;
+        }
+        let sender = players
+            .iter()
+            .find(|(id, _, _)| id.client_id() == client_id)
+            .map(|(_, name, _)| name.0.clone())
+            .unwrap_or_else(|| format!("Player {}", client_id));
+        let line = ChatLine {
+            sender: Some(sender),
+            text: censored,
+        };
+        let muted_by: Vec<ClientId> = players
+            .iter()
+            .filter(|(_, _, social)| social.is_some_and(|social| social.hides_chat_of(client_id)))
+            .map(|(id, _, _)| id.client_id())
+            .collect();
+        connection_manager
+            .send_message_to_target::<ChatChannel, ChatLine>(
+                &line,
+                NetworkTarget::AllExcept(muted_by),
+            )
+            .unwrap_or_else(|e| {
+                error!("Failed to relay chat message: {:?}", e);
+            });
+    }
+}
+
+fn read_console_commands(
+    console: Option<Res<ConsoleInput>>,
+    registry: Res<CommandRegistry>,
+    permissions: Res<CommandPermissions>,
+    mut invoked: EventWriter<CommandInvoked>,
+    mut replies: EventWriter<CommandReply>,
+) {
+    let Some(console) = console else {
+        return;
+    };
+    loop {
+        match console.0.try_recv() {
+            Ok(line) => {
+                let line = line.trim();
+                if line.is_empty() {
+                    continue;
+                }
+                // the prefix is optional on the console
+                let line = if line.starts_with(COMMAND_PREFIX) {
+                    line.to_string()
+                } else {
+                    format!("{}{}", COMMAND_PREFIX, line)
+                };
+                dispatch(
+                    &line,
+                    CommandSource::Console,
+                    &registry,
+                    &permissions,
+                    &mut invoked,
+                    &mut replies,
+                );
+            }
+            Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => break,
+        }
+    }
+}
+
+fn handle_help_command(
+    mut invoked: EventReader<CommandInvoked>,
+    registry: Res<CommandRegistry>,
+    permissions: Res<CommandPermissions>,
+    mut replies: EventWriter<CommandReply>,
+) {
+    for command in invoked.read().filter(|c| c.name == "help") {
+        let level = permissions.level(command.source);
+        replies.send(CommandReply::new(command.source, "Available commands:"));
+        for line in registry.help_lines(level) {
+            replies.send(CommandReply::new(command.source, line));
+        }
+    }
+}
+
+// Send command output back to the console or to the client that issued the command
+fn deliver_command_replies(
+    mut replies: EventReader<CommandReply>,
+    mut connection_manager: ResMut<ConnectionManager>,
+) {
+    for reply in replies.read() {
+        match reply.target {
+            CommandSource::Console => info!("{}", reply.text),
+            CommandSource::Client(client_id) => {
+                connection_manager
+                    .send_message::<ChatChannel, _>(client_id, &ChatLine::system(&reply.text))
+                    .unwrap_or_else(|e| {
+                        error!("Failed to send command reply to {:?}: {:?}", client_id, e);
+                    });
+            }
+        }
+    }
+}
@@ -0,0 +1,405 @@
+use bevy::asset::ron;
+use bevy::prelude::*;
+use lightyear::prelude::server::*;
+use lightyear::prelude::*;
+use serde::Deserialize;
+use std::fmt;
+use std::fs;
+use std::ops::Range;
+
+use crate::protocol::{ChatChannel, ChatLine, PlayerId};
+use crate::server::plugins::CommandPermissions;
+use crate::settings_common::Settings;
+use crate::shared::commands::{
+    ArgKind, CommandInvoked, CommandReply, CommandSource, CommandSpec, PermissionLevel,
+    RegisterCommandExt,
+};
+use crate::shared::error::{GameError, ReportError};
+
+// Character replacing the letters of censored words
+const CENSOR_CHAR: char = '*';
+
+// Server plugin filtering chat messages and player names through per-server wordlists and
+// hooks registered by other plugins, then warning, reporting or blocking as the lists say
+pub struct ServerContentFilterPlugin;
+
+impl Plugin for ServerContentFilterPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ContentFilter>()
+            .add_event::<ContentFlagged>()
+            .register_command(
+                CommandSpec::new(
+                    "filter",
+                    "'reload' the content filter lists, or 'test' a text against them",
+                )
+                .arg("action", ArgKind::Word)
+                .optional_arg("text", ArgKind::Text)
+                .permission(PermissionLevel::Admin),
+            )
+            .add_systems(Startup, load_filter_lists)
+            .add_systems(
+                Update,
+                (handle_filter_command, act_on_flagged_content).chain(),
+            );
+    }
+}
+
+/// What a piece of text is used for; lists can apply to some kinds only
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ContentKind {
+    Chat,
+    Name,
+}
+
+impl fmt::Display for ContentKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContentKind::Chat => write!(f, "chat message"),
+            ContentKind::Name => write!(f, "name"),
+        }
+    }
+}
+
+/// What to do with text a filter matched. Several actions can apply at once.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FilterAction {
+    /// Tell the author their text broke the rules
+    Warn,
+    /// Tell online moderators and admins, and log it
+    Report,
+    /// Replace the matched words with asterisks
+    Censor,
+    /// Drop the text altogether
+    Block,
+}
+
+/// A per-server list of words, loaded from one of the RON files named in the server settings
+#[derive(Deserialize, Clone, Debug)]
+pub struct Wordlist {
+    #[serde(skip)]
+    pub name: String,
+    #[serde(default = "all_kinds")]
+    pub applies_to: Vec<ContentKind>,
+    pub actions: Vec<FilterAction>,
+    /// Also match words hidden inside longer ones, e.g. in names without spaces
+    #[serde(default)]
+    pub match_inside_words: bool,
+    pub words: Vec<String>,
+}
+
+fn all_kinds() -> Vec<ContentKind> {
+    vec![ContentKind::Chat, ContentKind::Name]
+}
+
+impl Wordlist {
+    fn check(&self, kind: ContentKind, text: &str) -> Option<FilterMatch> {
+        if !self.applies_to.contains(&kind) {
+            return None;
+        }
+        let mut spans = Vec::new();
+        let mut matched = Vec::new();
+        for (span, token) in tokens(text) {
+            let word = self.words.iter().find(|word| {
+                if self.match_inside_words {
+                    token.contains(word.as_str())
+                } else {
+                    token == **word
+                }
+            });
+            if let Some(word) = word {
+                spans.push(span);
+                matched.push(word.as_str());
+            }
+        }
+        if spans.is_empty() {
+            return None;
+        }
+        Some(FilterMatch {
+            actions: self.actions.clone(),
+            spans,
+            reason: format!("{} ({})", self.name, matched.join(", ")),
+        })
+    }
+}
+
+/// What a filter found in a text: the parts it objects to, as byte ranges, and what to do about it
+#[derive(Clone, Debug, PartialEq)]
+pub struct FilterMatch {
+    pub actions: Vec<FilterAction>,
+    pub spans: Vec<Range<usize>>,
+    pub reason: String,
+}
+
+/// A custom filter, registered with [`AddContentFilterExt::add_content_filter`]
+pub type FilterHook = Box<dyn Fn(ContentKind, &str) -> Option<FilterMatch> + Send + Sync>;
+
+/// Result of running a text through every filter
+#[derive(Clone, Debug, PartialEq)]
+pub struct FilterOutcome {
+    /// The text, censored if any filter asked for it
+    pub text: String,
+    pub actions: Vec<FilterAction>,
+    pub reasons: Vec<String>,
+}
+
+impl FilterOutcome {
+    pub fn is_clean(&self) -> bool {
+        self.actions.is_empty()
+    }
+
+    pub fn has(&self, action: FilterAction) -> bool {
+        self.actions.contains(&action)
+    }
+
+    pub fn is_blocked(&self) -> bool {
+        self.has(FilterAction::Block)
+    }
+}
+
+/// The filter pipeline: wordlists from the server settings, then the registered hooks
+#[derive(Resource, Default)]
+pub struct ContentFilter {
+    pub lists: Vec<Wordlist>,
+    hooks: Vec<(&'static str, FilterHook)>,
+}
+
+impl ContentFilter {
+    pub fn add_hook(&mut self, name: &'static str, hook: FilterHook) {
+        self.hooks.push((name, hook));
+    }
+
+    /// Load the wordlists at the given paths, replacing the current ones
+    pub fn load_lists(&mut self, paths: &[String]) -> Result<(), GameError> {
+        let mut lists = Vec::new();
+        for path in paths {
+            let text = fs::read_to_string(path)?;
+            let mut list: Wordlist = ron::from_str(&text).map_err(|e| GameError::FilterList {
+                path: path.clone(),
+                reason: e.to_string(),
+            })?;
+            list.name = path.clone();
+            // compare words the way tokens are normalized
+            list.words = list
+                .words
+                .iter()
+                .map(|word| word.chars().map(normalize).collect())
+                .collect();
+            lists.push(list);
+        }
+        self.lists = lists;
+        Ok(())
+    }
+
+    pub fn check(&self, kind: ContentKind, text: &str) -> FilterOutcome {
+        let mut actions = Vec::new();
+        let mut reasons = Vec::new();
+        let mut censored = Vec::new();
+        let matches = self
+            .lists
+            .iter()
+            .filter_map(|list| list.check(kind, text))
+            .chain(self.hooks.iter().filter_map(|(name, hook)| {
+                hook(kind, text).map(|found| FilterMatch {
+                    reason: format!("{}: {}", name, found.reason),
+                    ..found
+                })
+            }));
+        for found in matches {
+            if found.actions.contains(&FilterAction::Censor) {
+                censored.extend(found.spans);
+            }
+            actions.extend(found.actions);
+            reasons.push(found.reason);
+        }
+        actions.sort();
+        actions.dedup();
+        FilterOutcome {
+            text: censor(text, &censored),
+            actions,
+            reasons,
+        }
+    }
+}
+
+/// Lets plugins add their own filters, e.g. a spam detector:
+/// `app.add_content_filter("links", |kind, text| ...)`
+pub trait AddContentFilterExt {
+    fn add_content_filter(
+        &mut self,
+        name: &'static str,
+        hook: impl Fn(ContentKind, &str) -> Option<FilterMatch> + Send + Sync + 'static,
+    ) -> &mut Self;
+}
+
+impl AddContentFilterExt for App {
+    fn add_content_filter(
+        &mut self,
+        name: &'static str,
+        hook: impl Fn(ContentKind, &str) -> Option<FilterMatch> + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.init_resource::<ContentFilter>();
+        self.world_mut()
+            .resource_mut::<ContentFilter>()
+            .add_hook(name, Box::new(hook));
+        self
+    }
+}
+
+/// Sent when a player's chat message or name matched a filter; the caller already censored or
+/// dropped the text, this takes care of warning and reporting
+#[derive(Event, Clone, Debug)]
+pub struct ContentFlagged {
+    pub client_id: ClientId,
+    pub kind: ContentKind,
+    pub original: String,
+    pub outcome: FilterOutcome,
+}
+
+// Undo the usual ways of dodging a wordlist: case and digits standing for letters
+fn normalize(c: char) -> char {
+    match c {
+        '0' => 'o',
+        '1' => 'i',
+        '3' => 'e',
+        '4' | '@' => 'a',
+        '5' | '$' => 's',
+        '7' => 't',
+        c => c.to_lowercase().next().unwrap_or(c),
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '@' | '$')
+}
+
+/// Words of a text, as their byte range and normalized spelling
+fn tokens(text: &str) -> Vec<(Range<usize>, String)> {
+    let mut tokens = Vec::new();
+    let mut current: Option<(usize, String)> = None;
+    for (i, c) in text.char_indices() {
+        if is_word_char(c) {
+            current
+                .get_or_insert_with(|| (i, String::new()))
+                .1
+                .push(normalize(c));
+        } else if let Some((start, token)) = current.take() {
+            tokens.push((start..i, token));
+        }
+    }
+    if let Some((start, token)) = current {
+        tokens.push((start..text.len(), token));
+    }
+    tokens
+}
+
+fn censor(text: &str, spans: &[Range<usize>]) -> String {
+    text.char_indices()
+        .map(|(i, c)| {
+            if spans.iter().any(|span| span.contains(&i)) {
+                CENSOR_CHAR
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+fn load_filter_lists(
+    settings: Option<Res<Settings>>,
+    mut filter: ResMut<ContentFilter>,
+    mut errors: EventWriter<ReportError>,
+) {
+    let Some(settings) = settings else {
+        return;
+    };
+    let paths = &settings.server.content_filter.lists;
+    match filter.load_lists(paths) {
+        Ok(()) => info!("Loaded {} content filter list(s)", filter.lists.len()),
+        Err(e) => errors.send(ReportError(e)),
+    }
+}
+
+fn handle_filter_command(
+    mut invoked: EventReader<CommandInvoked>,
+    mut replies: EventWriter<CommandReply>,
+    settings: Option<Res<Settings>>,
+    mut filter: ResMut<ContentFilter>,
+) {
+    for command in invoked.read().filter(|c| c.name == "filter") {
+        let reply = match command.args.str("action").unwrap_or_default() {
+            "reload" => {
+                let paths = settings
+                    .as_ref()
+                    .map(|settings| settings.server.content_filter.lists.clone())
+                    .unwrap_or_default();
+                match filter.load_lists(&paths) {
+                    Ok(()) => format!("Loaded {} content filter list(s)", filter.lists.len()),
+                    Err(e) => format!("Could not reload the filter lists: {}", e),
+                }
+            }
+            "test" => {
+                let text = command.args.str("text").unwrap_or_default();
+                let outcome = filter.check(ContentKind::Chat, text);
+                if outcome.is_clean() {
+                    "No filter matched".to_string()
+                } else {
+                    format!(
+                        "{:?} -> \"{}\" [{}]",
+                        outcome.actions,
+                        outcome.text,
+                        outcome.reasons.join("; ")
+                    )
+                }
+            }
+            other => format!("Unknown action '{}', expected 'reload' or 'test'", other),
+        };
+        replies.send(CommandReply::new(command.source, reply));
+    }
+}
+
+fn act_on_flagged_content(
+    mut flagged: EventReader<ContentFlagged>,
+    mut replies: EventWriter<CommandReply>,
+    permissions: Res<CommandPermissions>,
+    players: Query<&PlayerId>,
+    mut connection_manager: ResMut<ConnectionManager>,
+    mut errors: EventWriter<ReportError>,
+) {
+    for event in flagged.read() {
+        let author = CommandSource::Client(event.client_id);
+        if event.outcome.is_blocked() {
+            let reply = match event.kind {
+                ContentKind::Chat => "Your message was blocked by the server's content filter",
+                ContentKind::Name => "That name is not allowed on this server",
+            };
+            replies.send(CommandReply::new(author, reply));
+        }
+        if event.outcome.has(FilterAction::Warn) {
+            replies.send(CommandReply::new(
+                author,
+                format!("Warning: your {} breaks this server's rules", event.kind),
+            ));
+        }
+        if !event.outcome.has(FilterAction::Report) {
+            continue;
+        }
+        let report = format!(
+            "[filter] {} from player {}: \"{}\" [{}]",
+            event.kind,
+            event.client_id,
+            event.original,
+            event.outcome.reasons.join("; ")
+        );
+        info!("{}", report);
+        let moderators = players.iter().map(PlayerId::client_id).filter(|client_id| {
+            permissions.level(CommandSource::Client(*client_id)) >= PermissionLevel::Moderator
+        });
+        for client_id in moderators {
+            if let Err(e) = connection_manager
+                .send_message::<ChatChannel, _>(client_id, &ChatLine::system(&report))
+            {
+                errors.send(ReportError(GameError::send("ChatLine", e)));
+            }
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use bevy::prelude::*;
+
+use crate::protocol::{PlayerId, PlayerPosition};
+use crate::shared::commands::{
+    ArgKind, CommandInvoked, CommandReply, CommandSource, CommandSpec, RegisterCommandExt,
+};
+use crate::shared::instances::InstanceId;
+use crate::shared::items::{Inventory, ItemKind};
+use crate::shared::recipes::{recipe_for, RecipeCategory, Station, RECIPES};
+use crate::shared::world_generation::{Chunk, Decoration, WorldConfig, WorldGrid, WorldState};
+
+// Maximum distance, in tiles, between a player and the furnace they cook at
+const STATION_REACH: i32 = 2;
+// Most items cooked by a single command
+const MAX_BATCH: i64 = 64;
+
+// Server plugin for recipes: players standing by a furnace cook raw food into meals and craft
+// equipment
+pub struct ServerCookingPlugin;
+
+impl Plugin for ServerCookingPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_command(
+            CommandSpec::new(
+                "cook",
+                "Cook food at a nearby furnace, or list what can be cooked",
+            )
+            .optional_arg("item", ArgKind::Word)
+            .optional_arg("count", ArgKind::Int),
+        )
+        .register_command(
+            CommandSpec::new(
+                "craft",
+                "Craft equipment at a nearby furnace, or list what can be crafted",
+            )
+            .optional_arg("item", ArgKind::Word)
+            .optional_arg("count", ArgKind::Int),
+        )
+        .add_systems(Update, handle_recipe_commands);
+    }
+}
+
+// Recipes followed by each command, and the verb used in replies
+fn command_category(name: &str) -> Option<(RecipeCategory, &'static str)> {
+    match name {
+        "cook" => Some((RecipeCategory::Cooking, "cook")),
+        "craft" => Some((RecipeCategory::Crafting, "craft")),
+        _ => None,
+    }
+}
+
+// Whether a station stands within reach of `position`
+fn station_nearby(
+    station: Station,
+    position: Vec2,
+    world_state: &WorldState,
+    world_config: &WorldConfig,
+    chunks: &Query<&Chunk>,
+) -> bool {
+    let decoration = match station {
+        Station::Furnace => Decoration::Furnace,
+    };
+    let (center_x, center_y) = WorldGrid::world_to_tile(position);
+    (center_y - STATION_REACH..=center_y + STATION_REACH).any(|y| {
+        (center_x - STATION_REACH..=center_x + STATION_REACH).any(|x| {
+            let coord = world_config.grid().tile_to_chunk((x, y));
+            let (local_x, local_y) = world_config.grid().tile_to_local((x, y));
+            world_state
+                .chunks
+                .get(&coord)
+                .and_then(|entity| chunks.get(*entity).ok())
+                .and_then(|chunk| chunk.get_tile(local_x, local_y))
+                .is_some_and(|tile| tile.decoration == decoration)
+        })
+    })
+}
+
+fn handle_recipe_commands(
+    mut invoked: EventReader<CommandInvoked>,
+    mut replies: EventWriter<CommandReply>,
+    world_state: Res<WorldState>,
+    world_config: Res<WorldConfig>,
+    chunks: Query<&Chunk>,
+    // there are no furnaces in dungeon instances
+    mut players: Query<(&PlayerId, &PlayerPosition, &mut Inventory), Without<InstanceId>>,
+) {
+    for command in invoked.read() {
+        let Some((category, verb)) = command_category(&command.name) else {
+            continue;
+        };
+        let CommandSource::Client(client_id) = command.source else {
+            replies.send(CommandReply::new(
+                command.source,
+                format!("Only players can {}", verb),
+            ));
+            continue;
+        };
+        let reply = |text: String| CommandReply::new(command.source, text);
+
+        let Some(name) = command.args.str("item") else {
+            let recipes: Vec<String> = RECIPES
+                .iter()
+                .filter(|recipe| recipe.category == category)
+                .map(|recipe| {
+                    let inputs: Vec<String> = recipe
+                        .inputs
+                        .iter()
+                        .map(|(input, count)| format!("{} {:?}", count, input))
+                        .collect();
+                    format!("{:?} ({})", recipe.output, inputs.join(", "))
+                })
+                .collect();
+            replies.send(reply(format!("You can {}: {}", verb, recipes.join("; "))));
+            continue;
+        };
+        let Some(recipe) = ItemKind::from_name(name)
+            .and_then(recipe_for)
+            .filter(|recipe| recipe.category == category)
+        else {
+            replies.send(reply(format!("You can't {} {}", verb, name)));
+            continue;
+        };
+        let Some((_, position, mut inventory)) = players
+            .iter_mut()
+            .find(|(id, _, _)| id.client_id() == client_id)
+        else {
+            replies.send(reply(format!("You can't {} here", verb)));
+            continue;
+        };
+        let station = recipe.category.station();
+        if !station_nearby(station, position.0, &world_state, &world_config, &chunks) {
+            replies.send(reply(format!("You need to stand by a {:?}", station)));
+            continue;
+        }
+
+        let wanted = command.args.int("count").unwrap_or(1).clamp(1, MAX_BATCH) as u32;
+        let mut cooked = 0;
+        let mut full = false;
+        while cooked < wanted
+            && recipe
+                .inputs
+                .iter()
+                .all(|(input, count)| inventory.count(*input) >= *count)
+        {
+            // never cook what there is no room left for
+            let mut after = inventory.clone();
+            for (input, count) in recipe.inputs {
+                after.remove(*input, *count);
+            }
+            if after.add(recipe.output, 1) > 0 {
+                full = true;
+                break;
+            }
+            *inventory = after;
+            cooked += 1;
+        }
+        let text = if cooked > 0 {
+            format!("You {}ed {} {:?}", verb, cooked, recipe.output)
+        } else if full {
+            "Your inventory is full".to_string()
+        } else {
+            format!(
+                "You don't have what it takes to {} {:?}",
+                verb, recipe.output
+            )
+        };
+        replies.send(reply(text));
+    }
+}
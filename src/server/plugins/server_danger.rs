@@ -0,0 +1,123 @@
+use bevy::prelude::*;
+use bevy::time::common_conditions::on_timer;
+use bevy::utils::Duration;
+use lightyear::prelude::ClientId;
+use std::collections::HashSet;
+
+use crate::protocol::{PlayerId, PlayerPosition};
+use crate::server::plugins::PlayerDiedEvent;
+use crate::shared::danger::{DangerLevel, MAX_DANGER};
+use crate::shared::instances::InstanceId;
+use crate::shared::npc::{Hostile, Npc, Strength};
+
+// Hostiles within this many tiles of a player put them in danger
+const DANGER_RADIUS: f32 = 12.0;
+const DANGER_INTERVAL: Duration = Duration::from_millis(500);
+
+// Server plugin computing how much danger each player is in
+pub struct ServerDangerPlugin;
+
+impl Plugin for ServerDangerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ActiveRaid>()
+            .init_resource::<RaidWitnesses>()
+            .add_event::<RaidSurvivedEvent>()
+            .add_systems(
+                Update,
+                (
+                    add_danger_level,
+                    update_danger_levels.run_if(on_timer(DANGER_INTERVAL)),
+                    track_raid_survivors,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Whether a raid is under way. Systems starting and ending raids set it; everyone in the
+/// overworld is in maximum danger meanwhile.
+#[derive(Resource, Default)]
+pub struct ActiveRaid(pub bool);
+
+/// Sent when a raid ends, with the players who survived it
+#[derive(Event, Debug, Clone)]
+pub struct RaidSurvivedEvent {
+    pub survivors: Vec<ClientId>,
+}
+
+// Players in the overworld when the current raid started, who haven't died since
+#[derive(Resource, Default)]
+struct RaidWitnesses(Option<HashSet<ClientId>>);
+
+fn add_danger_level(
+    mut commands: Commands,
+    players: Query<Entity, (Added<PlayerId>, Without<DangerLevel>)>,
+) {
+    for entity in players.iter() {
+        commands.entity(entity).insert(DangerLevel::default());
+    }
+}
+
+// One level per hostile around the player in the same world as them, more for stronger ones
+fn update_danger_levels(
+    raid: Res<ActiveRaid>,
+    hostiles: Query<(&Npc, Option<&InstanceId>, Option<&Strength>), With<Hostile>>,
+    mut players: Query<(&PlayerPosition, &mut DangerLevel, Option<&InstanceId>)>,
+) {
+    for (position, mut danger, instance) in players.iter_mut() {
+        let level = if raid.0 && instance.is_none() {
+            MAX_DANGER
+        } else {
+            let nearby: f32 = hostiles
+                .iter()
+                .filter(|(npc, i, _)| {
+                    *i == instance && npc.position.distance(position.0) <= DANGER_RADIUS
+                })
+                .map(|(_, _, strength)| strength.map_or(1.0, |strength| strength.0))
+                .sum();
+            nearby.round().min(MAX_DANGER as f32) as u8
+        };
+        // only touch the component when it changes, so it's only replicated then
+        danger.set_if_neq(DangerLevel(level));
+    }
+}
+
+// A raid is survived by being in the overworld when it starts, and still alive and connected when
+// it ends
+fn track_raid_survivors(
+    raid: Res<ActiveRaid>,
+    mut witnesses: ResMut<RaidWitnesses>,
+    mut deaths: EventReader<PlayerDiedEvent>,
+    players: Query<(&PlayerId, Option<&InstanceId>)>,
+    mut survived: EventWriter<RaidSurvivedEvent>,
+) {
+    if let Some(alive) = witnesses.0.as_mut() {
+        for death in deaths.read() {
+            alive.remove(&death.client_id);
+        }
+    } else {
+        deaths.clear();
+    }
+    match (raid.0, witnesses.0.is_some()) {
+        (true, false) => {
+            witnesses.0 = Some(
+                players
+                    .iter()
+                    .filter(|(_, instance)| instance.is_none())
+                    .map(|(id, _)| id.client_id())
+                    .collect(),
+            );
+        }
+        (false, true) => {
+            let alive = witnesses.0.take().unwrap_or_default();
+            survived.send(RaidSurvivedEvent {
+                survivors: players
+                    .iter()
+                    .map(|(id, _)| id.client_id())
+                    .filter(|client_id| alive.contains(client_id))
+                    .collect(),
+            });
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,620 @@
+//! Sqlite persistence backend, used instead of flat files when the settings ask for it.
+//!
+//! Reads go straight to the database through their own connection. Writes are queued and
+//! committed in batches by a background thread, so saving the world never blocks a frame; until
+//! a write is committed, reads of the same chunk or profile are answered from the queue. The
+//! database runs in WAL mode, so it can be backed up or queried while the server is running.
+use bevy::prelude::*;
+use crossbeam_channel::{Receiver, Sender};
+use lightyear::prelude::*;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::server::plugins::{ChunkStore, LandClaims, PlayerProfile, ProfileStore};
+use crate::settings_common::{Persistence, Settings};
+use crate::shared::error::{GameError, ReportError};
+use crate::shared::world_generation::{ChunkCoord, SaveWorldEvent};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS chunks (
+        x INTEGER NOT NULL,
+        y INTEGER NOT NULL,
+        data BLOB NOT NULL,
+        PRIMARY KEY (x, y)
+    );
+    CREATE TABLE IF NOT EXISTS graves (
+        x INTEGER NOT NULL,
+        y INTEGER NOT NULL,
+        data BLOB NOT NULL,
+        PRIMARY KEY (x, y)
+    );
+    CREATE TABLE IF NOT EXISTS containers (
+        x INTEGER NOT NULL,
+        y INTEGER NOT NULL,
+        data BLOB NOT NULL,
+        PRIMARY KEY (x, y)
+    );
+    CREATE TABLE IF NOT EXISTS villagers (
+        x INTEGER NOT NULL,
+        y INTEGER NOT NULL,
+        data BLOB NOT NULL,
+        PRIMARY KEY (x, y)
+    );
+    CREATE TABLE IF NOT EXISTS profiles (
+        client_id INTEGER PRIMARY KEY,
+        profile TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS claims (
+        x INTEGER NOT NULL,
+        y INTEGER NOT NULL,
+        owner INTEGER NOT NULL,
+        PRIMARY KEY (x, y)
+    );
+    CREATE TABLE IF NOT EXISTS player_stats (
+        client_id INTEGER NOT NULL,
+        stat TEXT NOT NULL,
+        value INTEGER NOT NULL,
+        PRIMARY KEY (client_id, stat)
+    );
+";
+
+// Server plugin opening the sqlite database selected in the settings, and handing it to the
+// chunk and profile stores
+pub struct ServerDatabasePlugin;
+
+impl Plugin for ServerDatabasePlugin {
+    fn build(&self, app: &mut App) {
+        // before Startup, so that nothing is loaded from the flat files by mistake
+        app.add_systems(PreStartup, (open_database, load_claims).chain())
+            .add_systems(Update, save_claims.run_if(resource_exists::<Database>))
+            .add_systems(Last, flush_on_exit.run_if(resource_exists::<Database>));
+    }
+}
+
+enum Write {
+    Chunk {
+        coord: ChunkCoord,
+        data: Arc<Vec<u8>>,
+    },
+    Graves {
+        coord: ChunkCoord,
+        data: Arc<Vec<u8>>,
+    },
+    Containers {
+        coord: ChunkCoord,
+        data: Arc<Vec<u8>>,
+    },
+    Villagers {
+        coord: ChunkCoord,
+        data: Arc<Vec<u8>>,
+    },
+    Profile {
+        client_id: u64,
+        profile: Arc<String>,
+        stats: Vec<(String, i64)>,
+    },
+    Claims(Vec<(ChunkCoord, u64)>),
+    // Answered once every write queued before it is committed
+    Flush(Sender<()>),
+    // Checkpoint the WAL into the database file and answer, then commit nothing more until
+    // `resume` is dropped
+    Pause {
+        checkpointed: Sender<Result<(), String>>,
+        resume: Receiver<()>,
+    },
+}
+
+// Writes queued but not committed yet, newest per key
+#[derive(Default)]
+struct Pending {
+    chunks: HashMap<ChunkCoord, Arc<Vec<u8>>>,
+    graves: HashMap<ChunkCoord, Arc<Vec<u8>>>,
+    containers: HashMap<ChunkCoord, Arc<Vec<u8>>>,
+    villagers: HashMap<ChunkCoord, Arc<Vec<u8>>>,
+    profiles: HashMap<u64, Arc<String>>,
+}
+
+/// Holds back the database's commits while alive, see [`Database::pause`]
+pub struct WriterPause {
+    _resume: Sender<()>,
+}
+
+/// Handle to the database. Cheap to clone, so it can be handed to background tasks.
+#[derive(Resource, Clone)]
+pub struct Database {
+    path: String,
+    reader: Arc<Mutex<Connection>>,
+    writes: Sender<Write>,
+    pending: Arc<Mutex<Pending>>,
+}
+
+impl fmt::Debug for Database {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Database")
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+fn database_error(e: rusqlite::Error) -> GameError {
+    GameError::Database(e.to_string())
+}
+
+impl Database {
+    pub fn open(path: &str) -> Result<Self, GameError> {
+        if let Some(dir) = Path::new(path).parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let writer = Connection::open(path).map_err(database_error)?;
+        // WAL lets the reader and outside tools read while the writer commits
+        writer
+            .pragma_update(None, "journal_mode", "WAL")
+            .map_err(database_error)?;
+        writer
+            .pragma_update(None, "synchronous", "NORMAL")
+            .map_err(database_error)?;
+        writer.execute_batch(SCHEMA).map_err(database_error)?;
+        let reader = Connection::open(path).map_err(database_error)?;
+
+        let (writes, queue) = crossbeam_channel::unbounded();
+        let pending = Arc::new(Mutex::new(Pending::default()));
+        let writer_pending = pending.clone();
+        thread::Builder::new()
+            .name("database-writer".to_string())
+            .spawn(move || run_writer(writer, queue, writer_pending))?;
+        Ok(Self {
+            path: path.to_string(),
+            reader: Arc::new(Mutex::new(reader)),
+            writes,
+            pending,
+        })
+    }
+
+    fn pending(&self) -> std::sync::MutexGuard<'_, Pending> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn reader(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.reader.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn queue(&self, write: Write) -> Result<(), GameError> {
+        self.writes
+            .send(write)
+            .map_err(|_| GameError::Database("the writer thread stopped".to_string()))
+    }
+
+    pub fn contains_chunk(&self, coord: ChunkCoord) -> Result<bool, GameError> {
+        if self.pending().chunks.contains_key(&coord) {
+            return Ok(true);
+        }
+        self.reader()
+            .query_row(
+                "SELECT 1 FROM chunks WHERE x = ?1 AND y = ?2",
+                params![coord.x, coord.y],
+                |_| Ok(()),
+            )
+            .optional()
+            .map(|row| row.is_some())
+            .map_err(database_error)
+    }
+
+    pub fn load_chunk(&self, coord: ChunkCoord) -> Result<Option<Vec<u8>>, GameError> {
+        if let Some(data) = self.pending().chunks.get(&coord) {
+            return Ok(Some(data.to_vec()));
+        }
+        self.reader()
+            .query_row(
+                "SELECT data FROM chunks WHERE x = ?1 AND y = ?2",
+                params![coord.x, coord.y],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(database_error)
+    }
+
+    pub fn save_chunk(&self, coord: ChunkCoord, data: Vec<u8>) -> Result<(), GameError> {
+        let data = Arc::new(data);
+        self.pending().chunks.insert(coord, data.clone());
+        self.queue(Write::Chunk { coord, data })
+    }
+
+    pub fn load_graves(&self, coord: ChunkCoord) -> Result<Option<Vec<u8>>, GameError> {
+        if let Some(data) = self.pending().graves.get(&coord) {
+            return Ok(Some(data.to_vec()));
+        }
+        self.reader()
+            .query_row(
+                "SELECT data FROM graves WHERE x = ?1 AND y = ?2",
+                params![coord.x, coord.y],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(database_error)
+    }
+
+    pub fn save_graves(&self, coord: ChunkCoord, data: Vec<u8>) -> Result<(), GameError> {
+        let data = Arc::new(data);
+        self.pending().graves.insert(coord, data.clone());
+        self.queue(Write::Graves { coord, data })
+    }
+
+    pub fn load_containers(&self, coord: ChunkCoord) -> Result<Option<Vec<u8>>, GameError> {
+        if let Some(data) = self.pending().containers.get(&coord) {
+            return Ok(Some(data.to_vec()));
+        }
+        self.reader()
+            .query_row(
+                "SELECT data FROM containers WHERE x = ?1 AND y = ?2",
+                params![coord.x, coord.y],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(database_error)
+    }
+
+    pub fn save_containers(&self, coord: ChunkCoord, data: Vec<u8>) -> Result<(), GameError> {
+        let data = Arc::new(data);
+        self.pending().containers.insert(coord, data.clone());
+        self.queue(Write::Containers { coord, data })
+    }
+
+    pub fn load_villagers(&self, coord: ChunkCoord) -> Result<Option<Vec<u8>>, GameError> {
+        if let Some(data) = self.pending().villagers.get(&coord) {
+            return Ok(Some(data.to_vec()));
+        }
+        self.reader()
+            .query_row(
+                "SELECT data FROM villagers WHERE x = ?1 AND y = ?2",
+                params![coord.x, coord.y],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(database_error)
+    }
+
+    pub fn save_villagers(&self, coord: ChunkCoord, data: Vec<u8>) -> Result<(), GameError> {
+        let data = Arc::new(data);
+        self.pending().villagers.insert(coord, data.clone());
+        self.queue(Write::Villagers { coord, data })
+    }
+
+    pub fn load_profile(&self, client_id: ClientId) -> Result<Option<String>, GameError> {
+        let client_id = client_id.to_bits();
+        if let Some(profile) = self.pending().profiles.get(&client_id) {
+            return Ok(Some(profile.to_string()));
+        }
+        self.reader()
+            .query_row(
+                "SELECT profile FROM profiles WHERE client_id = ?1",
+                params![client_id as i64],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(database_error)
+    }
+
+    /// Save a profile in its serialized form, along with the stats extracted from it
+    pub fn save_profile(
+        &self,
+        client_id: ClientId,
+        text: String,
+        profile: &PlayerProfile,
+    ) -> Result<(), GameError> {
+        let client_id = client_id.to_bits();
+        let text = Arc::new(text);
+        self.pending().profiles.insert(client_id, text.clone());
+        self.queue(Write::Profile {
+            client_id,
+            profile: text,
+            stats: player_stats(profile),
+        })
+    }
+
+    pub fn load_claims(&self) -> Result<Vec<(ChunkCoord, u64)>, GameError> {
+        let reader = self.reader();
+        let mut statement = reader
+            .prepare("SELECT x, y, owner FROM claims")
+            .map_err(database_error)?;
+        let claims = statement
+            .query_map([], |row| {
+                Ok((
+                    ChunkCoord {
+                        x: row.get(0)?,
+                        y: row.get(1)?,
+                    },
+                    row.get::<_, i64>(2)? as u64,
+                ))
+            })
+            .map_err(database_error)?
+            .collect::<Result<_, _>>()
+            .map_err(database_error)?;
+        Ok(claims)
+    }
+
+    /// Replace every claim with the given ones
+    pub fn save_claims(&self, claims: Vec<(ChunkCoord, u64)>) -> Result<(), GameError> {
+        self.queue(Write::Claims(claims))
+    }
+
+    /// Wait until everything saved so far is committed
+    pub fn flush(&self) -> Result<(), GameError> {
+        let (done, wait) = crossbeam_channel::bounded(1);
+        self.queue(Write::Flush(done))?;
+        wait.recv()
+            .map_err(|_| GameError::Database("the writer thread stopped".to_string()))
+    }
+
+    /// Move everything committed so far from the WAL into the database file, and keep the file
+    /// as it is until the returned pause is dropped, e.g. while it's copied. Writes saved
+    /// meanwhile stay queued.
+    pub fn pause(&self) -> Result<WriterPause, GameError> {
+        let (checkpointed, wait) = crossbeam_channel::bounded(1);
+        let (resume, resumed) = crossbeam_channel::bounded(0);
+        self.queue(Write::Pause {
+            checkpointed,
+            resume: resumed,
+        })?;
+        wait.recv()
+            .map_err(|_| GameError::Database("the writer thread stopped".to_string()))?
+            .map_err(GameError::Database)?;
+        Ok(WriterPause { _resume: resume })
+    }
+}
+
+// The stats kept in their own table, so that they can be queried without parsing profiles
+fn player_stats(profile: &PlayerProfile) -> Vec<(String, i64)> {
+    let achievements = &profile.achievements;
+    let mut stats = vec![
+        (
+            "biomes_visited".to_string(),
+            achievements.biomes.len() as i64,
+        ),
+        (
+            "raids_survived".to_string(),
+            achievements.raids_survived as i64,
+        ),
+        (
+            "achievements_unlocked".to_string(),
+            achievements.unlocked.len() as i64,
+        ),
+    ];
+    stats.extend(achievements.harvested.iter().map(|(resource, count)| {
+        (
+            format!("harvested_{:?}", resource).to_lowercase(),
+            *count as i64,
+        )
+    }));
+    stats
+}
+
+// Commit queued writes, all the writes available at once going in the same transaction
+fn run_writer(mut connection: Connection, queue: Receiver<Write>, pending: Arc<Mutex<Pending>>) {
+    while let Ok(first) = queue.recv() {
+        let batch: Vec<Write> = std::iter::once(first).chain(queue.try_iter()).collect();
+        let mut flushed = Vec::new();
+        if let Err(e) = commit(&mut connection, &batch, &mut flushed) {
+            error!("Failed to write to the database: {}", e);
+        }
+        // drop what was committed from the pending writes, unless it was saved again since
+        let mut pending = pending.lock().unwrap_or_else(|e| e.into_inner());
+        for write in &batch {
+            match write {
+                Write::Chunk { coord, data } => {
+                    if pending
+                        .chunks
+                        .get(coord)
+                        .is_some_and(|queued| Arc::ptr_eq(queued, data))
+                    {
+                        pending.chunks.remove(coord);
+                    }
+                }
+                Write::Graves { coord, data } => {
+                    if pending
+                        .graves
+                        .get(coord)
+                        .is_some_and(|queued| Arc::ptr_eq(queued, data))
+                    {
+                        pending.graves.remove(coord);
+                    }
+                }
+                Write::Containers { coord, data } => {
+                    if pending
+                        .containers
+                        .get(coord)
+                        .is_some_and(|queued| Arc::ptr_eq(queued, data))
+                    {
+                        pending.containers.remove(coord);
+                    }
+                }
+                Write::Villagers { coord, data } => {
+                    if pending
+                        .villagers
+                        .get(coord)
+                        .is_some_and(|queued| Arc::ptr_eq(queued, data))
+                    {
+                        pending.villagers.remove(coord);
+                    }
+                }
+                Write::Profile {
+                    client_id, profile, ..
+                } => {
+                    if pending
+                        .profiles
+                        .get(client_id)
+                        .is_some_and(|queued| Arc::ptr_eq(queued, profile))
+                    {
+                        pending.profiles.remove(client_id);
+                    }
+                }
+                Write::Claims(_) | Write::Flush(_) | Write::Pause { .. } => {}
+            }
+        }
+        drop(pending);
+        for done in flushed {
+            let _ = done.send(());
+        }
+        for write in &batch {
+            if let Write::Pause {
+                checkpointed,
+                resume,
+            } = write
+            {
+                let _ = checkpointed.send(checkpoint(&connection));
+                // only ever answered by dropping the sender
+                let _ = resume.recv();
+            }
+        }
+    }
+}
+
+// Copy the WAL into the database file and empty it. Fails if a reader kept it from finishing.
+fn checkpoint(connection: &Connection) -> Result<(), String> {
+    let busy: i64 = connection
+        .query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    if busy != 0 {
+        return Err("the database is still being read".to_string());
+    }
+    Ok(())
+}
+
+fn commit(
+    connection: &mut Connection,
+    batch: &[Write],
+    flushed: &mut Vec<Sender<()>>,
+) -> rusqlite::Result<()> {
+    let transaction = connection.transaction()?;
+    for write in batch {
+        match write {
+            Write::Chunk { coord, data } => {
+                transaction.execute(
+                    "INSERT OR REPLACE INTO chunks (x, y, data) VALUES (?1, ?2, ?3)",
+                    params![coord.x, coord.y, data.as_slice()],
+                )?;
+            }
+            Write::Graves { coord, data } => {
+                transaction.execute(
+                    "INSERT OR REPLACE INTO graves (x, y, data) VALUES (?1, ?2, ?3)",
+                    params![coord.x, coord.y, data.as_slice()],
+                )?;
+            }
+            Write::Containers { coord, data } => {
+                transaction.execute(
+                    "INSERT OR REPLACE INTO containers (x, y, data) VALUES (?1, ?2, ?3)",
+                    params![coord.x, coord.y, data.as_slice()],
+                )?;
+            }
+            Write::Villagers { coord, data } => {
+                transaction.execute(
+                    "INSERT OR REPLACE INTO villagers (x, y, data) VALUES (?1, ?2, ?3)",
+                    params![coord.x, coord.y, data.as_slice()],
+                )?;
+            }
+            Write::Profile {
+                client_id,
+                profile,
+                stats,
+            } => {
+                let client_id = *client_id as i64;
+                transaction.execute(
+                    "INSERT OR REPLACE INTO profiles (client_id, profile) VALUES (?1, ?2)",
+                    params![client_id, profile.as_str()],
+                )?;
+                for (stat, value) in stats {
+                    transaction.execute(
+                        "INSERT OR REPLACE INTO player_stats (client_id, stat, value) VALUES (?1, ?2, ?3)",
+                        params![client_id, stat, value],
+                    )?;
+                }
+            }
+            Write::Claims(claims) => {
+                transaction.execute("DELETE FROM claims", [])?;
+                for (coord, owner) in claims {
+                    transaction.execute(
+                        "INSERT INTO claims (x, y, owner) VALUES (?1, ?2, ?3)",
+                        params![coord.x, coord.y, *owner as i64],
+                    )?;
+                }
+            }
+            Write::Flush(done) => flushed.push(done.clone()),
+            Write::Pause { .. } => {}
+        }
+    }
+    transaction.commit()
+}
+
+pub fn open_database(
+    mut commands: Commands,
+    settings: Option<Res<Settings>>,
+    mut chunk_store: ResMut<ChunkStore>,
+    mut profile_store: ResMut<ProfileStore>,
+) {
+    let Some(Persistence::Sqlite { path }) =
+        settings.map(|settings| settings.server.persistence.clone())
+    else {
+        return;
+    };
+    // saving to files behind the admin's back would be worse than not starting
+    let database = match Database::open(&path) {
+        Ok(database) => database,
+        Err(e) => panic!("Can't open the database {}: {}", path, e),
+    };
+    info!("Saving the world to the database {}", path);
+    chunk_store.use_database(database.clone());
+    profile_store.use_database(database.clone());
+    commands.insert_resource(database);
+}
+
+fn load_claims(
+    database: Option<Res<Database>>,
+    mut claims: ResMut<LandClaims>,
+    mut errors: EventWriter<ReportError>,
+) {
+    let Some(database) = database else {
+        return;
+    };
+    match database.load_claims() {
+        Ok(loaded) => {
+            info!("Loaded {} land claim(s)", loaded.len());
+            claims.owners = loaded
+                .into_iter()
+                .map(|(coord, owner)| (coord, ClientId::from_bits(owner)))
+                .collect();
+        }
+        Err(e) => errors.send(ReportError(e)),
+    }
+}
+
+fn save_claims(
+    mut save_events: EventReader<SaveWorldEvent>,
+    database: Res<Database>,
+    claims: Res<LandClaims>,
+    mut errors: EventWriter<ReportError>,
+) {
+    if save_events.read().count() == 0 {
+        return;
+    }
+    let claims = claims
+        .owners
+        .iter()
+        .map(|(coord, owner)| (*coord, owner.to_bits()))
+        .collect();
+    if let Err(e) = database.save_claims(claims) {
+        errors.send(ReportError(e));
+    }
+}
+
+// Don't lose the last saves when the server shuts down
+fn flush_on_exit(mut exits: EventReader<AppExit>, database: Res<Database>) {
+    if exits.read().count() == 0 {
+        return;
+    }
+    if let Err(e) = database.flush() {
+        error!("Failed to flush the database on exit: {}", e);
+    }
+}
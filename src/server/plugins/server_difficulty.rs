@@ -0,0 +1,388 @@
+use bevy::prelude::*;
+use bevy::time::common_conditions::on_timer;
+use bevy::utils::Duration;
+use lightyear::prelude::server::*;
+use lightyear::prelude::*;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::protocol::{ChatChannel, ChatLine, PlayerId, PlayerPosition};
+use crate::server::plugins::{ActiveRaid, NavChangedEvent, PathRequest, PathResult};
+use crate::settings_common::{DifficultyScaling, DifficultySettings, Settings};
+use crate::shared::commands::{
+    CommandInvoked, CommandReply, CommandSource, CommandSpec, RegisterCommandExt,
+};
+use crate::shared::day_night::DAY_LENGTH;
+use crate::shared::difficulty::Difficulty;
+use crate::shared::error::{GameError, ReportError};
+use crate::shared::feature_flags::ServerFeatureFlags;
+use crate::shared::instances::InstanceId;
+use crate::shared::line_of_sight::can_see;
+use crate::shared::movement::LoadedTiles;
+use crate::shared::npc::{Hostile, Npc, Strength};
+use crate::shared::pathfinding::TilePos;
+use crate::shared::regions::RegionCoord;
+use crate::shared::reputation::{Attitude, Faction, Reputation};
+use crate::shared::seasons::CurrentSeason;
+use crate::shared::survival::Spectator;
+use crate::shared::world_generation::{
+    seeded_hash, ChunkCoord, WorldConfig, WorldGrid, WorldState,
+};
+
+const ACTIVITY_INTERVAL: Duration = Duration::from_secs(1);
+// World time a raid lasts
+const RAID_SECS: f64 = 3.0 * 60.0;
+// Distance, in tiles, from their target at which raiders show up
+const RAIDER_DISTANCE: f32 = 10.0;
+// Speed of raiders, in tiles per second
+const RAIDER_SPEED: f32 = 2.0;
+// World time between two paths asked for by the same raider, as their target moves
+const REPATH_SECS: f64 = 2.0;
+
+// Server plugin making the world harder as it ages or as players spend time in it, and sending
+// raids more often as it does
+pub struct ServerDifficultyPlugin;
+
+impl Plugin for ServerDifficultyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WorldDifficulty>()
+            .init_resource::<Raids>()
+            .register_command(CommandSpec::new(
+                "status",
+                "Show the age of the world, its difficulty and when the next raid comes",
+            ))
+            .add_systems(Startup, load_difficulty_settings)
+            .add_systems(
+                Update,
+                (
+                    track_region_activity.run_if(on_timer(ACTIVITY_INTERVAL)),
+                    update_world_age,
+                    schedule_raids,
+                    chase_players,
+                    handle_status_command,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Difficulty of every part of the world
+#[derive(Resource, Default)]
+pub struct WorldDifficulty {
+    settings: DifficultySettings,
+    world_age_days: f64,
+    // Seconds players spent in each region, summed over players
+    activity: HashMap<RegionCoord, f64>,
+}
+
+impl WorldDifficulty {
+    pub fn at(&self, chunk: ChunkCoord) -> Difficulty {
+        let x = match self.settings.scaling {
+            DifficultyScaling::WorldAge => self.world_age_days,
+            DifficultyScaling::RegionActivity => {
+                let seconds = self
+                    .activity
+                    .get(&RegionCoord::of_chunk(chunk))
+                    .copied()
+                    .unwrap_or_default();
+                seconds / 3600.0
+            }
+        };
+        Difficulty::on_curve(&self.settings.curve, x)
+    }
+}
+
+/// Path a raider follows towards the closest player
+#[derive(Component, Default)]
+struct Chase {
+    path: VecDeque<TilePos>,
+    repath_at: f64,
+    // Where the raider last saw a player, starting with the one the raid was sent after
+    last_seen: Option<Vec2>,
+}
+
+// When the next raid comes, and the raid under way, if any
+#[derive(Resource, Default)]
+struct Raids {
+    next_at: Option<f64>,
+    ends_at: Option<f64>,
+    raiders: Vec<Entity>,
+}
+
+fn load_difficulty_settings(
+    settings: Option<Res<Settings>>,
+    mut difficulty: ResMut<WorldDifficulty>,
+) {
+    if let Some(settings) = settings {
+        difficulty.settings = settings.server.difficulty.clone();
+    }
+}
+
+fn track_region_activity(
+    world_config: Res<WorldConfig>,
+    mut difficulty: ResMut<WorldDifficulty>,
+    players: Query<&PlayerPosition, (With<PlayerId>, Without<InstanceId>)>,
+) {
+    let elapsed = ACTIVITY_INTERVAL.as_secs_f64();
+    for position in players.iter() {
+        let tile = WorldGrid::world_to_tile(position.0);
+        let region = RegionCoord::of_chunk(world_config.grid().tile_to_chunk(tile));
+        *difficulty.activity.entry(region).or_default() += elapsed;
+    }
+}
+
+fn update_world_age(world_state: Res<WorldState>, mut difficulty: ResMut<WorldDifficulty>) {
+    let days = world_state.world_time / DAY_LENGTH;
+    if difficulty.world_age_days != days {
+        difficulty.world_age_days = days;
+    }
+}
+
+fn announce(
+    connection_manager: &mut ConnectionManager,
+    errors: &mut EventWriter<ReportError>,
+    text: &str,
+) {
+    info!("{}", text);
+    connection_manager
+        .send_message_to_target::<ChatChannel, ChatLine>(
+            &ChatLine::system(text),
+            NetworkTarget::All,
+        )
+        .unwrap_or_else(|e| {
+            errors.send(ReportError(GameError::send("ChatLine", e)));
+        });
+}
+
+// Start a raid once it's due, sending hostiles after every player in the overworld, and end it
+// after a while. The next raid is due sooner on harder worlds.
+fn schedule_raids(
+    mut commands: Commands,
+    mut raids: ResMut<Raids>,
+    mut active: ResMut<ActiveRaid>,
+    mut connection_manager: ResMut<ConnectionManager>,
+    mut errors: EventWriter<ReportError>,
+    difficulty: Res<WorldDifficulty>,
+    flags: Res<ServerFeatureFlags>,
+    season: Res<CurrentSeason>,
+    world_state: Res<WorldState>,
+    world_config: Res<WorldConfig>,
+    players: Query<(&PlayerId, &PlayerPosition), Without<InstanceId>>,
+) {
+    let now = world_state.world_time;
+    if let Some(ends_at) = raids.ends_at {
+        if now < ends_at {
+            return;
+        }
+        raids.ends_at = None;
+        for entity in std::mem::take(&mut raids.raiders) {
+            if let Some(mut entity) = commands.get_entity(entity) {
+                entity.despawn();
+            }
+        }
+        active.0 = false;
+        announce(&mut connection_manager, &mut errors, "The raid is over");
+    }
+    let interval = difficulty
+        .settings
+        .raid_interval_secs
+        .filter(|_| flags.raids);
+    let Some(interval) = interval else {
+        raids.next_at = None;
+        return;
+    };
+    let chunk_of = |position: &PlayerPosition| {
+        world_config
+            .grid()
+            .tile_to_chunk(WorldGrid::world_to_tile(position.0))
+    };
+    let Some(next_at) = raids.next_at else {
+        // the hardest place someone stands in sets the pace
+        let hardest = players
+            .iter()
+            .map(|(_, position)| difficulty.at(chunk_of(position)))
+            .fold(Difficulty::default(), |a, b| if b > a { b } else { a });
+        raids.next_at = Some(now + hardest.raid_interval(interval));
+        return;
+    };
+    if now < next_at || players.is_empty() {
+        return;
+    }
+
+    raids.next_at = None;
+    raids.ends_at = Some(now + RAID_SECS);
+    active.0 = true;
+    for (player_id, position) in players.iter() {
+        let local = difficulty.at(chunk_of(position));
+        for index in 0..local.raiders(season.0) {
+            let roll = seeded_hash(
+                world_config.seed,
+                (player_id.client_id(), now.to_bits(), index),
+            );
+            let angle = (roll % 360) as f32 * std::f32::consts::PI / 180.0;
+            let offset = Vec2::from_angle(angle) * RAIDER_DISTANCE * WorldGrid::TILE_SIZE;
+            let raider = commands
+                .spawn((
+                    Npc {
+                        spawned_at: now,
+                        position: position.0 + offset,
+                    },
+                    Hostile,
+                    Faction::Bandits,
+                    Strength(local.npc_strength()),
+                    Chase {
+                        last_seen: Some(position.0),
+                        ..default()
+                    },
+                    Replicate {
+                        sync: SyncTarget {
+                            interpolation: NetworkTarget::All,
+                            ..default()
+                        },
+                        ..default()
+                    },
+                ))
+                .id();
+            raids.raiders.push(raider);
+        }
+    }
+    announce(&mut connection_manager, &mut errors, "Raiders are coming!");
+}
+
+// Walk raiders towards the closest player in the overworld bandits aren't friends with, asking
+// for a new path now and then, and right away when walls go up or come down along the way.
+// Raiders only go after players they can see; one losing sight of everyone keeps walking to where
+// it last saw someone.
+fn chase_players(
+    mut commands: Commands,
+    mut nav_changes: EventReader<NavChangedEvent>,
+    time: Res<Time>,
+    world_state: Res<WorldState>,
+    world_config: Res<WorldConfig>,
+    tiles: LoadedTiles,
+    players: Query<
+        (&PlayerPosition, Option<&Reputation>),
+        (With<PlayerId>, Without<InstanceId>, Without<Spectator>),
+    >,
+    mut raiders: Query<(
+        Entity,
+        &mut Npc,
+        &mut Chase,
+        Option<&PathResult>,
+        Has<PathRequest>,
+    )>,
+) {
+    let now = world_state.world_time;
+    let step = RAIDER_SPEED * WorldGrid::TILE_SIZE * time.delta_secs();
+    let changed: HashSet<ChunkCoord> = nav_changes
+        .read()
+        .flat_map(|event| event.chunks.iter().copied())
+        .collect();
+    for (entity, mut npc, mut chase, result, pending) in raiders.iter_mut() {
+        if let Some(PathResult(path)) = result {
+            // the first tile is the one the raider stands on
+            chase.path = path.iter().flatten().skip(1).copied().collect();
+            commands.entity(entity).remove::<PathResult>();
+        }
+        let grid = world_config.grid();
+        if chase
+            .path
+            .iter()
+            .any(|tile| changed.contains(&grid.tile_to_chunk(*tile)))
+        {
+            // stop rather than walk into a new wall until the next path comes
+            chase.path.clear();
+            chase.repath_at = now;
+        }
+        // raiders keep walking the previous path while the next one is computed
+        if now >= chase.repath_at && !pending {
+            chase.repath_at = now + REPATH_SECS;
+            let closest = players
+                .iter()
+                .filter(|(position, reputation)| {
+                    reputation.is_none_or(|reputation| {
+                        reputation.attitude(Faction::Bandits) < Attitude::Friendly
+                    }) && can_see(npc.position, position.0, &tiles)
+                })
+                .map(|(position, _)| position.0)
+                .min_by(|a, b| {
+                    a.distance_squared(npc.position)
+                        .total_cmp(&b.distance_squared(npc.position))
+                });
+            let target = match closest {
+                Some(target) => {
+                    chase.last_seen = Some(target);
+                    Some(target)
+                }
+                // head for the last sighting once, rather than wander back and forth around it
+                None if chase.path.is_empty() => chase.last_seen.take(),
+                None => None,
+            };
+            if let Some(target) = target {
+                commands.entity(entity).insert(PathRequest {
+                    from: WorldGrid::world_to_tile(npc.position),
+                    to: WorldGrid::world_to_tile(target),
+                });
+            }
+        }
+        let Some(next) = chase.path.front().copied() else {
+            continue;
+        };
+        let target = WorldGrid::tile_to_world(next);
+        let position = npc.position.move_towards(target, step);
+        npc.position = position;
+        if position == target {
+            chase.path.pop_front();
+        }
+    }
+}
+
+fn handle_status_command(
+    mut invoked: EventReader<CommandInvoked>,
+    mut replies: EventWriter<CommandReply>,
+    difficulty: Res<WorldDifficulty>,
+    raids: Res<Raids>,
+    world_state: Res<WorldState>,
+    world_config: Res<WorldConfig>,
+    players: Query<(&PlayerId, &PlayerPosition), Without<InstanceId>>,
+) {
+    for command in invoked.read() {
+        if command.name != "status" {
+            continue;
+        }
+        // players get the difficulty where they stand, the console that of the origin
+        let chunk = match command.source {
+            CommandSource::Client(client_id) => players
+                .iter()
+                .find(|(id, _)| id.client_id() == client_id)
+                .map(|(_, position)| {
+                    world_config
+                        .grid()
+                        .tile_to_chunk(WorldGrid::world_to_tile(position.0))
+                }),
+            _ => None,
+        }
+        .unwrap_or(ChunkCoord { x: 0, y: 0 });
+        let local = difficulty.at(chunk);
+        let raid = match (raids.ends_at, raids.next_at) {
+            (Some(_), _) => "A raid is under way".to_string(),
+            (None, Some(next_at)) => format!(
+                "Next raid in {:.0} minutes",
+                ((next_at - world_state.world_time) / 60.0).max(0.0)
+            ),
+            (None, None) => "No raids".to_string(),
+        };
+        replies.send(CommandReply::new(
+            command.source,
+            format!(
+                "World age: {:.1} days. Difficulty: {:.2} ({:?}), hostiles x{:.2}, \
+                 resources x{:.2}. {}",
+                difficulty.world_age_days,
+                local.0,
+                difficulty.settings.scaling,
+                local.npc_strength(),
+                local.resource_richness(),
+                raid
+            ),
+        ));
+    }
+}
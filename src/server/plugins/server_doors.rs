@@ -0,0 +1,76 @@
+use bevy::prelude::*;
+
+use crate::server::plugins::{
+    derive_traversable, CommandPermissions, LandClaims, StructureInteractionEvent,
+    TileModifiedEvent,
+};
+use crate::shared::commands::{CommandReply, CommandSource};
+use crate::shared::world_generation::{Chunk, WorldConfig, WorldState};
+
+// Server plugin opening and closing the doors and gates players built
+pub struct ServerDoorsPlugin;
+
+impl Plugin for ServerDoorsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, toggle_doors);
+    }
+}
+
+// Open or close a door for players who may modify the land it stands on. The modified tile goes
+// to the navigation graph through `TileModifiedEvent`, and to clients with the chunk's next delta.
+fn toggle_doors(
+    mut events: EventReader<StructureInteractionEvent>,
+    mut replies: EventWriter<CommandReply>,
+    mut modifications: EventWriter<TileModifiedEvent>,
+    world_state: Res<WorldState>,
+    world_config: Res<WorldConfig>,
+    claims: Res<LandClaims>,
+    permissions: Res<CommandPermissions>,
+    mut chunks: Query<&mut Chunk>,
+) {
+    for event in events.read() {
+        let reply = |text: &str| CommandReply::new(CommandSource::Client(event.client_id), text);
+        let coord = world_config.grid().tile_to_chunk(event.tile);
+        let Some(mut chunk) = world_state
+            .chunks
+            .get(&coord)
+            .and_then(|entity| chunks.get_mut(*entity).ok())
+        else {
+            continue;
+        };
+        let (local_x, local_y) = world_config.grid().tile_to_local(event.tile);
+        if !chunk
+            .get_tile(local_x, local_y)
+            .is_some_and(|tile| tile.decoration.is_openable())
+        {
+            continue;
+        }
+        if !claims.can_modify(coord, event.client_id, &permissions) {
+            replies.send(reply("This door is on land claimed by someone else"));
+            continue;
+        }
+        let Some(tile) = chunk.modify_tile(local_x, local_y, |tile| {
+            tile.decoration = tile.decoration.toggled();
+            tile.traversable = derive_traversable(tile);
+            tile.clone()
+        }) else {
+            continue;
+        };
+        let verb = if tile.decoration.is_closed() {
+            "closed"
+        } else {
+            "opened"
+        };
+        debug!(
+            "{:?} {} the door at {:?}",
+            event.client_id, verb, event.tile
+        );
+
+        modifications.send(TileModifiedEvent {
+            position: event.tile,
+            author: Some(event.client_id),
+            before: (tile.tile_type, tile.resource),
+            after: (tile.tile_type, tile.resource),
+        });
+    }
+}
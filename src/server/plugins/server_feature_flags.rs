@@ -0,0 +1,113 @@
+use bevy::asset::ron;
+use bevy::prelude::*;
+use lightyear::prelude::server::*;
+use std::fs;
+use std::path::Path;
+
+use crate::protocol::{Channel1, PlayerId};
+use crate::settings_common::Settings;
+use crate::shared::commands::{CommandInvoked, CommandReply, CommandSpec, RegisterCommandExt};
+use crate::shared::error::{GameError, ReportError};
+use crate::shared::feature_flags::{Ruleset, ServerFeatureFlags};
+
+// Rules the world was created with
+const RULES_PATH: &str = "world/rules.ron";
+
+// Server plugin applying the rules of the world, and telling clients about them as they connect.
+// A new world takes its rules from the settings and keeps them from then on, so that e.g. a
+// hardcore world can't be turned into a creative one by restarting the server.
+pub struct ServerFeatureFlagsPlugin;
+
+impl Plugin for ServerFeatureFlagsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_command(CommandSpec::new(
+            "rules",
+            "List the gameplay features enabled on this server",
+        ))
+        .add_systems(Startup, load_feature_flags)
+        .add_systems(Update, (send_feature_flags, handle_rules_command));
+    }
+}
+
+fn load_rules(path: &Path) -> Result<Option<ServerFeatureFlags>, GameError> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let text = fs::read_to_string(path)?;
+    ron::from_str(&text)
+        .map(Some)
+        .map_err(|e| GameError::Rules(e.to_string()))
+}
+
+fn save_rules(path: &Path, flags: &ServerFeatureFlags) -> Result<(), GameError> {
+    let text = ron::ser::to_string_pretty(flags, default())
+        .map_err(|e| GameError::Rules(e.to_string()))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    Ok(fs::write(path, text)?)
+}
+
+fn describe(flags: &ServerFeatureFlags) -> String {
+    let ruleset = Ruleset::matching(flags)
+        .map_or_else(|| "Custom".to_string(), |ruleset| format!("{:?}", ruleset));
+    let enabled = flags.enabled();
+    if enabled.is_empty() {
+        format!("{} rules, no optional features", ruleset)
+    } else {
+        format!("{} rules: {}", ruleset, enabled.join(", "))
+    }
+}
+
+fn load_feature_flags(
+    settings: Option<Res<Settings>>,
+    mut flags: ResMut<ServerFeatureFlags>,
+    mut errors: EventWriter<ReportError>,
+) {
+    let path = Path::new(RULES_PATH);
+    match load_rules(path) {
+        Ok(Some(saved)) => *flags = saved,
+        Ok(None) => {
+            if let Some(settings) = settings {
+                *flags = settings
+                    .server
+                    .ruleset
+                    .map_or(settings.server.features, Ruleset::flags);
+            }
+            if let Err(e) = save_rules(path, &flags) {
+                errors.send(ReportError(e));
+            }
+        }
+        // keep the defaults rather than guess, the file can be fixed by hand
+        Err(e) => errors.send(ReportError(e)),
+    }
+    info!("World has {}", describe(&flags));
+}
+
+fn send_feature_flags(
+    flags: Res<ServerFeatureFlags>,
+    mut connection_manager: ResMut<ConnectionManager>,
+    players: Query<&PlayerId, Added<PlayerId>>,
+    mut errors: EventWriter<ReportError>,
+) {
+    for player_id in players.iter() {
+        if let Err(e) =
+            connection_manager.send_message::<Channel1, _>(player_id.client_id(), &*flags)
+        {
+            errors.send(ReportError(GameError::send("ServerFeatureFlags", e)));
+        }
+    }
+}
+
+fn handle_rules_command(
+    mut invoked: EventReader<CommandInvoked>,
+    mut replies: EventWriter<CommandReply>,
+    flags: Res<ServerFeatureFlags>,
+) {
+    for command in invoked.read() {
+        if command.name != "rules" {
+            continue;
+        }
+        replies.send(CommandReply::new(command.source, describe(&flags)));
+    }
+}
@@ -0,0 +1,218 @@
+use bevy::prelude::*;
+use lightyear::prelude::server::*;
+use lightyear::prelude::*;
+use rand::prelude::*;
+
+use crate::protocol::{CastLine, Channel1, FishingEvent, PlayerId, PlayerPosition, ReelIn};
+use crate::server::plugins::spawn_dropped_item;
+use crate::shared::day_night::DayPhase;
+use crate::shared::instances::InstanceId;
+use crate::shared::items::{DroppedItem, Inventory, ItemKind};
+use crate::shared::world_generation::{
+    tile_distance, BiomeType, Chunk, WorldConfig, WorldGrid, WorldState,
+};
+
+// Range of the delay, in seconds, between casting and a bite
+const BITE_DELAY: (f64, f64) = (2.0, 6.0);
+// How long the player has to reel in once a fish bites
+const BITE_WINDOW: f64 = 1.0;
+
+// Server plugin running fishing sessions: cast, wait for a bite, reel in within the window
+pub struct ServerFishingPlugin;
+
+impl Plugin for ServerFishingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (handle_casts, handle_reel_in, tick_fishing_sessions).chain(),
+        );
+    }
+}
+
+/// Ongoing fishing session, stored on the server-side player entity
+#[derive(Component, Debug)]
+pub struct FishingSession {
+    pub tile: (i32, i32),
+    pub biome: BiomeType,
+    pub bite_at: f64, // WorldState::world_time at which the fish bites
+    pub bitten: bool,
+}
+
+/// Weighted loot table for fishing in a biome at a given time of day
+pub fn fishing_loot(biome: BiomeType, phase: DayPhase) -> Vec<(ItemKind, u32)> {
+    let mut loot = match biome {
+        BiomeType::Ocean => vec![(ItemKind::Fish, 80), (ItemKind::Wood, 15)],
+        BiomeType::Tundra => vec![(ItemKind::Fish, 60), (ItemKind::Stone, 40)],
+        BiomeType::Desert => vec![(ItemKind::Fish, 50), (ItemKind::Stone, 50)],
+        _ => vec![(ItemKind::Fish, 85), (ItemKind::Wood, 15)],
+    };
+    match phase {
+        // fish are most active around sunrise and sunset
+        DayPhase::Dawn | DayPhase::Dusk => loot[0].1 *= 2,
+        // and the odd lost treasure only surfaces at night
+        DayPhase::Night => loot.push((ItemKind::Gold, 5)),
+        DayPhase::Day => {}
+    }
+    loot
+}
+
+fn roll_loot(loot: &[(ItemKind, u32)], rng: &mut impl Rng) -> ItemKind {
+    let total: u32 = loot.iter().map(|(_, weight)| weight).sum();
+    let mut roll = rng.random_range(0..total);
+    for (kind, weight) in loot {
+        if roll < *weight {
+            return *kind;
+        }
+        roll -= weight;
+    }
+    loot[0].0
+}
+
+fn send_fishing_event(
+    connection_manager: &mut ConnectionManager,
+    client_id: ClientId,
+    event: FishingEvent,
+) {
+    connection_manager
+        .send_message::<Channel1, _>(client_id, &event)
+        .unwrap_or_else(|e| {
+            error!("Failed to send fishing event: {:?}", e);
+        });
+}
+
+fn handle_casts(
+    mut commands: Commands,
+    mut events: EventReader<MessageEvent<CastLine>>,
+    mut connection_manager: ResMut<ConnectionManager>,
+    world_state: Res<WorldState>,
+    world_config: Res<WorldConfig>,
+    chunks: Query<&Chunk>,
+    players: Query<
+        (Entity, &PlayerId, &PlayerPosition, Option<&FishingSession>),
+        Without<InstanceId>,
+    >,
+) {
+    for event in events.read() {
+        let client_id = event.from();
+        let tile = event.message().tile;
+        let Some((entity, _, position, session)) = players
+            .iter()
+            .find(|(_, id, _, _)| id.client_id() == client_id)
+        else {
+            continue;
+        };
+        if session.is_some() {
+            continue;
+        }
+
+        // only tiles adjacent to the player can be fished
+        let player_tile = WorldGrid::world_to_tile(position.0);
+        if tile_distance(tile, player_tile) > 1 {
+            send_fishing_event(
+                &mut connection_manager,
+                client_id,
+                FishingEvent::Rejected("Cast onto water next to you".to_string()),
+            );
+            continue;
+        }
+        let coord = world_config.grid().tile_to_chunk(tile);
+        let (local_x, local_y) = world_config.grid().tile_to_local(tile);
+        let Some(chunk) = world_state
+            .chunks
+            .get(&coord)
+            .and_then(|e| chunks.get(*e).ok())
+        else {
+            continue;
+        };
+        if !chunk
+            .get_tile(local_x, local_y)
+            .is_some_and(|tile| tile.tile_type.is_water())
+        {
+            send_fishing_event(
+                &mut connection_manager,
+                client_id,
+                FishingEvent::Rejected("You can only fish in water".to_string()),
+            );
+            continue;
+        }
+
+        let delay = rand::rng().random_range(BITE_DELAY.0..BITE_DELAY.1);
+        commands.entity(entity).insert(FishingSession {
+            tile,
+            biome: chunk.biome_type,
+            bite_at: world_state.world_time + delay,
+            bitten: false,
+        });
+        send_fishing_event(&mut connection_manager, client_id, FishingEvent::Cast);
+    }
+}
+
+fn handle_reel_in(
+    mut commands: Commands,
+    mut events: EventReader<MessageEvent<ReelIn>>,
+    mut connection_manager: ResMut<ConnectionManager>,
+    world_state: Res<WorldState>,
+    mut players: Query<(Entity, &PlayerId, &FishingSession, &mut Inventory)>,
+) {
+    for event in events.read() {
+        let client_id = event.from();
+        let Some((entity, _, session, mut inventory)) = players
+            .iter_mut()
+            .find(|(_, id, _, _)| id.client_id() == client_id)
+        else {
+            continue;
+        };
+        commands.entity(entity).remove::<FishingSession>();
+        if !session.bitten {
+            send_fishing_event(&mut connection_manager, client_id, FishingEvent::TooEarly);
+            continue;
+        }
+
+        let loot = fishing_loot(session.biome, DayPhase::at(world_state.world_time));
+        let kind = roll_loot(&loot, &mut rand::rng());
+        let overflow = inventory.add(kind, 1);
+        if overflow > 0 {
+            spawn_dropped_item(
+                &mut commands,
+                DroppedItem {
+                    kind,
+                    count: overflow,
+                    tile: session.tile,
+                    dropped_at: world_state.world_time,
+                },
+            );
+        }
+        send_fishing_event(
+            &mut connection_manager,
+            client_id,
+            FishingEvent::Caught { kind, count: 1 },
+        );
+    }
+}
+
+// Trigger bites and end sessions whose bite window has passed
+fn tick_fishing_sessions(
+    mut commands: Commands,
+    mut connection_manager: ResMut<ConnectionManager>,
+    world_state: Res<WorldState>,
+    mut sessions: Query<(Entity, &PlayerId, &mut FishingSession)>,
+) {
+    let now = world_state.world_time;
+    for (entity, player_id, mut session) in sessions.iter_mut() {
+        if !session.bitten && now >= session.bite_at {
+            session.bitten = true;
+            send_fishing_event(
+                &mut connection_manager,
+                player_id.client_id(),
+                FishingEvent::Bite,
+            );
+        } else if session.bitten && now >= session.bite_at + BITE_WINDOW {
+            commands.entity(entity).remove::<FishingSession>();
+            send_fishing_event(
+                &mut connection_manager,
+                player_id.client_id(),
+                FishingEvent::Escaped,
+            );
+        }
+    }
+}
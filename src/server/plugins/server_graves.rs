@@ -0,0 +1,285 @@
+use bevy::prelude::*;
+use lightyear::prelude::server::*;
+use std::collections::HashSet;
+
+use crate::protocol::{PlayerId, PlayerName};
+use crate::server::plugins::{ChunkStore, GraveInteractionEvent, Parties, PlayerDiedEvent};
+use crate::settings_common::{GraveAccess, Settings};
+use crate::shared::commands::{CommandReply, CommandSource};
+use crate::shared::error::ReportError;
+use crate::shared::graves::{Grave, GraveSites};
+use crate::shared::instances::InstanceId;
+use crate::shared::items::Inventory;
+use crate::shared::world_generation::{
+    Chunk, ChunkCoord, SaveWorldEvent, WorldConfig, WorldGrid, WorldState,
+};
+
+// Server plugin for graves: players who die leave what they carried behind, in a grave saved with
+// its chunk until they come back for it
+pub struct ServerGravesPlugin;
+
+impl Plugin for ServerGravesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GraveChunks>().add_systems(
+            Update,
+            (
+                load_chunk_graves,
+                prune_grave_sites,
+                dig_graves,
+                open_graves,
+                unload_chunk_graves,
+                save_graves,
+            )
+                .chain(),
+        );
+    }
+}
+
+// Overworld chunks whose graves were loaded, and those whose graves changed since they were saved
+#[derive(Resource, Default)]
+struct GraveChunks {
+    loaded: HashSet<ChunkCoord>,
+    changed: HashSet<ChunkCoord>,
+}
+
+fn load_chunk_graves(
+    mut commands: Commands,
+    mut grave_chunks: ResMut<GraveChunks>,
+    store: Res<ChunkStore>,
+    // instances are generated again every time, nobody is buried there
+    chunks: Query<&Chunk, (Added<Chunk>, Without<InstanceId>)>,
+    mut errors: EventWriter<ReportError>,
+) {
+    for chunk in chunks.iter() {
+        if !grave_chunks.loaded.insert(chunk.coord) {
+            continue;
+        }
+        match store.load_graves(chunk.coord) {
+            Ok(graves) => {
+                for grave in graves {
+                    commands.spawn((grave, Replicate::default()));
+                }
+            }
+            Err(e) => errors.send(ReportError(e)),
+        }
+    }
+}
+
+// Put the inventory of players who died in a grave where they died, and add it to their sites
+fn dig_graves(
+    mut commands: Commands,
+    mut deaths: EventReader<PlayerDiedEvent>,
+    mut grave_chunks: ResMut<GraveChunks>,
+    world_config: Res<WorldConfig>,
+    mut players: Query<
+        (
+            &PlayerId,
+            &PlayerName,
+            &mut Inventory,
+            Option<&mut GraveSites>,
+        ),
+        Without<InstanceId>,
+    >,
+    mut graves: Query<&mut Grave>,
+) {
+    for death in deaths.read() {
+        let Some((_, name, mut inventory, sites)) = players
+            .iter_mut()
+            .find(|(id, _, _, _)| id.client_id() == death.client_id)
+        else {
+            continue;
+        };
+        if inventory.slots.is_empty() {
+            continue;
+        }
+        let items = std::mem::take(&mut inventory.slots);
+        let tile = WorldGrid::world_to_tile(death.position);
+        info!(
+            "Player {} left {} stacks in a grave at {:?}",
+            death.client_id,
+            items.len(),
+            tile
+        );
+        // dying twice on the same tile adds to the same grave
+        match graves
+            .iter_mut()
+            .find(|grave| grave.tile == tile && grave.owner == death.client_id)
+        {
+            Some(mut grave) => grave.items.extend(items),
+            None => {
+                commands.spawn((
+                    Grave {
+                        owner: death.client_id,
+                        owner_name: name.0.clone(),
+                        tile,
+                        items,
+                    },
+                    Replicate::default(),
+                ));
+            }
+        }
+        grave_chunks
+            .changed
+            .insert(world_config.grid().tile_to_chunk(tile));
+        if let Some(mut sites) = sites {
+            if !sites.0.contains(&tile) {
+                sites.0.push(tile);
+            }
+        }
+    }
+}
+
+// Hand the items of a grave to whoever opened it, if the settings let them
+fn open_graves(
+    mut commands: Commands,
+    mut events: EventReader<GraveInteractionEvent>,
+    mut replies: EventWriter<CommandReply>,
+    mut grave_chunks: ResMut<GraveChunks>,
+    settings: Option<Res<Settings>>,
+    parties: Res<Parties>,
+    world_config: Res<WorldConfig>,
+    mut players: Query<(&PlayerId, &mut Inventory)>,
+    mut sites: Query<(&PlayerId, &mut GraveSites)>,
+    mut graves: Query<&mut Grave>,
+) {
+    let access = settings.map_or_else(GraveAccess::default, |settings| {
+        settings.server.grave_access
+    });
+    for event in events.read() {
+        let reply = |text: String| CommandReply::new(CommandSource::Client(event.client_id), text);
+        let Ok(mut grave) = graves.get_mut(event.grave) else {
+            continue;
+        };
+        let allowed = match access {
+            GraveAccess::Owner => grave.owner == event.client_id,
+            GraveAccess::Party => {
+                grave.owner == event.client_id || parties.same_party(grave.owner, event.client_id)
+            }
+            GraveAccess::Anyone => true,
+        };
+        if !allowed {
+            replies.send(reply(format!("This is the grave of {}", grave.owner_name)));
+            continue;
+        }
+        let Some((_, mut inventory)) = players
+            .iter_mut()
+            .find(|(id, _)| id.client_id() == event.client_id)
+        else {
+            continue;
+        };
+        let before = grave.items.len();
+        grave.items.retain_mut(|stack| {
+            stack.count = inventory.add(stack.kind, stack.count);
+            stack.count > 0
+        });
+        if grave.items.len() == before {
+            replies.send(reply("Your inventory is full".to_string()));
+            continue;
+        }
+        grave_chunks
+            .changed
+            .insert(world_config.grid().tile_to_chunk(grave.tile));
+        if grave.items.is_empty() {
+            commands.entity(event.grave).despawn();
+            // owners who are away find out when they come back
+            for (player_id, mut sites) in sites.iter_mut() {
+                if player_id.client_id() == grave.owner {
+                    sites.0.retain(|site| *site != grave.tile);
+                }
+            }
+        }
+    }
+}
+
+// Take the graves emptied while their owner was away off their sites, when they come back
+fn prune_grave_sites(
+    grave_chunks: Res<GraveChunks>,
+    world_config: Res<WorldConfig>,
+    store: Res<ChunkStore>,
+    graves: Query<&Grave>,
+    mut players: Query<(&PlayerId, &mut GraveSites), Added<GraveSites>>,
+    mut errors: EventWriter<ReportError>,
+) {
+    for (player_id, mut sites) in players.iter_mut() {
+        let owner = player_id.client_id();
+        let mut kept = Vec::with_capacity(sites.0.len());
+        for tile in sites.0.iter().copied() {
+            let coord = world_config.grid().tile_to_chunk(tile);
+            let is_grave = |grave: &Grave| grave.owner == owner && grave.tile == tile;
+            let exists = if grave_chunks.loaded.contains(&coord) {
+                graves.iter().any(is_grave)
+            } else {
+                match store.load_graves(coord) {
+                    Ok(stored) => stored.iter().any(is_grave),
+                    Err(e) => {
+                        errors.send(ReportError(e));
+                        true
+                    }
+                }
+            };
+            if exists {
+                kept.push(tile);
+            }
+        }
+        if kept.len() != sites.0.len() {
+            sites.0 = kept;
+        }
+    }
+}
+
+// Save the graves of chunks being unloaded, and despawn them with their chunk
+fn unload_chunk_graves(
+    mut commands: Commands,
+    mut grave_chunks: ResMut<GraveChunks>,
+    world_state: Res<WorldState>,
+    world_config: Res<WorldConfig>,
+    store: Res<ChunkStore>,
+    graves: Query<(Entity, &Grave)>,
+    mut errors: EventWriter<ReportError>,
+) {
+    let unloaded: Vec<ChunkCoord> = grave_chunks
+        .loaded
+        .iter()
+        .filter(|coord| !world_state.chunks.contains_key(coord))
+        .copied()
+        .collect();
+    for coord in unloaded {
+        grave_chunks.loaded.remove(&coord);
+        let in_chunk: Vec<(Entity, &Grave)> = graves
+            .iter()
+            .filter(|(_, grave)| world_config.grid().tile_to_chunk(grave.tile) == coord)
+            .collect();
+        if grave_chunks.changed.remove(&coord) {
+            let saved: Vec<Grave> = in_chunk.iter().map(|(_, grave)| (*grave).clone()).collect();
+            if let Err(e) = store.save_graves(coord, &saved) {
+                errors.send(ReportError(e));
+            }
+        }
+        for (entity, _) in in_chunk {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn save_graves(
+    mut events: EventReader<SaveWorldEvent>,
+    mut grave_chunks: ResMut<GraveChunks>,
+    world_config: Res<WorldConfig>,
+    store: Res<ChunkStore>,
+    graves: Query<&Grave>,
+    mut errors: EventWriter<ReportError>,
+) {
+    if events.read().count() == 0 {
+        return;
+    }
+    for coord in std::mem::take(&mut grave_chunks.changed) {
+        let saved: Vec<Grave> = graves
+            .iter()
+            .filter(|grave| world_config.grid().tile_to_chunk(grave.tile) == coord)
+            .cloned()
+            .collect();
+        if let Err(e) = store.save_graves(coord, &saved) {
+            errors.send(ReportError(e));
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use bevy::prelude::*;
+use bevy::time::common_conditions::on_timer;
+use bevy::utils::Duration;
+use lightyear::prelude::server::*;
+use lightyear::prelude::ClientId;
+use std::collections::{HashMap, HashSet};
+
+use crate::protocol::PlayerId;
+use crate::settings_common::{GuardrailSettings, Settings};
+use crate::shared::instances::InstanceId;
+use crate::shared::items::{DroppedItem, MAX_STACK};
+use crate::shared::npc::Npc;
+use crate::shared::villages::Villager;
+use crate::shared::world_generation::{ChunkCoord, WorldConfig};
+
+// How often entity counts are checked against the configured caps
+const GUARDRAIL_INTERVAL: Duration = Duration::from_secs(1);
+
+// Server plugin enforcing caps on replicated entities, so runaway gameplay systems can't melt replication
+pub struct ServerGuardrailsPlugin;
+
+impl Plugin for ServerGuardrailsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Guardrails>()
+            .add_systems(Startup, load_guardrail_settings)
+            .add_systems(
+                Update,
+                (
+                    enforce_dropped_item_cap,
+                    enforce_npc_cap,
+                    enforce_replication_budget,
+                )
+                    .chain()
+                    .run_if(on_timer(GUARDRAIL_INTERVAL)),
+            );
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Guardrails {
+    pub settings: GuardrailSettings,
+    /// Number of dropped item stacks absorbed into a neighbouring stack
+    pub merged: u64,
+    /// Number of entities despawned because a cap was exceeded
+    pub culled: u64,
+}
+
+fn load_guardrail_settings(settings: Option<Res<Settings>>, mut guardrails: ResMut<Guardrails>) {
+    if let Some(settings) = settings {
+        guardrails.settings = settings.server.guardrails.clone();
+    }
+}
+
+fn chebyshev_distance(a: (i32, i32), b: (i32, i32)) -> i32 {
+    (a.0 - b.0).abs().max((a.1 - b.1).abs())
+}
+
+/// Merge same-kind stacks close to each other, up to `MAX_STACK`, then despawn the oldest stacks of
+/// chunks still over the cap
+fn enforce_dropped_item_cap(
+    mut commands: Commands,
+    mut guardrails: ResMut<Guardrails>,
+    world_config: Res<WorldConfig>,
+    mut items: Query<(Entity, &mut DroppedItem)>,
+) {
+    let cap = guardrails.settings.max_dropped_items_per_chunk;
+    let radius = guardrails.settings.merge_radius;
+
+    let mut per_chunk: HashMap<ChunkCoord, Vec<(Entity, DroppedItem)>> = HashMap::new();
+    for (entity, item) in items.iter() {
+        let coord = world_config.grid().tile_to_chunk(item.tile);
+        per_chunk
+            .entry(coord)
+            .or_default()
+            .push((entity, item.clone()));
+    }
+
+    for (coord, mut stacks) in per_chunk {
+        if stacks.len() <= cap {
+            continue;
+        }
+        // oldest first: older stacks absorb newer ones, and are culled first
+        stacks.sort_by(|a, b| a.1.dropped_at.total_cmp(&b.1.dropped_at));
+
+        let mut kept: Vec<(Entity, DroppedItem)> = Vec::with_capacity(stacks.len());
+        for (entity, mut item) in stacks {
+            let target = kept.iter().position(|(_, other)| {
+                other.kind == item.kind
+                    && other.count < MAX_STACK
+                    && chebyshev_distance(other.tile, item.tile) <= radius
+            });
+            let Some(index) = target else {
+                kept.push((entity, item));
+                continue;
+            };
+            // stacks never grow past `MAX_STACK`: the overflow stays as its own stack
+            let stack = &mut kept[index].1;
+            let moved = item.count.min(MAX_STACK - stack.count);
+            stack.count += moved;
+            item.count -= moved;
+            if item.count == 0 {
+                commands.entity(entity).despawn();
+                guardrails.merged += 1;
+            } else {
+                kept.push((entity, item));
+            }
+        }
+
+        let excess = kept.len().saturating_sub(cap);
+        if excess > 0 {
+            warn!(
+                "Chunk {:?} has {} dropped item stacks (cap {}), despawning the {} oldest",
+                coord,
+                kept.len(),
+                cap,
+                excess
+            );
+        }
+        for (index, (entity, merged)) in kept.into_iter().enumerate() {
+            if index < excess {
+                commands.entity(entity).despawn();
+                guardrails.culled += 1;
+            } else if let Ok((_, mut item)) = items.get_mut(entity) {
+                if item.count != merged.count {
+                    item.count = merged.count;
+                }
+            }
+        }
+    }
+}
+
+/// Despawn the oldest NPCs over the cap. Villagers don't count: a village saved short of them
+/// is never populated again, so culling them would lose them for good.
+fn enforce_npc_cap(
+    mut commands: Commands,
+    mut guardrails: ResMut<Guardrails>,
+    npcs: Query<(Entity, &Npc), Without<Villager>>,
+) {
+    let cap = guardrails.settings.max_npcs;
+    let count = npcs.iter().count();
+    if count <= cap {
+        return;
+    }
+    let mut npcs: Vec<(Entity, &Npc)> = npcs.iter().collect();
+    npcs.sort_by(|a, b| a.1.spawned_at.total_cmp(&b.1.spawned_at));
+    warn!(
+        "{} NPCs alive (cap {}), despawning the {} oldest",
+        count,
+        cap,
+        count - cap
+    );
+    for (entity, _) in npcs.into_iter().take(count - cap) {
+        commands.entity(entity).despawn_recursive();
+        guardrails.culled += 1;
+    }
+}
+
+// Whether a replicated entity is sent to a client: targeted at it, and in the same room (the
+// overworld or the instance the player is in)
+fn relevant_to(
+    client_id: ClientId,
+    player_instance: Option<&InstanceId>,
+    replication: &ReplicationTarget,
+    instance: Option<&InstanceId>,
+) -> bool {
+    replication.target.targets(&client_id) && instance == player_instance
+}
+
+/// If a client would receive more replicated entities than allowed, cull the oldest dropped items
+/// sent to it, which are the only entities that can be removed without affecting gameplay state
+fn enforce_replication_budget(
+    mut commands: Commands,
+    mut guardrails: ResMut<Guardrails>,
+    replicated: Query<(Entity, &ReplicationTarget, Option<&InstanceId>)>,
+    players: Query<(&PlayerId, Option<&InstanceId>)>,
+    items: Query<(Entity, &DroppedItem)>,
+) {
+    let cap = guardrails.settings.max_replicated_per_client;
+    // items culled for one client are gone for the others too
+    let mut culled: HashSet<Entity> = HashSet::new();
+    for (player, player_instance) in players.iter() {
+        let client_id = player.client_id();
+        let relevant: Vec<Entity> = replicated
+            .iter()
+            .filter(|(entity, ..)| !culled.contains(entity))
+            .filter(|(_, replication, instance)| {
+                relevant_to(client_id, player_instance, replication, *instance)
+            })
+            .map(|(entity, ..)| entity)
+            .collect();
+        let excess = relevant.len().saturating_sub(cap);
+        if excess == 0 {
+            continue;
+        }
+
+        let mut candidates: Vec<(Entity, &DroppedItem)> = relevant
+            .iter()
+            .filter_map(|entity| items.get(*entity).ok())
+            .collect();
+        candidates.sort_by(|a, b| a.1.dropped_at.total_cmp(&b.1.dropped_at));
+        if candidates.len() < excess {
+            warn!(
+                "Client {:?} is sent {} entities, over the replication budget of {}, and only {} \
+                 are dropped items: the budget can't be met",
+                client_id,
+                relevant.len(),
+                cap,
+                candidates.len()
+            );
+        } else {
+            warn!(
+                "Client {:?} is sent {} entities, over the replication budget of {}, culling the \
+                 {} oldest dropped items it is sent",
+                client_id,
+                relevant.len(),
+                cap,
+                excess
+            );
+        }
+        for (entity, _) in candidates.into_iter().take(excess) {
+            commands.entity(entity).despawn();
+            culled.insert(entity);
+            guardrails.culled += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,53 @@
+//! Guardrails must keep entity counts down without culling what can't come back
+use bevy::ecs::system::RunSystemOnce;
+
+use super::*;
+use crate::shared::villages::TRADES;
+
+fn npc(spawned_at: f64) -> Npc {
+    Npc {
+        spawned_at,
+        position: Vec2::ZERO,
+    }
+}
+
+fn villager(home: (i32, i32)) -> Villager {
+    Villager {
+        name: "Villager".to_string(),
+        village: (0, 0),
+        home,
+        trade: TRADES[0],
+    }
+}
+
+#[test]
+fn raids_over_the_npc_cap_leave_villagers_alone() {
+    let mut world = World::new();
+    world.insert_resource(Guardrails {
+        settings: GuardrailSettings {
+            max_npcs: 2,
+            ..default()
+        },
+        ..default()
+    });
+    // the village was populated long before the raid
+    let villagers: Vec<Entity> = (0..3)
+        .map(|index| world.spawn((npc(0.0), villager((index, 0)))).id())
+        .collect();
+    let raiders: Vec<Entity> = (0..4)
+        .map(|index| world.spawn(npc(100.0 + index as f64)).id())
+        .collect();
+
+    world.run_system_once(enforce_npc_cap).unwrap();
+
+    assert!(villagers
+        .iter()
+        .all(|villager| world.entities().contains(*villager)));
+    // only the oldest raiders over the cap are culled
+    let alive: Vec<bool> = raiders
+        .iter()
+        .map(|raider| world.entities().contains(*raider))
+        .collect();
+    assert_eq!(alive, [false, false, true, true]);
+    assert_eq!(world.resource::<Guardrails>().culled, 2);
+}
@@ -0,0 +1,260 @@
+use bevy::prelude::*;
+use lightyear::prelude::server::*;
+use lightyear::prelude::*;
+use std::fmt;
+
+use crate::protocol::{HarvestRequest, PlayerId, PlayerPosition};
+use crate::server::plugins::{
+    derive_traversable, spawn_dropped_item, SkillActionEvent, SkillCurve, TileModifiedEvent,
+    WorldDifficulty,
+};
+use crate::shared::commands::{CommandReply, CommandSource};
+use crate::shared::day_night::DayPhase;
+use crate::shared::instances::InstanceId;
+use crate::shared::items::{DroppedItem, HeldItem, ItemKind};
+use crate::shared::seasons::CurrentSeason;
+use crate::shared::skills::{Skill, SkillTier, Skills};
+use crate::shared::world_generation::{
+    seeded_hash, Chunk, ResourceType, WorldConfig, WorldGrid, WorldState,
+};
+
+// Maximum distance, in tiles, between a player and the tile they harvest
+pub const HARVEST_REACH: f32 = 2.0;
+// Damage dealt to a resource node by one harvest
+pub const HARVEST_DAMAGE: u8 = 1;
+// One in this many felled trees also drops berries
+const BERRY_CHANCE: u64 = 3;
+
+// Server plugin validating and applying harvest requests
+pub struct ServerHarvestPlugin;
+
+impl Plugin for ServerHarvestPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<HarvestTileEvent>()
+            .add_event::<ResourceHarvestedEvent>()
+            .add_systems(Update, handle_harvest_requests);
+    }
+}
+
+/// Harvest requested through another action than a `HarvestRequest`, e.g. interacting with a tile.
+/// Validated exactly like a request.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct HarvestTileEvent {
+    pub client_id: ClientId,
+    pub tile: (i32, i32),
+}
+
+/// Sent when a player depletes a resource node, with the number of items it dropped
+#[derive(Event, Clone, Copy, Debug)]
+pub struct ResourceHarvestedEvent {
+    pub client_id: ClientId,
+    pub resource: ResourceType,
+    pub count: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HarvestError {
+    UnknownPlayer,
+    OutOfReach,
+    ChunkNotLoaded,
+    NothingToHarvest,
+    // The resource exists but can't be harvested at this time of day
+    OutOfSchedule(ResourceType, DayPhase),
+    // The resource needs a tool the player isn't holding
+    WrongTool(ItemKind),
+}
+
+impl fmt::Display for HarvestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HarvestError::UnknownPlayer => write!(f, "You are not in the world"),
+            HarvestError::OutOfReach => write!(f, "That tile is too far away"),
+            HarvestError::ChunkNotLoaded => write!(f, "That tile is not loaded"),
+            HarvestError::NothingToHarvest => write!(f, "There is nothing to harvest there"),
+            HarvestError::OutOfSchedule(resource, phase) => write!(
+                f,
+                "{:?} can't be harvested during the {:?}",
+                resource, phase
+            ),
+            HarvestError::WrongTool(tool) => write!(f, "You need to hold a {:?}", tool),
+        }
+    }
+}
+
+/// Check that a player standing at `player_position` and holding `held` may harvest `tile` at the
+/// given world time, returning the resource found there
+pub fn validate_harvest(
+    chunk: Option<&Chunk>,
+    tile: (i32, i32),
+    player_position: Vec2,
+    held: Option<ItemKind>,
+    world_time: f64,
+    grid: WorldGrid,
+) -> Result<ResourceType, HarvestError> {
+    let target = WorldGrid::tile_to_world(tile);
+    if player_position.distance(target) > HARVEST_REACH {
+        return Err(HarvestError::OutOfReach);
+    }
+    let chunk = chunk.ok_or(HarvestError::ChunkNotLoaded)?;
+    let (local_x, local_y) = grid.tile_to_local(tile);
+    let resource = chunk
+        .get_tile(local_x, local_y)
+        .map_or(ResourceType::None, |tile| tile.resource);
+    if resource == ResourceType::None {
+        return Err(HarvestError::NothingToHarvest);
+    }
+    let phase = DayPhase::at(world_time);
+    if !resource.is_available(phase) {
+        return Err(HarvestError::OutOfSchedule(resource, phase));
+    }
+    if let Some(tool) = ItemKind::tool_for(resource) {
+        if held != Some(tool) {
+            return Err(HarvestError::WrongTool(tool));
+        }
+    }
+    Ok(resource)
+}
+
+fn handle_harvest_requests(
+    mut commands: Commands,
+    mut events: EventReader<MessageEvent<HarvestRequest>>,
+    mut harvest_events: EventReader<HarvestTileEvent>,
+    mut replies: EventWriter<CommandReply>,
+    mut modifications: EventWriter<TileModifiedEvent>,
+    mut skill_actions: EventWriter<SkillActionEvent>,
+    mut harvested: EventWriter<ResourceHarvestedEvent>,
+    world_state: Res<WorldState>,
+    world_config: Res<WorldConfig>,
+    curve: Res<SkillCurve>,
+    difficulty: Res<WorldDifficulty>,
+    season: Res<CurrentSeason>,
+    mut chunks: Query<&mut Chunk>,
+    // dungeon instances have nothing to harvest
+    players: Query<(&PlayerId, &PlayerPosition, &HeldItem, Option<&Skills>), Without<InstanceId>>,
+) {
+    let requests: Vec<(ClientId, (i32, i32))> = events
+        .read()
+        .map(|event| (event.from(), event.message().tile))
+        .chain(
+            harvest_events
+                .read()
+                .map(|event| (event.client_id, event.tile)),
+        )
+        .collect();
+    for (client_id, tile) in requests {
+        let coord = world_config.grid().tile_to_chunk(tile);
+        let chunk_entity = world_state.chunks.get(&coord).copied();
+
+        let player = players
+            .iter()
+            .find(|(id, _, _, _)| id.client_id() == client_id);
+        let result =
+            player
+                .ok_or(HarvestError::UnknownPlayer)
+                .and_then(|(_, position, held, _)| {
+                    validate_harvest(
+                        chunk_entity.and_then(|e| chunks.get(e).ok()),
+                        tile,
+                        position.0,
+                        held.kind,
+                        world_state.world_time,
+                        world_config.grid(),
+                    )
+                });
+        let resource = match result {
+            Ok(resource) => resource,
+            Err(e) => {
+                debug!("Rejected harvest of {:?} by {:?}: {:?}", tile, client_id, e);
+                replies.send(CommandReply::new(
+                    CommandSource::Client(client_id),
+                    e.to_string(),
+                ));
+                continue;
+            }
+        };
+
+        let skill = Skill::for_resource(resource);
+        let tier = match (skill, player.and_then(|(_, _, _, skills)| skills)) {
+            (Some(skill), Some(skills)) => curve.tier(skills.xp(skill)),
+            _ => SkillTier::default(),
+        };
+        if let Some(skill) = skill {
+            skill_actions.send(SkillActionEvent { client_id, skill });
+        }
+
+        // validation succeeded, so the chunk is loaded
+        let Some(mut chunk) = chunk_entity.and_then(|e| chunks.get_mut(e).ok()) else {
+            continue;
+        };
+        let (local_x, local_y) = world_config.grid().tile_to_local(tile);
+        // every harvest is one hit: the node only yields once it's depleted.
+        // Skilled players hit harder.
+        let damage = (HARVEST_DAMAGE as f32 * tier.speed)
+            .round()
+            .clamp(1.0, u8::MAX as f32) as u8;
+        let Some((before, tile_data, depleted)) =
+            chunk.modify_tile(local_x, local_y, |tile_data| {
+                let before = (tile_data.tile_type, tile_data.resource);
+                tile_data.damage = tile_data.damage.saturating_add(damage);
+                let depleted = tile_data.damage >= resource.max_health();
+                if depleted {
+                    tile_data.resource = ResourceType::None;
+                    tile_data.damage = 0;
+                    tile_data.traversable = derive_traversable(tile_data);
+                }
+                (before, tile_data.clone(), depleted)
+            })
+        else {
+            continue;
+        };
+        modifications.send(TileModifiedEvent {
+            position: tile,
+            author: Some(client_id),
+            before,
+            after: (tile_data.tile_type, tile_data.resource),
+        });
+
+        if !depleted {
+            continue;
+        }
+        // harder places yield less: the fraction left over is a chance of one more item
+        let richness = difficulty.at(coord).resource_richness();
+        let scaled = (1 + tier.extra_yield) as f32 * richness;
+        let roll = seeded_hash(
+            world_config.seed,
+            ("yield", tile, world_state.world_time.to_bits()),
+        );
+        let count =
+            scaled.floor() as u32 + u32::from((roll % 1000) as f32 / 1000.0 < scaled.fract());
+        harvested.send(ResourceHarvestedEvent {
+            client_id,
+            resource,
+            count,
+        });
+        if let Some(kind) = ItemKind::from_resource(resource).filter(|_| count > 0) {
+            spawn_dropped_item(
+                &mut commands,
+                DroppedItem {
+                    kind,
+                    count,
+                    tile,
+                    dropped_at: world_state.world_time,
+                },
+            );
+        }
+        // berries are the crop of the wild: they grow with the seasons, and not at all in winter
+        let berries = seeded_hash(world_config.seed, (tile, world_state.world_time.to_bits()));
+        let count = season.0.crop_yield(1 + (berries / BERRY_CHANCE % 3) as u32);
+        if resource == ResourceType::Tree && berries % BERRY_CHANCE == 0 && count > 0 {
+            spawn_dropped_item(
+                &mut commands,
+                DroppedItem {
+                    kind: ItemKind::Berries,
+                    count,
+                    tile,
+                    dropped_at: world_state.world_time,
+                },
+            );
+        }
+    }
+}
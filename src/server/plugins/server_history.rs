@@ -0,0 +1,263 @@
+use bevy::asset::ron;
+use bevy::prelude::*;
+use bevy::time::common_conditions::on_timer;
+use bevy::utils::Duration;
+use lightyear::prelude::server::*;
+use lightyear::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::protocol::{Channel1, PlayerId, PlayerName, PlayerPosition};
+use crate::server::plugins::{RaidSurvivedEvent, TileModifiedEvent};
+use crate::shared::biome_map::BiomeMap;
+use crate::shared::commands::{
+    ArgKind, CommandInvoked, CommandReply, CommandSpec, RegisterCommandExt,
+};
+use crate::shared::error::{GameError, ReportError};
+use crate::shared::history::{
+    structure_name, HistoryEntry, HistoryRecorded, Milestone, WorldHistory,
+};
+use crate::shared::instances::InstanceId;
+use crate::shared::world_generation::{Chunk, WorldConfig, WorldState};
+
+const HISTORY_PATH: &str = "world/history.ron";
+const EXPLORE_INTERVAL: Duration = Duration::from_secs(1);
+// Entries listed by /history when no count is given
+const DEFAULT_LISTED: i64 = 10;
+
+// Server plugin recording the milestones of the world, saved to disk whenever one is reached
+pub struct ServerHistoryPlugin;
+
+impl Plugin for ServerHistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<History>()
+            .register_command(
+                CommandSpec::new("history", "List the latest milestones of the world")
+                    .optional_arg("count", ArgKind::Int),
+            )
+            .add_systems(Startup, load_history)
+            .add_systems(
+                Update,
+                (
+                    record_biomes.run_if(on_timer(EXPLORE_INTERVAL)),
+                    record_structures,
+                    record_raids,
+                    send_history,
+                    handle_history_command,
+                    save_history,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Milestones of the world, oldest first
+#[derive(Resource, Serialize, Deserialize, Default, Debug)]
+struct History {
+    entries: Vec<HistoryEntry>,
+    #[serde(skip)]
+    dirty: bool,
+}
+
+impl History {
+    fn load(path: &Path) -> Result<Self, GameError> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = fs::read_to_string(path)?;
+        ron::from_str(&text).map_err(|e| GameError::History(e.to_string()))
+    }
+
+    fn save(&self, path: &Path) -> Result<(), GameError> {
+        let text = ron::ser::to_string_pretty(self, default())
+            .map_err(|e| GameError::History(e.to_string()))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // write then rename, so that a crash never leaves a truncated history behind
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, text)?;
+        Ok(fs::rename(tmp, path)?)
+    }
+
+    fn has_reached(&self, milestone: &Milestone) -> bool {
+        self.entries
+            .iter()
+            .any(|entry| match (entry.milestone, milestone) {
+                (Milestone::StructureBuilt(built), Milestone::StructureBuilt(other)) => {
+                    structure_name(built) == structure_name(*other)
+                }
+                (recorded, _) => recorded == *milestone,
+            })
+    }
+
+    // Record a milestone and tell everyone about it
+    fn record(
+        &mut self,
+        connection_manager: &mut ConnectionManager,
+        entry: HistoryEntry,
+    ) -> Result<(), GameError> {
+        info!("History: {}", entry.describe());
+        self.entries.push(entry.clone());
+        self.dirty = true;
+        connection_manager
+            .send_message_to_target::<Channel1, _>(&HistoryRecorded { entry }, NetworkTarget::All)
+            .map_err(|e| GameError::send("HistoryRecorded", e))
+    }
+}
+
+fn load_history(mut history: ResMut<History>, mut errors: EventWriter<ReportError>) {
+    match History::load(Path::new(HISTORY_PATH)) {
+        Ok(loaded) => {
+            info!("Loaded {} history entries", loaded.entries.len());
+            *history = loaded;
+        }
+        Err(e) => errors.send(ReportError(e)),
+    }
+}
+
+fn record_biomes(
+    mut history: ResMut<History>,
+    mut connection_manager: ResMut<ConnectionManager>,
+    biome_map: Res<BiomeMap>,
+    world_state: Res<WorldState>,
+    players: Query<(&PlayerPosition, &PlayerName), (With<PlayerId>, Without<InstanceId>)>,
+    mut errors: EventWriter<ReportError>,
+) {
+    for (position, name) in players.iter() {
+        let milestone = Milestone::BiomeReached(biome_map.biome_at_position(position.0));
+        if history.has_reached(&milestone) {
+            continue;
+        }
+        let entry = HistoryEntry {
+            world_time: world_state.world_time,
+            milestone,
+            players: vec![name.0.clone()],
+        };
+        if let Err(e) = history.record(&mut connection_manager, entry) {
+            errors.send(ReportError(e));
+        }
+    }
+}
+
+// The first structure of each kind a player builds makes history
+fn record_structures(
+    mut modifications: EventReader<TileModifiedEvent>,
+    mut history: ResMut<History>,
+    mut connection_manager: ResMut<ConnectionManager>,
+    world_state: Res<WorldState>,
+    world_config: Res<WorldConfig>,
+    chunks: Query<&Chunk>,
+    players: Query<(&PlayerId, &PlayerName)>,
+    mut errors: EventWriter<ReportError>,
+) {
+    let grid = world_config.grid();
+    for modification in modifications.read() {
+        let Some(author) = modification.author else {
+            continue;
+        };
+        let (local_x, local_y) = grid.tile_to_local(modification.position);
+        let Some(chunk) = world_state
+            .chunks
+            .get(&grid.tile_to_chunk(modification.position))
+            .and_then(|entity| chunks.get(*entity).ok())
+        else {
+            continue;
+        };
+        let Some(decoration) = chunk.get_tile(local_x, local_y).map(|tile| tile.decoration) else {
+            continue;
+        };
+        let milestone = Milestone::StructureBuilt(decoration);
+        if !decoration.is_structure() || history.has_reached(&milestone) {
+            continue;
+        }
+        let Some((_, name)) = players.iter().find(|(id, _)| id.client_id() == author) else {
+            continue;
+        };
+        let entry = HistoryEntry {
+            world_time: world_state.world_time,
+            milestone,
+            players: vec![name.0.clone()],
+        };
+        if let Err(e) = history.record(&mut connection_manager, entry) {
+            errors.send(ReportError(e));
+        }
+    }
+}
+
+fn record_raids(
+    mut survived: EventReader<RaidSurvivedEvent>,
+    mut history: ResMut<History>,
+    mut connection_manager: ResMut<ConnectionManager>,
+    world_state: Res<WorldState>,
+    players: Query<(&PlayerId, &PlayerName)>,
+    mut errors: EventWriter<ReportError>,
+) {
+    for event in survived.read() {
+        let entry = HistoryEntry {
+            world_time: world_state.world_time,
+            milestone: Milestone::RaidSurvived,
+            players: players
+                .iter()
+                .filter(|(id, _)| event.survivors.contains(&id.client_id()))
+                .map(|(_, name)| name.0.clone())
+                .collect(),
+        };
+        if let Err(e) = history.record(&mut connection_manager, entry) {
+            errors.send(ReportError(e));
+        }
+    }
+}
+
+// Players joining get the whole history for their timeline
+fn send_history(
+    history: Res<History>,
+    mut connection_manager: ResMut<ConnectionManager>,
+    players: Query<&PlayerId, Added<PlayerId>>,
+    mut errors: EventWriter<ReportError>,
+) {
+    for player_id in players.iter() {
+        if let Err(e) = connection_manager.send_message::<Channel1, _>(
+            player_id.client_id(),
+            &WorldHistory {
+                entries: history.entries.clone(),
+            },
+        ) {
+            errors.send(ReportError(GameError::send("WorldHistory", e)));
+        }
+    }
+}
+
+fn handle_history_command(
+    mut invoked: EventReader<CommandInvoked>,
+    mut replies: EventWriter<CommandReply>,
+    history: Res<History>,
+) {
+    for command in invoked.read() {
+        if command.name != "history" {
+            continue;
+        }
+        if history.entries.is_empty() {
+            replies.send(CommandReply::new(command.source, "Nothing happened yet"));
+            continue;
+        }
+        let count = command.args.int("count").unwrap_or(DEFAULT_LISTED).max(1) as usize;
+        let skipped = history.entries.len().saturating_sub(count);
+        let lines: Vec<String> = history.entries[skipped..]
+            .iter()
+            .map(|entry| format!("Day {}: {}", entry.day(), entry.describe()))
+            .collect();
+        replies.send(CommandReply::new(command.source, lines.join("\n")));
+    }
+}
+
+fn save_history(mut history: ResMut<History>, mut errors: EventWriter<ReportError>) {
+    if !history.dirty {
+        return;
+    }
+    history.dirty = false;
+    if let Err(e) = history.save(Path::new(HISTORY_PATH)) {
+        errors.send(ReportError(e));
+    }
+}
@@ -0,0 +1,48 @@
+use bevy::prelude::*;
+use lightyear::prelude::server::*;
+
+use crate::protocol::{PlayerId, SelectHotbarSlot};
+use crate::shared::items::{HeldItem, Inventory, HOTBAR_SLOTS};
+
+// Server plugin keeping track of the item each player holds
+pub struct ServerHotbarPlugin;
+
+impl Plugin for ServerHotbarPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (handle_slot_selection, sync_held_items).chain());
+    }
+}
+
+fn handle_slot_selection(
+    mut events: EventReader<MessageEvent<SelectHotbarSlot>>,
+    mut players: Query<(&PlayerId, &Inventory, &mut HeldItem)>,
+) {
+    for event in events.read() {
+        let client_id = event.from();
+        let slot = event.message().slot;
+        if slot >= HOTBAR_SLOTS {
+            debug!("Rejected hotbar slot {} from {:?}", slot, client_id);
+            continue;
+        }
+        let Some((_, inventory, mut held)) = players
+            .iter_mut()
+            .find(|(id, _, _)| id.client_id() == client_id)
+        else {
+            continue;
+        };
+        let selected = HeldItem::from_inventory(slot, inventory);
+        if *held != selected {
+            *held = selected;
+        }
+    }
+}
+
+// The held item follows the inventory: using up or dropping a stack empties the hand
+fn sync_held_items(mut players: Query<(&Inventory, &mut HeldItem), Changed<Inventory>>) {
+    for (inventory, mut held) in players.iter_mut() {
+        let current = HeldItem::from_inventory(held.slot, inventory);
+        if *held != current {
+            *held = current;
+        }
+    }
+}
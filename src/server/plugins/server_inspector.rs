@@ -0,0 +1,170 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts, EguiPlugin};
+
+use crate::server::plugins::{ChunkInterest, Pregen, WarmChunks};
+use crate::shared::world_generation::{build_chunk, Chunk, ChunkCoord, WorldConfig, WorldState};
+
+// Server debug plugin showing the world state in an egui window, with buttons to unload or
+// regenerate a chunk. Only does anything when the app was built with the inspector enabled.
+pub struct ServerInspectorPlugin;
+
+impl Plugin for ServerInspectorPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            return;
+        }
+        app.init_resource::<InspectedChunk>()
+            .add_systems(Update, server_inspector_ui);
+    }
+}
+
+// Chunk selected in the inspector
+#[derive(Resource, Default)]
+struct InspectedChunk(Option<ChunkCoord>);
+
+enum ChunkAction {
+    Unload,
+    Regenerate,
+}
+
+fn server_inspector_ui(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    mut selected: ResMut<InspectedChunk>,
+    mut world_state: ResMut<WorldState>,
+    world_config: Res<WorldConfig>,
+    interest: Res<ChunkInterest>,
+    pregen: Res<Pregen>,
+    warm: Res<WarmChunks>,
+    chunks: Query<&Chunk>,
+) {
+    let Some(ctx) = contexts.try_ctx_mut() else {
+        return;
+    };
+    let mut action = None;
+    egui::Window::new("Server world").show(ctx, |ui| {
+        ui.label(format!("World time: {:.1}s", world_state.world_time));
+        ui.label(format!(
+            "Chunks: {} loaded, {} active, {} warm",
+            world_state.chunks.len(),
+            world_state.active_chunks.len(),
+            warm.len()
+        ));
+
+        ui.collapsing("Loaded chunks", |ui| {
+            let mut coords: Vec<ChunkCoord> = world_state.chunks.keys().copied().collect();
+            coords.sort_by_key(|coord| (coord.y, coord.x));
+            egui::ScrollArea::vertical()
+                .id_salt("loaded_chunks")
+                .max_height(200.0)
+                .show(ui, |ui| {
+                    for coord in coords {
+                        let label = format!(
+                            "({}, {}){}",
+                            coord.x,
+                            coord.y,
+                            if world_state.active_chunks.contains(&coord) {
+                                ""
+                            } else {
+                                " inactive"
+                            }
+                        );
+                        if ui
+                            .selectable_label(selected.0 == Some(coord), label)
+                            .clicked()
+                        {
+                            selected.0 = Some(coord);
+                        }
+                    }
+                });
+        });
+
+        if let Some(coord) = selected.0 {
+            ui.separator();
+            ui.heading(format!("Chunk ({}, {})", coord.x, coord.y));
+            match world_state
+                .chunks
+                .get(&coord)
+                .and_then(|entity| chunks.get(*entity).ok())
+            {
+                Some(chunk) => {
+                    ui.label(format!("Biome: {:?}", chunk.biome_type));
+                    ui.label(format!("Last accessed: {:.1}s", chunk.last_accessed));
+                    if let Some(time) = world_state.generation_time.get(&coord) {
+                        ui.label(format!("Generated at: {:.1}s", time));
+                    }
+                    let watchers: Vec<String> = interest
+                        .clients
+                        .iter()
+                        .filter(|(_, coords)| coords.contains(&coord))
+                        .map(|(client_id, _)| client_id.to_string())
+                        .collect();
+                    ui.label(format!("Requested by: {}", watchers.join(", ")));
+                    ui.horizontal(|ui| {
+                        if ui.button("Unload").clicked() {
+                            action = Some(ChunkAction::Unload);
+                        }
+                        if ui.button("Regenerate").clicked() {
+                            action = Some(ChunkAction::Regenerate);
+                        }
+                    });
+                }
+                None => {
+                    ui.label("Not loaded");
+                }
+            }
+        }
+
+        ui.separator();
+        ui.collapsing("Client interest", |ui| {
+            let mut clients: Vec<_> = interest.clients.iter().collect();
+            clients.sort_by_key(|(client_id, _)| client_id.to_bits());
+            for (client_id, coords) in clients {
+                ui.collapsing(format!("{} ({} chunks)", client_id, coords.len()), |ui| {
+                    let mut coords: Vec<&ChunkCoord> = coords.iter().collect();
+                    coords.sort_by_key(|coord| (coord.y, coord.x));
+                    for coord in coords {
+                        ui.label(format!("({}, {})", coord.x, coord.y));
+                    }
+                });
+            }
+        });
+
+        ui.collapsing("Generation queue", |ui| {
+            ui.label(format!(
+                "Pregen: {}/{} done, {} failed, {} in flight",
+                pregen.done,
+                pregen.total,
+                pregen.failed,
+                pregen.in_flight()
+            ));
+            for coord in pregen.queued().take(50) {
+                ui.label(format!("({}, {})", coord.x, coord.y));
+            }
+        });
+    });
+
+    let (Some(action), Some(coord)) = (action, selected.0) else {
+        return;
+    };
+    let Some(entity) = world_state.chunks.remove(&coord) else {
+        return;
+    };
+    commands.entity(entity).despawn();
+    world_state.active_chunks.remove(&coord);
+    world_state.generation_time.remove(&coord);
+    match action {
+        ChunkAction::Unload => info!("Inspector unloaded chunk {:?}", coord),
+        ChunkAction::Regenerate => {
+            // spawned as a new chunk, so it's sent to the clients like any other
+            let world_time = world_state.world_time;
+            let entity = commands
+                .spawn(build_chunk(&coord, &world_config, world_time))
+                .id();
+            world_state.chunks.insert(coord, entity);
+            world_state.active_chunks.insert(coord);
+            world_state.generation_time.insert(coord, world_time);
+            info!("Inspector regenerated chunk {:?}", coord);
+        }
+    }
+}
@@ -0,0 +1,335 @@
+use bevy::prelude::*;
+use lightyear::prelude::server::*;
+use lightyear::prelude::*;
+use rand::prelude::*;
+use std::collections::HashMap;
+
+use crate::protocol::{Channel1, PlayerId, PlayerPosition};
+use crate::server::plugins::{spawn_dropped_item, DoorInteractionEvent, PlayerAdmitted};
+use crate::shared::error::{GameError, ReportError};
+use crate::shared::instances::{
+    generate_dungeon, DungeonDoor, EnterInstance, InstanceId, LeaveInstance,
+};
+use crate::shared::items::{DroppedItem, ItemKind};
+use crate::shared::npc::{Hostile, Npc};
+use crate::shared::structures::StructureMap;
+use crate::shared::world_generation::{seeded_hash, Chunk, ChunkCoord, WorldConfig, WorldState};
+
+// One in this many overworld chunks has a dungeon entrance
+const ENTRANCE_RARITY: u64 = 12;
+// Replication room of everyone and everything outside of instances
+const OVERWORLD_ROOM: RoomId = RoomId(0);
+// What dungeon loot stacks are made of, and how many items they hold
+const DUNGEON_LOOT: [ItemKind; 4] = [
+    ItemKind::Coal,
+    ItemKind::Copper,
+    ItemKind::Iron,
+    ItemKind::Gold,
+];
+const LOOT_COUNT: (u32, u32) = (1, 5);
+
+// Server plugin for dungeon instances: each visit to a dungeon gets its own copy of it, replicated
+// only to the players inside
+pub struct ServerInstancesPlugin;
+
+impl Plugin for ServerInstancesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Instances>()
+            .add_systems(
+                Update,
+                (
+                    place_entrances,
+                    remove_unloaded_entrances,
+                    use_doors,
+                    close_abandoned_instances,
+                )
+                    .chain(),
+            )
+            // after the commands of Update spawned their replicated entities
+            .add_systems(PostUpdate, assign_rooms);
+    }
+}
+
+/// A running instance: its own partition of the world, and the players inside
+pub struct Instance {
+    pub world: WorldState,
+    // Players inside, with the overworld position they go back to
+    pub players: HashMap<ClientId, Vec2>,
+    // Everything spawned for the instance, despawned with it
+    entities: Vec<Entity>,
+}
+
+#[derive(Resource, Default)]
+pub struct Instances {
+    last_id: u32,
+    instances: HashMap<InstanceId, Instance>,
+    // Overworld chunks that were checked for an entrance, with the entrance if they have one
+    entrances: HashMap<ChunkCoord, Option<Entity>>,
+}
+
+impl Instances {
+    /// The world partition an entity lives in: the overworld's `WorldState`, or its instance's
+    pub fn world_state<'a>(
+        &'a self,
+        instance: Option<InstanceId>,
+        overworld: &'a WorldState,
+    ) -> Option<&'a WorldState> {
+        match instance {
+            None => Some(overworld),
+            Some(id) => self.instances.get(&id).map(|instance| &instance.world),
+        }
+    }
+
+    /// Generate a new instance of the dungeon behind `entrance` and spawn its contents.
+    /// Returns the instance with its chunk and where players appear in it.
+    fn open(
+        &mut self,
+        commands: &mut Commands,
+        entrance: (i32, i32),
+        config: &WorldConfig,
+        world_time: f64,
+    ) -> (InstanceId, Chunk, Vec2) {
+        self.last_id += 1;
+        let id = InstanceId(self.last_id);
+        let layout = generate_dungeon(seeded_hash(config.seed, entrance), config.chunk_size);
+
+        let mut world = WorldState {
+            world_time,
+            ..default()
+        };
+        let chunk = commands.spawn((layout.chunk.clone(), id)).id();
+        world.chunks.insert(layout.chunk.coord, chunk);
+        world.active_chunks.insert(layout.chunk.coord);
+        world.generation_time.insert(layout.chunk.coord, world_time);
+
+        let mut entities = vec![
+            chunk,
+            commands
+                .spawn((
+                    DungeonDoor::Exit { tile: layout.exit },
+                    id,
+                    Replicate::default(),
+                ))
+                .id(),
+        ];
+        // the layout is the same on every visit, the loot isn't
+        let mut rng = rand::rng();
+        for tile in layout.loot {
+            let item = DroppedItem {
+                kind: *DUNGEON_LOOT.choose(&mut rng).unwrap(),
+                count: rng.random_range(LOOT_COUNT.0..=LOOT_COUNT.1),
+                tile,
+                dropped_at: world_time,
+            };
+            let entity = spawn_dropped_item(commands, item);
+            commands.entity(entity).insert(id);
+            entities.push(entity);
+        }
+        for position in layout.npcs {
+            entities.push(
+                commands
+                    .spawn((
+                        Npc {
+                            spawned_at: world_time,
+                            position,
+                        },
+                        // dungeon dwellers don't like visitors
+                        Hostile,
+                        id,
+                        Replicate {
+                            sync: SyncTarget {
+                                interpolation: NetworkTarget::All,
+                                ..default()
+                            },
+                            ..default()
+                        },
+                    ))
+                    .id(),
+            );
+        }
+
+        self.instances.insert(
+            id,
+            Instance {
+                world,
+                players: HashMap::new(),
+                entities,
+            },
+        );
+        (id, layout.chunk, layout.spawn)
+    }
+}
+
+fn instance_room(id: InstanceId) -> RoomId {
+    RoomId(id.0 as u64)
+}
+
+// Give some newly generated overworld chunks a dungeon entrance. Where they go only depends on the
+// seed, so an unloaded chunk gets the same entrance back when it's generated again.
+fn place_entrances(
+    mut commands: Commands,
+    mut instances: ResMut<Instances>,
+    world_config: Res<WorldConfig>,
+    structures: Res<StructureMap>,
+    chunks: Query<&Chunk, (Added<Chunk>, Without<InstanceId>)>,
+) {
+    for chunk in chunks.iter() {
+        if instances.entrances.contains_key(&chunk.coord) {
+            continue;
+        }
+        let tile = entrance_tile(world_config.seed, chunk, &structures);
+        let entrance = tile.map(|tile| {
+            debug!("Placed a dungeon entrance at {:?}", tile);
+            commands
+                .spawn((DungeonDoor::Entrance { tile }, Replicate::default()))
+                .id()
+        });
+        instances.entrances.insert(chunk.coord, entrance);
+    }
+}
+
+/// Tile of the dungeon entrance of an overworld chunk, if it has one: the first dry tile from a
+/// seeded starting point
+pub fn entrance_tile(seed: u32, chunk: &Chunk, structures: &StructureMap) -> Option<(i32, i32)> {
+    let hash = seeded_hash(seed, chunk.coord);
+    if hash % ENTRANCE_RARITY != 0 {
+        return None;
+    }
+    chunk.first_dry_tile((hash / ENTRANCE_RARITY) as usize, structures, None)
+}
+
+fn remove_unloaded_entrances(
+    mut commands: Commands,
+    mut instances: ResMut<Instances>,
+    world_state: Res<WorldState>,
+) {
+    instances.entrances.retain(|coord, entrance| {
+        let loaded = world_state.chunks.contains_key(coord);
+        if let (false, Some(entity)) = (loaded, entrance) {
+            commands.entity(*entity).despawn();
+        }
+        loaded
+    });
+}
+
+// Every admitted client and every replicated entity is in exactly one room: the overworld's or
+// their instance's, so players in an instance only see what is in it
+fn assign_rooms(
+    mut commands: Commands,
+    mut admissions: EventReader<PlayerAdmitted>,
+    mut room_manager: ResMut<RoomManager>,
+    replicated: Query<(Entity, Option<&InstanceId>), Added<ReplicationTarget>>,
+) {
+    for admission in admissions.read() {
+        room_manager.add_client(admission.client_id, OVERWORLD_ROOM);
+    }
+    for (entity, instance) in replicated.iter() {
+        commands
+            .entity(entity)
+            .insert(NetworkRelevanceMode::InterestManagement);
+        let room = instance.map_or(OVERWORLD_ROOM, |id| instance_room(*id));
+        room_manager.add_entity(entity, room);
+    }
+}
+
+// Move players through the doors they use: entrances open a new instance, exits send them back
+// where they came from
+fn use_doors(
+    mut commands: Commands,
+    mut events: EventReader<DoorInteractionEvent>,
+    mut instances: ResMut<Instances>,
+    mut room_manager: ResMut<RoomManager>,
+    mut connection_manager: ResMut<ConnectionManager>,
+    world_state: Res<WorldState>,
+    world_config: Res<WorldConfig>,
+    doors: Query<&DungeonDoor>,
+    mut players: Query<(Entity, &PlayerId, &mut PlayerPosition, Option<&InstanceId>)>,
+    mut errors: EventWriter<ReportError>,
+) {
+    for event in events.read() {
+        let client_id = event.client_id;
+        let Ok(door) = doors.get(event.door) else {
+            continue;
+        };
+        let Some((player, _, mut position, current)) = players
+            .iter_mut()
+            .find(|(_, id, _, _)| id.client_id() == client_id)
+        else {
+            continue;
+        };
+
+        match (door, current) {
+            (DungeonDoor::Entrance { tile }, None) => {
+                let (id, chunk, spawn) =
+                    instances.open(&mut commands, *tile, &world_config, world_state.world_time);
+                if let Some(instance) = instances.instances.get_mut(&id) {
+                    instance.players.insert(client_id, position.0);
+                }
+                info!(
+                    "Client {:?} entered instance {:?} at {:?}",
+                    client_id, id, tile
+                );
+                commands.entity(player).insert(id);
+                position.0 = spawn;
+                room_manager.remove_client(client_id, OVERWORLD_ROOM);
+                room_manager.remove_entity(player, OVERWORLD_ROOM);
+                room_manager.add_client(client_id, instance_room(id));
+                room_manager.add_entity(player, instance_room(id));
+                if let Err(e) = connection_manager
+                    .send_message::<Channel1, _>(client_id, &EnterInstance { chunk })
+                {
+                    errors.send(ReportError(GameError::send("EnterInstance", e)));
+                }
+            }
+            (DungeonDoor::Exit { .. }, Some(&id)) => {
+                let Some(back) = instances
+                    .instances
+                    .get_mut(&id)
+                    .and_then(|instance| instance.players.remove(&client_id))
+                else {
+                    continue;
+                };
+                info!("Client {:?} left instance {:?}", client_id, id);
+                commands.entity(player).remove::<InstanceId>();
+                position.0 = back;
+                room_manager.remove_client(client_id, instance_room(id));
+                room_manager.remove_entity(player, instance_room(id));
+                room_manager.add_client(client_id, OVERWORLD_ROOM);
+                room_manager.add_entity(player, OVERWORLD_ROOM);
+                if let Err(e) =
+                    connection_manager.send_message::<Channel1, _>(client_id, &LeaveInstance)
+                {
+                    errors.send(ReportError(GameError::send("LeaveInstance", e)));
+                }
+            }
+            // entrances inside instances and exits in the overworld don't exist
+            _ => {}
+        }
+    }
+}
+
+// Instances live as long as someone is inside; players who disconnect inside one come back to
+// the overworld, since their profile kept the position they entered from
+fn close_abandoned_instances(
+    mut commands: Commands,
+    mut disconnections: EventReader<DisconnectEvent>,
+    mut instances: ResMut<Instances>,
+) {
+    for disconnection in disconnections.read() {
+        for instance in instances.instances.values_mut() {
+            instance.players.remove(&disconnection.client_id);
+        }
+    }
+    instances.instances.retain(|id, instance| {
+        if !instance.players.is_empty() {
+            return true;
+        }
+        debug!("Closing empty instance {:?}", id);
+        for entity in &instance.entities {
+            if let Some(mut entity) = commands.get_entity(*entity) {
+                entity.despawn();
+            }
+        }
+        false
+    });
+}
@@ -0,0 +1,200 @@
+use bevy::prelude::*;
+use lightyear::prelude::server::*;
+use lightyear::prelude::*;
+
+use crate::protocol::{InteractRequest, PlayerId, PlayerPosition};
+use crate::server::plugins::{HarvestTileEvent, Instances};
+use crate::shared::commands::{CommandReply, CommandSource};
+use crate::shared::graves::Grave;
+use crate::shared::instances::{DungeonDoor, InstanceId};
+use crate::shared::interaction::{nearby_targets, InteractionTarget};
+use crate::shared::items::{DroppedItem, Inventory};
+use crate::shared::npc::Npc;
+use crate::shared::rails::Cart;
+use crate::shared::survival::Spectator;
+use crate::shared::world_generation::{Chunk, WorldConfig, WorldGrid, WorldState};
+
+// Server plugin validating and executing interactions
+pub struct ServerInteractionPlugin;
+
+impl Plugin for ServerInteractionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<NpcInteractionEvent>()
+            .add_event::<DoorInteractionEvent>()
+            .add_event::<GraveInteractionEvent>()
+            .add_event::<StructureInteractionEvent>()
+            .add_event::<CartInteractionEvent>()
+            .add_systems(Update, handle_interactions);
+    }
+}
+
+/// A player interacted with an NPC, for NPC behaviours to react to
+#[derive(Event, Clone, Debug)]
+pub struct NpcInteractionEvent {
+    pub client_id: ClientId,
+    pub npc: Entity,
+}
+
+/// A player used a dungeon door, for the instances plugin to move them
+#[derive(Event, Clone, Debug)]
+pub struct DoorInteractionEvent {
+    pub client_id: ClientId,
+    pub door: Entity,
+}
+
+/// A player opened a grave, for the graves plugin to check they may and hand them its items
+#[derive(Event, Clone, Debug)]
+pub struct GraveInteractionEvent {
+    pub client_id: ClientId,
+    pub grave: Entity,
+}
+
+/// A player used a structure in the overworld: a door or gate, for the doors plugin to check they
+/// may and toggle it, or a container, for the logistics plugin to hand them what it holds
+#[derive(Event, Clone, Debug)]
+pub struct StructureInteractionEvent {
+    pub client_id: ClientId,
+    pub tile: (i32, i32),
+}
+
+/// A player boarded a cart, for the rails plugin to put them on it
+#[derive(Event, Clone, Debug)]
+pub struct CartInteractionEvent {
+    pub client_id: ClientId,
+    pub cart: Entity,
+}
+
+fn handle_interactions(
+    mut commands: Commands,
+    mut events: EventReader<MessageEvent<InteractRequest>>,
+    mut replies: EventWriter<CommandReply>,
+    mut harvests: EventWriter<HarvestTileEvent>,
+    mut npc_interactions: EventWriter<NpcInteractionEvent>,
+    mut door_interactions: EventWriter<DoorInteractionEvent>,
+    mut grave_interactions: EventWriter<GraveInteractionEvent>,
+    mut structure_interactions: EventWriter<StructureInteractionEvent>,
+    mut cart_interactions: EventWriter<CartInteractionEvent>,
+    world_state: Res<WorldState>,
+    world_config: Res<WorldConfig>,
+    instances: Res<Instances>,
+    chunks: Query<&Chunk>,
+    // spectators can only watch
+    mut players: Query<
+        (
+            &PlayerId,
+            &PlayerPosition,
+            &mut Inventory,
+            Option<&InstanceId>,
+        ),
+        Without<Spectator>,
+    >,
+    mut items: Query<(Entity, &mut DroppedItem, Option<&InstanceId>)>,
+    graves: Query<(Entity, &Grave)>,
+    npcs: Query<(Entity, &Npc, Option<&InstanceId>)>,
+    doors: Query<(Entity, &DungeonDoor, Option<&InstanceId>)>,
+    carts: Query<(Entity, &Cart)>,
+) {
+    for event in events.read() {
+        let client_id = event.from();
+        let target = event.message().target;
+        let reply = |text: &str| CommandReply::new(CommandSource::Client(client_id), text);
+        let Some((_, position, mut inventory, instance)) = players
+            .iter_mut()
+            .find(|(id, _, _, _)| id.client_id() == client_id)
+        else {
+            continue;
+        };
+        // players only reach what is in the same instance as them
+        let instance = instance.copied();
+        let Some(world) = instances.world_state(instance, &world_state) else {
+            continue;
+        };
+
+        // the client picked the target from its own view of the world: check it against ours
+        let targets = nearby_targets(
+            position.0,
+            world,
+            &world_config,
+            &chunks,
+            items
+                .iter()
+                .filter(|(_, _, i)| i.copied() == instance)
+                .map(|(_, item, _)| item),
+            // graves only lie in the overworld
+            graves
+                .iter()
+                .filter(|_| instance.is_none())
+                .map(|(_, grave)| grave),
+            npcs.iter()
+                .filter(|(_, _, i)| i.copied() == instance)
+                .map(|(_, npc, _)| npc),
+            doors
+                .iter()
+                .filter(|(_, _, i)| i.copied() == instance)
+                .map(|(_, door, _)| door),
+            // rails are only laid in the overworld
+            carts
+                .iter()
+                .filter(|_| instance.is_none())
+                .map(|(_, cart)| cart),
+        );
+        if !targets.contains(&target) {
+            debug!("Rejected interaction {:?} by {:?}", target, client_id);
+            replies.send(reply("You can't reach that"));
+            continue;
+        }
+
+        match target {
+            InteractionTarget::DroppedItem { tile } => {
+                let Some((entity, mut item, _)) = items
+                    .iter_mut()
+                    .find(|(_, item, i)| item.tile == tile && i.copied() == instance)
+                else {
+                    continue;
+                };
+                let overflow = inventory.add(item.kind, item.count);
+                if overflow == item.count {
+                    replies.send(reply("Your inventory is full"));
+                } else if overflow > 0 {
+                    item.count = overflow;
+                } else {
+                    commands.entity(entity).despawn();
+                }
+            }
+            InteractionTarget::Grave { tile } => {
+                if let Some((grave, _)) = graves.iter().find(|(_, grave)| grave.tile == tile) {
+                    grave_interactions.send(GraveInteractionEvent { client_id, grave });
+                }
+            }
+            InteractionTarget::Npc { tile } => {
+                let npc = npcs.iter().find(|(_, npc, i)| {
+                    WorldGrid::world_to_tile(npc.position) == tile && i.copied() == instance
+                });
+                if let Some((npc, _, _)) = npc {
+                    npc_interactions.send(NpcInteractionEvent { client_id, npc });
+                }
+            }
+            InteractionTarget::Door { tile } => {
+                let door = doors
+                    .iter()
+                    .find(|(_, door, i)| door.tile() == tile && i.copied() == instance);
+                if let Some((door, _, _)) = door {
+                    door_interactions.send(DoorInteractionEvent { client_id, door });
+                }
+            }
+            InteractionTarget::Resource { tile } => {
+                harvests.send(HarvestTileEvent { client_id, tile });
+            }
+            // structures are only built in the overworld
+            InteractionTarget::Structure { tile } if instance.is_none() => {
+                structure_interactions.send(StructureInteractionEvent { client_id, tile });
+            }
+            InteractionTarget::Structure { .. } => {}
+            InteractionTarget::Cart { tile } => {
+                if let Some((cart, _)) = carts.iter().find(|(_, cart)| cart.tile == tile) {
+                    cart_interactions.send(CartInteractionEvent { client_id, cart });
+                }
+            }
+        }
+    }
+}
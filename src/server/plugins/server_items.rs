@@ -0,0 +1,128 @@
+use bevy::prelude::*;
+use lightyear::prelude::server::*;
+use std::collections::HashMap;
+
+use crate::protocol::{PlayerId, PlayerPosition};
+use crate::shared::commands::{
+    ArgKind, CommandInvoked, CommandReply, CommandSource, CommandSpec, PermissionLevel,
+    RegisterCommandExt,
+};
+use crate::shared::instances::InstanceId;
+use crate::shared::items::{DroppedItem, ItemKind, MAX_STACK};
+use crate::shared::world_generation::{WorldGrid, WorldState};
+
+// Server plugin managing dropped items: stacking items that land on the same tile
+pub struct ServerItemsPlugin;
+
+impl Plugin for ServerItemsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_command(
+            CommandSpec::new("drop", "Drop items at your position")
+                .arg("item", ArgKind::Word)
+                .optional_arg("count", ArgKind::Int)
+                .permission(PermissionLevel::Admin),
+        )
+        .add_systems(Update, (handle_drop_command, stack_dropped_items).chain());
+    }
+}
+
+/// Spawn a replicated stack of items
+pub fn spawn_dropped_item(commands: &mut Commands, item: DroppedItem) -> Entity {
+    commands.spawn((item, Replicate::default())).id()
+}
+
+/// Merge newly dropped items into the stack already lying on the same tile, if any.
+/// Stacks never grow past `MAX_STACK`; the overflow stays as its own entity.
+fn stack_dropped_items(
+    mut commands: Commands,
+    // stacks in instances never merge with the overworld's
+    new_items: Query<Entity, (Added<DroppedItem>, Without<InstanceId>)>,
+    mut items: Query<(Entity, &mut DroppedItem), Without<InstanceId>>,
+) {
+    if new_items.is_empty() {
+        return;
+    }
+
+    // Existing stacks that still have room, by (tile, kind)
+    let mut stacks: HashMap<((i32, i32), ItemKind), Entity> = HashMap::new();
+    for (entity, item) in items.iter() {
+        if !new_items.contains(entity) && item.count < MAX_STACK {
+            stacks.insert((item.tile, item.kind), entity);
+        }
+    }
+
+    for entity in new_items.iter() {
+        let Ok((_, new_item)) = items.get(entity) else {
+            continue;
+        };
+        let key = (new_item.tile, new_item.kind);
+        let Some(&stack_entity) = stacks.get(&key) else {
+            // first item on this tile, later items stack onto it
+            stacks.insert(key, entity);
+            continue;
+        };
+        let Ok([(_, mut stack), (_, mut new_item)]) = items.get_many_mut([stack_entity, entity])
+        else {
+            continue;
+        };
+
+        let moved = new_item.count.min(MAX_STACK - stack.count);
+        stack.count += moved;
+        new_item.count -= moved;
+        if stack.count >= MAX_STACK {
+            stacks.remove(&key);
+        }
+        if new_item.count == 0 {
+            commands.entity(entity).despawn();
+        } else {
+            stacks.insert(key, entity);
+        }
+    }
+}
+
+fn handle_drop_command(
+    mut commands: Commands,
+    mut invoked: EventReader<CommandInvoked>,
+    mut replies: EventWriter<CommandReply>,
+    world_state: Res<WorldState>,
+    players: Query<(&PlayerId, &PlayerPosition)>,
+) {
+    for command in invoked.read().filter(|c| c.name == "drop") {
+        let CommandSource::Client(client_id) = command.source else {
+            replies.send(CommandReply::new(
+                command.source,
+                "Only players can drop items",
+            ));
+            continue;
+        };
+        let name = command.args.str("item").unwrap_or_default();
+        let Some(kind) = ItemKind::from_name(name) else {
+            replies.send(CommandReply::new(
+                command.source,
+                format!(
+                    "Unknown item '{}', expected one of {:?}",
+                    name,
+                    ItemKind::ALL
+                ),
+            ));
+            continue;
+        };
+        let Some((_, position)) = players.iter().find(|(id, _)| id.client_id() == client_id) else {
+            continue;
+        };
+        let count = command
+            .args
+            .int("count")
+            .unwrap_or(1)
+            .clamp(1, MAX_STACK as i64) as u32;
+        spawn_dropped_item(
+            &mut commands,
+            DroppedItem {
+                kind,
+                count,
+                tile: WorldGrid::world_to_tile(position.0),
+                dropped_at: world_state.world_time,
+            },
+        );
+    }
+}
@@ -0,0 +1,479 @@
+use bevy::prelude::*;
+use lightyear::prelude::server::*;
+use lightyear::prelude::*;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+
+use crate::protocol::{Channel1, PlayerId, PlayerPosition};
+use crate::server::plugins::{
+    spawn_dropped_item, ChunkStore, CommandPermissions, LandClaims, PowerGrid,
+    StructureInteractionEvent, TileModifiedEvent, WarmChunkTick, WarmChunks,
+};
+use crate::shared::commands::{
+    ArgKind, CommandInvoked, CommandReply, CommandSource, CommandSpec, RegisterCommandExt,
+};
+use crate::shared::error::{GameError, ReportError};
+use crate::shared::instances::InstanceId;
+use crate::shared::items::{DroppedItem, Inventory, ItemKind};
+use crate::shared::logistics::{Container, ContainerKind, ItemMove, ItemsMoved};
+use crate::shared::world_generation::{
+    tile_distance, Chunk, ChunkCoord, Decoration, Facing, SaveWorldEvent, Tile, WorldConfig,
+    WorldGrid, WorldState,
+};
+
+// Items an inserter moves per swing, and the in-game seconds a swing takes
+const ITEMS_PER_SWING: u32 = 1;
+const SWING_SECS: f64 = 2.0;
+// Most swings simulated at once, when a chunk catches up on a long warm tick; the rest is lost
+const MAX_SWINGS: u32 = 64;
+// Maximum distance, in tiles, between a player and the container they store items in
+const STORE_REACH: u32 = 2;
+
+// Server plugin for logistics: inserters take items out of the container behind them and put them
+// in the one in front of them, and furnaces and powered assemblers follow recipes with what they
+// are given. Only loaded and
+// warm chunks are simulated; what containers hold is saved with their chunk.
+pub struct ServerLogisticsPlugin;
+
+impl Plugin for ServerLogisticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Logistics>()
+            .register_command(
+                CommandSpec::new("store", "Put items in a nearby chest, furnace or assembler")
+                    .arg("item", ArgKind::Word)
+                    .optional_arg("count", ArgKind::Int),
+            )
+            .add_systems(
+                Update,
+                (
+                    load_chunk_logistics,
+                    track_structures,
+                    open_containers,
+                    store_items,
+                    run_inserters,
+                    unload_cold_logistics,
+                    save_containers,
+                )
+                    .chain(),
+            );
+    }
+}
+
+// Containers and inserters of a chunk, kept while it is loaded or warm
+#[derive(Default)]
+struct ChunkLogistics {
+    containers: HashMap<(i32, i32), Container>,
+    inserters: Vec<((i32, i32), Facing)>,
+    // In-game seconds the inserters haven't swung for yet
+    owed: f64,
+}
+
+#[derive(Resource, Default)]
+struct Logistics {
+    chunks: HashMap<ChunkCoord, ChunkLogistics>,
+    // Chunks whose containers changed since they were saved
+    changed: HashSet<ChunkCoord>,
+    last_update: f64,
+}
+
+impl Logistics {
+    fn container_mut(&mut self, grid: WorldGrid, tile: (i32, i32)) -> Option<&mut Container> {
+        self.chunks
+            .get_mut(&grid.tile_to_chunk(tile))?
+            .containers
+            .get_mut(&tile)
+    }
+}
+
+// Bring the containers and inserters of a chunk in line with its tiles
+fn sync_tile(chunk: &mut ChunkLogistics, tile: &Tile) -> Option<Container> {
+    chunk
+        .inserters
+        .retain(|(position, _)| *position != tile.position);
+    if let Decoration::Inserter { facing } = tile.decoration {
+        chunk.inserters.push((tile.position, facing));
+    }
+    match ContainerKind::of(tile.decoration) {
+        Some(kind) => {
+            let container = chunk
+                .containers
+                .entry(tile.position)
+                .or_insert_with(|| Container::new(kind, tile.position));
+            // a chest dug away and a furnace built in its place
+            if container.kind != kind {
+                return Some(std::mem::replace(
+                    container,
+                    Container::new(kind, tile.position),
+                ));
+            }
+            None
+        }
+        None => chunk.containers.remove(&tile.position),
+    }
+}
+
+// Leave the items of a container that is gone on its tile
+fn spill(commands: &mut Commands, container: Container, now: f64) {
+    for stack in container.items.slots {
+        spawn_dropped_item(
+            commands,
+            DroppedItem {
+                kind: stack.kind,
+                count: stack.count,
+                tile: container.tile,
+                dropped_at: now,
+            },
+        );
+    }
+}
+
+fn load_chunk_logistics(
+    mut commands: Commands,
+    mut logistics: ResMut<Logistics>,
+    store: Res<ChunkStore>,
+    world_state: Res<WorldState>,
+    // instances are generated again every time, nothing is built there
+    chunks: Query<&Chunk, (Added<Chunk>, Without<InstanceId>)>,
+    mut errors: EventWriter<ReportError>,
+) {
+    for chunk in chunks.iter() {
+        // chunks loaded again while still warm keep what they have in memory
+        let chunk_logistics = match logistics.chunks.entry(chunk.coord) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let containers = match store.load_containers(chunk.coord) {
+                    Ok(containers) => containers,
+                    Err(e) => {
+                        errors.send(ReportError(e));
+                        Vec::new()
+                    }
+                };
+                entry.insert(ChunkLogistics {
+                    containers: containers
+                        .into_iter()
+                        .map(|container| (container.tile, container))
+                        .collect(),
+                    ..default()
+                })
+            }
+        };
+        let mut spilled = false;
+        for tile in chunk.tiles() {
+            if let Some(gone) = sync_tile(chunk_logistics, tile) {
+                spill(&mut commands, gone, world_state.world_time);
+                spilled = true;
+            }
+        }
+        if spilled {
+            logistics.changed.insert(chunk.coord);
+        }
+    }
+}
+
+// Follow chests, furnaces and inserters being built and dug away
+fn track_structures(
+    mut commands: Commands,
+    mut modifications: EventReader<TileModifiedEvent>,
+    mut logistics: ResMut<Logistics>,
+    world_state: Res<WorldState>,
+    world_config: Res<WorldConfig>,
+    chunks: Query<&Chunk>,
+) {
+    let grid = world_config.grid();
+    for modification in modifications.read() {
+        let coord = grid.tile_to_chunk(modification.position);
+        let (local_x, local_y) = grid.tile_to_local(modification.position);
+        let (Some(tile), Some(chunk_logistics)) = (
+            world_state
+                .chunks
+                .get(&coord)
+                .and_then(|entity| chunks.get(*entity).ok())
+                .and_then(|chunk| chunk.get_tile(local_x, local_y)),
+            logistics.chunks.get_mut(&coord),
+        ) else {
+            continue;
+        };
+        let before = chunk_logistics.containers.len();
+        if let Some(removed) = sync_tile(chunk_logistics, tile) {
+            spill(&mut commands, removed, world_state.world_time);
+            logistics.changed.insert(coord);
+        } else if chunk_logistics.containers.len() != before {
+            logistics.changed.insert(coord);
+        }
+    }
+}
+
+// Hand what a container holds to whoever opened it, if they may modify its land
+fn open_containers(
+    mut events: EventReader<StructureInteractionEvent>,
+    mut replies: EventWriter<CommandReply>,
+    mut logistics: ResMut<Logistics>,
+    world_config: Res<WorldConfig>,
+    claims: Res<LandClaims>,
+    permissions: Res<CommandPermissions>,
+    mut players: Query<(&PlayerId, &mut Inventory)>,
+) {
+    let grid = world_config.grid();
+    for event in events.read() {
+        let reply = |text: &str| CommandReply::new(CommandSource::Client(event.client_id), text);
+        let coord = grid.tile_to_chunk(event.tile);
+        let Some(container) = logistics.container_mut(grid, event.tile) else {
+            continue;
+        };
+        if !claims.can_modify(coord, event.client_id, &permissions) {
+            replies.send(reply("This is on land claimed by someone else"));
+            continue;
+        }
+        let Some((_, mut inventory)) = players
+            .iter_mut()
+            .find(|(id, _)| id.client_id() == event.client_id)
+        else {
+            continue;
+        };
+        if container.items.slots.is_empty() {
+            replies.send(reply("There is nothing in there"));
+            continue;
+        }
+        let before = container.items.clone();
+        container.items.slots.retain_mut(|stack| {
+            stack.count = inventory.add(stack.kind, stack.count);
+            stack.count > 0
+        });
+        if container.items == before {
+            replies.send(reply("Your inventory is full"));
+            continue;
+        }
+        logistics.changed.insert(coord);
+    }
+}
+
+fn store_items(
+    mut invoked: EventReader<CommandInvoked>,
+    mut replies: EventWriter<CommandReply>,
+    mut logistics: ResMut<Logistics>,
+    world_config: Res<WorldConfig>,
+    claims: Res<LandClaims>,
+    permissions: Res<CommandPermissions>,
+    mut players: Query<(&PlayerId, &PlayerPosition, &mut Inventory), Without<InstanceId>>,
+) {
+    let grid = world_config.grid();
+    for command in invoked.read().filter(|command| command.name == "store") {
+        let reply = |text: String| CommandReply::new(command.source, text);
+        let CommandSource::Client(client_id) = command.source else {
+            replies.send(reply("Only players can store items".to_string()));
+            continue;
+        };
+        let Some(kind) = command.args.str("item").and_then(ItemKind::from_name) else {
+            replies.send(reply("There is no such item".to_string()));
+            continue;
+        };
+        let Some((_, position, mut inventory)) = players
+            .iter_mut()
+            .find(|(id, _, _)| id.client_id() == client_id)
+        else {
+            replies.send(reply("You can't store items here".to_string()));
+            continue;
+        };
+        let player_tile = WorldGrid::world_to_tile(position.0);
+        let Some(container) = logistics
+            .chunks
+            .values_mut()
+            .flat_map(|chunk| chunk.containers.values_mut())
+            .filter(|container| tile_distance(container.tile, player_tile) <= STORE_REACH)
+            .min_by_key(|container| tile_distance(container.tile, player_tile))
+        else {
+            replies.send(reply(
+                "You need to stand by a chest, furnace or assembler".to_string(),
+            ));
+            continue;
+        };
+        let coord = grid.tile_to_chunk(container.tile);
+        if !claims.can_modify(coord, client_id, &permissions) {
+            replies.send(reply("This is on land claimed by someone else".to_string()));
+            continue;
+        }
+        if !container.kind.accepts(kind) {
+            replies.send(reply(format!(
+                "A {:?} doesn't take {:?}",
+                container.kind, kind
+            )));
+            continue;
+        }
+        let carried = inventory.count(kind);
+        let wanted = command
+            .args
+            .int("count")
+            .map_or(carried, |count| count.clamp(0, carried as i64) as u32);
+        if wanted == 0 {
+            replies.send(reply(format!("You don't have any {:?}", kind)));
+            continue;
+        }
+        let stored = wanted - container.items.add(kind, wanted);
+        if stored == 0 {
+            replies.send(reply(format!("The {:?} is full", container.kind)));
+            continue;
+        }
+        inventory.remove(kind, stored);
+        logistics.changed.insert(coord);
+        replies.send(reply(format!("You stored {} {:?}", stored, kind)));
+    }
+}
+
+// Move items through an inserter, from the container behind it to the one in front of it
+fn swing(
+    logistics: &mut Logistics,
+    grid: WorldGrid,
+    (x, y): (i32, i32),
+    facing: Facing,
+) -> Option<ItemMove> {
+    let (dx, dy) = facing.offset();
+    let (from, to) = ((x - dx, y - dy), (x + dx, y + dy));
+    let accepts = logistics.container_mut(grid, to)?.kind;
+    let source = logistics.container_mut(grid, from)?;
+    let kind = source
+        .items
+        .slots
+        .iter()
+        .map(|stack| stack.kind)
+        .find(|kind| source.kind.gives(*kind) && accepts.accepts(*kind))?;
+    let count = ITEMS_PER_SWING.min(source.items.count(kind));
+    source.items.remove(kind, count);
+    let left = logistics.container_mut(grid, to)?.items.add(kind, count);
+    if left > 0 {
+        logistics.container_mut(grid, from)?.items.add(kind, left);
+    }
+    if left == count {
+        return None;
+    }
+    logistics.changed.insert(grid.tile_to_chunk(from));
+    logistics.changed.insert(grid.tile_to_chunk(to));
+    Some(ItemMove {
+        from,
+        to,
+        kind,
+        count: count - left,
+    })
+}
+
+// Swing the inserters, and follow recipes in the furnaces and powered assemblers, of loaded
+// chunks as time passes and of warm chunks on their ticks. Players only see items move in loaded
+// chunks.
+fn run_inserters(
+    mut logistics: ResMut<Logistics>,
+    mut ticks: EventReader<WarmChunkTick>,
+    mut connection_manager: ResMut<ConnectionManager>,
+    power: Res<PowerGrid>,
+    world_state: Res<WorldState>,
+    world_config: Res<WorldConfig>,
+    mut errors: EventWriter<ReportError>,
+) {
+    let grid = world_config.grid();
+    let now = world_state.world_time;
+    let elapsed = (now - logistics.last_update).max(0.0);
+    logistics.last_update = now;
+    for (coord, chunk) in logistics.chunks.iter_mut() {
+        if world_state.chunks.contains_key(coord) {
+            chunk.owed += elapsed;
+        }
+    }
+    for tick in ticks.read() {
+        if let Some(chunk) = logistics.chunks.get_mut(&tick.coord) {
+            chunk.owed += tick.delta;
+        }
+    }
+
+    let mut moves = Vec::new();
+    let coords: Vec<ChunkCoord> = logistics.chunks.keys().copied().collect();
+    for coord in coords {
+        let Some(chunk) = logistics.chunks.get_mut(&coord) else {
+            continue;
+        };
+        let swings = ((chunk.owed / SWING_SECS) as u32).min(MAX_SWINGS);
+        if swings == MAX_SWINGS {
+            chunk.owed %= SWING_SECS;
+        } else {
+            chunk.owed -= swings as f64 * SWING_SECS;
+        }
+        if swings == 0 || (chunk.inserters.is_empty() && chunk.containers.is_empty()) {
+            continue;
+        }
+        let inserters = chunk.inserters.clone();
+        let visible = world_state.chunks.contains_key(&coord);
+        for _ in 0..swings {
+            let Some(chunk) = logistics.chunks.get_mut(&coord) else {
+                break;
+            };
+            let cooked = chunk
+                .containers
+                .values_mut()
+                .filter_map(|container| container.process(power.is_powered(container.tile)))
+                .count();
+            if cooked > 0 {
+                logistics.changed.insert(coord);
+            }
+            for (tile, facing) in inserters.iter() {
+                if let Some(moved) = swing(&mut logistics, grid, *tile, *facing) {
+                    if visible {
+                        moves.push(moved);
+                    }
+                }
+            }
+        }
+    }
+    if moves.is_empty() {
+        return;
+    }
+    connection_manager
+        .send_message_to_target::<Channel1, ItemsMoved>(&ItemsMoved { moves }, NetworkTarget::All)
+        .unwrap_or_else(|e| {
+            errors.send(ReportError(GameError::send("ItemsMoved", e)));
+        });
+}
+
+// Save and forget the containers of chunks that are neither loaded nor warm anymore
+fn unload_cold_logistics(
+    mut logistics: ResMut<Logistics>,
+    world_state: Res<WorldState>,
+    warm: Res<WarmChunks>,
+    store: Res<ChunkStore>,
+    mut errors: EventWriter<ReportError>,
+) {
+    let cold: Vec<ChunkCoord> = logistics
+        .chunks
+        .keys()
+        .filter(|coord| !world_state.chunks.contains_key(coord) && !warm.is_warm(**coord))
+        .copied()
+        .collect();
+    for coord in cold {
+        let Some(chunk) = logistics.chunks.remove(&coord) else {
+            continue;
+        };
+        if logistics.changed.remove(&coord) {
+            let saved: Vec<Container> = chunk.containers.into_values().collect();
+            if let Err(e) = store.save_containers(coord, &saved) {
+                errors.send(ReportError(e));
+            }
+        }
+    }
+}
+
+fn save_containers(
+    mut events: EventReader<SaveWorldEvent>,
+    mut logistics: ResMut<Logistics>,
+    store: Res<ChunkStore>,
+    mut errors: EventWriter<ReportError>,
+) {
+    if events.read().count() == 0 {
+        return;
+    }
+    for coord in std::mem::take(&mut logistics.changed) {
+        let saved: Vec<Container> = logistics
+            .chunks
+            .get(&coord)
+            .map(|chunk| chunk.containers.values().cloned().collect())
+            .unwrap_or_default();
+        if let Err(e) = store.save_containers(coord, &saved) {
+            errors.send(ReportError(e));
+        }
+    }
+}
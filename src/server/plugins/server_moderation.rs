@@ -0,0 +1,798 @@
+use bevy::asset::ron;
+use bevy::prelude::*;
+use crossbeam_channel::{Receiver, Sender, TryRecvError};
+use lightyear::prelude::server::*;
+use lightyear::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::protocol::{ChatChannel, ChatLine, PlayerId, PlayerName};
+use crate::server::plugins::CommandPermissions;
+use crate::settings_common::{ModerationSettings, Settings};
+use crate::shared::commands::{
+    ArgKind, CommandInvoked, CommandReply, CommandSource, CommandSpec, PermissionLevel,
+    RegisterCommandExt,
+};
+use crate::shared::error::{GameError, ReportError};
+use crate::shared::moderation::{ReportPlayer, MAX_EXCERPT_LINES};
+
+// File holding the reports, mutes and bans
+const MODERATION_PATH: &str = "world/moderation.ron";
+// Open reports a single player can have at once, so nobody floods the queue
+const MAX_OPEN_REPORTS: usize = 5;
+// Reports listed at most by the /reports command
+const REPORT_LIST_LIMIT: usize = 10;
+// Longest accepted report reason and HTTP request body
+const MAX_REASON_LENGTH: usize = 200;
+const MAX_HTTP_BODY: usize = 4096;
+// How long the HTTP thread waits for the client and for the game to answer
+const HTTP_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Server plugin collecting player reports in a moderation queue saved on disk, which moderators
+// review and act on through chat commands or a small HTTP API
+pub struct ServerModerationPlugin;
+
+impl Plugin for ServerModerationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ModerationQueue>()
+            .insert_resource(ModerationConfig(ModerationSettings::default()))
+            .add_event::<ModerationEffect>()
+            .register_command(
+                CommandSpec::new("reports", "List open reports, or 'all' reports")
+                    .optional_arg("filter", ArgKind::Word)
+                    .permission(PermissionLevel::Moderator),
+            )
+            .register_command(
+                CommandSpec::new(
+                    "moderate",
+                    "Act on a report: show, warn, mute, ban, pardon or resolve, with an optional note",
+                )
+                .arg("report", ArgKind::Int)
+                .arg("action", ArgKind::Word)
+                .optional_arg("note", ArgKind::Text)
+                .permission(PermissionLevel::Moderator),
+            )
+            .add_systems(
+                Startup,
+                (load_moderation_queue, load_moderation_settings, spawn_http_api).chain(),
+            )
+            .add_systems(
+                Update,
+                (
+                    refuse_banned_players,
+                    receive_reports,
+                    handle_moderation_commands,
+                    handle_http_requests,
+                    apply_moderation_effects,
+                    save_moderation_queue,
+                )
+                    .chain(),
+            );
+    }
+}
+
+#[derive(Resource)]
+pub struct ModerationConfig(pub ModerationSettings);
+
+/// A report filed by a player against another one
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Report {
+    pub id: u32,
+    pub reporter: ClientId,
+    pub reporter_name: String,
+    pub reported: ClientId,
+    pub reported_name: String,
+    pub reason: String,
+    pub excerpt: Vec<String>,
+    /// Unix time, in seconds
+    pub created_at: u64,
+    /// What moderators did about it so far, e.g. "muted for 600s by Player 1"
+    pub actions: Vec<String>,
+    pub resolution: Option<Resolution>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Resolution {
+    pub by: String,
+    pub note: String,
+    pub at: u64,
+}
+
+impl Report {
+    fn summary(&self, now: u64) -> String {
+        let state = if self.resolution.is_some() {
+            "resolved"
+        } else {
+            "open"
+        };
+        format!(
+            "#{} [{}] {} reported by {} {}m ago: {}",
+            self.id,
+            state,
+            self.reported_name,
+            self.reporter_name,
+            now.saturating_sub(self.created_at) / 60,
+            self.reason
+        )
+    }
+
+    fn details(&self, now: u64) -> Vec<String> {
+        let mut lines = vec![self.summary(now)];
+        lines.extend(self.excerpt.iter().map(|line| format!("  | {}", line)));
+        lines.extend(self.actions.iter().map(|action| format!("  - {}", action)));
+        if let Some(resolution) = &self.resolution {
+            lines.push(format!(
+                "  resolved by {}: {}",
+                resolution.by, resolution.note
+            ));
+        }
+        lines
+    }
+}
+
+/// What a moderator can do about a report
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ModerationAction {
+    Warn,
+    /// Mute the reported player; the note is the duration in seconds, if any
+    Mute,
+    Ban,
+    /// Lift the mute and ban of the reported player
+    Pardon,
+    Resolve,
+}
+
+impl ModerationAction {
+    pub fn parse(name: &str) -> Result<Self, ModerationError> {
+        match name {
+            "warn" => Ok(ModerationAction::Warn),
+            "mute" => Ok(ModerationAction::Mute),
+            "ban" => Ok(ModerationAction::Ban),
+            "pardon" => Ok(ModerationAction::Pardon),
+            "resolve" => Ok(ModerationAction::Resolve),
+            other => Err(ModerationError::UnknownAction(other.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ModerationError {
+    UnknownReport(u32),
+    AlreadyResolved(u32),
+    UnknownAction(String),
+    UnknownPlayer(String),
+    SelfReport,
+    EmptyReason,
+    TooManyReports,
+}
+
+impl fmt::Display for ModerationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModerationError::UnknownReport(id) => write!(f, "There is no report #{}", id),
+            ModerationError::AlreadyResolved(id) => write!(f, "Report #{} is already resolved", id),
+            ModerationError::UnknownAction(action) => write!(
+                f,
+                "Unknown action '{}', expected show, warn, mute, ban, pardon or resolve",
+                action
+            ),
+            ModerationError::UnknownPlayer(name) => {
+                write!(f, "There is no player named '{}' online", name)
+            }
+            ModerationError::SelfReport => write!(f, "You can't report yourself"),
+            ModerationError::EmptyReason => write!(f, "Say why you are reporting this player"),
+            ModerationError::TooManyReports => write!(
+                f,
+                "You have too many open reports, wait for the moderators to review them"
+            ),
+        }
+    }
+}
+
+/// Something an action does to the reported player, applied by `apply_moderation_effects`
+#[derive(Event, Clone, Debug)]
+pub struct ModerationEffect {
+    pub client_id: ClientId,
+    pub message: String,
+    pub disconnect: bool,
+}
+
+/// Reports, mutes and bans, saved to disk whenever they change
+#[derive(Resource, Serialize, Deserialize, Default, Debug)]
+pub struct ModerationQueue {
+    next_id: u32,
+    pub reports: Vec<Report>,
+    /// Muted players, and until when (unix time, in seconds)
+    pub mutes: HashMap<ClientId, u64>,
+    /// Banned players, and why
+    pub bans: HashMap<ClientId, String>,
+    #[serde(skip)]
+    dirty: bool,
+}
+
+impl ModerationQueue {
+    fn load(path: &Path) -> Result<Self, GameError> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = fs::read_to_string(path)?;
+        ron::from_str(&text).map_err(|e| GameError::Moderation(e.to_string()))
+    }
+
+    fn save(&self, path: &Path) -> Result<(), GameError> {
+        let text = ron::ser::to_string_pretty(self, default())
+            .map_err(|e| GameError::Moderation(e.to_string()))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // write then rename, so that a crash never leaves a truncated queue behind
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, text)?;
+        Ok(fs::rename(tmp, path)?)
+    }
+
+    /// Seconds left on the player's mute, if they are muted
+    pub fn muted_for(&self, client_id: ClientId, now: u64) -> Option<u64> {
+        self.mutes
+            .get(&client_id)
+            .filter(|until| **until > now)
+            .map(|until| until - now)
+    }
+
+    pub fn ban_reason(&self, client_id: ClientId) -> Option<&str> {
+        self.bans.get(&client_id).map(String::as_str)
+    }
+
+    pub fn reports(&self, include_resolved: bool) -> impl Iterator<Item = &Report> {
+        self.reports
+            .iter()
+            .filter(move |report| include_resolved || report.resolution.is_none())
+    }
+
+    pub fn get(&self, id: u32) -> Result<&Report, ModerationError> {
+        self.reports
+            .iter()
+            .find(|report| report.id == id)
+            .ok_or(ModerationError::UnknownReport(id))
+    }
+
+    fn submit(&mut self, mut report: Report) -> Result<u32, ModerationError> {
+        if report.reporter == report.reported {
+            return Err(ModerationError::SelfReport);
+        }
+        if report.reason.trim().is_empty() {
+            return Err(ModerationError::EmptyReason);
+        }
+        let open = self
+            .reports(false)
+            .filter(|open| open.reporter == report.reporter)
+            .count();
+        if open >= MAX_OPEN_REPORTS {
+            return Err(ModerationError::TooManyReports);
+        }
+        self.next_id += 1;
+        report.id = self.next_id;
+        self.reports.push(report);
+        self.dirty = true;
+        Ok(self.next_id)
+    }
+
+    /// Apply a moderator's action to a report, returning the answer to give them and what it
+    /// does to the reported player
+    fn act(
+        &mut self,
+        id: u32,
+        action: ModerationAction,
+        note: &str,
+        by: &str,
+        now: u64,
+        default_mute_secs: u64,
+    ) -> Result<(String, Option<ModerationEffect>), ModerationError> {
+        let report = self.get(id)?;
+        // bans outlive their report, so pardons don't care whether it's resolved
+        if report.resolution.is_some() && action != ModerationAction::Pardon {
+            return Err(ModerationError::AlreadyResolved(id));
+        }
+        let (reported, name) = (report.reported, report.reported_name.clone());
+        let reason = if note.is_empty() {
+            report.reason.clone()
+        } else {
+            note.to_string()
+        };
+        let effect = |message: String, disconnect: bool| {
+            Some(ModerationEffect {
+                client_id: reported,
+                message,
+                disconnect,
+            })
+        };
+        let (record, reply, effect) = match action {
+            ModerationAction::Resolve => {
+                self.report_mut(id)?.resolution = Some(Resolution {
+                    by: by.to_string(),
+                    note: note.to_string(),
+                    at: now,
+                });
+                self.dirty = true;
+                return Ok((format!("Resolved report #{}", id), None));
+            }
+            ModerationAction::Warn => (
+                format!("warned by {}", by),
+                format!("Warned {}", name),
+                effect(format!("A moderator warned you: {}", reason), false),
+            ),
+            ModerationAction::Mute => {
+                let secs = note.parse().unwrap_or(default_mute_secs);
+                self.mutes.insert(reported, now + secs);
+                (
+                    format!("muted for {}s by {}", secs, by),
+                    format!("Muted {} for {}s", name, secs),
+                    effect(
+                        format!("A moderator muted you for {} minutes", secs.div_ceil(60)),
+                        false,
+                    ),
+                )
+            }
+            ModerationAction::Ban => {
+                self.bans.insert(reported, reason.clone());
+                (
+                    format!("banned by {}", by),
+                    format!("Banned {}", name),
+                    effect(format!("You are banned from this server: {}", reason), true),
+                )
+            }
+            ModerationAction::Pardon => {
+                self.mutes.remove(&reported);
+                self.bans.remove(&reported);
+                (
+                    format!("pardoned by {}", by),
+                    format!("Lifted the mute and ban of {}", name),
+                    None,
+                )
+            }
+        };
+        self.report_mut(id)?.actions.push(record);
+        self.dirty = true;
+        Ok((reply, effect))
+    }
+
+    fn report_mut(&mut self, id: u32) -> Result<&mut Report, ModerationError> {
+        self.reports
+            .iter_mut()
+            .find(|report| report.id == id)
+            .ok_or(ModerationError::UnknownReport(id))
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+fn load_moderation_queue(mut queue: ResMut<ModerationQueue>, mut errors: EventWriter<ReportError>) {
+    match ModerationQueue::load(Path::new(MODERATION_PATH)) {
+        Ok(loaded) => {
+            info!(
+                "Loaded {} open report(s) and {} ban(s)",
+                loaded.reports(false).count(),
+                loaded.bans.len()
+            );
+            *queue = loaded;
+        }
+        Err(e) => errors.send(ReportError(e)),
+    }
+}
+
+fn load_moderation_settings(settings: Option<Res<Settings>>, mut config: ResMut<ModerationConfig>) {
+    if let Some(settings) = settings {
+        config.0 = settings.server.moderation.clone();
+    }
+}
+
+fn save_moderation_queue(mut queue: ResMut<ModerationQueue>, mut errors: EventWriter<ReportError>) {
+    if !queue.dirty {
+        return;
+    }
+    queue.dirty = false;
+    if let Err(e) = queue.save(Path::new(MODERATION_PATH)) {
+        errors.send(ReportError(e));
+    }
+}
+
+// Banned players are told why and disconnected as soon as they connect
+fn refuse_banned_players(
+    mut connections: EventReader<ConnectEvent>,
+    queue: Res<ModerationQueue>,
+    mut effects: EventWriter<ModerationEffect>,
+) {
+    for connection in connections.read() {
+        if let Some(reason) = queue.ban_reason(connection.client_id) {
+            info!("Refusing banned player {}", connection.client_id);
+            effects.send(ModerationEffect {
+                client_id: connection.client_id,
+                message: format!("You are banned from this server: {}", reason),
+                disconnect: true,
+            });
+        }
+    }
+}
+
+fn receive_reports(
+    mut events: EventReader<MessageEvent<ReportPlayer>>,
+    mut queue: ResMut<ModerationQueue>,
+    permissions: Res<CommandPermissions>,
+    players: Query<(&PlayerId, &PlayerName)>,
+    mut replies: EventWriter<CommandReply>,
+    mut connection_manager: ResMut<ConnectionManager>,
+    mut errors: EventWriter<ReportError>,
+) {
+    for event in events.read() {
+        let reporter = event.from();
+        let message = event.message();
+        let name_of = |client_id: ClientId| {
+            players
+                .iter()
+                .find(|(id, _)| id.client_id() == client_id)
+                .map(|(_, name)| name.0.clone())
+        };
+        let result = players
+            .iter()
+            .find(|(_, name)| name.0.eq_ignore_ascii_case(message.player.trim()))
+            .map(|(id, name)| (id.client_id(), name.0.clone()))
+            .ok_or_else(|| ModerationError::UnknownPlayer(message.player.clone()))
+            .and_then(|(reported, reported_name)| {
+                queue.submit(Report {
+                    id: 0,
+                    reporter,
+                    reporter_name: name_of(reporter)
+                        .unwrap_or_else(|| format!("Player {}", reporter)),
+                    reported,
+                    reported_name,
+                    reason: message.reason.chars().take(MAX_REASON_LENGTH).collect(),
+                    excerpt: message
+                        .excerpt
+                        .iter()
+                        .flatten()
+                        .take(MAX_EXCERPT_LINES)
+                        .map(|line| line.chars().take(MAX_REASON_LENGTH).collect())
+                        .collect(),
+                    created_at: unix_now(),
+                    actions: Vec::new(),
+                    resolution: None,
+                })
+            });
+        let id = match result {
+            Ok(id) => id,
+            Err(e) => {
+                replies.send(CommandReply::new(
+                    CommandSource::Client(reporter),
+                    e.to_string(),
+                ));
+                continue;
+            }
+        };
+        replies.send(CommandReply::new(
+            CommandSource::Client(reporter),
+            format!("Report #{} sent to the moderators, thank you", id),
+        ));
+
+        let Ok(report) = queue.get(id) else {
+            continue;
+        };
+        let notice = format!("[report] {}", report.summary(unix_now()));
+        info!("{}", notice);
+        let moderators = players
+            .iter()
+            .map(|(id, _)| id.client_id())
+            .filter(|client_id| {
+                permissions.level(CommandSource::Client(*client_id)) >= PermissionLevel::Moderator
+            });
+        for client_id in moderators {
+            if let Err(e) = connection_manager
+                .send_message::<ChatChannel, _>(client_id, &ChatLine::system(&notice))
+            {
+                errors.send(ReportError(GameError::send("ChatLine", e)));
+            }
+        }
+    }
+}
+
+fn handle_moderation_commands(
+    mut invoked: EventReader<CommandInvoked>,
+    mut replies: EventWriter<CommandReply>,
+    mut queue: ResMut<ModerationQueue>,
+    config: Res<ModerationConfig>,
+    players: Query<(&PlayerId, &PlayerName)>,
+    mut effects: EventWriter<ModerationEffect>,
+) {
+    let now = unix_now();
+    for command in invoked.read() {
+        match command.name {
+            "reports" => {
+                let all = command.args.str("filter") == Some("all");
+                let reports: Vec<&Report> = queue.reports(all).collect();
+                if reports.is_empty() {
+                    replies.send(CommandReply::new(command.source, "No reports"));
+                    continue;
+                }
+                // newest last, right above the chat input
+                for report in reports.iter().rev().take(REPORT_LIST_LIMIT).rev() {
+                    replies.send(CommandReply::new(command.source, report.summary(now)));
+                }
+            }
+            "moderate" => {
+                let Some(id) = command
+                    .args
+                    .int("report")
+                    .and_then(|id| u32::try_from(id).ok())
+                else {
+                    replies.send(CommandReply::new(
+                        command.source,
+                        "Usage: /moderate <report> <action> [note]",
+                    ));
+                    continue;
+                };
+                let by = match command.source {
+                    CommandSource::Console => "console".to_string(),
+                    CommandSource::Client(client_id) => players
+                        .iter()
+                        .find(|(id, _)| id.client_id() == client_id)
+                        .map(|(_, name)| name.0.clone())
+                        .unwrap_or_else(|| format!("Player {}", client_id)),
+                };
+                let note = command.args.str("note").unwrap_or_default();
+                let action = command.args.str("action").unwrap_or_default();
+                let reply = if action == "show" {
+                    queue
+                        .get(id)
+                        .map_or_else(|e| e.to_string(), |report| report.details(now).join("\n"))
+                } else {
+                    let result = ModerationAction::parse(action).and_then(|action| {
+                        queue.act(id, action, note, &by, now, config.0.default_mute_secs)
+                    });
+                    match result {
+                        Ok((reply, effect)) => {
+                            effects.send_batch(effect);
+                            reply
+                        }
+                        Err(e) => e.to_string(),
+                    }
+                };
+                for line in reply.lines() {
+                    replies.send(CommandReply::new(command.source, line));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn apply_moderation_effects(
+    mut effects: EventReader<ModerationEffect>,
+    players: Query<&PlayerId>,
+    mut connection_manager: ResMut<ConnectionManager>,
+    mut connections: ResMut<ServerConnections>,
+    mut errors: EventWriter<ReportError>,
+) {
+    for effect in effects.read() {
+        let online = players
+            .iter()
+            .any(|player_id| player_id.client_id() == effect.client_id);
+        // banned players may still be waiting in the connection queue, without a player entity
+        if !online && !effect.disconnect {
+            continue;
+        }
+        if let Err(e) = connection_manager
+            .send_message::<ChatChannel, _>(effect.client_id, &ChatLine::system(&effect.message))
+        {
+            errors.send(ReportError(GameError::send("ChatLine", e)));
+        }
+        if effect.disconnect {
+            if let Err(e) = connections.disconnect(effect.client_id) {
+                debug!("Could not disconnect {}: {:?}", effect.client_id, e);
+            }
+        }
+    }
+}
+
+/// A request received by the HTTP API, answered by `handle_http_requests`
+struct HttpRequest {
+    method: String,
+    path: String,
+    body: String,
+    respond: Sender<HttpResponse>,
+}
+
+struct HttpResponse {
+    status: u16,
+    body: String,
+}
+
+impl HttpResponse {
+    fn new(status: u16, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            body: body.into(),
+        }
+    }
+}
+
+#[derive(Resource)]
+struct HttpApi(Receiver<HttpRequest>);
+
+// The API only starts with both a port and a token configured, and only listens on localhost:
+// put a reverse proxy in front of it to reach it from elsewhere
+fn spawn_http_api(mut commands: Commands, config: Res<ModerationConfig>) {
+    let (Some(port), Some(token)) = (config.0.http_port, config.0.http_token.clone()) else {
+        return;
+    };
+    let token = token.expose().to_string();
+    let listener = match TcpListener::bind((Ipv4Addr::LOCALHOST, port)) {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("Could not start the moderation API on port {}: {}", port, e);
+            return;
+        }
+    };
+    info!("Moderation API listening on http://127.0.0.1:{}", port);
+    let (sender, receiver) = crossbeam_channel::unbounded();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else {
+                continue;
+            };
+            if let Err(e) = serve_http(stream, &token, &sender) {
+                debug!("Moderation API request failed: {}", e);
+            }
+        }
+    });
+    commands.insert_resource(HttpApi(receiver));
+}
+
+// Read one request, hand it to the game and write its answer back; one request per connection
+fn serve_http(
+    mut stream: TcpStream,
+    token: &str,
+    requests: &Sender<HttpRequest>,
+) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(HTTP_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+
+    let mut authorized = false;
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("authorization") {
+            authorized = value.strip_prefix("Bearer ") == Some(token);
+        } else if name.eq_ignore_ascii_case("content-length") {
+            content_length = value.parse::<usize>().unwrap_or(0).min(MAX_HTTP_BODY);
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+
+    let response = if authorized {
+        let (respond, answer) = crossbeam_channel::bounded(1);
+        let request = HttpRequest {
+            method,
+            path,
+            body: String::from_utf8_lossy(&body).into_owned(),
+            respond,
+        };
+        match requests.send(request) {
+            Ok(()) => answer
+                .recv_timeout(HTTP_TIMEOUT)
+                .unwrap_or_else(|_| HttpResponse::new(503, "The server did not answer in time")),
+            Err(_) => HttpResponse::new(503, "The server is shutting down"),
+        }
+    } else {
+        HttpResponse::new(401, "Missing or wrong bearer token")
+    };
+    let reason = match response.status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        500 => "Internal Server Error",
+        _ => "Service Unavailable",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        reason,
+        response.body.len(),
+        response.body
+    )
+}
+
+// GET /reports[?all]       open (or all) reports, as RON
+// GET /reports/<id>        one report, as RON
+// POST /reports/<id>/<action>  act on a report; the body is the note
+fn handle_http_requests(
+    api: Option<Res<HttpApi>>,
+    mut queue: ResMut<ModerationQueue>,
+    config: Res<ModerationConfig>,
+    mut effects: EventWriter<ModerationEffect>,
+) {
+    let Some(api) = api else {
+        return;
+    };
+    loop {
+        let request = match api.0.try_recv() {
+            Ok(request) => request,
+            Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => break,
+        };
+        let (path, query) = request
+            .path
+            .split_once('?')
+            .unwrap_or((request.path.as_str(), ""));
+        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        let id = segments.get(1).and_then(|id| id.parse::<u32>().ok());
+        let response = match (request.method.as_str(), segments.as_slice(), id) {
+            ("GET", ["reports"], _) => {
+                let reports: Vec<&Report> = queue.reports(query.contains("all")).collect();
+                to_ron(&reports)
+            }
+            ("GET", ["reports", _], Some(id)) => match queue.get(id) {
+                Ok(report) => to_ron(report),
+                Err(e) => HttpResponse::new(404, e.to_string()),
+            },
+            ("POST", ["reports", _, action], Some(id)) => {
+                let result = ModerationAction::parse(action).and_then(|action| {
+                    queue.act(
+                        id,
+                        action,
+                        request.body.trim(),
+                        "moderation API",
+                        unix_now(),
+                        config.0.default_mute_secs,
+                    )
+                });
+                match result {
+                    Ok((reply, effect)) => {
+                        effects.send_batch(effect);
+                        HttpResponse::new(200, reply)
+                    }
+                    Err(e @ ModerationError::UnknownReport(_)) => {
+                        HttpResponse::new(404, e.to_string())
+                    }
+                    Err(e) => HttpResponse::new(400, e.to_string()),
+                }
+            }
+            _ => HttpResponse::new(404, "Unknown endpoint"),
+        };
+        // the HTTP thread may have given up waiting already
+        let _ = request.respond.send(response);
+    }
+}
+
+fn to_ron(value: &impl Serialize) -> HttpResponse {
+    match ron::ser::to_string_pretty(value, default()) {
+        Ok(text) => HttpResponse::new(200, text),
+        Err(e) => HttpResponse::new(500, e.to_string()),
+    }
+}
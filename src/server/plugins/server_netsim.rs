@@ -0,0 +1,139 @@
+use bevy::prelude::*;
+use lightyear::prelude::server::*;
+use lightyear::prelude::*;
+use lightyear::server::config::ServerConfig;
+
+use crate::protocol::{Channel1, LinkConditions, PlayerId, SetLinkConditioner};
+use crate::settings_common::Conditioner;
+use crate::shared::commands::{
+    ArgKind, CommandArgs, CommandInvoked, CommandReply, CommandSpec, PermissionLevel,
+    RegisterCommandExt,
+};
+
+// Server plugin exposing lightyear's link conditioner through the /netsim admin command
+pub struct ServerNetsimPlugin;
+
+impl Plugin for ServerNetsimPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PendingServerRestart>()
+            .register_command(
+                CommandSpec::new(
+                    "netsim",
+                    "Simulate latency/jitter/loss on the server or on a client id; no values turns it off",
+                )
+                .arg("target", ArgKind::Word)
+                .optional_arg("latency_ms", ArgKind::Int)
+                .optional_arg("jitter_ms", ArgKind::Int)
+                .optional_arg("packet_loss", ArgKind::Float)
+                .permission(PermissionLevel::Admin),
+            )
+            .add_systems(Update, (handle_netsim_command, restart_server).chain());
+    }
+}
+
+// Set when the server was stopped to apply a new conditioner, and must be started again
+#[derive(Resource, Default)]
+struct PendingServerRestart(bool);
+
+fn parse_conditions(args: &CommandArgs) -> Option<LinkConditions> {
+    if args.int("latency_ms").is_none()
+        && args.int("jitter_ms").is_none()
+        && args.float("packet_loss").is_none()
+    {
+        return None;
+    }
+    Some(LinkConditions {
+        latency_ms: args
+            .int("latency_ms")
+            .unwrap_or(0)
+            .clamp(0, u16::MAX as i64) as u16,
+        jitter_ms: args.int("jitter_ms").unwrap_or(0).clamp(0, u16::MAX as i64) as u16,
+        packet_loss: (args.float("packet_loss").unwrap_or(0.0) as f32).clamp(0.0, 1.0),
+    })
+}
+
+fn handle_netsim_command(
+    mut commands: Commands,
+    mut invoked: EventReader<CommandInvoked>,
+    mut replies: EventWriter<CommandReply>,
+    mut server_config: ResMut<ServerConfig>,
+    mut pending_restart: ResMut<PendingServerRestart>,
+    mut connection_manager: ResMut<ConnectionManager>,
+    players: Query<&PlayerId>,
+) {
+    for command in invoked.read().filter(|c| c.name == "netsim") {
+        let conditions = parse_conditions(&command.args);
+        let description = match &conditions {
+            Some(c) => format!(
+                "{}ms latency, {}ms jitter, {:.0}% loss",
+                c.latency_ms,
+                c.jitter_ms,
+                c.packet_loss * 100.0
+            ),
+            None => "no impairment".to_string(),
+        };
+        let target = command.args.str("target").unwrap_or_default();
+
+        if target == "server" {
+            // The conditioner is part of the io config, so the server has to be restarted to apply it.
+            // Connected clients will be disconnected.
+            let conditioner = conditions.map(|c| {
+                Conditioner {
+                    latency_ms: c.latency_ms,
+                    jitter_ms: c.jitter_ms,
+                    packet_loss: c.packet_loss,
+                }
+                .build()
+            });
+            for net in server_config.net.iter_mut() {
+                if let NetConfig::Netcode { io, .. } = net {
+                    io.conditioner = conditioner.clone();
+                }
+            }
+            commands.stop_server();
+            pending_restart.0 = true;
+            replies.send(CommandReply::new(
+                command.source,
+                format!("Restarting server with {}", description),
+            ));
+            continue;
+        }
+
+        let Some(client_id) = target.parse::<u64>().ok().and_then(|id| {
+            players
+                .iter()
+                .map(PlayerId::client_id)
+                .find(|client_id| client_id.to_bits() == id)
+        }) else {
+            replies.send(CommandReply::new(
+                command.source,
+                format!("No connected client with id '{}'", target),
+            ));
+            continue;
+        };
+        connection_manager
+            .send_message::<Channel1, _>(client_id, &SetLinkConditioner(conditions))
+            .unwrap_or_else(|e| {
+                error!(
+                    "Failed to send link conditioner to {:?}: {:?}",
+                    client_id, e
+                );
+            });
+        replies.send(CommandReply::new(
+            command.source,
+            format!("Client {} will reconnect with {}", client_id, description),
+        ));
+    }
+}
+
+fn restart_server(
+    mut commands: Commands,
+    state: Res<State<NetworkingState>>,
+    mut pending_restart: ResMut<PendingServerRestart>,
+) {
+    if pending_restart.0 && state.get() == &NetworkingState::Stopped {
+        info!("Starting server with the new link conditioner");
+        commands.start_server();
+        pending_restart.0 = false;
+    }
+}
@@ -0,0 +1,222 @@
+use bevy::prelude::*;
+use lightyear::prelude::server::*;
+use lightyear::prelude::*;
+use std::collections::{HashMap, VecDeque};
+
+use crate::protocol::{
+    ObserveRegion, ObservedPlayer, ObserverChannel, ObserverSnapshot, PlayerColor, PlayerName,
+    PlayerPosition,
+};
+use crate::settings_common::{client_ids_verified, ObserverSettings, Settings};
+use crate::shared::error::{GameError, ReportError};
+use crate::shared::npc::Npc;
+use crate::shared::world_generation::{WorldGrid, WorldState};
+
+// Server plugin streaming delayed snapshots of the world to observers: clients listed in the
+// settings, which connect without a player entity and don't take a player slot
+pub struct ServerObserversPlugin;
+
+impl Plugin for ServerObserversPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Observers>()
+            .add_systems(Startup, load_observer_settings)
+            .add_systems(
+                Update,
+                (
+                    track_observers,
+                    move_observed_regions,
+                    record_snapshots,
+                    send_delayed_snapshots,
+                )
+                    .chain(),
+            );
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Observers {
+    pub settings: ObserverSettings,
+    /// Connected observers and the region each of them watches
+    pub watching: HashMap<ClientId, ObserveRegion>,
+    // Snapshots of the whole world not sent yet, oldest first, with the time they were taken
+    history: VecDeque<(f64, ObserverSnapshot)>,
+    last_recorded: Option<f64>,
+}
+
+impl Observers {
+    /// Whether the client connects as an observer rather than as a player
+    pub fn is_observer(&self, client_id: ClientId) -> bool {
+        self.settings.client_ids.contains(&client_id.to_bits())
+    }
+
+    // The radius an observer asked for, capped by the settings
+    fn capped_radius(&self, radius: Option<f32>) -> Option<f32> {
+        match (radius, self.settings.max_region_radius) {
+            (Some(radius), Some(max)) => Some(radius.min(max)),
+            (None, max) => max,
+            (radius, None) => radius,
+        }
+    }
+}
+
+fn load_observer_settings(settings: Option<Res<Settings>>, mut observers: ResMut<Observers>) {
+    let Some(settings) = settings else {
+        return;
+    };
+    observers.settings = settings.server.observers.clone();
+    // anyone could claim an observer's id, and watch the world without taking a player slot
+    if !observers.settings.client_ids.is_empty() && !client_ids_verified(&settings.shared) {
+        warn!(
+            "Ignoring the {} observer id(s) in the settings: client ids aren't verified",
+            observers.settings.client_ids.len()
+        );
+        observers.settings.client_ids.clear();
+    }
+}
+
+// Start streaming to observers as they connect, up to the limit, and stop when they leave
+fn track_observers(
+    mut connections: EventReader<ConnectEvent>,
+    mut disconnections: EventReader<DisconnectEvent>,
+    mut observers: ResMut<Observers>,
+    mut server_connections: ResMut<ServerConnections>,
+) {
+    for connection in connections.read() {
+        let client_id = connection.client_id;
+        if !observers.is_observer(client_id) {
+            continue;
+        }
+        if observers.watching.len() >= observers.settings.max_observers {
+            warn!("Too many observers, disconnecting {}", client_id);
+            if let Err(e) = server_connections.disconnect(client_id) {
+                error!("Failed to disconnect observer {}: {:?}", client_id, e);
+            }
+            continue;
+        }
+        info!("Client {} is observing", client_id);
+        let radius = observers.capped_radius(None);
+        observers.watching.insert(
+            client_id,
+            ObserveRegion {
+                center: Vec2::ZERO,
+                radius,
+            },
+        );
+    }
+    for disconnection in disconnections.read() {
+        observers.watching.remove(&disconnection.client_id);
+    }
+}
+
+fn move_observed_regions(
+    mut requests: EventReader<MessageEvent<ObserveRegion>>,
+    mut observers: ResMut<Observers>,
+) {
+    for request in requests.read() {
+        let region = request.message();
+        let radius = observers.capped_radius(region.radius);
+        if let Some(watched) = observers.watching.get_mut(&request.from()) {
+            *watched = ObserveRegion {
+                center: region.center,
+                radius,
+            };
+        }
+    }
+}
+
+// Take a snapshot of every player and NPC at the configured interval
+fn record_snapshots(
+    time: Res<Time>,
+    world_state: Res<WorldState>,
+    mut observers: ResMut<Observers>,
+    players: Query<(&PlayerName, &PlayerColor, &PlayerPosition)>,
+    npcs: Query<&Npc>,
+) {
+    let now = time.elapsed_secs_f64();
+    let interval = observers.settings.snapshot_interval_ms as f64 / 1000.0;
+    if observers.watching.is_empty()
+        || observers
+            .last_recorded
+            .is_some_and(|last| now - last < interval)
+    {
+        return;
+    }
+    observers.last_recorded = Some(now);
+    let snapshot = ObserverSnapshot {
+        world_time: world_state.world_time,
+        delay_secs: observers.settings.delay_secs,
+        players: players
+            .iter()
+            .map(|(name, color, position)| ObservedPlayer {
+                name: name.0.clone(),
+                color: color.0,
+                position: position.0,
+            })
+            .collect(),
+        npcs: npcs.iter().map(|npc| npc.position).collect(),
+    };
+    observers.history.push_back((now, snapshot));
+}
+
+// Send each observer the snapshots that are old enough, cut down to the region it watches
+fn send_delayed_snapshots(
+    time: Res<Time>,
+    mut observers: ResMut<Observers>,
+    mut connection_manager: ResMut<ConnectionManager>,
+    mut errors: EventWriter<ReportError>,
+) {
+    let now = time.elapsed_secs_f64();
+    while observers
+        .history
+        .front()
+        .is_some_and(|(taken, _)| now - taken >= observers.settings.delay_secs)
+    {
+        let Some((_, snapshot)) = observers.history.pop_front() else {
+            break;
+        };
+        for (client_id, region) in &observers.watching {
+            let snapshot = in_region(&snapshot, region, observers.settings.max_entities);
+            if let Err(e) =
+                connection_manager.send_message::<ObserverChannel, _>(*client_id, &snapshot)
+            {
+                errors.send(ReportError(GameError::send("ObserverSnapshot", e)));
+            }
+        }
+    }
+}
+
+// The part of a snapshot inside a region, nearest entities first when there are too many
+fn in_region(
+    snapshot: &ObserverSnapshot,
+    region: &ObserveRegion,
+    max_entities: usize,
+) -> ObserverSnapshot {
+    let distance = |position: Vec2| (position - region.center).length();
+    let inside = |position: Vec2| {
+        region
+            .radius
+            .is_none_or(|radius| distance(position) <= radius * WorldGrid::TILE_SIZE)
+    };
+    let mut players: Vec<ObservedPlayer> = snapshot
+        .players
+        .iter()
+        .filter(|player| inside(player.position))
+        .cloned()
+        .collect();
+    let mut npcs: Vec<Vec2> = snapshot
+        .npcs
+        .iter()
+        .copied()
+        .filter(|npc| inside(*npc))
+        .collect();
+    players.sort_by(|a, b| distance(a.position).total_cmp(&distance(b.position)));
+    players.truncate(max_entities);
+    npcs.sort_by(|a, b| distance(*a).total_cmp(&distance(*b)));
+    npcs.truncate(max_entities - players.len());
+    ObserverSnapshot {
+        world_time: snapshot.world_time,
+        delay_secs: snapshot.delay_secs,
+        players,
+        npcs,
+    }
+}
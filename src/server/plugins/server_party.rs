@@ -0,0 +1,361 @@
+use bevy::prelude::*;
+use lightyear::prelude::server::*;
+use lightyear::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+use crate::protocol::{Channel1, PlayerId, PlayerName};
+use crate::shared::commands::{
+    ArgKind, CommandInvoked, CommandReply, CommandSource, CommandSpec, RegisterCommandExt,
+};
+use crate::shared::error::{GameError, ReportError};
+use crate::shared::party::{
+    PartyInvite, PartyMember, PartyPing, PartyRequest, PartySync, MAX_PARTY_SIZE,
+};
+use crate::shared::social::SocialLists;
+
+// Seconds an invite can be accepted for
+const INVITE_SECS: f64 = 60.0;
+// Seconds between two pings of the same player
+const PING_INTERVAL_SECS: f64 = 1.0;
+
+// Server plugin for parties: small groups of players sharing a waypoint and pings. Parties only
+// last while their members are online.
+pub struct ServerPartyPlugin;
+
+impl Plugin for ServerPartyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Parties>()
+            .register_command(
+                CommandSpec::new("party", "Invite a player to your party, accept or leave")
+                    .optional_arg("action", ArgKind::Word)
+                    .optional_arg("player", ArgKind::Text),
+            )
+            .add_systems(
+                Update,
+                (handle_party_requests, leave_on_disconnect, send_parties).chain(),
+            );
+    }
+}
+
+struct Party {
+    leader: ClientId,
+    members: Vec<ClientId>,
+    waypoint: Option<(i32, i32)>,
+}
+
+#[derive(Resource, Default)]
+pub struct Parties {
+    parties: HashMap<u64, Party>,
+    member_of: HashMap<ClientId, u64>,
+    // Pending invites, by invited player: the party and when the invite expires
+    invites: HashMap<ClientId, (u64, f64)>,
+    last_ping: HashMap<ClientId, f64>,
+    next_id: u64,
+    // Parties whose members haven't been sent their current state, and players who haven't been
+    // told they are no longer in one
+    changed: HashSet<u64>,
+    removed: HashSet<ClientId>,
+}
+
+impl Parties {
+    /// Everyone in the player's party, the player included
+    pub fn members_of(&self, client_id: ClientId) -> &[ClientId] {
+        self.member_of
+            .get(&client_id)
+            .and_then(|id| self.parties.get(id))
+            .map_or(&[], |party| &party.members)
+    }
+
+    pub fn same_party(&self, a: ClientId, b: ClientId) -> bool {
+        self.member_of
+            .get(&a)
+            .is_some_and(|party| self.member_of.get(&b) == Some(party))
+    }
+
+    fn leave(&mut self, client_id: ClientId) {
+        self.invites.remove(&client_id);
+        self.last_ping.remove(&client_id);
+        let Some(id) = self.member_of.remove(&client_id) else {
+            return;
+        };
+        self.removed.insert(client_id);
+        let Some(party) = self.parties.get_mut(&id) else {
+            return;
+        };
+        party.members.retain(|member| *member != client_id);
+        if party.leader == client_id {
+            if let Some(next) = party.members.first() {
+                party.leader = *next;
+            }
+        }
+        // a party of one is no party
+        if party.members.len() <= 1 {
+            for member in party.members.drain(..) {
+                self.member_of.remove(&member);
+                self.removed.insert(member);
+            }
+            self.parties.remove(&id);
+            self.invites.retain(|_, (party, _)| *party != id);
+        } else {
+            self.changed.insert(id);
+        }
+    }
+
+    fn join(&mut self, client_id: ClientId, id: u64) {
+        let Some(party) = self.parties.get_mut(&id) else {
+            return;
+        };
+        party.members.push(client_id);
+        self.member_of.insert(client_id, id);
+        self.removed.remove(&client_id);
+        self.changed.insert(id);
+    }
+
+    // Party of the player, creating one they lead if they aren't in any
+    fn party_led_or_joined(&mut self, client_id: ClientId) -> u64 {
+        if let Some(id) = self.member_of.get(&client_id) {
+            return *id;
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        self.parties.insert(
+            id,
+            Party {
+                leader: client_id,
+                members: Vec::new(),
+                waypoint: None,
+            },
+        );
+        self.join(client_id, id);
+        id
+    }
+}
+
+// Turn /party commands into the requests clients send, listing the party when no action is given
+fn party_commands(
+    invoked: &mut EventReader<CommandInvoked>,
+    replies: &mut EventWriter<CommandReply>,
+    parties: &Parties,
+    players: &Query<(&PlayerId, &PlayerName, Option<&SocialLists>)>,
+) -> Vec<(ClientId, PartyRequest)> {
+    let mut requests = Vec::new();
+    for command in invoked.read() {
+        if command.name != "party" {
+            continue;
+        }
+        let CommandSource::Client(client_id) = command.source else {
+            replies.send(CommandReply::new(
+                command.source,
+                "Only players can be in a party",
+            ));
+            continue;
+        };
+        let player = command.args.str("player").unwrap_or_default().trim();
+        let request = match command.args.str("action").unwrap_or_default() {
+            "invite" if !player.is_empty() => PartyRequest::Invite {
+                name: player.to_string(),
+            },
+            "accept" => PartyRequest::Accept,
+            "leave" => PartyRequest::Leave,
+            "" => {
+                let members = parties.members_of(client_id);
+                let names: Vec<&str> = players
+                    .iter()
+                    .filter(|(id, _, _)| members.contains(&id.client_id()))
+                    .map(|(_, name, _)| name.0.as_str())
+                    .collect();
+                let reply = if names.is_empty() {
+                    "You aren't in a party, invite someone with /party invite <player>".to_string()
+                } else {
+                    format!("Your party: {}", names.join(", "))
+                };
+                replies.send(CommandReply::new(command.source, reply));
+                continue;
+            }
+            _ => {
+                replies.send(CommandReply::new(
+                    command.source,
+                    "Usage: /party [invite <player> | accept | leave]",
+                ));
+                continue;
+            }
+        };
+        requests.push((client_id, request));
+    }
+    requests
+}
+
+// Requests sent by clients and made with the /party command. Their outcome is told to the player
+// like a command reply.
+#[allow(clippy::too_many_arguments)]
+fn handle_party_requests(
+    mut messages: EventReader<MessageEvent<PartyRequest>>,
+    mut invoked: EventReader<CommandInvoked>,
+    mut replies: EventWriter<CommandReply>,
+    mut parties: ResMut<Parties>,
+    mut connection_manager: ResMut<ConnectionManager>,
+    mut errors: EventWriter<ReportError>,
+    time: Res<Time>,
+    players: Query<(&PlayerId, &PlayerName, Option<&SocialLists>)>,
+) {
+    let mut requests = party_commands(&mut invoked, &mut replies, &parties, &players);
+    requests.extend(
+        messages
+            .read()
+            .map(|event| (event.from(), event.message().clone())),
+    );
+    let now = time.elapsed_secs_f64();
+    parties.invites.retain(|_, (_, expires)| *expires > now);
+    for (client_id, request) in requests {
+        let reply = |text: String| CommandReply::new(CommandSource::Client(client_id), text);
+        match request {
+            PartyRequest::Invite { name } => {
+                let Some((target, target_name, target_social)) = players
+                    .iter()
+                    .find(|(_, player_name, _)| player_name.0.eq_ignore_ascii_case(&name))
+                    .map(|(id, player_name, social)| (id.client_id(), player_name, social))
+                else {
+                    replies.send(reply(format!("No player named {} is online", name)));
+                    continue;
+                };
+                let inviter = players
+                    .iter()
+                    .find(|(id, _, _)| id.client_id() == client_id)
+                    .map_or_else(String::new, |(_, name, _)| name.0.clone());
+                if target == client_id {
+                    replies.send(reply("You can't invite yourself".to_string()));
+                } else if parties.member_of.contains_key(&target) {
+                    replies.send(reply(format!("{} is already in a party", target_name.0)));
+                } else if target_social.is_some_and(|social| social.blocks(client_id)) {
+                    replies.send(reply(format!("{} can't be invited", target_name.0)));
+                } else if parties.members_of(client_id).len() >= MAX_PARTY_SIZE {
+                    replies.send(reply("Your party is full".to_string()));
+                } else {
+                    let party = parties.party_led_or_joined(client_id);
+                    parties.invites.insert(target, (party, now + INVITE_SECS));
+                    if let Err(e) = connection_manager
+                        .send_message::<Channel1, _>(target, &PartyInvite { from: inviter })
+                    {
+                        errors.send(ReportError(GameError::send("PartyInvite", e)));
+                    }
+                    replies.send(reply(format!("Invited {} to your party", target_name.0)));
+                }
+            }
+            PartyRequest::Accept => {
+                let Some((party, _)) = parties.invites.remove(&client_id) else {
+                    replies.send(reply("You have no party invite".to_string()));
+                    continue;
+                };
+                if !parties.parties.contains_key(&party) {
+                    replies.send(reply("That party no longer exists".to_string()));
+                    continue;
+                }
+                if parties.parties[&party].members.len() >= MAX_PARTY_SIZE {
+                    replies.send(reply("That party is full".to_string()));
+                    continue;
+                }
+                parties.leave(client_id);
+                parties.join(client_id, party);
+                replies.send(reply("You joined the party".to_string()));
+            }
+            PartyRequest::Leave => {
+                if parties.member_of.contains_key(&client_id) {
+                    parties.leave(client_id);
+                    replies.send(reply("You left the party".to_string()));
+                } else {
+                    replies.send(reply("You aren't in a party".to_string()));
+                }
+            }
+            PartyRequest::SetWaypoint(waypoint) => {
+                let Some(id) = parties.member_of.get(&client_id).copied() else {
+                    continue;
+                };
+                if let Some(party) = parties.parties.get_mut(&id) {
+                    if party.waypoint != waypoint {
+                        party.waypoint = waypoint;
+                        parties.changed.insert(id);
+                    }
+                }
+            }
+            PartyRequest::Ping { tile } => {
+                if parties
+                    .last_ping
+                    .get(&client_id)
+                    .is_some_and(|last| now - last < PING_INTERVAL_SECS)
+                {
+                    continue;
+                }
+                let members = parties.members_of(client_id).to_vec();
+                if members.is_empty() {
+                    continue;
+                }
+                parties.last_ping.insert(client_id, now);
+                let ping = PartyPing {
+                    from: client_id,
+                    tile,
+                };
+                for member in members {
+                    if let Err(e) = connection_manager.send_message::<Channel1, _>(member, &ping) {
+                        errors.send(ReportError(GameError::send("PartyPing", e)));
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn leave_on_disconnect(
+    mut disconnections: EventReader<DisconnectEvent>,
+    mut parties: ResMut<Parties>,
+) {
+    for disconnection in disconnections.read() {
+        parties.leave(disconnection.client_id);
+    }
+}
+
+// Send their party to the members of parties that changed, and an empty one to players who left
+fn send_parties(
+    mut parties: ResMut<Parties>,
+    mut connection_manager: ResMut<ConnectionManager>,
+    mut errors: EventWriter<ReportError>,
+    players: Query<(&PlayerId, &PlayerName)>,
+) {
+    if parties.changed.is_empty() && parties.removed.is_empty() {
+        return;
+    }
+    let name_of = |client_id: ClientId| {
+        players
+            .iter()
+            .find(|(id, _)| id.client_id() == client_id)
+            .map_or_else(String::new, |(_, name)| name.0.clone())
+    };
+    let mut syncs: Vec<(ClientId, PartySync)> = parties
+        .removed
+        .drain()
+        .map(|client_id| (client_id, PartySync::default()))
+        .collect();
+    let changed: Vec<u64> = parties.changed.drain().collect();
+    for id in changed {
+        let Some(party) = parties.parties.get(&id) else {
+            continue;
+        };
+        let sync = PartySync {
+            leader: Some(party.leader),
+            members: party
+                .members
+                .iter()
+                .map(|client_id| PartyMember {
+                    client_id: *client_id,
+                    name: name_of(*client_id),
+                })
+                .collect(),
+            waypoint: party.waypoint,
+        };
+        syncs.extend(party.members.iter().map(|member| (*member, sync.clone())));
+    }
+    for (client_id, sync) in syncs {
+        if let Err(e) = connection_manager.send_message::<Channel1, _>(client_id, &sync) {
+            errors.send(ReportError(GameError::send("PartySync", e)));
+        }
+    }
+}
@@ -0,0 +1,280 @@
+use bevy::prelude::*;
+use bevy::time::common_conditions::on_timer;
+use bevy::utils::Duration;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::server::plugins::TileModifiedEvent;
+use crate::shared::pathfinding::{LoadedTiles, NavGraph, TilePos};
+use crate::shared::world_generation::{Chunk, ChunkCoord, WorldConfig, WorldState};
+
+// Paths computed per tick; requests beyond that wait for the next ticks
+const PATHS_PER_TICK: usize = 8;
+// Chunks added to the graph per tick, when many are loaded at once
+const CHUNKS_PER_TICK: usize = 4;
+// Cached paths kept before the cache is cleared
+const CACHE_CAPACITY: usize = 1024;
+// How often pathfinding statistics are logged
+const STATS_LOG_INTERVAL: Duration = Duration::from_secs(60);
+
+// Server plugin keeping the chunk graph up to date and computing the paths asked for, a few per
+// tick, from a cache when possible
+pub struct ServerPathfindingPlugin;
+
+impl Plugin for ServerPathfindingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<NavChangedEvent>()
+            .init_resource::<NavGraph>()
+            .init_resource::<NavChunks>()
+            .init_resource::<PathCache>()
+            .init_resource::<PathQueue>()
+            .init_resource::<PathfindingStats>()
+            .add_systems(
+                Update,
+                (
+                    update_nav_chunks,
+                    update_modified_tiles,
+                    queue_path_requests,
+                    compute_paths,
+                    log_pathfinding_stats.run_if(on_timer(STATS_LOG_INTERVAL)),
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Asks for a path between two overworld tiles. Replaced by a `PathResult` once computed.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct PathRequest {
+    pub from: TilePos,
+    pub to: TilePos,
+}
+
+/// Tiles to walk through, `from` and `to` included, or `None` if `to` can't be reached
+#[derive(Component, Clone, Debug, PartialEq)]
+pub struct PathResult(pub Option<Vec<TilePos>>);
+
+/// Sent when the graph changed in some chunks, for walkers to find new paths through them
+#[derive(Event, Clone, Debug)]
+pub struct NavChangedEvent {
+    pub chunks: HashSet<ChunkCoord>,
+}
+
+#[derive(Resource, Default, Debug, Clone)]
+pub struct PathfindingStats {
+    pub requests: u64,
+    pub cache_hits: u64,
+    pub failed: u64,
+    /// Requests left waiting at the end of a tick, summed over ticks
+    pub deferred: u64,
+    /// Tiles and graph nodes searched through
+    pub expansions: u64,
+    pub chunk_updates: u64,
+    pub compute_time: Duration,
+}
+
+// Paths already computed, by (from, to). Paths are dropped when a chunk they go through changes;
+// paths that weren't found when anything changes.
+#[derive(Resource, Default)]
+struct PathCache {
+    paths: HashMap<(TilePos, TilePos), Vec<TilePos>>,
+    by_chunk: HashMap<ChunkCoord, HashSet<(TilePos, TilePos)>>,
+    failed: HashSet<(TilePos, TilePos)>,
+}
+
+impl PathCache {
+    fn get(&self, key: (TilePos, TilePos)) -> Option<Option<Vec<TilePos>>> {
+        if self.failed.contains(&key) {
+            return Some(None);
+        }
+        self.paths.get(&key).cloned().map(Some)
+    }
+
+    fn insert(&mut self, graph: &NavGraph, key: (TilePos, TilePos), path: Option<Vec<TilePos>>) {
+        if self.paths.len() + self.failed.len() >= CACHE_CAPACITY {
+            *self = PathCache::default();
+        }
+        let Some(path) = path else {
+            self.failed.insert(key);
+            return;
+        };
+        for tile in path.iter() {
+            let coord = graph.grid().tile_to_chunk(*tile);
+            self.by_chunk.entry(coord).or_default().insert(key);
+        }
+        self.paths.insert(key, path);
+    }
+
+    fn invalidate(&mut self, coord: ChunkCoord) {
+        for key in self.by_chunk.remove(&coord).unwrap_or_default() {
+            self.paths.remove(&key);
+        }
+        self.failed.clear();
+    }
+}
+
+// Chunk entities the graph was built from, so chunks generated again are rebuilt
+#[derive(Resource, Default)]
+struct NavChunks(HashMap<ChunkCoord, Entity>);
+
+// Requests in the order they came in
+#[derive(Resource, Default)]
+struct PathQueue(VecDeque<Entity>);
+
+// Add loaded chunks to the graph, and take unloaded ones out of it
+fn update_nav_chunks(
+    mut graph: ResMut<NavGraph>,
+    mut nav_chunks: ResMut<NavChunks>,
+    mut cache: ResMut<PathCache>,
+    mut stats: ResMut<PathfindingStats>,
+    mut changes: EventWriter<NavChangedEvent>,
+    world_state: Res<WorldState>,
+    world_config: Res<WorldConfig>,
+    chunks: Query<&Chunk>,
+) {
+    let mut changed = HashSet::new();
+    let unloaded: Vec<ChunkCoord> = nav_chunks
+        .0
+        .keys()
+        .filter(|coord| !world_state.chunks.contains_key(coord))
+        .copied()
+        .collect();
+    for coord in unloaded {
+        nav_chunks.0.remove(&coord);
+        graph.remove_chunk(coord);
+        cache.invalidate(coord);
+        changed.insert(coord);
+    }
+
+    let loaded: Vec<(ChunkCoord, Entity)> = world_state
+        .chunks
+        .iter()
+        .filter(|(coord, entity)| nav_chunks.0.get(coord) != Some(entity))
+        .take(CHUNKS_PER_TICK)
+        .map(|(coord, entity)| (*coord, *entity))
+        .collect();
+    let tiles = LoadedTiles {
+        world_state: &world_state,
+        grid: world_config.grid(),
+        chunks: &chunks,
+    };
+    for (coord, entity) in loaded {
+        nav_chunks.0.insert(coord, entity);
+        graph.update_chunk(coord, &tiles, &mut stats.expansions);
+        cache.invalidate(coord);
+        stats.chunk_updates += 1;
+        changed.insert(coord);
+    }
+    if !changed.is_empty() {
+        changes.send(NavChangedEvent { chunks: changed });
+    }
+}
+
+// Update the graph around modified tiles: walls raised or dug away, structures built or broken
+fn update_modified_tiles(
+    mut modifications: EventReader<TileModifiedEvent>,
+    mut graph: ResMut<NavGraph>,
+    mut cache: ResMut<PathCache>,
+    mut stats: ResMut<PathfindingStats>,
+    mut changes: EventWriter<NavChangedEvent>,
+    world_state: Res<WorldState>,
+    world_config: Res<WorldConfig>,
+    chunks: Query<&Chunk>,
+) {
+    let modified: HashSet<TilePos> = modifications.read().map(|event| event.position).collect();
+    if modified.is_empty() {
+        return;
+    }
+    let tiles = LoadedTiles {
+        world_state: &world_state,
+        grid: world_config.grid(),
+        chunks: &chunks,
+    };
+    let mut changed = graph.update_tiles(modified.iter().copied(), &tiles, &mut stats.expansions);
+    // paths through the modified tiles themselves may be blocked, even if no links changed
+    changed.extend(
+        modified
+            .iter()
+            .map(|tile| graph.grid().tile_to_chunk(*tile)),
+    );
+    for coord in changed.iter() {
+        cache.invalidate(*coord);
+    }
+    stats.chunk_updates += changed.len() as u64;
+    changes.send(NavChangedEvent { chunks: changed });
+}
+
+fn queue_path_requests(mut queue: ResMut<PathQueue>, requests: Query<Entity, Added<PathRequest>>) {
+    queue.0.extend(requests.iter());
+}
+
+fn compute_paths(
+    mut commands: Commands,
+    mut queue: ResMut<PathQueue>,
+    mut cache: ResMut<PathCache>,
+    mut stats: ResMut<PathfindingStats>,
+    graph: Res<NavGraph>,
+    world_state: Res<WorldState>,
+    world_config: Res<WorldConfig>,
+    chunks: Query<&Chunk>,
+    requests: Query<&PathRequest>,
+) {
+    let tiles = LoadedTiles {
+        world_state: &world_state,
+        grid: world_config.grid(),
+        chunks: &chunks,
+    };
+    let mut computed = 0;
+    while computed < PATHS_PER_TICK {
+        let Some(entity) = queue.0.pop_front() else {
+            break;
+        };
+        // the requester may be gone, or have asked for something else meanwhile
+        let Ok(request) = requests.get(entity) else {
+            continue;
+        };
+        stats.requests += 1;
+        let key = (request.from, request.to);
+        let path = match cache.get(key) {
+            Some(path) => {
+                stats.cache_hits += 1;
+                path
+            }
+            None => {
+                computed += 1;
+                let start = std::time::Instant::now();
+                let path = graph.find_path(&tiles, request.from, request.to, &mut stats.expansions);
+                stats.compute_time += start.elapsed();
+                cache.insert(&graph, key, path.clone());
+                path
+            }
+        };
+        if path.is_none() {
+            stats.failed += 1;
+        }
+        commands
+            .entity(entity)
+            .remove::<PathRequest>()
+            .insert(PathResult(path));
+    }
+    stats.deferred += queue.0.len() as u64;
+}
+
+fn log_pathfinding_stats(graph: Res<NavGraph>, stats: Res<PathfindingStats>) {
+    let (chunks, entrances) = graph.size();
+    info!(
+        "Pathfinding: {} chunks, {} entrances; {} requests, {} from cache, {} failed, {} deferred, \
+         {} expansions, {} chunk updates, {:?} spent",
+        chunks,
+        entrances,
+        stats.requests,
+        stats.cache_hits,
+        stats.failed,
+        stats.deferred,
+        stats.expansions,
+        stats.chunk_updates,
+        stats.compute_time
+    );
+}
+
+#[cfg(test)]
+mod tests;
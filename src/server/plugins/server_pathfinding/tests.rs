@@ -0,0 +1,84 @@
+//! Cached paths must be dropped as soon as the tiles they go through are modified
+use super::*;
+use crate::shared::world_generation::{
+    BiomeType, Decoration, Overlay, ResourceType, Tile, TileType,
+};
+
+const CHUNK_SIZE: usize = 8;
+
+fn app() -> App {
+    let mut app = App::new();
+    app.init_resource::<Time>()
+        .insert_resource(WorldConfig {
+            chunk_size: CHUNK_SIZE,
+            ..default()
+        })
+        .init_resource::<WorldState>()
+        .add_event::<TileModifiedEvent>()
+        .add_plugins(ServerPathfindingPlugin);
+
+    let coord = ChunkCoord { x: 0, y: 0 };
+    let tiles = (0..CHUNK_SIZE as i32)
+        .flat_map(|y| (0..CHUNK_SIZE as i32).map(move |x| (x, y)))
+        .map(|position| Tile {
+            tile_type: TileType::Grass,
+            decoration: Decoration::None,
+            resource: ResourceType::None,
+            overlay: Overlay::None,
+            height: 0.0,
+            position,
+            traversable: true,
+            damage: 0,
+        })
+        .collect();
+    let chunk = app
+        .world_mut()
+        .spawn(Chunk::new(coord, CHUNK_SIZE, tiles, BiomeType::Plains, 0.0))
+        .id();
+    app.world_mut()
+        .resource_mut::<WorldState>()
+        .chunks
+        .insert(coord, chunk);
+    app.update();
+    app
+}
+
+fn request_path(app: &mut App, from: TilePos, to: TilePos) -> Option<Vec<TilePos>> {
+    let requester = app.world_mut().spawn(PathRequest { from, to }).id();
+    app.update();
+    let result = app.world().get::<PathResult>(requester).cloned();
+    result.expect("path computed within a tick").0
+}
+
+fn cache_hits(app: &App) -> u64 {
+    app.world().resource::<PathfindingStats>().cache_hits
+}
+
+#[test]
+fn modified_tiles_invalidate_cached_paths() {
+    let mut app = app();
+    let (from, to) = ((0, 0), (CHUNK_SIZE as i32 - 1, 0));
+
+    let path = request_path(&mut app, from, to);
+    assert_eq!(path.as_ref().map(Vec::len), Some(CHUNK_SIZE));
+    assert_eq!(request_path(&mut app, from, to), path);
+    assert_eq!(cache_hits(&app), 1);
+
+    // wall the chunk off from side to side
+    let mut chunks = app.world_mut().query::<&mut Chunk>();
+    let mut chunk = chunks.single_mut(app.world_mut());
+    for y in 0..CHUNK_SIZE {
+        chunk.modify_tile(3, y, |tile| tile.traversable = false);
+    }
+    for y in 0..CHUNK_SIZE as i32 {
+        app.world_mut().send_event(TileModifiedEvent {
+            position: (3, y),
+            author: None,
+            before: (TileType::Grass, ResourceType::None),
+            after: (TileType::Grass, ResourceType::None),
+        });
+    }
+
+    assert_eq!(request_path(&mut app, from, to), None);
+    assert_eq!(cache_hits(&app), 1);
+}
@@ -0,0 +1,450 @@
+use bevy::prelude::*;
+use bevy::time::common_conditions::on_timer;
+use bevy::utils::Duration;
+use lightyear::prelude::server::*;
+use lightyear::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::protocol::{Channel1, PlayerId, PlayerPosition};
+use crate::server::plugins::{entrance_tile, RESPAWN_POSITION};
+use crate::shared::commands::{
+    CommandInvoked, CommandReply, CommandSource, CommandSpec, PermissionLevel, RegisterCommandExt,
+};
+use crate::shared::error::{GameError, ReportError};
+use crate::shared::instances::InstanceId;
+use crate::shared::portals::{DiscoveredPortals, Portal, PortalLink, UsePortal, PORTAL_REACH};
+use crate::shared::structures::StructureMap;
+use crate::shared::world_generation::{
+    seeded_hash, tile_distance, Chunk, ChunkCoord, ChunkRequestEvent, WorldConfig, WorldGrid,
+    WorldState,
+};
+
+// One in this many overworld chunks has a portal to the hub
+const PORTAL_RARITY: u64 = 16;
+// Seconds before a player can use a portal again
+const PORTAL_COOLDOWN_SECS: f64 = 10.0;
+// Players discover the portals within this many tiles of them
+const DISCOVER_RADIUS: u32 = 6;
+const DISCOVER_INTERVAL: Duration = Duration::from_secs(1);
+// Chunks around the destination generated before the player is moved there
+const PRELOAD_RADIUS: i32 = 1;
+// Seconds after which a teleport whose destination didn't load is given up
+const PRELOAD_TIMEOUT_SECS: f64 = 10.0;
+
+// Server plugin for the portal network: generated portals lead to a hub next to the spawn point,
+// which leads to any portal a player discovered; admins can also place linked pairs
+pub struct ServerPortalsPlugin;
+
+impl Plugin for ServerPortalsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PortalNetwork>()
+            .register_command(
+                CommandSpec::new(
+                    "portal",
+                    "Place a portal; every second portal is linked to the one before it",
+                )
+                .permission(PermissionLevel::Admin),
+            )
+            .add_systems(Startup, place_hub)
+            .add_systems(
+                Update,
+                (
+                    place_generated_portals,
+                    remove_unloaded_portals,
+                    handle_portal_command,
+                    handle_portal_use,
+                    complete_teleports,
+                    discover_portals.run_if(on_timer(DISCOVER_INTERVAL)),
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Every portal of the world, loaded or not, and who discovered which
+#[derive(Resource, Default)]
+pub struct PortalNetwork {
+    pub portals: HashMap<(i32, i32), PortalLink>,
+    pub discovered: HashMap<ClientId, HashSet<(i32, i32)>>,
+    // Entities of the portals standing in loaded chunks
+    entities: HashMap<(i32, i32), Entity>,
+    // Overworld chunks that were checked for a generated portal, with the portal if they have one
+    generated: HashMap<ChunkCoord, Option<(i32, i32)>>,
+    // The portal each admin placed last, waiting for its pair
+    unpaired: HashMap<ClientId, (i32, i32)>,
+}
+
+impl PortalNetwork {
+    pub fn hub(&self) -> Option<(i32, i32)> {
+        self.portals
+            .iter()
+            .find(|(_, link)| **link == PortalLink::Hub)
+            .map(|(tile, _)| *tile)
+    }
+
+    /// Where the portal at `portal` takes a player who discovered `discovered`
+    pub fn destination(
+        &self,
+        portal: (i32, i32),
+        requested: Option<(i32, i32)>,
+        discovered: &HashSet<(i32, i32)>,
+    ) -> Result<(i32, i32), PortalError> {
+        match self.portals.get(&portal) {
+            None => Err(PortalError::NoPortal),
+            Some(PortalLink::Unlinked) => Err(PortalError::Unlinked),
+            Some(PortalLink::Pair(other)) => Ok(*other),
+            Some(PortalLink::ToHub) => self.hub().ok_or(PortalError::Unlinked),
+            Some(PortalLink::Hub) => {
+                let destination = requested.ok_or(PortalError::NoDestination)?;
+                if destination == portal || !self.portals.contains_key(&destination) {
+                    Err(PortalError::NoDestination)
+                } else if !discovered.contains(&destination) {
+                    Err(PortalError::Undiscovered)
+                } else {
+                    Ok(destination)
+                }
+            }
+        }
+    }
+
+    fn spawn(&mut self, commands: &mut Commands, tile: (i32, i32), link: PortalLink) {
+        self.portals.insert(tile, link);
+        let entity = commands
+            .spawn((Portal { tile, link }, Replicate::default()))
+            .id();
+        self.entities.insert(tile, entity);
+    }
+
+    // Change where a portal leads, updating its entity if it's loaded
+    fn link(&mut self, commands: &mut Commands, tile: (i32, i32), link: PortalLink) {
+        self.portals.insert(tile, link);
+        if let Some(entity) = self.entities.get(&tile) {
+            commands.entity(*entity).insert(Portal { tile, link });
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PortalError {
+    NoPortal,
+    OutOfReach,
+    // Seconds left before the player can travel again
+    Cooldown(f64),
+    Traveling,
+    Unlinked,
+    NoDestination,
+    Undiscovered,
+}
+
+impl fmt::Display for PortalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PortalError::NoPortal => write!(f, "There is no portal there"),
+            PortalError::OutOfReach => write!(f, "That portal is too far away"),
+            PortalError::Cooldown(secs) => {
+                write!(f, "You can use a portal again in {:.0}s", secs.ceil())
+            }
+            PortalError::Traveling => write!(f, "You are already traveling"),
+            PortalError::Unlinked => write!(f, "This portal doesn't lead anywhere yet"),
+            PortalError::NoDestination => write!(f, "Pick a destination on the map first"),
+            PortalError::Undiscovered => write!(f, "You haven't discovered that portal"),
+        }
+    }
+}
+
+/// Players can't use portals again until `until` (in `Time::elapsed_secs_f64`)
+#[derive(Component, Clone, Copy, Debug)]
+pub struct PortalCooldown {
+    pub until: f64,
+}
+
+/// A player on their way through a portal, waiting for the chunks around `destination`
+#[derive(Component, Clone, Debug)]
+pub struct PendingTeleport {
+    pub destination: (i32, i32),
+    pub chunks: Vec<ChunkCoord>,
+    pub started_at: f64,
+}
+
+// The hub stands next to the respawn point, so everyone finds it
+fn place_hub(mut commands: Commands, mut network: ResMut<PortalNetwork>) {
+    let (x, y) = WorldGrid::world_to_tile(RESPAWN_POSITION);
+    let tile = (x + 2, y);
+    network.spawn(&mut commands, tile, PortalLink::Hub);
+}
+
+// Give some newly generated overworld chunks a portal. Where they go only depends on the seed,
+// so an unloaded chunk gets the same portal back when it's generated again.
+fn place_generated_portals(
+    mut commands: Commands,
+    mut network: ResMut<PortalNetwork>,
+    world_config: Res<WorldConfig>,
+    structures: Res<StructureMap>,
+    chunks: Query<&Chunk, (Added<Chunk>, Without<InstanceId>)>,
+) {
+    for chunk in chunks.iter() {
+        let tile = match network.generated.get(&chunk.coord) {
+            Some(tile) => *tile,
+            None => {
+                let hash = seeded_hash(world_config.seed, ("portal", chunk.coord));
+                let tile = if hash % PORTAL_RARITY == 0 {
+                    // the first dry tile from a seeded starting point, leaving the chunk's
+                    // dungeon entrance alone
+                    let entrance = entrance_tile(world_config.seed, chunk, &structures);
+                    chunk.first_dry_tile((hash / PORTAL_RARITY) as usize, &structures, entrance)
+                } else {
+                    None
+                };
+                network.generated.insert(chunk.coord, tile);
+                tile
+            }
+        };
+        if let Some(tile) = tile.filter(|tile| !network.entities.contains_key(tile)) {
+            network.spawn(&mut commands, tile, PortalLink::ToHub);
+        }
+    }
+}
+
+// Generated portals disappear with their chunk, but stay in the network so they can still be
+// travelled to
+fn remove_unloaded_portals(
+    mut commands: Commands,
+    mut network: ResMut<PortalNetwork>,
+    world_state: Res<WorldState>,
+    world_config: Res<WorldConfig>,
+) {
+    let PortalNetwork {
+        portals, entities, ..
+    } = &mut *network;
+    entities.retain(|tile, entity| {
+        let coord = world_config.grid().tile_to_chunk(tile);
+        let keep = portals.get(tile) != Some(&PortalLink::ToHub)
+            || world_state.chunks.contains_key(&coord);
+        if !keep {
+            commands.entity(*entity).despawn();
+        }
+        keep
+    });
+}
+
+fn handle_portal_command(
+    mut commands: Commands,
+    mut invoked: EventReader<CommandInvoked>,
+    mut replies: EventWriter<CommandReply>,
+    mut network: ResMut<PortalNetwork>,
+    players: Query<(&PlayerId, &PlayerPosition), Without<InstanceId>>,
+) {
+    for command in invoked.read().filter(|c| c.name == "portal") {
+        let CommandSource::Client(client_id) = command.source else {
+            replies.send(CommandReply::new(
+                command.source,
+                "Only players can place portals",
+            ));
+            continue;
+        };
+        let Some((_, position)) = players.iter().find(|(id, _)| id.client_id() == client_id) else {
+            continue;
+        };
+        let tile = WorldGrid::world_to_tile(position.0);
+        if network.portals.contains_key(&tile) {
+            replies.send(CommandReply::new(
+                command.source,
+                "There is already a portal here",
+            ));
+            continue;
+        }
+        let reply = match network.unpaired.remove(&client_id) {
+            Some(other) if network.portals.contains_key(&other) => {
+                network.spawn(&mut commands, tile, PortalLink::Pair(other));
+                network.link(&mut commands, other, PortalLink::Pair(tile));
+                format!("Linked this portal to the one at {:?}", other)
+            }
+            _ => {
+                network.spawn(&mut commands, tile, PortalLink::Unlinked);
+                network.unpaired.insert(client_id, tile);
+                "Placed a portal, place another one to link them".to_string()
+            }
+        };
+        replies.send(CommandReply::new(command.source, reply));
+    }
+}
+
+// Validate portal uses, then start loading the destination
+fn handle_portal_use(
+    mut commands: Commands,
+    mut events: EventReader<MessageEvent<UsePortal>>,
+    mut replies: EventWriter<CommandReply>,
+    mut chunk_requests: EventWriter<ChunkRequestEvent>,
+    time: Res<Time>,
+    network: Res<PortalNetwork>,
+    world_state: Res<WorldState>,
+    world_config: Res<WorldConfig>,
+    players: Query<
+        (
+            Entity,
+            &PlayerId,
+            &PlayerPosition,
+            Option<&PortalCooldown>,
+            Has<PendingTeleport>,
+        ),
+        Without<InstanceId>,
+    >,
+) {
+    let now = time.elapsed_secs_f64();
+    for event in events.read() {
+        let client_id = event.from();
+        let UsePortal {
+            portal,
+            destination,
+        } = *event.message();
+        let Some((entity, _, position, cooldown, traveling)) = players
+            .iter()
+            .find(|(_, id, _, _, _)| id.client_id() == client_id)
+        else {
+            continue;
+        };
+
+        let player_tile = WorldGrid::world_to_tile(position.0);
+        let no_discoveries = HashSet::new();
+        let discovered = network
+            .discovered
+            .get(&client_id)
+            .unwrap_or(&no_discoveries);
+        let result = if traveling {
+            Err(PortalError::Traveling)
+        } else if let Some(cooldown) = cooldown.filter(|cooldown| cooldown.until > now) {
+            Err(PortalError::Cooldown(cooldown.until - now))
+        } else if network.portals.contains_key(&portal)
+            && tile_distance(player_tile, portal) > PORTAL_REACH
+        {
+            Err(PortalError::OutOfReach)
+        } else {
+            network.destination(portal, destination, discovered)
+        };
+        let destination = match result {
+            Ok(destination) => destination,
+            Err(e) => {
+                debug!("Rejected portal use by {:?}: {:?}", client_id, e);
+                replies.send(CommandReply::new(
+                    CommandSource::Client(client_id),
+                    e.to_string(),
+                ));
+                continue;
+            }
+        };
+
+        let center = world_config.grid().tile_to_chunk(destination);
+        let chunks: Vec<ChunkCoord> = (-PRELOAD_RADIUS..=PRELOAD_RADIUS)
+            .flat_map(|y| {
+                (-PRELOAD_RADIUS..=PRELOAD_RADIUS).map(move |x| ChunkCoord {
+                    x: center.x + x,
+                    y: center.y + y,
+                })
+            })
+            .filter(|coord| coord.is_in_world(world_config.chunk_size))
+            .collect();
+        for coord in chunks
+            .iter()
+            .filter(|c| !world_state.chunks.contains_key(c))
+        {
+            chunk_requests.send(ChunkRequestEvent {
+                coord: *coord,
+                client_id: None,
+            });
+        }
+        info!(
+            "Client {:?} travels from portal {:?} to {:?}",
+            client_id, portal, destination
+        );
+        commands.entity(entity).insert((
+            PortalCooldown {
+                until: now + PORTAL_COOLDOWN_SECS,
+            },
+            PendingTeleport {
+                destination,
+                chunks,
+                started_at: now,
+            },
+        ));
+    }
+}
+
+// Move travelling players once the chunks around their destination exist
+fn complete_teleports(
+    mut commands: Commands,
+    mut replies: EventWriter<CommandReply>,
+    time: Res<Time>,
+    world_state: Res<WorldState>,
+    mut players: Query<(Entity, &PlayerId, &mut PlayerPosition, &PendingTeleport)>,
+) {
+    let now = time.elapsed_secs_f64();
+    for (entity, player_id, mut position, teleport) in players.iter_mut() {
+        if teleport
+            .chunks
+            .iter()
+            .all(|coord| world_state.chunks.contains_key(coord))
+        {
+            position.0 = WorldGrid::tile_to_world(teleport.destination);
+            commands.entity(entity).remove::<PendingTeleport>();
+        } else if now - teleport.started_at > PRELOAD_TIMEOUT_SECS {
+            warn!(
+                "Destination {:?} of {:?} didn't load, cancelling the teleport",
+                teleport.destination,
+                player_id.client_id()
+            );
+            replies.send(CommandReply::new(
+                CommandSource::Client(player_id.client_id()),
+                "The portal flickers and nothing happens",
+            ));
+            commands.entity(entity).remove::<PendingTeleport>();
+        }
+    }
+}
+
+// Players discover the portals they walk by, and always know the hub
+fn discover_portals(
+    mut network: ResMut<PortalNetwork>,
+    mut connection_manager: ResMut<ConnectionManager>,
+    players: Query<(&PlayerId, &PlayerPosition), Without<InstanceId>>,
+    mut errors: EventWriter<ReportError>,
+) {
+    let hub = network.hub();
+    let PortalNetwork {
+        portals,
+        discovered,
+        ..
+    } = &mut *network;
+    for (player_id, position) in players.iter() {
+        let client_id = player_id.client_id();
+        let player_tile = WorldGrid::world_to_tile(position.0);
+        let known = discovered.entry(client_id).or_default();
+        let before = known.len();
+        known.extend(
+            portals
+                .keys()
+                .filter(|tile| {
+                    Some(**tile) == hub || tile_distance(**tile, player_tile) <= DISCOVER_RADIUS
+                })
+                .copied(),
+        );
+        if known.len() == before {
+            continue;
+        }
+        let mut list: Vec<Portal> = known
+            .iter()
+            .filter_map(|tile| {
+                portals.get(tile).map(|link| Portal {
+                    tile: *tile,
+                    link: *link,
+                })
+            })
+            .collect();
+        list.sort_by_key(|portal| portal.tile);
+        if let Err(e) =
+            connection_manager.send_message::<Channel1, _>(client_id, &DiscoveredPortals(list))
+        {
+            errors.send(ReportError(GameError::send("DiscoveredPortals", e)));
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use bevy::prelude::*;
+use lightyear::prelude::server::*;
+use lightyear::prelude::*;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::server::plugins::{TileModifiedEvent, WarmChunks};
+use crate::shared::instances::InstanceId;
+use crate::shared::power::PowerState;
+use crate::shared::world_generation::{Chunk, ChunkCoord, Decoration, WorldConfig, WorldState};
+
+// Power a generator puts into its network, and power an assembler draws from it
+const GENERATOR_OUTPUT: u32 = 4;
+const ASSEMBLER_DRAW: u32 = 2;
+
+// Server plugin for power networks: generators, wires and assemblers touching each other form a
+// network, solved again whenever one of them is built, dug away, loaded or forgotten
+pub struct ServerPowerPlugin;
+
+impl Plugin for ServerPowerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PowerGrid>()
+            .init_resource::<PowerEntities>()
+            .add_systems(
+                Update,
+                (
+                    load_chunk_nodes,
+                    track_nodes,
+                    forget_cold_nodes,
+                    solve_networks,
+                    replicate_power,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Power nodes of loaded and warm chunks, and the assemblers they power. A network whose
+/// generators put out at least what its assemblers draw powers all of them; one that falls short
+/// powers none.
+#[derive(Resource, Default)]
+pub struct PowerGrid {
+    nodes: HashMap<(i32, i32), Decoration>,
+    chunks: HashMap<ChunkCoord, HashSet<(i32, i32)>>,
+    powered: HashSet<(i32, i32)>,
+    // Whether nodes changed since networks were last solved
+    dirty: bool,
+}
+
+impl PowerGrid {
+    pub fn is_powered(&self, tile: (i32, i32)) -> bool {
+        self.powered.contains(&tile)
+    }
+
+    // Add, replace or remove the node on a tile, returning whether it changed
+    fn set(&mut self, coord: ChunkCoord, tile: (i32, i32), decoration: Decoration) -> bool {
+        let changed = if decoration.is_power_node() {
+            self.chunks.entry(coord).or_default().insert(tile);
+            self.nodes.insert(tile, decoration) != Some(decoration)
+        } else {
+            if let Some(tiles) = self.chunks.get_mut(&coord) {
+                tiles.remove(&tile);
+            }
+            self.nodes.remove(&tile).is_some()
+        };
+        self.dirty |= changed;
+        changed
+    }
+}
+
+// Entities replicating the power state of the assemblers of loaded chunks
+#[derive(Resource, Default)]
+struct PowerEntities(HashMap<(i32, i32), Entity>);
+
+fn load_chunk_nodes(
+    mut grid: ResMut<PowerGrid>,
+    // nothing is built in instances
+    chunks: Query<&Chunk, (Added<Chunk>, Without<InstanceId>)>,
+) {
+    for chunk in chunks.iter() {
+        for tile in chunk.tiles() {
+            grid.set(chunk.coord, tile.position, tile.decoration);
+        }
+    }
+}
+
+fn track_nodes(
+    mut modifications: EventReader<TileModifiedEvent>,
+    mut grid: ResMut<PowerGrid>,
+    world_state: Res<WorldState>,
+    world_config: Res<WorldConfig>,
+    chunks: Query<&Chunk>,
+) {
+    let world_grid = world_config.grid();
+    for modification in modifications.read() {
+        let coord = world_grid.tile_to_chunk(modification.position);
+        let (local_x, local_y) = world_grid.tile_to_local(modification.position);
+        let Some(chunk) = world_state
+            .chunks
+            .get(&coord)
+            .and_then(|entity| chunks.get(*entity).ok())
+        else {
+            continue;
+        };
+        let Some(tile) = chunk.get_tile(local_x, local_y) else {
+            continue;
+        };
+        grid.set(coord, tile.position, tile.decoration);
+    }
+}
+
+// Networks running through chunks that are neither loaded nor warm anymore lose those nodes
+fn forget_cold_nodes(
+    mut grid: ResMut<PowerGrid>,
+    world_state: Res<WorldState>,
+    warm: Res<WarmChunks>,
+) {
+    let cold: Vec<ChunkCoord> = grid
+        .chunks
+        .keys()
+        .filter(|coord| !world_state.chunks.contains_key(coord) && !warm.is_warm(**coord))
+        .copied()
+        .collect();
+    for coord in cold {
+        let Some(tiles) = grid.chunks.remove(&coord) else {
+            continue;
+        };
+        for tile in tiles {
+            grid.nodes.remove(&tile);
+        }
+        grid.dirty = true;
+    }
+}
+
+// Split the nodes into connected networks, and power the assemblers of those with enough supply
+fn solve_networks(mut grid: ResMut<PowerGrid>) {
+    if !grid.dirty {
+        return;
+    }
+    let grid = &mut *grid;
+    grid.dirty = false;
+    grid.powered.clear();
+    let mut seen = HashSet::new();
+    for start in grid.nodes.keys() {
+        if !seen.insert(*start) {
+            continue;
+        }
+        let mut network = Vec::new();
+        let mut queue = VecDeque::from([*start]);
+        while let Some((x, y)) = queue.pop_front() {
+            network.push((x, y));
+            for next in [(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)] {
+                if grid.nodes.contains_key(&next) && seen.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+        let count = |decoration: Decoration| {
+            network
+                .iter()
+                .filter(|tile| grid.nodes[*tile] == decoration)
+                .count() as u32
+        };
+        let supply = count(Decoration::Generator) * GENERATOR_OUTPUT;
+        let demand = count(Decoration::Assembler) * ASSEMBLER_DRAW;
+        if demand > 0 && supply >= demand {
+            grid.powered.extend(
+                network
+                    .iter()
+                    .filter(|tile| grid.nodes[*tile] == Decoration::Assembler),
+            );
+        }
+    }
+}
+
+// Keep one replicated power state per assembler of the loaded chunks
+fn replicate_power(
+    mut commands: Commands,
+    grid: Res<PowerGrid>,
+    mut entities: ResMut<PowerEntities>,
+    world_state: Res<WorldState>,
+    world_config: Res<WorldConfig>,
+    mut states: Query<&mut PowerState>,
+) {
+    let world_grid = world_config.grid();
+    let visible = |tile: &(i32, i32)| {
+        grid.nodes.get(tile) == Some(&Decoration::Assembler)
+            && world_state
+                .chunks
+                .contains_key(&world_grid.tile_to_chunk(*tile))
+    };
+    entities.0.retain(|tile, entity| {
+        let keep = visible(tile);
+        if !keep {
+            commands.entity(*entity).despawn();
+        }
+        keep
+    });
+    for tile in grid.nodes.keys().filter(|tile| visible(tile)) {
+        let powered = grid.is_powered(*tile);
+        match entities.0.get(tile) {
+            Some(entity) => {
+                if let Ok(mut state) = states.get_mut(*entity) {
+                    if state.powered != powered {
+                        state.powered = powered;
+                    }
+                }
+            }
+            None => {
+                let entity = commands
+                    .spawn((
+                        PowerState {
+                            tile: *tile,
+                            powered,
+                        },
+                        Replicate {
+                            sync: SyncTarget {
+                                interpolation: NetworkTarget::All,
+                                ..default()
+                            },
+                            ..default()
+                        },
+                    ))
+                    .id();
+                entities.0.insert(*tile, entity);
+            }
+        }
+    }
+}
@@ -0,0 +1,240 @@
+use bevy::prelude::*;
+use bevy::tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::server::plugins::{ChunkStore, PendingSaves};
+use crate::shared::commands::{
+    ArgKind, CommandInvoked, CommandReply, CommandSource, CommandSpec, PermissionLevel,
+    RegisterCommandExt,
+};
+use crate::shared::error::{GameError, ReportError};
+use crate::shared::frame_pacing::FrameLoad;
+use crate::shared::world_generation::{build_chunk, ChunkCoord, WorldConfig, WorldState};
+
+// Chunks being generated at the same time; keeps worker threads available for the live server
+const MAX_IN_FLIGHT: usize = 4;
+// How often progress is reported to whoever started the pre-generation
+const REPORT_INTERVAL: Duration = Duration::from_secs(5);
+// Largest accepted radius, in chunks
+const MAX_RADIUS: i32 = 256;
+
+// Server plugin pre-generating and persisting the chunks around spawn in the background
+pub struct ServerPregenPlugin;
+
+impl Plugin for ServerPregenPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Pregen>()
+            .register_command(
+                CommandSpec::new(
+                    "pregen",
+                    "Generate and save all chunks within a radius (in chunks) of spawn, or 'cancel'",
+                )
+                .arg("radius", ArgKind::Word)
+                .permission(PermissionLevel::Admin),
+            )
+            .add_systems(Update, (handle_pregen_command, run_pregen).chain());
+    }
+}
+
+/// State of the running pre-generation, if any
+#[derive(Resource, Default)]
+pub struct Pregen {
+    queue: VecDeque<ChunkCoord>,
+    tasks: Vec<Task<Result<(), GameError>>>,
+    source: Option<CommandSource>,
+    started: Option<Instant>,
+    last_report: Option<Instant>,
+    pub total: usize,
+    pub done: usize,
+    pub failed: usize,
+}
+
+impl Pregen {
+    pub fn is_running(&self) -> bool {
+        !self.queue.is_empty() || !self.tasks.is_empty()
+    }
+
+    /// Chunks waiting to be generated, next first
+    pub fn queued(&self) -> impl Iterator<Item = &ChunkCoord> {
+        self.queue.iter()
+    }
+
+    /// Chunks being generated right now
+    pub fn in_flight(&self) -> usize {
+        self.tasks.len()
+    }
+
+    fn progress(&self) -> String {
+        let elapsed = self
+            .started
+            .map(|started| started.elapsed().as_secs_f32())
+            .unwrap_or_default();
+        let rate = self.done as f32 / elapsed.max(0.001);
+        let remaining = self.total - self.done;
+        format!(
+            "Pregen: {}/{} chunks ({:.0}%), {:.1} chunks/s, about {:.0}s left",
+            self.done,
+            self.total,
+            self.done as f32 * 100.0 / self.total.max(1) as f32,
+            rate,
+            remaining as f32 / rate.max(0.001)
+        )
+    }
+}
+
+/// Coordinates within `radius` chunks of the origin, from the center outwards, so that the most
+/// useful chunks are ready first
+fn spiral(radius: i32) -> Vec<ChunkCoord> {
+    let mut coords = Vec::with_capacity(((2 * radius + 1) * (2 * radius + 1)) as usize);
+    for y in -radius..=radius {
+        for x in -radius..=radius {
+            coords.push(ChunkCoord { x, y });
+        }
+    }
+    coords.sort_by_key(|coord| coord.x.abs().max(coord.y.abs()));
+    coords
+}
+
+fn handle_pregen_command(
+    mut invoked: EventReader<CommandInvoked>,
+    mut replies: EventWriter<CommandReply>,
+    mut pregen: ResMut<Pregen>,
+    store: Res<ChunkStore>,
+    world_state: Res<WorldState>,
+) {
+    for command in invoked.read().filter(|c| c.name == "pregen") {
+        let argument = command.args.str("radius").unwrap_or_default();
+        if argument == "cancel" {
+            // chunks already being generated are still saved
+            pregen.queue.clear();
+            replies.send(CommandReply::new(command.source, "Pregen cancelled"));
+            continue;
+        }
+        let Some(radius) = argument
+            .parse::<i32>()
+            .ok()
+            .filter(|r| (0..=MAX_RADIUS).contains(r))
+        else {
+            replies.send(CommandReply::new(
+                command.source,
+                format!("Usage: /pregen <radius 0-{}|cancel>", MAX_RADIUS),
+            ));
+            continue;
+        };
+        if pregen.is_running() {
+            replies.send(CommandReply::new(
+                command.source,
+                "A pregen is already running, cancel it first",
+            ));
+            continue;
+        }
+
+        // loaded chunks are saved with the rest of the world
+        let queue: VecDeque<ChunkCoord> = spiral(radius)
+            .into_iter()
+            .filter(|coord| !world_state.chunks.contains_key(coord) && !store.contains(*coord))
+            .collect();
+        *pregen = Pregen {
+            total: queue.len(),
+            queue,
+            source: Some(command.source),
+            started: Some(Instant::now()),
+            last_report: Some(Instant::now()),
+            ..default()
+        };
+        replies.send(CommandReply::new(
+            command.source,
+            format!(
+                "Pre-generating {} chunks within {} chunks of spawn",
+                pregen.total, radius
+            ),
+        ));
+    }
+}
+
+// Keep a few generation tasks running on the worker pool and collect the finished ones
+fn run_pregen(
+    mut pregen: ResMut<Pregen>,
+    mut replies: EventWriter<CommandReply>,
+    mut errors: EventWriter<ReportError>,
+    store: Res<ChunkStore>,
+    pending: Res<PendingSaves>,
+    world_state: Res<WorldState>,
+    world_config: Res<WorldConfig>,
+    load: Res<FrameLoad>,
+) {
+    if !pregen.is_running() {
+        return;
+    }
+
+    let mut finished = Vec::new();
+    pregen
+        .tasks
+        .retain_mut(|task| match block_on(future::poll_once(task)) {
+            Some(result) => {
+                finished.push(result);
+                false
+            }
+            None => true,
+        });
+    for result in finished {
+        pregen.done += 1;
+        if let Err(e) = result {
+            pregen.failed += 1;
+            errors.send(ReportError(e));
+        }
+    }
+
+    // pregen competes with the game for the cores; it waits while frames are over budget
+    let pool = AsyncComputeTaskPool::get();
+    while !load.degraded && pregen.tasks.len() < MAX_IN_FLIGHT {
+        let Some(coord) = pregen.queue.pop_front() else {
+            break;
+        };
+        // loaded by a player since the pregen started: it is saved with their changes
+        if world_state.chunks.contains_key(&coord)
+            || world_state.pending_chunks.contains_key(&coord)
+            || pending.contains(coord)
+        {
+            pregen.done += 1;
+            continue;
+        }
+        let store = store.clone();
+        let config = world_config.clone();
+        pregen.tasks.push(pool.spawn(async move {
+            // the chunk isn't loaded into the world, only persisted, and never over a copy saved
+            // while it was being generated
+            store
+                .save_new(&build_chunk(&coord, &config, 0.0))
+                .map(|_| ())
+        }));
+    }
+
+    let Some(source) = pregen.source else {
+        return;
+    };
+    if !pregen.is_running() {
+        let elapsed = pregen
+            .started
+            .map(|started| started.elapsed().as_secs_f32())
+            .unwrap_or_default();
+        info!("Pregen finished in {:.1}s", elapsed);
+        replies.send(CommandReply::new(
+            source,
+            format!(
+                "Pregen finished: {} chunks in {:.1}s, {} failed",
+                pregen.done, elapsed, pregen.failed
+            ),
+        ));
+        pregen.source = None;
+    } else if pregen
+        .last_report
+        .is_none_or(|last| last.elapsed() >= REPORT_INTERVAL)
+    {
+        let progress = pregen.progress();
+        info!("{}", progress);
+        replies.send(CommandReply::new(source, progress));
+        pregen.last_report = Some(Instant::now());
+    }
+}
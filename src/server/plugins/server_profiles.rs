@@ -0,0 +1,640 @@
+use bevy::asset::ron;
+use bevy::prelude::*;
+use lightyear::prelude::server::*;
+use lightyear::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::protocol::{PlayerId, PlayerName, PlayerPosition};
+#[cfg(feature = "sqlite")]
+use crate::server::plugins::Database;
+use crate::server::plugins::{
+    AchievementStats, ChunkStore, ContentFilter, ContentFlagged, ContentKind, RESPAWN_POSITION,
+};
+use crate::shared::commands::{
+    ArgKind, CommandInvoked, CommandReply, CommandSource, CommandSpec, RegisterCommandExt,
+};
+use crate::shared::error::{GameError, ReportError};
+use crate::shared::feature_flags::ServerFeatureFlags;
+use crate::shared::graves::GraveSites;
+use crate::shared::instances::InstanceId;
+use crate::shared::reputation::Reputation;
+use crate::shared::skills::Skills;
+use crate::shared::social::SocialLists;
+use crate::shared::survival::{Health, Spectator};
+use crate::shared::tutorial::Tutorial;
+use crate::shared::world_generation::{
+    build_chunk, Chunk, ChunkCoord, SaveWorldEvent, WorldConfig, WorldGrid, WorldState,
+};
+
+// Directory holding one profile per player
+const PROFILE_DIR: &str = "world/players";
+// Tiles searched around the saved position when it's no longer safe to stand on
+const SAFE_SEARCH_RADIUS: i32 = 8;
+// Seconds a player counts as in combat after taking damage
+const COMBAT_TAG_SECS: f64 = 15.0;
+// Seconds the body of a player who logged out in combat stays in the world
+const COMBAT_LOG_SECS: f64 = 30.0;
+// Length bounds of the names players pick, in characters
+const NAME_LENGTH: std::ops::RangeInclusive<usize> = 3..=20;
+
+// Server plugin persisting player profiles across sessions: players come back where they logged out
+pub struct ServerProfilesPlugin;
+
+impl Plugin for ServerProfilesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ProfileStore>()
+            .init_resource::<OnlinePlayers>()
+            .register_command(
+                CommandSpec::new("name", "Change the name other players see")
+                    .arg("name", ArgKind::Text),
+            )
+            .add_systems(
+                Update,
+                (
+                    place_returning_players,
+                    handle_name_command,
+                    tag_players_in_combat,
+                    track_online_players,
+                    save_disconnected_players,
+                    save_online_players,
+                    expire_logged_out_bodies,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// What is remembered about a player between sessions
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PlayerProfile {
+    pub position: Vec2,
+    pub chunk: ChunkCoord,
+    /// Missing from profiles saved before players could pick a name
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub social: SocialLists,
+    #[serde(default)]
+    pub skills: Skills,
+    #[serde(default)]
+    pub achievements: AchievementStats,
+    #[serde(default)]
+    pub graves: GraveSites,
+    #[serde(default)]
+    pub reputation: Reputation,
+    /// Players who joined before the tutorial existed don't have to go through it
+    #[serde(default = "tutorial_done_default")]
+    pub tutorial_done: bool,
+    /// Died on a world with permadeath, and comes back as a spectator
+    #[serde(default)]
+    pub dead: bool,
+}
+
+fn tutorial_done_default() -> bool {
+    true
+}
+
+impl PlayerProfile {
+    fn new(position: Vec2, name: String, chunk_size: usize) -> Self {
+        Self {
+            position,
+            chunk: chunk_of(position, chunk_size),
+            name: Some(name),
+            social: SocialLists::default(),
+            skills: Skills::default(),
+            achievements: AchievementStats::default(),
+            graves: GraveSites::default(),
+            reputation: Reputation::default(),
+            tutorial_done: true,
+            dead: false,
+        }
+    }
+
+    fn move_to(&mut self, position: Vec2, chunk_size: usize) {
+        self.position = position;
+        self.chunk = chunk_of(position, chunk_size);
+    }
+
+    /// A profile whose chunk doesn't match its position has been tampered with or corrupted
+    fn is_consistent(&self, chunk_size: usize) -> bool {
+        self.chunk == chunk_of(self.position, chunk_size)
+    }
+}
+
+fn chunk_of(position: Vec2, chunk_size: usize) -> ChunkCoord {
+    WorldGrid::new(chunk_size).world_to_chunk(position)
+}
+
+/// Player profiles saved on disk, one RON file per client id
+#[derive(Resource, Clone, Debug)]
+pub struct ProfileStore {
+    dir: PathBuf,
+    #[cfg(feature = "sqlite")]
+    database: Option<Database>,
+}
+
+impl Default for ProfileStore {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from(PROFILE_DIR),
+            #[cfg(feature = "sqlite")]
+            database: None,
+        }
+    }
+}
+
+impl ProfileStore {
+    /// Save profiles to the database instead of one file each
+    #[cfg(feature = "sqlite")]
+    pub fn use_database(&mut self, database: Database) {
+        self.database = Some(database);
+    }
+
+    fn path(&self, client_id: ClientId) -> PathBuf {
+        self.dir.join(format!("{}.ron", client_id.to_bits()))
+    }
+
+    pub fn load(&self, client_id: ClientId) -> Result<Option<PlayerProfile>, GameError> {
+        let text = match self.read(client_id)? {
+            Some(text) => text,
+            None => return Ok(None),
+        };
+        ron::from_str(&text)
+            .map(Some)
+            .map_err(|e| GameError::Profile(e.to_string()))
+    }
+
+    fn read(&self, client_id: ClientId) -> Result<Option<String>, GameError> {
+        #[cfg(feature = "sqlite")]
+        if let Some(database) = &self.database {
+            return database.load_profile(client_id);
+        }
+        let path = self.path(client_id);
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(fs::read_to_string(path)?))
+    }
+
+    pub fn save(&self, client_id: ClientId, profile: &PlayerProfile) -> Result<(), GameError> {
+        let text = ron::ser::to_string_pretty(profile, default())
+            .map_err(|e| GameError::Profile(e.to_string()))?;
+        #[cfg(feature = "sqlite")]
+        if let Some(database) = &self.database {
+            return database.save_profile(client_id, text, profile);
+        }
+        fs::create_dir_all(&self.dir)?;
+        // write then rename, so that a crash never leaves a truncated profile behind
+        let path = self.path(client_id);
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, text)?;
+        Ok(fs::rename(tmp, path)?)
+    }
+}
+
+/// Set when a player takes damage; logging out before it runs out leaves the body behind on PvP servers
+#[derive(Component, Debug)]
+pub struct CombatTag {
+    last_health: f32,
+    until: f64,
+}
+
+/// Body of a player who logged out in combat. It can still be hurt until it expires.
+#[derive(Component, Debug)]
+pub struct LoggedOutBody {
+    pub client_id: ClientId,
+    pub expires_at: f64,
+}
+
+// Last known state of connected players. The player entity may already be gone by the time the
+// disconnection is processed, so the profile is saved from here.
+#[derive(Clone, Debug)]
+struct OnlinePlayer {
+    position: Vec2,
+    name: String,
+    social: SocialLists,
+    skills: Skills,
+    achievements: AchievementStats,
+    graves: GraveSites,
+    reputation: Reputation,
+    tutorial_done: bool,
+    dead: bool,
+    health: Option<Health>,
+    in_combat: bool,
+}
+
+impl OnlinePlayer {
+    fn profile(&self, chunk_size: usize) -> PlayerProfile {
+        PlayerProfile {
+            social: self.social.clone(),
+            skills: self.skills.clone(),
+            achievements: self.achievements.clone(),
+            graves: self.graves.clone(),
+            reputation: self.reputation.clone(),
+            tutorial_done: self.tutorial_done,
+            dead: self.dead,
+            ..PlayerProfile::new(self.position, self.name.clone(), chunk_size)
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+struct OnlinePlayers(HashMap<ClientId, OnlinePlayer>);
+
+// Whether a player can safely stand on the tile at the given world coordinates
+fn is_safe_tile(
+    x: i32,
+    y: i32,
+    chunk_cache: &mut HashMap<ChunkCoord, Chunk>,
+    loaded: &dyn Fn(ChunkCoord) -> Option<Chunk>,
+    world_config: &WorldConfig,
+) -> bool {
+    let coord = world_config.grid().tile_to_chunk((x, y));
+    if !coord.is_in_world(world_config.chunk_size) {
+        return false;
+    }
+    let chunk = chunk_cache
+        .entry(coord)
+        .or_insert_with(|| loaded(coord).unwrap_or_else(|| build_chunk(&coord, world_config, 0.0)));
+    let (local_x, local_y) = world_config.grid().tile_to_local((x, y));
+    chunk
+        .get_tile(local_x, local_y)
+        .is_some_and(|tile| tile.traversable && !tile.tile_type.is_hazard())
+}
+
+/// Closest safe position to the saved one, searching outwards ring by ring
+fn find_safe_position(
+    saved: Vec2,
+    loaded: &dyn Fn(ChunkCoord) -> Option<Chunk>,
+    world_config: &WorldConfig,
+) -> Option<Vec2> {
+    let (center_x, center_y) = WorldGrid::world_to_tile(saved);
+    let mut chunk_cache = HashMap::new();
+    if is_safe_tile(center_x, center_y, &mut chunk_cache, loaded, world_config) {
+        return Some(saved);
+    }
+    for radius in 1..=SAFE_SEARCH_RADIUS {
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                if dx.abs() != radius && dy.abs() != radius {
+                    continue;
+                }
+                let (x, y) = (center_x.saturating_add(dx), center_y.saturating_add(dy));
+                if is_safe_tile(x, y, &mut chunk_cache, loaded, world_config) {
+                    return Some(WorldGrid::tile_to_world((x, y)));
+                }
+            }
+        }
+    }
+    None
+}
+
+// Put players back where they logged out, or where their body is if it's still around
+fn place_returning_players(
+    mut commands: Commands,
+    time: Res<Time>,
+    store: Res<ProfileStore>,
+    chunk_store: Res<ChunkStore>,
+    world_state: Res<WorldState>,
+    world_config: Res<WorldConfig>,
+    filter: Res<ContentFilter>,
+    flags: Res<ServerFeatureFlags>,
+    chunks: Query<&Chunk>,
+    mut players: Query<(Entity, &PlayerId, &mut PlayerPosition, &mut PlayerName), Added<PlayerId>>,
+    bodies: Query<(Entity, &LoggedOutBody, &PlayerPosition, &PlayerName), Without<PlayerId>>,
+    mut errors: EventWriter<ReportError>,
+) {
+    for (entity, player_id, mut position, mut name) in players.iter_mut() {
+        let client_id = player_id.client_id();
+        commands.entity(entity).insert(CombatTag {
+            last_health: f32::MAX,
+            until: time.elapsed_secs_f64(),
+        });
+        let profile = match store.load(client_id) {
+            Ok(profile) => profile,
+            Err(e) => {
+                errors.send(ReportError(e));
+                None
+            }
+        };
+        let (social, skills, achievements, graves, reputation) = profile
+            .as_ref()
+            .map(|profile| {
+                (
+                    profile.social.clone(),
+                    profile.skills.clone(),
+                    profile.achievements.clone(),
+                    profile.graves.clone(),
+                    profile.reputation.clone(),
+                )
+            })
+            .unwrap_or_default();
+        commands
+            .entity(entity)
+            .insert((social, skills, achievements, graves, reputation));
+        // new players, and those who left halfway through, go through the tutorial
+        if profile
+            .as_ref()
+            .is_none_or(|profile| !profile.tutorial_done)
+        {
+            commands.entity(entity).insert(Tutorial::default());
+        }
+        if flags.permadeath && profile.as_ref().is_some_and(|profile| profile.dead) {
+            commands.entity(entity).insert(Spectator);
+        }
+
+        if let Some((body, _, body_position, body_name)) = bodies
+            .iter()
+            .find(|(_, body, _, _)| body.client_id == client_id)
+        {
+            position.0 = body_position.0;
+            name.0 = body_name.0.clone();
+            commands.entity(body).despawn();
+            info!("Player {} reclaimed their body", client_id);
+            continue;
+        }
+
+        let Some(profile) = profile else {
+            continue;
+        };
+        // the filter lists may have changed since the name was picked
+        if let Some(saved) = profile.name.as_deref() {
+            let outcome = filter.check(ContentKind::Name, saved);
+            if outcome.is_blocked() {
+                info!("Saved name of player {} is no longer allowed", client_id);
+            } else {
+                name.0 = outcome.text;
+            }
+        }
+        if !profile.is_consistent(world_config.chunk_size) {
+            warn!(
+                "Profile of player {} is inconsistent, spawning them",
+                client_id
+            );
+            position.0 = RESPAWN_POSITION;
+            continue;
+        }
+        let loaded = |coord: ChunkCoord| {
+            world_state
+                .chunks
+                .get(&coord)
+                .and_then(|entity| chunks.get(*entity).ok())
+                .cloned()
+                .or_else(|| chunk_store.load(coord).ok())
+        };
+        position.0 = match find_safe_position(profile.position, &loaded, &world_config) {
+            Some(safe) => safe,
+            None => {
+                info!(
+                    "Area around {:?} changed, spawning player {} instead",
+                    profile.position, client_id
+                );
+                RESPAWN_POSITION
+            }
+        };
+    }
+}
+
+fn tag_players_in_combat(
+    time: Res<Time>,
+    mut players: Query<(&Health, &mut CombatTag), Changed<Health>>,
+) {
+    for (health, mut tag) in players.iter_mut() {
+        if health.current < tag.last_health {
+            tag.until = time.elapsed_secs_f64() + COMBAT_TAG_SECS;
+        }
+        tag.last_health = health.current;
+    }
+}
+
+// Players in an instance keep the overworld position they entered it from
+fn track_online_players(
+    time: Res<Time>,
+    mut online: ResMut<OnlinePlayers>,
+    players: Query<
+        (
+            &PlayerId,
+            &PlayerPosition,
+            &PlayerName,
+            Option<&SocialLists>,
+            Option<&Skills>,
+            Option<&AchievementStats>,
+            Option<&GraveSites>,
+            Option<&Reputation>,
+            Has<Tutorial>,
+            Has<Spectator>,
+            Option<&Health>,
+            Option<&CombatTag>,
+        ),
+        Without<InstanceId>,
+    >,
+) {
+    let now = time.elapsed_secs_f64();
+    for (
+        player_id,
+        position,
+        name,
+        social,
+        skills,
+        achievements,
+        graves,
+        reputation,
+        in_tutorial,
+        dead,
+        health,
+        tag,
+    ) in players.iter()
+    {
+        online.0.insert(
+            player_id.client_id(),
+            OnlinePlayer {
+                position: position.0,
+                name: name.0.clone(),
+                social: social.cloned().unwrap_or_default(),
+                skills: skills.cloned().unwrap_or_default(),
+                achievements: achievements.cloned().unwrap_or_default(),
+                graves: graves.cloned().unwrap_or_default(),
+                reputation: reputation.cloned().unwrap_or_default(),
+                tutorial_done: !in_tutorial,
+                dead,
+                health: health.cloned(),
+                in_combat: tag.is_some_and(|tag| tag.until > now),
+            },
+        );
+    }
+}
+
+fn save_disconnected_players(
+    mut commands: Commands,
+    mut disconnections: EventReader<DisconnectEvent>,
+    mut online: ResMut<OnlinePlayers>,
+    time: Res<Time>,
+    flags: Res<ServerFeatureFlags>,
+    store: Res<ProfileStore>,
+    world_config: Res<WorldConfig>,
+    mut errors: EventWriter<ReportError>,
+) {
+    for disconnection in disconnections.read() {
+        let client_id = disconnection.client_id;
+        let Some(player) = online.0.remove(&client_id) else {
+            continue;
+        };
+        let profile = player.profile(world_config.chunk_size);
+        if let Err(e) = store.save(client_id, &profile) {
+            errors.send(ReportError(e));
+        }
+        if flags.pvp && player.in_combat {
+            info!(
+                "Player {} logged out in combat, leaving their body at {:?}",
+                client_id, player.position
+            );
+            commands.spawn((
+                LoggedOutBody {
+                    client_id,
+                    expires_at: time.elapsed_secs_f64() + COMBAT_LOG_SECS,
+                },
+                PlayerPosition(player.position),
+                PlayerName(player.name),
+                player.health.unwrap_or_default(),
+                Replicate {
+                    sync: SyncTarget {
+                        interpolation: NetworkTarget::All,
+                        ..default()
+                    },
+                    ..default()
+                },
+            ));
+        }
+    }
+}
+
+// Save everyone on world saves, so a crash or restart doesn't lose positions
+fn save_online_players(
+    mut save_events: EventReader<SaveWorldEvent>,
+    online: Res<OnlinePlayers>,
+    store: Res<ProfileStore>,
+    world_config: Res<WorldConfig>,
+    mut errors: EventWriter<ReportError>,
+) {
+    if save_events.read().count() == 0 {
+        return;
+    }
+    for (client_id, player) in online.0.iter() {
+        let profile = player.profile(world_config.chunk_size);
+        if let Err(e) = store.save(*client_id, &profile) {
+            errors.send(ReportError(e));
+        }
+    }
+}
+
+// Bodies disappear once the combat-log timer runs out; killed bodies send their owner back to spawn
+fn expire_logged_out_bodies(
+    mut commands: Commands,
+    time: Res<Time>,
+    store: Res<ProfileStore>,
+    world_config: Res<WorldConfig>,
+    flags: Res<ServerFeatureFlags>,
+    bodies: Query<(Entity, &LoggedOutBody, &Health, &PlayerName)>,
+    mut errors: EventWriter<ReportError>,
+) {
+    let now = time.elapsed_secs_f64();
+    for (entity, body, health, name) in bodies.iter() {
+        if health.current <= 0.0 {
+            info!("Body of player {} was killed", body.client_id);
+            // keep the rest of the profile saved when they logged out
+            let mut profile = match store.load(body.client_id) {
+                Ok(Some(profile)) => profile,
+                _ => PlayerProfile::new(RESPAWN_POSITION, name.0.clone(), world_config.chunk_size),
+            };
+            if flags.permadeath {
+                profile.dead = true;
+            } else {
+                profile.move_to(RESPAWN_POSITION, world_config.chunk_size);
+            }
+            if let Err(e) = store.save(body.client_id, &profile) {
+                errors.send(ReportError(e));
+            }
+            commands.entity(entity).despawn();
+        } else if now >= body.expires_at {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+// Players pick their name; it goes through the content filter, and is saved with their profile
+fn handle_name_command(
+    mut invoked: EventReader<CommandInvoked>,
+    mut replies: EventWriter<CommandReply>,
+    mut flagged: EventWriter<ContentFlagged>,
+    filter: Res<ContentFilter>,
+    mut players: Query<(&PlayerId, &mut PlayerName)>,
+) {
+    for command in invoked.read().filter(|c| c.name == "name") {
+        let CommandSource::Client(client_id) = command.source else {
+            replies.send(CommandReply::new(
+                command.source,
+                "Only players have a name",
+            ));
+            continue;
+        };
+        let requested = command.args.str("name").unwrap_or_default().trim();
+        if !NAME_LENGTH.contains(&requested.chars().count()) {
+            replies.send(CommandReply::new(
+                command.source,
+                format!(
+                    "Names are {} to {} characters long",
+                    NAME_LENGTH.start(),
+                    NAME_LENGTH.end()
+                ),
+            ));
+            continue;
+        }
+        if !requested
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, ' ' | '_' | '-'))
+        {
+            replies.send(CommandReply::new(
+                command.source,
+                "Names may only contain letters, digits, spaces, '_' and '-'",
+            ));
+            continue;
+        }
+        if players
+            .iter()
+            .any(|(id, name)| id.client_id() != client_id && name.0.eq_ignore_ascii_case(requested))
+        {
+            replies.send(CommandReply::new(command.source, "That name is taken"));
+            continue;
+        }
+
+        let outcome = filter.check(ContentKind::Name, requested);
+        let blocked = outcome.is_blocked();
+        let censored = outcome.text.clone();
+        if !outcome.is_clean() {
+            flagged.send(ContentFlagged {
+                client_id,
+                kind: ContentKind::Name,
+                original: requested.to_string(),
+                outcome,
+            });
+        }
+        if blocked {
+            continue;
+        }
+        let Some((_, mut name)) = players
+            .iter_mut()
+            .find(|(id, _)| id.client_id() == client_id)
+        else {
+            continue;
+        };
+        info!("Player {} is now known as {}", client_id, censored);
+        replies.send(CommandReply::new(
+            command.source,
+            format!("You are now known as {}", censored),
+        ));
+        name.0 = censored;
+    }
+}
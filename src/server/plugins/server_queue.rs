@@ -0,0 +1,156 @@
+use bevy::prelude::*;
+use lightyear::prelude::server::*;
+use lightyear::prelude::*;
+use std::collections::{HashSet, VecDeque};
+
+use crate::protocol::{Channel1, QueueStatus};
+use crate::server::handle_connections;
+use crate::server::plugins::Observers;
+use crate::settings_common::Settings;
+use crate::shared::error::{GameError, ReportError};
+
+// Used when no settings are available
+const DEFAULT_MAX_PLAYERS: usize = 16;
+
+// Server plugin limiting the number of players in the world. Clients connecting to a full server
+// wait in a queue and are admitted in order as players leave.
+pub struct ServerQueuePlugin;
+
+impl Plugin for ServerQueuePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<PlayerAdmitted>()
+            .insert_resource(ConnectionQueue::new(DEFAULT_MAX_PLAYERS))
+            .add_systems(Startup, load_queue_settings)
+            .add_systems(
+                Update,
+                (queue_connections, free_slots)
+                    .chain()
+                    .before(handle_connections),
+            );
+    }
+}
+
+/// A connected client got a slot; its player gets spawned
+#[derive(Event, Clone, Copy, Debug)]
+pub struct PlayerAdmitted {
+    pub client_id: ClientId,
+}
+
+#[derive(Resource, Debug)]
+pub struct ConnectionQueue {
+    pub max_players: usize,
+    pub admitted: HashSet<ClientId>,
+    pub waiting: VecDeque<ClientId>,
+}
+
+impl ConnectionQueue {
+    pub fn new(max_players: usize) -> Self {
+        Self {
+            max_players,
+            admitted: HashSet::new(),
+            waiting: VecDeque::new(),
+        }
+    }
+
+    fn has_free_slot(&self) -> bool {
+        self.admitted.len() < self.max_players
+    }
+
+    /// 1-based position of a waiting client
+    pub fn position(&self, client_id: ClientId) -> Option<usize> {
+        self.waiting
+            .iter()
+            .position(|id| *id == client_id)
+            .map(|index| index + 1)
+    }
+}
+
+fn load_queue_settings(settings: Option<Res<Settings>>, mut queue: ResMut<ConnectionQueue>) {
+    if let Some(settings) = settings {
+        queue.max_players = settings.server.max_players;
+    }
+}
+
+fn send_queue_status(
+    queue: &ConnectionQueue,
+    connection_manager: &mut ConnectionManager,
+    errors: &mut EventWriter<ReportError>,
+) {
+    let length = queue.waiting.len();
+    for (index, client_id) in queue.waiting.iter().enumerate() {
+        let status = QueueStatus {
+            position: index + 1,
+            length,
+        };
+        if let Err(e) = connection_manager.send_message::<Channel1, _>(*client_id, &status) {
+            errors.send(ReportError(GameError::send("QueueStatus", e)));
+        }
+    }
+}
+
+fn queue_connections(
+    mut connections: EventReader<ConnectEvent>,
+    mut queue: ResMut<ConnectionQueue>,
+    mut admissions: EventWriter<PlayerAdmitted>,
+    mut connection_manager: ResMut<ConnectionManager>,
+    mut errors: EventWriter<ReportError>,
+    observers: Option<Res<Observers>>,
+) {
+    let mut queued = false;
+    for connection in connections.read() {
+        let client_id = connection.client_id;
+        // observers don't get a player, nor take a slot
+        if observers
+            .as_ref()
+            .is_some_and(|observers| observers.is_observer(client_id))
+        {
+            continue;
+        }
+        if queue.waiting.is_empty() && queue.has_free_slot() {
+            queue.admitted.insert(client_id);
+            admissions.send(PlayerAdmitted { client_id });
+        } else {
+            queue.waiting.push_back(client_id);
+            queued = true;
+            info!(
+                "Server full, client {} queued at position {}",
+                client_id,
+                queue.waiting.len()
+            );
+        }
+    }
+    if queued {
+        send_queue_status(&queue, &mut connection_manager, &mut errors);
+    }
+}
+
+// Players leaving free their slot for the first client in line
+fn free_slots(
+    mut disconnections: EventReader<DisconnectEvent>,
+    mut queue: ResMut<ConnectionQueue>,
+    mut admissions: EventWriter<PlayerAdmitted>,
+    mut connection_manager: ResMut<ConnectionManager>,
+    mut errors: EventWriter<ReportError>,
+) {
+    let mut changed = false;
+    for disconnection in disconnections.read() {
+        let client_id = disconnection.client_id;
+        changed |= queue.admitted.remove(&client_id);
+        if let Some(index) = queue.waiting.iter().position(|id| *id == client_id) {
+            queue.waiting.remove(index);
+            changed = true;
+        }
+    }
+    if !changed {
+        return;
+    }
+    while queue.has_free_slot() {
+        let Some(client_id) = queue.waiting.pop_front() else {
+            break;
+        };
+        info!("Admitting queued client {}", client_id);
+        queue.admitted.insert(client_id);
+        admissions.send(PlayerAdmitted { client_id });
+    }
+    send_queue_status(&queue, &mut connection_manager, &mut errors);
+}
@@ -0,0 +1,221 @@
+use bevy::prelude::*;
+use lightyear::prelude::server::*;
+use lightyear::prelude::*;
+use std::collections::HashSet;
+
+use crate::protocol::{PlayerId, PlayerPosition};
+use crate::server::plugins::{
+    spawn_dropped_item, CartInteractionEvent, CommandPermissions, LandClaims, PlayerDiedEvent,
+    TileModifiedEvent,
+};
+use crate::shared::commands::{CommandReply, CommandSource};
+use crate::shared::feature_flags::ServerFeatureFlags;
+use crate::shared::instances::InstanceId;
+use crate::shared::items::{DroppedItem, Inventory, ItemKind};
+use crate::shared::movement::LoadedTiles;
+use crate::shared::rails::{is_rail, Cart, LeaveCart, PlaceCart, Riding};
+use crate::shared::survival::Spectator;
+use crate::shared::world_generation::{tile_distance, WorldConfig, WorldGrid, WorldState};
+
+// Iron taken from the inventory to put a cart on the rails, and given back if it's ever lost
+const CART_COST: u32 = 3;
+// Maximum distance, in tiles, between a player and the rail they put a cart on
+const PLACE_REACH: u32 = 2;
+
+// Server plugin for carts: putting them on rails, boarding and leaving them. Rides themselves are
+// simulated along with player movement.
+pub struct ServerRailsPlugin;
+
+impl Plugin for ServerRailsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                place_carts,
+                board_carts,
+                leave_carts,
+                leave_carts_on_death,
+                drop_derailed_carts,
+            )
+                .chain(),
+        );
+    }
+}
+
+fn spawn_cart(commands: &mut Commands, tile: (i32, i32)) {
+    commands.spawn((
+        Cart { tile },
+        Replicate {
+            sync: SyncTarget {
+                interpolation: NetworkTarget::All,
+                ..default()
+            },
+            ..default()
+        },
+    ));
+}
+
+// Leave a cart where its rider got off: on the rails if there are any, else as the iron it was
+// made of
+fn park_cart(commands: &mut Commands, tiles: &LoadedTiles, tile: (i32, i32), now: f64) {
+    if is_rail(tiles, tile) {
+        spawn_cart(commands, tile);
+        return;
+    }
+    spawn_dropped_item(
+        commands,
+        DroppedItem {
+            kind: ItemKind::Iron,
+            count: CART_COST,
+            tile,
+            dropped_at: now,
+        },
+    );
+}
+
+fn place_carts(
+    mut commands: Commands,
+    mut events: EventReader<MessageEvent<PlaceCart>>,
+    mut replies: EventWriter<CommandReply>,
+    tiles: LoadedTiles,
+    world_config: Res<WorldConfig>,
+    claims: Res<LandClaims>,
+    permissions: Res<CommandPermissions>,
+    flags: Res<ServerFeatureFlags>,
+    mut players: Query<
+        (&PlayerId, &PlayerPosition, &mut Inventory),
+        (Without<InstanceId>, Without<Spectator>),
+    >,
+    carts: Query<&Cart>,
+) {
+    for event in events.read() {
+        let client_id = *event.from();
+        let tile = event.message().tile;
+        let reply = |text: &str| CommandReply::new(CommandSource::Client(client_id), text);
+        let Some((_, position, mut inventory)) = players
+            .iter_mut()
+            .find(|(id, _, _)| id.client_id() == client_id)
+        else {
+            continue;
+        };
+        if tile_distance(tile, WorldGrid::world_to_tile(position.0)) > PLACE_REACH {
+            replies.send(reply("That rail is too far away"));
+            continue;
+        }
+        if !claims.can_modify(
+            world_config.grid().tile_to_chunk(tile),
+            client_id,
+            &permissions,
+        ) {
+            replies.send(reply("This land is claimed by someone else"));
+            continue;
+        }
+        if !is_rail(&tiles, tile) {
+            replies.send(reply("Carts only go on rails"));
+            continue;
+        }
+        if carts.iter().any(|cart| cart.tile == tile) {
+            replies.send(reply("There is a cart there already"));
+            continue;
+        }
+        if !flags.creative && !inventory.remove(ItemKind::Iron, CART_COST) {
+            replies.send(reply(&format!("A cart takes {} iron to build", CART_COST)));
+            continue;
+        }
+        spawn_cart(&mut commands, tile);
+    }
+}
+
+fn board_carts(
+    mut commands: Commands,
+    mut events: EventReader<CartInteractionEvent>,
+    mut players: Query<(Entity, &PlayerId, &mut PlayerPosition), Without<Riding>>,
+    carts: Query<&Cart>,
+) {
+    let mut boarded = HashSet::new();
+    for event in events.read() {
+        let Ok(cart) = carts.get(event.cart) else {
+            continue;
+        };
+        // two players reaching for the same cart at once: the first one gets it
+        if !boarded.insert(event.cart) {
+            continue;
+        }
+        let Some((player, _, mut position)) = players
+            .iter_mut()
+            .find(|(_, id, _)| id.client_id() == event.client_id)
+        else {
+            continue;
+        };
+        position.0 = WorldGrid::tile_to_world(cart.tile);
+        commands.entity(event.cart).despawn();
+        commands.entity(player).insert(Riding::default());
+    }
+}
+
+fn leave_carts(
+    mut commands: Commands,
+    mut events: EventReader<MessageEvent<LeaveCart>>,
+    tiles: LoadedTiles,
+    world_state: Res<WorldState>,
+    players: Query<(Entity, &PlayerId, &PlayerPosition), With<Riding>>,
+) {
+    for event in events.read() {
+        let client_id = *event.from();
+        let Some((player, _, position)) = players
+            .iter()
+            .find(|(_, id, _)| id.client_id() == client_id)
+        else {
+            continue;
+        };
+        commands.entity(player).remove::<Riding>();
+        let tile = WorldGrid::world_to_tile(position.0);
+        park_cart(&mut commands, &tiles, tile, world_state.world_time);
+    }
+}
+
+fn leave_carts_on_death(
+    mut commands: Commands,
+    mut deaths: EventReader<PlayerDiedEvent>,
+    tiles: LoadedTiles,
+    world_state: Res<WorldState>,
+    players: Query<(Entity, &PlayerId), With<Riding>>,
+) {
+    for death in deaths.read() {
+        let Some((player, _)) = players
+            .iter()
+            .find(|(_, id)| id.client_id() == death.client_id)
+        else {
+            continue;
+        };
+        commands.entity(player).remove::<Riding>();
+        let tile = WorldGrid::world_to_tile(death.position);
+        park_cart(&mut commands, &tiles, tile, world_state.world_time);
+    }
+}
+
+// Carts whose rail was dug away fall apart into the iron they were made of
+fn drop_derailed_carts(
+    mut commands: Commands,
+    mut modifications: EventReader<TileModifiedEvent>,
+    tiles: LoadedTiles,
+    world_state: Res<WorldState>,
+    carts: Query<(Entity, &Cart)>,
+) {
+    for modification in modifications.read() {
+        if is_rail(&tiles, modification.position) {
+            continue;
+        }
+        for (entity, cart) in carts.iter() {
+            if cart.tile == modification.position {
+                commands.entity(entity).despawn();
+                park_cart(
+                    &mut commands,
+                    &tiles,
+                    modification.position,
+                    world_state.world_time,
+                );
+            }
+        }
+    }
+}
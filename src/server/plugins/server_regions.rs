@@ -0,0 +1,37 @@
+use bevy::prelude::*;
+use lightyear::prelude::server::*;
+use lightyear::prelude::*;
+
+use crate::protocol::Channel1;
+use crate::shared::biome_map::BiomeMap;
+use crate::shared::error::{GameError, ReportError};
+use crate::shared::regions::{region_name, RegionNameRequest, RegionNameSync};
+use crate::shared::world_generation::WorldConfig;
+
+// Server plugin answering clients asking for the names of regions
+pub struct ServerRegionsPlugin;
+
+impl Plugin for ServerRegionsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, answer_region_names);
+    }
+}
+
+fn answer_region_names(
+    mut requests: EventReader<MessageEvent<RegionNameRequest>>,
+    mut connection_manager: ResMut<ConnectionManager>,
+    mut errors: EventWriter<ReportError>,
+    world_config: Res<WorldConfig>,
+    biome_map: Res<BiomeMap>,
+) {
+    for request in requests.read() {
+        let region = request.message().0;
+        let biome = biome_map.chunk_biome(region.center_chunk());
+        let name = region_name(region, world_config.seed, biome);
+        if let Err(e) = connection_manager
+            .send_message::<Channel1, _>(request.from(), &RegionNameSync { region, name })
+        {
+            errors.send(ReportError(GameError::send("RegionNameSync", e)));
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use bevy::prelude::*;
+use lightyear::prelude::*;
+
+use crate::protocol::PlayerId;
+use crate::server::plugins::{NpcStruckEvent, SkillActionEvent};
+use crate::shared::commands::{
+    CommandInvoked, CommandReply, CommandSource, CommandSpec, RegisterCommandExt,
+};
+use crate::shared::reputation::{Faction, Reputation};
+use crate::shared::skills::Skill;
+use crate::shared::villages::Villager;
+
+// Standing lost with a faction by attacking one of its members
+const ATTACK_STANDING: i32 = 20;
+// Standing gained with villagers by driving off a bandit
+const DEFEND_STANDING: i32 = 5;
+
+// Server plugin keeping track of the standing of players with NPC factions
+pub struct ServerReputationPlugin;
+
+impl Plugin for ServerReputationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ReputationEvent>()
+            .register_command(CommandSpec::new(
+                "reputation",
+                "Show your standing with each faction",
+            ))
+            .add_systems(
+                Update,
+                (handle_strikes, change_reputation, handle_reputation_command).chain(),
+            );
+    }
+}
+
+/// Sent when a player did something a faction likes or dislikes, e.g. trading with a villager
+#[derive(Event, Clone, Copy, Debug)]
+pub struct ReputationEvent {
+    pub client_id: ClientId,
+    pub faction: Faction,
+    pub delta: i32,
+}
+
+// Bandits are driven off by a single blow; villagers are only offended
+fn handle_strikes(
+    mut commands: Commands,
+    mut struck: EventReader<NpcStruckEvent>,
+    mut replies: EventWriter<CommandReply>,
+    mut reputation: EventWriter<ReputationEvent>,
+    mut skill_actions: EventWriter<SkillActionEvent>,
+    npcs: Query<(&Faction, Option<&Villager>)>,
+) {
+    for event in struck.read() {
+        let client_id = event.client_id;
+        let entity = event.npc;
+        let reply = |text: String| CommandReply::new(CommandSource::Client(client_id), text);
+        // NPCs without a faction don't hold grudges
+        let Ok((faction, villager)) = npcs.get(entity) else {
+            continue;
+        };
+        reputation.send(ReputationEvent {
+            client_id,
+            faction: *faction,
+            delta: -ATTACK_STANDING,
+        });
+        match faction {
+            Faction::Bandits => {
+                commands.entity(entity).despawn();
+                reputation.send(ReputationEvent {
+                    client_id,
+                    faction: Faction::Villagers,
+                    delta: DEFEND_STANDING,
+                });
+                skill_actions.send(SkillActionEvent {
+                    client_id,
+                    skill: Skill::Combat,
+                });
+                replies.send(reply("You drove off a bandit".to_string()));
+            }
+            Faction::Villagers => {
+                let name = villager.map_or("The villager", |villager| villager.name.as_str());
+                replies.send(reply(format!("{} cries for help", name)));
+            }
+        }
+    }
+}
+
+fn change_reputation(
+    mut events: EventReader<ReputationEvent>,
+    mut players: Query<(&PlayerId, &mut Reputation)>,
+) {
+    for event in events.read() {
+        let Some((_, mut reputation)) = players
+            .iter_mut()
+            .find(|(id, _)| id.client_id() == event.client_id)
+        else {
+            continue;
+        };
+        reputation.change(event.faction, event.delta);
+    }
+}
+
+fn handle_reputation_command(
+    mut invoked: EventReader<CommandInvoked>,
+    mut replies: EventWriter<CommandReply>,
+    players: Query<(&PlayerId, &Reputation)>,
+) {
+    for command in invoked.read() {
+        if command.name != "reputation" {
+            continue;
+        }
+        let CommandSource::Client(client_id) = command.source else {
+            replies.send(CommandReply::new(
+                command.source,
+                "Only players have a reputation",
+            ));
+            continue;
+        };
+        let Some((_, reputation)) = players.iter().find(|(id, _)| id.client_id() == client_id)
+        else {
+            continue;
+        };
+        let standings: Vec<String> = Faction::ALL
+            .iter()
+            .map(|faction| {
+                format!(
+                    "{:?}: {} ({:?})",
+                    faction,
+                    reputation.standing(*faction),
+                    reputation.attitude(*faction)
+                )
+            })
+            .collect();
+        replies.send(CommandReply::new(command.source, standings.join(", ")));
+    }
+}
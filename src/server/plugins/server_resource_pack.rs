@@ -0,0 +1,140 @@
+use bevy::prelude::*;
+use lightyear::prelude::server::*;
+use lightyear::prelude::ClientId;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::protocol::{
+    ResourcePackChannel, ResourcePackOffer, ResourcePackPart, ResourcePackRequest,
+};
+use crate::settings_common::Settings;
+use crate::shared::error::{GameError, ReportError};
+use crate::shared::resource_pack::{hash, ResourcePack};
+
+// Bytes of the pack in each part, and parts sent to each downloading client per frame
+const PART_BYTES: usize = 16 * 1024;
+const PARTS_PER_FRAME: usize = 4;
+
+// Server plugin offering the resource pack set in the settings to clients as they connect, and
+// sending it to those who accept it a few parts per frame
+pub struct ServerResourcePackPlugin;
+
+impl Plugin for ServerResourcePackPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ServerResourcePack>()
+            .add_systems(Startup, load_resource_pack)
+            .add_systems(
+                Update,
+                (offer_resource_pack, handle_pack_requests, send_pack_parts).chain(),
+            );
+    }
+}
+
+#[derive(Resource, Default)]
+struct ServerResourcePack {
+    offer: Option<ResourcePackOffer>,
+    data: Vec<u8>,
+    // Clients downloading the pack, and how many bytes they were sent so far
+    downloads: HashMap<ClientId, usize>,
+}
+
+fn load_resource_pack(
+    settings: Option<Res<Settings>>,
+    mut pack: ResMut<ServerResourcePack>,
+    mut errors: EventWriter<ReportError>,
+) {
+    let Some(dir) = settings.and_then(|settings| settings.server.resource_pack.clone()) else {
+        return;
+    };
+    let loaded = ResourcePack::load(Path::new(&dir))
+        .and_then(|resource_pack| Ok((resource_pack.encode()?, resource_pack.manifest)));
+    let (data, manifest) = match loaded {
+        Ok(loaded) => loaded,
+        // clients keep the built-in visuals rather than get a broken pack
+        Err(e) => {
+            errors.send(ReportError(e));
+            return;
+        }
+    };
+    info!(
+        "Offering resource pack '{}' ({} KiB) to clients",
+        manifest.name,
+        data.len() / 1024
+    );
+    pack.offer = Some(ResourcePackOffer {
+        name: manifest.name,
+        description: manifest.description,
+        size: data.len() as u32,
+        hash: hash(&data),
+    });
+    pack.data = data;
+}
+
+// Offer the pack as clients connect, before they are admitted into the world, so that they
+// download it while waiting in the queue
+fn offer_resource_pack(
+    pack: Res<ServerResourcePack>,
+    mut connections: EventReader<ConnectEvent>,
+    mut connection_manager: ResMut<ConnectionManager>,
+    mut errors: EventWriter<ReportError>,
+) {
+    let Some(offer) = &pack.offer else {
+        connections.clear();
+        return;
+    };
+    for connection in connections.read() {
+        connection_manager
+            .send_message::<ResourcePackChannel, _>(connection.client_id, offer)
+            .unwrap_or_else(|e| {
+                errors.send(ReportError(GameError::send("ResourcePackOffer", e)));
+            });
+    }
+}
+
+fn handle_pack_requests(
+    mut requests: EventReader<MessageEvent<ResourcePackRequest>>,
+    mut disconnections: EventReader<DisconnectEvent>,
+    mut pack: ResMut<ServerResourcePack>,
+) {
+    for disconnection in disconnections.read() {
+        pack.downloads.remove(&disconnection.client_id);
+    }
+    for request in requests.read() {
+        // the request may be for a pack offered before a restart
+        if pack
+            .offer
+            .as_ref()
+            .is_some_and(|offer| offer.hash == request.message().hash)
+        {
+            pack.downloads.insert(request.from(), 0);
+        }
+    }
+}
+
+fn send_pack_parts(
+    mut pack: ResMut<ServerResourcePack>,
+    mut connection_manager: ResMut<ConnectionManager>,
+    mut errors: EventWriter<ReportError>,
+) {
+    let pack = &mut *pack;
+    pack.downloads.retain(|client_id, sent| {
+        for _ in 0..PARTS_PER_FRAME {
+            if *sent >= pack.data.len() {
+                break;
+            }
+            let end = (*sent + PART_BYTES).min(pack.data.len());
+            let part = ResourcePackPart {
+                offset: *sent as u32,
+                data: pack.data[*sent..end].to_vec(),
+            };
+            if let Err(e) =
+                connection_manager.send_message::<ResourcePackChannel, _>(*client_id, &part)
+            {
+                errors.send(ReportError(GameError::send("ResourcePackPart", e)));
+                return false;
+            }
+            *sent = end;
+        }
+        *sent < pack.data.len()
+    });
+}
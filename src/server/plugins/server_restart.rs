@@ -0,0 +1,251 @@
+use bevy::prelude::*;
+use lightyear::prelude::server::*;
+use lightyear::prelude::*;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::protocol::{Channel1, ChatChannel, ChatLine, ServerRestarting};
+#[cfg(feature = "sqlite")]
+use crate::server::plugins::Database;
+use crate::server::plugins::{ChunkStore, PendingSaves};
+use crate::settings_common::{RestartAction, Settings};
+use crate::shared::commands::{
+    ArgKind, CommandInvoked, CommandReply, CommandSpec, PermissionLevel, RegisterCommandExt,
+};
+use crate::shared::error::{GameError, ReportError};
+use crate::shared::world_generation::SaveWorldEvent;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+// Warnings used for restarts scheduled with the /restart command
+const DEFAULT_WARNINGS: [u64; 5] = [900, 300, 60, 30, 10];
+// Frames to wait after requesting a save before the process goes away
+const SHUTDOWN_GRACE_FRAMES: u32 = 5;
+
+// Server plugin that restarts the server on a schedule, warning players beforehand
+pub struct ServerRestartPlugin;
+
+impl Plugin for ServerRestartPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RestartScheduler>()
+            .register_command(
+                CommandSpec::new(
+                    "restart",
+                    "Restart the server in the given number of seconds, or 'cancel'",
+                )
+                .arg("delay", ArgKind::Word)
+                .permission(PermissionLevel::Admin),
+            )
+            .add_systems(Startup, schedule_daily_restart)
+            .add_systems(
+                Update,
+                (handle_restart_command, tick_restart_schedule).chain(),
+            );
+    }
+}
+
+#[derive(Resource)]
+pub struct RestartScheduler {
+    pub restart_at: Option<SystemTime>,
+    pub action: RestartAction,
+    // Remaining warnings in seconds before the restart, sorted from the earliest to the latest
+    warnings: Vec<u64>,
+    // Set once the save has been requested; counts frames until we actually exit
+    shutdown_frames: Option<u32>,
+}
+
+impl Default for RestartScheduler {
+    fn default() -> Self {
+        Self {
+            restart_at: None,
+            action: RestartAction::Exit,
+            warnings: Vec::new(),
+            shutdown_frames: None,
+        }
+    }
+}
+
+impl RestartScheduler {
+    pub fn schedule(&mut self, restart_at: SystemTime, warnings: &[u64]) {
+        let remaining = restart_at
+            .duration_since(SystemTime::now())
+            .unwrap_or_default()
+            .as_secs();
+        let mut warnings: Vec<u64> = warnings
+            .iter()
+            .copied()
+            .filter(|w| *w < remaining)
+            .collect();
+        warnings.sort_unstable_by(|a, b| b.cmp(a));
+        self.restart_at = Some(restart_at);
+        self.warnings = warnings;
+    }
+
+    pub fn cancel(&mut self) {
+        self.restart_at = None;
+        self.warnings.clear();
+    }
+}
+
+/// Next occurrence of the given UTC time of day
+fn next_daily_restart(now: SystemTime, hour: u8, minute: u8) -> SystemTime {
+    let now_secs = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let day_start = now_secs - now_secs % SECONDS_PER_DAY;
+    let mut restart = day_start + hour as u64 * 3600 + minute as u64 * 60;
+    if restart <= now_secs {
+        restart += SECONDS_PER_DAY;
+    }
+    UNIX_EPOCH + Duration::from_secs(restart)
+}
+
+fn format_delay(seconds: u64) -> String {
+    if seconds >= 60 {
+        format!("{} minute(s)", seconds / 60)
+    } else {
+        format!("{} second(s)", seconds)
+    }
+}
+
+fn schedule_daily_restart(
+    settings: Option<Res<Settings>>,
+    mut scheduler: ResMut<RestartScheduler>,
+) {
+    let Some(schedule) = settings.and_then(|s| s.server.restart.clone()) else {
+        return;
+    };
+    let (hour, minute) = schedule.daily_at;
+    let restart_at = next_daily_restart(SystemTime::now(), hour, minute);
+    scheduler.action = schedule.action;
+    scheduler.schedule(restart_at, &schedule.warnings);
+    info!(
+        "Scheduled daily restart at {:02}:{:02} UTC ({:?})",
+        hour, minute, scheduler.action
+    );
+}
+
+fn handle_restart_command(
+    mut invoked: EventReader<CommandInvoked>,
+    mut replies: EventWriter<CommandReply>,
+    mut scheduler: ResMut<RestartScheduler>,
+) {
+    for command in invoked.read().filter(|c| c.name == "restart") {
+        let delay = command.args.str("delay").unwrap_or_default();
+        if delay == "cancel" {
+            scheduler.cancel();
+            replies.send(CommandReply::new(command.source, "Restart cancelled"));
+            continue;
+        }
+        let Ok(seconds) = delay.parse::<u64>() else {
+            replies.send(CommandReply::new(
+                command.source,
+                "Usage: /restart <seconds|cancel>",
+            ));
+            continue;
+        };
+        scheduler.schedule(
+            SystemTime::now() + Duration::from_secs(seconds),
+            &DEFAULT_WARNINGS,
+        );
+        replies.send(CommandReply::new(
+            command.source,
+            format!("Restarting in {}", format_delay(seconds)),
+        ));
+    }
+}
+
+fn tick_restart_schedule(
+    mut scheduler: ResMut<RestartScheduler>,
+    mut connection_manager: ResMut<ConnectionManager>,
+    mut save_events: EventWriter<SaveWorldEvent>,
+    mut app_exit: EventWriter<AppExit>,
+    mut errors: EventWriter<ReportError>,
+    mut pending: ResMut<PendingSaves>,
+    store: Res<ChunkStore>,
+    #[cfg(feature = "sqlite")] database: Option<Res<Database>>,
+) {
+    // The save was requested a few frames ago, we can go away now
+    if let Some(frames) = scheduler.shutdown_frames.as_mut() {
+        if *frames > 0 {
+            *frames -= 1;
+            return;
+        }
+        scheduler.shutdown_frames = None;
+        // re-exec replaces the process before `AppExit` is handled, so the chunks unloaded since
+        // the save and the database don't get a chance to finish writing on exit
+        pending.finish(&store, &mut errors);
+        #[cfg(feature = "sqlite")]
+        if let Some(Err(e)) = database.map(|database| database.flush()) {
+            error!("Failed to flush the database before restarting: {}", e);
+        }
+        match scheduler.action {
+            RestartAction::Exit => {
+                info!("Exiting for scheduled restart");
+            }
+            RestartAction::Reexec => {
+                if let Err(e) = reexec() {
+                    error!("Failed to re-exec the server, exiting instead: {:?}", e);
+                }
+            }
+        }
+        app_exit.send(AppExit::Success);
+        return;
+    }
+
+    let Some(restart_at) = scheduler.restart_at else {
+        return;
+    };
+    let remaining = restart_at
+        .duration_since(SystemTime::now())
+        .unwrap_or_default()
+        .as_secs();
+
+    let mut announcement = None;
+    while scheduler.warnings.first().is_some_and(|w| remaining <= *w) {
+        let warning = scheduler.warnings.remove(0);
+        announcement = Some(format!("Server restarting in {}", format_delay(warning)));
+    }
+    if SystemTime::now() >= restart_at {
+        announcement = Some("Server restarting now, please reconnect shortly".to_string());
+        scheduler.restart_at = None;
+        scheduler.shutdown_frames = Some(SHUTDOWN_GRACE_FRAMES);
+        save_events.send(SaveWorldEvent);
+        info!("Restart time reached, saving world");
+        connection_manager
+            .send_message_to_target::<Channel1, _>(&ServerRestarting, NetworkTarget::All)
+            .unwrap_or_else(|e| {
+                errors.send(ReportError(GameError::send("ServerRestarting", e)));
+            });
+    }
+
+    if let Some(text) = announcement {
+        info!("{}", text);
+        connection_manager
+            .send_message_to_target::<ChatChannel, ChatLine>(
+                &ChatLine::system(text),
+                NetworkTarget::All,
+            )
+            .unwrap_or_else(|e| {
+                errors.send(ReportError(GameError::send("ChatLine", e)));
+            });
+    }
+}
+
+/// Replace the current process with a new instance of the server.
+/// On unix this `exec`s in place (keeping the pid, which supervisors appreciate), elsewhere a new
+/// process is spawned and the current one exits.
+/// The listener socket isn't handed over: the transports bind their own sockets from the settings,
+/// so the new process binds the port again, and clients sent `ServerRestarting` reconnect once it
+/// has.
+fn reexec() -> std::io::Result<()> {
+    let exe = std::env::current_exe()?;
+    let mut command = std::process::Command::new(exe);
+    command.args(std::env::args_os().skip(1));
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        // exec only returns on failure
+        Err(command.exec())
+    }
+    #[cfg(not(unix))]
+    {
+        command.spawn().map(|_| ())
+    }
+}
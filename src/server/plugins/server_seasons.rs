@@ -0,0 +1,102 @@
+use bevy::prelude::*;
+use lightyear::prelude::server::*;
+use lightyear::prelude::*;
+
+use crate::protocol::Channel1;
+use crate::settings_common::{SeasonSettings, Settings};
+use crate::shared::commands::{
+    ArgKind, CommandInvoked, CommandReply, CommandSpec, PermissionLevel, RegisterCommandExt,
+};
+use crate::shared::seasons::{CurrentSeason, Season, SeasonChanged};
+use crate::shared::world_generation::WorldState;
+
+// Server plugin advancing the season cycle and replicating it to clients
+pub struct ServerSeasonsPlugin;
+
+impl Plugin for ServerSeasonsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SeasonConfig(SeasonSettings::default()))
+            .register_command(
+                CommandSpec::new("season", "Lock the season to the given one, or 'unlock'")
+                    .arg("season", ArgKind::Word)
+                    .permission(PermissionLevel::Admin),
+            )
+            .add_systems(Startup, load_season_settings)
+            .add_systems(
+                Update,
+                (handle_season_command, update_season, send_season_on_connect).chain(),
+            );
+    }
+}
+
+#[derive(Resource)]
+pub struct SeasonConfig(pub SeasonSettings);
+
+fn load_season_settings(settings: Option<Res<Settings>>, mut config: ResMut<SeasonConfig>) {
+    if let Some(settings) = settings {
+        config.0 = settings.server.seasons.clone();
+    }
+}
+
+fn update_season(
+    config: Res<SeasonConfig>,
+    world_state: Res<WorldState>,
+    mut current: ResMut<CurrentSeason>,
+    mut connection_manager: ResMut<ConnectionManager>,
+) {
+    let season = config
+        .0
+        .locked
+        .unwrap_or_else(|| Season::at(world_state.world_time, config.0.length_days));
+    if current.0 == season {
+        return;
+    }
+    info!("Season changed to {:?}", season);
+    current.0 = season;
+    connection_manager
+        .send_message_to_target::<Channel1, SeasonChanged>(
+            &SeasonChanged(season),
+            NetworkTarget::All,
+        )
+        .unwrap_or_else(|e| {
+            error!("Failed to send season: {:?}", e);
+        });
+}
+
+fn send_season_on_connect(
+    mut connections: EventReader<ConnectEvent>,
+    current: Res<CurrentSeason>,
+    mut connection_manager: ResMut<ConnectionManager>,
+) {
+    for connection in connections.read() {
+        connection_manager
+            .send_message::<Channel1, _>(connection.client_id, &SeasonChanged(current.0))
+            .unwrap_or_else(|e| {
+                error!("Failed to send season: {:?}", e);
+            });
+    }
+}
+
+fn handle_season_command(
+    mut invoked: EventReader<CommandInvoked>,
+    mut replies: EventWriter<CommandReply>,
+    mut config: ResMut<SeasonConfig>,
+) {
+    for command in invoked.read().filter(|c| c.name == "season") {
+        let name = command.args.str("season").unwrap_or_default();
+        let reply = if name == "unlock" {
+            config.0.locked = None;
+            "Season cycle resumed".to_string()
+        } else if let Some(season) = Season::from_name(name) {
+            config.0.locked = Some(season);
+            format!("Season locked to {:?}", season)
+        } else {
+            format!(
+                "Unknown season '{}', expected one of {:?}",
+                name,
+                Season::ALL
+            )
+        };
+        replies.send(CommandReply::new(command.source, reply));
+    }
+}
@@ -0,0 +1,279 @@
+use bevy::asset::ron;
+use bevy::prelude::*;
+use bevy::time::common_conditions::on_timer;
+use bevy::utils::Duration;
+use lightyear::prelude::server::*;
+use lightyear::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::protocol::{Channel1, PlayerId, PlayerPosition, ShardHandoff};
+use crate::settings_common::{Settings, ShardSettings};
+use crate::shared::error::{GameError, ReportError};
+use crate::shared::instances::InstanceId;
+use crate::shared::world_generation::{ChunkCoord, WorldConfig};
+
+// How often a shard refreshes its heartbeat and reads the cluster state again
+const COORDINATOR_INTERVAL: Duration = Duration::from_secs(2);
+// Shards whose heartbeat is older than this are considered down, and their regions reassigned
+const SHARD_TIMEOUT_SECS: u64 = 10;
+// How often players are checked for having left the regions of this shard
+const HANDOFF_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+// Seconds a handed off client has to reconnect elsewhere before it is disconnected
+const HANDOFF_GRACE_SECS: f64 = 2.0;
+
+// Server plugin running this server as one shard of a cluster (experimental). Shards share the
+// chunk and profile stores, and a coordinator directory where each shard keeps a heartbeat.
+// Every region of the world belongs to one live shard; players walking into a region owned by
+// another shard are handed off to it.
+pub struct ServerShardsPlugin;
+
+impl Plugin for ServerShardsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, load_shard_settings).add_systems(
+            Update,
+            (
+                refresh_cluster.run_if(on_timer(COORDINATOR_INTERVAL)),
+                hand_off_players.run_if(on_timer(HANDOFF_CHECK_INTERVAL)),
+                disconnect_handed_off,
+            )
+                .chain()
+                .run_if(resource_exists::<ShardMap>),
+        );
+    }
+}
+
+/// A shard as registered with the coordinator
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ShardInfo {
+    pub id: u32,
+    pub addr: SocketAddr,
+    /// Unix time of the shard's last heartbeat, in seconds
+    pub heartbeat: u64,
+}
+
+/// Regions assigned by hand, overriding the default split. Stored as `ownership.ron` in the
+/// cluster directory.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Ownership {
+    pub regions: HashMap<(i32, i32), u32>,
+}
+
+/// The coordinator: a directory shared by the shards
+#[derive(Clone, Debug)]
+pub struct Coordinator {
+    dir: PathBuf,
+}
+
+impl Coordinator {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn shard_path(&self, id: u32) -> PathBuf {
+        self.dir.join("shards").join(format!("{}.ron", id))
+    }
+
+    pub fn heartbeat(&self, shard: &ShardInfo) -> Result<(), GameError> {
+        let text = ron::to_string(shard).map_err(|e| GameError::Shard(e.to_string()))?;
+        let path = self.shard_path(shard.id);
+        fs::create_dir_all(self.dir.join("shards"))?;
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, text)?;
+        Ok(fs::rename(tmp, path)?)
+    }
+
+    /// Shards with a recent heartbeat, by id
+    pub fn live_shards(&self, now: u64) -> Result<Vec<ShardInfo>, GameError> {
+        let mut shards = Vec::new();
+        for entry in fs::read_dir(self.dir.join("shards"))? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|extension| extension != "ron") {
+                continue;
+            }
+            let text = fs::read_to_string(&path)?;
+            let shard: ShardInfo = ron::from_str(&text)
+                .map_err(|e| GameError::Shard(format!("{}: {}", path.display(), e)))?;
+            if now.saturating_sub(shard.heartbeat) <= SHARD_TIMEOUT_SECS {
+                shards.push(shard);
+            }
+        }
+        shards.sort_by_key(|shard| shard.id);
+        Ok(shards)
+    }
+
+    pub fn ownership(&self) -> Result<Ownership, GameError> {
+        let path = self.dir.join("ownership.ron");
+        if !path.exists() {
+            return Ok(Ownership::default());
+        }
+        let text = fs::read_to_string(&path)?;
+        ron::from_str(&text).map_err(|e| GameError::Shard(format!("{}: {}", path.display(), e)))
+    }
+}
+
+/// This shard's view of the cluster
+#[derive(Resource, Debug)]
+pub struct ShardMap {
+    pub me: ShardInfo,
+    pub region_chunks: i32,
+    pub coordinator: Coordinator,
+    pub live: Vec<ShardInfo>,
+    pub ownership: Ownership,
+}
+
+impl ShardMap {
+    fn new(settings: &ShardSettings) -> Self {
+        let me = ShardInfo {
+            id: settings.id,
+            addr: settings.public_addr,
+            heartbeat: unix_now(),
+        };
+        Self {
+            live: vec![me.clone()],
+            me,
+            region_chunks: settings.region_chunks.max(1),
+            coordinator: Coordinator::new(&settings.cluster_dir),
+            ownership: Ownership::default(),
+        }
+    }
+
+    pub fn region_of(&self, coord: ChunkCoord) -> (i32, i32) {
+        (
+            coord.x.div_euclid(self.region_chunks),
+            coord.y.div_euclid(self.region_chunks),
+        )
+    }
+
+    /// The live shard owning a region: the one it was assigned to if it's up, otherwise live
+    /// shards take turns along the x axis
+    pub fn owner_of(&self, region: (i32, i32)) -> &ShardInfo {
+        if let Some(shard) = self
+            .ownership
+            .regions
+            .get(&region)
+            .and_then(|id| self.live.iter().find(|shard| shard.id == *id))
+        {
+            return shard;
+        }
+        &self.live[region.0.rem_euclid(self.live.len() as i32) as usize]
+    }
+
+    pub fn owns_chunk(&self, coord: ChunkCoord) -> bool {
+        self.owner_of(self.region_of(coord)).id == self.me.id
+    }
+}
+
+/// Marks a player that was told to reconnect to another shard
+#[derive(Component, Debug)]
+pub struct HandedOff {
+    pub at: f64,
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+fn load_shard_settings(mut commands: Commands, settings: Option<Res<Settings>>) {
+    let Some(shard) = settings.and_then(|settings| settings.server.shard.clone()) else {
+        return;
+    };
+    info!(
+        "Running as shard {} of the cluster in {}",
+        shard.id, shard.cluster_dir
+    );
+    commands.insert_resource(ShardMap::new(&shard));
+}
+
+// Send our heartbeat and read which shards are up and who owns what
+fn refresh_cluster(mut map: ResMut<ShardMap>, mut errors: EventWriter<ReportError>) {
+    let now = unix_now();
+    map.me.heartbeat = now;
+    if let Err(e) = map.coordinator.heartbeat(&map.me) {
+        errors.send(ReportError(e));
+        return;
+    }
+    match map.coordinator.live_shards(now) {
+        // our own heartbeat was just written, so we're always in the list
+        Ok(live) if !live.is_empty() => {
+            let ids =
+                |shards: &[ShardInfo]| shards.iter().map(|shard| shard.id).collect::<Vec<_>>();
+            if ids(&live) != ids(&map.live) {
+                info!("Live shards: {:?}", ids(&live));
+            }
+            map.live = live;
+        }
+        Ok(_) => {}
+        Err(e) => errors.send(ReportError(e)),
+    }
+    match map.coordinator.ownership() {
+        Ok(ownership) => map.ownership = ownership,
+        Err(e) => errors.send(ReportError(e)),
+    }
+}
+
+// Tell players outside of our regions to reconnect to the shard owning theirs. The player is
+// saved to the shared profile store when it disconnects, and loaded back by the other shard.
+fn hand_off_players(
+    mut commands: Commands,
+    time: Res<Time>,
+    map: Res<ShardMap>,
+    world_config: Res<WorldConfig>,
+    // instances live on the shard that created them
+    players: Query<(Entity, &PlayerId, &PlayerPosition), (Without<InstanceId>, Without<HandedOff>)>,
+    mut connection_manager: ResMut<ConnectionManager>,
+    mut errors: EventWriter<ReportError>,
+) {
+    let grid = world_config.grid();
+    for (entity, player_id, position) in players.iter() {
+        let region = map.region_of(grid.world_to_chunk(position.0));
+        let owner = map.owner_of(region);
+        if owner.id == map.me.id {
+            continue;
+        }
+        info!(
+            "Handing player {} off to shard {} for region {:?}",
+            player_id.client_id(),
+            owner.id,
+            region
+        );
+        let handoff = ShardHandoff {
+            server_addr: owner.addr,
+        };
+        if let Err(e) =
+            connection_manager.send_message::<Channel1, _>(player_id.client_id(), &handoff)
+        {
+            errors.send(ReportError(GameError::send("ShardHandoff", e)));
+        }
+        commands.entity(entity).insert(HandedOff {
+            at: time.elapsed_secs_f64(),
+        });
+    }
+}
+
+// Clients normally leave on their own when handed off; this catches the ones that don't
+fn disconnect_handed_off(
+    time: Res<Time>,
+    players: Query<(&PlayerId, &HandedOff)>,
+    mut connections: ResMut<ServerConnections>,
+) {
+    let now = time.elapsed_secs_f64();
+    for (player_id, handed_off) in players.iter() {
+        if now - handed_off.at < HANDOFF_GRACE_SECS {
+            continue;
+        }
+        if let Err(e) = connections.disconnect(player_id.client_id()) {
+            error!(
+                "Failed to disconnect handed off player {}: {:?}",
+                player_id.client_id(),
+                e
+            );
+        }
+    }
+}
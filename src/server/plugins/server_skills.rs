@@ -0,0 +1,203 @@
+use bevy::asset::ron;
+use bevy::prelude::*;
+use lightyear::prelude::*;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+
+use crate::protocol::PlayerId;
+use crate::settings_common::Settings;
+use crate::shared::commands::{CommandReply, CommandSource};
+use crate::shared::error::{GameError, ReportError};
+use crate::shared::skills::{Skill, SkillLevel, SkillLevels, Skills};
+
+// Server plugin granting skill experience for actions, and computing the levels players reach
+pub struct ServerSkillsPlugin;
+
+impl Plugin for ServerSkillsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SkillCurve>()
+            .add_event::<SkillActionEvent>()
+            .add_systems(Startup, load_skill_curve)
+            .add_systems(Update, (grant_skill_xp, update_skill_levels).chain());
+    }
+}
+
+/// Sent for every action training a skill, e.g. each harvest hit. The curve decides how much
+/// experience it's worth.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct SkillActionEvent {
+    pub client_id: ClientId,
+    pub skill: Skill,
+}
+
+/// A level of the curve, with the bonuses it grants
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct SkillTier {
+    // Experience needed to reach the level
+    pub xp: u32,
+    // Multiplier of the damage each harvest deals to a resource node
+    #[serde(default = "default_speed")]
+    pub speed: f32,
+    // Items obtained on top of the usual one when a node is depleted
+    #[serde(default)]
+    pub extra_yield: u32,
+}
+
+fn default_speed() -> f32 {
+    1.0
+}
+
+impl Default for SkillTier {
+    fn default() -> Self {
+        Self {
+            xp: 0,
+            speed: default_speed(),
+            extra_yield: 0,
+        }
+    }
+}
+
+/// How experience turns into levels and bonuses, loaded from the file named in the server settings
+#[derive(Resource, Deserialize, Clone, Debug, PartialEq)]
+pub struct SkillCurve {
+    pub xp_per_action: BTreeMap<Skill, u32>,
+    // Sorted by experience; the first tier is level 1
+    pub tiers: Vec<SkillTier>,
+}
+
+impl Default for SkillCurve {
+    fn default() -> Self {
+        let tier = |xp, speed, extra_yield| SkillTier {
+            xp,
+            speed,
+            extra_yield,
+        };
+        Self {
+            xp_per_action: Skill::ALL.into_iter().map(|skill| (skill, 10)).collect(),
+            tiers: vec![
+                tier(0, 1.0, 0),
+                tier(100, 1.0, 0),
+                tier(300, 2.0, 0),
+                tier(700, 2.0, 1),
+                tier(1500, 3.0, 1),
+            ],
+        }
+    }
+}
+
+impl SkillCurve {
+    pub fn load(path: &str) -> Result<Self, GameError> {
+        let text = fs::read_to_string(path)?;
+        let mut curve: SkillCurve = ron::from_str(&text).map_err(|e| GameError::SkillCurve {
+            path: path.to_string(),
+            reason: e.to_string(),
+        })?;
+        curve.tiers.sort_by_key(|tier| tier.xp);
+        Ok(curve)
+    }
+
+    pub fn xp_per_action(&self, skill: Skill) -> u32 {
+        self.xp_per_action.get(&skill).copied().unwrap_or(0)
+    }
+
+    // Number of tiers reached with that much experience, i.e. the level
+    fn reached(&self, xp: u32) -> usize {
+        self.tiers
+            .iter()
+            .take_while(|tier| tier.xp <= xp)
+            .count()
+            .max(1)
+    }
+
+    /// Bonuses of the level reached with that much experience
+    pub fn tier(&self, xp: u32) -> SkillTier {
+        self.tiers
+            .get(self.reached(xp) - 1)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn level(&self, xp: u32) -> SkillLevel {
+        let reached = self.reached(xp);
+        SkillLevel {
+            level: reached as u32,
+            xp,
+            level_xp: self.tiers.get(reached - 1).map_or(0, |tier| tier.xp),
+            next_level_xp: self.tiers.get(reached).map(|tier| tier.xp),
+        }
+    }
+}
+
+fn load_skill_curve(
+    settings: Option<Res<Settings>>,
+    mut curve: ResMut<SkillCurve>,
+    mut errors: EventWriter<ReportError>,
+) {
+    let Some(settings) = settings else {
+        return;
+    };
+    match SkillCurve::load(&settings.server.skills.curve) {
+        Ok(loaded) => {
+            info!("Loaded a skill curve of {} levels", loaded.tiers.len());
+            *curve = loaded;
+        }
+        Err(e) => errors.send(ReportError(e)),
+    }
+}
+
+fn grant_skill_xp(
+    mut actions: EventReader<SkillActionEvent>,
+    mut replies: EventWriter<CommandReply>,
+    curve: Res<SkillCurve>,
+    mut players: Query<(&PlayerId, &mut Skills)>,
+) {
+    for action in actions.read() {
+        let Some((_, mut skills)) = players
+            .iter_mut()
+            .find(|(id, _)| id.client_id() == action.client_id)
+        else {
+            continue;
+        };
+        let before = skills.xp(action.skill);
+        let after = before.saturating_add(curve.xp_per_action(action.skill));
+        if after == before {
+            continue;
+        }
+        skills.xp.insert(action.skill, after);
+        let level = curve.level(after).level;
+        if level > curve.level(before).level {
+            replies.send(CommandReply::new(
+                CommandSource::Client(action.client_id),
+                format!("Your {:?} skill is now level {}", action.skill, level),
+            ));
+        }
+    }
+}
+
+// Levels are replicated rather than experience, so that clients don't need the curve
+fn update_skill_levels(
+    mut commands: Commands,
+    curve: Res<SkillCurve>,
+    mut players: Query<(Entity, Ref<Skills>, Option<&mut SkillLevels>)>,
+) {
+    for (entity, skills, levels) in players.iter_mut() {
+        if levels.is_some() && !skills.is_changed() && !curve.is_changed() {
+            continue;
+        }
+        let computed = SkillLevels {
+            levels: Skill::ALL
+                .into_iter()
+                .map(|skill| (skill, curve.level(skills.xp(skill))))
+                .collect(),
+        };
+        match levels {
+            Some(mut levels) => {
+                levels.set_if_neq(computed);
+            }
+            None => {
+                commands.entity(entity).insert(computed);
+            }
+        }
+    }
+}
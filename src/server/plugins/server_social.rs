@@ -0,0 +1,182 @@
+use bevy::prelude::*;
+use lightyear::prelude::server::*;
+use lightyear::prelude::*;
+
+use crate::protocol::{Channel1, PlayerId, PlayerName};
+use crate::shared::commands::{
+    ArgKind, CommandInvoked, CommandReply, CommandSource, CommandSpec, RegisterCommandExt,
+};
+use crate::shared::error::{GameError, ReportError};
+use crate::shared::social::{Relation, SetRelation, SocialLists, SocialListsSync};
+
+// Server plugin managing the players each player muted or blocked. The lists live on the player
+// entity, are saved with the profile, and decide what gets relayed to whom.
+pub struct ServerSocialPlugin;
+
+impl Plugin for ServerSocialPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_command(
+            CommandSpec::new("mute", "Stop seeing a player's chat").arg("player", ArgKind::Text),
+        )
+        .register_command(
+            CommandSpec::new("unmute", "See a muted player's chat again")
+                .arg("player", ArgKind::Text),
+        )
+        .register_command(
+            CommandSpec::new(
+                "block",
+                "Stop seeing a player's chat, name, emotes and trade requests",
+            )
+            .arg("player", ArgKind::Text),
+        )
+        .register_command(
+            CommandSpec::new("unblock", "Stop blocking a player").arg("player", ArgKind::Text),
+        )
+        .register_command(CommandSpec::new(
+            "ignored",
+            "List the players you muted or blocked",
+        ))
+        .add_systems(
+            Update,
+            (
+                handle_social_commands,
+                receive_relation_requests,
+                send_social_lists,
+            )
+                .chain(),
+        );
+    }
+}
+
+fn relation_name(relation: Relation) -> &'static str {
+    match relation {
+        Relation::Muted => "muted",
+        Relation::Blocked => "blocked",
+    }
+}
+
+fn handle_social_commands(
+    mut invoked: EventReader<CommandInvoked>,
+    mut replies: EventWriter<CommandReply>,
+    players: Query<(&PlayerId, &PlayerName)>,
+    mut lists: Query<(&PlayerId, &mut SocialLists)>,
+) {
+    for command in invoked.read() {
+        let relation = match command.name {
+            "mute" => Some(Relation::Muted),
+            "block" => Some(Relation::Blocked),
+            "unmute" | "unblock" | "ignored" => None,
+            _ => continue,
+        };
+        let CommandSource::Client(client_id) = command.source else {
+            replies.send(CommandReply::new(
+                command.source,
+                "Only players can mute or block",
+            ));
+            continue;
+        };
+        let Some((_, mut own)) = lists.iter_mut().find(|(id, _)| id.client_id() == client_id)
+        else {
+            continue;
+        };
+
+        if command.name == "ignored" {
+            let mut entries: Vec<String> = own
+                .entries
+                .values()
+                .map(|entry| format!("{} ({})", entry.name, relation_name(entry.relation)))
+                .collect();
+            entries.sort();
+            let reply = if entries.is_empty() {
+                "You haven't muted or blocked anyone".to_string()
+            } else {
+                entries.join(", ")
+            };
+            replies.send(CommandReply::new(command.source, reply));
+            continue;
+        }
+
+        // online players first, then the lists, so that offline players can be unmuted
+        let wanted = command.args.str("player").unwrap_or_default().trim();
+        let target = players
+            .iter()
+            .find(|(_, name)| name.0.eq_ignore_ascii_case(wanted))
+            .map(|(id, name)| (id.client_id(), name.0.clone()))
+            .or_else(|| {
+                own.entries
+                    .iter()
+                    .find(|(_, entry)| entry.name.eq_ignore_ascii_case(wanted))
+                    .map(|(id, entry)| (*id, entry.name.clone()))
+            });
+        let Some((target, name)) = target else {
+            replies.send(CommandReply::new(
+                command.source,
+                format!("There is no player named '{}'", wanted),
+            ));
+            continue;
+        };
+        if target == client_id {
+            replies.send(CommandReply::new(
+                command.source,
+                "You can't mute or block yourself",
+            ));
+            continue;
+        }
+
+        let reply = match (relation, own.relation(target)) {
+            (Some(relation), _) => {
+                own.set(target, name.clone(), Some(relation));
+                format!("{} is now {}", name, relation_name(relation))
+            }
+            (None, Some(previous)) => {
+                own.set(target, name.clone(), None);
+                format!("{} is no longer {}", name, relation_name(previous))
+            }
+            (None, None) => format!("{} isn't muted or blocked", name),
+        };
+        replies.send(CommandReply::new(command.source, reply));
+    }
+}
+
+// Toggles from the client's roster
+fn receive_relation_requests(
+    mut events: EventReader<MessageEvent<SetRelation>>,
+    players: Query<(&PlayerId, &PlayerName)>,
+    mut lists: Query<(&PlayerId, &mut SocialLists)>,
+) {
+    for event in events.read() {
+        let client_id = event.from();
+        let request = event.message();
+        if request.player == client_id {
+            continue;
+        }
+        let Some((_, mut own)) = lists.iter_mut().find(|(id, _)| id.client_id() == client_id)
+        else {
+            continue;
+        };
+        let name = players
+            .iter()
+            .find(|(id, _)| id.client_id() == request.player)
+            .map(|(_, name)| name.0.clone());
+        match (name, request.relation) {
+            (Some(name), relation) => own.set(request.player, name, relation),
+            // players who left can only be removed from the lists
+            (None, None) => own.set(request.player, String::new(), None),
+            (None, Some(_)) => {}
+        }
+    }
+}
+
+fn send_social_lists(
+    lists: Query<(&PlayerId, &SocialLists), Changed<SocialLists>>,
+    mut connection_manager: ResMut<ConnectionManager>,
+    mut errors: EventWriter<ReportError>,
+) {
+    for (player_id, social) in lists.iter() {
+        if let Err(e) = connection_manager
+            .send_message::<Channel1, _>(player_id.client_id(), &SocialListsSync(social.clone()))
+        {
+            errors.send(ReportError(GameError::send("SocialListsSync", e)));
+        }
+    }
+}
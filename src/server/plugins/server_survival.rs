@@ -0,0 +1,231 @@
+use bevy::prelude::*;
+use lightyear::prelude::server::MessageEvent;
+use lightyear::prelude::*;
+
+use crate::protocol::{PlayerId, PlayerPosition};
+use crate::shared::feature_flags::ServerFeatureFlags;
+use crate::shared::instances::InstanceId;
+use crate::shared::items::{HeldItem, Inventory};
+pub use crate::shared::survival::RESPAWN_POSITION;
+use crate::shared::survival::{
+    Breath, EatHeldItem, Health, Hunger, Spectator, SurvivalPaused, SwimModifiers,
+};
+use crate::shared::world_generation::{Chunk, TileType, WorldConfig, WorldGrid, WorldState};
+
+// Breath regained per second out of deep water
+const BREATH_RECOVERY: f32 = 3.0;
+// Damage per second once out of breath
+const DROWNING_DAMAGE: f32 = 10.0;
+// Hunger lost per second; a full stomach lasts about twenty minutes
+const HUNGER_DRAIN: f32 = 0.08;
+// Damage per second once hunger is empty
+const STARVATION_DAMAGE: f32 = 1.0;
+
+// Server plugin for player survival: breath and drowning, hunger and starvation, death and respawn
+pub struct ServerSurvivalPlugin;
+
+impl Plugin for ServerSurvivalPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<PlayerDiedEvent>().add_systems(
+            Update,
+            (
+                add_survival_components,
+                drown_players,
+                starve_players,
+                eat_held_items,
+                respawn_dead_players,
+            )
+                .chain(),
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeathCause {
+    Drowned,
+    Starved,
+    Froze,
+    Overheated,
+}
+
+/// Sent when a player's health reaches zero, before they are respawned
+#[derive(Event, Debug, Clone)]
+pub struct PlayerDiedEvent {
+    pub client_id: ClientId,
+    pub position: Vec2,
+    pub cause: DeathCause,
+}
+
+fn add_survival_components(
+    mut commands: Commands,
+    players: Query<Entity, (Added<PlayerId>, Without<Health>)>,
+) {
+    for entity in players.iter() {
+        commands.entity(entity).insert((
+            Health::default(),
+            Breath::default(),
+            Hunger::default(),
+            SwimModifiers::default(),
+        ));
+    }
+}
+
+/// Tile type under the given world position, if its chunk is loaded
+pub fn tile_type_at(
+    position: Vec2,
+    world_state: &WorldState,
+    world_config: &WorldConfig,
+    chunks: &Query<&Chunk>,
+) -> Option<TileType> {
+    let (x, y) = WorldGrid::world_to_tile(position);
+    let coord = world_config.grid().tile_to_chunk((x, y));
+    let (local_x, local_y) = world_config.grid().tile_to_local((x, y));
+    let chunk = chunks.get(*world_state.chunks.get(&coord)?).ok()?;
+    Some(chunk.get_tile(local_x, local_y)?.tile_type)
+}
+
+fn drown_players(
+    time: Res<Time>,
+    flags: Res<ServerFeatureFlags>,
+    world_state: Res<WorldState>,
+    world_config: Res<WorldConfig>,
+    chunks: Query<&Chunk>,
+    mut players: Query<
+        (
+            &PlayerId,
+            &PlayerPosition,
+            &SwimModifiers,
+            &mut Breath,
+            &mut Health,
+        ),
+        // instanced players stand on their instance's tiles, not the overworld's
+        (
+            Without<SurvivalPaused>,
+            Without<InstanceId>,
+            Without<Spectator>,
+        ),
+    >,
+    mut deaths: EventWriter<PlayerDiedEvent>,
+) {
+    if flags.creative {
+        return;
+    }
+    let delta = time.delta_secs();
+    for (player_id, position, modifiers, mut breath, mut health) in players.iter_mut() {
+        let in_deep_water = tile_type_at(position.0, &world_state, &world_config, &chunks)
+            == Some(TileType::DeepWater);
+
+        if !in_deep_water || modifiers.in_boat {
+            if breath.current < breath.max {
+                breath.current = (breath.current + BREATH_RECOVERY * delta).min(breath.max);
+            }
+            continue;
+        }
+
+        if breath.current > 0.0 {
+            breath.current = (breath.current - modifiers.breath_drain * delta).max(0.0);
+            continue;
+        }
+
+        health.current = (health.current - DROWNING_DAMAGE * delta).max(0.0);
+        if health.current == 0.0 {
+            deaths.send(PlayerDiedEvent {
+                client_id: player_id.client_id(),
+                position: position.0,
+                cause: DeathCause::Drowned,
+            });
+        }
+    }
+}
+
+fn starve_players(
+    time: Res<Time>,
+    flags: Res<ServerFeatureFlags>,
+    mut players: Query<
+        (&PlayerId, &PlayerPosition, &mut Hunger, &mut Health),
+        (Without<SurvivalPaused>, Without<Spectator>),
+    >,
+    mut deaths: EventWriter<PlayerDiedEvent>,
+) {
+    if !flags.hunger || flags.creative {
+        return;
+    }
+    let delta = time.delta_secs();
+    for (player_id, position, mut hunger, mut health) in players.iter_mut() {
+        if hunger.current > 0.0 {
+            hunger.current = (hunger.current - HUNGER_DRAIN * delta).max(0.0);
+            continue;
+        }
+        if health.current == 0.0 {
+            continue;
+        }
+        health.current = (health.current - STARVATION_DAMAGE * delta).max(0.0);
+        if health.current == 0.0 {
+            deaths.send(PlayerDiedEvent {
+                client_id: player_id.client_id(),
+                position: position.0,
+                cause: DeathCause::Starved,
+            });
+        }
+    }
+}
+
+// Eat one of the held item when it is food and the player is hungry enough for it to matter
+fn eat_held_items(
+    mut events: EventReader<MessageEvent<EatHeldItem>>,
+    mut players: Query<(&PlayerId, &HeldItem, &mut Inventory, &mut Hunger)>,
+) {
+    for event in events.read() {
+        let client_id = *event.from();
+        let Some((_, held, mut inventory, mut hunger)) = players
+            .iter_mut()
+            .find(|(id, _, _, _)| id.client_id() == client_id)
+        else {
+            continue;
+        };
+        let Some((kind, value)) = held
+            .kind
+            .and_then(|kind| kind.food_value().map(|value| (kind, value)))
+        else {
+            continue;
+        };
+        if hunger.current >= hunger.max || !inventory.remove(kind, 1) {
+            continue;
+        }
+        hunger.current = (hunger.current + value).min(hunger.max);
+    }
+}
+
+// On worlds with permadeath, the dead stay where they fell and become spectators
+fn respawn_dead_players(
+    mut commands: Commands,
+    mut deaths: EventReader<PlayerDiedEvent>,
+    flags: Res<ServerFeatureFlags>,
+    mut players: Query<(
+        Entity,
+        &PlayerId,
+        &mut PlayerPosition,
+        &mut Health,
+        &mut Breath,
+        &mut Hunger,
+    )>,
+) {
+    for death in deaths.read() {
+        info!("Player {} died: {:?}", death.client_id, death.cause);
+        let Some((entity, _, mut position, mut health, mut breath, mut hunger)) = players
+            .iter_mut()
+            .find(|(_, id, _, _, _, _)| id.client_id() == death.client_id)
+        else {
+            continue;
+        };
+        if flags.permadeath {
+            info!("Player {} is now a spectator", death.client_id);
+            commands.entity(entity).insert(Spectator);
+            continue;
+        }
+        position.0 = RESPAWN_POSITION;
+        *health = Health::default();
+        *breath = Breath::default();
+        *hunger = Hunger::default();
+    }
+}
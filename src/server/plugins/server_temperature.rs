@@ -0,0 +1,256 @@
+use bevy::prelude::*;
+use lightyear::prelude::server::*;
+use lightyear::prelude::*;
+
+use crate::protocol::{Channel1, PlayerId, PlayerPosition};
+use crate::server::plugins::{tile_type_at, DeathCause, PlayerDiedEvent};
+use crate::settings_common::{HazardSettings, Settings};
+use crate::shared::biome_map::BiomeMap;
+use crate::shared::error::{GameError, ReportError};
+use crate::shared::feature_flags::ServerFeatureFlags;
+use crate::shared::instances::InstanceId;
+use crate::shared::items::{HeldItem, Inventory, ItemKind};
+use crate::shared::survival::{EatHeldItem, Health, Spectator, SurvivalPaused};
+use crate::shared::temperature::{
+    CurrentWeather, Exposure, Hazard, Weather, WeatherChanged, MAX_EXPOSURE,
+};
+use crate::shared::world_generation::{seeded_hash, Chunk, WorldConfig, WorldGrid, WorldState};
+
+// Exposure gained per second in a hazardous biome, before severity and weather
+const EXPOSURE_RATE: f32 = 2.0;
+// Exposure lost per second when protected or out of hazardous biomes
+const RECOVERY_RATE: f32 = 5.0;
+// Damage per second at full exposure, before severity and weather
+const EXPOSURE_DAMAGE: f32 = 2.0;
+// Exposure taken away by drinking
+const DRINK_RELIEF: f32 = 40.0;
+
+// Server plugin for biome hazards: cold in tundras, heat in deserts, and the storms making them
+// worse
+pub struct ServerTemperaturePlugin;
+
+impl Plugin for ServerTemperaturePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                add_exposure,
+                update_weather,
+                send_weather_on_connect,
+                expose_players,
+                drink_water,
+                reset_exposure_on_death,
+            )
+                .chain(),
+        );
+    }
+}
+
+fn hazard_settings(settings: Option<Res<Settings>>) -> HazardSettings {
+    settings.map_or_else(HazardSettings::default, |settings| {
+        settings.server.hazards.clone()
+    })
+}
+
+fn add_exposure(
+    mut commands: Commands,
+    players: Query<Entity, (Added<PlayerId>, Without<Exposure>)>,
+) {
+    for entity in players.iter() {
+        commands.entity(entity).insert(Exposure::default());
+    }
+}
+
+// Roll the weather again every `weather_change_secs` of world time. Rolls only depend on the seed
+// and the world time, so a restarted server keeps its weather.
+fn update_weather(
+    settings: Option<Res<Settings>>,
+    world_state: Res<WorldState>,
+    world_config: Res<WorldConfig>,
+    mut current: ResMut<CurrentWeather>,
+    mut connection_manager: ResMut<ConnectionManager>,
+    mut errors: EventWriter<ReportError>,
+) {
+    let hazards = hazard_settings(settings);
+    let period = world_state.world_time as u64 / hazards.weather_change_secs.max(1);
+    let roll = seeded_hash(world_config.seed, ("weather", period));
+    let weather = if roll % 100 >= hazards.storm_chance as u64 {
+        Weather::Clear
+    } else if roll / 100 % 2 == 0 {
+        Weather::Blizzard
+    } else {
+        Weather::Sandstorm
+    };
+    if current.0 == weather {
+        return;
+    }
+    info!("Weather changed to {:?}", weather);
+    current.0 = weather;
+    connection_manager
+        .send_message_to_target::<Channel1, WeatherChanged>(
+            &WeatherChanged(weather),
+            NetworkTarget::All,
+        )
+        .unwrap_or_else(|e| {
+            errors.send(ReportError(GameError::send("WeatherChanged", e)));
+        });
+}
+
+fn send_weather_on_connect(
+    mut connections: EventReader<ConnectEvent>,
+    current: Res<CurrentWeather>,
+    mut connection_manager: ResMut<ConnectionManager>,
+    mut errors: EventWriter<ReportError>,
+) {
+    for connection in connections.read() {
+        connection_manager
+            .send_message::<Channel1, _>(connection.client_id, &WeatherChanged(current.0))
+            .unwrap_or_else(|e| {
+                errors.send(ReportError(GameError::send("WeatherChanged", e)));
+            });
+    }
+}
+
+fn expose_players(
+    time: Res<Time>,
+    settings: Option<Res<Settings>>,
+    flags: Res<ServerFeatureFlags>,
+    weather: Res<CurrentWeather>,
+    biome_map: Res<BiomeMap>,
+    world_state: Res<WorldState>,
+    world_config: Res<WorldConfig>,
+    chunks: Query<&Chunk>,
+    mut players: Query<
+        (
+            &PlayerId,
+            &PlayerPosition,
+            &HeldItem,
+            &mut Exposure,
+            &mut Health,
+        ),
+        // instances have no biome of their own
+        (
+            Without<SurvivalPaused>,
+            Without<InstanceId>,
+            Without<Spectator>,
+        ),
+    >,
+    mut deaths: EventWriter<PlayerDiedEvent>,
+) {
+    let severity = hazard_settings(settings).severity.max(0.0);
+    let delta = time.delta_secs();
+    for (player_id, position, held, mut exposure, mut health) in players.iter_mut() {
+        let hazard = Hazard::of(biome_map.biome_at_position(position.0)).filter(|_| severity > 0.0);
+        let protected = match hazard {
+            Some(Hazard::Cold) => held.kind == Some(ItemKind::Torch),
+            // wading cools players down
+            Some(Hazard::Heat) => tile_type_at(position.0, &world_state, &world_config, &chunks)
+                .is_some_and(|tile| tile.is_water()),
+            None => false,
+        };
+        if exposure.hazard != hazard || exposure.protected != protected {
+            exposure.hazard = hazard;
+            exposure.protected = protected;
+        }
+
+        let intensity = hazard.map_or(1.0, |hazard| hazard.intensity(weather.0));
+        let Some(hazard) = hazard.filter(|_| !protected) else {
+            if exposure.level > 0.0 {
+                exposure.level = (exposure.level - RECOVERY_RATE * delta).max(0.0);
+            }
+            continue;
+        };
+        if exposure.level < MAX_EXPOSURE {
+            exposure.level =
+                (exposure.level + EXPOSURE_RATE * severity * intensity * delta).min(MAX_EXPOSURE);
+            continue;
+        }
+        // exposure still builds up in creative, but nobody dies of it
+        if flags.creative || health.current == 0.0 {
+            continue;
+        }
+        health.current = (health.current - EXPOSURE_DAMAGE * severity * intensity * delta).max(0.0);
+        if health.current == 0.0 {
+            deaths.send(PlayerDiedEvent {
+                client_id: player_id.client_id(),
+                position: position.0,
+                cause: match hazard {
+                    Hazard::Cold => DeathCause::Froze,
+                    Hazard::Heat => DeathCause::Overheated,
+                },
+            });
+        }
+    }
+}
+
+// The eat key drinks the held water, or from water next to the player, filling up a waterskin
+fn drink_water(
+    mut events: EventReader<MessageEvent<EatHeldItem>>,
+    world_state: Res<WorldState>,
+    world_config: Res<WorldConfig>,
+    chunks: Query<&Chunk>,
+    mut players: Query<
+        (
+            &PlayerId,
+            &PlayerPosition,
+            &HeldItem,
+            &mut Inventory,
+            &mut Exposure,
+        ),
+        Without<InstanceId>,
+    >,
+) {
+    for event in events.read() {
+        let client_id = *event.from();
+        let Some((_, position, held, mut inventory, mut exposure)) = players
+            .iter_mut()
+            .find(|(id, _, _, _, _)| id.client_id() == client_id)
+        else {
+            continue;
+        };
+        match held.kind {
+            Some(ItemKind::Water) => {
+                if !inventory.remove(ItemKind::Water, 1) {
+                    continue;
+                }
+            }
+            // food is eaten instead
+            Some(kind) if kind.food_value().is_some() => continue,
+            _ => {
+                let (x, y) = WorldGrid::world_to_tile(position.0);
+                let next_to_water = (y - 1..=y + 1)
+                    .flat_map(|y| (x - 1..=x + 1).map(move |x| (x, y)))
+                    .any(|tile| {
+                        tile_type_at(
+                            WorldGrid::tile_to_world(tile),
+                            &world_state,
+                            &world_config,
+                            &chunks,
+                        )
+                        .is_some_and(|tile| tile.is_water())
+                    });
+                if !next_to_water {
+                    continue;
+                }
+                inventory.add(ItemKind::Water, 1);
+            }
+        }
+        // water does nothing against the cold
+        if exposure.hazard != Some(Hazard::Cold) {
+            exposure.level = (exposure.level - DRINK_RELIEF).max(0.0);
+        }
+    }
+}
+
+fn reset_exposure_on_death(
+    mut deaths: EventReader<PlayerDiedEvent>,
+    mut players: Query<(&PlayerId, &mut Exposure)>,
+) {
+    for death in deaths.read() {
+        for (player_id, mut exposure) in players.iter_mut() {
+            if player_id.client_id() == death.client_id {
+                *exposure = Exposure::default();
+            }
+        }
+    }
+}
@@ -0,0 +1,380 @@
+use bevy::prelude::*;
+use lightyear::prelude::server::*;
+use lightyear::prelude::*;
+use std::collections::{HashSet, VecDeque};
+
+use crate::protocol::{PlayerId, PlayerPosition, TerrainAction, TerrainEditRequest};
+use crate::server::plugins::{CommandPermissions, LandClaims, TileModifiedEvent};
+use crate::shared::commands::{CommandReply, CommandSource};
+use crate::shared::feature_flags::ServerFeatureFlags;
+use crate::shared::instances::InstanceId;
+use crate::shared::items::{Inventory, ItemKind};
+use crate::shared::survival::Spectator;
+use crate::shared::world_generation::{
+    is_cliff, is_traversable, tile_distance, Chunk, Decoration, Facing, Overlay, ResourceType,
+    Tile, TileType, WorldConfig, WorldGrid, WorldState,
+};
+
+// Height change applied by a single dig or raise
+pub const TERRAIN_STEP: f32 = 0.1;
+// Tiles raised above this height become walls that can't be walked through
+pub const WALL_HEIGHT: f32 = 0.9;
+// Dug tiles below this height fill with water from neighbouring water tiles
+pub const WATER_LEVEL: f32 = 0.2;
+// Maximum distance, in tiles, between a player and the tile they edit
+const EDIT_REACH: u32 = 2;
+// Maximum number of tiles flooded by a single edit
+const FLOW_LIMIT: usize = 64;
+// Stone taken from the inventory to build a furnace
+pub const FURNACE_COST: u32 = 8;
+// Wood taken from the inventory to build a door, and a gate
+const DOOR_COST: u32 = 4;
+const GATE_COST: u32 = 6;
+// Iron taken from the inventory to lay a rail
+const RAIL_COST: u32 = 1;
+// Wood taken from the inventory to build a chest, and iron to build an inserter
+const CHEST_COST: u32 = 6;
+const INSERTER_COST: u32 = 2;
+// Iron taken from the inventory to build a generator and an assembler, and copper to lay a wire
+const GENERATOR_COST: u32 = 6;
+const ASSEMBLER_COST: u32 = 4;
+const WIRE_COST: u32 = 1;
+// Items taken from the inventory to lay a tile of ground
+const PLACE_COST: u32 = 2;
+
+// Server plugin for digging, raising and laying terrain, building stairs up cliffs, furnaces, doors,
+// gates, rails, chests, inserters and power networks
+pub struct ServerTerrainPlugin;
+
+impl Plugin for ServerTerrainPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<TerrainEditedEvent>()
+            .add_systems(Update, handle_terrain_edits);
+    }
+}
+
+/// Sent when a player successfully edits a tile
+#[derive(Event, Clone, Copy, Debug)]
+pub struct TerrainEditedEvent {
+    pub client_id: ClientId,
+    pub tile: (i32, i32),
+    pub action: TerrainAction,
+}
+
+/// Traversability of a tile, taking its height and closed doors into account
+pub fn derive_traversable(tile: &Tile) -> bool {
+    tile.height < WALL_HEIGHT
+        && is_traversable(tile.tile_type, tile.resource)
+        && !tile.decoration.is_closed()
+}
+
+// Items taken from the inventory to build something
+fn build_cost(action: TerrainAction) -> Option<(ItemKind, u32)> {
+    match action {
+        TerrainAction::Furnace => Some((ItemKind::Stone, FURNACE_COST)),
+        TerrainAction::Door => Some((ItemKind::Wood, DOOR_COST)),
+        TerrainAction::Gate => Some((ItemKind::Wood, GATE_COST)),
+        TerrainAction::Rail => Some((ItemKind::Iron, RAIL_COST)),
+        TerrainAction::Chest => Some((ItemKind::Wood, CHEST_COST)),
+        TerrainAction::Inserter => Some((ItemKind::Iron, INSERTER_COST)),
+        TerrainAction::Generator => Some((ItemKind::Iron, GENERATOR_COST)),
+        TerrainAction::Wire => Some((ItemKind::Copper, WIRE_COST)),
+        TerrainAction::Assembler => Some((ItemKind::Iron, ASSEMBLER_COST)),
+        TerrainAction::Place(tile_type) => placed_from(tile_type).map(|kind| (kind, PLACE_COST)),
+        TerrainAction::Dig | TerrainAction::Raise | TerrainAction::Stairs => None,
+    }
+}
+
+// Ground players can lay, and the item it's laid from
+fn placed_from(tile_type: TileType) -> Option<ItemKind> {
+    match tile_type {
+        TileType::Stone => Some(ItemKind::Stone),
+        TileType::ShallowWater => Some(ItemKind::Water),
+        _ => None,
+    }
+}
+
+// Apply a terrain edit to a tile, returning false if the tile can't be edited that way. Inserters
+// face away from `builder`, the tile the player building them stands on.
+fn apply_action(tile: &mut Tile, action: TerrainAction, builder: (i32, i32)) -> bool {
+    match action {
+        TerrainAction::Dig => {
+            if tile.resource != ResourceType::None {
+                return false;
+            }
+            match tile.tile_type {
+                // digging exposes the sand/dirt underneath
+                TileType::Grass | TileType::Forest | TileType::Snow | TileType::Sand => {
+                    tile.tile_type = TileType::Sand;
+                }
+                _ => return false,
+            }
+            tile.height -= TERRAIN_STEP;
+        }
+        TerrainAction::Raise => {
+            match tile.tile_type {
+                // filling in shallow water turns it back into land
+                TileType::ShallowWater => tile.tile_type = TileType::Sand,
+                TileType::Grass | TileType::Sand | TileType::Snow => {}
+                _ => return false,
+            }
+            tile.height += TERRAIN_STEP;
+        }
+        TerrainAction::Place(tile_type) => {
+            // ground is laid over open ground, not into deep water or onto mountains
+            if placed_from(tile_type).is_none()
+                || tile.tile_type == tile_type
+                || matches!(tile.tile_type, TileType::DeepWater | TileType::Mountain)
+                || tile.resource != ResourceType::None
+                || tile.decoration.is_structure()
+            {
+                return false;
+            }
+            tile.tile_type = tile_type;
+        }
+        TerrainAction::Stairs
+        | TerrainAction::Furnace
+        | TerrainAction::Door
+        | TerrainAction::Gate
+        | TerrainAction::Rail
+        | TerrainAction::Chest
+        | TerrainAction::Inserter
+        | TerrainAction::Generator
+        | TerrainAction::Wire
+        | TerrainAction::Assembler => {
+            if tile.resource != ResourceType::None
+                || tile.tile_type.is_water()
+                || tile.decoration.is_structure()
+            {
+                return false;
+            }
+            tile.decoration = match action {
+                TerrainAction::Stairs => Decoration::Stairs,
+                TerrainAction::Furnace => Decoration::Furnace,
+                // doors and gates are built closed
+                TerrainAction::Door => Decoration::Door { open: false },
+                TerrainAction::Gate => Decoration::Gate { open: false },
+                TerrainAction::Chest => Decoration::Chest,
+                TerrainAction::Generator => Decoration::Generator,
+                TerrainAction::Wire => Decoration::Wire,
+                TerrainAction::Assembler => Decoration::Assembler,
+                TerrainAction::Inserter => Decoration::Inserter {
+                    facing: Facing::towards(builder, tile.position),
+                },
+                _ => Decoration::Rail,
+            };
+            tile.overlay = Overlay::None;
+            tile.traversable = derive_traversable(tile);
+            return true;
+        }
+    }
+    // the ground moved, taking whatever lay on it along
+    tile.decoration = Decoration::None;
+    tile.overlay = Overlay::None;
+    tile.traversable = derive_traversable(tile);
+    true
+}
+
+/// Mutable access to tiles by world coordinates, across chunks
+struct TileAccess<'a, 'w, 's> {
+    world_state: &'a WorldState,
+    grid: WorldGrid,
+    chunks: &'a mut Query<'w, 's, &'static mut Chunk>,
+}
+
+impl TileAccess<'_, '_, '_> {
+    fn get(&self, (x, y): (i32, i32)) -> Option<&Tile> {
+        let coord = self.grid.tile_to_chunk((x, y));
+        let (local_x, local_y) = self.grid.tile_to_local((x, y));
+        let chunk = self
+            .chunks
+            .get(*self.world_state.chunks.get(&coord)?)
+            .ok()?;
+        chunk.get_tile(local_x, local_y)
+    }
+
+    // Modify a tile in place, keeping what its chunk bakes from it up to date
+    fn modify<R>(&mut self, (x, y): (i32, i32), modify: impl FnOnce(&mut Tile) -> R) -> Option<R> {
+        let coord = self.grid.tile_to_chunk((x, y));
+        let (local_x, local_y) = self.grid.tile_to_local((x, y));
+        let mut chunk = self
+            .chunks
+            .get_mut(*self.world_state.chunks.get(&coord)?)
+            .ok()?;
+        chunk.modify_tile(local_x, local_y, modify)
+    }
+}
+
+fn neighbours((x, y): (i32, i32)) -> [(i32, i32); 4] {
+    [(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)]
+}
+
+/// Let water flow from neighbouring water tiles into `start` and onwards into connected tiles
+/// below the water level. Returns the flooded tiles with their previous state.
+fn flow_water(
+    tiles: &mut TileAccess,
+    start: (i32, i32),
+) -> Vec<((i32, i32), (TileType, ResourceType))> {
+    let mut flooded = Vec::new();
+    let mut queue = VecDeque::from([start]);
+    while let Some(position) = queue.pop_front() {
+        if flooded.len() >= FLOW_LIMIT {
+            break;
+        }
+        let Some(tile) = tiles.get(position) else {
+            continue;
+        };
+        if tile.tile_type.is_water()
+            || tile.height >= WATER_LEVEL
+            || tile.tile_type != TileType::Sand
+        {
+            continue;
+        }
+        let next_to_water = neighbours(position)
+            .iter()
+            .any(|n| tiles.get(*n).is_some_and(|t| t.tile_type.is_water()));
+        if !next_to_water {
+            continue;
+        }
+        let Some(before) = tiles.modify(position, |tile| {
+            let before = (tile.tile_type, tile.resource);
+            tile.tile_type = TileType::ShallowWater;
+            tile.traversable = derive_traversable(tile);
+            before
+        }) else {
+            continue;
+        };
+        flooded.push((position, before));
+        queue.extend(neighbours(position));
+    }
+    flooded
+}
+
+fn handle_terrain_edits(
+    mut events: EventReader<MessageEvent<TerrainEditRequest>>,
+    mut replies: EventWriter<CommandReply>,
+    mut modifications: EventWriter<TileModifiedEvent>,
+    mut edits: EventWriter<TerrainEditedEvent>,
+    world_state: Res<WorldState>,
+    world_config: Res<WorldConfig>,
+    claims: Res<LandClaims>,
+    permissions: Res<CommandPermissions>,
+    flags: Res<ServerFeatureFlags>,
+    mut players: Query<
+        (&PlayerId, &PlayerPosition, &mut Inventory),
+        (Without<InstanceId>, Without<Spectator>),
+    >,
+    mut chunks: Query<&mut Chunk>,
+) {
+    let mut tiles = TileAccess {
+        world_state: &world_state,
+        grid: world_config.grid(),
+        chunks: &mut chunks,
+    };
+    let occupied: HashSet<(i32, i32)> = players
+        .iter()
+        .map(|(_, position, _)| WorldGrid::world_to_tile(position.0))
+        .collect();
+    for event in events.read() {
+        let client_id = event.from();
+        let TerrainEditRequest {
+            tile: position,
+            action,
+        } = *event.message();
+        let reply = |text: &str| CommandReply::new(CommandSource::Client(client_id), text);
+
+        let Some((_, player_position, mut inventory)) = players
+            .iter_mut()
+            .find(|(id, _, _)| id.client_id() == client_id)
+        else {
+            continue;
+        };
+        let player_tile = WorldGrid::world_to_tile(player_position.0);
+        if tile_distance(position, player_tile) > EDIT_REACH {
+            replies.send(reply("That tile is too far away"));
+            continue;
+        }
+        let coord = world_config.grid().tile_to_chunk(position);
+        if !claims.can_modify(coord, client_id, &permissions) {
+            replies.send(reply("This land is claimed by someone else"));
+            continue;
+        }
+
+        let cost = build_cost(action).filter(|_| !flags.creative);
+        if let Some((kind, count)) = cost.filter(|(kind, count)| inventory.count(*kind) < *count) {
+            let name = match action {
+                TerrainAction::Place(tile_type) => format!("{:?} tile", tile_type),
+                _ => format!("{:?}", action),
+            };
+            let material = format!("{:?}", kind);
+            replies.send(reply(&format!(
+                "A {} takes {} {} to build",
+                name.to_lowercase(),
+                count,
+                material.to_lowercase()
+            )));
+            continue;
+        }
+
+        // the edit is tried on a copy, so that it's only made once every rule agrees
+        let Some(current) = tiles.get(position).cloned() else {
+            continue;
+        };
+        let mut edited = current.clone();
+        if !apply_action(&mut edited, action, player_tile) {
+            replies.send(reply("You can't do that here"));
+            continue;
+        }
+        // nobody gets walled in: tiles players stand on stay walkable
+        if current.traversable && !edited.traversable && occupied.contains(&position) {
+            replies.send(reply("Someone is standing there"));
+            continue;
+        }
+        // stairs only go where there's a cliff to climb
+        if action == TerrainAction::Stairs
+            && !neighbours(position)
+                .into_iter()
+                .filter_map(|neighbour| tiles.get(neighbour))
+                .any(|neighbour| is_cliff(edited.height, neighbour.height))
+        {
+            replies.send(reply("Stairs need a cliff next to them"));
+            continue;
+        }
+        // ground is laid out from ground players can walk on, not in the middle of a lake
+        if matches!(action, TerrainAction::Place(_))
+            && !neighbours(position)
+                .into_iter()
+                .filter_map(|neighbour| tiles.get(neighbour))
+                .any(|neighbour| neighbour.traversable && !neighbour.tile_type.is_water())
+        {
+            replies.send(reply("Ground has to be laid next to walkable ground"));
+            continue;
+        }
+        let before = (current.tile_type, current.resource);
+        tiles.modify(position, |tile| *tile = edited);
+        if let Some((kind, count)) = cost {
+            inventory.remove(kind, count);
+        }
+        edits.send(TerrainEditedEvent {
+            client_id,
+            tile: position,
+            action,
+        });
+        let mut changed = vec![(position, before)];
+        if action == TerrainAction::Dig {
+            changed.extend(flow_water(&mut tiles, position));
+        }
+
+        for (position, before) in changed {
+            let Some(tile) = tiles.get(position) else {
+                continue;
+            };
+            modifications.send(TileModifiedEvent {
+                position,
+                author: Some(client_id),
+                before,
+                after: (tile.tile_type, tile.resource),
+            });
+        }
+    }
+}
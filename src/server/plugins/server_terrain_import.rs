@@ -0,0 +1,45 @@
+use bevy::prelude::*;
+use std::sync::Arc;
+
+use crate::settings_common::Settings;
+use crate::shared::biome_map::BiomeMap;
+use crate::shared::height_map::HeightMap;
+use crate::shared::structures::StructureMap;
+use crate::shared::terrain_import::ImportedTerrain;
+use crate::shared::world_generation::WorldConfig;
+
+// Server plugin loading the hand-made terrain from the settings into the world config, before
+// the spawn chunks are generated
+pub struct ServerTerrainImportPlugin;
+
+impl Plugin for ServerTerrainImportPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PreStartup, import_terrain);
+    }
+}
+
+fn import_terrain(
+    mut commands: Commands,
+    settings: Option<Res<Settings>>,
+    mut world_config: ResMut<WorldConfig>,
+) {
+    let Some(import) = settings.and_then(|settings| settings.server.terrain_import.clone()) else {
+        return;
+    };
+    // generating noise where the creator expected their terrain would end up saved with the world
+    let terrain = match ImportedTerrain::load(
+        &import.heightmap,
+        import.biome_map.as_deref(),
+        import.origin,
+        import.scale,
+    ) {
+        Ok(terrain) => terrain,
+        Err(e) => panic!("Can't import the terrain: {}", e),
+    };
+    info!("Imported terrain {:?} from {}", terrain, import.heightmap);
+    world_config.imported_terrain = Some(Arc::new(terrain));
+    // they were built from the config before the import
+    commands.insert_resource(HeightMap::new(world_config.clone()));
+    commands.insert_resource(BiomeMap::new(world_config.clone()));
+    commands.insert_resource(StructureMap::new(world_config.clone()));
+}
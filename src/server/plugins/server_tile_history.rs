@@ -0,0 +1,178 @@
+use bevy::prelude::*;
+use lightyear::prelude::*;
+use std::collections::{HashMap, VecDeque};
+
+use crate::protocol::{PlayerId, PlayerName};
+use crate::shared::commands::{
+    ArgKind, CommandInvoked, CommandReply, CommandSpec, PermissionLevel, RegisterCommandExt,
+};
+use crate::shared::world_generation::{ResourceType, TileType, WorldConfig, WorldState};
+
+// Number of modifications remembered per tile
+pub const TILE_HISTORY_LEN: usize = 8;
+
+// Server plugin recording who modified which tile, for moderation purposes
+pub struct ServerTileHistoryPlugin;
+
+impl Plugin for ServerTileHistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<TileModifiedEvent>()
+            .register_command(
+                CommandSpec::new("inspect", "Show who recently modified a tile")
+                    .alias("history")
+                    .arg("x", ArgKind::Int)
+                    .arg("y", ArgKind::Int)
+                    .permission(PermissionLevel::Moderator),
+            )
+            .add_systems(
+                Update,
+                (record_tile_modifications, handle_inspect_command).chain(),
+            );
+    }
+}
+
+/// Sent by every system that mutates a tile, so the change ends up in the tile's history
+#[derive(Event, Clone, Debug)]
+pub struct TileModifiedEvent {
+    pub position: (i32, i32), // World coordinates
+    pub author: Option<ClientId>,
+    pub before: (TileType, ResourceType),
+    pub after: (TileType, ResourceType),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TileModification {
+    pub author: Option<ClientId>,
+    pub time: f64, // WorldState::world_time at which the change happened
+    pub before: (TileType, ResourceType),
+    pub after: (TileType, ResourceType),
+}
+
+/// Per-chunk metadata holding the last [`TILE_HISTORY_LEN`] modifications of each modified tile.
+/// Lives next to the `Chunk` component on the server only; it is never sent to clients.
+#[derive(Component, Default, Debug)]
+pub struct TileHistory {
+    entries: HashMap<(usize, usize), VecDeque<TileModification>>,
+}
+
+impl TileHistory {
+    pub fn record(&mut self, local: (usize, usize), modification: TileModification) {
+        let entries = self.entries.entry(local).or_default();
+        entries.push_back(modification);
+        while entries.len() > TILE_HISTORY_LEN {
+            entries.pop_front();
+        }
+    }
+
+    // Modifications of a tile, oldest first
+    pub fn get(&self, local: (usize, usize)) -> impl Iterator<Item = &TileModification> {
+        self.entries.get(&local).into_iter().flatten()
+    }
+}
+
+fn record_tile_modifications(
+    mut commands: Commands,
+    mut events: EventReader<TileModifiedEvent>,
+    world_state: Res<WorldState>,
+    world_config: Res<WorldConfig>,
+    mut histories: Query<&mut TileHistory>,
+) {
+    // Chunks that don't have a history yet get one inserted once all events are processed
+    let mut new_histories: HashMap<Entity, TileHistory> = HashMap::new();
+
+    for event in events.read() {
+        let (world_x, world_y) = event.position;
+        let coord = world_config.grid().tile_to_chunk((world_x, world_y));
+        let local = world_config.grid().tile_to_local((world_x, world_y));
+        let Some(&chunk_entity) = world_state.chunks.get(&coord) else {
+            warn!(
+                "Tile {:?} modified in unloaded chunk {:?}",
+                event.position, coord
+            );
+            continue;
+        };
+        let modification = TileModification {
+            author: event.author,
+            time: world_state.world_time,
+            before: event.before,
+            after: event.after,
+        };
+        if let Ok(mut history) = histories.get_mut(chunk_entity) {
+            history.record(local, modification);
+        } else {
+            new_histories
+                .entry(chunk_entity)
+                .or_default()
+                .record(local, modification);
+        }
+    }
+
+    for (entity, history) in new_histories {
+        commands.entity(entity).insert(history);
+    }
+}
+
+fn handle_inspect_command(
+    mut invoked: EventReader<CommandInvoked>,
+    mut replies: EventWriter<CommandReply>,
+    world_state: Res<WorldState>,
+    world_config: Res<WorldConfig>,
+    histories: Query<&TileHistory>,
+    players: Query<(&PlayerId, &PlayerName)>,
+) {
+    for command in invoked.read().filter(|c| c.name == "inspect") {
+        let (Some(x), Some(y)) = (command.args.int("x"), command.args.int("y")) else {
+            continue;
+        };
+        let (x, y) = (x as i32, y as i32);
+        let coord = world_config.grid().tile_to_chunk((x, y));
+        let local = world_config.grid().tile_to_local((x, y));
+
+        let modifications: Vec<&TileModification> = world_state
+            .chunks
+            .get(&coord)
+            .and_then(|entity| histories.get(*entity).ok())
+            .map(|history| history.get(local).collect())
+            .unwrap_or_default();
+
+        if modifications.is_empty() {
+            replies.send(CommandReply::new(
+                command.source,
+                format!("Tile ({}, {}) has no recorded modifications", x, y),
+            ));
+            continue;
+        }
+
+        replies.send(CommandReply::new(
+            command.source,
+            format!(
+                "Tile ({}, {}) - last {} modifications:",
+                x,
+                y,
+                modifications.len()
+            ),
+        ));
+        for modification in modifications {
+            let author = match modification.author {
+                Some(client_id) => players
+                    .iter()
+                    .find(|(id, _)| id.client_id() == client_id)
+                    .map(|(_, name)| name.0.clone())
+                    .unwrap_or_else(|| format!("Player {}", client_id)),
+                None => "server".to_string(),
+            };
+            replies.send(CommandReply::new(
+                command.source,
+                format!(
+                    "  [{:.0}s] {}: {:?}/{:?} -> {:?}/{:?}",
+                    modification.time,
+                    author,
+                    modification.before.0,
+                    modification.before.1,
+                    modification.after.0,
+                    modification.after.1
+                ),
+            ));
+        }
+    }
+}
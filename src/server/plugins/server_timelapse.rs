@@ -0,0 +1,104 @@
+use bevy::prelude::*;
+use image::RgbImage;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::settings_common::{Settings, TimelapseSettings};
+use crate::shared::error::{GameError, ReportError};
+use crate::shared::map_image::MapRegion;
+use crate::shared::world_generation::{Chunk, ChunkCoord, WorldConfig, WorldState};
+
+// Server plugin recording a time-lapse of a region of the world: every so often the loaded chunks
+// of the region are drawn into a low resolution map, saved as a frame. `timelapse` mode assembles
+// the frames into an animation.
+pub struct ServerTimelapsePlugin;
+
+impl Plugin for ServerTimelapsePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Timelapse>()
+            .add_systems(Startup, load_timelapse_settings)
+            .add_systems(Update, record_timelapse_frame);
+    }
+}
+
+#[derive(Resource, Default)]
+struct Timelapse {
+    settings: Option<TimelapseSettings>,
+    region: Option<MapRegion>,
+    timer: Timer,
+    // Last frame recorded. Parts of the region with no chunk loaded keep what it showed.
+    frame: Option<RgbImage>,
+}
+
+fn load_timelapse_settings(settings: Option<Res<Settings>>, mut timelapse: ResMut<Timelapse>) {
+    let Some(settings) = settings.and_then(|settings| settings.server.timelapse.clone()) else {
+        return;
+    };
+    let region = MapRegion::between(settings.from, settings.to, settings.tiles_per_pixel);
+    info!(
+        "Recording a {}x{} time-lapse every {}s into {}",
+        region.width, region.height, settings.interval, settings.dir
+    );
+    timelapse.timer = Timer::from_seconds(settings.interval.max(1.0), TimerMode::Repeating);
+    timelapse.region = Some(region);
+    timelapse.settings = Some(settings);
+}
+
+fn record_timelapse_frame(
+    time: Res<Time>,
+    mut timelapse: ResMut<Timelapse>,
+    world_state: Res<WorldState>,
+    world_config: Res<WorldConfig>,
+    chunks: Query<&Chunk>,
+    mut errors: EventWriter<ReportError>,
+) {
+    let timelapse = &mut *timelapse;
+    let (Some(settings), Some(region)) = (&timelapse.settings, timelapse.region) else {
+        return;
+    };
+    if !timelapse.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let grid = world_config.grid();
+    let (min, max) = (
+        grid.tile_to_chunk(region.origin),
+        grid.tile_to_chunk((
+            region.origin.0 + (region.width * region.tiles_per_pixel) as i32 - 1,
+            region.origin.1 + (region.height * region.tiles_per_pixel) as i32 - 1,
+        )),
+    );
+    let loaded: Vec<&Chunk> = (min.y..=max.y)
+        .flat_map(|y| (min.x..=max.x).map(move |x| ChunkCoord { x, y }))
+        .filter_map(|coord| world_state.chunks.get(&coord))
+        .filter_map(|entity| chunks.get(*entity).ok())
+        .collect();
+    // nothing changes where nobody is, so an empty server doesn't fill the disk with copies
+    if loaded.is_empty() {
+        return;
+    }
+    let frame = timelapse.frame.get_or_insert_with(|| region.blank());
+    for chunk in loaded {
+        region.draw_chunk(frame, chunk);
+    }
+
+    // frames are named after the time they were taken, so that they sort across restarts
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let path = PathBuf::from(&settings.dir).join(format!("{:012}.png", seconds));
+    let saved = fs::create_dir_all(&settings.dir)
+        .map_err(GameError::from)
+        .and_then(|()| {
+            frame
+                .save(&path)
+                .map_err(|e| GameError::Timelapse(e.to_string()))
+        });
+    match saved {
+        Ok(()) => debug!("Saved time-lapse frame {}", path.display()),
+        Err(e) => {
+            errors.send(ReportError(e));
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use bevy::prelude::*;
+use bevy::time::common_conditions::on_timer;
+use bevy::utils::Duration;
+use lightyear::prelude::*;
+use std::collections::HashSet;
+
+use crate::protocol::{PlayerId, PlayerPosition, TerrainAction};
+use crate::server::plugins::{ResourceHarvestedEvent, TerrainEditedEvent};
+use crate::shared::commands::{
+    ArgKind, CommandInvoked, CommandReply, CommandSource, CommandSpec, RegisterCommandExt,
+};
+use crate::shared::items::{HeldItem, ItemKind};
+use crate::shared::tutorial::{Tutorial, TutorialStep};
+use crate::shared::world_generation::{Chunk, ResourceType, WorldConfig, WorldGrid, WorldState};
+
+// Tiles a player has to walk away from where they started the tutorial
+const MOVE_DISTANCE: f32 = 3.0;
+// Tiles searched around the player for a tree to mark
+const TREE_SEARCH_RADIUS: i32 = 12;
+const MARKER_INTERVAL: Duration = Duration::from_secs(1);
+
+// Server plugin walking new players through the basics, one step at a time
+pub struct ServerTutorialPlugin;
+
+impl Plugin for ServerTutorialPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_command(
+            CommandSpec::new("tutorial", "'skip' the tutorial, or 'restart' it")
+                .arg("action", ArgKind::Word),
+        )
+        .add_systems(
+            Update,
+            (
+                handle_tutorial_command,
+                start_tutorials,
+                advance_tutorials,
+                place_tutorial_markers.run_if(on_timer(MARKER_INTERVAL)),
+            )
+                .chain(),
+        );
+    }
+}
+
+// Where a player stood when they started the tutorial. Server only.
+#[derive(Component, Debug)]
+struct TutorialOrigin(Vec2);
+
+fn handle_tutorial_command(
+    mut commands: Commands,
+    mut invoked: EventReader<CommandInvoked>,
+    mut replies: EventWriter<CommandReply>,
+    players: Query<(Entity, &PlayerId, Has<Tutorial>)>,
+) {
+    for command in invoked.read().filter(|c| c.name == "tutorial") {
+        let CommandSource::Client(client_id) = command.source else {
+            replies.send(CommandReply::new(
+                command.source,
+                "Only players take the tutorial",
+            ));
+            continue;
+        };
+        let Some((entity, _, in_tutorial)) = players
+            .iter()
+            .find(|(_, id, _)| id.client_id() == client_id)
+        else {
+            continue;
+        };
+        let reply = match (command.args.str("action").unwrap_or_default(), in_tutorial) {
+            ("skip", true) => {
+                commands
+                    .entity(entity)
+                    .remove::<(Tutorial, TutorialOrigin)>();
+                "Tutorial skipped. Type /tutorial restart to take it again"
+            }
+            ("skip", false) => "You aren't taking the tutorial",
+            ("restart", _) => {
+                commands
+                    .entity(entity)
+                    .remove::<TutorialOrigin>()
+                    .insert(Tutorial::default());
+                "Tutorial restarted"
+            }
+            _ => "Usage: /tutorial skip|restart",
+        };
+        replies.send(CommandReply::new(command.source, reply));
+    }
+}
+
+fn start_tutorials(
+    mut commands: Commands,
+    mut replies: EventWriter<CommandReply>,
+    players: Query<(Entity, &PlayerId, &PlayerPosition), (With<Tutorial>, Without<TutorialOrigin>)>,
+) {
+    for (entity, player_id, position) in players.iter() {
+        commands.entity(entity).insert(TutorialOrigin(position.0));
+        replies.send(CommandReply::new(
+            CommandSource::Client(player_id.client_id()),
+            "Welcome! Follow the tutorial prompts, or type /tutorial skip",
+        ));
+    }
+}
+
+// Each step completes when the player does what it asks; actions done ahead of time don't count
+fn advance_tutorials(
+    mut commands: Commands,
+    mut harvested: EventReader<ResourceHarvestedEvent>,
+    mut edits: EventReader<TerrainEditedEvent>,
+    mut replies: EventWriter<CommandReply>,
+    mut players: Query<(
+        Entity,
+        &PlayerId,
+        &PlayerPosition,
+        &HeldItem,
+        &mut Tutorial,
+        &TutorialOrigin,
+    )>,
+) {
+    let felled: HashSet<ClientId> = harvested
+        .read()
+        .filter(|event| event.resource == ResourceType::Tree)
+        .map(|event| event.client_id)
+        .collect();
+    let raised: HashSet<ClientId> = edits
+        .read()
+        .filter(|event| event.action == TerrainAction::Raise)
+        .map(|event| event.client_id)
+        .collect();
+    for (entity, player_id, position, held, mut tutorial, origin) in players.iter_mut() {
+        let client_id = player_id.client_id();
+        let done = match tutorial.step {
+            TutorialStep::Move => position.0.distance(origin.0) >= MOVE_DISTANCE,
+            TutorialStep::HoldAxe => held.kind == Some(ItemKind::Axe),
+            TutorialStep::HarvestTree => felled.contains(&client_id),
+            TutorialStep::RaiseTerrain => raised.contains(&client_id),
+        };
+        if !done {
+            continue;
+        }
+        match tutorial.step.next() {
+            Some(next) => {
+                *tutorial = Tutorial {
+                    step: next,
+                    marker: None,
+                };
+            }
+            None => {
+                commands
+                    .entity(entity)
+                    .remove::<(Tutorial, TutorialOrigin)>();
+                info!("Player {} completed the tutorial", client_id);
+                replies.send(CommandReply::new(
+                    CommandSource::Client(client_id),
+                    "Tutorial complete. Have fun!",
+                ));
+            }
+        }
+    }
+}
+
+/// Closest tile holding a tree around a position, among loaded chunks
+fn nearest_tree(
+    position: Vec2,
+    world_state: &WorldState,
+    world_config: &WorldConfig,
+    chunks: &Query<&Chunk>,
+) -> Option<Vec2> {
+    let (center_x, center_y) = WorldGrid::world_to_tile(position);
+    let mut best: Option<(f32, Vec2)> = None;
+    for dy in -TREE_SEARCH_RADIUS..=TREE_SEARCH_RADIUS {
+        for dx in -TREE_SEARCH_RADIUS..=TREE_SEARCH_RADIUS {
+            let (x, y) = (center_x + dx, center_y + dy);
+            let coord = world_config.grid().tile_to_chunk((x, y));
+            let Some(chunk) = world_state
+                .chunks
+                .get(&coord)
+                .and_then(|entity| chunks.get(*entity).ok())
+            else {
+                continue;
+            };
+            let (local_x, local_y) = world_config.grid().tile_to_local((x, y));
+            if chunk
+                .get_tile(local_x, local_y)
+                .is_none_or(|tile| tile.resource != ResourceType::Tree)
+            {
+                continue;
+            }
+            let tile = WorldGrid::tile_to_world((x, y));
+            let distance = tile.distance_squared(position);
+            if best.is_none_or(|(closest, _)| distance < closest) {
+                best = Some((distance, tile));
+            }
+        }
+    }
+    best.map(|(_, tile)| tile)
+}
+
+// Only the tree step points somewhere; the marker follows the closest tree as the player moves
+fn place_tutorial_markers(
+    world_state: Res<WorldState>,
+    world_config: Res<WorldConfig>,
+    chunks: Query<&Chunk>,
+    mut players: Query<(&PlayerPosition, &mut Tutorial)>,
+) {
+    for (position, mut tutorial) in players.iter_mut() {
+        let marker = match tutorial.step {
+            TutorialStep::HarvestTree => {
+                nearest_tree(position.0, &world_state, &world_config, &chunks)
+            }
+            _ => None,
+        };
+        if tutorial.marker != marker {
+            tutorial.marker = marker;
+        }
+    }
+}
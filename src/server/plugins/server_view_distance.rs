@@ -0,0 +1,149 @@
+use bevy::prelude::*;
+use bevy::utils::Duration;
+use lightyear::prelude::server::*;
+use lightyear::prelude::*;
+
+use crate::protocol::{Channel1, ViewDistanceLimit};
+use crate::settings_common::{Settings, ViewDistanceSettings};
+use crate::shared::error::{GameError, ReportError};
+use crate::shared::frame_pacing::FrameLoad;
+use crate::shared::world_generation::ChunkCoord;
+
+// How long frames must stay over budget before the radii are halved once more, and within budget
+// before they double back
+const REDUCE_AFTER: Duration = Duration::from_secs(5);
+const RESTORE_AFTER: Duration = Duration::from_secs(15);
+
+// Server plugin shrinking how far around players chunks are generated and sent while frames run
+// over budget, and growing it back once the load drops. Clients are told the limit, so that they
+// don't ask for chunks beyond it and can show that the server is busy.
+pub struct ServerViewDistancePlugin;
+
+impl Plugin for ServerViewDistancePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ServerViewDistance>()
+            .add_systems(Startup, load_view_distance_settings)
+            .add_systems(
+                Update,
+                (scale_view_distance, announce_view_distance).chain(),
+            );
+    }
+}
+
+/// Chunk radii around players, halved `reduction` times under load
+#[derive(Resource, Debug, Default)]
+pub struct ServerViewDistance {
+    pub settings: ViewDistanceSettings,
+    pub reduction: u32,
+    // Time frames have been over budget, or within it, since the last change
+    over_for: Duration,
+    within_for: Duration,
+}
+
+impl ServerViewDistance {
+    fn scaled(&self, radius: i32) -> i32 {
+        (radius >> self.reduction).max(1)
+    }
+
+    /// Chunks around a player sent to its client
+    pub fn replication_radius(&self) -> i32 {
+        self.scaled(self.settings.replication_radius)
+    }
+
+    /// Chunks around a player generated ahead of time
+    pub fn generation_lookahead(&self) -> i32 {
+        self.scaled(self.settings.generation_lookahead)
+    }
+
+    /// Whether a chunk is close enough to a player's chunk to be sent to them
+    pub fn reaches(&self, player_chunk: ChunkCoord, coord: ChunkCoord) -> bool {
+        chunk_distance(player_chunk, coord) <= self.replication_radius()
+    }
+
+    /// Whether to answer a client asking for a chunk. The client may already be a chunk further
+    /// than the server has it, so one more chunk is accepted.
+    pub fn accepts_request(&self, player_chunk: ChunkCoord, coord: ChunkCoord) -> bool {
+        chunk_distance(player_chunk, coord) <= self.replication_radius() + 1
+    }
+
+    fn limit(&self) -> ViewDistanceLimit {
+        ViewDistanceLimit {
+            max_chunks: self.replication_radius(),
+            reduced: self.reduction > 0,
+        }
+    }
+}
+
+// Chunks between two chunks, diagonals counting as one
+fn chunk_distance(a: ChunkCoord, b: ChunkCoord) -> i32 {
+    (a.x - b.x).abs().max((a.y - b.y).abs())
+}
+
+fn load_view_distance_settings(
+    settings: Option<Res<Settings>>,
+    mut view: ResMut<ServerViewDistance>,
+) {
+    if let Some(settings) = settings {
+        view.settings = settings.server.view_distance.clone();
+    }
+}
+
+fn scale_view_distance(
+    time: Res<Time<Real>>,
+    load: Option<Res<FrameLoad>>,
+    mut view: ResMut<ServerViewDistance>,
+) {
+    let Some(load) = load else {
+        return;
+    };
+    // the timers change every frame, but clients are only told about changes of the radii
+    let timers = view.bypass_change_detection();
+    if load.degraded {
+        timers.within_for = Duration::ZERO;
+        timers.over_for += time.delta();
+    } else {
+        timers.over_for = Duration::ZERO;
+        timers.within_for += time.delta();
+    }
+    if timers.over_for >= REDUCE_AFTER && timers.reduction < timers.settings.max_reductions {
+        timers.over_for = Duration::ZERO;
+        view.reduction += 1;
+        warn!(
+            "Frames over budget (average {:.1}ms), reducing view distance to {} chunks",
+            load.average_ms,
+            view.replication_radius()
+        );
+    } else if timers.within_for >= RESTORE_AFTER && timers.reduction > 0 {
+        timers.within_for = Duration::ZERO;
+        view.reduction -= 1;
+        info!(
+            "Frames back within budget, increasing view distance to {} chunks",
+            view.replication_radius()
+        );
+    }
+}
+
+// Tell clients the limit when they connect, and all of them whenever it changes
+fn announce_view_distance(
+    view: Res<ServerViewDistance>,
+    mut connections: EventReader<ConnectEvent>,
+    mut connection_manager: ResMut<ConnectionManager>,
+    mut errors: EventWriter<ReportError>,
+) {
+    let limit = view.limit();
+    let target = if view.is_changed() {
+        connections.clear();
+        NetworkTarget::All
+    } else {
+        let clients: Vec<ClientId> = connections.read().map(|event| event.client_id).collect();
+        if clients.is_empty() {
+            return;
+        }
+        NetworkTarget::Only(clients)
+    };
+    connection_manager
+        .send_message_to_target::<Channel1, ViewDistanceLimit>(&limit, target)
+        .unwrap_or_else(|e| {
+            errors.send(ReportError(GameError::send("ViewDistanceLimit", e)));
+        });
+}
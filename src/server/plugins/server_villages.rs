@@ -0,0 +1,348 @@
+use bevy::prelude::*;
+use lightyear::prelude::server::*;
+use lightyear::prelude::*;
+use std::collections::{HashMap, VecDeque};
+
+use crate::protocol::PlayerId;
+use crate::server::plugins::{
+    ActiveRaid, ChunkStore, NpcInteractionEvent, PathRequest, PathResult, ReputationEvent,
+};
+use crate::shared::commands::{CommandReply, CommandSource};
+use crate::shared::day_night::DayPhase;
+use crate::shared::error::ReportError;
+use crate::shared::instances::InstanceId;
+use crate::shared::items::Inventory;
+use crate::shared::npc::Npc;
+use crate::shared::pathfinding::TilePos;
+use crate::shared::reputation::{Faction, Reputation};
+use crate::shared::structures::{chunk_structures, StructureKind, StructurePlan};
+use crate::shared::villages::{Villager, VillagerRecord, TRADES, VILLAGER_NAMES};
+use crate::shared::world_generation::{
+    chunk_biome, seeded_hash, Chunk, ChunkCoord, SaveWorldEvent, WorldConfig, WorldGrid, WorldState,
+};
+
+// Speed of villagers, in tiles per second, and how much faster they run home from raiders
+const VILLAGER_SPEED: f32 = 1.5;
+const FLEE_FACTOR: f32 = 2.0;
+// World time villagers spend at one spot of the square before strolling to another
+const STROLL_SECS: f64 = 30.0;
+// Standing gained with villagers by trading with one of them
+const TRADE_STANDING: i32 = 2;
+
+// Server plugin for villagers: villages are populated the first time they are loaded, one
+// villager per house, and saved with the chunk at their center. Villagers spend the day on the
+// square, go home at dusk or when raiders come, and trade with players.
+pub struct ServerVillagesPlugin;
+
+impl Plugin for ServerVillagesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Villages>().add_systems(
+            Update,
+            (
+                load_villages,
+                plan_errands,
+                walk_villagers,
+                trade_with_players,
+                unload_villages,
+                save_villages,
+            )
+                .chain(),
+        );
+    }
+}
+
+// Villages whose center chunk is loaded, by that chunk
+#[derive(Resource, Default)]
+struct Villages(HashMap<ChunkCoord, StructurePlan>);
+
+impl Villages {
+    fn plan(&self, origin: (i32, i32)) -> Option<&StructurePlan> {
+        self.0.values().find(|plan| plan.origin == origin)
+    }
+}
+
+/// Where a villager is heading, and the path it follows there
+#[derive(Component, Default)]
+struct Errand {
+    target: Option<(i32, i32)>,
+    path: VecDeque<TilePos>,
+    // World time at which the villager strolls to another spot of the square
+    stroll_at: f64,
+}
+
+fn spawn_villager(commands: &mut Commands, record: VillagerRecord, now: f64) {
+    commands.spawn((
+        Npc {
+            spawned_at: now,
+            position: record.position,
+        },
+        record.villager,
+        Faction::Villagers,
+        Errand::default(),
+        Replicate {
+            sync: SyncTarget {
+                interpolation: NetworkTarget::All,
+                ..default()
+            },
+            ..default()
+        },
+    ));
+}
+
+// The villagers of a village never populated before: one per house, at home
+fn populate(plan: &StructurePlan, seed: u32) -> Vec<VillagerRecord> {
+    plan.homes()
+        .into_iter()
+        .map(|home| {
+            let roll = seeded_hash(seed, ("villager", home));
+            VillagerRecord {
+                villager: Villager {
+                    name: VILLAGER_NAMES[roll as usize % VILLAGER_NAMES.len()].to_string(),
+                    village: plan.origin,
+                    home,
+                    trade: TRADES[(roll >> 32) as usize % TRADES.len()],
+                },
+                position: WorldGrid::tile_to_world(home),
+            }
+        })
+        .collect()
+}
+
+fn load_villages(
+    mut commands: Commands,
+    mut villages: ResMut<Villages>,
+    store: Res<ChunkStore>,
+    world_state: Res<WorldState>,
+    world_config: Res<WorldConfig>,
+    // nobody lives in instances
+    chunks: Query<&Chunk, (Added<Chunk>, Without<InstanceId>)>,
+    mut errors: EventWriter<ReportError>,
+) {
+    let grid = world_config.grid();
+    for chunk in chunks.iter() {
+        if villages.0.contains_key(&chunk.coord) {
+            continue;
+        }
+        let plan = chunk_structures(&chunk.coord, &world_config, |coord| {
+            chunk_biome(coord, &world_config)
+        })
+        .into_iter()
+        .find(|plan| {
+            plan.kind == StructureKind::Village && grid.tile_to_chunk(plan.center()) == chunk.coord
+        });
+        let Some(plan) = plan else {
+            continue;
+        };
+        let records = match store.load_villagers(chunk.coord) {
+            Ok(Some(records)) => records,
+            Ok(None) => {
+                let records = populate(&plan, world_config.seed);
+                info!(
+                    "Populated the village at {:?} with {} villagers",
+                    plan.origin,
+                    records.len()
+                );
+                records
+            }
+            Err(e) => {
+                errors.send(ReportError(e));
+                continue;
+            }
+        };
+        for record in records {
+            spawn_villager(&mut commands, record, world_state.world_time);
+        }
+        villages.0.insert(chunk.coord, plan);
+    }
+}
+
+// Send villagers home at dusk and while raiders are about, and strolling on the square by day
+fn plan_errands(
+    mut commands: Commands,
+    villages: Res<Villages>,
+    raid: Res<ActiveRaid>,
+    world_state: Res<WorldState>,
+    world_config: Res<WorldConfig>,
+    mut villagers: Query<(Entity, &Npc, &Villager, &mut Errand), Without<PathRequest>>,
+) {
+    let now = world_state.world_time;
+    let at_home = raid.0 || matches!(DayPhase::at(now), DayPhase::Dusk | DayPhase::Night);
+    for (entity, npc, villager, mut errand) in villagers.iter_mut() {
+        let target = if at_home {
+            villager.home
+        } else if errand.target.is_some_and(|target| target != villager.home)
+            && now < errand.stroll_at
+        {
+            continue;
+        } else {
+            let roll = seeded_hash(world_config.seed, (villager.home, now.to_bits()));
+            let Some(spot) = villages
+                .plan(villager.village)
+                .and_then(|plan| plan.square_spot(roll))
+            else {
+                continue;
+            };
+            errand.stroll_at = now + STROLL_SECS;
+            spot
+        };
+        if errand.target == Some(target) {
+            continue;
+        }
+        errand.target = Some(target);
+        commands.entity(entity).insert(PathRequest {
+            from: WorldGrid::world_to_tile(npc.position),
+            to: target,
+        });
+    }
+}
+
+fn walk_villagers(
+    mut commands: Commands,
+    time: Res<Time>,
+    raid: Res<ActiveRaid>,
+    mut villagers: Query<(Entity, &mut Npc, &mut Errand, Option<&PathResult>), With<Villager>>,
+) {
+    let speed = if raid.0 {
+        VILLAGER_SPEED * FLEE_FACTOR
+    } else {
+        VILLAGER_SPEED
+    };
+    let step = speed * WorldGrid::TILE_SIZE * time.delta_secs();
+    for (entity, mut npc, mut errand, result) in villagers.iter_mut() {
+        if let Some(PathResult(path)) = result {
+            // the first tile is the one the villager stands on
+            errand.path = path.iter().flatten().skip(1).copied().collect();
+            commands.entity(entity).remove::<PathResult>();
+        }
+        let Some(next) = errand.path.front().copied() else {
+            continue;
+        };
+        let target = WorldGrid::tile_to_world(next);
+        let position = npc.position.move_towards(target, step);
+        npc.position = position;
+        if position == target {
+            errand.path.pop_front();
+        }
+    }
+}
+
+// Trade with players who talk to a villager, unless raiders are about or villagers can't stand
+// the player. The better their standing, the less villagers ask.
+fn trade_with_players(
+    mut events: EventReader<NpcInteractionEvent>,
+    mut replies: EventWriter<CommandReply>,
+    mut reputation: EventWriter<ReputationEvent>,
+    raid: Res<ActiveRaid>,
+    villagers: Query<&Villager>,
+    mut players: Query<(&PlayerId, &mut Inventory, &Reputation)>,
+) {
+    for event in events.read() {
+        let Ok(villager) = villagers.get(event.npc) else {
+            continue;
+        };
+        let reply = |text: String| CommandReply::new(CommandSource::Client(event.client_id), text);
+        if raid.0 {
+            replies.send(reply(format!(
+                "{} is hiding from the raiders",
+                villager.name
+            )));
+            continue;
+        }
+        let Some((_, mut inventory, standing)) = players
+            .iter_mut()
+            .find(|(id, _, _)| id.client_id() == event.client_id)
+        else {
+            continue;
+        };
+        let (wants, gives) = (villager.trade.wants, villager.trade.gives);
+        let Some(price) = standing.attitude(Faction::Villagers).price(wants.count) else {
+            replies.send(reply(format!("{} won't trade with you", villager.name)));
+            continue;
+        };
+        let offer = format!(
+            "{} {:?} for {} {:?}",
+            gives.count, gives.kind, price, wants.kind
+        );
+        let mut after = inventory.clone();
+        if !after.remove(wants.kind, price) {
+            replies.send(reply(format!("{} offers {}", villager.name, offer)));
+            continue;
+        }
+        if after.add(gives.kind, gives.count) > 0 {
+            replies.send(reply("Your inventory is full".to_string()));
+            continue;
+        }
+        *inventory = after;
+        reputation.send(ReputationEvent {
+            client_id: event.client_id,
+            faction: Faction::Villagers,
+            delta: TRADE_STANDING,
+        });
+        replies.send(reply(format!("{} traded you {}", villager.name, offer)));
+    }
+}
+
+fn records<'a>(
+    origin: (i32, i32),
+    villagers: impl Iterator<Item = (&'a Npc, &'a Villager)>,
+) -> Vec<VillagerRecord> {
+    villagers
+        .filter(|(_, villager)| villager.village == origin)
+        .map(|(npc, villager)| VillagerRecord {
+            villager: villager.clone(),
+            position: npc.position,
+        })
+        .collect()
+}
+
+// Save the villagers of villages whose center chunk is unloaded, and despawn them
+fn unload_villages(
+    mut commands: Commands,
+    mut villages: ResMut<Villages>,
+    world_state: Res<WorldState>,
+    store: Res<ChunkStore>,
+    villagers: Query<(Entity, &Npc, &Villager)>,
+    mut errors: EventWriter<ReportError>,
+) {
+    let unloaded: Vec<ChunkCoord> = villages
+        .0
+        .keys()
+        .filter(|coord| !world_state.chunks.contains_key(coord))
+        .copied()
+        .collect();
+    for coord in unloaded {
+        let Some(plan) = villages.0.remove(&coord) else {
+            continue;
+        };
+        let saved = records(
+            plan.origin,
+            villagers.iter().map(|(_, npc, villager)| (npc, villager)),
+        );
+        if let Err(e) = store.save_villagers(coord, &saved) {
+            errors.send(ReportError(e));
+        }
+        for (entity, _, villager) in villagers.iter() {
+            if villager.village == plan.origin {
+                commands.entity(entity).despawn();
+            }
+        }
+    }
+}
+
+fn save_villages(
+    mut events: EventReader<SaveWorldEvent>,
+    villages: Res<Villages>,
+    store: Res<ChunkStore>,
+    villagers: Query<(&Npc, &Villager)>,
+    mut errors: EventWriter<ReportError>,
+) {
+    if events.read().count() == 0 {
+        return;
+    }
+    for (coord, plan) in villages.0.iter() {
+        let saved = records(plan.origin, villagers.iter());
+        if let Err(e) = store.save_villagers(*coord, &saved) {
+            errors.send(ReportError(e));
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use bevy::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+use crate::server::plugins::LandClaims;
+use crate::settings_common::{Settings, WarmChunkSettings};
+use crate::shared::frame_pacing::not_degraded;
+use crate::shared::world_generation::{ChunkCoord, WorldState};
+
+// Server plugin keeping a few unloaded chunks "warm": their tiles are gone from memory, but they
+// keep receiving simulation ticks at a reduced rate, so that whatever progresses in them (claims,
+// and later on machines or crops) doesn't freeze when nobody is around
+pub struct ServerWarmChunksPlugin;
+
+impl Plugin for ServerWarmChunksPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WarmChunks>()
+            .add_event::<WarmChunkTick>()
+            .add_systems(Startup, load_warm_chunk_settings)
+            .add_systems(
+                Update,
+                (
+                    pin_claimed_chunks,
+                    update_warm_chunks,
+                    // ticks carry the time since the previous one, so skipping some loses nothing
+                    tick_warm_chunks.run_if(not_degraded),
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Sent every warm tick for each warm chunk, with the in-game seconds elapsed since its last
+/// tick. Systems that keep lightweight per-chunk state advance it from this instead of `Update`
+/// while the chunk is unloaded.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct WarmChunkTick {
+    pub coord: ChunkCoord,
+    pub delta: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WarmChunk {
+    // World time at which the chunk was unloaded
+    pub since: f64,
+    // In-game seconds simulated since then
+    pub simulated: f64,
+}
+
+/// Unloaded chunks that are still simulated. Chunks are promoted back to hot as soon as they are
+/// loaded again, and go cold once they linger unpinned past the configured time or when the cap
+/// is reached.
+#[derive(Resource, Default)]
+pub struct WarmChunks {
+    pub settings: WarmChunkSettings,
+    // Chunks to keep warm however long they stay unloaded, by the system that asked for them
+    pins: HashMap<&'static str, HashSet<ChunkCoord>>,
+    warm: HashMap<ChunkCoord, WarmChunk>,
+    // Chunks loaded last frame, to notice the ones that got unloaded
+    loaded: HashSet<ChunkCoord>,
+    last_tick: f64,
+}
+
+impl WarmChunks {
+    /// Replace the chunks `reason` wants kept warm
+    pub fn set_pins(&mut self, reason: &'static str, coords: HashSet<ChunkCoord>) {
+        self.pins.insert(reason, coords);
+    }
+
+    pub fn is_pinned(&self, coord: ChunkCoord) -> bool {
+        self.pins.values().any(|coords| coords.contains(&coord))
+    }
+
+    pub fn is_warm(&self, coord: ChunkCoord) -> bool {
+        self.warm.contains_key(&coord)
+    }
+
+    pub fn len(&self) -> usize {
+        self.warm.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.warm.is_empty()
+    }
+}
+
+fn load_warm_chunk_settings(settings: Option<Res<Settings>>, mut warm: ResMut<WarmChunks>) {
+    if let Some(settings) = settings {
+        warm.settings = settings.server.warm_chunks.clone();
+    }
+}
+
+// Claimed land stays warm, so that it keeps progressing while its owner is away
+fn pin_claimed_chunks(claims: Res<LandClaims>, mut warm: ResMut<WarmChunks>) {
+    if claims.is_changed() {
+        warm.set_pins("claims", claims.owners.keys().copied().collect());
+    }
+}
+
+fn update_warm_chunks(world_state: Res<WorldState>, mut warm: ResMut<WarmChunks>) {
+    let now = world_state.world_time;
+    let warm = &mut *warm;
+
+    // promote chunks loaded again, demote the ones just unloaded
+    warm.warm
+        .retain(|coord, _| !world_state.chunks.contains_key(coord));
+    let unloaded: Vec<ChunkCoord> = warm
+        .loaded
+        .iter()
+        .filter(|coord| !world_state.chunks.contains_key(coord))
+        .copied()
+        .collect();
+    for coord in unloaded {
+        warm.warm.insert(
+            coord,
+            WarmChunk {
+                since: now,
+                simulated: 0.0,
+            },
+        );
+    }
+    warm.loaded.clear();
+    warm.loaded.extend(world_state.chunks.keys().copied());
+
+    // unpinned chunks go cold after lingering for a while
+    let linger = warm.settings.linger_secs as f64;
+    let pins = &warm.pins;
+    warm.warm.retain(|coord, chunk| {
+        now - chunk.since < linger || pins.values().any(|coords| coords.contains(coord))
+    });
+
+    // over the cap, unpinned chunks go cold before pinned ones, the oldest first
+    let excess = warm.warm.len().saturating_sub(warm.settings.max_warm);
+    if excess > 0 {
+        let mut by_priority: Vec<(bool, f64, ChunkCoord)> = warm
+            .warm
+            .iter()
+            .map(|(coord, chunk)| (warm.is_pinned(*coord), chunk.since, *coord))
+            .collect();
+        by_priority.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)));
+        for (pinned, _, coord) in by_priority.into_iter().take(excess) {
+            if pinned {
+                warn!(
+                    "Too many warm chunks, chunk {:?} stops simulating while unloaded",
+                    coord
+                );
+            }
+            warm.warm.remove(&coord);
+        }
+    }
+}
+
+fn tick_warm_chunks(
+    world_state: Res<WorldState>,
+    mut warm: ResMut<WarmChunks>,
+    mut ticks: EventWriter<WarmChunkTick>,
+) {
+    let now = world_state.world_time;
+    let delta = now - warm.last_tick;
+    if delta < warm.settings.tick_secs as f64 {
+        return;
+    }
+    warm.last_tick = now;
+    for (coord, chunk) in warm.warm.iter_mut() {
+        // chunks warmed since the last tick only simulate the time they were unloaded
+        let delta = delta.min(now - chunk.since - chunk.simulated);
+        chunk.simulated += delta;
+        ticks.send(WarmChunkTick {
+            coord: *coord,
+            delta,
+        });
+    }
+}
@@ -1,8 +1,16 @@
+use bevy::ecs::component::Tick;
 use bevy::prelude::*;
+use bevy::tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task};
+use bevy::time::common_conditions::on_timer;
+use bevy::utils::Duration;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
+use crate::shared::chunk_format::ChunkFormat;
+use crate::shared::instances::InstanceId;
 use crate::shared::world_generation::{
-    Chunk, ChunkChannel, ChunkCoord, ChunkData, ChunkRequest, ChunkRequestEvent, WorldConfig,
-    WorldState,
+    is_newer_version, serialize_chunk, Chunk, ChunkChannel, ChunkCoord, ChunkData, ChunkDelta,
+    ChunkRequest, ChunkRequestEvent, ChunkStreamChannel, ChunkUnload, WorldConfig, WorldState,
 };
 
 use lightyear::prelude::client::{Confirmed, Predicted};
@@ -13,24 +21,198 @@ use serde::{Deserialize, Serialize};
 
 use lightyear::client::components::{ComponentSyncMode, LerpFn};
 use lightyear::prelude::client::{self};
-use lightyear::prelude::server::{Replicate, SyncTarget};
 
-use crate::protocol::PlayerId;
+use crate::protocol::{Channel1, PlayerId, WorldTimeSync};
+use crate::server::plugins::{Idle, ServerViewDistance};
+use crate::shared::error::{GameError, ReportError};
+
+// How often clients are resynchronized with the server's world time
+const WORLD_TIME_SYNC_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Chunks each connected client has or asked for, and the format chunks are sent to it in.
+/// Chunks, and updates of their tiles, only go to the clients that have them.
+#[derive(Resource, Default, Debug)]
+pub struct ChunkInterest {
+    pub clients: HashMap<ClientId, HashSet<ChunkCoord>>,
+    pub formats: HashMap<ClientId, ChunkFormat>,
+    /// Chunk the player of each client is in, for clients with a player in the world
+    pub player_chunks: HashMap<ClientId, ChunkCoord>,
+    /// Latest version of each chunk sent to clients, whole or as a delta. A chunk rebuilt from
+    /// scratch carries on from it, so that clients replace their copy.
+    pub versions: HashMap<ChunkCoord, u32>,
+}
+
+impl ChunkInterest {
+    /// Format chunks are sent to a client in. Clients that haven't asked for anything yet get the
+    /// format every client reads.
+    pub fn format(&self, client_id: ClientId) -> ChunkFormat {
+        self.formats.get(&client_id).copied().unwrap_or_default()
+    }
+
+    // Remember the version of a chunk sent to clients
+    fn sent(&mut self, coord: ChunkCoord, version: u32) {
+        let latest = self.versions.entry(coord).or_insert(version);
+        if is_newer_version(version, *latest) {
+            *latest = version;
+        }
+    }
+
+    /// Clients that have any of these chunks, to send changes to them to
+    pub fn watching(&self, coords: impl IntoIterator<Item = ChunkCoord>) -> NetworkTarget {
+        let coords: HashSet<ChunkCoord> = coords.into_iter().collect();
+        NetworkTarget::Only(
+            self.clients
+                .iter()
+                .filter(|(_, chunks)| !chunks.is_disjoint(&coords))
+                .map(|(client_id, _)| *client_id)
+                .collect(),
+        )
+    }
+}
+
+/// Chunks on their way to clients. Chunks are encoded on the async compute pool rather than in
+/// the frame, and once per version and format however many clients they are sent to.
+#[derive(Resource, Default)]
+pub struct ChunkPayloads {
+    // Last encoding of each chunk in each format, with the change tick of the chunk encoded
+    cache: HashMap<(ChunkCoord, ChunkFormat), (Tick, Arc<Vec<u8>>)>,
+    tasks: Vec<PayloadTask>,
+    // Encoded chunks to send this frame
+    ready: Vec<(ClientId, ChunkCoord, Arc<Vec<u8>>)>,
+}
+
+// A chunk being encoded, and the clients waiting for it
+struct PayloadTask {
+    coord: ChunkCoord,
+    format: ChunkFormat,
+    version: Tick,
+    clients: Vec<ClientId>,
+    task: Task<Result<Vec<u8>, GameError>>,
+}
+
+impl ChunkPayloads {
+    /// Send a chunk to a client, encoded in the format negotiated with it, as soon as it's encoded
+    pub fn send(&mut self, interest: &ChunkInterest, client_id: ClientId, chunk: &Ref<Chunk>) {
+        let (coord, format, version) = (
+            chunk.coord,
+            interest.format(client_id),
+            chunk.last_changed(),
+        );
+        if let Some((_, data)) = self
+            .cache
+            .get(&(coord, format))
+            .filter(|(cached, _)| *cached == version)
+        {
+            self.ready.push((client_id, coord, data.clone()));
+        } else if let Some(pending) = self.tasks.iter_mut().find(|pending| {
+            (pending.coord, pending.format, pending.version) == (coord, format, version)
+        }) {
+            pending.clients.push(client_id);
+        } else {
+            let snapshot = Chunk::clone(chunk);
+            let task = AsyncComputeTaskPool::get()
+                .spawn(async move { serialize_chunk(&snapshot, format) });
+            self.tasks.push(PayloadTask {
+                coord,
+                format,
+                version,
+                clients: vec![client_id],
+                task,
+            });
+        }
+    }
+}
+
+// Follow the chunk each player is in, and have their clients drop the chunks they moved away from
+fn track_player_chunks(
+    players: Query<(&PlayerId, &Transform), Without<InstanceId>>,
+    view: Res<ServerViewDistance>,
+    world_config: Res<WorldConfig>,
+    mut interest: ResMut<ChunkInterest>,
+    mut connection_manager: ResMut<ConnectionManager>,
+    mut errors: EventWriter<ReportError>,
+) {
+    for (player_id, transform) in players.iter() {
+        let client_id = player_id.client_id();
+        let player_chunk = world_config
+            .grid()
+            .world_to_chunk(transform.translation.truncate());
+        let moved = interest.player_chunks.insert(client_id, player_chunk) != Some(player_chunk);
+        if !moved && !view.is_changed() {
+            continue;
+        }
+        let Some(chunks) = interest.clients.get_mut(&client_id) else {
+            continue;
+        };
+        // with the same slack as requests, so that walking along the edge of a chunk doesn't
+        // unload and send chunks over and over
+        let far: Vec<ChunkCoord> = chunks
+            .iter()
+            .filter(|coord| !view.accepts_request(player_chunk, **coord))
+            .copied()
+            .collect();
+        if far.is_empty() {
+            continue;
+        }
+        for coord in &far {
+            chunks.remove(coord);
+        }
+        debug!(
+            "Client {:?} moved away from {} chunks, unloading them",
+            client_id,
+            far.len()
+        );
+        connection_manager
+            .send_message::<ChunkChannel, _>(client_id, &ChunkUnload { coords: far })
+            .unwrap_or_else(|e| {
+                errors.send(ReportError(GameError::send("ChunkUnload", e)));
+            });
+    }
+    // players who left the world, into an instance or out of their body, no longer pull chunks in
+    let in_world: HashSet<ClientId> = players
+        .iter()
+        .map(|(player_id, _)| player_id.client_id())
+        .collect();
+    interest
+        .player_chunks
+        .retain(|client_id, _| in_world.contains(client_id));
+}
 
 // Handle client requests for chunks
 pub fn handle_chunk_network_requests(
-    mut commands: Commands,
     mut events: EventReader<MessageEvent<ChunkRequest>>,
-    mut world_state: ResMut<WorldState>,
+    mut interest: ResMut<ChunkInterest>,
+    world_state: Res<WorldState>,
     world_config: Res<WorldConfig>,
     mut chunk_request_events: EventWriter<ChunkRequestEvent>,
-    mut connection_manager: ResMut<ConnectionManager>,
-    chunks: Query<&Chunk>, // Add this query to access Chunk components
+    mut payloads: ResMut<ChunkPayloads>,
+    chunks: Query<Ref<Chunk>>, // Add this query to access Chunk components
+    view: Res<ServerViewDistance>,
 ) {
     for event in events.read() {
         let client_id = event.from();
-        let coord = event.message().coord;
+        let ChunkRequest { coord, accepts } = *event.message();
+        if !coord.is_in_world(world_config.chunk_size) {
+            warn!(
+                "Client {:?} requested chunk {:?} outside of the world",
+                client_id, coord
+            );
+            continue;
+        }
+        // clients without a player, like observers, may ask for any chunk
+        let player_chunk = interest.player_chunks.get(&client_id).copied();
+        if player_chunk.is_some_and(|player_chunk| !view.accepts_request(player_chunk, coord)) {
+            debug!(
+                "Client {:?} requested chunk {:?} beyond its view distance",
+                client_id, coord
+            );
+            continue;
+        }
         info!("Client {:?} requested chunk at {:?}", client_id, coord);
+        interest.clients.entry(client_id).or_default().insert(coord);
+        interest
+            .formats
+            .insert(client_id, ChunkFormat::negotiate(accepts));
         // Convert to internal event
         chunk_request_events.send(ChunkRequestEvent {
             coord,
@@ -41,58 +223,143 @@ pub fn handle_chunk_network_requests(
             if let Ok(chunk) = chunks.get(*chunk_entity) {
                 // Use the Query instead
                 // Send the chunk data to the requesting client
-                let _ = connection_manager.send_message::<ChunkChannel, _>(
-                    client_id,
-                    &mut ChunkData {
-                        chunk: chunk.clone(),
-                    },
+                payloads.send(&interest, client_id, &chunk);
+                interest.sent(coord, chunk.version());
+                info!(
+                    "Sending existing chunk {:?} to client {:?}",
+                    coord, client_id
                 );
-                info!("Sent existing chunk {:?} to client {:?}", coord, client_id);
             }
         }
     }
 }
 
-// System to send newly generated chunks to clients who need them
+// Send the tiles modified this frame, by whichever system, to the clients that have their chunk
+fn send_chunk_deltas(
+    mut chunks: Query<(&mut Chunk, Has<InstanceId>), Changed<Chunk>>,
+    mut interest: ResMut<ChunkInterest>,
+    mut connection_manager: ResMut<ConnectionManager>,
+    mut errors: EventWriter<ReportError>,
+) {
+    for (mut chunk, in_instance) in chunks.iter_mut() {
+        // taking the delta doesn't modify the tiles, so the chunk's encoded payload stays good
+        let Some(delta) = chunk.bypass_change_detection().take_delta() else {
+            continue;
+        };
+        // instance chunks share their coordinates with overworld ones, and their players get
+        // them whole
+        if in_instance {
+            continue;
+        }
+        interest.sent(delta.coord, delta.version);
+        connection_manager
+            .send_message_to_target::<ChunkChannel, ChunkDelta>(
+                &delta,
+                interest.watching([delta.coord]),
+            )
+            .unwrap_or_else(|e| {
+                errors.send(ReportError(GameError::send("ChunkDelta", e)));
+            });
+    }
+}
+
 // System to send newly generated chunks to clients who need them
 pub fn send_new_chunks(
-    mut commands: Commands,
-    mut world_state: ResMut<WorldState>,
-    chunk_query: Query<(Entity, &Chunk), Added<Chunk>>,
-    player_query: Query<(&PlayerId, &Transform)>,
-    mut connection_manager: ResMut<ConnectionManager>,
+    // instance chunks are only sent to the players inside
+    mut chunk_query: Query<Mut<Chunk>, (Added<Chunk>, Without<InstanceId>)>,
+    mut interest: ResMut<ChunkInterest>,
+    view: Res<ServerViewDistance>,
+    mut payloads: ResMut<ChunkPayloads>,
 ) {
     // For each newly generated chunk
-    for (entity, chunk) in chunk_query.iter() {
+    for mut chunk in chunk_query.iter_mut() {
         let coord = chunk.coord;
+        // a chunk rebuilt, or loaded again without having been saved, replaces the copy clients
+        // were sent
+        if let Some(previous) = interest.versions.get(&coord) {
+            chunk.continue_from(*previous);
+        }
+        let chunk: Ref<Chunk> = chunk.into();
 
-        // Find players who should receive this chunk (those close enough)
-        for (player_id, transform) in player_query.iter() {
-            // Here you'd calculate if this player needs this chunk
-            // This is a simple implementation - in practice, you might use distance checks
-
-            // Send the chunk data to the client
-            // Use player_id.0 which is the ClientId that connection_manager expects
-            let _ = connection_manager.send_message::<ChunkChannel, _>(
-                player_id.client_id(), // This is now correct - using the ClientId inside PlayerId
-                &mut ChunkData {
-                    chunk: chunk.clone(),
-                },
-            );
+        // Players whose view distance reaches the chunk, and clients that asked for it before it
+        // was generated
+        let clients: HashSet<ClientId> = interest
+            .player_chunks
+            .iter()
+            .filter(|(_, player_chunk)| view.reaches(**player_chunk, coord))
+            .map(|(client_id, _)| *client_id)
+            .chain(
+                interest
+                    .clients
+                    .iter()
+                    .filter(|(_, chunks)| chunks.contains(&coord))
+                    .map(|(client_id, _)| *client_id),
+            )
+            .collect();
+        for client_id in clients {
+            interest.clients.entry(client_id).or_default().insert(coord);
+            payloads.send(&interest, client_id, &chunk);
+            interest.sent(coord, chunk.version());
+            debug!("Sent new chunk {:?} to client {:?}", coord, client_id);
+        }
+    }
+}
 
-            // Add Replicate component to ensure the chunk is replicated to the client
-            commands.entity(entity).insert(Replicate {
-                sync: SyncTarget {
-                    interpolation: NetworkTarget::All,
-                    ..default()
-                },
-                relevance_mode: NetworkRelevanceMode::All,
-                ..default()
-            });
+// Collect the chunks done encoding, and send every encoded chunk to the clients waiting for it.
+// Encodings of chunks no longer loaded are dropped.
+fn flush_chunk_payloads(
+    mut payloads: ResMut<ChunkPayloads>,
+    world_state: Res<WorldState>,
+    mut connection_manager: ResMut<ConnectionManager>,
+    mut errors: EventWriter<ReportError>,
+) {
+    let payloads = &mut *payloads;
+    let mut finished = Vec::new();
+    payloads.tasks.retain_mut(
+        |pending| match block_on(future::poll_once(&mut pending.task)) {
+            Some(result) => {
+                finished.push((
+                    pending.coord,
+                    pending.format,
+                    pending.version,
+                    std::mem::take(&mut pending.clients),
+                    result,
+                ));
+                false
+            }
+            None => true,
+        },
+    );
+    for (coord, format, version, clients, result) in finished {
+        let data = match result {
+            Ok(data) => Arc::new(data),
+            Err(e) => {
+                errors.send(ReportError(e));
+                continue;
+            }
+        };
+        payloads.ready.extend(
+            clients
+                .into_iter()
+                .map(|client_id| (client_id, coord, data.clone())),
+        );
+        payloads.cache.insert((coord, format), (version, data));
+    }
 
-            debug!("Sent new chunk {:?} to player {:?}", coord, player_id);
+    for (client_id, coord, data) in payloads.ready.drain(..) {
+        if let Err(e) = connection_manager.send_message::<ChunkStreamChannel, _>(
+            client_id,
+            &mut ChunkData {
+                coord,
+                data: data.to_vec(),
+            },
+        ) {
+            errors.send(ReportError(GameError::send("ChunkData", e)));
         }
     }
+    payloads
+        .cache
+        .retain(|(coord, _), _| world_state.chunks.contains_key(coord));
 }
 
 // Generate chunks around player when they move to a new area
@@ -100,22 +367,22 @@ pub fn generate_chunks_around_players(
     mut commands: Commands,
     mut world_state: ResMut<WorldState>,
     world_config: Res<WorldConfig>,
-    player_query: Query<(&PlayerId, &Transform), Changed<Transform>>,
+    // idle and instanced players don't keep the world around them generating
+    player_query: Query<
+        (&PlayerId, &Transform),
+        (Changed<Transform>, Without<Idle>, Without<InstanceId>),
+    >,
+    view: Res<ServerViewDistance>,
     mut chunk_request_events: EventWriter<ChunkRequestEvent>,
 ) {
-    let chunk_size = world_config.chunk_size as f32;
-
     for (_, transform) in player_query.iter() {
         // Calculate which chunk the player is in
-        let chunk_x = (transform.translation.x / chunk_size).floor() as i32;
-        let chunk_y = (transform.translation.y / chunk_size).floor() as i32;
-        let player_chunk = ChunkCoord {
-            x: chunk_x,
-            y: chunk_y,
-        };
+        let player_chunk = world_config
+            .grid()
+            .world_to_chunk(transform.translation.truncate());
 
-        // Generate chunks in a radius around the player
-        let view_distance = 128; // Customize based on your needs
+        // Generate chunks in a radius around the player, smaller while the server is busy
+        let view_distance = view.generation_lookahead();
 
         for y in -view_distance..=view_distance {
             for x in -view_distance..=view_distance {
@@ -136,18 +403,80 @@ pub fn generate_chunks_around_players(
     }
 }
 
+// Keep the clients' world time (and thus their day/night cycle) in step with the server
+fn broadcast_world_time(
+    world_state: Res<WorldState>,
+    mut connection_manager: ResMut<ConnectionManager>,
+    mut errors: EventWriter<ReportError>,
+) {
+    connection_manager
+        .send_message_to_target::<Channel1, WorldTimeSync>(
+            &WorldTimeSync {
+                world_time: world_state.world_time,
+            },
+            NetworkTarget::All,
+        )
+        .unwrap_or_else(|e| {
+            errors.send(ReportError(GameError::send("WorldTimeSync", e)));
+        });
+}
+
+// Send the world time to newly connected clients right away
+fn send_world_time_on_connect(
+    mut connections: EventReader<ConnectEvent>,
+    world_state: Res<WorldState>,
+    mut connection_manager: ResMut<ConnectionManager>,
+    mut errors: EventWriter<ReportError>,
+) {
+    for connection in connections.read() {
+        connection_manager
+            .send_message::<Channel1, _>(
+                connection.client_id,
+                &WorldTimeSync {
+                    world_time: world_state.world_time,
+                },
+            )
+            .unwrap_or_else(|e| {
+                errors.send(ReportError(GameError::send("WorldTimeSync", e)));
+            });
+    }
+}
+
+fn forget_disconnected_interest(
+    mut disconnections: EventReader<DisconnectEvent>,
+    mut interest: ResMut<ChunkInterest>,
+) {
+    for disconnection in disconnections.read() {
+        interest.clients.remove(&disconnection.client_id);
+        interest.formats.remove(&disconnection.client_id);
+        interest.player_chunks.remove(&disconnection.client_id);
+    }
+}
+
 // Server plugin for world management with networking
 pub struct ServerWorldPlugin;
 
 impl Plugin for ServerWorldPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
-            Update,
-            (
-                handle_chunk_network_requests,
-                send_new_chunks,
-                generate_chunks_around_players,
-            ),
-        );
+        app.init_resource::<ChunkInterest>()
+            .init_resource::<ChunkPayloads>()
+            .add_systems(
+                Update,
+                (
+                    (
+                        track_player_chunks,
+                        handle_chunk_network_requests,
+                        send_new_chunks,
+                        flush_chunk_payloads,
+                    )
+                        .chain(),
+                    forget_disconnected_interest,
+                    generate_chunks_around_players,
+                    send_world_time_on_connect,
+                    broadcast_world_time.run_if(on_timer(WORLD_TIME_SYNC_INTERVAL)),
+                ),
+            )
+            // once every system had its chance to modify tiles this frame
+            .add_systems(PostUpdate, send_chunk_deltas);
     }
 }
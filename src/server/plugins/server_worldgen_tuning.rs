@@ -0,0 +1,150 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts, EguiPlugin};
+use lightyear::prelude::client::ClientConfig;
+
+use crate::shared::biome_map::BiomeMap;
+use crate::shared::height_map::HeightMap;
+use crate::shared::structures::StructureMap;
+use crate::shared::world_generation::{build_chunk, ChunkCoord, WorldConfig, WorldState};
+
+// Seconds without changes before the world is regenerated, so that dragging a slider doesn't
+// regenerate every chunk on every frame
+const APPLY_DELAY_SECS: f64 = 0.3;
+
+// Debug plugin with sliders for the world generation parameters, regenerating the loaded chunks
+// as they change. Only shown when hosting, as it changes the world for everyone.
+pub struct ServerWorldgenTuningPlugin;
+
+impl Plugin for ServerWorldgenTuningPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            return;
+        }
+        app.init_resource::<TuningDraft>().add_systems(
+            Update,
+            (tuning_ui, apply_tuning)
+                .chain()
+                // a local client only exists when hosting
+                .run_if(resource_exists::<ClientConfig>),
+        );
+    }
+}
+
+// Parameters being edited, and when they were last changed
+#[derive(Resource, Default)]
+struct TuningDraft {
+    config: Option<WorldConfig>,
+    changed_at: Option<f64>,
+}
+
+fn tuning_ui(
+    mut contexts: EguiContexts,
+    time: Res<Time>,
+    world_config: Res<WorldConfig>,
+    mut draft: ResMut<TuningDraft>,
+) {
+    let Some(ctx) = contexts.try_ctx_mut() else {
+        return;
+    };
+    let mut config = draft.config.clone().unwrap_or_else(|| world_config.clone());
+    let before = config.clone();
+    egui::Window::new("World generation").show(ctx, |ui| {
+        ui.add(
+            egui::Slider::new(&mut config.biome_scale, 0.001..=0.2)
+                .logarithmic(true)
+                .text("Biome scale"),
+        );
+        ui.add(
+            egui::Slider::new(&mut config.height_scale, 0.005..=0.3)
+                .logarithmic(true)
+                .text("Height scale"),
+        );
+        ui.add(egui::Slider::new(&mut config.resource_density, 0.0..=0.3).text("Resource density"));
+        ui.separator();
+        ui.label("Upper bound of each biome on the biome noise");
+        let thresholds = &mut config.biome_thresholds;
+        // each bound stays between its neighbours, so that every biome can still appear
+        ui.add(egui::Slider::new(&mut thresholds.ocean, -1.0..=thresholds.desert).text("Ocean"));
+        ui.add(
+            egui::Slider::new(&mut thresholds.desert, thresholds.ocean..=thresholds.plains)
+                .text("Desert"),
+        );
+        ui.add(
+            egui::Slider::new(
+                &mut thresholds.plains,
+                thresholds.desert..=thresholds.forest,
+            )
+            .text("Plains"),
+        );
+        ui.add(
+            egui::Slider::new(
+                &mut thresholds.forest,
+                thresholds.plains..=thresholds.mountain,
+            )
+            .text("Forest"),
+        );
+        ui.add(
+            egui::Slider::new(&mut thresholds.mountain, thresholds.forest..=1.0).text("Mountain"),
+        );
+        ui.separator();
+        if ui.button("Reset to defaults").clicked() {
+            config = WorldConfig {
+                seed: config.seed,
+                chunk_size: config.chunk_size,
+                max_active_chunks: config.max_active_chunks,
+                imported_terrain: config.imported_terrain.clone(),
+                ..default()
+            };
+        }
+        if draft.changed_at.is_some() {
+            ui.label("Regenerating...");
+        }
+    });
+    if config != before {
+        draft.changed_at = Some(time.elapsed_secs_f64());
+    }
+    draft.config = Some(config);
+}
+
+// Once the sliders settle, switch to the new parameters and regenerate every loaded chunk
+fn apply_tuning(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut draft: ResMut<TuningDraft>,
+    mut world_config: ResMut<WorldConfig>,
+    mut world_state: ResMut<WorldState>,
+) {
+    let (Some(config), Some(changed_at)) = (&draft.config, draft.changed_at) else {
+        return;
+    };
+    if time.elapsed_secs_f64() - changed_at < APPLY_DELAY_SECS {
+        return;
+    }
+    *world_config = config.clone();
+    draft.changed_at = None;
+    // they cache generated terrain
+    commands.insert_resource(HeightMap::new(world_config.clone()));
+    commands.insert_resource(BiomeMap::new(world_config.clone()));
+    commands.insert_resource(StructureMap::new(world_config.clone()));
+
+    let coords: Vec<ChunkCoord> = world_state.chunks.keys().copied().collect();
+    let world_time = world_state.world_time;
+    for coord in &coords {
+        let Some(entity) = world_state.chunks.remove(coord) else {
+            continue;
+        };
+        commands.entity(entity).despawn();
+        // spawned as new chunks, so they're sent to the clients like any other
+        let entity = commands
+            .spawn(build_chunk(coord, &world_config, world_time))
+            .id();
+        world_state.chunks.insert(*coord, entity);
+        world_state.active_chunks.insert(*coord);
+        world_state.generation_time.insert(*coord, world_time);
+    }
+    info!(
+        "Regenerated {} chunks with {:?}",
+        coords.len(),
+        *world_config
+    );
+}
@@ -1,8 +1,14 @@
 use lightyear::prelude::CompressionConfig;
 use crate::settings_common::{
-    ClientSettings, ClientTransports, Conditioner, ServerSettings, ServerTransports, Settings,
-    SharedSettings, WebTransportCertificateSettings,
+    AchievementSettings, AfkSettings, ClientSettings, ClientTransports, Conditioner,
+    ContentFilterSettings, DifficultySettings, GraveAccess, GuardrailSettings, HazardSettings, ModerationSettings, MusicSettings, ObserverSettings,
+    TelemetrySettings,
+    Persistence, ResourcePackSettings, SeasonSettings,
+    ServerSettings, ServerTransports, Settings, SharedSettings, SkillSettings, ViewDistanceSettings, WarmChunkSettings,
+    WebTransportCertificateSettings,
 };
+use crate::shared::feature_flags::ServerFeatureFlags;
+use crate::shared::frame_pacing::FrameBudget;
 use std::net::Ipv4Addr;
 use std::string::ToString;
 
@@ -35,9 +41,35 @@ pub(crate) fn get_settings() -> Settings {
                     query_port: 27016,
                 },
             ],
+            admins: vec![],
+            restart: None,
+            guardrails: GuardrailSettings::default(),
+            seasons: SeasonSettings::default(),
+            features: ServerFeatureFlags::default(),
+            ruleset: None,
+            grave_access: GraveAccess::Owner,
+            hazards: HazardSettings::default(),
+            difficulty: DifficultySettings::default(),
+            afk: AfkSettings::default(),
+            max_players: 16,
+            content_filter: ContentFilterSettings::default(),
+            moderation: ModerationSettings::default(),
+            skills: SkillSettings::default(),
+            achievements: AchievementSettings::default(),
+            warm_chunks: WarmChunkSettings::default(),
+            view_distance: ViewDistanceSettings::default(),
+            observers: ObserverSettings::default(),
+            shard: None,
+            persistence: Persistence::Files,
+            cloud_sync: None,
+            terrain_import: None,
+            resource_pack: None,
+            timelapse: None,
+            audit_auto_repair: false,
         },
         client: ClientSettings {
             inspector: true,
+            lockstep_debug: false,
             client_id: 0,
             client_port: 0, // 0 means that the OS will assign a random port
             server_addr: Ipv4Addr::LOCALHOST,
@@ -47,6 +79,10 @@ pub(crate) fn get_settings() -> Settings {
                 certificate_digest: include_str!("../../certificates/digest.txt").to_string(),
             },
             conditioner: None,
+            isometric: false,
+            music: MusicSettings::default(),
+            telemetry: TelemetrySettings::default(),
+            resource_packs: ResourcePackSettings::default(),
         },
         shared: SharedSettings {
             protocol_id: 0,
@@ -55,6 +91,7 @@ pub(crate) fn get_settings() -> Settings {
                 0, 0, 0, 0,
             ],
             compression: CompressionConfig::None,
+            frame_budget: FrameBudget::default(),
         },
     }
 }
\ No newline at end of file
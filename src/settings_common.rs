@@ -22,6 +22,10 @@ use lightyear::prelude::{CompressionConfig, LinkConditionerConfig};
 
 use lightyear::prelude::{client, server};
 
+use crate::shared::feature_flags::{Ruleset, ServerFeatureFlags};
+use crate::shared::frame_pacing::FrameBudget;
+use crate::shared::seasons::Season;
+
 
 /// Read certificate digest from alternate sources, for WASM builds.
 #[cfg(target_family = "wasm")]
@@ -135,6 +139,478 @@ pub struct ServerSettings {
 
     /// Which transport to use
     pub transport: Vec<ServerTransports>,
+
+    /// Client ids that are granted admin permissions for chat commands. Ignored unless client ids
+    /// are verified, see [`client_ids_verified`].
+    pub admins: Vec<u64>,
+
+    /// Optional daily restart schedule
+    pub restart: Option<RestartSchedule>,
+
+    /// Caps on replicated entities
+    pub guardrails: GuardrailSettings,
+
+    /// Length of the season cycle, and optional locked season
+    pub seasons: SeasonSettings,
+
+    /// Gameplay features turned on or off on this server, sent to clients when they connect.
+    /// Only read when the world is created; its rules are saved with it from then on.
+    pub features: ServerFeatureFlags,
+
+    /// Preset the features of a new world are taken from instead of `features`
+    pub ruleset: Option<Ruleset>,
+
+    /// Who can take the items out of the grave a player leaves when they die
+    pub grave_access: GraveAccess,
+
+    /// Cold and heat hurting players in tundras and deserts, and the weather making it worse
+    pub hazards: HazardSettings,
+
+    /// How the world gets harder over time, and raids
+    pub difficulty: DifficultySettings,
+
+    /// Idle player detection and kicking
+    pub afk: AfkSettings,
+
+    /// Players allowed in the world at once; clients connecting beyond that wait in a queue
+    pub max_players: usize,
+
+    /// Wordlists filtering chat messages and player names
+    pub content_filter: ContentFilterSettings,
+
+    /// Player reports, mutes and bans
+    pub moderation: ModerationSettings,
+
+    /// Skill experience and the bonuses it grants
+    pub skills: SkillSettings,
+
+    /// Achievements players can unlock
+    pub achievements: AchievementSettings,
+
+    /// Unloaded chunks that keep simulating at a reduced rate
+    pub warm_chunks: WarmChunkSettings,
+
+    /// How far around players chunks are generated and sent, and how far it shrinks under load
+    pub view_distance: ViewDistanceSettings,
+
+    /// Clients allowed to watch the world without playing, and how much they are sent
+    pub observers: ObserverSettings,
+
+    /// Set when this server is one shard of a cluster sharing the world (`sharding` feature)
+    pub shard: Option<ShardSettings>,
+
+    /// Where chunks, player profiles and claims are saved
+    pub persistence: Persistence,
+
+    /// Optional copy of the world kept on a remote endpoint, for hosted worlds (`cloud-sync` feature)
+    pub cloud_sync: Option<CloudSyncSettings>,
+
+    /// Optional hand-made terrain replacing the generated one over part of the world
+    pub terrain_import: Option<TerrainImportSettings>,
+
+    /// Directory of the resource pack offered to clients as they connect, holding `pack.ron` and
+    /// `atlas.png`
+    pub resource_pack: Option<String>,
+
+    /// Optional time-lapse of a region of the world, recorded as low resolution frames
+    pub timelapse: Option<TimelapseSettings>,
+
+    /// If true, the chunk audit regenerates corrupted chunks as soon as it finds them instead of
+    /// waiting for `audit repair`
+    pub audit_auto_repair: bool,
+}
+
+/// Region of the world recorded as a time-lapse, and how often
+#[derive(Clone, Debug)]
+pub struct TimelapseSettings {
+    /// Directory the frames are saved to, one PNG each
+    pub dir: String,
+    /// Seconds between frames
+    pub interval: f32,
+    /// Tiles at two opposite corners of the region
+    pub from: (i32, i32),
+    pub to: (i32, i32),
+    /// Tiles along each side of the square a pixel of the frames stands for
+    pub tiles_per_pixel: u32,
+}
+
+/// Grayscale images used as the terrain of a region of the world. Chunks already saved keep
+/// their terrain, so this is meant for new worlds.
+#[derive(Clone, Debug)]
+pub struct TerrainImportSettings {
+    /// Path of the heightmap: black is the lowest ground, white the highest
+    pub heightmap: String,
+    /// Path of an optional biome map the size of the heightmap, going through the same biome
+    /// thresholds as the biome noise from black (ocean) to white (tundra)
+    pub biome_map: Option<String>,
+    /// World tile the bottom left corner of the images is placed at
+    pub origin: (i32, i32),
+    /// Tiles per pixel
+    pub scale: f32,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GraveAccess {
+    /// Only the player who died
+    #[default]
+    Owner,
+    /// The player who died and their party
+    Party,
+    Anyone,
+}
+
+/// A credential kept in the settings, printed as `<redacted>` so that it stays out of logs and
+/// crash reports
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(value.into())
+    }
+
+    /// The credential itself, to send to whatever asks for it
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("<redacted>")
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CloudSyncSettings {
+    pub endpoint: CloudEndpoint,
+    /// Name of the world on the endpoint, so that several worlds can share it
+    pub world_name: String,
+}
+
+#[derive(Clone, Debug)]
+pub enum CloudEndpoint {
+    /// A WebDAV collection, with basic auth
+    WebDav {
+        /// URL of the collection the world is saved in, ending with a `/`
+        url: String,
+        username: String,
+        password: Secret,
+    },
+    /// An S3-compatible bucket, addressed path-style
+    S3 {
+        /// e.g. `https://s3.eu-west-1.amazonaws.com`
+        endpoint: String,
+        bucket: String,
+        region: String,
+        access_key: Secret,
+        secret_key: Secret,
+    },
+}
+
+#[derive(Clone, Debug, Default)]
+pub enum Persistence {
+    /// Region files of 32x32 chunks, and one file per player, under `world/`
+    #[default]
+    Files,
+    /// A single sqlite database (`sqlite` feature). Also keeps land claims and player stats.
+    #[cfg(feature = "sqlite")]
+    Sqlite {
+        /// Path of the database file
+        path: String,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct ShardSettings {
+    /// Unique id of this shard in the cluster
+    pub id: u32,
+    /// Address clients are sent to when they are handed off to this shard
+    pub public_addr: SocketAddr,
+    /// Directory shared by all the shards, where the coordinator keeps track of them
+    pub cluster_dir: String,
+    /// Width of the regions shards own, in chunks
+    pub region_chunks: i32,
+}
+
+impl ShardSettings {
+    /// A shard of a cluster running on this machine
+    pub fn local(id: u32, port: u16) -> Self {
+        Self {
+            id,
+            public_addr: SocketAddr::new(Ipv4Addr::LOCALHOST.into(), port),
+            cluster_dir: "world/cluster".to_string(),
+            region_chunks: 8,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ObserverSettings {
+    /// Client ids that connect as observers instead of players. Ignored unless client ids are
+    /// verified, see [`client_ids_verified`].
+    pub client_ids: Vec<u64>,
+    /// Most observers connected at once; others are disconnected
+    pub max_observers: usize,
+    /// Seconds observers lag behind the game, so that casts can't be used to spy on players
+    pub delay_secs: f64,
+    /// Milliseconds between two snapshots
+    pub snapshot_interval_ms: u64,
+    /// Most entities in one snapshot, nearest to the observed region's center first
+    pub max_entities: usize,
+    /// Largest region an observer can watch, in tiles from its center; `None` allows the whole world
+    pub max_region_radius: Option<f32>,
+}
+
+impl Default for ObserverSettings {
+    fn default() -> Self {
+        Self {
+            client_ids: vec![],
+            max_observers: 4,
+            delay_secs: 30.0,
+            snapshot_interval_ms: 250,
+            max_entities: 256,
+            max_region_radius: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct WarmChunkSettings {
+    /// Most chunks kept warm at once; beyond it, the chunks warm the longest go cold first
+    pub max_warm: usize,
+    /// Seconds between two simulation ticks of warm chunks
+    pub tick_secs: u64,
+    /// Seconds an unloaded chunk nobody keeps warm still simulates before going cold
+    pub linger_secs: u64,
+}
+
+impl Default for WarmChunkSettings {
+    fn default() -> Self {
+        Self {
+            max_warm: 32,
+            tick_secs: 5,
+            linger_secs: 5 * 60,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ViewDistanceSettings {
+    /// Chunks around each player sent to its client at most
+    pub replication_radius: i32,
+    /// Chunks around each player generated ahead of time
+    pub generation_lookahead: i32,
+    /// Most times both radii are halved while the server is over its frame budget
+    pub max_reductions: u32,
+}
+
+impl Default for ViewDistanceSettings {
+    fn default() -> Self {
+        Self {
+            replication_radius: 8,
+            generation_lookahead: 128,
+            max_reductions: 3,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AchievementSettings {
+    /// Path of the RON file listing the achievements and their goals
+    pub list: String,
+}
+
+impl Default for AchievementSettings {
+    fn default() -> Self {
+        Self {
+            list: "data/achievements.ron".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SkillSettings {
+    /// Path of the RON file describing the experience curve and the bonuses of each level
+    pub curve: String,
+}
+
+impl Default for SkillSettings {
+    fn default() -> Self {
+        Self {
+            curve: "data/skills.ron".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ModerationSettings {
+    /// Port of the moderation HTTP API, on localhost. The API is off unless a token is set too.
+    pub http_port: Option<u16>,
+    /// Bearer token the moderation HTTP API requires
+    pub http_token: Option<Secret>,
+    /// Length of mutes given without an explicit duration, in seconds
+    pub default_mute_secs: u64,
+}
+
+impl Default for ModerationSettings {
+    fn default() -> Self {
+        Self {
+            http_port: None,
+            http_token: None,
+            default_mute_secs: 10 * 60,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ContentFilterSettings {
+    /// Paths of the RON wordlists to load, each with its own actions
+    pub lists: Vec<String>,
+}
+
+impl Default for ContentFilterSettings {
+    fn default() -> Self {
+        Self {
+            lists: vec![
+                "filters/default.ron".to_string(),
+                "filters/names.ron".to_string(),
+            ],
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AfkSettings {
+    /// Seconds without input after which a player is marked idle
+    pub idle_after_secs: u64,
+    /// If true, idle players don't lose breath (or anything else survival drains)
+    pub pause_survival: bool,
+    /// Seconds without input after which an idle player is kicked, if the server is full
+    pub kick_after_secs: Option<u64>,
+    /// Number of connected players from which the server counts as full
+    pub full_at: usize,
+}
+
+impl Default for AfkSettings {
+    fn default() -> Self {
+        Self {
+            idle_after_secs: 5 * 60,
+            pause_survival: true,
+            kick_after_secs: Some(30 * 60),
+            full_at: 16,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct HazardSettings {
+    /// Multiplier applied to how fast players get cold or hot, and the damage they take once
+    /// frozen or overheated. 0 turns hazards off.
+    pub severity: f32,
+    /// Seconds between two changes of the weather
+    pub weather_change_secs: u64,
+    /// Chance, out of 100, that the weather turns to a storm when it changes
+    pub storm_chance: u8,
+}
+
+impl Default for HazardSettings {
+    fn default() -> Self {
+        Self {
+            severity: 1.0,
+            weather_change_secs: 5 * 60,
+            storm_chance: 25,
+        }
+    }
+}
+
+/// What the difficulty curve is drawn over
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DifficultyScaling {
+    /// In-game days since the world was created; the whole world gets harder at once
+    #[default]
+    WorldAge,
+    /// Hours players spent in a region, summed over players; busy regions get harder
+    RegionActivity,
+}
+
+#[derive(Clone, Debug)]
+pub struct DifficultySettings {
+    pub scaling: DifficultyScaling,
+    /// `(days or hours, difficulty)` points, sorted. Difficulty is linear between points, flat
+    /// after the last one, and never below 1.
+    pub curve: Vec<(f64, f32)>,
+    /// Seconds between raids at difficulty 1, shorter on harder worlds. `None` disables raids.
+    pub raid_interval_secs: Option<u64>,
+}
+
+impl Default for DifficultySettings {
+    fn default() -> Self {
+        Self {
+            scaling: DifficultyScaling::WorldAge,
+            curve: vec![(0.0, 1.0), (30.0, 2.0), (100.0, 3.0)],
+            raid_interval_secs: Some(40 * 60),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SeasonSettings {
+    /// Number of in-game days each season lasts
+    pub length_days: u32,
+    /// If set, the season never changes
+    pub locked: Option<Season>,
+}
+
+impl Default for SeasonSettings {
+    fn default() -> Self {
+        Self {
+            length_days: 7,
+            locked: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct GuardrailSettings {
+    /// Maximum number of replicated entities a single client receives
+    pub max_replicated_per_client: usize,
+    /// Maximum number of NPCs alive on the server, not counting villagers
+    pub max_npcs: usize,
+    /// Maximum number of dropped item stacks in a single chunk
+    pub max_dropped_items_per_chunk: usize,
+    /// Dropped items of the same kind within this many tiles of each other get stacked
+    /// when a chunk goes over its cap
+    pub merge_radius: i32,
+}
+
+impl Default for GuardrailSettings {
+    fn default() -> Self {
+        Self {
+            max_replicated_per_client: 2000,
+            max_npcs: 256,
+            max_dropped_items_per_chunk: 64,
+            merge_radius: 2,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RestartAction {
+    /// Exit the process and let an external supervisor (systemd, docker, ...) start it again
+    Exit,
+    /// Replace the running process with a fresh copy of the same binary and arguments. The new
+    /// process binds the listener socket again; the old one isn't kept open across the restart.
+    Reexec,
+}
+
+#[derive(Clone, Debug)]
+pub struct RestartSchedule {
+    /// Time of day (UTC) of the restart, as (hour, minute)
+    pub daily_at: (u8, u8),
+    /// Seconds before the restart at which connected players are warned
+    pub warnings: Vec<u64>,
+    pub action: RestartAction,
 }
 
 #[derive(Clone, Debug)]
@@ -142,6 +618,10 @@ pub struct ClientSettings {
     /// If true, enable bevy_inspector_egui
     pub inspector: bool,
 
+    /// If true, replay the shared simulation locally and dump the first state that diverges from
+    /// the server's to a file
+    pub lockstep_debug: bool,
+
     /// The client id
     pub client_id: u64,
 
@@ -159,6 +639,82 @@ pub struct ClientSettings {
 
     /// Possibly add a conditioner to simulate network conditions
     pub conditioner: Option<Conditioner>,
+
+    /// If true, render the world with an isometric projection instead of top-down
+    pub isometric: bool,
+
+    /// Background music volume
+    pub music: MusicSettings,
+
+    /// Anonymous gameplay statistics, only collected and sent if the player opts in
+    pub telemetry: TelemetrySettings,
+
+    /// Whether to download the resource packs servers offer
+    pub resource_packs: ResourcePackSettings,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct MusicSettings {
+    /// Volume of the music, from 0.0 (silent) to 1.0
+    pub volume: f32,
+    /// If true, no music plays at all
+    pub muted: bool,
+}
+
+impl Default for MusicSettings {
+    fn default() -> Self {
+        Self {
+            volume: 0.5,
+            muted: false,
+        }
+    }
+}
+
+/// Statistics about how the game is played (session length, loaded chunks, features used),
+/// tied to a random id drawn for each session rather than to the player. Nothing is recorded
+/// unless `enabled` is set and the game was built with the `telemetry` feature.
+#[derive(Clone, Debug)]
+pub struct TelemetrySettings {
+    /// Opt-in switch, off by default. Turning it off also deletes the reports not sent yet.
+    pub enabled: bool,
+    /// URL the reports are POSTed to, as a JSON array
+    pub endpoint: String,
+    /// How often the statistics of the session are cut into a report and the queue is flushed
+    pub flush_interval: Duration,
+}
+
+impl Default for TelemetrySettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: "https://telemetry.dreamgame.invalid/v1/reports".to_string(),
+            flush_interval: Duration::from_secs(300),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PackConsent {
+    /// Ask the player for each pack that isn't cached yet
+    Ask,
+    Always,
+    Never,
+}
+
+#[derive(Clone, Debug)]
+pub struct ResourcePackSettings {
+    pub consent: PackConsent,
+    /// Largest pack downloaded, in bytes; bigger ones are declined without asking
+    pub max_size: usize,
+}
+
+impl Default for ResourcePackSettings {
+    fn default() -> Self {
+        Self {
+            consent: PackConsent::Ask,
+            max_size: 16 * 1024 * 1024,
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -171,6 +727,9 @@ pub struct SharedSettings {
 
     /// compression options
     pub compression: CompressionConfig,
+
+    /// Frame time budget, and how the app protects itself when frames go over it
+    pub frame_budget: FrameBudget,
 }
 
 #[derive(Resource, Debug, Clone)]
@@ -449,6 +1008,20 @@ pub fn get_client_net_config(settings: &Settings, client_id: u64) -> client::Net
     }
 }
 
+/// Whether the server can trust the ids clients connect with. Clients built from these settings
+/// forge their own connect tokens with the shared private key (`Authentication::Manual`), so they
+/// pick whatever id they like. Ids are only verified when the server runs with a private key of
+/// its own, from LIGHTYEAR_PRIVATE_KEY, and clients get their connect tokens from a token server
+/// holding that key.
+///
+/// Settings granting rights to client ids are ignored unless ids are verified. Bans, mutes and
+/// player profiles are keyed on client ids too: without verified ids, banned players can come back
+/// under another id, and anyone can claim another player's profile.
+#[cfg(feature = "server")]
+pub fn client_ids_verified(shared: &SharedSettings) -> bool {
+    parse_private_key_from_env().is_some_and(|key| key != shared.private_key)
+}
+
 /// Reads and parses the LIGHTYEAR_PRIVATE_KEY environment variable into a private key.
 #[cfg(feature = "server")]
 pub fn parse_private_key_from_env() -> Option<[u8; PRIVATE_KEY_BYTES]> {
@@ -1,2 +1,47 @@
+pub mod achievements;
+pub mod biome_map;
+pub mod chunk_format;
+#[cfg(feature = "cloud-sync")]
+pub mod cloud_sync;
+pub mod commands;
+pub mod danger;
+pub mod day_night;
+pub mod difficulty;
+pub mod encyclopedia;
+pub mod error;
+pub mod feature_flags;
+pub mod frame_pacing;
+pub mod graves;
+pub mod height_map;
+pub mod history;
+pub mod instances;
+pub mod interaction;
+pub mod items;
+pub mod kinematics;
+pub mod line_of_sight;
+pub mod logistics;
+pub mod map_image;
+pub mod moderation;
 pub mod movement;
+pub mod net_diagnostics;
+pub mod npc;
+pub mod party;
+pub mod pathfinding;
+pub mod portals;
+pub mod power;
+pub mod projectiles;
+pub mod rails;
+pub mod recipes;
+pub mod regions;
+pub mod reputation;
+pub mod resource_pack;
+pub mod seasons;
+pub mod skills;
+pub mod social;
+pub mod structures;
+pub mod survival;
+pub mod temperature;
+pub mod terrain_import;
+pub mod tutorial;
+pub mod villages;
 pub mod world_generation;
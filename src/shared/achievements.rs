@@ -0,0 +1,32 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// How far a player is towards one achievement
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AchievementStatus {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub progress: u32,
+    pub goal: u32,
+    pub unlocked: bool,
+}
+
+/// Every achievement with the player's progress, in the order the server lists them
+#[derive(Component, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Achievements {
+    pub list: Vec<AchievementStatus>,
+}
+
+impl Achievements {
+    pub fn unlocked(&self) -> usize {
+        self.list.iter().filter(|status| status.unlocked).count()
+    }
+}
+
+/// Sent to a player when they unlock an achievement
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AchievementUnlocked {
+    pub name: String,
+    pub description: String,
+}
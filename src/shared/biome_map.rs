@@ -0,0 +1,60 @@
+use bevy::prelude::*;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::shared::world_generation::{chunk_biome, BiomeType, ChunkCoord, WorldConfig, WorldGrid};
+
+// Number of cached chunk biomes before the cache is cleared
+const BIOME_CACHE_LIMIT: usize = 16 * 1024;
+
+/// Answers "which biome is at this world position" for any coordinates, without generating
+/// or loading chunks. Biomes are computed from the world seed and cached per chunk.
+#[derive(Resource)]
+pub struct BiomeMap {
+    config: WorldConfig,
+    // behind a mutex so that lookups only need `Res<BiomeMap>`
+    cache: Mutex<HashMap<ChunkCoord, BiomeType>>,
+}
+
+impl FromWorld for BiomeMap {
+    fn from_world(world: &mut World) -> Self {
+        let config = world
+            .get_resource::<WorldConfig>()
+            .cloned()
+            .unwrap_or_default();
+        BiomeMap::new(config)
+    }
+}
+
+impl BiomeMap {
+    pub fn new(config: WorldConfig) -> Self {
+        Self {
+            config,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Biome of the given world tile
+    pub fn biome_at(&self, world_x: i32, world_y: i32) -> BiomeType {
+        self.chunk_biome(self.config.grid().tile_to_chunk((world_x, world_y)))
+    }
+
+    /// Biome at a world position, e.g. a player or NPC position
+    pub fn biome_at_position(&self, position: Vec2) -> BiomeType {
+        let (x, y) = WorldGrid::world_to_tile(position);
+        self.biome_at(x, y)
+    }
+
+    pub fn chunk_biome(&self, coord: ChunkCoord) -> BiomeType {
+        let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(biome) = cache.get(&coord) {
+            return *biome;
+        }
+        if cache.len() >= BIOME_CACHE_LIMIT {
+            cache.clear();
+        }
+        let biome = chunk_biome(&coord, &self.config);
+        cache.insert(coord, biome);
+        biome
+    }
+}
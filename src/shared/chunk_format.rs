@@ -0,0 +1,240 @@
+//! Encodings of chunk data, on the wire and on disk. Encoded chunks start with a byte naming
+//! their format, so that any of them can be read back whatever the other side chose.
+//!
+//! Most of a chunk is long runs of the same values: the same few tile types, and no decoration,
+//! resource or overlay on most tiles. The packed format stores the tile types as indices into a
+//! palette, and every layer as runs of equal values; heights are kept as they are.
+use bincode::Options;
+use serde::{Deserialize, Serialize};
+
+use crate::shared::error::GameError;
+use crate::shared::world_generation::{
+    BiomeType, Chunk, ChunkCoord, Decoration, Overlay, ResourceType, Tile, TileType, WorldGrid,
+    MAX_CHUNK_BYTES,
+};
+
+/// How a chunk is encoded. Clients name the best format they can read in their chunk requests,
+/// and the server answers in the best one both sides support.
+#[derive(
+    Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+pub enum ChunkFormat {
+    /// The chunk as it is in memory
+    #[default]
+    Raw,
+    /// Tile types through a palette, and every layer run-length encoded
+    Packed,
+    /// Packed, then compressed with LZ4
+    Lz4,
+}
+
+impl ChunkFormat {
+    const ALL: [ChunkFormat; 3] = [ChunkFormat::Raw, ChunkFormat::Packed, ChunkFormat::Lz4];
+
+    /// Best format this build can read and write
+    pub fn best_supported() -> Self {
+        if cfg!(feature = "chunk-compression") {
+            ChunkFormat::Lz4
+        } else {
+            ChunkFormat::Packed
+        }
+    }
+
+    /// Format to send chunks in to a peer that reads up to `accepted`
+    pub fn negotiate(accepted: ChunkFormat) -> Self {
+        accepted.min(Self::best_supported())
+    }
+
+    fn tag(self) -> u8 {
+        self as u8
+    }
+
+    fn from_tag(tag: u8) -> Option<Self> {
+        Self::ALL.into_iter().find(|format| format.tag() == tag)
+    }
+}
+
+// Runs of equal values, in order. Runs never grow past what their length can count.
+fn run_length<T: PartialEq + Copy>(values: impl IntoIterator<Item = T>) -> Vec<(T, u16)> {
+    let mut runs: Vec<(T, u16)> = Vec::new();
+    for value in values {
+        match runs.last_mut() {
+            Some((last, count)) if *last == value && *count < u16::MAX => *count += 1,
+            _ => runs.push((value, 1)),
+        }
+    }
+    runs
+}
+
+// Values back from their runs, which must add up to exactly `len` values
+fn expand<T: Copy>(runs: &[(T, u16)], len: usize) -> Result<Vec<T>, GameError> {
+    let total: usize = runs.iter().map(|(_, count)| *count as usize).sum();
+    if total != len {
+        return Err(GameError::ChunkFormat(format!(
+            "runs cover {} tiles instead of {}",
+            total, len
+        )));
+    }
+    Ok(runs
+        .iter()
+        .flat_map(|(value, count)| std::iter::repeat_n(*value, *count as usize))
+        .collect())
+}
+
+// A chunk in the packed format. Tile positions follow from the chunk's coordinates, and the baked
+// bitmaps from the tiles, so neither is stored.
+#[derive(Serialize, Deserialize)]
+struct PackedChunk {
+    coord: ChunkCoord,
+    size: u32,
+    biome_type: BiomeType,
+    last_accessed: f64,
+    version: u32,
+    // Tile types found in the chunk; tile types are runs of indices into it
+    palette: Vec<TileType>,
+    tile_types: Vec<(u8, u16)>,
+    decorations: Vec<(Decoration, u16)>,
+    resources: Vec<(ResourceType, u16)>,
+    overlays: Vec<(Overlay, u16)>,
+    traversable: Vec<(bool, u16)>,
+    damage: Vec<(u8, u16)>,
+    heights: Vec<f32>,
+}
+
+impl PackedChunk {
+    fn pack(chunk: &Chunk) -> Self {
+        let tiles = chunk.tiles();
+        let mut palette = Vec::new();
+        let indices = tiles.iter().map(|tile| {
+            let index = palette.iter().position(|t| *t == tile.tile_type);
+            index.unwrap_or_else(|| {
+                palette.push(tile.tile_type);
+                palette.len() - 1
+            }) as u8
+        });
+        let tile_types = run_length(indices);
+        PackedChunk {
+            coord: chunk.coord,
+            size: chunk.size() as u32,
+            biome_type: chunk.biome_type,
+            last_accessed: chunk.last_accessed,
+            version: chunk.version(),
+            palette,
+            tile_types,
+            decorations: run_length(tiles.iter().map(|tile| tile.decoration)),
+            resources: run_length(tiles.iter().map(|tile| tile.resource)),
+            overlays: run_length(tiles.iter().map(|tile| tile.overlay)),
+            traversable: run_length(tiles.iter().map(|tile| tile.traversable)),
+            damage: run_length(tiles.iter().map(|tile| tile.damage)),
+            heights: tiles.iter().map(|tile| tile.height).collect(),
+        }
+    }
+
+    fn unpack(self) -> Result<Chunk, GameError> {
+        let size = self.size as usize;
+        // the heights were read within the size limit, so they bound everything else
+        let len = self.heights.len();
+        if size.checked_mul(size) != Some(len) {
+            return Err(GameError::MalformedChunk(self.coord));
+        }
+        let tile_types = expand(&self.tile_types, len)?
+            .into_iter()
+            .map(|index| self.palette.get(index as usize).copied())
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| GameError::ChunkFormat("tile type outside of the palette".into()))?;
+        let decorations = expand(&self.decorations, len)?;
+        let resources = expand(&self.resources, len)?;
+        let overlays = expand(&self.overlays, len)?;
+        let traversable = expand(&self.traversable, len)?;
+        let damage = expand(&self.damage, len)?;
+        let grid = WorldGrid::new(size);
+        let tiles = (0..len)
+            .map(|index| Tile {
+                tile_type: tile_types[index],
+                decoration: decorations[index],
+                resource: resources[index],
+                overlay: overlays[index],
+                height: self.heights[index],
+                position: grid.local_to_tile(self.coord, (index % size, index / size)),
+                traversable: traversable[index],
+                damage: damage[index],
+            })
+            .collect();
+        Ok(
+            Chunk::new(self.coord, size, tiles, self.biome_type, self.last_accessed)
+                .with_version(self.version),
+        )
+    }
+}
+
+#[cfg(feature = "chunk-compression")]
+fn compress(data: &[u8]) -> Result<Vec<u8>, GameError> {
+    Ok(lz4_flex::compress_prepend_size(data))
+}
+
+#[cfg(feature = "chunk-compression")]
+fn decompress(data: &[u8]) -> Result<Vec<u8>, GameError> {
+    let error = |e: &dyn std::fmt::Display| GameError::ChunkFormat(e.to_string());
+    let (size, compressed) = data
+        .split_first_chunk::<4>()
+        .ok_or_else(|| error(&"truncated compressed chunk"))?;
+    // the size comes first, so that hostile data can't make us allocate unbounded memory
+    let size = u32::from_le_bytes(*size) as u64;
+    if size > MAX_CHUNK_BYTES {
+        return Err(error(&format!("compressed chunk claims {} bytes", size)));
+    }
+    lz4_flex::decompress(compressed, size as usize).map_err(|e| error(&e))
+}
+
+#[cfg(not(feature = "chunk-compression"))]
+fn compress(_data: &[u8]) -> Result<Vec<u8>, GameError> {
+    Err(GameError::ChunkFormat(
+        "the game was built without chunk compression".to_string(),
+    ))
+}
+
+#[cfg(not(feature = "chunk-compression"))]
+fn decompress(_data: &[u8]) -> Result<Vec<u8>, GameError> {
+    compress(&[])
+}
+
+fn options() -> impl Options {
+    bincode::options()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(MAX_CHUNK_BYTES)
+}
+
+pub(crate) fn encode(chunk: &Chunk, format: ChunkFormat) -> Result<Vec<u8>, GameError> {
+    let body = match format {
+        ChunkFormat::Raw => options().serialize(chunk),
+        ChunkFormat::Packed | ChunkFormat::Lz4 => options().serialize(&PackedChunk::pack(chunk)),
+    }
+    .map_err(|source| GameError::ChunkEncode {
+        coord: chunk.coord,
+        source,
+    })?;
+    let body = match format {
+        ChunkFormat::Lz4 => compress(&body)?,
+        _ => body,
+    };
+    let mut data = Vec::with_capacity(body.len() + 1);
+    data.push(format.tag());
+    data.extend(body);
+    Ok(data)
+}
+
+pub(crate) fn decode(data: &[u8]) -> Result<Chunk, GameError> {
+    let (&tag, body) = data
+        .split_first()
+        .ok_or_else(|| GameError::ChunkFormat("empty chunk data".to_string()))?;
+    let format = ChunkFormat::from_tag(tag)
+        .ok_or_else(|| GameError::ChunkFormat(format!("unknown format {}", tag)))?;
+    match format {
+        ChunkFormat::Raw => Ok(options().deserialize(body)?),
+        ChunkFormat::Packed => options().deserialize::<PackedChunk>(body)?.unpack(),
+        ChunkFormat::Lz4 => options()
+            .deserialize::<PackedChunk>(&decompress(body)?)?
+            .unpack(),
+    }
+}
@@ -0,0 +1,75 @@
+//! State of the cloud copy of a hosted world, shared between the server side doing the syncing
+//! and the client side asking the player to settle conflicts. Both run in the host's app.
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+pub struct CloudSyncPlugin;
+
+impl Plugin for CloudSyncPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CloudSyncStatus>()
+            .add_event::<ResolveSyncConflict>();
+    }
+}
+
+/// Identifies one save of the world
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SaveManifest {
+    /// Unix time the save was made, in seconds
+    pub saved_at: u64,
+    /// Hash of the archived world
+    pub hash: String,
+}
+
+#[derive(Resource, Clone, Debug, Default, PartialEq)]
+pub enum CloudSyncStatus {
+    /// No cloud endpoint is configured
+    #[default]
+    Disabled,
+    InSync,
+    Uploading,
+    /// The cloud copy picked in a conflict is being downloaded. The game exits once it's done.
+    Downloading,
+    /// Both the local world and the cloud copy changed since the last sync. Nothing is uploaded
+    /// until the player picks one.
+    Conflict {
+        local: SaveManifest,
+        cloud: SaveManifest,
+    },
+    Failed(String),
+}
+
+/// The player's choice for a [`CloudSyncStatus::Conflict`]
+#[derive(Event, Clone, Copy, Debug, PartialEq)]
+pub enum ResolveSyncConflict {
+    /// Overwrite the cloud copy with the local world
+    KeepLocal,
+    /// Replace the local world with the cloud copy. The game exits once it is downloaded, and the
+    /// copy takes the place of the world at the next start.
+    UseCloud,
+}
+
+/// Calendar date and time in UTC of a unix time: year, month, day, hours, minutes, seconds
+pub fn utc_time(secs: u64) -> (i64, u32, u32, u32, u32, u32) {
+    let days = (secs / 86_400) as i64;
+    let time = secs % 86_400;
+    // days to civil date, from Howard Hinnant's algorithm
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (
+        year,
+        month,
+        day,
+        (time / 3600) as u32,
+        (time % 3600 / 60) as u32,
+        (time % 60) as u32,
+    )
+}
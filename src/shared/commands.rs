@@ -0,0 +1,426 @@
+//! Declarative "/command" framework shared by the client and the server.
+//!
+//! Commands are described once with a [`CommandSpec`] (name, typed arguments, required permission)
+//! and registered with [`RegisterCommandExt::register_command`]. The [`CommandRegistry`] then takes care of
+//! parsing a raw chat line into typed [`CommandArgs`], checking the caller's [`PermissionLevel`] and
+//! generating the `/help` output, so gameplay code only has to react to [`CommandInvoked`] events.
+use bevy::prelude::*;
+use lightyear::prelude::ClientId;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// Prefix that marks a chat line as a command
+pub const COMMAND_PREFIX: char = '/';
+
+/// Permission tiers, ordered from least to most privileged
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub enum PermissionLevel {
+    #[default]
+    Player,
+    Moderator,
+    Admin,
+}
+
+impl fmt::Display for PermissionLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PermissionLevel::Player => write!(f, "player"),
+            PermissionLevel::Moderator => write!(f, "moderator"),
+            PermissionLevel::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+// Types an argument can be parsed into
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArgKind {
+    Int,
+    Float,
+    Bool,
+    /// A single whitespace-delimited word
+    Word,
+    /// Everything until the end of the line. Must be the last argument.
+    Text,
+}
+
+impl fmt::Display for ArgKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArgKind::Int => write!(f, "integer"),
+            ArgKind::Float => write!(f, "number"),
+            ArgKind::Bool => write!(f, "true/false"),
+            ArgKind::Word => write!(f, "word"),
+            ArgKind::Text => write!(f, "text"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ArgSpec {
+    pub name: &'static str,
+    pub kind: ArgKind,
+    pub optional: bool,
+}
+
+/// Declarative description of a command
+#[derive(Clone, Debug)]
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub aliases: Vec<&'static str>,
+    pub description: &'static str,
+    pub args: Vec<ArgSpec>,
+    pub permission: PermissionLevel,
+}
+
+impl CommandSpec {
+    pub fn new(name: &'static str, description: &'static str) -> Self {
+        Self {
+            name,
+            aliases: Vec::new(),
+            description,
+            args: Vec::new(),
+            permission: PermissionLevel::Player,
+        }
+    }
+
+    pub fn alias(mut self, alias: &'static str) -> Self {
+        self.aliases.push(alias);
+        self
+    }
+
+    pub fn arg(mut self, name: &'static str, kind: ArgKind) -> Self {
+        self.args.push(ArgSpec {
+            name,
+            kind,
+            optional: false,
+        });
+        self
+    }
+
+    pub fn optional_arg(mut self, name: &'static str, kind: ArgKind) -> Self {
+        self.args.push(ArgSpec {
+            name,
+            kind,
+            optional: true,
+        });
+        self
+    }
+
+    pub fn permission(mut self, permission: PermissionLevel) -> Self {
+        self.permission = permission;
+        self
+    }
+
+    /// Usage line, e.g. `/tp <x> <y> [player]`
+    pub fn usage(&self) -> String {
+        let mut usage = format!("{}{}", COMMAND_PREFIX, self.name);
+        for arg in &self.args {
+            if arg.optional {
+                usage.push_str(&format!(" [{}]", arg.name));
+            } else {
+                usage.push_str(&format!(" <{}>", arg.name));
+            }
+        }
+        usage
+    }
+}
+
+/// A parsed argument value
+#[derive(Clone, Debug, PartialEq)]
+pub enum ArgValue {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Word(String),
+    Text(String),
+}
+
+/// Typed arguments of an invoked command, keyed by the names declared in the [`CommandSpec`]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CommandArgs(BTreeMap<&'static str, ArgValue>);
+
+impl CommandArgs {
+    pub fn get(&self, name: &str) -> Option<&ArgValue> {
+        self.0.get(name)
+    }
+
+    pub fn int(&self, name: &str) -> Option<i64> {
+        match self.get(name) {
+            Some(ArgValue::Int(v)) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Floats also accept integer arguments
+    pub fn float(&self, name: &str) -> Option<f64> {
+        match self.get(name) {
+            Some(ArgValue::Float(v)) => Some(*v),
+            Some(ArgValue::Int(v)) => Some(*v as f64),
+            _ => None,
+        }
+    }
+
+    pub fn bool(&self, name: &str) -> Option<bool> {
+        match self.get(name) {
+            Some(ArgValue::Bool(v)) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn str(&self, name: &str) -> Option<&str> {
+        match self.get(name) {
+            Some(ArgValue::Word(v)) | Some(ArgValue::Text(v)) => Some(v.as_str()),
+            _ => None,
+        }
+    }
+}
+
+/// Who issued a command
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CommandSource {
+    /// The server console (always has [`PermissionLevel::Admin`])
+    Console,
+    Client(ClientId),
+}
+
+/// Emitted once a command has been parsed and its permission checked.
+/// Command handlers read these and filter on `name`.
+#[derive(Event, Clone, Debug)]
+pub struct CommandInvoked {
+    pub source: CommandSource,
+    pub name: &'static str,
+    pub args: CommandArgs,
+}
+
+/// Text sent back to whoever issued a command
+#[derive(Event, Clone, Debug)]
+pub struct CommandReply {
+    pub target: CommandSource,
+    pub text: String,
+}
+
+impl CommandReply {
+    pub fn new(target: CommandSource, text: impl Into<String>) -> Self {
+        Self {
+            target,
+            text: text.into(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CommandError {
+    NotACommand,
+    Unknown(String),
+    MissingArgument {
+        command: &'static str,
+        arg: &'static str,
+    },
+    InvalidArgument {
+        command: &'static str,
+        arg: &'static str,
+        expected: ArgKind,
+        got: String,
+    },
+    TooManyArguments {
+        command: &'static str,
+    },
+    PermissionDenied {
+        command: &'static str,
+        required: PermissionLevel,
+    },
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::NotACommand => write!(f, "not a command"),
+            CommandError::Unknown(name) => {
+                write!(f, "unknown command '{}', try {}help", name, COMMAND_PREFIX)
+            }
+            CommandError::MissingArgument { command, arg } => {
+                write!(
+                    f,
+                    "{}{}: missing argument <{}>",
+                    COMMAND_PREFIX, command, arg
+                )
+            }
+            CommandError::InvalidArgument {
+                command,
+                arg,
+                expected,
+                got,
+            } => write!(
+                f,
+                "{}{}: argument <{}> expects {}, got '{}'",
+                COMMAND_PREFIX, command, arg, expected, got
+            ),
+            CommandError::TooManyArguments { command } => {
+                write!(f, "{}{}: too many arguments", COMMAND_PREFIX, command)
+            }
+            CommandError::PermissionDenied { command, required } => write!(
+                f,
+                "{}{} requires {} permission",
+                COMMAND_PREFIX, command, required
+            ),
+        }
+    }
+}
+
+/// All registered commands
+#[derive(Resource, Default)]
+pub struct CommandRegistry {
+    commands: BTreeMap<&'static str, CommandSpec>,
+    aliases: HashMap<&'static str, &'static str>,
+}
+
+impl CommandRegistry {
+    pub fn register(&mut self, spec: CommandSpec) {
+        if self.commands.contains_key(spec.name) {
+            warn!("Command {} registered twice, overriding", spec.name);
+        }
+        for alias in &spec.aliases {
+            self.aliases.insert(*alias, spec.name);
+        }
+        self.commands.insert(spec.name, spec);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&CommandSpec> {
+        let name = self.aliases.get(name).copied().unwrap_or(name);
+        self.commands.get(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &CommandSpec> {
+        self.commands.values()
+    }
+
+    /// Parse a raw chat line such as `/tp 10 -4` and check it against the caller's permission level
+    pub fn parse(
+        &self,
+        line: &str,
+        permission: PermissionLevel,
+    ) -> Result<(&CommandSpec, CommandArgs), CommandError> {
+        let Some(body) = line.trim().strip_prefix(COMMAND_PREFIX) else {
+            return Err(CommandError::NotACommand);
+        };
+        let (name, mut rest) = split_word(body);
+        if name.is_empty() {
+            return Err(CommandError::NotACommand);
+        }
+        let name = name.to_lowercase();
+        let Some(spec) = self.get(&name) else {
+            return Err(CommandError::Unknown(name));
+        };
+        if permission < spec.permission {
+            return Err(CommandError::PermissionDenied {
+                command: spec.name,
+                required: spec.permission,
+            });
+        }
+
+        let mut args = CommandArgs::default();
+        for arg in &spec.args {
+            let raw = if arg.kind == ArgKind::Text {
+                let text = rest.trim();
+                rest = "";
+                text
+            } else {
+                let (word, remaining) = split_word(rest);
+                rest = remaining;
+                word
+            };
+            if raw.is_empty() {
+                if arg.optional {
+                    continue;
+                }
+                return Err(CommandError::MissingArgument {
+                    command: spec.name,
+                    arg: arg.name,
+                });
+            }
+            let value = parse_arg(arg.kind, raw).ok_or_else(|| CommandError::InvalidArgument {
+                command: spec.name,
+                arg: arg.name,
+                expected: arg.kind,
+                got: raw.to_string(),
+            })?;
+            args.0.insert(arg.name, value);
+        }
+        if !rest.trim().is_empty() {
+            return Err(CommandError::TooManyArguments { command: spec.name });
+        }
+        Ok((spec, args))
+    }
+
+    /// Generate the `/help` listing, only showing commands available at the given permission level
+    pub fn help_lines(&self, permission: PermissionLevel) -> Vec<String> {
+        self.iter()
+            .filter(|spec| spec.permission <= permission)
+            .map(|spec| format!("{} - {}", spec.usage(), spec.description))
+            .collect()
+    }
+}
+
+fn split_word(input: &str) -> (&str, &str) {
+    let input = input.trim_start();
+    match input.find(char::is_whitespace) {
+        Some(index) => (&input[..index], &input[index..]),
+        None => (input, ""),
+    }
+}
+
+fn parse_arg(kind: ArgKind, raw: &str) -> Option<ArgValue> {
+    match kind {
+        ArgKind::Int => raw.parse().ok().map(ArgValue::Int),
+        // "NaN" and "inf" parse as floats, but no command can do anything with them
+        ArgKind::Float => raw
+            .parse::<f64>()
+            .ok()
+            .filter(|value| value.is_finite())
+            .map(ArgValue::Float),
+        ArgKind::Bool => match raw.to_lowercase().as_str() {
+            "true" | "on" | "yes" | "1" => Some(ArgValue::Bool(true)),
+            "false" | "off" | "no" | "0" => Some(ArgValue::Bool(false)),
+            _ => None,
+        },
+        ArgKind::Word => Some(ArgValue::Word(raw.to_string())),
+        ArgKind::Text => Some(ArgValue::Text(raw.to_string())),
+    }
+}
+
+/// Lets plugins register their commands declaratively: `app.register_command(CommandSpec::new(..))`
+pub trait RegisterCommandExt {
+    fn register_command(&mut self, spec: CommandSpec) -> &mut Self;
+}
+
+impl RegisterCommandExt for App {
+    fn register_command(&mut self, spec: CommandSpec) -> &mut Self {
+        self.init_resource::<CommandRegistry>();
+        self.world_mut()
+            .resource_mut::<CommandRegistry>()
+            .register(spec);
+        self
+    }
+}
+
+// Plugin that sets up the command registry and the built-in /help command
+#[derive(Clone)]
+pub struct CommandsPlugin;
+
+impl Plugin for CommandsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CommandRegistry>()
+            .add_event::<CommandInvoked>()
+            .add_event::<CommandReply>()
+            .register_command(CommandSpec::new(
+                "help",
+                "List the commands available to you",
+            ));
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,128 @@
+//! Chat lines must parse into the declared arguments, and be refused with the right error
+use super::*;
+
+fn registry() -> CommandRegistry {
+    let mut registry = CommandRegistry::default();
+    registry.register(
+        CommandSpec::new("tp", "Teleport")
+            .alias("teleport")
+            .arg("x", ArgKind::Int)
+            .arg("y", ArgKind::Int)
+            .optional_arg("player", ArgKind::Word),
+    );
+    registry.register(CommandSpec::new("say", "Say something").arg("message", ArgKind::Text));
+    registry.register(
+        CommandSpec::new("netsim", "Simulate a bad network")
+            .arg("loss", ArgKind::Float)
+            .optional_arg("enabled", ArgKind::Bool)
+            .permission(PermissionLevel::Admin),
+    );
+    registry
+}
+
+fn parse(line: &str) -> Result<(&'static str, CommandArgs), CommandError> {
+    let registry = registry();
+    registry
+        .parse(line, PermissionLevel::Admin)
+        .map(|(spec, args)| (spec.name, args))
+}
+
+#[test]
+fn aliases_and_any_case_name_the_same_command() {
+    assert_eq!(parse("/teleport 1 2").unwrap().0, "tp");
+    assert_eq!(parse("/TP 1 2").unwrap().0, "tp");
+    assert_eq!(parse("  /Teleport 1 2  ").unwrap().0, "tp");
+}
+
+#[test]
+fn arguments_are_parsed_into_their_kind() {
+    let (_, args) = parse("/tp 10 -4 alice").unwrap();
+    assert_eq!(args.int("x"), Some(10));
+    assert_eq!(args.int("y"), Some(-4));
+    assert_eq!(args.str("player"), Some("alice"));
+
+    let (_, args) = parse("/netsim 0.25 on").unwrap();
+    assert_eq!(args.float("loss"), Some(0.25));
+    assert_eq!(args.bool("enabled"), Some(true));
+}
+
+#[test]
+fn optional_arguments_can_be_left_out() {
+    let (_, args) = parse("/tp 10 -4").unwrap();
+    assert_eq!(args.str("player"), None);
+    assert_eq!(
+        parse("/tp 10"),
+        Err(CommandError::MissingArgument {
+            command: "tp",
+            arg: "y",
+        })
+    );
+}
+
+#[test]
+fn text_takes_the_rest_of_the_line() {
+    let (_, args) = parse("/say  hello there,   world ").unwrap();
+    assert_eq!(args.str("message"), Some("hello there,   world"));
+}
+
+#[test]
+fn invalid_values_are_refused() {
+    assert_eq!(
+        parse("/tp ten 4"),
+        Err(CommandError::InvalidArgument {
+            command: "tp",
+            arg: "x",
+            expected: ArgKind::Int,
+            got: "ten".to_string(),
+        })
+    );
+    assert_eq!(
+        parse("/netsim 0.5 maybe"),
+        Err(CommandError::InvalidArgument {
+            command: "netsim",
+            arg: "enabled",
+            expected: ArgKind::Bool,
+            got: "maybe".to_string(),
+        })
+    );
+    for raw in ["NaN", "inf", "-inf"] {
+        assert!(
+            matches!(
+                parse(&format!("/netsim {}", raw)),
+                Err(CommandError::InvalidArgument { arg: "loss", .. })
+            ),
+            "{} was accepted",
+            raw
+        );
+    }
+}
+
+#[test]
+fn extra_arguments_are_refused() {
+    assert_eq!(
+        parse("/tp 1 2 alice bob"),
+        Err(CommandError::TooManyArguments { command: "tp" })
+    );
+}
+
+#[test]
+fn commands_need_their_permission() {
+    let registry = registry();
+    assert_eq!(
+        registry
+            .parse("/netsim 0.1", PermissionLevel::Moderator)
+            .err(),
+        Some(CommandError::PermissionDenied {
+            command: "netsim",
+            required: PermissionLevel::Admin,
+        })
+    );
+    assert!(registry.parse("/tp 1 2", PermissionLevel::Player).is_ok());
+}
+
+#[test]
+fn other_lines_are_not_commands() {
+    assert_eq!(parse("hello"), Err(CommandError::NotACommand));
+    assert_eq!(parse("/"), Err(CommandError::NotACommand));
+    assert_eq!(parse("/fly"), Err(CommandError::Unknown("fly".to_string())));
+}
@@ -0,0 +1,10 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// Highest danger level, reached during raids or when surrounded by hostiles
+pub const MAX_DANGER: u8 = 3;
+
+/// How much danger a player is in, from 0 (safe) to `MAX_DANGER`. Computed by the server from the
+/// hostiles around the player and active raids; clients use it for music and warnings.
+#[derive(Component, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DangerLevel(pub u8);
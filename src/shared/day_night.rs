@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+
+// Length of a full in-game day, in seconds of WorldState::world_time
+pub const DAY_LENGTH: f64 = 20.0 * 60.0;
+
+// Part of the day, derived from the world time
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DayPhase {
+    Dawn,
+    Day,
+    Dusk,
+    Night,
+}
+
+impl DayPhase {
+    pub fn at(world_time: f64) -> DayPhase {
+        match time_of_day(world_time) {
+            t if t < 0.2 => DayPhase::Dawn,
+            t if t < 0.5 => DayPhase::Day,
+            t if t < 0.7 => DayPhase::Dusk,
+            _ => DayPhase::Night,
+        }
+    }
+}
+
+/// Position in the current day, from 0.0 (start of dawn) to 1.0 (end of night)
+pub fn time_of_day(world_time: f64) -> f32 {
+    (world_time.rem_euclid(DAY_LENGTH) / DAY_LENGTH) as f32
+}
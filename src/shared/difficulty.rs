@@ -0,0 +1,57 @@
+//! How hard the world is. Difficulty starts at 1 and follows a curve set per server, over the
+//! age of the world or the time players spent in a region, so that long-running servers stay
+//! challenging.
+use serde::{Deserialize, Serialize};
+
+use crate::shared::seasons::Season;
+
+// Seasons raiders like to come in; they come in smaller numbers in the others
+const RAID_SEASONS: [Season; 2] = [Season::Autumn, Season::Winter];
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub struct Difficulty(pub f32);
+
+impl Default for Difficulty {
+    fn default() -> Self {
+        Difficulty(1.0)
+    }
+}
+
+impl Difficulty {
+    /// Difficulty at `x` on a curve of `(x, difficulty)` points sorted by `x`: linear between two
+    /// points, flat before the first and after the last one. Never below 1.
+    pub fn on_curve(curve: &[(f64, f32)], x: f64) -> Difficulty {
+        let value = match curve.iter().position(|(point, _)| *point > x) {
+            None => curve.last().map_or(1.0, |(_, value)| *value),
+            Some(0) => curve[0].1,
+            Some(index) => {
+                let (x0, y0) = curve[index - 1];
+                let (x1, y1) = curve[index];
+                let t = ((x - x0) / (x1 - x0)) as f32;
+                y0 + (y1 - y0) * t
+            }
+        };
+        Difficulty(value.max(1.0))
+    }
+
+    /// Multiplier applied to the strength of hostile NPCs
+    pub fn npc_strength(&self) -> f32 {
+        self.0
+    }
+
+    /// Seconds between two raids, for raids every `base_secs` at difficulty 1
+    pub fn raid_interval(&self, base_secs: u64) -> f64 {
+        base_secs as f64 / self.0 as f64
+    }
+
+    /// Hostiles a raid sends after each player, in the given season. There is always at least one.
+    pub fn raiders(&self, season: Season) -> usize {
+        let weight = season.npc_spawn_weight(&RAID_SEASONS);
+        ((2.0 * self.0).ceil() * weight).ceil().max(1.0) as usize
+    }
+
+    /// Multiplier applied to what resource nodes yield: harder worlds are poorer
+    pub fn resource_richness(&self) -> f32 {
+        1.0 / self.0.sqrt()
+    }
+}
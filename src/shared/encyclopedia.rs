@@ -0,0 +1,152 @@
+use crate::shared::items::ItemKind;
+use crate::shared::recipes::recipe_for;
+use crate::shared::skills::Skill;
+use crate::shared::world_generation::{biome_contents, BiomeType, ResourceType};
+
+/// What an encyclopedia entry describes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EntryCategory {
+    Item,
+    Resource,
+    Biome,
+}
+
+/// A page of the in-game encyclopedia
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub category: EntryCategory,
+    pub title: String,
+    pub text: String,
+}
+
+fn names<T: std::fmt::Debug>(values: impl IntoIterator<Item = T>) -> String {
+    let names: Vec<String> = values
+        .into_iter()
+        .map(|value| format!("{:?}", value))
+        .collect();
+    if names.is_empty() {
+        "nowhere".to_string()
+    } else {
+        names.join(", ")
+    }
+}
+
+/// Every encyclopedia entry, built from the item, resource and world generation tables so that it
+/// never disagrees with the game
+pub fn catalog() -> Vec<Entry> {
+    let biomes: Vec<_> = BiomeType::ALL
+        .into_iter()
+        .map(|biome| (biome, biome_contents(biome)))
+        .collect();
+    let found_in = |resource: ResourceType| {
+        names(
+            biomes
+                .iter()
+                .filter(|(_, (_, resources))| resources.contains(&resource))
+                .map(|(biome, _)| *biome),
+        )
+    };
+
+    let mut entries = Vec::new();
+    for kind in ItemKind::ALL {
+        let food = kind.food_value().map_or(String::new(), |value| {
+            format!(" Restores {} hunger.", value)
+        });
+        let text = if kind.is_tool() {
+            let harvests = ResourceType::NODES
+                .into_iter()
+                .filter(|resource| ItemKind::tool_for(*resource) == Some(kind));
+            format!(
+                "A tool. Hold it to harvest: {}. Part of the starter kit.",
+                names(harvests)
+            )
+        } else if let Some(recipe) = recipe_for(kind) {
+            let inputs: Vec<String> = recipe
+                .inputs
+                .iter()
+                .map(|(input, count)| format!("{} {:?}", count, input))
+                .collect();
+            format!(
+                "Made by {:?} at a {:?} from: {}.{}",
+                recipe.category,
+                recipe.category.station(),
+                inputs.join(", "),
+                food
+            )
+        } else if let Some(gathered) = kind.gathered_from() {
+            format!("A material. Gathered from {}.{}", gathered, food)
+        } else {
+            let sources: Vec<_> = ResourceType::NODES
+                .into_iter()
+                .filter(|resource| ItemKind::from_resource(*resource) == Some(kind))
+                .collect();
+            let biomes = sources
+                .iter()
+                .map(|resource| found_in(*resource))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!(
+                "A material. Harvested from: {}. Found in: {}.{}",
+                names(sources),
+                biomes,
+                food
+            )
+        };
+        entries.push(Entry {
+            category: EntryCategory::Item,
+            title: format!("{:?}", kind),
+            text,
+        });
+    }
+    for resource in ResourceType::NODES {
+        let tool = ItemKind::tool_for(resource)
+            .map_or("no tool".to_string(), |tool| format!("a {:?}", tool));
+        let skill = Skill::for_resource(resource)
+            .map_or(String::new(), |skill| format!(" Trains {:?}.", skill));
+        entries.push(Entry {
+            category: EntryCategory::Resource,
+            title: format!("{:?}", resource),
+            text: format!(
+                "Takes {} hit(s) with {} and drops {}.{} Can be seen at: {}. Found in: {}.",
+                resource.max_health(),
+                tool,
+                names(ItemKind::from_resource(resource)),
+                skill,
+                names(resource.schedule().iter()),
+                found_in(resource)
+            ),
+        });
+    }
+    for (biome, (tile_types, resources)) in biomes.iter() {
+        entries.push(Entry {
+            category: EntryCategory::Biome,
+            title: format!("{:?}", biome),
+            text: format!(
+                "A biome. Ground: {}. Resources: {}.",
+                names(tile_types.iter()),
+                names(resources.iter())
+            ),
+        });
+    }
+    entries
+}
+
+/// Entries mentioning all the words of `query`, ignoring case. Entries whose title matches come
+/// first; an empty query matches everything.
+pub fn search<'a>(entries: &'a [Entry], query: &str) -> Vec<&'a Entry> {
+    let words: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+    let mut matches: Vec<(bool, &Entry)> = entries
+        .iter()
+        .filter_map(|entry| {
+            let title = entry.title.to_lowercase();
+            let text = entry.text.to_lowercase();
+            words
+                .iter()
+                .all(|word| title.contains(word) || text.contains(word))
+                .then(|| (words.iter().any(|word| title.contains(word)), entry))
+        })
+        .collect();
+    // stable, so entries keep their catalog order within each group
+    matches.sort_by_key(|(in_title, _)| !in_title);
+    matches.into_iter().map(|(_, entry)| entry).collect()
+}
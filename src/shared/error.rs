@@ -0,0 +1,144 @@
+use bevy::prelude::*;
+use std::collections::HashMap;
+use std::fmt;
+use thiserror::Error;
+
+use crate::shared::world_generation::ChunkCoord;
+
+/// Errors raised by the game itself. Functions return them; systems that have no caller to
+/// propagate to send them as a `ReportError` event, so that every failure is logged and counted.
+#[derive(Error, Debug)]
+pub enum GameError {
+    #[error("failed to encode chunk {coord:?}: {source}")]
+    ChunkEncode {
+        coord: ChunkCoord,
+        #[source]
+        source: bincode::Error,
+    },
+    #[error("failed to decode chunk: {0}")]
+    ChunkDecode(#[from] bincode::Error),
+    #[error("chunk {0:?} is malformed")]
+    MalformedChunk(ChunkCoord),
+    #[error("chunk {coord:?} is {actual} tiles wide, expected {expected}")]
+    ChunkSize {
+        coord: ChunkCoord,
+        expected: usize,
+        actual: usize,
+    },
+    #[error("storage failed: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid player profile: {0}")]
+    Profile(String),
+    #[error("invalid filter list {path}: {reason}")]
+    FilterList { path: String, reason: String },
+    #[error("invalid skill curve {path}: {reason}")]
+    SkillCurve { path: String, reason: String },
+    #[error("invalid achievement list {path}: {reason}")]
+    AchievementList { path: String, reason: String },
+    #[error("invalid moderation queue: {0}")]
+    Moderation(String),
+    #[error("invalid shard state: {0}")]
+    Shard(String),
+    #[error("database failed: {0}")]
+    Database(String),
+    #[error("cloud sync failed: {0}")]
+    CloudSync(String),
+    #[error("invalid terrain image {path}: {reason}")]
+    TerrainImport { path: String, reason: String },
+    #[error("failed to send {message}: {reason}")]
+    Send {
+        message: &'static str,
+        reason: String,
+    },
+    #[error("invalid world history: {0}")]
+    History(String),
+    #[error("telemetry failed: {0}")]
+    Telemetry(String),
+    #[error("invalid world rules: {0}")]
+    Rules(String),
+    #[error("unsupported chunk data: {0}")]
+    ChunkFormat(String),
+    #[error("invalid resource pack: {0}")]
+    ResourcePack(String),
+    #[error("time-lapse failed: {0}")]
+    Timelapse(String),
+}
+
+impl GameError {
+    pub fn send(message: &'static str, error: impl fmt::Debug) -> Self {
+        GameError::Send {
+            message,
+            reason: format!("{:?}", error),
+        }
+    }
+
+    /// Short name of the kind of error, used to count failures
+    pub fn kind(&self) -> &'static str {
+        match self {
+            GameError::ChunkEncode { .. } => "chunk_encode",
+            GameError::ChunkDecode(_) => "chunk_decode",
+            GameError::MalformedChunk(_) => "malformed_chunk",
+            GameError::ChunkSize { .. } => "chunk_size",
+            GameError::Io(_) => "io",
+            GameError::Profile(_) => "profile",
+            GameError::FilterList { .. } => "filter_list",
+            GameError::SkillCurve { .. } => "skill_curve",
+            GameError::AchievementList { .. } => "achievement_list",
+            GameError::Moderation(_) => "moderation",
+            GameError::Shard(_) => "shard",
+            GameError::Database(_) => "database",
+            GameError::CloudSync(_) => "cloud_sync",
+            GameError::TerrainImport { .. } => "terrain_import",
+            GameError::Send { .. } => "send",
+            GameError::History(_) => "history",
+            GameError::Telemetry(_) => "telemetry",
+            GameError::Rules(_) => "rules",
+            GameError::ChunkFormat(_) => "chunk_format",
+            GameError::ResourcePack(_) => "resource_pack",
+            GameError::Timelapse(_) => "timelapse",
+        }
+    }
+}
+
+/// Report an error that can't be propagated any further
+#[derive(Event, Debug)]
+pub struct ReportError(pub GameError);
+
+/// Number of errors reported since startup, per kind
+#[derive(Resource, Default, Debug)]
+pub struct ErrorStats {
+    counts: HashMap<&'static str, u64>,
+}
+
+impl ErrorStats {
+    pub fn count(&self, kind: &str) -> u64 {
+        self.counts.get(kind).copied().unwrap_or(0)
+    }
+
+    pub fn total(&self) -> u64 {
+        self.counts.values().sum()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, u64)> + '_ {
+        self.counts.iter().map(|(kind, count)| (*kind, *count))
+    }
+}
+
+#[derive(Clone)]
+pub struct ErrorsPlugin;
+
+impl Plugin for ErrorsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ReportError>()
+            .init_resource::<ErrorStats>()
+            .add_systems(Last, report_errors);
+    }
+}
+
+// Log every reported error and count it
+fn report_errors(mut errors: EventReader<ReportError>, mut stats: ResMut<ErrorStats>) {
+    for ReportError(error) in errors.read() {
+        error!("{}", error);
+        *stats.counts.entry(error.kind()).or_default() += 1;
+    }
+}
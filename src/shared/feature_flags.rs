@@ -0,0 +1,115 @@
+//! Gameplay features a server can turn on or off from its settings, so that one binary can host
+//! worlds with different rules. The server sends its flags to every client as it connects.
+//! Rulesets are named presets of flags, picked when a world is created.
+use bevy::prelude::*;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// Rules of the server. Clients hold the defaults until the server's flags arrive.
+#[derive(Resource, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct ServerFeatureFlags {
+    /// Players logging out shortly after taking damage leave their body behind for a while
+    pub pvp: bool,
+    /// Bandits raid the players every so often, as set by the world's difficulty
+    pub raids: bool,
+    /// Hunger drains over time, and starving hurts
+    pub hunger: bool,
+    /// Players can claim chunks so that only they can modify them
+    pub claims: bool,
+    /// Building costs nothing and players can't drown or starve
+    pub creative: bool,
+    /// Players who die don't respawn: they can only watch the world from then on
+    pub permadeath: bool,
+}
+
+impl Default for ServerFeatureFlags {
+    fn default() -> Self {
+        Self {
+            pvp: false,
+            raids: true,
+            hunger: true,
+            claims: true,
+            creative: false,
+            permadeath: false,
+        }
+    }
+}
+
+impl ServerFeatureFlags {
+    /// Names of the flags that are on
+    pub fn enabled(&self) -> Vec<&'static str> {
+        [
+            ("pvp", self.pvp),
+            ("raids", self.raids),
+            ("hunger", self.hunger),
+            ("claims", self.claims),
+            ("creative", self.creative),
+            ("permadeath", self.permadeath),
+        ]
+        .into_iter()
+        .filter_map(|(name, on)| on.then_some(name))
+        .collect()
+    }
+}
+
+/// Named presets of feature flags
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ruleset {
+    /// No raids, hunger or PvP
+    Peaceful,
+    /// The default rules
+    Survival,
+    /// Survival with PvP, where death is final
+    Hardcore,
+    /// Free building, no raids and no way to die
+    Creative,
+}
+
+impl Ruleset {
+    pub const ALL: [Ruleset; 4] = [
+        Ruleset::Peaceful,
+        Ruleset::Survival,
+        Ruleset::Hardcore,
+        Ruleset::Creative,
+    ];
+
+    pub fn flags(self) -> ServerFeatureFlags {
+        let survival = ServerFeatureFlags::default();
+        match self {
+            Ruleset::Peaceful => ServerFeatureFlags {
+                raids: false,
+                hunger: false,
+                ..survival
+            },
+            Ruleset::Survival => survival,
+            Ruleset::Hardcore => ServerFeatureFlags {
+                pvp: true,
+                permadeath: true,
+                ..survival
+            },
+            Ruleset::Creative => ServerFeatureFlags {
+                raids: false,
+                hunger: false,
+                creative: true,
+                ..survival
+            },
+        }
+    }
+
+    /// The preset the flags come from, if they weren't customized
+    pub fn matching(flags: &ServerFeatureFlags) -> Option<Ruleset> {
+        Ruleset::ALL
+            .into_iter()
+            .find(|ruleset| ruleset.flags() == *flags)
+    }
+}
+
+#[derive(Clone)]
+pub struct FeatureFlagsPlugin;
+
+impl Plugin for FeatureFlagsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ServerFeatureFlags>();
+    }
+}
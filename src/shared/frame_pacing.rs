@@ -0,0 +1,92 @@
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+/// How long frames may take before the app protects itself
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrameBudget {
+    /// Frames slower than this, in milliseconds, count as over budget
+    pub frame_ms: f32,
+    /// Most fixed updates run in a single frame to catch up after a hitch. The time beyond it is
+    /// dropped, so the simulation slows down instead of spiralling into ever longer frames.
+    pub max_catch_up_steps: u32,
+    /// Consecutive frames over budget after which non-essential systems are paused
+    pub degrade_after: u32,
+    /// Consecutive frames within budget after which they run again
+    pub recover_after: u32,
+    /// Time, in milliseconds, each frame may spend bringing chunks generated in the background
+    /// into the world
+    pub chunk_generation_ms: f32,
+}
+
+impl Default for FrameBudget {
+    fn default() -> Self {
+        Self {
+            frame_ms: 50.0,
+            max_catch_up_steps: 4,
+            degrade_after: 30,
+            recover_after: 120,
+            chunk_generation_ms: 4.0,
+        }
+    }
+}
+
+/// Whether the app is keeping up with its frame budget
+#[derive(Resource, Debug, Default)]
+pub struct FrameLoad {
+    pub budget: FrameBudget,
+    pub degraded: bool,
+    // exponential moving average of the frame time, in milliseconds
+    pub average_ms: f32,
+    over_budget: u32,
+    within_budget: u32,
+}
+
+/// Run condition for systems that can be skipped while frames are over budget: debug logging,
+/// background work, anything that can catch up later
+pub fn not_degraded(load: Option<Res<FrameLoad>>) -> bool {
+    load.is_none_or(|load| !load.degraded)
+}
+
+// Plugin capping the fixed update catch-up and pausing non-essential systems when frames run long
+#[derive(Clone)]
+pub struct FramePacingPlugin(pub FrameBudget);
+
+impl Plugin for FramePacingPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(FrameLoad {
+            budget: self.0,
+            ..default()
+        })
+        .add_systems(Startup, cap_catch_up)
+        .add_systems(Last, measure_frame_load);
+    }
+}
+
+// Fixed updates run while there is accumulated virtual time, so capping how much virtual time a
+// frame can add caps the number of catch-up steps
+fn cap_catch_up(load: Res<FrameLoad>, fixed: Res<Time<Fixed>>, mut time: ResMut<Time<Virtual>>) {
+    let max_delta = fixed.timestep() * load.budget.max_catch_up_steps.max(1);
+    time.set_max_delta(max_delta.max(Duration::from_millis(1)));
+}
+
+fn measure_frame_load(time: Res<Time<Real>>, mut load: ResMut<FrameLoad>) {
+    let frame_ms = time.delta_secs() * 1000.0;
+    load.average_ms = load.average_ms * 0.9 + frame_ms * 0.1;
+    if frame_ms > load.budget.frame_ms {
+        load.over_budget += 1;
+        load.within_budget = 0;
+    } else {
+        load.within_budget += 1;
+        load.over_budget = 0;
+    }
+    if !load.degraded && load.over_budget >= load.budget.degrade_after {
+        load.degraded = true;
+        warn!(
+            "Frames over the {:.0}ms budget (average {:.1}ms), pausing non-essential systems",
+            load.budget.frame_ms, load.average_ms
+        );
+    } else if load.degraded && load.within_budget >= load.budget.recover_after {
+        load.degraded = false;
+        info!("Frames back within budget, resuming non-essential systems");
+    }
+}
@@ -0,0 +1,20 @@
+use bevy::prelude::*;
+use lightyear::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::shared::items::ItemStack;
+
+/// What a player carried when they died, left on the tile they died on until it is emptied.
+/// Saved with the chunk it lies in.
+#[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Grave {
+    pub owner: ClientId,
+    pub owner_name: String,
+    pub tile: (i32, i32), // World tile coordinates
+    pub items: Vec<ItemStack>,
+}
+
+/// Tiles of the graves a player left and hasn't emptied yet, for their map and compass. Saved in
+/// their profile.
+#[derive(Component, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct GraveSites(pub Vec<(i32, i32)>);
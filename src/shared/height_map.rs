@@ -0,0 +1,54 @@
+use bevy::prelude::*;
+use noise::Perlin;
+
+use crate::shared::world_generation::{noise_height, Chunk, WorldConfig, WorldState};
+
+/// Terrain height at arbitrary world positions, without generating the chunk.
+/// Loaded chunks are used when available, since their tiles carry terrain modifications.
+#[derive(Resource)]
+pub struct HeightMap {
+    config: WorldConfig,
+    perlin: Perlin,
+}
+
+impl FromWorld for HeightMap {
+    fn from_world(world: &mut World) -> Self {
+        let config = world
+            .get_resource::<WorldConfig>()
+            .cloned()
+            .unwrap_or_default();
+        HeightMap::new(config)
+    }
+}
+
+impl HeightMap {
+    pub fn new(config: WorldConfig) -> Self {
+        Self {
+            perlin: Perlin::new(config.seed),
+            config,
+        }
+    }
+
+    /// Generated height of a world tile, straight from the noise
+    pub fn get_height(&self, world_x: i32, world_y: i32) -> f32 {
+        noise_height(&self.perlin, world_x, world_y, &self.config)
+    }
+
+    /// Height of a world tile, read from its chunk if loaded and from the noise otherwise
+    pub fn height_at(
+        &self,
+        world_x: i32,
+        world_y: i32,
+        world_state: &WorldState,
+        chunks: &Query<&Chunk>,
+    ) -> f32 {
+        let coord = self.config.grid().tile_to_chunk((world_x, world_y));
+        let (local_x, local_y) = self.config.grid().tile_to_local((world_x, world_y));
+        world_state
+            .chunks
+            .get(&coord)
+            .and_then(|entity| chunks.get(*entity).ok())
+            .and_then(|chunk| Some(chunk.get_tile(local_x, local_y)?.height))
+            .unwrap_or_else(|| self.get_height(world_x, world_y))
+    }
+}
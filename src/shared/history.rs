@@ -0,0 +1,79 @@
+//! History of the world: milestones reached by the players of a server, kept for as long as the
+//! world lives. The server records them; clients get the whole log when they join and every new
+//! entry as it happens.
+use serde::{Deserialize, Serialize};
+
+use crate::shared::day_night::DAY_LENGTH;
+use crate::shared::world_generation::{BiomeType, Decoration};
+
+/// Something worth remembering that happened in the world
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Milestone {
+    /// A player set foot in a biome nobody had reached before
+    BiomeReached(BiomeType),
+    /// A player built the first structure of a kind
+    StructureBuilt(Decoration),
+    RaidSurvived,
+}
+
+/// A milestone, when it happened and the players behind it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct HistoryEntry {
+    pub world_time: f64,
+    pub milestone: Milestone,
+    pub players: Vec<String>,
+}
+
+impl HistoryEntry {
+    /// Day of the world the entry happened on, counting from 1
+    pub fn day(&self) -> u64 {
+        (self.world_time / DAY_LENGTH) as u64 + 1
+    }
+
+    pub fn describe(&self) -> String {
+        let who = if self.players.is_empty() {
+            "Someone".to_string()
+        } else {
+            self.players.join(", ")
+        };
+        match self.milestone {
+            Milestone::BiomeReached(biome) => format!("{} first reached the {:?}", who, biome),
+            Milestone::StructureBuilt(decoration) => {
+                format!("{} built the first {}", who, structure_name(decoration))
+            }
+            Milestone::RaidSurvived if self.players.is_empty() => {
+                "Nobody survived a raid".to_string()
+            }
+            Milestone::RaidSurvived => format!("{} survived a raid", who),
+        }
+    }
+}
+
+/// Name of a kind of structure, whatever state it is in
+pub fn structure_name(decoration: Decoration) -> &'static str {
+    match decoration {
+        Decoration::Stairs => "stairs",
+        Decoration::Furnace => "furnace",
+        Decoration::Door { .. } => "door",
+        Decoration::Gate { .. } => "gate",
+        Decoration::Rail => "rail",
+        Decoration::Chest => "chest",
+        Decoration::Inserter { .. } => "inserter",
+        Decoration::Generator => "generator",
+        Decoration::Wire => "wire",
+        Decoration::Assembler => "assembler",
+        _ => "structure",
+    }
+}
+
+/// The whole history of the world, sent to players when they join
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct WorldHistory {
+    pub entries: Vec<HistoryEntry>,
+}
+
+/// Sent to everyone when a new entry is recorded
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct HistoryRecorded {
+    pub entry: HistoryEntry,
+}
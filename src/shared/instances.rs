@@ -0,0 +1,145 @@
+use bevy::prelude::*;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::shared::world_generation::{
+    is_traversable, BiomeType, Chunk, ChunkCoord, Decoration, Overlay, ResourceType, Tile,
+    TileType, WorldGrid,
+};
+
+// Rooms carved into a dungeon layout
+const DUNGEON_ROOMS: usize = 5;
+// Smallest and largest room side, in tiles
+const ROOM_SIZE: (usize, usize) = (4, 9);
+// Loot stacks and NPCs placed in every room but the first one
+const LOOT_PER_ROOM: usize = 1;
+const NPCS_PER_ROOM: usize = 1;
+
+/// The instance an entity lives in. Entities without it live in the overworld.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct InstanceId(pub u32);
+
+/// A dungeon door, replicated so that clients can show and target it
+#[derive(Component, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum DungeonDoor {
+    // In the overworld: enters a new instance of the dungeon
+    Entrance { tile: (i32, i32) },
+    // In an instance: returns to where the player entered
+    Exit { tile: (i32, i32) },
+}
+
+impl DungeonDoor {
+    pub fn tile(&self) -> (i32, i32) {
+        match self {
+            DungeonDoor::Entrance { tile } | DungeonDoor::Exit { tile } => *tile,
+        }
+    }
+}
+
+/// The generated contents of a dungeon instance. Instances are a single chunk at the origin,
+/// so tile positions are the same as in chunk (0, 0) of the overworld.
+#[derive(Clone, Debug)]
+pub struct DungeonLayout {
+    pub chunk: Chunk,
+    pub spawn: Vec2,
+    pub exit: (i32, i32),
+    pub loot: Vec<(i32, i32)>,
+    pub npcs: Vec<Vec2>,
+}
+
+fn dungeon_tile(position: (i32, i32), tile_type: TileType) -> Tile {
+    Tile {
+        tile_type,
+        decoration: Decoration::None,
+        resource: ResourceType::None,
+        overlay: Overlay::None,
+        height: if tile_type == TileType::Mountain {
+            1.0
+        } else {
+            0.3
+        },
+        position,
+        traversable: is_traversable(tile_type, ResourceType::None),
+        damage: 0,
+    }
+}
+
+/// Carve a dungeon out of solid rock: rooms joined by corridors, in the order they were carved.
+/// Deterministic for a given seed, so a dungeon always has the same layout.
+pub fn generate_dungeon(seed: u64, chunk_size: usize) -> DungeonLayout {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let size = chunk_size as i32;
+    let mut tiles: Vec<Tile> = (0..size * size)
+        .map(|index| dungeon_tile((index % size, index / size), TileType::Mountain))
+        .collect();
+    let mut carve = |x: i32, y: i32| {
+        // the border always stays solid
+        if x > 0 && y > 0 && x < size - 1 && y < size - 1 {
+            tiles[(y * size + x) as usize] = dungeon_tile((x, y), TileType::Stone);
+        }
+    };
+
+    // rooms may overlap, which only makes them more interesting
+    let max_side = ROOM_SIZE.1.min(chunk_size.saturating_sub(3)).max(1);
+    let min_side = ROOM_SIZE.0.min(max_side);
+    let mut centers = Vec::new();
+    for _ in 0..DUNGEON_ROOMS {
+        let width = rng.random_range(min_side..=max_side) as i32;
+        let height = rng.random_range(min_side..=max_side) as i32;
+        let x0 = rng.random_range(1..=(size - 1 - width).max(1));
+        let y0 = rng.random_range(1..=(size - 1 - height).max(1));
+        for y in y0..y0 + height {
+            for x in x0..x0 + width {
+                carve(x, y);
+            }
+        }
+        centers.push((x0 + width / 2, y0 + height / 2));
+    }
+    for pair in centers.windows(2) {
+        let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+        for x in x0.min(x1)..=x0.max(x1) {
+            carve(x, y0);
+        }
+        for y in y0.min(y1)..=y0.max(y1) {
+            carve(x1, y);
+        }
+    }
+
+    let spawn = centers[0];
+    let exit = (spawn.0, (spawn.1 - 1).max(1));
+    let rooms = &centers[1..];
+    let loot = rooms
+        .iter()
+        .flat_map(|&(x, y)| (0..LOOT_PER_ROOM as i32).map(move |i| (x + i, y + 1)))
+        .collect();
+    let npcs = rooms
+        .iter()
+        .flat_map(|&(x, y)| {
+            (0..NPCS_PER_ROOM as i32).map(move |i| WorldGrid::tile_to_world((x - i, y)))
+        })
+        .collect();
+
+    DungeonLayout {
+        chunk: Chunk::new(
+            ChunkCoord { x: 0, y: 0 },
+            chunk_size,
+            tiles,
+            BiomeType::Mountain,
+            0.0,
+        ),
+        spawn: WorldGrid::tile_to_world(spawn),
+        exit,
+        loot,
+        npcs,
+    }
+}
+
+/// Sent to a player entering an instance, with the only chunk it has
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct EnterInstance {
+    pub chunk: Chunk,
+}
+
+/// Sent to a player back in the overworld, so they request the chunks around them again
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct LeaveInstance;
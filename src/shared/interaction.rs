@@ -0,0 +1,123 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::shared::day_night::DayPhase;
+use crate::shared::graves::Grave;
+use crate::shared::instances::DungeonDoor;
+use crate::shared::items::DroppedItem;
+use crate::shared::npc::Npc;
+use crate::shared::rails::Cart;
+use crate::shared::world_generation::{Chunk, ResourceType, WorldConfig, WorldGrid, WorldState};
+
+// Maximum distance, in tiles, between a player and what they interact with
+pub const INTERACT_REACH: f32 = 1.5;
+
+/// Something a player can interact with. Targets are identified by their tile, which means the same
+/// thing on the client and on the server.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum InteractionTarget {
+    DroppedItem { tile: (i32, i32) },
+    Grave { tile: (i32, i32) },
+    Npc { tile: (i32, i32) },
+    Door { tile: (i32, i32) },
+    Resource { tile: (i32, i32) },
+    // A door or gate built by a player, opened or closed on interaction, or a chest or furnace,
+    // emptied into the inventory
+    Structure { tile: (i32, i32) },
+    // A cart standing on a rail, boarded on interaction
+    Cart { tile: (i32, i32) },
+}
+
+impl InteractionTarget {
+    pub fn tile(&self) -> (i32, i32) {
+        match self {
+            InteractionTarget::DroppedItem { tile }
+            | InteractionTarget::Grave { tile }
+            | InteractionTarget::Npc { tile }
+            | InteractionTarget::Door { tile }
+            | InteractionTarget::Resource { tile }
+            | InteractionTarget::Structure { tile }
+            | InteractionTarget::Cart { tile } => *tile,
+        }
+    }
+
+    // Targets with a higher priority win over closer targets with a lower one
+    pub fn priority(&self) -> u8 {
+        match self {
+            // picking things up is never destructive, so it goes first
+            InteractionTarget::DroppedItem { .. } | InteractionTarget::Grave { .. } => 4,
+            InteractionTarget::Npc { .. } | InteractionTarget::Cart { .. } => 3,
+            InteractionTarget::Door { .. } | InteractionTarget::Structure { .. } => 2,
+            InteractionTarget::Resource { .. } => 1,
+        }
+    }
+
+    fn distance(&self, position: Vec2) -> f32 {
+        let (x, y) = self.tile();
+        position.distance(WorldGrid::tile_to_world((x, y)))
+    }
+}
+
+/// Every target within reach of `position`
+#[allow(clippy::too_many_arguments)]
+pub fn nearby_targets<'a>(
+    position: Vec2,
+    world_state: &WorldState,
+    world_config: &WorldConfig,
+    chunks: &Query<&Chunk>,
+    items: impl Iterator<Item = &'a DroppedItem>,
+    graves: impl Iterator<Item = &'a Grave>,
+    npcs: impl Iterator<Item = &'a Npc>,
+    doors: impl Iterator<Item = &'a DungeonDoor>,
+    carts: impl Iterator<Item = &'a Cart>,
+) -> Vec<InteractionTarget> {
+    let mut targets: Vec<InteractionTarget> = items
+        .map(|item| InteractionTarget::DroppedItem { tile: item.tile })
+        .chain(graves.map(|grave| InteractionTarget::Grave { tile: grave.tile }))
+        .chain(npcs.map(|npc| InteractionTarget::Npc {
+            tile: WorldGrid::world_to_tile(npc.position),
+        }))
+        .chain(doors.map(|door| InteractionTarget::Door { tile: door.tile() }))
+        .chain(carts.map(|cart| InteractionTarget::Cart { tile: cart.tile }))
+        .collect();
+
+    let phase = DayPhase::at(world_state.world_time);
+    let reach = INTERACT_REACH.ceil() as i32;
+    let center = WorldGrid::world_to_tile(position);
+    for y in center.1 - reach..=center.1 + reach {
+        for x in center.0 - reach..=center.0 + reach {
+            let coord = world_config.grid().tile_to_chunk((x, y));
+            let (local_x, local_y) = world_config.grid().tile_to_local((x, y));
+            let Some(tile) = world_state
+                .chunks
+                .get(&coord)
+                .and_then(|entity| chunks.get(*entity).ok())
+                .and_then(|chunk| chunk.get_tile(local_x, local_y))
+            else {
+                continue;
+            };
+            if tile.resource != ResourceType::None && tile.resource.is_available(phase) {
+                targets.push(InteractionTarget::Resource { tile: (x, y) });
+            }
+            if tile.decoration.is_openable() || tile.decoration.is_container() {
+                targets.push(InteractionTarget::Structure { tile: (x, y) });
+            }
+        }
+    }
+
+    targets.retain(|target| target.distance(position) <= INTERACT_REACH);
+    targets
+}
+
+/// The target a player standing at `position` most likely means: highest priority first, then
+/// the closest one
+pub fn best_target(
+    position: Vec2,
+    targets: impl IntoIterator<Item = InteractionTarget>,
+) -> Option<InteractionTarget> {
+    targets.into_iter().max_by(|a, b| {
+        a.priority()
+            .cmp(&b.priority())
+            .then(b.distance(position).total_cmp(&a.distance(position)))
+    })
+}
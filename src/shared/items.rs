@@ -0,0 +1,203 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::shared::world_generation::ResourceType;
+
+// Items that can be carried, dropped and stacked
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum ItemKind {
+    Wood,
+    Stone,
+    Iron,
+    Copper,
+    Coal,
+    Gold,
+    Fish,
+    Berries,
+    CookedFish,
+    Stew,
+    Axe,
+    Pickaxe,
+    Torch,
+    Water,
+}
+
+// Largest number of items a single dropped stack can hold
+pub const MAX_STACK: u32 = 999;
+
+impl ItemKind {
+    pub const ALL: [ItemKind; 14] = [
+        ItemKind::Wood,
+        ItemKind::Stone,
+        ItemKind::Iron,
+        ItemKind::Copper,
+        ItemKind::Coal,
+        ItemKind::Gold,
+        ItemKind::Fish,
+        ItemKind::Berries,
+        ItemKind::CookedFish,
+        ItemKind::Stew,
+        ItemKind::Axe,
+        ItemKind::Pickaxe,
+        ItemKind::Torch,
+        ItemKind::Water,
+    ];
+
+    // Case-insensitive lookup by name, used by commands
+    pub fn from_name(name: &str) -> Option<ItemKind> {
+        ItemKind::ALL
+            .into_iter()
+            .find(|kind| format!("{:?}", kind).eq_ignore_ascii_case(name))
+    }
+
+    // Item obtained when harvesting a resource
+    pub fn from_resource(resource: ResourceType) -> Option<ItemKind> {
+        match resource {
+            ResourceType::None => None,
+            ResourceType::Tree => Some(ItemKind::Wood),
+            ResourceType::Stone => Some(ItemKind::Stone),
+            ResourceType::Iron => Some(ItemKind::Iron),
+            ResourceType::Copper => Some(ItemKind::Copper),
+            ResourceType::Coal => Some(ItemKind::Coal),
+            ResourceType::Gold => Some(ItemKind::Gold),
+            ResourceType::Fish => Some(ItemKind::Fish),
+        }
+    }
+
+    pub fn is_tool(&self) -> bool {
+        matches!(self, ItemKind::Axe | ItemKind::Pickaxe)
+    }
+
+    /// Hunger the item restores when eaten, if it can be eaten
+    pub fn food_value(&self) -> Option<f32> {
+        match self {
+            ItemKind::Berries => Some(10.0),
+            ItemKind::Fish => Some(15.0),
+            ItemKind::CookedFish => Some(35.0),
+            ItemKind::Stew => Some(60.0),
+            _ => None,
+        }
+    }
+
+    /// Where items that aren't harvested from a resource node come from, if anywhere
+    pub fn gathered_from(&self) -> Option<&'static str> {
+        match self {
+            ItemKind::Berries => Some("felled trees"),
+            ItemKind::Water => Some("drinking next to water"),
+            _ => None,
+        }
+    }
+
+    // Tool that has to be held to harvest a resource, if any
+    pub fn tool_for(resource: ResourceType) -> Option<ItemKind> {
+        match resource {
+            ResourceType::Tree => Some(ItemKind::Axe),
+            ResourceType::Stone
+            | ResourceType::Iron
+            | ResourceType::Copper
+            | ResourceType::Coal
+            | ResourceType::Gold => Some(ItemKind::Pickaxe),
+            ResourceType::Fish | ResourceType::None => None,
+        }
+    }
+}
+
+// A stack of items lying on a tile
+#[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DroppedItem {
+    pub kind: ItemKind,
+    pub count: u32,
+    pub tile: (i32, i32), // World tile coordinates
+    pub dropped_at: f64,  // WorldState::world_time when the item was dropped
+}
+
+// Number of stacks a player inventory can hold
+pub const INVENTORY_SLOTS: usize = 24;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct ItemStack {
+    pub kind: ItemKind,
+    pub count: u32,
+}
+
+/// Items carried by a player. Only the server modifies it; the owning client gets a copy.
+#[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct Inventory {
+    pub slots: Vec<ItemStack>,
+}
+
+impl Inventory {
+    // What a new player starts with
+    pub fn starter_kit() -> Self {
+        Self {
+            slots: vec![
+                ItemStack {
+                    kind: ItemKind::Axe,
+                    count: 1,
+                },
+                ItemStack {
+                    kind: ItemKind::Pickaxe,
+                    count: 1,
+                },
+            ],
+        }
+    }
+
+    /// Add items, filling existing stacks first. Returns how many items didn't fit.
+    pub fn add(&mut self, kind: ItemKind, mut count: u32) -> u32 {
+        for stack in self.slots.iter_mut().filter(|s| s.kind == kind) {
+            let moved = count.min(MAX_STACK - stack.count);
+            stack.count += moved;
+            count -= moved;
+        }
+        while count > 0 && self.slots.len() < INVENTORY_SLOTS {
+            let moved = count.min(MAX_STACK);
+            self.slots.push(ItemStack { kind, count: moved });
+            count -= moved;
+        }
+        count
+    }
+
+    /// Remove items if the inventory holds enough of them
+    pub fn remove(&mut self, kind: ItemKind, count: u32) -> bool {
+        if self.count(kind) < count {
+            return false;
+        }
+        let mut remaining = count;
+        for stack in self.slots.iter_mut().filter(|s| s.kind == kind).rev() {
+            let moved = remaining.min(stack.count);
+            stack.count -= moved;
+            remaining -= moved;
+        }
+        self.slots.retain(|s| s.count > 0);
+        true
+    }
+
+    pub fn count(&self, kind: ItemKind) -> u32 {
+        self.slots
+            .iter()
+            .filter(|s| s.kind == kind)
+            .map(|s| s.count)
+            .sum()
+    }
+}
+
+// Number of hotbar slots; they are bound to the first inventory slots
+pub const HOTBAR_SLOTS: usize = 9;
+
+/// Hotbar slot selected by a player and the item in it. Set by the server and replicated to every
+/// client, so that actions can be validated against the held tool and others can see it.
+#[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct HeldItem {
+    pub slot: usize,
+    pub kind: Option<ItemKind>,
+}
+
+impl HeldItem {
+    pub fn from_inventory(slot: usize, inventory: &Inventory) -> Self {
+        Self {
+            slot,
+            kind: inventory.slots.get(slot).map(|stack| stack.kind),
+        }
+    }
+}
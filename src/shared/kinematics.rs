@@ -0,0 +1,85 @@
+//! Minimal kinematics shared by the server and the predicted client. Bodies with a velocity drift
+//! with it every tick and slow down with friction; cliffs stop them the same way they stop walking
+//! players. Knockback is an impulse added to a velocity, and projectiles are bodies that drift
+//! with little friction.
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::protocol::PlayerPosition;
+use crate::shared::movement::{crosses_cliff, TileSource};
+
+/// Fraction of its velocity a body on the ground keeps from one tick to the next
+pub const GROUND_FRICTION: f32 = 0.8;
+/// Speed given to a body struck in combat, in world units per tick
+pub const KNOCKBACK: f32 = 0.4;
+// Speed under which a body stops, in world units per tick
+const REST_SPEED: f32 = 0.05;
+
+/// Velocity of a body, in world units per tick, on top of whatever moves it on purpose (inputs,
+/// paths)
+#[derive(Component, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct Velocity(pub Vec2);
+
+impl Velocity {
+    pub fn is_resting(&self) -> bool {
+        self.0.length() < REST_SPEED
+    }
+
+    /// Push a body at `at` away from `from`, e.g. an attacker. A body standing right on `from`
+    /// isn't pushed anywhere.
+    pub fn knockback(&mut self, from: Vec2, at: Vec2, strength: f32) {
+        self.0 += (at - from).normalize_or_zero() * strength;
+    }
+}
+
+/// Move by `delta` one axis at a time, so that moving diagonally into a cliff slides along it.
+/// Returns which axes were blocked.
+pub fn slide(position: &mut Vec2, delta: Vec2, tiles: &impl TileSource) -> BVec2 {
+    let mut blocked = BVec2::FALSE;
+    for (axis, step) in [Vec2::new(delta.x, 0.0), Vec2::new(0.0, delta.y)]
+        .into_iter()
+        .enumerate()
+    {
+        if step == Vec2::ZERO {
+            continue;
+        }
+        if crosses_cliff(*position, *position + step, tiles) {
+            blocked.set(axis, true);
+        } else {
+            *position += step;
+        }
+    }
+    blocked
+}
+
+/// One tick of drift. Blocked axes lose their speed, the rest is slowed down by `friction`.
+/// Returns which axes were blocked.
+pub fn step_velocity(
+    position: &mut Vec2,
+    velocity: &mut Velocity,
+    friction: f32,
+    tiles: &impl TileSource,
+) -> BVec2 {
+    if velocity.0 == Vec2::ZERO {
+        return BVec2::FALSE;
+    }
+    let blocked = slide(position, velocity.0, tiles);
+    velocity.0 = Vec2::select(blocked, Vec2::ZERO, velocity.0 * friction);
+    if velocity.is_resting() {
+        velocity.0 = Vec2::ZERO;
+    }
+    blocked
+}
+
+/// Drift of a player, after their input was applied for the tick
+pub(crate) fn shared_drift_behaviour(
+    mut position: Mut<PlayerPosition>,
+    mut velocity: Mut<Velocity>,
+    tiles: &impl TileSource,
+) {
+    // don't touch the components of players standing still, so that they aren't sent again
+    if velocity.0 == Vec2::ZERO {
+        return;
+    }
+    step_velocity(&mut position.0, &mut velocity, GROUND_FRICTION, tiles);
+}
@@ -0,0 +1,66 @@
+//! Line of sight over the tile grid, shared by the server and the client. A ray walks the tiles
+//! between two points (Bresenham), and stops at the first one that blocks it: opaque tiles for
+//! sight, untraversable ones for anything flying low, like thrown stones.
+use bevy::prelude::*;
+
+use crate::shared::movement::TileSource;
+use crate::shared::world_generation::{Tile, WorldGrid};
+
+/// Distance, in tiles, players and NPCs see at most
+pub const SIGHT_RANGE: f32 = 12.0;
+
+/// Tiles on the straight line from `from` to `to`, both included, each one a step away from the
+/// previous one
+pub fn tile_line(from: (i32, i32), to: (i32, i32)) -> impl Iterator<Item = (i32, i32)> {
+    let (dx, dy) = ((to.0 - from.0).abs(), -(to.1 - from.1).abs());
+    let (sx, sy) = ((to.0 - from.0).signum(), (to.1 - from.1).signum());
+    let mut current = Some(from);
+    let mut error = dx + dy;
+    std::iter::from_fn(move || {
+        let tile = current?;
+        current = (tile != to).then(|| {
+            let (mut x, mut y) = tile;
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                x += sx;
+            }
+            if doubled <= dx {
+                error += dx;
+                y += sy;
+            }
+            (x, y)
+        });
+        Some(tile)
+    })
+}
+
+// First tile of a ray from `from` to `to` that `blocks`, leaving out the tile it starts from
+fn cast(from: Vec2, to: Vec2, blocks: impl Fn((i32, i32)) -> bool) -> Option<(i32, i32)> {
+    tile_line(WorldGrid::world_to_tile(from), WorldGrid::world_to_tile(to))
+        .skip(1)
+        .find(|tile| blocks(*tile))
+}
+
+/// First tile blocking a ray from `from` to `to`, leaving out the tile the ray starts from.
+/// Tiles that aren't loaded never block.
+pub fn first_blocking(
+    from: Vec2,
+    to: Vec2,
+    tiles: &impl TileSource,
+    blocks: impl Fn(&Tile) -> bool,
+) -> Option<(i32, i32)> {
+    cast(from, to, |tile| tiles.tile(tile).is_some_and(&blocks))
+}
+
+/// Whether `to` can be seen from `from`. The tile seen is visible even when it's opaque itself,
+/// e.g. the edge of a forest, but nothing behind it is.
+pub fn has_line_of_sight(from: Vec2, to: Vec2, tiles: &impl TileSource) -> bool {
+    cast(from, to, |tile| tiles.is_opaque(tile))
+        .is_none_or(|tile| tile == WorldGrid::world_to_tile(to))
+}
+
+/// Whether `to` is within sight range of `from`, with nothing in the way
+pub fn can_see(from: Vec2, to: Vec2, tiles: &impl TileSource) -> bool {
+    from.distance(to) <= SIGHT_RANGE * WorldGrid::TILE_SIZE && has_line_of_sight(from, to, tiles)
+}
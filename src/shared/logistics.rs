@@ -0,0 +1,121 @@
+//! Containers players store items in, and the inserters moving items from one container to
+//! another. Only the server knows what containers hold; clients are told when items move, so they
+//! can draw them on their way.
+use serde::{Deserialize, Serialize};
+
+use crate::shared::items::{Inventory, ItemKind};
+use crate::shared::recipes::{Recipe, RECIPES};
+use crate::shared::world_generation::Decoration;
+
+/// What a container is, which decides what inserters put in it and take out of it
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ContainerKind {
+    /// Takes and gives anything
+    Chest,
+    /// Takes what its recipes need, cooks it, and only gives what it cooked
+    Furnace,
+    /// Like a furnace, but runs on power instead of burning coal
+    Assembler,
+}
+
+impl ContainerKind {
+    /// Kind of container a decoration is, if any
+    pub fn of(decoration: Decoration) -> Option<ContainerKind> {
+        match decoration {
+            Decoration::Chest => Some(ContainerKind::Chest),
+            Decoration::Furnace => Some(ContainerKind::Furnace),
+            Decoration::Assembler => Some(ContainerKind::Assembler),
+            _ => None,
+        }
+    }
+
+    /// Whether inserters may put items of `kind` in the container
+    pub fn accepts(&self, kind: ItemKind) -> bool {
+        match self {
+            ContainerKind::Chest => true,
+            ContainerKind::Furnace | ContainerKind::Assembler => RECIPES
+                .iter()
+                .any(|recipe| self.inputs(recipe).any(|(input, _)| *input == kind)),
+        }
+    }
+
+    /// Whether inserters may take items of `kind` out of the container
+    pub fn gives(&self, kind: ItemKind) -> bool {
+        match self {
+            ContainerKind::Chest => true,
+            ContainerKind::Furnace | ContainerKind::Assembler => {
+                RECIPES.iter().any(|recipe| recipe.output == kind)
+            }
+        }
+    }
+
+    // Inputs of a recipe the container uses up following it
+    fn inputs<'a>(&self, recipe: &'a Recipe) -> impl Iterator<Item = &'a (ItemKind, u32)> {
+        let powered = *self == ContainerKind::Assembler;
+        recipe
+            .inputs
+            .iter()
+            .filter(move |(input, _)| !powered || *input != ItemKind::Coal)
+    }
+}
+
+/// Items held by a chest, a furnace or an assembler. Saved with the chunk it stands in.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Container {
+    pub kind: ContainerKind,
+    pub tile: (i32, i32), // World tile coordinates
+    pub items: Inventory,
+}
+
+impl Container {
+    pub fn new(kind: ContainerKind, tile: (i32, i32)) -> Self {
+        Self {
+            kind,
+            tile,
+            items: Inventory::default(),
+        }
+    }
+
+    /// Follow the first recipe the container holds the inputs of, if it's a furnace, or an
+    /// assembler with `power`, and has room for the output. Returns what was made.
+    pub fn process(&mut self, power: bool) -> Option<ItemKind> {
+        match self.kind {
+            ContainerKind::Chest => return None,
+            ContainerKind::Assembler if !power => return None,
+            ContainerKind::Furnace | ContainerKind::Assembler => {}
+        }
+        RECIPES.iter().find_map(|recipe| {
+            if !self
+                .kind
+                .inputs(recipe)
+                .all(|(input, count)| self.items.count(*input) >= *count)
+            {
+                return None;
+            }
+            let mut after = self.items.clone();
+            for (input, count) in self.kind.inputs(recipe) {
+                after.remove(*input, *count);
+            }
+            if after.add(recipe.output, 1) > 0 {
+                return None;
+            }
+            self.items = after;
+            Some(recipe.output)
+        })
+    }
+}
+
+/// Items an inserter moved from one container to the next
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct ItemMove {
+    pub from: (i32, i32),
+    pub to: (i32, i32),
+    pub kind: ItemKind,
+    pub count: u32,
+}
+
+/// Items moved by the inserters of loaded chunks since the previous message
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ItemsMoved {
+    pub moves: Vec<ItemMove>,
+}
@@ -0,0 +1,91 @@
+//! Top-down images of the world, a pixel per tile or per square of tiles, for looking at the world
+//! from outside of the game
+use image::{Rgb, RgbImage};
+
+use crate::shared::world_generation::{Chunk, ResourceType, Tile, TileType};
+
+// Pixels of the map no chunk was drawn into yet
+const UNSEEN: Rgb<u8> = Rgb([0, 0, 0]);
+
+/// Region of the world drawn into a map image
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MapRegion {
+    /// Tile at the bottom left corner of the image
+    pub origin: (i32, i32),
+    /// Tiles along each side of the square a pixel stands for
+    pub tiles_per_pixel: u32,
+    /// Size of the image, in pixels
+    pub width: u32,
+    pub height: u32,
+}
+
+impl MapRegion {
+    /// Region between two corner tiles, both included
+    pub fn between(from: (i32, i32), to: (i32, i32), tiles_per_pixel: u32) -> Self {
+        let tiles_per_pixel = tiles_per_pixel.max(1);
+        let side = |a: i32, b: i32| (a.abs_diff(b) + 1).div_ceil(tiles_per_pixel);
+        Self {
+            origin: (from.0.min(to.0), from.1.min(to.1)),
+            tiles_per_pixel,
+            width: side(from.0, to.0),
+            height: side(from.1, to.1),
+        }
+    }
+
+    pub fn blank(&self) -> RgbImage {
+        RgbImage::from_pixel(self.width, self.height, UNSEEN)
+    }
+
+    // Pixel showing a tile, if the tile is the one sampled for it. Images go from the top down.
+    fn pixel(&self, (x, y): (i32, i32)) -> Option<(u32, u32)> {
+        let step = self.tiles_per_pixel as i64;
+        let (dx, dy) = (
+            x as i64 - self.origin.0 as i64,
+            y as i64 - self.origin.1 as i64,
+        );
+        if dx < 0 || dy < 0 || dx % step != 0 || dy % step != 0 {
+            return None;
+        }
+        let (px, py) = (dx / step, dy / step);
+        if px >= self.width as i64 || py >= self.height as i64 {
+            return None;
+        }
+        Some((px as u32, self.height - 1 - py as u32))
+    }
+
+    /// Draw the tiles of a chunk that fall in the region
+    pub fn draw_chunk(&self, image: &mut RgbImage, chunk: &Chunk) {
+        for tile in chunk.tiles() {
+            if let Some((px, py)) = self.pixel(tile.position) {
+                image.put_pixel(px, py, tile_color(tile));
+            }
+        }
+    }
+}
+
+/// Color of a tile seen from above: what was built on it, then what grows or lies on it, then
+/// its ground
+pub fn tile_color(tile: &Tile) -> Rgb<u8> {
+    if tile.decoration.is_structure() {
+        return Rgb([200, 60, 40]);
+    }
+    match tile.resource {
+        ResourceType::Tree => return Rgb([20, 90, 30]),
+        ResourceType::Stone => return Rgb([120, 120, 120]),
+        ResourceType::Iron => return Rgb([160, 110, 90]),
+        ResourceType::Copper => return Rgb([190, 110, 50]),
+        ResourceType::Coal => return Rgb([40, 40, 40]),
+        ResourceType::Gold => return Rgb([230, 190, 40]),
+        ResourceType::Fish | ResourceType::None => {}
+    }
+    match tile.tile_type {
+        TileType::Grass => Rgb([90, 160, 60]),
+        TileType::ShallowWater => Rgb([70, 140, 210]),
+        TileType::DeepWater => Rgb([30, 70, 150]),
+        TileType::Sand => Rgb([220, 200, 140]),
+        TileType::Stone => Rgb([140, 140, 130]),
+        TileType::Forest => Rgb([40, 120, 50]),
+        TileType::Mountain => Rgb([100, 90, 80]),
+        TileType::Snow => Rgb([240, 240, 245]),
+    }
+}
@@ -0,0 +1,13 @@
+use serde::{Deserialize, Serialize};
+
+// Chat lines of the reported player a report carries at most
+pub const MAX_EXCERPT_LINES: usize = 5;
+
+/// Report a player to the moderators. `excerpt` holds their recent chat lines, as the reporter
+/// saw them.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ReportPlayer {
+    pub player: String,
+    pub reason: String,
+    pub excerpt: Option<Vec<String>>,
+}
@@ -2,24 +2,116 @@
 //!
 //! The simulation logic (movement, etc.) should be shared between client and server to guarantee that there won't be
 //! mispredictions/rollbacks.
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
 
 use crate::protocol::*;
+use crate::shared::kinematics::slide;
+use crate::shared::world_generation::{Chunk, Tile, WorldConfig, WorldGrid, WorldState};
 
-pub(crate) fn shared_movement_behaviour(mut position: Mut<PlayerPosition>, input: &Inputs) {
-    const MOVE_SPEED: f32 = 10.0;
+// Distance moved per tick in each pressed direction, in world units (see `WorldGrid`)
+pub(crate) const MOVE_SPEED: f32 = 10.0;
+// Spacing of the points checked along a move, in tiles, so that no tile crossed is skipped
+const STEP_SAMPLE: f32 = 0.5;
+
+/// Tiles players move over
+pub trait TileSource {
+    fn tile(&self, position: (i32, i32)) -> Option<&Tile>;
+
+    /// Whether a tile blocks sight. Tiles that aren't loaded don't.
+    fn is_opaque(&self, position: (i32, i32)) -> bool {
+        self.tile(position).is_some_and(Tile::is_opaque)
+    }
+}
+
+/// No terrain at all: moves are never blocked
+impl TileSource for () {
+    fn tile(&self, _position: (i32, i32)) -> Option<&Tile> {
+        None
+    }
+}
+
+/// The tiles of the loaded overworld chunks
+#[derive(SystemParam)]
+pub struct LoadedTiles<'w, 's> {
+    world_state: Res<'w, WorldState>,
+    world_config: Res<'w, WorldConfig>,
+    chunks: Query<'w, 's, &'static Chunk>,
+}
+
+impl TileSource for LoadedTiles<'_, '_> {
+    fn tile(&self, position: (i32, i32)) -> Option<&Tile> {
+        let grid = self.world_config.grid();
+        let entity = self.world_state.chunks.get(&grid.tile_to_chunk(position))?;
+        let (local_x, local_y) = grid.tile_to_local(position);
+        self.chunks.get(*entity).ok()?.get_tile(local_x, local_y)
+    }
+
+    // read from the chunk's baked opacity rather than the tile itself
+    fn is_opaque(&self, position: (i32, i32)) -> bool {
+        let grid = self.world_config.grid();
+        let Some(chunk) = self
+            .world_state
+            .chunks
+            .get(&grid.tile_to_chunk(position))
+            .and_then(|entity| self.chunks.get(*entity).ok())
+        else {
+            return false;
+        };
+        let (local_x, local_y) = grid.tile_to_local(position);
+        chunk.is_opaque(local_x, local_y)
+    }
+}
+
+/// Whether a straight move crosses a cliff without stairs. Tiles that aren't loaded don't block,
+/// so that a slow chunk never freezes a player in place.
+pub fn crosses_cliff(from: Vec2, to: Vec2, tiles: &impl TileSource) -> bool {
+    let steps = (from.distance(to) / STEP_SAMPLE).ceil() as usize;
+    let mut last = WorldGrid::world_to_tile(from);
+    for step in 1..=steps {
+        let tile = WorldGrid::world_to_tile(from.lerp(to, step as f32 / steps as f32));
+        if tile == last {
+            continue;
+        }
+        if let (Some(a), Some(b)) = (tiles.tile(last), tiles.tile(tile)) {
+            if !a.can_step_to(b) {
+                return true;
+            }
+        }
+        last = tile;
+    }
+    false
+}
+
+pub(crate) fn shared_movement_behaviour(
+    mut position: Mut<PlayerPosition>,
+    input: &Inputs,
+    tiles: &impl TileSource,
+) {
+    step_movement(&mut position, input, tiles);
+}
+
+// Apply one tick of input to a position. Also used to replay inputs outside of the ECS.
+// A player pushing diagonally into a cliff slides along it.
+pub(crate) fn step_movement(
+    position: &mut PlayerPosition,
+    input: &Inputs,
+    tiles: &impl TileSource,
+) {
     if let Inputs::Direction(direction) = input {
+        let mut delta = Vec2::ZERO;
         if direction.up {
-            position.y += MOVE_SPEED;
+            delta.y += MOVE_SPEED;
         }
         if direction.down {
-            position.y -= MOVE_SPEED;
+            delta.y -= MOVE_SPEED;
         }
         if direction.left {
-            position.x -= MOVE_SPEED;
+            delta.x -= MOVE_SPEED;
         }
         if direction.right {
-            position.x += MOVE_SPEED;
+            delta.x += MOVE_SPEED;
         }
+        slide(&mut position.0, delta, tiles);
     }
-}
\ No newline at end of file
+}
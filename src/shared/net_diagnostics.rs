@@ -0,0 +1,213 @@
+//! Bookkeeping of the network protocol: which message travels on which channel, and how much
+//! traffic each channel carries.
+//!
+//! Messages are registered with [`RegisterNetMessageExt::register_net_message`], which declares
+//! their channel next to their direction. The resulting [`ProtocolManifest`] is checked at startup,
+//! so a message sent on a channel that was never added (or registered twice) fails loudly instead
+//! of silently never arriving. Received traffic and failed sends are counted per channel in
+//! [`ChannelStats`].
+use bevy::prelude::*;
+use bevy::time::common_conditions::on_timer;
+use bevy::utils::Duration;
+use lightyear::prelude::*;
+use lightyear::prelude::{client, server};
+use serde::Serialize;
+use std::any::type_name;
+use std::collections::{BTreeMap, HashSet};
+
+use crate::shared::error::{GameError, ReportError};
+use crate::shared::frame_pacing::not_degraded;
+
+// How often channel statistics are logged
+const STATS_LOG_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Name of a type without its module path, as used in logs and `GameError::Send`
+fn short_name<T>() -> &'static str {
+    let name = type_name::<T>();
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MessageEntry {
+    pub message: &'static str,
+    pub channel: &'static str,
+    pub direction: ChannelDirection,
+}
+
+/// Every message and channel registered through this module, in registration order
+#[derive(Resource, Default, Debug)]
+pub struct ProtocolManifest {
+    pub messages: Vec<MessageEntry>,
+    pub channels: Vec<&'static str>,
+}
+
+impl ProtocolManifest {
+    pub fn channel_of(&self, message: &str) -> Option<&'static str> {
+        self.messages
+            .iter()
+            .find(|entry| entry.message == message)
+            .map(|entry| entry.channel)
+    }
+
+    /// Problems with the declared protocol, empty if it's consistent
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let mut seen = HashSet::new();
+        for entry in &self.messages {
+            if !seen.insert(entry.message) {
+                problems.push(format!("message {} is registered twice", entry.message));
+            }
+            if !self.channels.contains(&entry.channel) {
+                problems.push(format!(
+                    "message {} is sent on channel {}, which is never added",
+                    entry.message, entry.channel
+                ));
+            }
+        }
+        problems
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ChannelCounters {
+    pub received: u64,
+    /// Approximate: measured with bincode, not with the wire encoding
+    pub bytes_received: u64,
+    /// Sends that failed, as reported through `ReportError`
+    pub dropped: u64,
+}
+
+#[derive(Resource, Default, Debug)]
+pub struct ChannelStats {
+    pub channels: BTreeMap<&'static str, ChannelCounters>,
+}
+
+/// Registers messages together with the channel they're sent on:
+/// `app.register_net_message::<ChatLine, ChatChannel>(ChannelDirection::ServerToClient)`
+pub trait RegisterNetMessageExt {
+    fn add_net_channel<C: Channel>(&mut self, settings: ChannelSettings) -> &mut Self;
+    fn register_net_message<M: Message + Serialize, C: Channel>(
+        &mut self,
+        direction: ChannelDirection,
+    ) -> &mut Self;
+}
+
+impl RegisterNetMessageExt for App {
+    fn add_net_channel<C: Channel>(&mut self, settings: ChannelSettings) -> &mut Self {
+        self.add_channel::<C>(settings);
+        self.init_resource::<ProtocolManifest>();
+        self.world_mut()
+            .resource_mut::<ProtocolManifest>()
+            .channels
+            .push(short_name::<C>());
+        self
+    }
+
+    fn register_net_message<M: Message + Serialize, C: Channel>(
+        &mut self,
+        direction: ChannelDirection,
+    ) -> &mut Self {
+        self.register_message::<M>(direction);
+        self.init_resource::<ProtocolManifest>();
+        self.world_mut()
+            .resource_mut::<ProtocolManifest>()
+            .messages
+            .push(MessageEntry {
+                message: short_name::<M>(),
+                channel: short_name::<C>(),
+                direction,
+            });
+        // only the side(s) receiving the message have its events
+        self.add_systems(
+            Update,
+            (
+                count_client_received::<M, C>
+                    .run_if(resource_exists::<Events<client::MessageEvent<M>>>),
+                count_server_received::<M, C>
+                    .run_if(resource_exists::<Events<server::MessageEvent<M>>>),
+            ),
+        );
+        self
+    }
+}
+
+// Plugin checking the protocol manifest at startup and collecting channel statistics
+#[derive(Clone)]
+pub struct NetDiagnosticsPlugin;
+
+impl Plugin for NetDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ProtocolManifest>()
+            .init_resource::<ChannelStats>()
+            .add_systems(Startup, check_protocol_manifest)
+            .add_systems(
+                Update,
+                (
+                    count_dropped_messages,
+                    log_channel_stats
+                        .run_if(on_timer(STATS_LOG_INTERVAL))
+                        .run_if(not_degraded),
+                ),
+            );
+    }
+}
+
+fn record_received<M: Serialize, C>(stats: &mut ChannelStats, message: &M) {
+    let counters = stats.channels.entry(short_name::<C>()).or_default();
+    counters.received += 1;
+    counters.bytes_received += bincode::serialized_size(message).unwrap_or(0);
+}
+
+fn count_client_received<M: Message + Serialize, C: Channel>(
+    mut events: EventReader<client::MessageEvent<M>>,
+    mut stats: ResMut<ChannelStats>,
+) {
+    for event in events.read() {
+        record_received::<M, C>(&mut stats, event.message());
+    }
+}
+
+fn count_server_received<M: Message + Serialize, C: Channel>(
+    mut events: EventReader<server::MessageEvent<M>>,
+    mut stats: ResMut<ChannelStats>,
+) {
+    for event in events.read() {
+        record_received::<M, C>(&mut stats, event.message());
+    }
+}
+
+fn check_protocol_manifest(manifest: Res<ProtocolManifest>) {
+    let problems = manifest.problems();
+    assert!(
+        problems.is_empty(),
+        "Protocol misconfiguration:\n  {}",
+        problems.join("\n  ")
+    );
+    info!(
+        "Protocol: {} messages on {} channels",
+        manifest.messages.len(),
+        manifest.channels.len()
+    );
+}
+
+fn count_dropped_messages(
+    mut errors: EventReader<ReportError>,
+    manifest: Res<ProtocolManifest>,
+    mut stats: ResMut<ChannelStats>,
+) {
+    for ReportError(error) in errors.read() {
+        if let GameError::Send { message, .. } = error {
+            let channel = manifest.channel_of(message).unwrap_or("unknown");
+            stats.channels.entry(channel).or_default().dropped += 1;
+        }
+    }
+}
+
+fn log_channel_stats(stats: Res<ChannelStats>) {
+    for (channel, counters) in &stats.channels {
+        info!(
+            "Channel {}: {} received ({} bytes), {} dropped",
+            channel, counters.received, counters.bytes_received, counters.dropped
+        );
+    }
+}
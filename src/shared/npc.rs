@@ -0,0 +1,18 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// Marker for non-player characters, so that server-wide systems (budgets, AI) can find them
+#[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Npc {
+    pub spawned_at: f64, // WorldState::world_time when the NPC was spawned
+    pub position: Vec2,  // World coordinates
+}
+
+/// NPCs that attack players; players near them are in danger
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq)]
+pub struct Hostile;
+
+/// How strong an NPC is, from the difficulty where it spawned. NPCs without one have a strength
+/// of 1.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct Strength(pub f32);
@@ -0,0 +1,46 @@
+use lightyear::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Players in a party at once
+pub const MAX_PARTY_SIZE: usize = 8;
+
+/// What a player asks of the party system
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum PartyRequest {
+    /// Invite an online player, by name, to our party, creating it if we aren't in one
+    Invite { name: String },
+    /// Join the party we were last invited to
+    Accept,
+    Leave,
+    /// Share a waypoint with the party, or clear it
+    SetWaypoint(Option<(i32, i32)>),
+    /// Point at a tile for every member to see
+    Ping { tile: (i32, i32) },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PartyMember {
+    pub client_id: ClientId,
+    pub name: String,
+}
+
+/// The player's party, sent to every member whenever it changes. Empty when not in a party.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct PartySync {
+    pub leader: Option<ClientId>,
+    pub members: Vec<PartyMember>,
+    pub waypoint: Option<(i32, i32)>,
+}
+
+/// Someone invited the player to their party
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PartyInvite {
+    pub from: String,
+}
+
+/// A member pinged a tile
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PartyPing {
+    pub from: ClientId,
+    pub tile: (i32, i32),
+}
@@ -0,0 +1,490 @@
+//! Hierarchical pathfinding over the loaded chunks.
+//!
+//! The border between two loaded chunks is split into entrances: runs of tiles that can be
+//! crossed both ways, with a node on each side of the border in the middle of the run. The nodes
+//! of a chunk are linked by the length of the shortest path between them inside the chunk. Long
+//! paths are searched on that small graph first, then refined tile by tile one chunk at a time.
+use bevy::prelude::*;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+
+use crate::shared::world_generation::{
+    Chunk, ChunkCoord, Tile, WorldConfig, WorldGrid, WorldState,
+};
+
+pub type TilePos = (i32, i32);
+
+// Widest run of crossable border tiles covered by a single entrance
+const MAX_ENTRANCE_WIDTH: i32 = 8;
+// Nodes expanded by a single search on the chunk graph before it gives up
+const MAX_GRAPH_EXPANSIONS: u64 = 8192;
+
+/// Read access to tiles by world coordinates
+pub trait TileLookup {
+    fn tile(&self, position: TilePos) -> Option<&Tile>;
+}
+
+/// Tiles of the chunks loaded in the world
+pub struct LoadedTiles<'a, 'w, 's> {
+    pub world_state: &'a WorldState,
+    pub grid: WorldGrid,
+    pub chunks: &'a Query<'w, 's, &'static Chunk>,
+}
+
+impl TileLookup for LoadedTiles<'_, '_, '_> {
+    fn tile(&self, position: TilePos) -> Option<&Tile> {
+        let coord = self.grid.tile_to_chunk(position);
+        let (local_x, local_y) = self.grid.tile_to_local(position);
+        let chunk = self
+            .chunks
+            .get(*self.world_state.chunks.get(&coord)?)
+            .ok()?;
+        chunk.get_tile(local_x, local_y)
+    }
+}
+
+fn neighbours((x, y): TilePos) -> [TilePos; 4] {
+    [(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)]
+}
+
+fn manhattan(a: TilePos, b: TilePos) -> u32 {
+    a.0.abs_diff(b.0) + a.1.abs_diff(b.1)
+}
+
+// Whether a walker may stand on a tile: it has to be traversable, and not a hazard
+fn walkable(tile: &Tile) -> bool {
+    tile.traversable && !tile.tile_type.is_hazard()
+}
+
+/// Whether a walker can step from a tile onto a neighbouring one
+pub fn can_step(tiles: &impl TileLookup, from: TilePos, to: TilePos) -> bool {
+    match (tiles.tile(from), tiles.tile(to)) {
+        (Some(from), Some(to)) => walkable(to) && from.can_step_to(to),
+        _ => false,
+    }
+}
+
+// A* from `start` to `goal`, only stepping on tiles for which `allowed` holds. Returns the tiles
+// walked through, the start and the goal included.
+fn search(
+    tiles: &impl TileLookup,
+    start: TilePos,
+    goal: TilePos,
+    allowed: impl Fn(TilePos) -> bool,
+    expansions: &mut u64,
+) -> Option<Vec<TilePos>> {
+    let mut open = BinaryHeap::from([Reverse((manhattan(start, goal), 0, start))]);
+    let mut cost = HashMap::from([(start, 0)]);
+    let mut came_from: HashMap<TilePos, TilePos> = HashMap::new();
+    while let Some(Reverse((_, current_cost, current))) = open.pop() {
+        if current == goal {
+            let mut path = vec![current];
+            while let Some(previous) = came_from.get(path.last()?) {
+                path.push(*previous);
+            }
+            path.reverse();
+            return Some(path);
+        }
+        if cost.get(&current).is_some_and(|best| *best < current_cost) {
+            continue;
+        }
+        *expansions += 1;
+        for next in neighbours(current) {
+            if !allowed(next) || !can_step(tiles, current, next) {
+                continue;
+            }
+            let next_cost = current_cost + 1;
+            if cost.get(&next).is_some_and(|best| *best <= next_cost) {
+                continue;
+            }
+            cost.insert(next, next_cost);
+            came_from.insert(next, current);
+            open.push(Reverse((
+                next_cost + manhattan(next, goal),
+                next_cost,
+                next,
+            )));
+        }
+    }
+    None
+}
+
+// Breadth-first distances from `from` to each of `targets` it can reach through allowed tiles
+fn distances(
+    tiles: &impl TileLookup,
+    from: TilePos,
+    targets: &HashSet<TilePos>,
+    allowed: impl Fn(TilePos) -> bool,
+    expansions: &mut u64,
+) -> Vec<(TilePos, u32)> {
+    let mut found = Vec::new();
+    let mut seen = HashSet::from([from]);
+    let mut queue = VecDeque::from([(from, 0)]);
+    while let Some((current, distance)) = queue.pop_front() {
+        if targets.contains(&current) {
+            found.push((current, distance));
+            if found.len() == targets.len() {
+                break;
+            }
+        }
+        *expansions += 1;
+        for next in neighbours(current) {
+            if allowed(next) && can_step(tiles, current, next) && seen.insert(next) {
+                queue.push_back((next, distance + 1));
+            }
+        }
+    }
+    found
+}
+
+/// Graph of the entrances between loaded chunks, and of the paths linking them inside chunks
+#[derive(Resource)]
+pub struct NavGraph {
+    grid: WorldGrid,
+    // Entrances between two neighbouring chunks, keyed by the pair of chunks with the lowest
+    // first. Each entrance is a pair of tiles facing each other, in the same order as the key.
+    borders: HashMap<(ChunkCoord, ChunkCoord), Vec<(TilePos, TilePos)>>,
+    // Length of the shortest path inside a chunk between each pair of its entrance tiles
+    links: HashMap<ChunkCoord, HashMap<TilePos, Vec<(TilePos, u32)>>>,
+}
+
+impl FromWorld for NavGraph {
+    fn from_world(world: &mut World) -> Self {
+        let config = world
+            .get_resource::<WorldConfig>()
+            .cloned()
+            .unwrap_or_default();
+        NavGraph::new(config.grid())
+    }
+}
+
+fn border_key(a: ChunkCoord, b: ChunkCoord) -> (ChunkCoord, ChunkCoord) {
+    if (a.x, a.y) <= (b.x, b.y) {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+fn neighbour_chunks(coord: ChunkCoord) -> [ChunkCoord; 4] {
+    let (x, y) = (coord.x, coord.y);
+    [(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)].map(|(x, y)| ChunkCoord { x, y })
+}
+
+impl NavGraph {
+    pub fn new(grid: WorldGrid) -> Self {
+        Self {
+            grid,
+            borders: HashMap::new(),
+            links: HashMap::new(),
+        }
+    }
+
+    pub fn grid(&self) -> WorldGrid {
+        self.grid
+    }
+
+    pub fn contains(&self, coord: ChunkCoord) -> bool {
+        self.links.contains_key(&coord)
+    }
+
+    /// Chunks in the graph
+    pub fn chunks(&self) -> impl Iterator<Item = ChunkCoord> + '_ {
+        self.links.keys().copied()
+    }
+
+    /// Number of chunks in the graph, and of entrances between them
+    pub fn size(&self) -> (usize, usize) {
+        (
+            self.links.len(),
+            self.borders.values().map(|entrances| entrances.len()).sum(),
+        )
+    }
+
+    fn in_chunk(&self, coord: ChunkCoord) -> impl Fn(TilePos) -> bool {
+        let grid = self.grid;
+        move |tile| grid.tile_to_chunk(tile) == coord
+    }
+
+    /// (Re)build everything about a chunk: its borders with the loaded chunks around it, and the
+    /// links inside it and inside those chunks, whose entrances may have changed
+    pub fn update_chunk(
+        &mut self,
+        coord: ChunkCoord,
+        tiles: &impl TileLookup,
+        expansions: &mut u64,
+    ) {
+        self.links.entry(coord).or_default();
+        let mut relink = vec![coord];
+        for other in neighbour_chunks(coord) {
+            if self.contains(other) {
+                self.scan_border(coord, other, tiles);
+                relink.push(other);
+            }
+        }
+        for chunk in relink {
+            self.link_chunk(chunk, tiles, expansions);
+        }
+    }
+
+    /// Update the graph after some tiles changed, only scanning again the borders they lie on and
+    /// linking again the chunks whose entrances moved. Returns the chunks whose links changed.
+    pub fn update_tiles(
+        &mut self,
+        positions: impl IntoIterator<Item = TilePos>,
+        tiles: &impl TileLookup,
+        expansions: &mut u64,
+    ) -> HashSet<ChunkCoord> {
+        let size = self.grid.chunk_size - 1;
+        let mut borders = HashSet::new();
+        let mut relink = HashSet::new();
+        for position in positions {
+            let coord = self.grid.tile_to_chunk(position);
+            if !self.contains(coord) {
+                continue;
+            }
+            relink.insert(coord);
+            let (local_x, local_y) = self.grid.tile_to_local(position);
+            let (x, y) = (coord.x, coord.y);
+            let edges = [
+                (local_x == 0, (x - 1, y)),
+                (local_x == size, (x + 1, y)),
+                (local_y == 0, (x, y - 1)),
+                (local_y == size, (x, y + 1)),
+            ];
+            for (on_edge, (x, y)) in edges {
+                let other = ChunkCoord { x, y };
+                if on_edge && self.contains(other) {
+                    borders.insert((coord, other));
+                }
+            }
+        }
+        for (coord, other) in borders {
+            let key = border_key(coord, other);
+            let before = self.borders.get(&key).cloned();
+            self.scan_border(coord, other, tiles);
+            if self.borders.get(&key) != before.as_ref() {
+                relink.insert(other);
+            }
+        }
+        for coord in relink.iter() {
+            self.link_chunk(*coord, tiles, expansions);
+        }
+        relink
+    }
+
+    pub fn remove_chunk(&mut self, coord: ChunkCoord) {
+        self.links.remove(&coord);
+        self.borders.retain(|(a, b), _| *a != coord && *b != coord);
+    }
+
+    // Find the entrances on the border between two neighbouring chunks
+    fn scan_border(&mut self, coord: ChunkCoord, other: ChunkCoord, tiles: &impl TileLookup) {
+        let size = self.grid.chunk_size as i32;
+        let (origin_x, origin_y) = self.grid.chunk_origin(coord);
+        let (dx, dy) = (other.x - coord.x, other.y - coord.y);
+        // first tile of the border on our side, and the direction it runs in
+        let (first, along) = match (dx, dy) {
+            (1, 0) => ((origin_x + size - 1, origin_y), (0, 1)),
+            (-1, 0) => ((origin_x, origin_y), (0, 1)),
+            (0, 1) => ((origin_x, origin_y + size - 1), (1, 0)),
+            _ => ((origin_x, origin_y), (1, 0)),
+        };
+        let pair = |index: i32| {
+            let ours = (first.0 + along.0 * index, first.1 + along.1 * index);
+            (ours, (ours.0 + dx, ours.1 + dy))
+        };
+        let crossable = |(ours, theirs): (TilePos, TilePos)| {
+            can_step(tiles, ours, theirs) && can_step(tiles, theirs, ours)
+        };
+
+        let mut entrances = Vec::new();
+        let mut index = 0;
+        while index < size {
+            if !crossable(pair(index)) {
+                index += 1;
+                continue;
+            }
+            let start = index;
+            while index < size && index - start < MAX_ENTRANCE_WIDTH && crossable(pair(index)) {
+                index += 1;
+            }
+            entrances.push(pair((start + index - 1) / 2));
+        }
+
+        let key = border_key(coord, other);
+        if key.0 != coord {
+            for entrance in entrances.iter_mut() {
+                *entrance = (entrance.1, entrance.0);
+            }
+        }
+        if entrances.is_empty() {
+            self.borders.remove(&key);
+        } else {
+            self.borders.insert(key, entrances);
+        }
+    }
+
+    /// Entrance tiles of a chunk, on its side of its borders
+    fn entrances(&self, coord: ChunkCoord) -> HashSet<TilePos> {
+        let mut entrances = HashSet::new();
+        for other in neighbour_chunks(coord) {
+            let key = border_key(coord, other);
+            let ours_first = key.0 == coord;
+            for (a, b) in self.borders.get(&key).into_iter().flatten() {
+                entrances.insert(if ours_first { *a } else { *b });
+            }
+        }
+        entrances
+    }
+
+    fn link_chunk(&mut self, coord: ChunkCoord, tiles: &impl TileLookup, expansions: &mut u64) {
+        let entrances = self.entrances(coord);
+        let in_chunk = self.in_chunk(coord);
+        let links = entrances
+            .iter()
+            .map(|from| {
+                let reached = distances(tiles, *from, &entrances, &in_chunk, expansions)
+                    .into_iter()
+                    .filter(|(to, _)| to != from)
+                    .collect();
+                (*from, reached)
+            })
+            .collect();
+        self.links.insert(coord, links);
+    }
+
+    // Nodes reachable from an entrance tile in one edge: entrances of the same chunk, and the tile
+    // across the border
+    fn edges(&self, node: TilePos) -> Vec<(TilePos, u32)> {
+        let coord = self.grid.tile_to_chunk(node);
+        let mut edges: Vec<(TilePos, u32)> = self
+            .links
+            .get(&coord)
+            .and_then(|links| links.get(&node))
+            .cloned()
+            .unwrap_or_default();
+        for other in neighbour_chunks(coord) {
+            for (a, b) in self
+                .borders
+                .get(&border_key(coord, other))
+                .into_iter()
+                .flatten()
+            {
+                if *a == node {
+                    edges.push((*b, 1));
+                } else if *b == node {
+                    edges.push((*a, 1));
+                }
+            }
+        }
+        edges
+    }
+
+    /// Shortest path, as near as the graph allows, between two tiles of loaded chunks. The path
+    /// starts with `from` and ends with `to`.
+    pub fn find_path(
+        &self,
+        tiles: &impl TileLookup,
+        from: TilePos,
+        to: TilePos,
+        expansions: &mut u64,
+    ) -> Option<Vec<TilePos>> {
+        if from == to {
+            return Some(vec![from]);
+        }
+        let (from_chunk, to_chunk) = (self.grid.tile_to_chunk(from), self.grid.tile_to_chunk(to));
+        if !self.contains(from_chunk) || !self.contains(to_chunk) {
+            return None;
+        }
+        if !tiles.tile(to).is_some_and(walkable) {
+            return None;
+        }
+        if from_chunk == to_chunk {
+            let local = search(tiles, from, to, self.in_chunk(from_chunk), expansions);
+            if local.is_some() {
+                return local;
+            }
+        }
+
+        // leave the start chunk and enter the goal chunk through their entrances
+        let exits = distances(
+            tiles,
+            from,
+            &self.entrances(from_chunk),
+            self.in_chunk(from_chunk),
+            expansions,
+        );
+        let arrivals: HashMap<TilePos, u32> = distances(
+            tiles,
+            to,
+            &self.entrances(to_chunk),
+            self.in_chunk(to_chunk),
+            expansions,
+        )
+        .into_iter()
+        .collect();
+
+        let mut open = BinaryHeap::from([Reverse((manhattan(from, to), 0, from))]);
+        let mut cost = HashMap::from([(from, 0)]);
+        let mut came_from: HashMap<TilePos, TilePos> = HashMap::new();
+        let mut graph_expansions = 0;
+        let mut found = false;
+        while let Some(Reverse((_, current_cost, current))) = open.pop() {
+            if current == to {
+                found = true;
+                break;
+            }
+            if cost.get(&current).is_some_and(|best| *best < current_cost) {
+                continue;
+            }
+            graph_expansions += 1;
+            if graph_expansions > MAX_GRAPH_EXPANSIONS {
+                break;
+            }
+            let mut edges = self.edges(current);
+            if current == from {
+                edges.extend(exits.iter().copied());
+            }
+            if let Some(distance) = arrivals.get(&current) {
+                edges.push((to, *distance));
+            }
+            for (next, length) in edges {
+                let next_cost = current_cost + length;
+                if cost.get(&next).is_some_and(|best| *best <= next_cost) {
+                    continue;
+                }
+                cost.insert(next, next_cost);
+                came_from.insert(next, current);
+                open.push(Reverse((next_cost + manhattan(next, to), next_cost, next)));
+            }
+        }
+        *expansions += graph_expansions;
+        if !found {
+            return None;
+        }
+
+        let mut waypoints = vec![to];
+        while let Some(previous) = came_from.get(waypoints.last()?) {
+            waypoints.push(*previous);
+        }
+        waypoints.reverse();
+
+        // walk each leg inside its chunk
+        let mut path = vec![from];
+        for leg in waypoints.windows(2) {
+            let (start, end) = (leg[0], leg[1]);
+            let coord = self.grid.tile_to_chunk(start);
+            if coord != self.grid.tile_to_chunk(end) {
+                path.push(end);
+                continue;
+            }
+            let steps = search(tiles, start, end, self.in_chunk(coord), expansions)?;
+            path.extend(steps.into_iter().skip(1));
+        }
+        Some(path)
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,127 @@
+//! Hierarchical paths must reach wherever a plain A* over the tiles does, and follow tile changes
+use proptest::prelude::*;
+use rand::prelude::*;
+
+use super::*;
+use crate::shared::world_generation::{Decoration, Overlay, ResourceType, TileType};
+
+const CHUNK_SIZE: usize = 8;
+// The test world is this many chunks wide and high, from chunk (0, 0)
+const CHUNKS: i32 = 3;
+const WIDTH: i32 = CHUNK_SIZE as i32 * CHUNKS;
+
+struct TestTiles(HashMap<TilePos, Tile>);
+
+impl TileLookup for TestTiles {
+    fn tile(&self, position: TilePos) -> Option<&Tile> {
+        self.0.get(&position)
+    }
+}
+
+impl TestTiles {
+    // Flat ground over the whole test world, with walls where `wall` holds
+    fn new(wall: impl Fn(TilePos) -> bool) -> Self {
+        let tiles = (0..WIDTH)
+            .flat_map(|x| (0..WIDTH).map(move |y| (x, y)))
+            .map(|position| (position, tile(position, !wall(position))))
+            .collect();
+        TestTiles(tiles)
+    }
+
+    fn set_open(&mut self, position: TilePos, open: bool) {
+        self.0.insert(position, tile(position, open));
+    }
+}
+
+fn tile(position: TilePos, open: bool) -> Tile {
+    Tile {
+        tile_type: TileType::Grass,
+        decoration: Decoration::None,
+        resource: ResourceType::None,
+        overlay: Overlay::None,
+        height: 0.0,
+        position,
+        traversable: open,
+        damage: 0,
+    }
+}
+
+fn graph(tiles: &TestTiles) -> NavGraph {
+    let mut graph = NavGraph::new(WorldGrid::new(CHUNK_SIZE));
+    for x in 0..CHUNKS {
+        for y in 0..CHUNKS {
+            graph.update_chunk(ChunkCoord { x, y }, tiles, &mut 0);
+        }
+    }
+    graph
+}
+
+// Plain A* over every tile of the test world
+fn flat_path(tiles: &TestTiles, from: TilePos, to: TilePos) -> Option<Vec<TilePos>> {
+    search(tiles, from, to, |_| true, &mut 0)
+}
+
+fn find_path(graph: &NavGraph, tiles: &TestTiles, from: TilePos, to: TilePos) -> Option<usize> {
+    graph
+        .find_path(tiles, from, to, &mut 0)
+        .map(|path| path.len())
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn paths_match_a_flat_search(seed in any::<u64>()) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let walls: HashSet<TilePos> = (0..WIDTH)
+            .flat_map(|x| (0..WIDTH).map(move |y| (x, y)))
+            .filter(|_| rng.random_bool(0.3))
+            .collect();
+        let tiles = TestTiles::new(|position| walls.contains(&position));
+        let graph = graph(&tiles);
+        for _ in 0..16 {
+            let from = (rng.random_range(0..WIDTH), rng.random_range(0..WIDTH));
+            let to = (rng.random_range(0..WIDTH), rng.random_range(0..WIDTH));
+            let flat = flat_path(&tiles, from, to);
+            let path = graph.find_path(&tiles, from, to, &mut 0);
+            prop_assert_eq!(path.is_some(), flat.is_some(), "from {:?} to {:?}", from, to);
+            let (Some(path), Some(flat)) = (path, flat) else {
+                continue;
+            };
+            prop_assert_eq!(path.first(), Some(&from));
+            prop_assert_eq!(path.last(), Some(&to));
+            for step in path.windows(2) {
+                prop_assert_eq!(manhattan(step[0], step[1]), 1, "{:?}", step);
+                prop_assert!(can_step(&tiles, step[0], step[1]), "{:?}", step);
+            }
+            // going through the entrances may take a few more steps than the shortest path
+            prop_assert!(path.len() >= flat.len());
+        }
+    }
+}
+
+#[test]
+fn walls_placed_and_removed_change_paths() {
+    // a single corridor through the three chunks of the middle row
+    let mut tiles = TestTiles::new(|(_, y)| y != 12);
+    let mut graph = graph(&tiles);
+    let (from, to) = ((0, 12), (WIDTH - 1, 12));
+    assert_eq!(find_path(&graph, &tiles, from, to), Some(WIDTH as usize));
+
+    // inside a chunk, then on a border between two chunks
+    for wall in [(12, 12), (7, 12)] {
+        tiles.set_open(wall, false);
+        let changed = graph.update_tiles([wall], &tiles, &mut 0);
+        assert!(changed.contains(&graph.grid().tile_to_chunk(wall)));
+        assert_eq!(find_path(&graph, &tiles, from, to), None, "{:?}", wall);
+
+        tiles.set_open(wall, true);
+        graph.update_tiles([wall], &tiles, &mut 0);
+        assert_eq!(
+            find_path(&graph, &tiles, from, to),
+            Some(WIDTH as usize),
+            "{:?}",
+            wall
+        );
+    }
+}
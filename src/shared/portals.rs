@@ -0,0 +1,37 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// Maximum distance, in tiles, between a player and the portal they use
+pub const PORTAL_REACH: u32 = 2;
+
+/// Where a portal takes the players using it
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PortalLink {
+    // The hub: leads to any portal the player discovered
+    Hub,
+    // Generated portals all lead to the hub
+    ToHub,
+    // Placed portals lead to their pair
+    Pair((i32, i32)),
+    // A placed portal whose pair wasn't placed yet
+    Unlinked,
+}
+
+/// A portal standing on a tile, replicated so that clients can show it
+#[derive(Component, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Portal {
+    pub tile: (i32, i32),
+    pub link: PortalLink,
+}
+
+/// Travel through the portal at `portal`. Only the hub uses `destination`: it must be one of the
+/// portals the player discovered.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct UsePortal {
+    pub portal: (i32, i32),
+    pub destination: Option<(i32, i32)>,
+}
+
+/// Every portal the player discovered so far, sent again whenever they discover a new one
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DiscoveredPortals(pub Vec<Portal>);
@@ -0,0 +1,12 @@
+//! Power networks: generators, and the wires and assemblers they are connected to. The server
+//! solves networks on its own; clients only get whether each assembler is powered, to show it.
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Whether the assembler on a tile gets the power it needs. Replicated for the assemblers of
+/// loaded chunks.
+#[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PowerState {
+    pub tile: (i32, i32),
+    pub powered: bool,
+}
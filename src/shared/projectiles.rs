@@ -0,0 +1,25 @@
+//! Stones players throw. Projectiles are bodies the server moves with the shared kinematics until
+//! they hit something or run out of speed; clients only draw them where the server says they are.
+use bevy::prelude::*;
+use lightyear::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Speed a stone leaves the hand at, in world units per tick
+pub const THROW_SPEED: f32 = 0.5;
+/// Fraction of its velocity a projectile keeps from one tick to the next
+pub const AIR_FRICTION: f32 = 0.96;
+/// Distance from a body at which a projectile hits it
+pub const HIT_RADIUS: f32 = 0.6;
+
+/// A projectile in flight, thrown by `owner`
+#[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Projectile {
+    pub owner: ClientId,
+    pub position: Vec2,
+}
+
+/// Ask the server to throw one of the player's stones
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct ThrowStone {
+    pub direction: Vec2,
+}
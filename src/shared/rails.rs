@@ -0,0 +1,143 @@
+//! Rails players build, and the carts they ride along them. Rides are simulated the same way on
+//! the server and on the client, from the rider's inputs and the tiles around them, so that the
+//! rider's position can be predicted.
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::protocol::{Inputs, PlayerPosition};
+use crate::shared::movement::{TileSource, MOVE_SPEED};
+use crate::shared::world_generation::{Decoration, WorldGrid};
+
+/// Distance a cart rolls per tick
+pub const RIDE_SPEED: f32 = 1.5 * MOVE_SPEED;
+// How close to the center of a tile a cart counts as on it, in world units
+const CENTER_EPSILON: f32 = 0.001;
+// Tiles a cart may roll through in a single tick, however fast it goes
+const MAX_TILES_PER_TICK: usize = 64;
+
+/// A cart standing on a rail, waiting for a rider
+#[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Cart {
+    pub tile: (i32, i32),
+}
+
+/// Put on players riding a cart. The cart rolls along the rails in its heading, turning with
+/// bends, until it reaches the end of the line or a junction the rider doesn't choose a way at.
+#[derive(Component, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Riding {
+    /// Direction the cart last rolled in, one tile step; (0, 0) when just boarded
+    pub heading: (i32, i32),
+}
+
+/// Ask the server to put a cart, made of iron, on a rail near the player
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct PlaceCart {
+    pub tile: (i32, i32),
+}
+
+/// Ask the server to get off the cart the player rides, leaving it on the rails
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct LeaveCart;
+
+pub fn is_rail(tiles: &impl TileSource, tile: (i32, i32)) -> bool {
+    tiles
+        .tile(tile)
+        .is_some_and(|tile| tile.decoration == Decoration::Rail)
+}
+
+// Rails a cart on `tile` can roll onto: neighbouring rails it can step to
+fn connections(tiles: &impl TileSource, (x, y): (i32, i32)) -> Vec<(i32, i32)> {
+    let Some(from) = tiles.tile((x, y)) else {
+        return Vec::new();
+    };
+    [(1, 0), (-1, 0), (0, 1), (0, -1)]
+        .into_iter()
+        .filter(|(dx, dy)| {
+            tiles
+                .tile((x + dx, y + dy))
+                .is_some_and(|to| to.decoration == Decoration::Rail && from.can_step_to(to))
+        })
+        .collect()
+}
+
+// Direction the rider asks for, if any
+fn asked_heading(input: &Inputs) -> Option<(i32, i32)> {
+    let Inputs::Direction(direction) = input else {
+        return None;
+    };
+    [
+        (direction.up, (0, 1)),
+        (direction.down, (0, -1)),
+        (direction.left, (-1, 0)),
+        (direction.right, (1, 0)),
+    ]
+    .into_iter()
+    .find(|(pressed, _)| *pressed)
+    .map(|(_, heading)| heading)
+}
+
+// Where a cart standing in the middle of `tile` goes next: where the rider asks if the rails go
+// there, else straight on, else around the bend if there is a single one
+fn next_heading(
+    tiles: &impl TileSource,
+    tile: (i32, i32),
+    heading: (i32, i32),
+    wanted: Option<(i32, i32)>,
+) -> Option<(i32, i32)> {
+    let connections = connections(tiles, tile);
+    if let Some(wanted) = wanted.filter(|wanted| connections.contains(wanted)) {
+        return Some(wanted);
+    }
+    if connections.contains(&heading) {
+        return Some(heading);
+    }
+    let back = (-heading.0, -heading.1);
+    let mut ahead = connections.into_iter().filter(|next| *next != back);
+    match (ahead.next(), ahead.next()) {
+        (Some(next), None) => Some(next),
+        _ => None,
+    }
+}
+
+/// Roll a ridden cart for one tick, along the rails from tile center to tile center
+pub fn ride_rails(
+    position: &mut PlayerPosition,
+    riding: &mut Riding,
+    input: &Inputs,
+    tiles: &impl TileSource,
+) {
+    let wanted = asked_heading(input);
+    let mut left = RIDE_SPEED;
+    for _ in 0..MAX_TILES_PER_TICK {
+        if left <= 0.0 {
+            break;
+        }
+        let tile = WorldGrid::world_to_tile(position.0);
+        if !is_rail(tiles, tile) {
+            riding.heading = (0, 0);
+            return;
+        }
+        let center = WorldGrid::tile_to_world(tile);
+        let heading = Vec2::new(riding.heading.0 as f32, riding.heading.1 as f32);
+        // distance left to the center of the tile, negative once past it
+        let ahead = (center - position.0).dot(heading);
+        let step = if ahead > CENTER_EPSILON {
+            ahead
+        } else if ahead < -CENTER_EPSILON {
+            WorldGrid::TILE_SIZE + ahead
+        } else {
+            position.0 = center;
+            // the rider may turn back or pick a way at a junction. Stopped carts keep their
+            // heading, so they don't roll back on their own.
+            let Some(next) = next_heading(tiles, tile, riding.heading, wanted) else {
+                return;
+            };
+            riding.heading = next;
+            WorldGrid::TILE_SIZE
+        };
+        let heading = Vec2::new(riding.heading.0 as f32, riding.heading.1 as f32);
+        let step = step.min(left);
+        position.0 += heading * step;
+        left -= step;
+    }
+}
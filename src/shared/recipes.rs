@@ -0,0 +1,59 @@
+use crate::shared::items::ItemKind;
+
+/// Group of recipes sharing how they are made
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RecipeCategory {
+    /// Food cooked at a furnace, burning coal
+    Cooking,
+    /// Equipment put together at a furnace
+    Crafting,
+}
+
+/// What has to stand near the player for them to follow a recipe
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Station {
+    Furnace,
+}
+
+impl RecipeCategory {
+    pub fn station(&self) -> Station {
+        match self {
+            RecipeCategory::Cooking | RecipeCategory::Crafting => Station::Furnace,
+        }
+    }
+}
+
+/// Turns items taken from the inventory into another item
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Recipe {
+    pub category: RecipeCategory,
+    pub inputs: &'static [(ItemKind, u32)],
+    pub output: ItemKind,
+}
+
+pub static RECIPES: [Recipe; 3] = [
+    Recipe {
+        category: RecipeCategory::Cooking,
+        inputs: &[(ItemKind::Fish, 1), (ItemKind::Coal, 1)],
+        output: ItemKind::CookedFish,
+    },
+    Recipe {
+        category: RecipeCategory::Cooking,
+        inputs: &[
+            (ItemKind::Fish, 1),
+            (ItemKind::Berries, 2),
+            (ItemKind::Coal, 1),
+        ],
+        output: ItemKind::Stew,
+    },
+    Recipe {
+        category: RecipeCategory::Crafting,
+        inputs: &[(ItemKind::Wood, 1), (ItemKind::Coal, 1)],
+        output: ItemKind::Torch,
+    },
+];
+
+/// The recipe making `output`, if any
+pub fn recipe_for(output: ItemKind) -> Option<&'static Recipe> {
+    RECIPES.iter().find(|recipe| recipe.output == output)
+}
@@ -0,0 +1,79 @@
+//! Named regions of the world. Names are generated by the server from the seed and the biome,
+//! and sent to clients when they ask for them.
+use serde::{Deserialize, Serialize};
+
+use crate::shared::world_generation::{seeded_hash, BiomeType, ChunkCoord};
+
+/// Width of a region, in chunks
+pub const REGION_CHUNKS: i32 = 16;
+
+// Syllables region names are made of
+const ONSETS: [&str; 16] = [
+    "b", "br", "c", "d", "dr", "f", "g", "k", "l", "m", "n", "r", "s", "th", "v", "w",
+];
+const VOWELS: [&str; 8] = ["a", "e", "i", "o", "u", "ae", "ei", "ou"];
+const CODAS: [&str; 10] = ["", "", "n", "r", "l", "th", "nd", "sk", "m", "rn"];
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RegionCoord {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl RegionCoord {
+    pub fn of_chunk(coord: ChunkCoord) -> Self {
+        RegionCoord {
+            x: coord.x.div_euclid(REGION_CHUNKS),
+            y: coord.y.div_euclid(REGION_CHUNKS),
+        }
+    }
+
+    /// Chunk in the middle of the region, whose biome names it
+    pub fn center_chunk(&self) -> ChunkCoord {
+        let half = REGION_CHUNKS / 2;
+        ChunkCoord {
+            x: self.x.saturating_mul(REGION_CHUNKS).saturating_add(half),
+            y: self.y.saturating_mul(REGION_CHUNKS).saturating_add(half),
+        }
+    }
+}
+
+/// Name of a region: a word of seeded syllables, placed in a phrase fitting the biome.
+/// Deterministic for a given seed.
+pub fn region_name(region: RegionCoord, seed: u32, biome: BiomeType) -> String {
+    let hash = seeded_hash(seed, ("region", region));
+    let syllables = 2 + (hash % 2) as usize;
+    let mut word = String::new();
+    for index in 0..syllables {
+        let roll = (hash >> (8 + index * 12)) as usize;
+        word.push_str(ONSETS[roll % ONSETS.len()]);
+        word.push_str(VOWELS[(roll >> 4) % VOWELS.len()]);
+        if index == syllables - 1 {
+            word.push_str(CODAS[(roll >> 7) % CODAS.len()]);
+        }
+    }
+    let mut letters = word.chars();
+    let word: String = letters
+        .next()
+        .map(|first| first.to_uppercase().chain(letters).collect())
+        .unwrap_or_default();
+    match biome {
+        BiomeType::Plains => format!("{} Plains", word),
+        BiomeType::Forest => format!("{}wood", word),
+        BiomeType::Desert => format!("{} Wastes", word),
+        BiomeType::Ocean => format!("Sea of {}", word),
+        BiomeType::Mountain => format!("Mount {}", word),
+        BiomeType::Tundra => format!("{} Reach", word),
+    }
+}
+
+/// Asks the server for the name of a region
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct RegionNameRequest(pub RegionCoord);
+
+/// Name of a region, in answer to a `RegionNameRequest`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RegionNameSync {
+    pub region: RegionCoord,
+    pub name: String,
+}
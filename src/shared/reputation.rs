@@ -0,0 +1,84 @@
+//! Standing of each player with the NPC factions. Trading with a faction raises it, attacking its
+//! members lowers it; it decides the prices a faction asks and whether its members turn on the
+//! player.
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Standing is kept between these bounds, so that a long history can always be made up for
+pub const MAX_STANDING: i32 = 100;
+
+/// Group of NPCs players earn a reputation with, put on the NPCs belonging to it
+#[derive(
+    Component, Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
+pub enum Faction {
+    Villagers,
+    /// Raiders, sent after players during raids
+    Bandits,
+}
+
+impl Faction {
+    pub const ALL: [Faction; 2] = [Faction::Villagers, Faction::Bandits];
+}
+
+/// How a faction feels about a player, from their standing with it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Attitude {
+    Hostile,
+    Unfriendly,
+    Neutral,
+    Friendly,
+    Honored,
+}
+
+impl Attitude {
+    pub fn of(standing: i32) -> Attitude {
+        match standing {
+            s if s <= -50 => Attitude::Hostile,
+            s if s < -10 => Attitude::Unfriendly,
+            s if s <= 10 => Attitude::Neutral,
+            s if s < 50 => Attitude::Friendly,
+            _ => Attitude::Honored,
+        }
+    }
+
+    /// What members of the faction ask for something worth `count` items, or None if they
+    /// refuse to deal with the player at all
+    pub fn price(&self, count: u32) -> Option<u32> {
+        match self {
+            Attitude::Hostile => None,
+            Attitude::Unfriendly => Some(count + count.div_ceil(2)),
+            Attitude::Neutral => Some(count),
+            Attitude::Friendly => Some((count * 3).div_ceil(4)),
+            Attitude::Honored => Some(count.div_ceil(2)),
+        }
+    }
+}
+
+/// Standing of a player with each faction. Only the server has it; it's saved in the profile.
+#[derive(Component, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Reputation {
+    pub standing: BTreeMap<Faction, i32>,
+}
+
+impl Reputation {
+    pub fn standing(&self, faction: Faction) -> i32 {
+        self.standing.get(&faction).copied().unwrap_or(0)
+    }
+
+    pub fn attitude(&self, faction: Faction) -> Attitude {
+        Attitude::of(self.standing(faction))
+    }
+
+    pub fn change(&mut self, faction: Faction, delta: i32) {
+        let standing = (self.standing(faction) + delta).clamp(-MAX_STANDING, MAX_STANDING);
+        self.standing.insert(faction, standing);
+    }
+}
+
+/// Attack the NPC standing on a tile within reach of the player
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct AttackNpc {
+    pub tile: (i32, i32),
+}
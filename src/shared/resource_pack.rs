@@ -0,0 +1,126 @@
+//! Resource packs: a texture atlas and data tables a server offers to its clients, so that it can
+//! change how its world looks without clients being rebuilt. Packs only change visuals, the rules
+//! stay the server's.
+//!
+//! A pack is authored as a directory holding `pack.ron`, the manifest, and `atlas.png`. It's sent
+//! to clients as a single blob identified by its SHA-256 hash, which clients check after the
+//! download and use as the name of their cached copy.
+use bevy::asset::ron;
+use image::{ImageFormat, ImageReader, RgbaImage};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+use crate::shared::error::GameError;
+use crate::shared::items::ItemKind;
+
+/// Largest pack a server can offer, whatever its clients would accept
+pub const MAX_PACK_BYTES: usize = 32 * 1024 * 1024;
+// Largest atlas, in pixels along each side, so that a small PNG can't decode to a huge image
+const MAX_ATLAS_SIZE: u32 = 4096;
+
+const MANIFEST_FILE: &str = "pack.ron";
+const ATLAS_FILE: &str = "atlas.png";
+
+pub type PackHash = [u8; 32];
+
+/// Description of a pack and its data tables
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+#[serde(default)]
+pub struct PackManifest {
+    pub name: String,
+    pub description: String,
+    /// Width and height of each sprite of the atlas, in pixels
+    pub sprite_size: u32,
+    /// Cell of the atlas, as `(column, row)`, replacing each built-in sprite, e.g. `"grass"`,
+    /// `"tree"` or `"tree_damaged_1"`
+    pub sprites: HashMap<String, (u32, u32)>,
+    /// Colors replacing the built-in colors of items, as sRGB
+    pub item_colors: HashMap<ItemKind, (f32, f32, f32)>,
+}
+
+/// A pack as sent to clients, the atlas still PNG encoded
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ResourcePack {
+    pub manifest: PackManifest,
+    pub atlas: Vec<u8>,
+}
+
+fn invalid(reason: impl ToString) -> GameError {
+    GameError::ResourcePack(reason.to_string())
+}
+
+impl ResourcePack {
+    /// Read the pack authored in a directory
+    pub fn load(dir: &Path) -> Result<Self, GameError> {
+        let manifest = fs::read_to_string(dir.join(MANIFEST_FILE))?;
+        let pack = ResourcePack {
+            manifest: ron::from_str(&manifest)
+                .map_err(|e| invalid(format!("{}: {}", dir.display(), e)))?,
+            atlas: fs::read(dir.join(ATLAS_FILE))?,
+        };
+        pack.atlas_image()?;
+        Ok(pack)
+    }
+
+    /// The pack as sent to clients and cached by them
+    pub fn encode(&self) -> Result<Vec<u8>, GameError> {
+        let data = bincode::serialize(self).map_err(invalid)?;
+        if data.len() > MAX_PACK_BYTES {
+            return Err(invalid(format!(
+                "{} bytes, more than the {} allowed",
+                data.len(),
+                MAX_PACK_BYTES
+            )));
+        }
+        Ok(data)
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, GameError> {
+        if data.len() > MAX_PACK_BYTES {
+            return Err(invalid("oversized pack"));
+        }
+        let pack: ResourcePack = bincode::deserialize(data).map_err(invalid)?;
+        pack.atlas_image()?;
+        Ok(pack)
+    }
+
+    /// Decode the atlas, checking that every sprite of the manifest is inside of it
+    pub fn atlas_image(&self) -> Result<RgbaImage, GameError> {
+        let reader = || ImageReader::with_format(Cursor::new(&self.atlas), ImageFormat::Png);
+        let (width, height) = reader().into_dimensions().map_err(invalid)?;
+        if width > MAX_ATLAS_SIZE || height > MAX_ATLAS_SIZE {
+            return Err(invalid(format!("{}x{} atlas is too large", width, height)));
+        }
+        let size = self.manifest.sprite_size as u64;
+        if size == 0 && !self.manifest.sprites.is_empty() {
+            return Err(invalid("sprites have no size"));
+        }
+        for (name, (column, row)) in &self.manifest.sprites {
+            if (*column as u64 + 1) * size > width as u64
+                || (*row as u64 + 1) * size > height as u64
+            {
+                return Err(invalid(format!("sprite {} is outside of the atlas", name)));
+            }
+        }
+        Ok(reader().decode().map_err(invalid)?.to_rgba8())
+    }
+
+    /// Image of a sprite, cut out of the atlas
+    pub fn sprite(&self, atlas: &RgbaImage, (column, row): (u32, u32)) -> RgbaImage {
+        let size = self.manifest.sprite_size;
+        image::imageops::crop_imm(atlas, column * size, row * size, size, size).to_image()
+    }
+}
+
+pub fn hash(data: &[u8]) -> PackHash {
+    Sha256::digest(data).into()
+}
+
+/// Hash as text, naming cached packs
+pub fn hex(hash: &PackHash) -> String {
+    hash.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
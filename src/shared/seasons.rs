@@ -0,0 +1,79 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::shared::day_night::DAY_LENGTH;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Season {
+    #[default]
+    Spring,
+    Summer,
+    Autumn,
+    Winter,
+}
+
+impl Season {
+    pub const ALL: [Season; 4] = [
+        Season::Spring,
+        Season::Summer,
+        Season::Autumn,
+        Season::Winter,
+    ];
+
+    /// Season at the given world time, with seasons lasting `length_days` in-game days
+    pub fn at(world_time: f64, length_days: u32) -> Season {
+        let day = (world_time / DAY_LENGTH).floor() as u64;
+        let index = day / length_days.max(1) as u64 % Season::ALL.len() as u64;
+        Season::ALL[index as usize]
+    }
+
+    pub fn from_name(name: &str) -> Option<Season> {
+        Season::ALL
+            .into_iter()
+            .find(|season| format!("{:?}", season).eq_ignore_ascii_case(name))
+    }
+
+    // Multiplier applied to crop growth speed, and so to how much crops yield
+    pub fn crop_growth(&self) -> f32 {
+        match self {
+            Season::Spring => 1.25,
+            Season::Summer => 1.0,
+            Season::Autumn => 0.75,
+            Season::Winter => 0.0,
+        }
+    }
+
+    /// What a crop yielding `base` at normal growth yields this season
+    pub fn crop_yield(&self, base: u32) -> u32 {
+        (base as f32 * self.crop_growth()).round() as u32
+    }
+
+    // Multiplier applied to the spawn weight of NPCs that like this season
+    pub fn npc_spawn_weight(&self, preferred: &[Season]) -> f32 {
+        if preferred.is_empty() || preferred.contains(self) {
+            1.0
+        } else {
+            0.25
+        }
+    }
+}
+
+/// Current season, decided by the server and replicated to clients
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq)]
+pub struct CurrentSeason(pub Season);
+
+/// Sent by the server to all clients when the season changes, and to clients when they connect
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct SeasonChanged(pub Season);
+
+#[derive(Clone)]
+pub struct SeasonsPlugin;
+
+impl Plugin for SeasonsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CurrentSeason>();
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,29 @@
+//! Seasons must change what the world gives and what it sends at players
+use super::*;
+use crate::shared::difficulty::Difficulty;
+
+#[test]
+fn crops_yield_with_the_season() {
+    assert_eq!(Season::Spring.crop_yield(4), 5);
+    assert_eq!(Season::Summer.crop_yield(4), 4);
+    assert_eq!(Season::Autumn.crop_yield(4), 3);
+    assert_eq!(Season::Winter.crop_yield(4), 0);
+}
+
+#[test]
+fn raids_are_larger_in_the_seasons_raiders_like() {
+    let difficulty = Difficulty(3.0);
+    assert_eq!(difficulty.raiders(Season::Winter), 6);
+    assert_eq!(difficulty.raiders(Season::Autumn), 6);
+    assert_eq!(difficulty.raiders(Season::Summer), 2);
+    // the smallest raid still sends someone
+    assert_eq!(Difficulty::default().raiders(Season::Spring), 1);
+}
+
+#[test]
+fn seasons_follow_world_time() {
+    assert_eq!(Season::at(0.0, 2), Season::Spring);
+    assert_eq!(Season::at(2.0 * DAY_LENGTH, 2), Season::Summer);
+    assert_eq!(Season::at(7.5 * DAY_LENGTH, 2), Season::Winter);
+    assert_eq!(Season::at(8.0 * DAY_LENGTH, 2), Season::Spring);
+}
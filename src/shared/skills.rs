@@ -0,0 +1,69 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::shared::world_generation::ResourceType;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Skill {
+    Mining,
+    Woodcutting,
+    Farming,
+    Combat,
+}
+
+impl Skill {
+    pub const ALL: [Skill; 4] = [
+        Skill::Mining,
+        Skill::Woodcutting,
+        Skill::Farming,
+        Skill::Combat,
+    ];
+
+    // Skill trained by harvesting a resource, if any
+    pub fn for_resource(resource: ResourceType) -> Option<Skill> {
+        match resource {
+            ResourceType::Tree => Some(Skill::Woodcutting),
+            ResourceType::Stone
+            | ResourceType::Iron
+            | ResourceType::Copper
+            | ResourceType::Coal
+            | ResourceType::Gold => Some(Skill::Mining),
+            ResourceType::Fish | ResourceType::None => None,
+        }
+    }
+}
+
+/// Experience a player earned in each skill. Only the server has it; it's saved in the profile.
+#[derive(Component, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Skills {
+    pub xp: BTreeMap<Skill, u32>,
+}
+
+impl Skills {
+    pub fn xp(&self, skill: Skill) -> u32 {
+        self.xp.get(&skill).copied().unwrap_or(0)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct SkillLevel {
+    pub level: u32,
+    pub xp: u32,
+    // Experience the current level was reached at
+    pub level_xp: u32,
+    // Experience needed for the next level, None once the last level is reached
+    pub next_level_xp: Option<u32>,
+}
+
+/// Level of a player in each skill, computed by the server from their experience
+#[derive(Component, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct SkillLevels {
+    pub levels: BTreeMap<Skill, SkillLevel>,
+}
+
+impl SkillLevels {
+    pub fn get(&self, skill: Skill) -> SkillLevel {
+        self.levels.get(&skill).copied().unwrap_or_default()
+    }
+}
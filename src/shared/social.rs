@@ -0,0 +1,65 @@
+use bevy::prelude::*;
+use lightyear::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// How a player treats another one. Blocking also mutes.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Relation {
+    // Their chat isn't relayed to us
+    Muted,
+    // Nothing of theirs reaches us: chat, emotes, trade requests, and we don't see their name
+    Blocked,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SocialEntry {
+    pub relation: Relation,
+    /// Name of the player when they were muted or blocked, to list them while they are offline
+    pub name: String,
+}
+
+/// The players someone muted or blocked. Saved in their profile, and sent to their client
+/// whenever it changes.
+#[derive(Component, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct SocialLists {
+    pub entries: HashMap<ClientId, SocialEntry>,
+}
+
+impl SocialLists {
+    pub fn relation(&self, client_id: ClientId) -> Option<Relation> {
+        self.entries.get(&client_id).map(|entry| entry.relation)
+    }
+
+    /// Whether chat lines of the player should be kept from us
+    pub fn hides_chat_of(&self, client_id: ClientId) -> bool {
+        self.entries.contains_key(&client_id)
+    }
+
+    pub fn blocks(&self, client_id: ClientId) -> bool {
+        self.relation(client_id) == Some(Relation::Blocked)
+    }
+
+    pub fn set(&mut self, client_id: ClientId, name: String, relation: Option<Relation>) {
+        match relation {
+            Some(relation) => {
+                self.entries
+                    .insert(client_id, SocialEntry { relation, name });
+            }
+            None => {
+                self.entries.remove(&client_id);
+            }
+        }
+    }
+}
+
+/// Mute or block a player, or clear what we set for them with `None`
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct SetRelation {
+    pub player: ClientId,
+    pub relation: Option<Relation>,
+}
+
+/// The player's own lists, sent when they join and whenever they change
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SocialListsSync(pub SocialLists);
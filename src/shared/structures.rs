@@ -0,0 +1,370 @@
+//! Structures larger than a chunk. The world is split into cells holding at most one structure
+//! each, whose plan only depends on the world config. Every chunk a structure overlaps builds its
+//! own part of it when generated, so it comes out whole whatever order chunks are generated in,
+//! and its footprint is reserved so that nothing else gets placed on it.
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::f32::consts::TAU;
+use std::sync::Mutex;
+
+use crate::shared::world_generation::{
+    chunk_biome, seeded_hash, BiomeType, ChunkCoord, Decoration, ResourceType, Tile, TileType,
+    WorldConfig,
+};
+
+/// Width of the square cells structures are planned in, in tiles. Structures stay inside their
+/// cell, so they never overlap each other.
+pub const STRUCTURE_CELL: i32 = 128;
+// One in this many cells has a structure
+const STRUCTURE_RARITY: u64 = 3;
+// Number of cell plans cached before the cache is cleared
+const PLAN_CACHE_LIMIT: usize = 4 * 1024;
+// Standing stones of a stone circle, and their distance to the edge of the clearing
+const CIRCLE_STONES: usize = 12;
+const CIRCLE_MARGIN: f32 = 3.0;
+// Blueprint of a village house, top row first: walls (#), floor (.) and the doorway (D). Houses
+// south of the square are built upside down, so that every door opens onto it.
+const HOUSE: [&str; 5] = ["#######", "#.....#", "#.....#", "#.....#", "###D###"];
+// Bottom left corners of the house lots of a village, relative to its origin, north of the square
+// then south of it
+const HOUSE_LOTS: [(i32, i32); 6] = [(4, 27), (16, 27), (28, 27), (4, 8), (16, 8), (28, 8)];
+// Rows of the village square, relative to the origin; the doors of both rows open onto it
+const SQUARE_ROWS: std::ops::RangeInclusive<i32> = 13..=26;
+// One in this many lots is left empty
+const EMPTY_LOT_RARITY: u64 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum StructureKind {
+    /// Crumbling walls around a paved courtyard, with a gate in the middle of each side
+    Ruins,
+    /// A ring of standing stones around a clearing
+    StoneCircle,
+    /// Houses built from a blueprint around a square, each home to a villager
+    Village,
+}
+
+impl StructureKind {
+    pub const ALL: [StructureKind; 3] = [
+        StructureKind::Ruins,
+        StructureKind::StoneCircle,
+        StructureKind::Village,
+    ];
+
+    /// Width and height in tiles, more than a chunk of the default size
+    pub fn size(&self) -> (i32, i32) {
+        match self {
+            StructureKind::Ruins => (48, 40),
+            StructureKind::StoneCircle => (36, 36),
+            StructureKind::Village => (40, 40),
+        }
+    }
+
+    // Biomes the structure can be planned in, from the biome under its center
+    fn fits(&self, biome: BiomeType) -> bool {
+        match self {
+            StructureKind::Ruins => matches!(
+                biome,
+                BiomeType::Plains | BiomeType::Desert | BiomeType::Forest | BiomeType::Tundra
+            ),
+            StructureKind::StoneCircle => matches!(
+                biome,
+                BiomeType::Plains | BiomeType::Forest | BiomeType::Mountain | BiomeType::Tundra
+            ),
+            StructureKind::Village => matches!(biome, BiomeType::Plains | BiomeType::Forest),
+        }
+    }
+}
+
+/// Where a structure is, and everything needed to build any tile of it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StructurePlan {
+    pub kind: StructureKind,
+    /// World tile at the bottom left corner
+    pub origin: (i32, i32),
+    // World seed, for the details that vary from one structure to the next
+    seed: u32,
+}
+
+impl StructurePlan {
+    // Position of a world tile relative to the origin, if it's on the footprint
+    fn local(&self, tile: (i32, i32)) -> Option<(i32, i32)> {
+        let (width, height) = self.kind.size();
+        let x = tile.0 as i64 - self.origin.0 as i64;
+        let y = tile.1 as i64 - self.origin.1 as i64;
+        let inside = (0..width as i64).contains(&x) && (0..height as i64).contains(&y);
+        inside.then_some((x as i32, y as i32))
+    }
+
+    /// Whether a world tile is reserved by the structure
+    pub fn contains(&self, tile: (i32, i32)) -> bool {
+        self.local(tile).is_some()
+    }
+
+    pub fn center(&self) -> (i32, i32) {
+        let (width, height) = self.kind.size();
+        (self.origin.0 + width / 2, self.origin.1 + height / 2)
+    }
+
+    // Bottom left corners of the houses of a village, relative to the origin, and whether each is
+    // built upside down
+    fn houses(&self) -> Vec<((i32, i32), bool)> {
+        if self.kind != StructureKind::Village {
+            return Vec::new();
+        }
+        HOUSE_LOTS
+            .iter()
+            .enumerate()
+            .filter(|(_, lot)| {
+                seeded_hash(self.seed, ("lot", self.origin, **lot)) % EMPTY_LOT_RARITY != 0
+            })
+            .map(|(index, lot)| (*lot, index >= HOUSE_LOTS.len() / 2))
+            .collect()
+    }
+
+    /// World tiles in the middle of each house of a village, one per villager living there
+    pub fn homes(&self) -> Vec<(i32, i32)> {
+        let (width, height) = (HOUSE[0].len() as i32, HOUSE.len() as i32);
+        self.houses()
+            .into_iter()
+            .map(|((x, y), _)| {
+                (
+                    self.origin.0 + x + width / 2,
+                    self.origin.1 + y + height / 2,
+                )
+            })
+            .collect()
+    }
+
+    /// A world tile of the village square picked by `roll`, for villagers to stroll to
+    pub fn square_spot(&self, roll: u64) -> Option<(i32, i32)> {
+        if self.kind != StructureKind::Village {
+            return None;
+        }
+        let (width, _) = self.kind.size();
+        let columns = (width - 4) as u64;
+        let rows = (SQUARE_ROWS.end() - SQUARE_ROWS.start() + 1) as u64;
+        let (x, y) = ((roll % columns) as i32, (roll / columns % rows) as i32);
+        Some((
+            self.origin.0 + 2 + x,
+            self.origin.1 + SQUARE_ROWS.start() + y,
+        ))
+    }
+
+    /// Whether a world tile is on the square of a village, where villagers spend the day
+    pub fn on_square(&self, tile: (i32, i32)) -> bool {
+        let (width, _) = self.kind.size();
+        self.kind == StructureKind::Village
+            && self
+                .local(tile)
+                .is_some_and(|(x, y)| SQUARE_ROWS.contains(&y) && (2..width - 2).contains(&x))
+    }
+
+    /// Build the structure's part of a generated tile. Only the ground, decoration and resource
+    /// are set; water is left as is, so structures planned over a lake end up flooded.
+    pub fn realize(&self, tile: &mut Tile) {
+        let Some((x, y)) = self.local(tile.position) else {
+            return;
+        };
+        // clear what generation placed, whatever the structure builds
+        tile.decoration = Decoration::None;
+        tile.resource = ResourceType::None;
+        if tile.tile_type.is_water() {
+            return;
+        }
+        let (width, height) = self.kind.size();
+        match self.kind {
+            StructureKind::Ruins => {
+                let wall = x == 0 || y == 0 || x == width - 1 || y == height - 1;
+                let gate = (x - width / 2).abs() <= 1 || (y - height / 2).abs() <= 1;
+                // a third of the wall collapsed
+                let standing = seeded_hash(self.seed, ("ruins", tile.position)) % 3 != 0;
+                tile.tile_type = if wall && !gate && standing {
+                    TileType::Mountain
+                } else {
+                    TileType::Stone
+                };
+                if gate && !wall {
+                    // paved ways between the gates
+                    tile.decoration = Decoration::Path;
+                }
+            }
+            StructureKind::StoneCircle => {
+                let center = Vec2::new((width - 1) as f32, (height - 1) as f32) * 0.5;
+                let radius = width.min(height) as f32 * 0.5 - CIRCLE_MARGIN;
+                let position = Vec2::new(x as f32, y as f32);
+                let stone = (0..CIRCLE_STONES).any(|index| {
+                    let angle = index as f32 * TAU / CIRCLE_STONES as f32;
+                    let stone = center + Vec2::from_angle(angle) * radius;
+                    stone.round() == position
+                });
+                tile.tile_type = if stone {
+                    TileType::Mountain
+                } else if position.distance(center) <= radius {
+                    TileType::Grass
+                } else {
+                    tile.tile_type
+                };
+            }
+            StructureKind::Village => {
+                tile.tile_type = TileType::Grass;
+                if self.on_square(tile.position) {
+                    tile.decoration = Decoration::Path;
+                }
+                let (house_width, house_height) = (HOUSE[0].len() as i32, HOUSE.len() as i32);
+                for ((lot_x, lot_y), upside_down) in self.houses() {
+                    let (dx, dy) = (x - lot_x, y - lot_y);
+                    if !(0..house_width).contains(&dx) || !(0..house_height).contains(&dy) {
+                        continue;
+                    }
+                    let row = if upside_down {
+                        dy
+                    } else {
+                        house_height - 1 - dy
+                    };
+                    match HOUSE[row as usize].as_bytes()[dx as usize] {
+                        b'#' => tile.tile_type = TileType::Mountain,
+                        b'D' => {
+                            tile.tile_type = TileType::Stone;
+                            tile.decoration = Decoration::Path;
+                        }
+                        _ => tile.tile_type = TileType::Stone,
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Cell holding a world tile
+fn cell_of(tile: (i32, i32)) -> (i32, i32) {
+    (
+        tile.0.div_euclid(STRUCTURE_CELL),
+        tile.1.div_euclid(STRUCTURE_CELL),
+    )
+}
+
+/// The structure planned in a cell, if any. `biome` is the biome of a chunk, passed in so that
+/// generation can use the noise it already built.
+pub fn plan_structure(
+    cell: (i32, i32),
+    config: &WorldConfig,
+    biome: impl Fn(&ChunkCoord) -> BiomeType,
+) -> Option<StructurePlan> {
+    let hash = seeded_hash(config.seed, ("structure", cell));
+    if hash % STRUCTURE_RARITY != 0 {
+        return None;
+    }
+    let kind = StructureKind::ALL[(hash / STRUCTURE_RARITY) as usize % StructureKind::ALL.len()];
+    let (width, height) = kind.size();
+    // anywhere in the cell it fits
+    let offset_x = (hash >> 16) % (STRUCTURE_CELL - width + 1) as u64;
+    let offset_y = (hash >> 40) % (STRUCTURE_CELL - height + 1) as u64;
+    let origin = (
+        i32::try_from(cell.0 as i64 * STRUCTURE_CELL as i64 + offset_x as i64).ok()?,
+        i32::try_from(cell.1 as i64 * STRUCTURE_CELL as i64 + offset_y as i64).ok()?,
+    );
+    // the whole footprint must have valid tile coordinates
+    origin.0.checked_add(width)?;
+    origin.1.checked_add(height)?;
+    let plan = StructurePlan {
+        kind,
+        origin,
+        seed: config.seed,
+    };
+    if !kind.fits(biome(&config.grid().tile_to_chunk(plan.center()))) {
+        return None;
+    }
+    // hand-made terrain is left as it was made
+    if let Some(terrain) = &config.imported_terrain {
+        let corners = [
+            origin,
+            (origin.0 + width - 1, origin.1),
+            (origin.0, origin.1 + height - 1),
+            (origin.0 + width - 1, origin.1 + height - 1),
+        ];
+        if corners
+            .iter()
+            .any(|corner| terrain.height_at(corner.0, corner.1).is_some())
+        {
+            return None;
+        }
+    }
+    Some(plan)
+}
+
+/// Plans of the structures overlapping a chunk
+pub fn chunk_structures(
+    coord: &ChunkCoord,
+    config: &WorldConfig,
+    biome: impl Fn(&ChunkCoord) -> BiomeType,
+) -> Vec<StructurePlan> {
+    let size = config.chunk_size as i32;
+    if size == 0 {
+        return Vec::new();
+    }
+    let first = config.grid().chunk_origin(*coord);
+    let last = (first.0 + size - 1, first.1 + size - 1);
+    let (first_cell, last_cell) = (cell_of(first), cell_of(last));
+    let mut plans = Vec::new();
+    for cell_y in first_cell.1..=last_cell.1 {
+        for cell_x in first_cell.0..=last_cell.0 {
+            plans.extend(plan_structure((cell_x, cell_y), config, &biome));
+        }
+    }
+    // a plan can be in a neighbouring cell of the chunk without reaching it
+    plans.retain(|plan| {
+        let (width, height) = plan.kind.size();
+        plan.origin.0 <= last.0
+            && plan.origin.1 <= last.1
+            && plan.origin.0 + width > first.0
+            && plan.origin.1 + height > first.1
+    });
+    plans
+}
+
+/// Answers "is there a structure here" for any world tile, without generating chunks, so that
+/// features placed after generation can stay off structures. Plans are cached per cell.
+#[derive(Resource)]
+pub struct StructureMap {
+    config: WorldConfig,
+    // behind a mutex so that lookups only need `Res<StructureMap>`
+    plans: Mutex<HashMap<(i32, i32), Option<StructurePlan>>>,
+}
+
+impl FromWorld for StructureMap {
+    fn from_world(world: &mut World) -> Self {
+        let config = world
+            .get_resource::<WorldConfig>()
+            .cloned()
+            .unwrap_or_default();
+        StructureMap::new(config)
+    }
+}
+
+impl StructureMap {
+    pub fn new(config: WorldConfig) -> Self {
+        Self {
+            config,
+            plans: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The structure a world tile is part of
+    pub fn structure_at(&self, tile: (i32, i32)) -> Option<StructurePlan> {
+        let cell = cell_of(tile);
+        let mut plans = self.plans.lock().unwrap_or_else(|e| e.into_inner());
+        if plans.len() >= PLAN_CACHE_LIMIT && !plans.contains_key(&cell) {
+            plans.clear();
+        }
+        let plan = *plans.entry(cell).or_insert_with(|| {
+            plan_structure(cell, &self.config, |coord| chunk_biome(coord, &self.config))
+        });
+        plan.filter(|plan| plan.contains(tile))
+    }
+
+    /// Whether a world tile is reserved by a structure
+    pub fn is_reserved(&self, tile: (i32, i32)) -> bool {
+        self.structure_at(tile).is_some()
+    }
+}
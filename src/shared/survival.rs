@@ -0,0 +1,87 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// Where players come back after dying; clients point their compass at it
+pub const RESPAWN_POSITION: Vec2 = Vec2::ZERO;
+pub const MAX_HEALTH: f32 = 100.0;
+// Seconds of breath when fully rested
+pub const MAX_BREATH: f32 = 15.0;
+pub const MAX_HUNGER: f32 = 100.0;
+
+#[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self {
+            current: MAX_HEALTH,
+            max: MAX_HEALTH,
+        }
+    }
+}
+
+/// Breath left while swimming in deep water, in seconds
+#[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Breath {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Default for Breath {
+    fn default() -> Self {
+        Self {
+            current: MAX_BREATH,
+            max: MAX_BREATH,
+        }
+    }
+}
+
+/// How fed a player is; it drains over time and players starve once it is empty
+#[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Hunger {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Default for Hunger {
+    fn default() -> Self {
+        Self {
+            current: MAX_HUNGER,
+            max: MAX_HUNGER,
+        }
+    }
+}
+
+/// Asks the server to eat one of the held item if it is food, or else to drink
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct EatHeldItem;
+
+/// Multipliers applied to how fast breath runs out in deep water.
+/// Boats and the swim skill change these; the server only reads them.
+#[derive(Component, Clone, Debug, PartialEq)]
+pub struct SwimModifiers {
+    pub breath_drain: f32,
+    // Players in a boat don't drain breath at all
+    pub in_boat: bool,
+}
+
+impl Default for SwimModifiers {
+    fn default() -> Self {
+        Self {
+            breath_drain: 1.0,
+            in_boat: false,
+        }
+    }
+}
+
+/// Survival drains (breath...) are suspended for players with this marker, e.g. while idle
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq)]
+pub struct SurvivalPaused;
+
+/// Marks a player who died on a world with permadeath: they can move around and watch, but no
+/// longer take part in the game
+#[derive(Component, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct Spectator;
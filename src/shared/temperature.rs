@@ -0,0 +1,77 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::shared::world_generation::BiomeType;
+
+// Exposure at which players start taking damage
+pub const MAX_EXPOSURE: f32 = 100.0;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Weather {
+    #[default]
+    Clear,
+    // Only blows over tundras, making the cold worse
+    Blizzard,
+    // Only blows over deserts, making the heat worse
+    Sandstorm,
+}
+
+/// What a biome does to players standing in it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Hazard {
+    /// Kept away by holding a torch
+    Cold,
+    /// Kept away by drinking water
+    Heat,
+}
+
+impl Hazard {
+    pub fn of(biome: BiomeType) -> Option<Hazard> {
+        match biome {
+            BiomeType::Tundra => Some(Hazard::Cold),
+            BiomeType::Desert => Some(Hazard::Heat),
+            _ => None,
+        }
+    }
+
+    /// Multiplier the weather applies to the hazard
+    pub fn intensity(&self, weather: Weather) -> f32 {
+        match (self, weather) {
+            (Hazard::Cold, Weather::Blizzard) | (Hazard::Heat, Weather::Sandstorm) => 2.5,
+            _ => 1.0,
+        }
+    }
+
+    /// Whether the weather is a storm making this hazard worse
+    pub fn storm(&self, weather: Weather) -> bool {
+        self.intensity(weather) > 1.0
+    }
+}
+
+/// How cold or hot a player got. It builds up while they stand in a hazardous biome unprotected,
+/// goes back down elsewhere, and hurts them once it reaches `MAX_EXPOSURE`.
+#[derive(Component, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Exposure {
+    /// Hazard of the biome the player stands in
+    pub hazard: Option<Hazard>,
+    pub level: f32,
+    /// Whether the player is protected from the hazard right now
+    pub protected: bool,
+}
+
+/// Current weather, decided by the server and replicated to clients
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq)]
+pub struct CurrentWeather(pub Weather);
+
+/// Sent by the server to all clients when the weather changes, and to clients when they connect
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct WeatherChanged(pub Weather);
+
+#[derive(Clone)]
+pub struct TemperaturePlugin;
+
+impl Plugin for TemperaturePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CurrentWeather>();
+    }
+}
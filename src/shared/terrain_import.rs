@@ -0,0 +1,155 @@
+//! Hand-made terrain: grayscale images standing in for the height and biome noise over a bounded
+//! region of the world. Loaded by the server from its settings and carried by the `WorldConfig`,
+//! so that everything generating terrain uses it.
+use std::fmt;
+
+use crate::shared::error::GameError;
+
+/// Heights, and optionally biomes, of a region of the world read from grayscale images. Black is
+/// -1 and white is 1 on the same scale as the noise they replace.
+pub struct ImportedTerrain {
+    /// World tile at the bottom left corner of the images
+    pub origin: (i32, i32),
+    /// Tiles per pixel. Heights are interpolated between pixels when above 1.
+    pub scale: f32,
+    width: usize,
+    height: usize,
+    // Values from -1 to 1, row by row from the top of the image
+    heights: Vec<f32>,
+    biomes: Option<Vec<f32>>,
+}
+
+impl ImportedTerrain {
+    /// Read a heightmap and an optional biome map the same size as it. 8 and 16 bit images work.
+    pub fn load(
+        heightmap: &str,
+        biome_map: Option<&str>,
+        origin: (i32, i32),
+        scale: f32,
+    ) -> Result<Self, GameError> {
+        if !scale.is_finite() || scale <= 0.0 {
+            return Err(GameError::TerrainImport {
+                path: heightmap.to_string(),
+                reason: format!("scale must be positive, got {}", scale),
+            });
+        }
+        let (width, height, heights) = read_grayscale(heightmap)?;
+        let biomes = match biome_map {
+            Some(path) => {
+                let (biome_width, biome_height, biomes) = read_grayscale(path)?;
+                if (biome_width, biome_height) != (width, height) {
+                    return Err(GameError::TerrainImport {
+                        path: path.to_string(),
+                        reason: format!(
+                            "biome map is {}x{}, expected the heightmap's {}x{}",
+                            biome_width, biome_height, width, height
+                        ),
+                    });
+                }
+                Some(biomes)
+            }
+            None => None,
+        };
+        Ok(Self::from_pixels(width, heights, biomes, origin, scale))
+    }
+
+    /// Terrain from pixel values from -1 to 1, row by row from the top. `biomes`, if any, has as
+    /// many pixels as `heights`, and both are a whole number of non-empty rows.
+    pub fn from_pixels(
+        width: usize,
+        heights: Vec<f32>,
+        biomes: Option<Vec<f32>>,
+        origin: (i32, i32),
+        scale: f32,
+    ) -> Self {
+        Self {
+            origin,
+            scale,
+            width,
+            height: heights.len() / width,
+            heights,
+            biomes,
+        }
+    }
+
+    // Position of a world tile in pixels, from the bottom left pixel, if it's in the region
+    fn pixel(&self, world_x: i32, world_y: i32) -> Option<(f32, f32)> {
+        let x = (world_x as i64 - self.origin.0 as i64) as f32 / self.scale;
+        let y = (world_y as i64 - self.origin.1 as i64) as f32 / self.scale;
+        let inside =
+            x >= 0.0 && y >= 0.0 && x <= (self.width - 1) as f32 && y <= (self.height - 1) as f32;
+        inside.then_some((x, y))
+    }
+
+    fn value(values: &[f32], width: usize, height: usize, x: usize, y: usize) -> f32 {
+        // image rows go top down while the world goes bottom up
+        values[(height - 1 - y) * width + x]
+    }
+
+    /// Height of a world tile, or `None` outside the region
+    pub fn height_at(&self, world_x: i32, world_y: i32) -> Option<f32> {
+        let (x, y) = self.pixel(world_x, world_y)?;
+        let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+        let (x1, y1) = ((x0 + 1).min(self.width - 1), (y0 + 1).min(self.height - 1));
+        let (fx, fy) = (x.fract(), y.fract());
+        let at = |x, y| Self::value(&self.heights, self.width, self.height, x, y);
+        let bottom = at(x0, y0) * (1.0 - fx) + at(x1, y0) * fx;
+        let top = at(x0, y1) * (1.0 - fx) + at(x1, y1) * fx;
+        Some(bottom * (1.0 - fy) + top * fy)
+    }
+
+    /// Biome value of a world tile, to go through the biome thresholds like the biome noise.
+    /// `None` outside the region or without a biome map.
+    pub fn biome_at(&self, world_x: i32, world_y: i32) -> Option<f64> {
+        let biomes = self.biomes.as_ref()?;
+        let (x, y) = self.pixel(world_x, world_y)?;
+        // biomes don't blend, so take the nearest pixel
+        let value = Self::value(
+            biomes,
+            self.width,
+            self.height,
+            x.round() as usize,
+            y.round() as usize,
+        );
+        Some(value as f64)
+    }
+}
+
+// Imports are shared through the world config and never modified, so they're the same import
+// when they're the same object
+impl PartialEq for ImportedTerrain {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl fmt::Debug for ImportedTerrain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ImportedTerrain")
+            .field("origin", &self.origin)
+            .field("scale", &self.scale)
+            .field("pixels", &(self.width, self.height))
+            .field("biomes", &self.biomes.is_some())
+            .finish()
+    }
+}
+
+// Width, height and pixels of a grayscale image, mapped to -1..1
+fn read_grayscale(path: &str) -> Result<(usize, usize, Vec<f32>), GameError> {
+    let error = |reason: String| GameError::TerrainImport {
+        path: path.to_string(),
+        reason,
+    };
+    let image = image::open(path).map_err(|e| error(e.to_string()))?;
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    if width == 0 || height == 0 {
+        return Err(error("image is empty".to_string()));
+    }
+    let values = image
+        .to_luma32f()
+        .into_raw()
+        .into_iter()
+        .map(|value| value * 2.0 - 1.0)
+        .collect();
+    Ok((width, height, values))
+}
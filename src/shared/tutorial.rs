@@ -0,0 +1,54 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Steps of the tutorial, in order
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum TutorialStep {
+    #[default]
+    Move,
+    HoldAxe,
+    HarvestTree,
+    RaiseTerrain,
+}
+
+impl TutorialStep {
+    pub const ALL: [TutorialStep; 4] = [
+        TutorialStep::Move,
+        TutorialStep::HoldAxe,
+        TutorialStep::HarvestTree,
+        TutorialStep::RaiseTerrain,
+    ];
+
+    pub fn index(&self) -> usize {
+        TutorialStep::ALL
+            .iter()
+            .position(|step| step == self)
+            .unwrap_or(0)
+    }
+
+    pub fn next(&self) -> Option<TutorialStep> {
+        TutorialStep::ALL.get(self.index() + 1).copied()
+    }
+
+    // What the HUD tells the player to do
+    pub fn prompt(&self) -> &'static str {
+        match self {
+            TutorialStep::Move => "Walk around with the arrow keys",
+            TutorialStep::HoldAxe => "Hold your axe: pick its hotbar slot with the number keys",
+            TutorialStep::HarvestTree => {
+                "Walk up to the marked tree, hover it and press H until it falls"
+            }
+            TutorialStep::RaiseTerrain => {
+                "Hover grass or sand next to you and press T to raise the ground"
+            }
+        }
+    }
+}
+
+/// Present on players going through the tutorial; removed once they complete or skip it
+#[derive(Component, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Tutorial {
+    pub step: TutorialStep,
+    // Where the current step takes place, if anywhere in particular
+    pub marker: Option<Vec2>,
+}
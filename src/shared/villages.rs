@@ -0,0 +1,60 @@
+//! Villagers living in the villages generated with the world. Each has a home in one of the
+//! village houses, spends the day on the village square and the night at home, and trades with
+//! players who talk to them.
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::shared::items::{ItemKind, ItemStack};
+
+/// Items a villager gives a player in exchange for others
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Trade {
+    pub wants: ItemStack,
+    pub gives: ItemStack,
+}
+
+const fn trade(wants: ItemKind, wanted: u32, gives: ItemKind, given: u32) -> Trade {
+    Trade {
+        wants: ItemStack {
+            kind: wants,
+            count: wanted,
+        },
+        gives: ItemStack {
+            kind: gives,
+            count: given,
+        },
+    }
+}
+
+/// Trades villagers offer; each villager offers one of them
+pub const TRADES: [Trade; 5] = [
+    trade(ItemKind::Fish, 3, ItemKind::Iron, 1),
+    trade(ItemKind::Wood, 10, ItemKind::Coal, 2),
+    trade(ItemKind::Berries, 6, ItemKind::Stew, 1),
+    trade(ItemKind::Stone, 12, ItemKind::Copper, 2),
+    trade(ItemKind::Gold, 1, ItemKind::Torch, 4),
+];
+
+/// Names given to villagers
+pub const VILLAGER_NAMES: [&str; 12] = [
+    "Alder", "Bryn", "Cael", "Dara", "Edda", "Fenn", "Greta", "Holt", "Isla", "Joss", "Kira",
+    "Lorne",
+];
+
+/// An NPC living in a village, put on the same entity as its `Npc`
+#[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Villager {
+    pub name: String,
+    /// World tile at the bottom left corner of the village
+    pub village: (i32, i32),
+    /// World tile in the middle of the house the villager lives in
+    pub home: (i32, i32),
+    pub trade: Trade,
+}
+
+/// A villager and where it stood, saved with its village
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct VillagerRecord {
+    pub villager: Villager,
+    pub position: Vec2,
+}
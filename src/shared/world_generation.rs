@@ -1,13 +1,24 @@
 use bevy::prelude::*;
-use lightyear::client::components::ComponentSyncMode;
+use bevy::tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task};
 use lightyear::prelude::*;
 use noise::{NoiseFn, Perlin, Seedable};
 use rand::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+use crate::shared::biome_map::BiomeMap;
+use crate::shared::chunk_format::{self, ChunkFormat};
+use crate::shared::day_night::DayPhase;
+use crate::shared::error::GameError;
+use crate::shared::frame_pacing::{FrameBudget, FrameLoad};
+use crate::shared::height_map::HeightMap;
+use crate::shared::structures::{chunk_structures, StructureMap, StructurePlan};
+use crate::shared::terrain_import::ImportedTerrain;
 
 // World generation configuration
-#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct WorldConfig {
     pub seed: u32,
     pub chunk_size: usize,
@@ -15,6 +26,41 @@ pub struct WorldConfig {
     pub biome_scale: f64,
     pub height_scale: f64,
     pub resource_density: f32,
+    #[serde(default)]
+    pub biome_thresholds: BiomeThresholds,
+    /// Hand-made terrain replacing the noise over part of the world. Loaded from the server
+    /// settings at startup rather than saved with the config.
+    #[serde(skip)]
+    pub imported_terrain: Option<Arc<ImportedTerrain>>,
+}
+
+/// Upper bound of each biome on the biome noise, which goes from -1 to 1. The bounds must be
+/// increasing; tundra takes everything above the last one.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BiomeThresholds {
+    pub ocean: f64,
+    pub desert: f64,
+    pub plains: f64,
+    pub forest: f64,
+    pub mountain: f64,
+}
+
+impl Default for BiomeThresholds {
+    fn default() -> Self {
+        Self {
+            ocean: -0.6,
+            desert: -0.3,
+            plains: 0.1,
+            forest: 0.4,
+            mountain: 0.7,
+        }
+    }
+}
+
+impl WorldConfig {
+    pub fn grid(&self) -> WorldGrid {
+        WorldGrid::new(self.chunk_size)
+    }
 }
 
 impl Default for WorldConfig {
@@ -26,6 +72,8 @@ impl Default for WorldConfig {
             biome_scale: 0.03,
             height_scale: 0.05,
             resource_density: 0.02,
+            biome_thresholds: BiomeThresholds::default(),
+            imported_terrain: None,
         }
     }
 }
@@ -37,11 +85,109 @@ pub struct ChunkCoord {
     pub y: i32,
 }
 
+impl ChunkCoord {
+    /// Whether every tile of the chunk has coordinates that fit in an `i32`.
+    /// Chunks outside of that range can't be generated, so requests for them are rejected.
+    pub fn is_in_world(&self, chunk_size: usize) -> bool {
+        let size = chunk_size as i32;
+        [self.x, self.y].iter().all(|c| {
+            c.checked_mul(size)
+                .and_then(|first| first.checked_add(size - 1))
+                .is_some()
+        })
+    }
+}
+
+/// Conversions between the coordinate spaces of the world. Everything converting between them
+/// goes through here, so that they agree whatever the chunk size:
+/// - world positions (`Vec2`), used by movement, rendering and the camera. One unit is one tile.
+/// - world tiles `(i32, i32)`. A tile is centered on its coordinates, so a position belongs to
+///   the nearest tile.
+/// - chunks, and the position `(local_x, local_y)` of a tile inside its chunk
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WorldGrid {
+    pub chunk_size: usize,
+}
+
+impl WorldGrid {
+    /// Size of a tile in world units
+    pub const TILE_SIZE: f32 = 1.0;
+
+    pub fn new(chunk_size: usize) -> Self {
+        WorldGrid { chunk_size }
+    }
+
+    // Tile containing a world position
+    pub fn world_to_tile(world: Vec2) -> (i32, i32) {
+        let tile = (world / Self::TILE_SIZE).round();
+        (tile.x as i32, tile.y as i32)
+    }
+
+    // World position of the center of a tile
+    pub fn tile_to_world(tile: (i32, i32)) -> Vec2 {
+        Vec2::new(tile.0 as f32, tile.1 as f32) * Self::TILE_SIZE
+    }
+
+    // Chunk containing the given world tile
+    pub fn tile_to_chunk(&self, tile: (i32, i32)) -> ChunkCoord {
+        let size = self.chunk_size as i32;
+        ChunkCoord {
+            x: tile.0.div_euclid(size),
+            y: tile.1.div_euclid(size),
+        }
+    }
+
+    // Position of a world tile inside its chunk, as (local_x, local_y)
+    pub fn tile_to_local(&self, tile: (i32, i32)) -> (usize, usize) {
+        let size = self.chunk_size as i32;
+        (
+            tile.0.rem_euclid(size) as usize,
+            tile.1.rem_euclid(size) as usize,
+        )
+    }
+
+    // World tile at a position inside a chunk
+    pub fn local_to_tile(&self, coord: ChunkCoord, local: (usize, usize)) -> (i32, i32) {
+        let (first_x, first_y) = self.chunk_origin(coord);
+        (first_x + local.0 as i32, first_y + local.1 as i32)
+    }
+
+    // First (bottom left) tile of a chunk
+    pub fn chunk_origin(&self, coord: ChunkCoord) -> (i32, i32) {
+        let size = self.chunk_size as i32;
+        (coord.x * size, coord.y * size)
+    }
+
+    // Chunk containing a world position
+    pub fn world_to_chunk(&self, world: Vec2) -> ChunkCoord {
+        self.tile_to_chunk(Self::world_to_tile(world))
+    }
+
+    // World position of the center of a chunk's first tile, where its tiles are rendered from
+    pub fn chunk_to_world(&self, coord: ChunkCoord) -> Vec2 {
+        Self::tile_to_world(self.chunk_origin(coord))
+    }
+}
+
+/// Chebyshev distance between two world tiles. Never overflows, whatever coordinates a client sends.
+pub fn tile_distance(a: (i32, i32), b: (i32, i32)) -> u32 {
+    a.0.abs_diff(b.0).max(a.1.abs_diff(b.1))
+}
+
+/// Hash of `value` that only depends on the world seed, for placing things deterministically
+pub fn seeded_hash(seed: u32, value: impl Hash) -> u64 {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    seed.hash(&mut hasher);
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
 // Tile types that can exist in the world
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum TileType {
     Grass,
-    Water,
+    ShallowWater,
+    DeepWater,
     Sand,
     Stone,
     Forest,
@@ -49,8 +195,25 @@ pub enum TileType {
     Snow,
 }
 
+impl TileType {
+    pub fn is_water(&self) -> bool {
+        matches!(self, TileType::ShallowWater | TileType::DeepWater)
+    }
+
+    /// Whether the ground can be crossed but harms whoever stays on it: deep water drowns
+    /// players, so walkers keep out of it
+    pub fn is_hazard(&self) -> bool {
+        matches!(self, TileType::DeepWater)
+    }
+
+    /// Whether the ground blocks sight: forests and mountains do, open ground and water don't
+    pub fn is_opaque(&self) -> bool {
+        matches!(self, TileType::Forest | TileType::Mountain)
+    }
+}
+
 // Resources that can be found in the world
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ResourceType {
     None,
     Iron,
@@ -59,10 +222,69 @@ pub enum ResourceType {
     Gold,
     Tree,
     Stone,
+    Fish,
 }
 
+impl ResourceType {
+    // Every resource node, leaving out `None`
+    pub const NODES: [ResourceType; 7] = [
+        ResourceType::Tree,
+        ResourceType::Stone,
+        ResourceType::Coal,
+        ResourceType::Copper,
+        ResourceType::Iron,
+        ResourceType::Gold,
+        ResourceType::Fish,
+    ];
+
+    // Parts of the day during which the resource can be seen and harvested
+    pub fn schedule(&self) -> &'static [DayPhase] {
+        match self {
+            // fish only bite around sunrise and sunset
+            ResourceType::Fish => &[DayPhase::Dawn, DayPhase::Dusk],
+            // gold veins only glint in the moonlight
+            ResourceType::Gold => &[DayPhase::Dusk, DayPhase::Night],
+            _ => &[
+                DayPhase::Dawn,
+                DayPhase::Day,
+                DayPhase::Dusk,
+                DayPhase::Night,
+            ],
+        }
+    }
+
+    pub fn is_available(&self, phase: DayPhase) -> bool {
+        self.schedule().contains(&phase)
+    }
+
+    // Number of harvesting hits the resource node takes before it's depleted
+    pub fn max_health(&self) -> u8 {
+        match self {
+            ResourceType::None => 0,
+            ResourceType::Fish => 1,
+            ResourceType::Tree => 3,
+            ResourceType::Stone | ResourceType::Coal => 4,
+            ResourceType::Iron | ResourceType::Copper | ResourceType::Gold => 5,
+        }
+    }
+
+    /// Visual damage state of a node that took `damage` hits, from 0 (intact) to
+    /// `DAMAGE_TIERS - 1` (about to break)
+    pub fn damage_tier(&self, damage: u8) -> u8 {
+        let health = self.max_health();
+        if health == 0 || damage == 0 {
+            return 0;
+        }
+        let tier = (damage as u32 * DAMAGE_TIERS as u32).div_ceil(health as u32) as u8;
+        tier.min(DAMAGE_TIERS - 1)
+    }
+}
+
+// Number of visual damage states of a resource node, including the intact one
+pub const DAMAGE_TIERS: u8 = 3;
+
 // Biomes used for world generation and determining tile types
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum BiomeType {
     Plains,
     Ocean,
@@ -72,23 +294,525 @@ pub enum BiomeType {
     Tundra,
 }
 
-// A single tile in the world
+impl BiomeType {
+    pub const ALL: [BiomeType; 6] = [
+        BiomeType::Plains,
+        BiomeType::Ocean,
+        BiomeType::Desert,
+        BiomeType::Forest,
+        BiomeType::Mountain,
+        BiomeType::Tundra,
+    ];
+}
+
+/// Decoration layer of a tile, drawn over the ground. Flowers, grass tufts, pebbles, rocks and
+/// shells are scattered by world generation and purely visual; structures built by players
+/// change how the tile is used.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Decoration {
+    #[default]
+    None,
+    Flowers,
+    GrassTufts,
+    Pebbles,
+    Rocks,
+    /// Only scattered next to water
+    Shells,
+    /// Trail laid by world generation and through villages, always walkable
+    Path,
+    /// Lets players climb the cliff next to it
+    Stairs,
+    /// Cooks the items put in it
+    Furnace,
+    /// Blocks the way while closed
+    Door {
+        open: bool,
+    },
+    /// Blocks the way while closed, like a door
+    Gate {
+        open: bool,
+    },
+    /// Track carts roll along
+    Rail,
+    /// Holds items
+    Chest,
+    /// Moves items from the container behind it to the one it faces
+    Inserter {
+        facing: Facing,
+    },
+    /// Feeds power to the network it is part of
+    Generator,
+    /// Carries power between its neighbours
+    Wire,
+    /// Follows recipes with the items put in it, drawing power from its network
+    Assembler,
+}
+
+/// One of the four directions along the tile grid
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Facing {
+    #[default]
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Facing {
+    /// Tile step in this direction
+    pub fn offset(&self) -> (i32, i32) {
+        match self {
+            Facing::North => (0, 1),
+            Facing::East => (1, 0),
+            Facing::South => (0, -1),
+            Facing::West => (-1, 0),
+        }
+    }
+
+    /// Direction from `from` to `to`, along the axis they are furthest apart on
+    pub fn towards(from: (i32, i32), to: (i32, i32)) -> Facing {
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        if dx.abs() > dy.abs() {
+            if dx > 0 {
+                Facing::East
+            } else {
+                Facing::West
+            }
+        } else if dy < 0 {
+            Facing::South
+        } else {
+            Facing::North
+        }
+    }
+}
+
+impl Decoration {
+    /// Whether the decoration was built by a player, and nothing else can be built over it
+    pub fn is_structure(&self) -> bool {
+        matches!(
+            self,
+            Decoration::Stairs
+                | Decoration::Furnace
+                | Decoration::Door { .. }
+                | Decoration::Gate { .. }
+                | Decoration::Rail
+                | Decoration::Chest
+                | Decoration::Inserter { .. }
+                | Decoration::Generator
+                | Decoration::Wire
+                | Decoration::Assembler
+        )
+    }
+
+    /// Whether the decoration holds items: chests, and furnaces and assemblers following recipes
+    /// with what they are given
+    pub fn is_container(&self) -> bool {
+        matches!(
+            self,
+            Decoration::Chest | Decoration::Furnace | Decoration::Assembler
+        )
+    }
+
+    /// Whether the decoration is part of a power network, passing power on to its neighbours
+    pub fn is_power_node(&self) -> bool {
+        matches!(
+            self,
+            Decoration::Generator | Decoration::Wire | Decoration::Assembler
+        )
+    }
+
+    /// Whether players can open and close the decoration
+    pub fn is_openable(&self) -> bool {
+        matches!(self, Decoration::Door { .. } | Decoration::Gate { .. })
+    }
+
+    /// Whether the decoration is a closed door or gate, which can't be walked through
+    pub fn is_closed(&self) -> bool {
+        matches!(
+            self,
+            Decoration::Door { open: false } | Decoration::Gate { open: false }
+        )
+    }
+
+    /// The same door or gate, opened or closed
+    pub fn toggled(&self) -> Decoration {
+        match *self {
+            Decoration::Door { open } => Decoration::Door { open: !open },
+            Decoration::Gate { open } => Decoration::Gate { open: !open },
+            other => other,
+        }
+    }
+}
+
+// Overlay layer of a tile, drawn over everything else: cover lying on top of the ground
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Overlay {
+    #[default]
+    None,
+    Snow,
+}
+
+// A single tile in the world. Its layers, bottom to top, are the ground (`tile_type`), the
+// decoration, the resource node and the overlay; each can be modified on its own.
 #[derive(Clone, Debug, Component, Serialize, Deserialize, PartialEq)]
 pub struct Tile {
     pub tile_type: TileType,
+    pub decoration: Decoration,
     pub resource: ResourceType,
+    pub overlay: Overlay,
     pub height: f32,
     pub position: (i32, i32), // World coordinates
     pub traversable: bool,
+    pub damage: u8, // Hits taken by the tile's resource node
+}
+
+/// Heights are grouped into elevation levels this high. Neighbouring tiles more than one level
+/// apart are separated by a cliff.
+pub const ELEVATION_STEP: f32 = 0.15;
+
+// Elevation level of a height
+pub fn elevation_level(height: f32) -> i32 {
+    (height / ELEVATION_STEP).floor() as i32
+}
+
+// Whether two neighbouring heights are separated by a cliff
+pub fn is_cliff(a: f32, b: f32) -> bool {
+    (elevation_level(a) - elevation_level(b)).abs() > 1
+}
+
+impl Tile {
+    /// Whether a player can step from this tile onto a neighbouring one: never up or down a
+    /// cliff, unless there are stairs on either side, and never into a closed door
+    pub fn can_step_to(&self, to: &Tile) -> bool {
+        if to.decoration.is_closed() {
+            return false;
+        }
+        !is_cliff(self.height, to.height)
+            || self.decoration == Decoration::Stairs
+            || to.decoration == Decoration::Stairs
+    }
+
+    /// Whether nothing can be seen through the tile: opaque ground, trees and closed doors and
+    /// gates all block sight
+    pub fn is_opaque(&self) -> bool {
+        self.tile_type.is_opaque()
+            || self.resource == ResourceType::Tree
+            || self.decoration.is_closed()
+    }
+}
+
+/// One bit per tile of a chunk, row by row, for queries that only need a single fact about each
+/// tile (e.g. whether it blocks sight) and shouldn't have to go through whole tiles
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct TileBitmap {
+    size: usize,
+    bits: Vec<u64>,
+}
+
+impl TileBitmap {
+    /// Bitmap of a chunk `size` tiles wide, from its tiles row by row
+    pub fn from_tiles(size: usize, tiles: &[Tile], bit: impl Fn(&Tile) -> bool) -> Self {
+        let mut bitmap = TileBitmap {
+            size,
+            bits: vec![0; (size * size).div_ceil(64)],
+        };
+        for (index, tile) in tiles.iter().enumerate().take(size * size) {
+            bitmap.set(index % size, index / size, bit(tile));
+        }
+        bitmap
+    }
+
+    fn index(&self, local_x: usize, local_y: usize) -> Option<usize> {
+        (local_x < self.size && local_y < self.size).then_some(local_y * self.size + local_x)
+    }
+
+    /// Bit of a tile; tiles outside of the chunk are never set
+    pub fn get(&self, local_x: usize, local_y: usize) -> bool {
+        self.index(local_x, local_y)
+            .and_then(|index| Some((self.bits.get(index / 64)? >> (index % 64)) & 1 == 1))
+            .unwrap_or(false)
+    }
+
+    pub fn set(&mut self, local_x: usize, local_y: usize, value: bool) {
+        let Some(index) = self.index(local_x, local_y) else {
+            return;
+        };
+        if let Some(word) = self.bits.get_mut(index / 64) {
+            let mask = 1 << (index % 64);
+            if value {
+                *word |= mask;
+            } else {
+                *word &= !mask;
+            }
+        }
+    }
+
+    // Whether the bitmap covers exactly a chunk of the given size
+    fn fits(&self, size: usize) -> bool {
+        self.size == size && self.bits.len() == (size * size).div_ceil(64)
+    }
 }
 
-// A chunk containing multiple tiles
-#[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq)]
+// A chunk containing multiple tiles. Tiles are only reached through the accessors below, so that
+// what is baked from them always follows their modifications.
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
 pub struct Chunk {
     pub coord: ChunkCoord,
-    pub tiles: Vec<Vec<Tile>>,
+    // Width and height of the chunk, in tiles
+    size: usize,
+    // Tiles row by row: the tile at `(local_x, local_y)` is at `local_y * size + local_x`
+    tiles: Vec<Tile>,
     pub biome_type: BiomeType,
     pub last_accessed: f64, // Used for unloading inactive chunks
+    // Which tiles block sight, baked from the tiles so that line of sight never reads them
+    opacity: TileBitmap,
+    // Modifications of the chunk so far, so that clients can tell which deltas they are missing
+    version: u32,
+    // Tiles modified since the last delta was taken, and how many modifications that was. Only
+    // the server takes deltas, so neither is saved nor sent.
+    #[serde(skip)]
+    modified: Vec<usize>,
+    #[serde(skip)]
+    unsent: u32,
+}
+
+// Chunks are equal when their contents are, whatever deltas are waiting to be taken
+impl PartialEq for Chunk {
+    fn eq(&self, other: &Self) -> bool {
+        self.coord == other.coord
+            && self.size == other.size
+            && self.tiles == other.tiles
+            && self.biome_type == other.biome_type
+            && self.last_accessed == other.last_accessed
+            && self.opacity == other.opacity
+            && self.version == other.version
+    }
+}
+
+/// Whether a chunk version comes after another. Versions wrap around: a version is newer if it's
+/// less than half of them ahead.
+pub fn is_newer_version(version: u32, than: u32) -> bool {
+    (version.wrapping_sub(than) as i32) > 0
+}
+
+/// What became of a delta applied to a chunk
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeltaOutcome {
+    Applied,
+    /// The chunk already had these modifications
+    Stale,
+    /// The chunk is missing modifications made before the delta, or the delta doesn't fit it: it
+    /// has to be sent whole again
+    Missed,
+}
+
+impl Chunk {
+    /// A chunk `size` tiles wide, from its tiles row by row
+    pub fn new(
+        coord: ChunkCoord,
+        size: usize,
+        tiles: Vec<Tile>,
+        biome_type: BiomeType,
+        last_accessed: f64,
+    ) -> Self {
+        let opacity = TileBitmap::from_tiles(size, &tiles, Tile::is_opaque);
+        Chunk {
+            coord,
+            size,
+            tiles,
+            biome_type,
+            last_accessed,
+            opacity,
+            version: 0,
+            modified: Vec::new(),
+            unsent: 0,
+        }
+    }
+
+    /// The chunk at the version it was saved or sent at
+    pub fn with_version(mut self, version: u32) -> Self {
+        self.version = version;
+        self
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Carry on from the version of a copy of the chunk this one replaces, e.g. when it's rebuilt
+    /// from scratch, so that clients holding that copy take this one as newer
+    pub fn continue_from(&mut self, previous: u32) {
+        if !is_newer_version(self.version, previous) {
+            self.version = previous.wrapping_add(1);
+        }
+    }
+
+    /// Width and height of the chunk, in tiles
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    fn index(&self, local_x: usize, local_y: usize) -> Option<usize> {
+        (local_x < self.size && local_y < self.size).then_some(local_y * self.size + local_x)
+    }
+
+    pub fn get_tile(&self, local_x: usize, local_y: usize) -> Option<&Tile> {
+        self.tiles.get(self.index(local_x, local_y)?)
+    }
+
+    /// Modify a tile in place, and update what is baked from it. Returns what `modify` returned,
+    /// or `None` for tiles outside of the chunk.
+    pub fn modify_tile<R>(
+        &mut self,
+        local_x: usize,
+        local_y: usize,
+        modify: impl FnOnce(&mut Tile) -> R,
+    ) -> Option<R> {
+        let index = self.index(local_x, local_y)?;
+        let tile = self.tiles.get_mut(index)?;
+        let result = modify(tile);
+        let opaque = tile.is_opaque();
+        self.opacity.set(local_x, local_y, opaque);
+        self.version = self.version.wrapping_add(1);
+        self.modified.push(index);
+        self.unsent += 1;
+        Some(result)
+    }
+
+    /// The tiles modified since the previous delta, if any were
+    pub fn take_delta(&mut self) -> Option<ChunkDelta> {
+        if self.unsent == 0 {
+            return None;
+        }
+        let mut indices = std::mem::take(&mut self.modified);
+        indices.sort_unstable();
+        indices.dedup();
+        let delta = ChunkDelta {
+            coord: self.coord,
+            base: self.version.wrapping_sub(self.unsent),
+            version: self.version,
+            tiles: indices
+                .into_iter()
+                .map(|index| (index as u32, self.tiles[index].clone()))
+                .collect(),
+        };
+        self.unsent = 0;
+        Some(delta)
+    }
+
+    /// Bring the chunk to the version of a delta taken from the server's copy of it. The chunk is
+    /// left untouched unless the delta applies to the version it is at.
+    pub fn apply_delta(&mut self, delta: &ChunkDelta) -> DeltaOutcome {
+        if delta.coord != self.coord {
+            return DeltaOutcome::Missed;
+        }
+        if self.version != delta.base {
+            return if is_newer_version(delta.version, self.version) {
+                DeltaOutcome::Missed
+            } else {
+                DeltaOutcome::Stale
+            };
+        }
+        let fits = delta.tiles.iter().all(|(index, tile)| {
+            self.tiles
+                .get(*index as usize)
+                .is_some_and(|current| current.position == tile.position)
+        });
+        if !fits {
+            return DeltaOutcome::Missed;
+        }
+        for (index, tile) in &delta.tiles {
+            let index = *index as usize;
+            self.opacity
+                .set(index % self.size, index / self.size, tile.is_opaque());
+            self.tiles[index] = tile.clone();
+        }
+        self.version = delta.version;
+        DeltaOutcome::Applied
+    }
+
+    /// All the tiles, row by row
+    pub fn tiles(&self) -> &[Tile] {
+        &self.tiles
+    }
+
+    /// First tile players can stand on out of the water and outside of structures, going through
+    /// the tiles row by row from `start` and wrapping around. Things placed at a seeded spot of the
+    /// chunk go there, never on the `excluded` tile.
+    pub fn first_dry_tile(
+        &self,
+        start: usize,
+        structures: &StructureMap,
+        excluded: Option<(i32, i32)>,
+    ) -> Option<(i32, i32)> {
+        let start = start % self.tiles.len().max(1);
+        self.tiles[start..]
+            .iter()
+            .chain(&self.tiles[..start])
+            .find(|tile| {
+                tile.traversable
+                    && !tile.tile_type.is_water()
+                    && !structures.is_reserved(tile.position)
+                    && Some(tile.position) != excluded
+            })
+            .map(|tile| tile.position)
+    }
+
+    /// The rows of tiles, from `local_y` 0 up
+    pub fn rows(&self) -> impl Iterator<Item = &[Tile]> {
+        self.tiles.chunks(self.size.max(1))
+    }
+
+    /// Whether a tile blocks sight, without reading the tile itself
+    pub fn is_opaque(&self, local_x: usize, local_y: usize) -> bool {
+        self.opacity.get(local_x, local_y)
+    }
+
+    // Hash of the tile contents, used to detect unexpected modifications
+    pub fn checksum(&self) -> u64 {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        self.coord.hash(&mut hasher);
+        for tile in &self.tiles {
+            tile.tile_type.hash(&mut hasher);
+            tile.decoration.hash(&mut hasher);
+            tile.resource.hash(&mut hasher);
+            tile.overlay.hash(&mut hasher);
+            tile.height.to_bits().hash(&mut hasher);
+            tile.position.hash(&mut hasher);
+            tile.traversable.hash(&mut hasher);
+            tile.damage.hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    /// Whether the chunk is square, every tile's position matches its place in the chunk, and
+    /// the baked bitmaps cover the whole chunk. Everything indexing into `tiles` relies on it.
+    pub fn is_well_formed(&self) -> bool {
+        let size = self.size;
+        let grid = WorldGrid::new(size);
+        size > 0
+            && self.tiles.len() == size * size
+            && self.opacity.fits(size)
+            && self.tiles.iter().enumerate().all(|(index, tile)| {
+                grid.tile_to_chunk(tile.position) == self.coord
+                    && grid.tile_to_local(tile.position) == (index % size, index / size)
+            })
+    }
+
+    /// Check a chunk received from elsewhere before storing it in a world of the given chunk size
+    pub fn validate(&self, chunk_size: usize) -> Result<(), GameError> {
+        if self.size != chunk_size {
+            return Err(GameError::ChunkSize {
+                coord: self.coord,
+                expected: chunk_size,
+                actual: self.size,
+            });
+        }
+        if !self.is_well_formed() {
+            return Err(GameError::MalformedChunk(self.coord));
+        }
+        Ok(())
+    }
 }
 
 // Tracks the world state including all generated chunks
@@ -98,25 +822,58 @@ pub struct WorldState {
     pub active_chunks: HashSet<ChunkCoord>,  // Currently active chunks
     pub generation_time: HashMap<ChunkCoord, f64>, // Performance tracking
     pub world_time: f64,                     // In-game time (could drive day/night cycles)
+    pub pending_chunks: HashMap<ChunkCoord, Entity>, // Chunks being generated in the background
+    // Chunks never generated, e.g. because their saved copy couldn't be read and must be kept
+    pub held_chunks: HashSet<ChunkCoord>,
 }
 
-// Channel for world chunk data transmission
+// Channel for chunk requests and tile updates
 #[derive(Channel)]
 pub struct ChunkChannel;
 
+/// Channel for bulk terrain (`ChunkData`). Unordered, so one slow chunk doesn't hold back the
+/// others, and low priority, so terrain streaming never delays inputs or chat.
+#[derive(Channel)]
+pub struct ChunkStreamChannel;
+
+// Priority of `ChunkStreamChannel` relative to the default priority (1.0) of the other channels
+pub const CHUNK_STREAM_PRIORITY: f32 = 0.2;
+
 // Message for requesting chunks
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct ChunkRequest {
     pub coord: ChunkCoord,
+    /// Best format the client can read the chunk in
+    pub accepts: ChunkFormat,
 }
 
-// Message for sending chunk data
+// Message for sending chunk data, encoded in the format negotiated with the client
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct ChunkData {
-    pub chunk: Chunk,
+    pub coord: ChunkCoord,
+    pub data: Vec<u8>,
+}
+
+// Message carrying the tiles of a chunk modified since the previous delta, taking the chunk from
+// version `base` to `version`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ChunkDelta {
+    pub coord: ChunkCoord,
+    pub base: u32,
+    pub version: u32,
+    /// Index of each modified tile, row by row, and its new value
+    pub tiles: Vec<(u32, Tile)>,
+}
+
+// Message telling a client to drop chunks its player moved away from; the server no longer sends
+// their updates
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ChunkUnload {
+    pub coords: Vec<ChunkCoord>,
 }
 
-// Plugin f
+// Plugin generating and tracking the chunks of the world. Chunks and terrain messages are
+// registered by the protocol (see `protocol::world`).
 #[derive(Clone)]
 pub struct WorldGenerationPlugin;
 
@@ -124,29 +881,21 @@ impl Plugin for WorldGenerationPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<WorldConfig>()
             .init_resource::<WorldState>()
+            .init_resource::<BiomeMap>()
+            .init_resource::<HeightMap>()
+            .init_resource::<StructureMap>()
+            .init_resource::<GeneratorPool>()
             .add_event::<ChunkRequestEvent>()
+            .add_event::<ChunkUnloadedEvent>()
+            .add_event::<SaveWorldEvent>()
             .add_systems(Startup, setup_world)
-            .add_systems(Update, (handle_chunk_requests, manage_active_chunks));
-
-        // Register this only on the server
-        #[cfg(feature = "server")]
-        {
-            app.register_component::<Chunk>(ChannelDirection::ServerToClient)
-                .add_interpolation(ComponentSyncMode::Once);
-
-            app.register_component::<ChunkCoord>(ChannelDirection::ServerToClient)
-                .add_interpolation(ComponentSyncMode::Once);
-
-            // Register messages
-            app.register_message::<ChunkRequest>(ChannelDirection::ClientToServer);
-            app.register_message::<ChunkData>(ChannelDirection::ServerToClient);
-
-            // Add channel for chunk data
-            app.add_channel::<ChunkChannel>(ChannelSettings {
-                mode: ChannelMode::OrderedReliable(ReliableSettings::default()),
-                ..default()
-            });
-        }
+            .add_systems(
+                Update,
+                (
+                    (handle_chunk_requests, finish_pending_chunks).chain(),
+                    manage_active_chunks,
+                ),
+            );
     }
 }
 
@@ -155,6 +904,7 @@ fn setup_world(
     mut commands: Commands,
     mut world_state: ResMut<WorldState>,
     world_config: Res<WorldConfig>,
+    mut generator: Local<ChunkGenerator>,
 ) {
     info!("Initializing world with seed: {}", world_config.seed);
 
@@ -168,20 +918,60 @@ fn setup_world(
     ];
 
     for coord in spawn_coords.iter() {
-        generate_chunk(coord, &mut commands, &mut world_state, &world_config);
+        generate_chunk(
+            coord,
+            &mut commands,
+            &mut world_state,
+            &world_config,
+            &mut generator,
+        );
     }
 }
 
-// Handle requests for new chunks (e.g., from player movement)
-fn handle_chunk_requests(
+/// Chunk being generated on the async compute pool. The entity gets its `Chunk` once done.
+#[derive(Component)]
+pub struct PendingChunk {
+    pub coord: ChunkCoord,
+    task: Task<Chunk>,
+}
+
+// Generators shared by the generation tasks, so that each task doesn't build its noise again
+#[derive(Resource, Clone, Default)]
+struct GeneratorPool(Arc<Mutex<Vec<ChunkGenerator>>>);
+
+// Handle requests for new chunks (e.g., from player movement), generating them in the background
+pub fn handle_chunk_requests(
     mut commands: Commands,
     mut world_state: ResMut<WorldState>,
     world_config: Res<WorldConfig>,
     mut chunk_request_events: EventReader<ChunkRequestEvent>,
+    generators: Res<GeneratorPool>,
 ) {
+    let pool = AsyncComputeTaskPool::get();
     for event in chunk_request_events.read() {
-        if !world_state.chunks.contains_key(&event.coord) {
-            generate_chunk(&event.coord, &mut commands, &mut world_state, &world_config);
+        let coord = event.coord;
+        if world_state.held_chunks.contains(&coord) {
+            continue;
+        }
+        if !world_state.chunks.contains_key(&coord)
+            && !world_state.pending_chunks.contains_key(&coord)
+        {
+            let config = world_config.clone();
+            let generators = generators.clone();
+            let world_time = world_state.world_time;
+            let task = pool.spawn(async move {
+                let mut generator = generators.0.lock().unwrap().pop().unwrap_or_default();
+                let chunk = generator.build(
+                    &coord,
+                    &config,
+                    world_time,
+                    &mut GenerationTimings::default(),
+                );
+                generators.0.lock().unwrap().push(generator);
+                chunk
+            });
+            let entity = commands.spawn(PendingChunk { coord, task }).id();
+            world_state.pending_chunks.insert(coord, entity);
         }
 
         // Mark the chunk as active
@@ -189,12 +979,55 @@ fn handle_chunk_requests(
     }
 }
 
+// Bring the chunks done generating into the world, for as long as the frame budget allows; the
+// others are picked up next frame
+fn finish_pending_chunks(
+    mut commands: Commands,
+    mut world_state: ResMut<WorldState>,
+    mut pending: Query<(Entity, &mut PendingChunk)>,
+    load: Option<Res<FrameLoad>>,
+) {
+    if pending.is_empty() {
+        return;
+    }
+    let start = std::time::Instant::now();
+    let budget_ms = load
+        .map_or(FrameBudget::default(), |load| load.budget)
+        .chunk_generation_ms;
+    let budget = std::time::Duration::from_secs_f32(budget_ms / 1000.0);
+    for (entity, mut pending) in pending.iter_mut() {
+        if start.elapsed() >= budget {
+            break;
+        }
+        let Some(chunk) = block_on(future::poll_once(&mut pending.task)) else {
+            continue;
+        };
+        let coord = pending.coord;
+        world_state.pending_chunks.remove(&coord);
+        // loaded some other way in the meantime, e.g. regenerated by hand
+        if world_state.chunks.contains_key(&coord) {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        commands
+            .entity(entity)
+            .remove::<PendingChunk>()
+            .insert(chunk);
+        let world_time = world_state.world_time;
+        world_state.chunks.insert(coord, entity);
+        world_state.generation_time.insert(coord, world_time);
+        debug!("Generated chunk at {:?}", coord);
+    }
+}
+
 // Manage active chunks, unload distant ones if needed
 fn manage_active_chunks(
     mut commands: Commands,
     mut world_state: ResMut<WorldState>,
     world_config: Res<WorldConfig>,
     time: Res<Time>,
+    chunks: Query<&Chunk>,
+    mut unloaded: EventWriter<ChunkUnloadedEvent>,
 ) {
     // Update world time
     world_state.world_time += time.delta_secs_f64();
@@ -225,6 +1058,11 @@ fn manage_active_chunks(
             }
 
             if let Some(entity) = world_state.chunks.remove(coord) {
+                if let Ok(chunk) = chunks.get(entity) {
+                    unloaded.send(ChunkUnloadedEvent {
+                        chunk: chunk.clone(),
+                    });
+                }
                 commands.entity(entity).despawn();
                 world_state.active_chunks.remove(coord);
                 world_state.generation_time.remove(coord);
@@ -241,106 +1079,484 @@ pub struct ChunkRequestEvent {
     pub client_id: Option<ClientId>,
 }
 
+// Event sent with the contents of each chunk unloaded to stay under the active chunk limit
+#[derive(Event)]
+pub struct ChunkUnloadedEvent {
+    pub chunk: Chunk,
+}
+
+// Event asking every system that holds unsaved state to flush it (e.g. before a restart)
+#[derive(Event)]
+pub struct SaveWorldEvent;
+
 // Generate a single chunk at the given coordinates
 fn generate_chunk(
     coord: &ChunkCoord,
     commands: &mut Commands,
     world_state: &mut WorldState,
     config: &WorldConfig,
+    generator: &mut ChunkGenerator,
 ) {
     let start_time = std::time::Instant::now();
 
-    // Create noise generators with the world seed
-    let perlin = Perlin::new(config.seed);
-    let biome_noise = Perlin::new(config.seed + 1);
-    let resource_noise = Perlin::new(config.seed + 2);
+    let chunk = generator.build(
+        coord,
+        config,
+        world_state.world_time,
+        &mut GenerationTimings::default(),
+    );
 
-    // Determine dominant biome for this chunk
-    let biome_value = biome_noise.get([
-        coord.x as f64 * config.biome_scale,
-        coord.y as f64 * config.biome_scale,
-    ]);
+    // Spawn the chunk entity
+    let chunk_entity = commands.spawn(chunk).id();
+
+    // Update world state
+    world_state.chunks.insert(*coord, chunk_entity);
+    world_state.active_chunks.insert(*coord);
+    world_state
+        .generation_time
+        .insert(*coord, world_state.world_time);
 
-    let biome_type = determine_biome(biome_value);
+    let generation_time = start_time.elapsed().as_millis();
+    debug!("Generated chunk at {:?} in {}ms", coord, generation_time);
+}
 
-    // Generate the tiles for this chunk
-    let mut tiles = vec![vec![create_empty_tile(); config.chunk_size]; config.chunk_size];
+// Build the contents of the chunk at the given coordinates. Pure and deterministic for a given
+// config, so it can run on any thread.
+pub fn build_chunk(coord: &ChunkCoord, config: &WorldConfig, world_time: f64) -> Chunk {
+    build_chunk_timed(coord, config, world_time, &mut GenerationTimings::default())
+}
 
-    for local_y in 0..config.chunk_size {
-        for local_x in 0..config.chunk_size {
-            // Calculate world coordinates
-            let world_x = coord.x * config.chunk_size as i32 + local_x as i32;
-            let world_y = coord.y * config.chunk_size as i32 + local_y as i32;
+/// Time spent in each generation pass, accumulated over any number of chunks
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GenerationTimings {
+    pub biome: std::time::Duration,
+    pub noise: std::time::Duration,
+    pub resources: std::time::Duration,
+    pub structures: std::time::Duration,
+    pub decorations: std::time::Duration,
+}
 
-            // Get height value for this tile
-            let height_value = perlin.get([
-                world_x as f64 * config.height_scale,
-                world_y as f64 * config.height_scale,
-            ]) as f32;
+/// Same as [`build_chunk`], adding the time spent in each pass to `timings`
+pub fn build_chunk_timed(
+    coord: &ChunkCoord,
+    config: &WorldConfig,
+    world_time: f64,
+    timings: &mut GenerationTimings,
+) -> Chunk {
+    ChunkGenerator::default().build(coord, config, world_time, timings)
+}
 
-            // Determine tile type based on biome and height
-            let tile_type = determine_tile_type(biome_type, height_value);
+/// Noise functions of a world. Building them shuffles permutation tables, so they are built once
+/// per seed rather than once per chunk.
+#[derive(Clone)]
+pub struct NoiseSampler {
+    seed: u32,
+    height: Perlin,
+    biome: Perlin,
+    resource: Perlin,
+    path: Perlin,
+}
 
-            // Determine if there's a resource here
-            let resource_value = resource_noise.get([
-                world_x as f64 * config.height_scale * 2.0,
-                world_y as f64 * config.height_scale * 2.0,
-            ]) as f32;
+impl NoiseSampler {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            seed,
+            height: Perlin::new(seed),
+            biome: Perlin::new(seed.wrapping_add(1)),
+            resource: Perlin::new(seed.wrapping_add(2)),
+            path: Perlin::new(seed.wrapping_add(3)),
+        }
+    }
+
+    pub fn biome(&self, coord: &ChunkCoord, config: &WorldConfig) -> BiomeType {
+        biome_from_noise(&self.biome, coord, config)
+    }
 
-            let resource = determine_resource(tile_type, resource_value, config.resource_density);
+    /// Whether a generated tile lies on a path. Paths follow the zero line of a low frequency
+    /// noise, so they wind across the world over any walkable ground.
+    pub fn on_path(
+        &self,
+        tile_type: TileType,
+        resource: ResourceType,
+        position: (i32, i32),
+    ) -> bool {
+        let walkable = matches!(
+            tile_type,
+            TileType::Grass | TileType::Forest | TileType::Sand | TileType::Snow
+        );
+        if !walkable || resource != ResourceType::None {
+            return false;
+        }
+        let path = self.path.get([
+            position.0 as f64 * PATH_SCALE,
+            position.1 as f64 * PATH_SCALE,
+        ]);
+        path.abs() < PATH_WIDTH
+    }
+
+    /// Fill `buffers` with the height and resource noise of every tile of a chunk, row by row.
+    /// Sample coordinates are computed once per row and column rather than once per tile.
+    pub fn sample_chunk(
+        &self,
+        coord: &ChunkCoord,
+        config: &WorldConfig,
+        buffers: &mut NoiseBuffers,
+    ) {
+        let size = config.chunk_size;
+        let (first_x, first_y) = config.grid().chunk_origin(*coord);
+        buffers.xs.clear();
+        buffers
+            .xs
+            .extend((0..size).map(|local| (first_x + local as i32) as f64 * config.height_scale));
+        buffers.ys.clear();
+        buffers
+            .ys
+            .extend((0..size).map(|local| (first_y + local as i32) as f64 * config.height_scale));
+        buffers.heights.clear();
+        buffers.heights.resize(size * size, 0.0);
+        buffers.resources.clear();
+        buffers.resources.resize(size * size, 0.0);
+        if size == 0 {
+            return;
+        }
+        let xs = &buffers.xs;
+        let fill_row = |(y, (heights, resources)): (&f64, (&mut [f32], &mut [f32]))| {
+            for (local_x, &x) in xs.iter().enumerate() {
+                heights[local_x] = self.height.get([x, *y]) as f32;
+                // resources vary twice as fast as the terrain
+                resources[local_x] = self.resource.get([x * 2.0, y * 2.0]) as f32;
+            }
+        };
+        #[cfg(feature = "parallel-worldgen")]
+        {
+            use rayon::prelude::*;
+            let rows = buffers
+                .heights
+                .par_chunks_mut(size)
+                .zip(buffers.resources.par_chunks_mut(size));
+            buffers.ys.par_iter().zip(rows).for_each(fill_row);
+        }
+        #[cfg(not(feature = "parallel-worldgen"))]
+        {
+            let rows = buffers
+                .heights
+                .chunks_mut(size)
+                .zip(buffers.resources.chunks_mut(size));
+            buffers.ys.iter().zip(rows).for_each(fill_row);
+        }
+        if let Some(terrain) = &config.imported_terrain {
+            for (index, height) in buffers.heights.iter_mut().enumerate() {
+                let (local_x, local_y) = (index % size, index / size);
+                if let Some(imported) =
+                    terrain.height_at(first_x + local_x as i32, first_y + local_y as i32)
+                {
+                    *height = imported;
+                }
+            }
+        }
+    }
+}
+
+/// Per-tile noise of one chunk. Kept around to generate the next chunk without reallocating.
+#[derive(Default, Debug)]
+pub struct NoiseBuffers {
+    pub heights: Vec<f32>,
+    pub resources: Vec<f32>,
+    xs: Vec<f64>,
+    ys: Vec<f64>,
+}
+
+/// Reusable state for generating chunks one after the other
+#[derive(Default)]
+pub struct ChunkGenerator {
+    sampler: Option<NoiseSampler>,
+    buffers: NoiseBuffers,
+}
 
-            // Create the tile
-            tiles[local_y][local_x] = Tile {
+impl ChunkGenerator {
+    pub fn build(
+        &mut self,
+        coord: &ChunkCoord,
+        config: &WorldConfig,
+        world_time: f64,
+        timings: &mut GenerationTimings,
+    ) -> Chunk {
+        if self
+            .sampler
+            .as_ref()
+            .is_some_and(|sampler| sampler.seed != config.seed)
+        {
+            self.sampler = None;
+        }
+        let sampler = self
+            .sampler
+            .get_or_insert_with(|| NoiseSampler::new(config.seed));
+        let size = config.chunk_size;
+
+        // Determine dominant biome for this chunk
+        let start = std::time::Instant::now();
+        let biome_type = sampler.biome(coord, config);
+        timings.biome += start.elapsed();
+
+        let start = std::time::Instant::now();
+        sampler.sample_chunk(coord, config, &mut self.buffers);
+        timings.noise += start.elapsed();
+
+        // Tile types from the biome and height, then resources on top of them
+        let start = std::time::Instant::now();
+        let sampler = &*sampler;
+        let buffers = &self.buffers;
+        let grid = config.grid();
+        let build_tile = |index: usize| -> Tile {
+            let (local_x, local_y) = (index % size, index / size);
+            let height_value = buffers.heights[index];
+            let tile_type = determine_tile_type(biome_type, height_value);
+            let resource =
+                determine_resource(tile_type, buffers.resources[index], config.resource_density);
+            let position = grid.local_to_tile(*coord, (local_x, local_y));
+            Tile {
                 tile_type,
+                decoration: if sampler.on_path(tile_type, resource, position) {
+                    Decoration::Path
+                } else {
+                    Decoration::None
+                },
                 resource,
+                overlay: determine_overlay(biome_type, tile_type),
                 height: height_value,
-                position: (world_x, world_y),
+                position,
                 traversable: is_traversable(tile_type, resource),
-            };
+                damage: 0,
+            }
+        };
+        #[cfg(feature = "parallel-worldgen")]
+        let mut tiles: Vec<Tile> = {
+            use rayon::prelude::*;
+            (0..size * size).into_par_iter().map(build_tile).collect()
+        };
+        #[cfg(not(feature = "parallel-worldgen"))]
+        let mut tiles: Vec<Tile> = (0..size * size).map(build_tile).collect();
+        timings.resources += start.elapsed();
+
+        // structures build their part of the chunk over the generated tiles
+        let start = std::time::Instant::now();
+        let structures = chunk_structures(coord, config, |coord| sampler.biome(coord, config));
+        realize_structures(&mut tiles, &structures, biome_type);
+        timings.structures += start.elapsed();
+
+        let start = std::time::Instant::now();
+        scatter_decorations(&mut tiles, size, biome_type, config.seed, &structures);
+        timings.decorations += start.elapsed();
+
+        Chunk::new(*coord, size, tiles, biome_type, world_time)
+    }
+}
+
+// Helper functions for world generation
+
+// Scale of the noise paths follow, and how close to its zero line a tile must be to be on a path
+const PATH_SCALE: f64 = 0.01;
+const PATH_WIDTH: f64 = 0.015;
+
+/// One line of a biome's decoration table
+pub struct Scatter {
+    pub decoration: Decoration,
+    // Ground the decoration can be scattered on
+    pub ground: &'static [TileType],
+    // Share of the eligible tiles that get it
+    pub density: f32,
+    // Only on tiles next to water, like shells on a beach
+    pub near_water: bool,
+}
+
+const fn scatter(decoration: Decoration, ground: &'static [TileType], density: f32) -> Scatter {
+    Scatter {
+        decoration,
+        ground,
+        density,
+        near_water: false,
+    }
+}
+
+/// Decorations scattered over each biome. The densities of a biome add up to well under 1, so
+/// most tiles stay bare.
+pub fn scatter_table(biome: BiomeType) -> &'static [Scatter] {
+    use Decoration::*;
+    use TileType::*;
+    match biome {
+        BiomeType::Plains => &[
+            scatter(Flowers, &[Grass], 0.08),
+            scatter(GrassTufts, &[Grass], 0.12),
+            scatter(Rocks, &[Stone], 0.05),
+        ],
+        BiomeType::Forest => &[
+            scatter(GrassTufts, &[Forest], 0.1),
+            scatter(Flowers, &[Forest], 0.03),
+        ],
+        BiomeType::Desert => &[
+            scatter(Pebbles, &[Sand], 0.04),
+            scatter(Rocks, &[Stone], 0.08),
+        ],
+        BiomeType::Ocean => &[
+            Scatter {
+                decoration: Shells,
+                ground: &[Sand],
+                density: 0.15,
+                near_water: true,
+            },
+            scatter(Pebbles, &[Sand], 0.03),
+        ],
+        BiomeType::Mountain => &[
+            scatter(Rocks, &[Stone], 0.1),
+            scatter(GrassTufts, &[Grass], 0.06),
+            scatter(Flowers, &[Grass], 0.02),
+        ],
+        BiomeType::Tundra => &[
+            scatter(Rocks, &[Stone, Snow], 0.05),
+            scatter(GrassTufts, &[Grass], 0.03),
+        ],
+    }
+}
+
+// Structure pass: tiles on a structure's footprint become part of it
+fn realize_structures(tiles: &mut [Tile], structures: &[StructurePlan], biome: BiomeType) {
+    if structures.is_empty() {
+        return;
+    }
+    for tile in tiles.iter_mut() {
+        if let Some(plan) = structures.iter().find(|plan| plan.contains(tile.position)) {
+            plan.realize(tile);
+            tile.overlay = determine_overlay(biome, tile.tile_type);
+            tile.traversable = is_traversable(tile.tile_type, tile.resource);
         }
     }
+}
 
-    // Create the chunk entity
-    let chunk = Chunk {
-        coord: *coord,
-        tiles,
-        biome_type,
-        last_accessed: world_state.world_time,
+// Scatter pass: give bare tiles a decoration from the biome's table. Decorations are cosmetic,
+// so they never go under a resource node, nor on structures. Whether a tile is next to water is
+// only known inside the chunk, which keeps chunks independent of their neighbours.
+fn scatter_decorations(
+    tiles: &mut [Tile],
+    size: usize,
+    biome: BiomeType,
+    seed: u32,
+    structures: &[StructurePlan],
+) {
+    let table = scatter_table(biome);
+    let is_water = |tiles: &[Tile], x: usize, y: usize| {
+        matches!(
+            tiles[y * size + x].tile_type,
+            TileType::ShallowWater | TileType::DeepWater
+        )
     };
+    for y in 0..size {
+        for x in 0..size {
+            let tile = &tiles[y * size + x];
+            if tile.decoration != Decoration::None
+                || tile.resource != ResourceType::None
+                || structures.iter().any(|plan| plan.contains(tile.position))
+            {
+                continue;
+            }
+            let near_water = (x > 0 && is_water(tiles, x - 1, y))
+                || (x + 1 < size && is_water(tiles, x + 1, y))
+                || (y > 0 && is_water(tiles, x, y - 1))
+                || (y + 1 < size && is_water(tiles, x, y + 1));
+            // one roll per tile, walked through the table so that densities don't overlap
+            let roll = (seeded_hash(seed, tile.position) % 10_000) as f32 / 10_000.0;
+            let mut threshold = 0.0;
+            let decoration = table
+                .iter()
+                .filter(|entry| {
+                    entry.ground.contains(&tile.tile_type) && (!entry.near_water || near_water)
+                })
+                .find(|entry| {
+                    threshold += entry.density;
+                    roll < threshold
+                })
+                .map(|entry| entry.decoration);
+            if let Some(decoration) = decoration {
+                tiles[y * size + x].decoration = decoration;
+            }
+        }
+    }
+}
 
-    // Spawn the chunk entity
-    let chunk_entity = commands.spawn(chunk).id();
+// Cover generated over the ground: the tundra is snowed in
+fn determine_overlay(biome: BiomeType, tile_type: TileType) -> Overlay {
+    match (biome, tile_type) {
+        (BiomeType::Tundra, TileType::Grass | TileType::Stone) => Overlay::Snow,
+        _ => Overlay::None,
+    }
+}
 
-    // Update world state
-    world_state.chunks.insert(*coord, chunk_entity);
-    world_state.active_chunks.insert(*coord);
-    world_state
-        .generation_time
-        .insert(*coord, world_state.world_time);
+// Height of a world tile as generated, before any terrain modification
+pub fn noise_height(perlin: &Perlin, world_x: i32, world_y: i32, config: &WorldConfig) -> f32 {
+    if let Some(height) = config
+        .imported_terrain
+        .as_ref()
+        .and_then(|terrain| terrain.height_at(world_x, world_y))
+    {
+        return height;
+    }
+    perlin.get([
+        world_x as f64 * config.height_scale,
+        world_y as f64 * config.height_scale,
+    ]) as f32
+}
 
-    let generation_time = start_time.elapsed().as_millis();
-    debug!("Generated chunk at {:?} in {}ms", coord, generation_time);
+// Dominant biome of a chunk. Deterministic for a given seed, and doesn't need the chunk to exist.
+pub fn chunk_biome(coord: &ChunkCoord, config: &WorldConfig) -> BiomeType {
+    biome_from_noise(&Perlin::new(config.seed.wrapping_add(1)), coord, config)
 }
 
-// Helper functions for world generation
+fn biome_from_noise(biome_noise: &Perlin, coord: &ChunkCoord, config: &WorldConfig) -> BiomeType {
+    // imported biomes are read at the center of the chunk
+    let imported = config.imported_terrain.as_ref().and_then(|terrain| {
+        let (first_x, first_y) = config.grid().chunk_origin(*coord);
+        let half = config.chunk_size as i32 / 2;
+        terrain.biome_at(first_x + half, first_y + half)
+    });
+    let biome_value = imported.unwrap_or_else(|| {
+        biome_noise.get([
+            coord.x as f64 * config.biome_scale,
+            coord.y as f64 * config.biome_scale,
+        ])
+    });
+    determine_biome(biome_value, &config.biome_thresholds)
+}
+
+// Steps taken across the -1..1 noise range when sweeping the generation tables
+const SWEEP_STEPS: i32 = 200;
 
-fn create_empty_tile() -> Tile {
-    Tile {
-        tile_type: TileType::Grass,
-        resource: ResourceType::None,
-        height: 0.0,
-        position: (0, 0),
-        traversable: true,
+/// Tile types and resources that generation can place in a biome, found by sweeping every height
+/// and resource noise value through the same tables chunks are built with, at full density
+pub fn biome_contents(biome: BiomeType) -> (Vec<TileType>, Vec<ResourceType>) {
+    let sweep = (0..=SWEEP_STEPS).map(|step| step as f32 * 2.0 / SWEEP_STEPS as f32 - 1.0);
+    let mut tile_types = Vec::new();
+    let mut resources = Vec::new();
+    for height in sweep.clone() {
+        let tile_type = determine_tile_type(biome, height);
+        if !tile_types.contains(&tile_type) {
+            tile_types.push(tile_type);
+        }
+        for value in sweep.clone() {
+            let resource = determine_resource(tile_type, value, 1.0);
+            if resource != ResourceType::None && !resources.contains(&resource) {
+                resources.push(resource);
+            }
+        }
     }
+    (tile_types, resources)
 }
 
-fn determine_biome(value: f64) -> BiomeType {
+fn determine_biome(value: f64, thresholds: &BiomeThresholds) -> BiomeType {
     match value {
-        v if v < -0.6 => BiomeType::Ocean,
-        v if v < -0.3 => BiomeType::Desert,
-        v if v < 0.1 => BiomeType::Plains,
-        v if v < 0.4 => BiomeType::Forest,
-        v if v < 0.7 => BiomeType::Mountain,
+        v if v < thresholds.ocean => BiomeType::Ocean,
+        v if v < thresholds.desert => BiomeType::Desert,
+        v if v < thresholds.plains => BiomeType::Plains,
+        v if v < thresholds.forest => BiomeType::Forest,
+        v if v < thresholds.mountain => BiomeType::Mountain,
         _ => BiomeType::Tundra,
     }
 }
@@ -350,8 +1566,10 @@ fn determine_tile_type(biome: BiomeType, height: f32) -> TileType {
         BiomeType::Ocean => {
             if height > 0.2 {
                 TileType::Sand
+            } else if height > -0.2 {
+                TileType::ShallowWater
             } else {
-                TileType::Water
+                TileType::DeepWater
             }
         }
         BiomeType::Desert => {
@@ -426,28 +1644,45 @@ fn determine_resource(tile_type: TileType, resource_value: f32, density: f32) ->
                 ResourceType::Stone
             }
         }
+        TileType::ShallowWater | TileType::DeepWater => {
+            if resource_value.abs() > 0.6 {
+                ResourceType::Fish
+            } else {
+                ResourceType::None
+            }
+        }
         _ => ResourceType::None,
     }
 }
 
-fn is_traversable(tile_type: TileType, resource: ResourceType) -> bool {
+/// Whether a tile can be crossed: mountains and trees block the way. Deep water is swum through,
+/// it is a hazard rather than an obstacle (see `TileType::is_hazard`).
+pub(crate) fn is_traversable(tile_type: TileType, resource: ResourceType) -> bool {
     match (tile_type, resource) {
-        (TileType::Water, _) => false,
         (TileType::Mountain, _) => false,
         (_, ResourceType::Tree) => false,
         _ => true,
     }
 }
 
-// System to serialize a chunk for network transmission
-pub fn serialize_chunk(chunk: &Chunk) -> Vec<u8> {
-    bincode::serialize(chunk).unwrap_or_else(|_| {
-        error!("Failed to serialize chunk at {:?}", chunk.coord);
-        Vec::new()
-    })
+// Largest serialized chunk we accept; a 64x64 chunk is about 125KB
+pub const MAX_CHUNK_BYTES: u64 = 1024 * 1024;
+
+// System to serialize a chunk for network transmission or storage, in the given format
+pub fn serialize_chunk(chunk: &Chunk, format: ChunkFormat) -> Result<Vec<u8>, GameError> {
+    chunk_format::encode(chunk, format)
 }
 
-// System to deserialize a chunk from network data
-pub fn deserialize_chunk(data: &[u8]) -> Option<Chunk> {
-    bincode::deserialize(data).ok()
+// System to deserialize a chunk from network data, in whichever format it was written.
+// Rejects oversized input and malformed chunks, so that hostile data can't make us allocate
+// unbounded memory or index out of bounds later on.
+pub fn deserialize_chunk(data: &[u8]) -> Result<Chunk, GameError> {
+    let chunk = chunk_format::decode(data)?;
+    if !chunk.is_well_formed() {
+        return Err(GameError::MalformedChunk(chunk.coord));
+    }
+    Ok(chunk)
 }
+
+#[cfg(test)]
+mod tests;
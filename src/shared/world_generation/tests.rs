@@ -0,0 +1,400 @@
+//! Invariants of world generation that must hold for any seed and any chunk
+use proptest::prelude::*;
+
+use super::*;
+use crate::shared::structures::plan_structure;
+
+fn config(seed: u32) -> WorldConfig {
+    WorldConfig {
+        seed,
+        ..WorldConfig::default()
+    }
+}
+
+// Resources a tile type can hold, mirroring the table in `determine_resource`
+fn allowed_resources(tile_type: TileType) -> &'static [ResourceType] {
+    match tile_type {
+        TileType::Grass | TileType::Forest => &[ResourceType::None, ResourceType::Tree],
+        TileType::Stone | TileType::Mountain => &[
+            ResourceType::None,
+            ResourceType::Stone,
+            ResourceType::Coal,
+            ResourceType::Copper,
+            ResourceType::Iron,
+            ResourceType::Gold,
+        ],
+        TileType::ShallowWater | TileType::DeepWater => &[ResourceType::None, ResourceType::Fish],
+        TileType::Sand | TileType::Snow => &[ResourceType::None],
+    }
+}
+
+fn chunk_coord() -> impl Strategy<Value = ChunkCoord> {
+    (-10_000i32..10_000, -10_000i32..10_000).prop_map(|(x, y)| ChunkCoord { x, y })
+}
+
+// The default chunk size, and sizes other parts of the game must not make assumptions against
+fn chunk_size() -> impl Strategy<Value = usize> {
+    prop_oneof![Just(16usize), Just(32), Just(64)]
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn blocking_tiles_are_never_traversable(seed in any::<u32>(), coord in chunk_coord()) {
+        let chunk = build_chunk(&coord, &config(seed), 0.0);
+        for tile in chunk.tiles() {
+            if tile.tile_type == TileType::Mountain || tile.resource == ResourceType::Tree {
+                prop_assert!(!tile.traversable, "{:?} is traversable", tile);
+            }
+            // deep water is a hazard, not an obstacle: players swim into it and drown
+            if tile.tile_type == TileType::DeepWater && tile.resource == ResourceType::None {
+                prop_assert!(tile.traversable, "{:?} is not traversable", tile);
+            }
+        }
+    }
+
+    #[test]
+    fn resources_only_appear_on_valid_tiles(seed in any::<u32>(), coord in chunk_coord()) {
+        let chunk = build_chunk(&coord, &config(seed), 0.0);
+        for tile in chunk.tiles() {
+            prop_assert!(
+                allowed_resources(tile.tile_type).contains(&tile.resource),
+                "{:?} on {:?} at {:?}",
+                tile.resource,
+                tile.tile_type,
+                tile.position
+            );
+        }
+    }
+
+    #[test]
+    fn decorations_follow_the_scatter_tables(
+        seed in any::<u32>(),
+        coord in chunk_coord(),
+    ) {
+        let chunk = build_chunk(&coord, &config(seed), 0.0);
+        for tile in chunk.tiles() {
+            if tile.decoration != Decoration::None {
+                prop_assert_eq!(tile.resource, ResourceType::None, "{:?}", tile);
+            }
+            match tile.decoration {
+                Decoration::None => {}
+                Decoration::Path => prop_assert!(tile.traversable, "{:?}", tile),
+                decoration => prop_assert!(
+                    scatter_table(chunk.biome_type).iter().any(|entry| {
+                        entry.decoration == decoration && entry.ground.contains(&tile.tile_type)
+                    }),
+                    "{:?} isn't scattered in {:?}",
+                    tile,
+                    chunk.biome_type
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn tile_positions_match_chunk_coordinates(
+        seed in any::<u32>(),
+        coord in chunk_coord(),
+        chunk_size in chunk_size(),
+    ) {
+        let config = WorldConfig { chunk_size, ..config(seed) };
+        let grid = config.grid();
+        let chunk = build_chunk(&coord, &config, 0.0);
+        prop_assert_eq!(chunk.size(), chunk_size);
+        prop_assert!(chunk.validate(chunk_size).is_ok());
+        for (local_y, row) in chunk.rows().enumerate() {
+            prop_assert_eq!(row.len(), chunk_size);
+            for (local_x, tile) in row.iter().enumerate() {
+                let (world_x, world_y) = tile.position;
+                prop_assert_eq!(world_x, coord.x * chunk_size as i32 + local_x as i32);
+                prop_assert_eq!(world_y, coord.y * chunk_size as i32 + local_y as i32);
+                prop_assert_eq!(grid.tile_to_chunk(tile.position), coord);
+                prop_assert_eq!(grid.tile_to_local(tile.position), (local_x, local_y));
+            }
+        }
+    }
+
+    #[test]
+    fn generation_is_deterministic(seed in any::<u32>(), coord in chunk_coord()) {
+        let config = config(seed);
+        let first = build_chunk(&coord, &config, 0.0);
+        let second = build_chunk(&coord, &config, 0.0);
+        prop_assert_eq!(first.checksum(), second.checksum());
+        prop_assert_eq!(first.biome_type, chunk_biome(&coord, &config));
+    }
+
+    #[test]
+    fn reused_generator_matches_per_tile_sampling(
+        seed in any::<u32>(),
+        coords in prop::collection::vec(chunk_coord(), 1..4),
+    ) {
+        let config = config(seed);
+        let height = Perlin::new(seed);
+        let resource = Perlin::new(seed.wrapping_add(2));
+        let structures = StructureMap::new(config.clone());
+        let mut generator = ChunkGenerator::default();
+        for coord in coords {
+            let chunk = generator.build(&coord, &config, 0.0, &mut GenerationTimings::default());
+            for tile in chunk.tiles() {
+                let (x, y) = tile.position;
+                prop_assert_eq!(tile.height, noise_height(&height, x, y, &config));
+                // structures clear the resources under them
+                if structures.is_reserved(tile.position) {
+                    prop_assert_eq!(tile.resource, ResourceType::None);
+                    continue;
+                }
+                let value = resource.get([
+                    x as f64 * config.height_scale * 2.0,
+                    y as f64 * config.height_scale * 2.0,
+                ]) as f32;
+                prop_assert_eq!(
+                    tile.resource,
+                    determine_resource(tile.tile_type, value, config.resource_density)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn world_tiles_round_trip_through_chunk_coordinates(
+        world_x in any::<i32>(),
+        world_y in any::<i32>(),
+        chunk_size in 1usize..256,
+    ) {
+        let grid = WorldGrid::new(chunk_size);
+        let coord = grid.tile_to_chunk((world_x, world_y));
+        let (local_x, local_y) = grid.tile_to_local((world_x, world_y));
+        prop_assert_eq!(grid.local_to_tile(coord, (local_x, local_y)), (world_x, world_y));
+        prop_assert!(local_x < chunk_size && local_y < chunk_size);
+        prop_assert_eq!(coord.x as i64 * chunk_size as i64 + local_x as i64, world_x as i64);
+        prop_assert_eq!(coord.y as i64 * chunk_size as i64 + local_y as i64, world_y as i64);
+    }
+
+    #[test]
+    fn world_positions_belong_to_the_nearest_tile(
+        x in -100_000.0f32..100_000.0,
+        y in -100_000.0f32..100_000.0,
+        chunk_size in chunk_size(),
+    ) {
+        let grid = WorldGrid::new(chunk_size);
+        let position = Vec2::new(x, y);
+        let tile = WorldGrid::world_to_tile(position);
+        let center = WorldGrid::tile_to_world(tile);
+        prop_assert!((center - position).abs().max_element() <= WorldGrid::TILE_SIZE / 2.0);
+        prop_assert_eq!(grid.world_to_chunk(position), grid.tile_to_chunk(tile));
+    }
+
+    #[test]
+    fn chunks_are_rendered_where_their_tiles_are(coord in chunk_coord(), chunk_size in chunk_size()) {
+        let grid = WorldGrid::new(chunk_size);
+        let origin = grid.chunk_to_world(coord);
+        let last = origin + Vec2::splat((chunk_size - 1) as f32 * WorldGrid::TILE_SIZE);
+        prop_assert_eq!(grid.world_to_chunk(origin), coord);
+        prop_assert_eq!(grid.world_to_chunk(last), coord);
+        prop_assert_eq!(WorldGrid::world_to_tile(origin), grid.chunk_origin(coord));
+    }
+
+    #[test]
+    fn steps_are_symmetric_and_stairs_cross_cliffs(seed in any::<u32>(), coord in chunk_coord()) {
+        let chunk = build_chunk(&coord, &config(seed), 0.0);
+        for row in chunk.rows() {
+            for pair in row.windows(2) {
+                let (a, b) = (&pair[0], &pair[1]);
+                prop_assert_eq!(a.can_step_to(b), b.can_step_to(a));
+                prop_assert_eq!(a.can_step_to(b), !is_cliff(a.height, b.height));
+                let stairs = Tile {
+                    decoration: Decoration::Stairs,
+                    ..a.clone()
+                };
+                prop_assert!(stairs.can_step_to(b) && b.can_step_to(&stairs));
+            }
+        }
+    }
+
+    #[test]
+    fn only_open_doors_can_be_stepped_into(seed in any::<u32>(), coord in chunk_coord()) {
+        let chunk = build_chunk(&coord, &config(seed), 0.0);
+        for row in chunk.rows() {
+            for pair in row.windows(2) {
+                let (a, b) = (&pair[0], &pair[1]);
+                let closed = Tile {
+                    decoration: Decoration::Door { open: false },
+                    ..b.clone()
+                };
+                prop_assert!(!a.can_step_to(&closed));
+                let open = Tile {
+                    decoration: closed.decoration.toggled(),
+                    ..b.clone()
+                };
+                prop_assert_eq!(a.can_step_to(&open), a.can_step_to(b));
+            }
+        }
+    }
+
+    #[test]
+    fn forests_trees_and_closed_doors_block_sight(seed in any::<u32>(), coord in chunk_coord()) {
+        let chunk = build_chunk(&coord, &config(seed), 0.0);
+        for tile in chunk.tiles() {
+            let blocks = matches!(tile.tile_type, TileType::Forest | TileType::Mountain)
+                || tile.resource == ResourceType::Tree;
+            prop_assert_eq!(tile.is_opaque(), blocks || tile.decoration.is_closed(), "{:?}", tile);
+            let closed = Tile {
+                decoration: Decoration::Gate { open: false },
+                ..tile.clone()
+            };
+            prop_assert!(closed.is_opaque());
+        }
+    }
+
+    #[test]
+    fn baked_opacity_follows_the_tiles(seed in any::<u32>(), coord in chunk_coord()) {
+        let mut chunk = build_chunk(&coord, &config(seed), 0.0);
+        for (local_y, row) in chunk.rows().enumerate() {
+            for (local_x, tile) in row.iter().enumerate() {
+                prop_assert_eq!(chunk.is_opaque(local_x, local_y), tile.is_opaque());
+            }
+        }
+        chunk.modify_tile(0, 0, |tile| tile.decoration = Decoration::Door { open: false });
+        prop_assert!(chunk.is_opaque(0, 0));
+        let data = serialize_chunk(&chunk, ChunkFormat::Raw).unwrap();
+        prop_assert_eq!(deserialize_chunk(&data).unwrap(), chunk);
+    }
+
+    #[test]
+    fn chunks_decode_the_same_in_every_format(seed in any::<u32>(), coord in chunk_coord()) {
+        let mut chunk = build_chunk(&coord, &config(seed), 0.0);
+        chunk.modify_tile(3, 5, |tile| tile.damage = 2);
+        let raw = serialize_chunk(&chunk, ChunkFormat::Raw).unwrap();
+        for format in [ChunkFormat::Packed, ChunkFormat::best_supported()] {
+            let data = serialize_chunk(&chunk, format).unwrap();
+            prop_assert!(data.len() < raw.len());
+            prop_assert_eq!(deserialize_chunk(&data).unwrap(), chunk.clone());
+        }
+    }
+
+    #[test]
+    fn deltas_bring_copies_up_to_date_in_order(seed in any::<u32>(), coord in chunk_coord()) {
+        let mut server = build_chunk(&coord, &config(seed), 0.0);
+        let mut client = server.clone();
+        server.modify_tile(1, 2, |tile| tile.damage = 1);
+        server.modify_tile(1, 2, |tile| tile.damage = 2);
+        let first = server.take_delta().unwrap();
+        prop_assert_eq!(first.tiles.len(), 1);
+        prop_assert!(server.take_delta().is_none());
+        server.modify_tile(7, 3, |tile| tile.decoration = Decoration::Door { open: false });
+        let second = server.take_delta().unwrap();
+
+        // a delta that skips modifications isn't applied
+        let mut behind = client.clone();
+        prop_assert_eq!(behind.apply_delta(&second), DeltaOutcome::Missed);
+        prop_assert_eq!(&behind, &client);
+
+        prop_assert_eq!(client.apply_delta(&first), DeltaOutcome::Applied);
+        prop_assert_eq!(client.apply_delta(&first), DeltaOutcome::Stale);
+        prop_assert_eq!(client.apply_delta(&second), DeltaOutcome::Applied);
+        prop_assert!(client.is_opaque(7, 3));
+        prop_assert_eq!(client, server);
+    }
+
+    #[test]
+    fn rebuilt_chunks_are_newer_than_the_copies_they_replace(
+        seed in any::<u32>(),
+        coord in chunk_coord(),
+    ) {
+        let mut server = build_chunk(&coord, &config(seed), 0.0);
+        server.modify_tile(1, 2, |tile| tile.damage = 1);
+        server.modify_tile(4, 4, |tile| tile.damage = 1);
+        let mut client = server.clone();
+        server.take_delta();
+
+        // rebuilt from scratch, the chunk starts over at version 0: its deltas would look stale
+        let mut rebuilt = build_chunk(&coord, &config(seed), 0.0);
+        rebuilt.continue_from(server.version());
+        prop_assert!(is_newer_version(rebuilt.version(), client.version()));
+        rebuilt.modify_tile(2, 2, |tile| tile.damage = 1);
+        let delta = rebuilt.take_delta().unwrap();
+        prop_assert_eq!(client.apply_delta(&delta), DeltaOutcome::Missed);
+
+        // a chunk already newer keeps its version
+        let version = rebuilt.version();
+        rebuilt.continue_from(server.version());
+        prop_assert_eq!(rebuilt.version(), version);
+    }
+
+    #[test]
+    fn imported_terrain_replaces_the_noise_only_inside_its_region(
+        seed in any::<u32>(),
+        coord in (-4i32..4, -4i32..4).prop_map(|(x, y)| ChunkCoord { x, y }),
+    ) {
+        // a 16x16 pixel ramp over the 64x64 tiles around the origin
+        let heights = (0..16 * 16).map(|index| (index % 16) as f32 / 15.0 * 2.0 - 1.0).collect();
+        let terrain = ImportedTerrain::from_pixels(16, heights, None, (-32, -32), 4.0);
+        let imported = WorldConfig {
+            imported_terrain: Some(Arc::new(terrain)),
+            ..config(seed)
+        };
+        let chunk = build_chunk(&coord, &imported, 0.0);
+        let generated = build_chunk(&coord, &config(seed), 0.0);
+        let terrain = imported.imported_terrain.as_ref().unwrap();
+        for (tile, noise) in chunk.tiles().iter().zip(generated.tiles()) {
+            let (x, y) = tile.position;
+            match terrain.height_at(x, y) {
+                Some(height) => prop_assert_eq!(tile.height, height),
+                None => prop_assert_eq!(tile.height, noise.height),
+            }
+            prop_assert_eq!(
+                terrain.height_at(x, y).is_some(),
+                (-32..=28).contains(&x) && (-32..=28).contains(&y)
+            );
+        }
+    }
+
+    #[test]
+    fn structures_come_out_whole_whatever_the_chunk_size(seed in any::<u32>()) {
+        let config = config(seed);
+        let biome = |coord: &ChunkCoord| chunk_biome(coord, &config);
+        let plan = (0..64).find_map(|cell| plan_structure((cell, 0), &config, biome));
+        prop_assume!(plan.is_some());
+        let plan = plan.unwrap();
+        // every tile of the footprint, built by the chunks overlapping it
+        let footprint = |chunk_size: usize| {
+            let config = WorldConfig { chunk_size, ..config.clone() };
+            let grid = config.grid();
+            let (width, height) = plan.kind.size();
+            let first = grid.tile_to_chunk(plan.origin);
+            let corner = (plan.origin.0 + width - 1, plan.origin.1 + height - 1);
+            let last = grid.tile_to_chunk(corner);
+            let mut tiles = HashMap::new();
+            for y in first.y..=last.y {
+                for x in first.x..=last.x {
+                    let chunk = build_chunk(&ChunkCoord { x, y }, &config, 0.0);
+                    for tile in chunk.tiles().iter().cloned() {
+                        if plan.contains(tile.position) {
+                            tiles.insert(tile.position, tile);
+                        }
+                    }
+                }
+            }
+            tiles
+        };
+        let (small, large) = (footprint(16), footprint(64));
+        let (width, height) = plan.kind.size();
+        prop_assert_eq!(small.len(), (width * height) as usize);
+        for (position, tile) in &small {
+            let other = &large[position];
+            prop_assert!(
+                matches!(tile.decoration, Decoration::None | Decoration::Path),
+                "{:?} was decorated",
+                tile
+            );
+            prop_assert_eq!(tile.resource, ResourceType::None);
+            // chunk biomes depend on the chunk size, and with them where the water is
+            if !tile.tile_type.is_water() && !other.tile_type.is_water() {
+                prop_assert_eq!(tile.tile_type, other.tile_type, "at {:?}", position);
+                prop_assert_eq!(tile.decoration, other.decoration, "at {:?}", position);
+            }
+        }
+    }
+}
@@ -3,6 +3,9 @@ use std::time::Duration;
 
 pub const FIXED_TIMESTEP_HZ: f64 = 64.0;
 pub const REPLICATION_INTERVAL: Duration = Duration::from_millis(100);
+/// Bytes per second the server sends to each client. Capping the send rate is what makes channel
+/// priorities matter: when the budget is exhausted, low priority channels (bulk terrain) wait.
+pub const SEND_BANDWIDTH_CAP: u32 = 512 * 1024;
 
 /// The [`SharedConfig`] must be shared between the `ClientConfig` and `ServerConfig`
 pub fn shared_config(mode: Mode) -> SharedConfig {
@@ -0,0 +1,66 @@
+//! Assembles the frames a server recorded with its time-lapse settings into an animated GIF, with
+//! `timelapse --frames <dir>`, outside of any app
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame};
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::PathBuf;
+
+/// Assemble the frames and return the exit code of the process
+pub fn run(frames_dir: &str, output: &str, frame_ms: u32) -> i32 {
+    match assemble(frames_dir, output, frame_ms) {
+        Ok(count) => {
+            println!("Assembled {} frames into {}", count, output);
+            0
+        }
+        Err(e) => {
+            eprintln!("Can't assemble the time-lapse in {}: {}", frames_dir, e);
+            1
+        }
+    }
+}
+
+fn assemble(frames_dir: &str, output: &str, frame_ms: u32) -> Result<usize, String> {
+    // frames are named after the time they were taken
+    let mut paths: Vec<PathBuf> = fs::read_dir(frames_dir)
+        .map_err(|e| e.to_string())?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "png"))
+        .collect();
+    paths.sort();
+    if paths.is_empty() {
+        return Err("no frames".to_string());
+    }
+
+    let file = File::create(output).map_err(|e| e.to_string())?;
+    let mut encoder = GifEncoder::new(BufWriter::new(file));
+    encoder
+        .set_repeat(Repeat::Infinite)
+        .map_err(|e| e.to_string())?;
+    let delay = Delay::from_numer_denom_ms(frame_ms, 1);
+    let mut size = None;
+    let mut count = 0;
+    for path in &paths {
+        let image = image::open(path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?
+            .to_rgba8();
+        // the recorded region changed in the settings: only the frames of the first one are kept
+        let (width, height) = *size.get_or_insert(image.dimensions());
+        if (width, height) != image.dimensions() {
+            eprintln!(
+                "Skipping {}, a {}x{} frame among {}x{} ones",
+                path.display(),
+                image.width(),
+                image.height(),
+                width,
+                height
+            );
+            continue;
+        }
+        encoder
+            .encode_frame(Frame::from_parts(image, 0, 0, delay))
+            .map_err(|e| e.to_string())?;
+        count += 1;
+    }
+    Ok(count)
+}
@@ -0,0 +1,372 @@
+//! Visual regression tests run from the command line with `visual-test`: chunks of a fixed seed
+//! are rendered offscreen by the game's own renderer and compared with reference images, so that
+//! changes to tile mapping, autotiling or shading don't go unnoticed
+use bevy::app::AppExit;
+use bevy::asset::ron;
+use bevy::prelude::*;
+use bevy::render::camera::RenderTarget;
+use bevy::render::gpu_readback::{Readback, ReadbackComplete};
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat, TextureUsages};
+use bevy::window::ExitCondition;
+use image::{Rgba, RgbaImage};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::client::plugins::{
+    ChunkRenderQueue, ClientWorldRenderPlugin, ClientWorldState, TileProjection, TileRenderState,
+    WorldCamera,
+};
+use crate::shared::height_map::HeightMap;
+use crate::shared::seasons::CurrentSeason;
+use crate::shared::world_generation::{
+    build_chunk, Chunk, ChunkCoord, WorldConfig, WorldGrid, WorldState,
+};
+
+// Frames rendered once the chunks of a case are built, before reading the target back, so that
+// systems reacting to new sprites (shading, season overlays) have run
+const SETTLE_FRAMES: u32 = 3;
+// Where renders that don't match their reference are written, next to an image of the differences
+const FAILURE_DIR: &str = "target/visual_tests";
+
+/// Chunks to render and how close renders must stay to their references
+#[derive(Deserialize, Clone, Debug)]
+struct Suite {
+    seed: u32,
+    width: u32,
+    height: u32,
+    // Directory holding one `<case>.png` reference per case, relative to the suite file
+    references: PathBuf,
+    tolerance: Tolerance,
+    cases: Vec<Case>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct Case {
+    name: String,
+    // The chunk in the middle of the render, drawn with its eight neighbours around it
+    chunk: (i32, i32),
+    #[serde(default)]
+    isometric: bool,
+    #[serde(default)]
+    world_time: f64,
+}
+
+/// How far a render may drift from its reference: pixels with a channel further than `channel`
+/// from the reference differ, and at most `max_differing` of the pixels may differ
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Tolerance {
+    pub channel: u8,
+    pub max_differing: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Comparison {
+    pub differing: usize,
+    pub total: usize,
+    // Largest difference of a channel over the whole image
+    pub max_delta: u8,
+}
+
+impl Comparison {
+    pub fn passes(&self, tolerance: &Tolerance) -> bool {
+        self.differing as f32 <= tolerance.max_differing * self.total as f32
+    }
+}
+
+fn pixel_delta(a: &Rgba<u8>, b: &Rgba<u8>) -> u8 {
+    a.0.iter()
+        .zip(b.0.iter())
+        .map(|(a, b)| a.abs_diff(*b))
+        .max()
+        .unwrap_or(0)
+}
+
+/// Compare a render with its reference, pixel by pixel. None if their sizes differ.
+pub fn compare(
+    reference: &RgbaImage,
+    actual: &RgbaImage,
+    tolerance: &Tolerance,
+) -> Option<Comparison> {
+    if reference.dimensions() != actual.dimensions() {
+        return None;
+    }
+    let mut comparison = Comparison {
+        differing: 0,
+        total: reference.pixels().len(),
+        max_delta: 0,
+    };
+    for (expected, got) in reference.pixels().zip(actual.pixels()) {
+        let delta = pixel_delta(expected, got);
+        comparison.max_delta = comparison.max_delta.max(delta);
+        if delta > tolerance.channel {
+            comparison.differing += 1;
+        }
+    }
+    Some(comparison)
+}
+
+/// The reference, faded, with the pixels of the render that differ from it in red
+pub fn diff_image(reference: &RgbaImage, actual: &RgbaImage, tolerance: &Tolerance) -> RgbaImage {
+    RgbaImage::from_fn(reference.width(), reference.height(), |x, y| {
+        let expected = reference.get_pixel(x, y);
+        match actual.get_pixel_checked(x, y) {
+            Some(got) if pixel_delta(expected, got) <= tolerance.channel => {
+                let [r, g, b, _] = expected.0;
+                Rgba([r / 3, g / 3, b / 3, 255])
+            }
+            _ => Rgba([255, 0, 0, 255]),
+        }
+    })
+}
+
+/// Run every case of the suite and return the exit code of the process
+pub fn run(suite_path: &str, update: bool) -> i32 {
+    let suite: Suite = match fs::read_to_string(suite_path)
+        .map_err(|e| e.to_string())
+        .and_then(|text| ron::from_str(&text).map_err(|e| e.to_string()))
+    {
+        Ok(suite) => suite,
+        Err(e) => {
+            eprintln!("Can't read the visual test suite {}: {}", suite_path, e);
+            return 1;
+        }
+    };
+    let references = Path::new(suite_path)
+        .parent()
+        .unwrap_or(Path::new("."))
+        .join(&suite.references);
+    println!(
+        "Rendering {} case(s) of seed {} at {}x{}",
+        suite.cases.len(),
+        suite.seed,
+        suite.width,
+        suite.height
+    );
+
+    let world_config = WorldConfig {
+        seed: suite.seed,
+        ..default()
+    };
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins.set(WindowPlugin {
+        // nothing is shown on screen: the camera renders to an image
+        primary_window: None,
+        exit_condition: ExitCondition::DontExit,
+        close_when_requested: false,
+    }))
+    .insert_resource(HeightMap::new(world_config.clone()))
+    .insert_resource(world_config)
+    .init_resource::<WorldState>()
+    .init_resource::<ClientWorldState>()
+    .init_resource::<CurrentSeason>()
+    .add_plugins(ClientWorldRenderPlugin)
+    .insert_resource(VisualTestRun {
+        suite,
+        references,
+        update,
+        target: Handle::default(),
+        phase: Phase::Start(0),
+        failures: Vec::new(),
+    })
+    .add_systems(Startup, create_target)
+    .add_systems(Update, (aim_camera, run_cases).chain());
+    match app.run() {
+        AppExit::Success => 0,
+        AppExit::Error(code) => code.get() as i32,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Phase {
+    // The next case to set up
+    Start(usize),
+    // Waiting for the chunks of a case to be built
+    Rendering { case: usize, settle: u32 },
+    // Waiting for the render of a case to come back from the GPU
+    Reading(usize),
+}
+
+#[derive(Resource)]
+struct VisualTestRun {
+    suite: Suite,
+    references: PathBuf,
+    update: bool,
+    target: Handle<Image>,
+    phase: Phase,
+    failures: Vec<String>,
+}
+
+impl VisualTestRun {
+    // Compare a render with its reference, or make it the reference when updating
+    fn check(&mut self, case: usize, actual: RgbaImage) -> Result<(), String> {
+        let name = &self.suite.cases[case].name;
+        let reference_path = self.references.join(format!("{}.png", name));
+        if self.update {
+            fs::create_dir_all(&self.references).map_err(|e| e.to_string())?;
+            actual.save(&reference_path).map_err(|e| e.to_string())?;
+            println!("{}: reference updated", name);
+            return Ok(());
+        }
+        let reference = image::open(&reference_path)
+            .map_err(|e| format!("no reference at {}: {}", reference_path.display(), e))?
+            .to_rgba8();
+        let tolerance = self.suite.tolerance;
+        let Some(comparison) = compare(&reference, &actual, &tolerance) else {
+            return Err(format!(
+                "rendered {:?}, the reference is {:?}",
+                actual.dimensions(),
+                reference.dimensions()
+            ));
+        };
+        if comparison.passes(&tolerance) {
+            println!("{}: ok", name);
+            return Ok(());
+        }
+        let failures = Path::new(FAILURE_DIR);
+        fs::create_dir_all(failures).map_err(|e| e.to_string())?;
+        let diff = diff_image(&reference, &actual, &tolerance);
+        actual
+            .save(failures.join(format!("{}.actual.png", name)))
+            .and_then(|_| diff.save(failures.join(format!("{}.diff.png", name))))
+            .map_err(|e| e.to_string())?;
+        Err(format!(
+            "{} of {} pixels differ (largest difference {}), see {}",
+            comparison.differing,
+            comparison.total,
+            comparison.max_delta,
+            failures.display()
+        ))
+    }
+}
+
+fn create_target(mut run: ResMut<VisualTestRun>, mut images: ResMut<Assets<Image>>) {
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: run.suite.width,
+            height: run.suite.height,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0, 0, 0, 255],
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    image.texture_descriptor.usage |=
+        TextureUsages::COPY_SRC | TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING;
+    run.target = images.add(image);
+}
+
+// The world camera renders to the offscreen target rather than to a window
+fn aim_camera(run: Res<VisualTestRun>, mut cameras: Query<&mut Camera, Added<WorldCamera>>) {
+    for mut camera in cameras.iter_mut() {
+        camera.target = RenderTarget::Image(run.target.clone());
+    }
+}
+
+fn run_cases(
+    mut commands: Commands,
+    mut run: ResMut<VisualTestRun>,
+    mut exit: EventWriter<AppExit>,
+    mut world_state: ResMut<WorldState>,
+    mut client_world: ResMut<ClientWorldState>,
+    mut projection: ResMut<TileProjection>,
+    world_config: Res<WorldConfig>,
+    render_state: Res<TileRenderState>,
+    queue: Res<ChunkRenderQueue>,
+    chunks: Query<Entity, With<Chunk>>,
+) {
+    match run.phase {
+        Phase::Start(case) if case == run.suite.cases.len() => {
+            if run.failures.is_empty() {
+                println!("All {} case(s) passed", case);
+                exit.send(AppExit::Success);
+            } else {
+                eprintln!("{} case(s) failed:", run.failures.len());
+                for failure in run.failures.iter() {
+                    eprintln!("  {}", failure);
+                }
+                exit.send(AppExit::error());
+            }
+        }
+        Phase::Start(case) => {
+            let setup = run.suite.cases[case].clone();
+            for entity in chunks.iter() {
+                commands.entity(entity).despawn();
+            }
+            world_state.chunks.clear();
+            world_state.world_time = setup.world_time;
+            *projection = if setup.isometric {
+                TileProjection::Isometric
+            } else {
+                TileProjection::TopDown
+            };
+            let center = ChunkCoord {
+                x: setup.chunk.0,
+                y: setup.chunk.1,
+            };
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    let coord = ChunkCoord {
+                        x: center.x + dx,
+                        y: center.y + dy,
+                    };
+                    let chunk = build_chunk(&coord, &world_config, setup.world_time);
+                    let entity = commands.spawn((chunk, coord)).id();
+                    world_state.chunks.insert(coord, entity);
+                }
+            }
+            let middle = (world_config.chunk_size as f32 - 1.0) * 0.5 * WorldGrid::TILE_SIZE;
+            client_world.focus =
+                Some(world_config.grid().chunk_to_world(center) + Vec2::splat(middle));
+            run.phase = Phase::Rendering { case, settle: 0 };
+        }
+        Phase::Rendering { case, settle } => {
+            let coord = ChunkCoord {
+                x: run.suite.cases[case].chunk.0,
+                y: run.suite.cases[case].chunk.1,
+            };
+            if !queue.is_empty() || !render_state.rendered_chunks.contains_key(&coord) {
+                return;
+            }
+            if settle < SETTLE_FRAMES {
+                run.phase = Phase::Rendering {
+                    case,
+                    settle: settle + 1,
+                };
+                return;
+            }
+            commands
+                .spawn(Readback::texture(run.target.clone()))
+                .observe(read_back);
+            run.phase = Phase::Reading(case);
+        }
+        Phase::Reading(_) => {}
+    }
+}
+
+fn read_back(
+    trigger: Trigger<ReadbackComplete>,
+    mut commands: Commands,
+    mut run: ResMut<VisualTestRun>,
+) {
+    // a readback keeps going every frame until its entity is gone
+    commands.entity(trigger.entity()).despawn();
+    let Phase::Reading(case) = run.phase else {
+        return;
+    };
+    let (width, height) = (run.suite.width, run.suite.height);
+    let result = RgbaImage::from_raw(width, height, trigger.event().0.clone())
+        .ok_or_else(|| "the render came back with an unexpected size".to_string())
+        .and_then(|actual| run.check(case, actual));
+    if let Err(e) = result {
+        let failure = format!("{}: {}", run.suite.cases[case].name, e);
+        eprintln!("{}", failure);
+        run.failures.push(failure);
+    }
+    run.phase = Phase::Start(case + 1);
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,54 @@
+//! Comparison of renders with their references
+use super::*;
+
+const TOLERANCE: Tolerance = Tolerance {
+    channel: 4,
+    max_differing: 0.01,
+};
+
+fn filled(width: u32, height: u32, pixel: [u8; 4]) -> RgbaImage {
+    RgbaImage::from_pixel(width, height, Rgba(pixel))
+}
+
+#[test]
+fn identical_renders_pass() {
+    let reference = filled(16, 16, [40, 200, 40, 255]);
+    let comparison = compare(&reference, &reference.clone(), &TOLERANCE).unwrap();
+    assert_eq!(comparison.differing, 0);
+    assert_eq!(comparison.max_delta, 0);
+    assert!(comparison.passes(&TOLERANCE));
+}
+
+#[test]
+fn small_drift_within_the_channel_tolerance_passes() {
+    let reference = filled(16, 16, [40, 200, 40, 255]);
+    let actual = filled(16, 16, [43, 197, 40, 255]);
+    let comparison = compare(&reference, &actual, &TOLERANCE).unwrap();
+    assert_eq!(comparison.differing, 0);
+    assert_eq!(comparison.max_delta, 3);
+    assert!(comparison.passes(&TOLERANCE));
+}
+
+#[test]
+fn a_wrong_tile_fails_and_shows_in_the_diff() {
+    let reference = filled(16, 16, [40, 200, 40, 255]);
+    let mut actual = reference.clone();
+    for y in 0..4 {
+        for x in 0..4 {
+            actual.put_pixel(x, y, Rgba([0, 50, 150, 255]));
+        }
+    }
+    let comparison = compare(&reference, &actual, &TOLERANCE).unwrap();
+    assert_eq!(comparison.differing, 16);
+    assert!(!comparison.passes(&TOLERANCE));
+    let diff = diff_image(&reference, &actual, &TOLERANCE);
+    assert_eq!(diff.get_pixel(0, 0), &Rgba([255, 0, 0, 255]));
+    assert_ne!(diff.get_pixel(8, 8), &Rgba([255, 0, 0, 255]));
+}
+
+#[test]
+fn renders_of_another_size_dont_compare() {
+    let reference = filled(16, 16, [0, 0, 0, 255]);
+    let actual = filled(16, 8, [0, 0, 0, 255]);
+    assert_eq!(compare(&reference, &actual, &TOLERANCE), None);
+}